@@ -129,6 +129,16 @@ fn sparse_filter(b: &mut test::Bencher) {
     bench_query(b, "select trip_id from trips_e8 where (passenger_count = 0) AND (vendor_id = \"DDS\") AND (total_amount < 500) AND (cab_type = \"green\") limit 100;");
 }
 
+#[bench]
+fn integer_range_filter(b: &mut test::Bencher) {
+    bench_query(b, "select count(0) from trips_e8 where (passenger_count = 1) AND (reducible1 < 2000) AND (uniform_u32 > 2000000000);");
+}
+
+#[bench]
+fn negated_filter(b: &mut test::Bencher) {
+    bench_query(b, "select passenger_count, count(0) from trips_e8 where not (total_amount < 1000);");
+}
+
 #[bench]
 fn top_n(b: &mut test::Bencher) {
     bench_query(b, "SELECT passenger_count, uniform_u32, total_amount FROM trips_e8 ORDER BY total_amount DESC LIMIT 100;");
@@ -1,5 +1,7 @@
 #[cfg(feature = "enable_rocksdb")]
 extern crate capnpc;
+#[cfg(feature = "enable_proto")]
+extern crate prost_build;
 
 fn main() {
     #[cfg(feature = "enable_rocksdb")]
@@ -8,4 +10,6 @@ fn main() {
         .file("src/storage_format.capnp")
         .run()
         .unwrap();
+    #[cfg(feature = "enable_proto")]
+        ::prost_build::compile_protos(&["src/query.proto"], &["src"]).unwrap();
 }
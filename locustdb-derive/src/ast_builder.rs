@@ -149,6 +149,8 @@ fn create_buffer(field_ident: &Ident, field_type: &Type) -> Stmt {
         parse_quote!(let #field_ident = self.buffer_provider.buffer_i64(#field_name);)
     } else if *field_type == parse_quote!(BufferRef<u32>) {
         parse_quote!(let #field_ident = self.buffer_provider.buffer_u32(#field_name);)
+    } else if *field_type == parse_quote!(BufferRef<u64>) {
+        parse_quote!(let #field_ident = self.buffer_provider.buffer_u64(#field_name);)
     } else if *field_type == parse_quote!(BufferRef<Nullable<i64>>) {
         parse_quote!(let #field_ident = self.buffer_provider.nullable_buffer_i64(#field_name);)
     } else if *field_type == parse_quote!(BufferRef<MergeOp>) {
@@ -182,6 +184,8 @@ fn convert(expr: Expr, field_type: &Type) -> Expr {
         parse_quote!(#expr.i64().unwrap())
     } else if *field_type == parse_quote!(BufferRef<u32>) {
         parse_quote!(#expr.u32().unwrap())
+    } else if *field_type == parse_quote!(BufferRef<u64>) {
+        parse_quote!(#expr.u64().unwrap())
     } else if *field_type == parse_quote!(BufferRef<Nullable<i64>>) {
         parse_quote!(#expr.nullable_i64().unwrap())
     } else if *field_type == parse_quote!(BufferRef<MergeOp>) {
@@ -202,14 +206,14 @@ fn convert(expr: Expr, field_type: &Type) -> Expr {
 fn hash(field_ident: &Ident, field_type: &Type) -> Stmt {
     if *field_type == parse_quote!(String) {
         parse_quote!(hasher.input_str(#field_ident);)
-    } else if *field_type == parse_quote!(usize) || *field_type == parse_quote!(i64) {
+    } else if *field_type == parse_quote!(usize) || *field_type == parse_quote!(i64) || *field_type == parse_quote!(u64) {
         parse_quote!(hasher.input(&#field_ident.to_ne_bytes());)
     } else if *field_type == parse_quote!(u8) {
         parse_quote!(hasher.input(&[#field_ident]);)
     } else if *field_type == parse_quote!(bool) {
         parse_quote!(hasher.input(&[#field_ident as u8]);)
     } else if *field_type == parse_quote!(Aggregator) {
-        parse_quote!(hasher.input(&[#field_ident as u8]);)
+        parse_quote!(hasher.input_str(&format!("{:?}", #field_ident));)
     } else if *field_type == parse_quote!(TypedBufferRef) {
         parse_quote!(hasher.input(&#field_ident.buffer.i.to_ne_bytes());)
     } else {
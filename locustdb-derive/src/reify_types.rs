@@ -200,11 +200,13 @@ fn types(t: &Ident) -> Option<Vec<Type>> {
     match t.to_string().as_ref() {
         "Str" => Some(vec![Type::Str]),
         "IntegerNoU64" => Some(vec![Type::U8, Type::U16, Type::U32, Type::I64]),
+        "SmallInteger" => Some(vec![Type::U8, Type::U16, Type::U32]),
+        "I64" => Some(vec![Type::I64]),
         "Integer" => Some(vec![Type::U8, Type::U16, Type::U32, Type::U64, Type::I64]),
         "NullableInteger" => Some(vec![Type::NullableU8, Type::NullableU16, Type::NullableU32, Type::NullableI64]),
         "Primitive" => Some(vec![Type::U8, Type::U16, Type::U32, Type::U64, Type::I64, Type::Str, Type::OptStr]),
         "NullablePrimitive" => Some(vec![Type::NullableU8, Type::NullableU16, Type::NullableU32, Type::NullableI64, Type::NullableStr]),
-        "PrimitiveUSize" => Some(vec![Type::U8, Type::U16, Type::U32, Type::U64, Type::I64, Type::Str, Type::USize]),
+        "PrimitiveUSize" => Some(vec![Type::U8, Type::U16, Type::U32, Type::U64, Type::I64, Type::Str, Type::OptStr, Type::USize]),
         "PrimitiveNoU64" => Some(vec![Type::U8, Type::U16, Type::U32, Type::I64, Type::Str]),
         "Const" => Some(vec![Type::ScalarI64, Type::ScalarStr]),
         "ScalarI64" => Some(vec![Type::ScalarI64]),
@@ -283,7 +285,7 @@ impl Type {
             Type::ScalarI64 => parse_quote!( let #variable = #variable.buffer.scalar_i64(); ),
             Type::ScalarStr => parse_quote!( let #variable = #variable.buffer.scalar_str(); ),
             Type::AggregatorCount => parse_quote!( let #variable = PhantomData::<Count>; ),
-            Type::AggregatorSum => parse_quote!( let #variable = PhantomData::<Sum>; ),
+            Type::AggregatorSum => parse_quote!( let #variable = PhantomData::<WrappingSum>; ),
             Type::AggregatorMax => parse_quote!( let #variable = PhantomData::<Max>; ),
             Type::AggregatorMin => parse_quote!( let #variable = PhantomData::<Min>; ),
         }
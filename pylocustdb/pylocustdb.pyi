@@ -0,0 +1,25 @@
+from typing import Dict, List, Optional, Union
+
+import pandas
+
+Value = Union[int, str, None]
+
+
+class LocustDB:
+    def __init__(self, threads: Optional[int] = None) -> None: ...
+
+    def ingest_csv(self, path: str, table: str, partition_size: Optional[int] = None) -> None: ...
+
+    def run_query(self, query: str) -> QueryResult: ...
+
+
+class QueryResult:
+    @property
+    def colnames(self) -> List[str]: ...
+
+    @property
+    def rows(self) -> List[List[Value]]: ...
+
+    def to_dict(self) -> Dict[str, List[Value]]: ...
+
+    def to_pandas(self) -> pandas.DataFrame: ...
@@ -0,0 +1,117 @@
+#![feature(specialization)]
+
+extern crate futures_executor;
+extern crate locustdb as locustdb_rs;
+#[macro_use]
+extern crate pyo3;
+
+use futures_executor::block_on;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use locustdb_rs::{LoadOptions, Options, QueryOutput, Value};
+
+/// In-memory LocustDB instance, queries and ingestion block the calling thread until they complete.
+#[pyclass]
+struct LocustDB {
+    inner: locustdb_rs::LocustDB,
+    token: PyToken,
+}
+
+#[pymethods]
+impl LocustDB {
+    #[new]
+    #[args(threads = "None")]
+    fn __new__(obj: &PyRawObject, threads: Option<usize>) -> PyResult<()> {
+        let mut opts = Options::default();
+        if let Some(threads) = threads {
+            opts.threads = threads;
+        }
+        let inner = locustdb_rs::LocustDB::new(&opts);
+        obj.init(|token| LocustDB { inner, token })
+    }
+
+    /// Loads the `.csv` or `.csv.gz` file at `path` into `table`, the first line of the file must be a header.
+    #[args(partition_size = "None")]
+    fn ingest_csv(&self, path: &str, table: &str, partition_size: Option<usize>) -> PyResult<()> {
+        let mut options = LoadOptions::new(path, table);
+        if let Some(partition_size) = partition_size {
+            options = options.with_partition_size(partition_size);
+        }
+        match block_on(self.inner.load_csv(options)) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => Err(PyErr::new::<exceptions::IOError, _>(err)),
+            Err(_) => Err(PyErr::new::<exceptions::RuntimeError, _>("Ingestion was canceled")),
+        }
+    }
+
+    fn run_query(&self, query: &str) -> PyResult<Py<QueryResult>> {
+        match block_on(self.inner.run_query(query, false, vec![])) {
+            Ok((Ok(output), _)) => Py::new(self.py(), |token| QueryResult { output, token }),
+            Ok((Err(err), _)) => Err(PyErr::new::<exceptions::ValueError, _>(err.to_string())),
+            Err(_) => Err(PyErr::new::<exceptions::RuntimeError, _>("Query was canceled")),
+        }
+    }
+}
+
+/// Result of a query, which can be converted into a `dict` that maps column names to lists of values
+/// or into a `pandas.DataFrame`.
+#[pyclass]
+struct QueryResult {
+    output: QueryOutput,
+    token: PyToken,
+}
+
+#[pymethods]
+impl QueryResult {
+    #[getter]
+    fn colnames(&self) -> PyResult<Vec<String>> {
+        Ok(self.output.colnames.clone())
+    }
+
+    #[getter]
+    fn rows(&self) -> PyResult<PyObject> {
+        let py = self.py();
+        let rows = self.output.rows.iter()
+            .map(|row| PyList::new(py, &row.iter().map(|value| to_py(py, value)).collect::<Vec<_>>()).to_object(py))
+            .collect::<Vec<_>>();
+        Ok(PyList::new(py, &rows).to_object(py))
+    }
+
+    fn to_dict(&self) -> PyResult<PyObject> {
+        let py = self.py();
+        let dict = PyDict::new(py);
+        for (i, colname) in self.output.colnames.iter().enumerate() {
+            let column = self.output.rows.iter()
+                .map(|row| to_py(py, &row[i]))
+                .collect::<Vec<_>>();
+            dict.set_item(colname, PyList::new(py, &column))?;
+        }
+        Ok(dict.to_object(py))
+    }
+
+    /// Converts the result into a `pandas.DataFrame` by way of a `pyarrow.Table`, which requires `pyarrow` and `pandas`
+    /// to be installed.
+    fn to_pandas(&self) -> PyResult<PyObject> {
+        let py = self.py();
+        let dict = self.to_dict()?;
+        let table = py.import("pyarrow")?.get("Table")?.call_method1("from_pydict", (dict,))?;
+        Ok(table.call_method0("to_pandas")?.to_object(py))
+    }
+}
+
+fn to_py(py: Python, value: &Value) -> PyObject {
+    match value {
+        Value::Int(i) => i.to_object(py),
+        Value::Str(s) => s.to_object(py),
+        Value::Null => py.None(),
+    }
+}
+
+#[pymodinit]
+fn pylocustdb(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<LocustDB>()?;
+    m.add_class::<QueryResult>()?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+extern crate futures_executor;
+extern crate locustdb;
+extern crate rustyline;
+
+use std::env;
+use std::path::Path;
+
+use futures_executor::block_on;
+
+use locustdb::{LoadOptions, LocustDB, Value};
+
+const HISTORY_FILE: &str = ".locustdb_cli_history";
+
+/// Loads the `.csv` or `.csv.gz` files passed as arguments into an in-memory database and then runs the queries read
+/// from stdin, one per line. Each file is loaded into a table named after the file, e.g. `trips` for `data/trips.csv`.
+/// `\d` lists all tables and `\d <TABLE>` shows the columns of a table.
+fn main() {
+    let locustdb = LocustDB::memory_only();
+    for path in env::args().skip(1) {
+        let table = table_name(&path);
+        match block_on(locustdb.load_csv(LoadOptions::new(&path, &table))) {
+            Ok(Ok(())) => println!("Loaded {} into table {}.", path, table),
+            Ok(Err(err)) => eprintln!("Failed to load {}: {}", path, err),
+            Err(_) => eprintln!("Loading {} was canceled", path),
+        }
+    }
+
+    let mut rl = rustyline::Editor::<()>::new();
+    rl.load_history(HISTORY_FILE).ok();
+    while let Ok(line) = rl.readline("locustdb> ") {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        rl.add_history_entry(line);
+        if line == "exit" || line == "\\q" {
+            break;
+        }
+        match run_command(&locustdb, line) {
+            Ok(table) => println!("{}", table),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    rl.save_history(HISTORY_FILE).ok();
+}
+
+fn run_command(locustdb: &LocustDB, command: &str) -> Result<String, String> {
+    if command == "\\d" {
+        let mut tables = block_on(locustdb.table_stats()).map_err(|_| "Listing tables was canceled".to_string())?;
+        tables.sort_by(|t1, t2| t1.name.cmp(&t2.name));
+        let rows = tables.into_iter()
+            .map(|table| {
+                let columns = locustdb.schema(&table.name).map_or(0, |schema| schema.columns.len());
+                vec![Value::Str(table.name), Value::Int(table.rows as i64), Value::Int(columns as i64)]
+            })
+            .collect::<Vec<_>>();
+        Ok(locustdb::markdown_table(&["table".to_string(), "rows".to_string(), "columns".to_string()], &rows))
+    } else if command.starts_with("\\d ") {
+        let table = command[3..].trim();
+        let schema = locustdb.schema(table).ok_or_else(|| format!("Table {} does not exist", table))?;
+        let rows = schema.columns.into_iter()
+            .map(|(name, basic_type, encoding_type)| vec![
+                Value::Str(name),
+                Value::Str(format!("{:?}", basic_type)),
+                Value::Str(format!("{:?}", encoding_type)),
+            ])
+            .collect::<Vec<_>>();
+        Ok(locustdb::markdown_table(&["column".to_string(), "type".to_string(), "encoding".to_string()], &rows))
+    } else {
+        match block_on(locustdb.run_query(command, false, vec![])) {
+            Ok((Ok(output), _)) => Ok(output.to_markdown_table()),
+            Ok((Err(err), _)) => Err(err.to_string()),
+            Err(_) => Err("Query was canceled".to_string()),
+        }
+    }
+}
+
+/// Name of the file without directory and extensions.
+fn table_name(path: &str) -> String {
+    Path::new(path).file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("default")
+        .to_string()
+}
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use mem_store::column::Column;
+use mem_store::column_aliases::ColumnAliases;
 use scheduler::inner_locustdb::InnerLocustDB;
 
 
@@ -9,7 +12,20 @@ pub trait DiskStore: Sync + Send + 'static {
     fn load_column(&self, partition: PartitionID, column_name: &str) -> Column;
     fn load_column_range(&self, start: PartitionID, end: PartitionID, column_name: &str, ldb: &InnerLocustDB);
     fn bulk_load(&self, ldb: &InnerLocustDB);
-    fn store_partition(&self, partition: PartitionID, tablename: &str, columns: &[Arc<Column>]);
+    /// Stores `columns` as the partition with the given id, replacing all columns of any partition stored under that id.
+    fn store_partition(&self, partition: PartitionID, tablename: &str, ingested_at: SystemTime, columns: &[Arc<Column>]);
+    /// Stores `columns` as the partition with id `partition` and removes the partitions with ids `old`, which it
+    /// replaces. After a crash either the old partitions or the new one are restored, but never both.
+    fn replace_partitions(&self, old: &[PartitionID], partition: PartitionID, tablename: &str, ingested_at: SystemTime, columns: &[Arc<Column>]);
+    /// Adds `column` to a stored partition, replacing any column of the same name. Does nothing if no partition is
+    /// stored under the given id.
+    fn store_column(&self, partition: PartitionID, column: &Column);
+    /// Removes a stored partition together with all of its columns.
+    fn delete_partition(&self, partition: PartitionID);
+    /// Replaces the column aliases stored for the table `tablename`, see `Table::rename_column`.
+    fn store_column_aliases(&self, tablename: &str, aliases: &ColumnAliases);
+    /// Column aliases of all tables that have renamed columns, keyed by table name.
+    fn load_column_aliases(&self) -> HashMap<String, ColumnAliases>;
 }
 
 pub type PartitionID = u64;
@@ -18,6 +34,8 @@ pub struct PartitionMetadata {
     pub id: PartitionID,
     pub tablename: String,
     pub len: usize,
+    /// Time at which the rows of the partition were ingested, `UNIX_EPOCH` for partitions stored without one.
+    pub ingested_at: SystemTime,
     pub columns: Vec<ColumnMetadata>,
 }
 
@@ -25,3 +43,13 @@ pub struct ColumnMetadata {
     pub name: String,
     pub size_bytes: usize,
 }
+
+/// Nanoseconds since `UNIX_EPOCH`, which is how stores persist ingest timestamps.
+pub fn timestamp_nanos(time: SystemTime) -> u64 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_secs() * 1_000_000_000 + u64::from(since_epoch.subsec_nanos())
+}
+
+pub fn from_timestamp_nanos(nanos: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos)
+}
@@ -0,0 +1,614 @@
+extern crate memmap;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use hex;
+use self::memmap::Mmap;
+
+use disk_store::interface::*;
+use engine::data_types::EncodingType;
+use mem_store::bloom::BloomIndex;
+use mem_store::codec::CodecOp;
+use mem_store::column_aliases::ColumnAliases;
+use mem_store::column::{Column, DataSection, DataSource, MappedSection};
+use scheduler::inner_locustdb::InnerLocustDB;
+
+
+const MAGIC: &[u8; 8] = b"LOCUSTC1";
+const TABLE_FILE: &str = "table";
+const REPLACES_FILE: &str = "replaces";
+const ALIASES_FILE: &str = "aliases";
+const COLUMN_EXTENSION: &str = "col";
+
+/// Stores every column in a separate file that is memory-mapped when the column is loaded.
+/// Loaded columns borrow their data sections directly from the mapping instead of copying them onto the heap.
+///
+/// Layout: `<path>/<partition id>/table` contains the row count, table name and ingest timestamp in nanoseconds since
+/// the epoch of the partition on separate lines,
+/// `<path>/<partition id>/<hex encoded column name>.col` contains a column in the format written by `Column::save`.
+/// `<path>/<partition id>/replaces` lists the ids of partitions that are replaced by the partition but may not have been
+/// removed yet, see `replace_partitions`.
+/// `<path>/aliases` contains the hex encoded table name, new name and stored name of each renamed column on a line.
+pub struct MmapColumnStore {
+    path: PathBuf,
+}
+
+impl MmapColumnStore {
+    pub fn new(path: &str) -> MmapColumnStore {
+        fs::create_dir_all(path).unwrap();
+        let store = MmapColumnStore { path: PathBuf::from(path) };
+        store.finish_replacements();
+        store
+    }
+
+    /// Removes partitions that remain on disk because a crash interrupted `replace_partitions`.
+    fn finish_replacements(&self) {
+        for id in self.partition_ids() {
+            let replaces = self.partition_dir(id).join(REPLACES_FILE);
+            if !replaces.exists() {
+                continue;
+            }
+            let mut old = String::new();
+            File::open(&replaces).unwrap().read_to_string(&mut old).unwrap();
+            for old_id in old.lines() {
+                self.delete_partition(old_id.parse::<PartitionID>().unwrap());
+            }
+            fs::remove_file(&replaces).unwrap();
+        }
+    }
+
+    /// Stores `columns` as the partition with the given id together with the ids of the partitions it replaces.
+    fn write_partition(&self,
+                       partition: PartitionID,
+                       tablename: &str,
+                       ingested_at: SystemTime,
+                       columns: &[Arc<Column>],
+                       replaces: &[PartitionID]) {
+        // Partition is written to a temporary directory first so that it is either stored completely or not at all
+        let tmp_dir = self.path.join(format!("{}.tmp", partition));
+        fs::create_dir_all(&tmp_dir).unwrap();
+        for column in columns {
+            column.save(&tmp_dir.join(format!("{}.{}", hex::encode(column.name()), COLUMN_EXTENSION))).unwrap();
+        }
+        File::create(tmp_dir.join(TABLE_FILE)).unwrap()
+            .write_all(format!("{}\n{}\n{}", columns[0].len(), tablename, timestamp_nanos(ingested_at)).as_bytes()).unwrap();
+        if !replaces.is_empty() {
+            let ids = replaces.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+            File::create(tmp_dir.join(REPLACES_FILE)).unwrap()
+                .write_all(ids.join("\n").as_bytes()).unwrap();
+        }
+        let dir = self.partition_dir(partition);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::rename(&tmp_dir, dir).unwrap();
+    }
+
+    fn partition_ids(&self) -> Vec<PartitionID> {
+        let mut ids = fs::read_dir(&self.path).unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().to_str().and_then(|name| name.parse::<PartitionID>().ok()))
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    fn partition_dir(&self, partition: PartitionID) -> PathBuf {
+        self.path.join(partition.to_string())
+    }
+
+    fn column_path(&self, partition: PartitionID, column_name: &str) -> PathBuf {
+        self.partition_dir(partition).join(format!("{}.{}", hex::encode(column_name), COLUMN_EXTENSION))
+    }
+
+    fn column_names(&self, partition: PartitionID) -> Vec<String> {
+        let mut names = fs::read_dir(self.partition_dir(partition)).unwrap()
+            .filter_map(|entry| {
+                let path = entry.unwrap().path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some(COLUMN_EXTENSION) {
+                    return None;
+                }
+                let name = hex::decode(path.file_stem()?.to_str()?).ok()?;
+                String::from_utf8(name).ok()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+impl DiskStore for MmapColumnStore {
+    fn load_metadata(&self) -> Vec<PartitionMetadata> {
+        self.partition_ids().into_iter().map(|id| {
+            let mut table = String::new();
+            File::open(self.partition_dir(id).join(TABLE_FILE)).unwrap()
+                .read_to_string(&mut table).unwrap();
+            let mut lines = table.lines();
+            let len = lines.next().unwrap().parse::<usize>().unwrap();
+            let tablename = lines.next().unwrap().to_string();
+            // Partitions stored before ingest timestamps were persisted don't have one
+            let ingested_at = from_timestamp_nanos(lines.next().map_or(0, |nanos| nanos.parse::<u64>().unwrap()));
+            let columns = self.column_names(id).into_iter()
+                .map(|name| {
+                    let size_bytes = fs::metadata(self.column_path(id, &name)).unwrap().len() as usize;
+                    ColumnMetadata { name, size_bytes }
+                })
+                .collect();
+            PartitionMetadata { id, tablename, len, ingested_at, columns }
+        }).collect()
+    }
+
+    fn load_column(&self, partition: PartitionID, column_name: &str) -> Column {
+        Column::load(&self.column_path(partition, column_name)).unwrap()
+    }
+
+    fn load_column_range(&self, start: PartitionID, end: PartitionID, column_name: &str, ldb: &InnerLocustDB) {
+        for id in self.partition_ids() {
+            if id < start || id > end { continue; }
+            let path = self.column_path(id, column_name);
+            if path.exists() {
+                ldb.restore(id, Column::load(&path).unwrap());
+            }
+        }
+    }
+
+    fn bulk_load(&self, ldb: &InnerLocustDB) {
+        for id in self.partition_ids() {
+            for name in self.column_names(id) {
+                ldb.restore(id, self.load_column(id, &name));
+            }
+        }
+    }
+
+    fn store_partition(&self, partition: PartitionID, tablename: &str, ingested_at: SystemTime, columns: &[Arc<Column>]) {
+        self.write_partition(partition, tablename, ingested_at, columns, &[]);
+    }
+
+    fn replace_partitions(&self, old: &[PartitionID], partition: PartitionID, tablename: &str, ingested_at: SystemTime, columns: &[Arc<Column>]) {
+        // Once the new partition is in place, the old partitions are removed on startup if a crash interrupts this
+        self.write_partition(partition, tablename, ingested_at, columns, old);
+        if !old.is_empty() {
+            for &id in old {
+                self.delete_partition(id);
+            }
+            fs::remove_file(self.partition_dir(partition).join(REPLACES_FILE)).unwrap();
+        }
+    }
+
+    fn store_column(&self, partition: PartitionID, column: &Column) {
+        if !self.partition_dir(partition).exists() {
+            return;
+        }
+        column.save(&self.column_path(partition, column.name())).unwrap();
+    }
+
+    fn delete_partition(&self, partition: PartitionID) {
+        let dir = self.partition_dir(partition);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    fn store_column_aliases(&self, tablename: &str, aliases: &ColumnAliases) {
+        let mut all = self.load_column_aliases();
+        all.insert(tablename.to_string(), aliases.clone());
+        let mut lines = Vec::new();
+        for (table, aliases) in &all {
+            for (new, old) in aliases.iter() {
+                lines.push(format!("{} {} {}", hex::encode(table), hex::encode(new), hex::encode(old)));
+            }
+        }
+        lines.sort();
+        let tmp_path = self.path.join(format!("{}.tmp", ALIASES_FILE));
+        File::create(&tmp_path).unwrap().write_all(lines.join("\n").as_bytes()).unwrap();
+        fs::rename(&tmp_path, self.path.join(ALIASES_FILE)).unwrap();
+    }
+
+    fn load_column_aliases(&self) -> HashMap<String, ColumnAliases> {
+        let mut all = HashMap::<String, ColumnAliases>::new();
+        let path = self.path.join(ALIASES_FILE);
+        if !path.exists() {
+            return all;
+        }
+        let mut lines = String::new();
+        File::open(&path).unwrap().read_to_string(&mut lines).unwrap();
+        for line in lines.lines() {
+            let names = line.split(' ')
+                .map(|name| String::from_utf8(hex::decode(name).unwrap()).unwrap())
+                .collect::<Vec<_>>();
+            all.entry(names[0].clone()).or_insert_with(ColumnAliases::default).insert(&names[1], &names[2]);
+        }
+        all
+    }
+}
+
+impl Column {
+    /// Writes the column to `path`, replacing any existing file only once the column has been written completely.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let tmp_path = path.with_extension("tmp");
+        File::create(&tmp_path)
+            .and_then(|mut file| file.write_all(&serialize_column(self)))
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|err| format!("Failed to write {:?}: {}", path, err))
+    }
+
+    /// Memory-maps a column file written by `Column::save`.
+    /// The data sections of the returned column reference the mapping, which stays alive as long as the column.
+    pub fn load(path: &Path) -> Result<Column, String> {
+        let file = File::open(path).map_err(|err| format!("Failed to open {:?}: {}", path, err))?;
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|err| format!("Failed to map {:?}: {}", path, err))?;
+        let buffer: Arc<AsRef<[u8]> + Send + Sync> = Arc::new(mmap);
+        let header = read_header((*buffer).as_ref())
+            .map_err(|err| format!("Failed to read column header of {:?}: {}", path, err))?;
+
+        let mut data = Vec::with_capacity(header.sections.len());
+        for (t, len, offset) in header.sections {
+            data.push(match t {
+                EncodingType::Null => DataSection::Null(len),
+                _ => DataSection::Mapped(MappedSection::new(buffer.clone(), offset, len, t)?),
+            });
+        }
+        let mut column = Column::new(&header.name, header.len, header.range, header.codec, data);
+        if let Some(bloom) = header.bloom {
+            column.set_bloom_index(bloom);
+        }
+        Ok(column)
+    }
+}
+
+struct Header {
+    name: String,
+    len: usize,
+    range: Option<(i64, i64)>,
+    codec: Vec<CodecOp>,
+    /// Type, number of elements and byte offset of each data section.
+    sections: Vec<(EncodingType, usize, usize)>,
+    bloom: Option<BloomIndex>,
+}
+
+/// Layout of a column file, all integers in the header are little endian:
+/// - magic bytes `LOCUSTC1` followed by the length of the header as u64
+/// - name (u32 length followed by UTF-8 bytes) and number of rows as u64
+/// - range as u8 flag, followed by min and max as i64 if the flag is 1
+/// - number of codec ops as u32 followed by the ops, each a u8 tag (see `write_codec_op`) and its parameters, e.g. the
+///   encoding type and i64 offset of `CodecOp::Add`
+/// - bloom index as u8 flag, followed by the number of hashes as u32 and the bit vector as u32 length and u64 words
+/// - number of data sections as u32 and for each section its encoding type tag as u8, number of elements and byte
+///   offset in the file as u64
+/// - the data sections in native byte order, each starting at an offset that is a multiple of 8
+///
+/// Tags of existing codec ops and encoding types must not change, new ones get the next unused tag.
+fn serialize_column(col: &Column) -> Vec<u8> {
+    let mut header = Vec::new();
+    write_str(&mut header, col.name());
+    header.write_u64::<LittleEndian>(col.len() as u64).unwrap();
+    match col.range() {
+        Some((min, max)) => {
+            header.write_u8(1).unwrap();
+            header.write_i64::<LittleEndian>(min).unwrap();
+            header.write_i64::<LittleEndian>(max).unwrap();
+        }
+        None => header.write_u8(0).unwrap(),
+    }
+    header.write_u32::<LittleEndian>(col.codec().ops().len() as u32).unwrap();
+    for &op in col.codec().ops() {
+        write_codec_op(&mut header, op);
+    }
+    match col.bloom_index() {
+        Some(bloom) => {
+            header.write_u8(1).unwrap();
+            header.write_u32::<LittleEndian>(bloom.hashes()).unwrap();
+            header.write_u32::<LittleEndian>(bloom.bits().len() as u32).unwrap();
+            for &word in bloom.bits() {
+                header.write_u64::<LittleEndian>(word).unwrap();
+            }
+        }
+        None => header.write_u8(0).unwrap(),
+    }
+
+    // Sections are stored in native byte order, each aligned to 8 bytes so they can be used in place
+    let sections = col.data();
+    header.write_u32::<LittleEndian>(sections.len() as u32).unwrap();
+    let section_table_len = sections.len() * 17;
+    let mut offset = align(MAGIC.len() + 8 + header.len() + section_table_len);
+    let mut offsets = Vec::with_capacity(sections.len());
+    for section in sections {
+        offsets.push(offset);
+        offset = align(offset + section_bytes(section).len());
+    }
+    for (section, &offset) in sections.iter().zip(offsets.iter()) {
+        header.write_u8(encoding_type_to_tag(section.encoding_type())).unwrap();
+        header.write_u64::<LittleEndian>(section.len() as u64).unwrap();
+        header.write_u64::<LittleEndian>(offset as u64).unwrap();
+    }
+
+    let mut buffer = Vec::with_capacity(offset);
+    buffer.extend_from_slice(MAGIC);
+    buffer.write_u64::<LittleEndian>(header.len() as u64).unwrap();
+    buffer.extend(header);
+    for (section, &offset) in sections.iter().zip(offsets.iter()) {
+        buffer.resize(offset, 0);
+        buffer.extend_from_slice(section_bytes(section));
+    }
+    buffer
+}
+
+fn read_header(mut buffer: &[u8]) -> io::Result<Header> {
+    let mut magic = [0; 8];
+    buffer.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("wrong magic bytes"));
+    }
+    buffer.read_u64::<LittleEndian>()?;
+    let name = read_str(&mut buffer)?;
+    let len = buffer.read_u64::<LittleEndian>()? as usize;
+    let range = if buffer.read_u8()? == 1 {
+        Some((buffer.read_i64::<LittleEndian>()?, buffer.read_i64::<LittleEndian>()?))
+    } else {
+        None
+    };
+    let op_count = buffer.read_u32::<LittleEndian>()?;
+    let mut codec = Vec::with_capacity(op_count as usize);
+    for _ in 0..op_count {
+        codec.push(read_codec_op(&mut buffer)?);
+    }
+    let bloom = if buffer.read_u8()? == 1 {
+        let hashes = buffer.read_u32::<LittleEndian>()?;
+        let words = buffer.read_u32::<LittleEndian>()?;
+        let mut bits = Vec::with_capacity(words as usize);
+        for _ in 0..words {
+            bits.push(buffer.read_u64::<LittleEndian>()?);
+        }
+        Some(BloomIndex::from_raw_parts(bits, hashes))
+    } else {
+        None
+    };
+    let section_count = buffer.read_u32::<LittleEndian>()?;
+    let mut sections = Vec::with_capacity(section_count as usize);
+    for _ in 0..section_count {
+        let t = tag_to_encoding_type(buffer.read_u8()?)?;
+        let len = buffer.read_u64::<LittleEndian>()? as usize;
+        let offset = buffer.read_u64::<LittleEndian>()? as usize;
+        sections.push((t, len, offset));
+    }
+    Ok(Header { name, len, range, codec, sections, bloom })
+}
+
+fn write_codec_op(buffer: &mut Vec<u8>, op: CodecOp) {
+    match op {
+        CodecOp::Nullable => buffer.write_u8(0).unwrap(),
+        CodecOp::Add(t, amount) => {
+            buffer.write_u8(1).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+            buffer.write_i64::<LittleEndian>(amount).unwrap();
+        }
+        CodecOp::Delta(t) => {
+            buffer.write_u8(2).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+        }
+        CodecOp::ToI64(t) => {
+            buffer.write_u8(3).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+        }
+        CodecOp::PushDataSection(section) => {
+            buffer.write_u8(4).unwrap();
+            buffer.write_u64::<LittleEndian>(section as u64).unwrap();
+        }
+        CodecOp::DictLookup(t) => {
+            buffer.write_u8(5).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+        }
+        CodecOp::LZ4(t, decoded_length) => {
+            buffer.write_u8(6).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+            buffer.write_u64::<LittleEndian>(decoded_length as u64).unwrap();
+        }
+        CodecOp::UnpackBits(len) => {
+            buffer.write_u8(7).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+        }
+        CodecOp::UnpackStrings => buffer.write_u8(8).unwrap(),
+        CodecOp::UnhexpackStrings(uppercase, total_bytes) => {
+            buffer.write_u8(9).unwrap();
+            buffer.write_u8(uppercase as u8).unwrap();
+            buffer.write_u64::<LittleEndian>(total_bytes as u64).unwrap();
+        }
+        CodecOp::Zstd(t, decoded_length) => {
+            buffer.write_u8(10).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+            buffer.write_u64::<LittleEndian>(decoded_length as u64).unwrap();
+        }
+        CodecOp::FrameOfReference(t, frame_size) => {
+            buffer.write_u8(11).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+            buffer.write_u64::<LittleEndian>(frame_size as u64).unwrap();
+        }
+        CodecOp::UnpackInts(bits, len) => {
+            buffer.write_u8(12).unwrap();
+            buffer.write_u8(bits as u8).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+        }
+        CodecOp::RunLength(t, len) => {
+            buffer.write_u8(13).unwrap();
+            buffer.write_u8(encoding_type_to_tag(t)).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+        }
+        CodecOp::Unknown => panic!("Trying to serialize CodecOp::Unkown"),
+    }
+}
+
+fn read_codec_op(buffer: &mut &[u8]) -> io::Result<CodecOp> {
+    Ok(match buffer.read_u8()? {
+        0 => CodecOp::Nullable,
+        1 => CodecOp::Add(tag_to_encoding_type(buffer.read_u8()?)?, buffer.read_i64::<LittleEndian>()?),
+        2 => CodecOp::Delta(tag_to_encoding_type(buffer.read_u8()?)?),
+        3 => CodecOp::ToI64(tag_to_encoding_type(buffer.read_u8()?)?),
+        4 => CodecOp::PushDataSection(buffer.read_u64::<LittleEndian>()? as usize),
+        5 => CodecOp::DictLookup(tag_to_encoding_type(buffer.read_u8()?)?),
+        6 => CodecOp::LZ4(tag_to_encoding_type(buffer.read_u8()?)?, buffer.read_u64::<LittleEndian>()? as usize),
+        7 => CodecOp::UnpackBits(buffer.read_u64::<LittleEndian>()? as usize),
+        8 => CodecOp::UnpackStrings,
+        9 => CodecOp::UnhexpackStrings(buffer.read_u8()? == 1, buffer.read_u64::<LittleEndian>()? as usize),
+        10 => CodecOp::Zstd(tag_to_encoding_type(buffer.read_u8()?)?, buffer.read_u64::<LittleEndian>()? as usize),
+        11 => CodecOp::FrameOfReference(tag_to_encoding_type(buffer.read_u8()?)?, buffer.read_u64::<LittleEndian>()? as usize),
+        12 => CodecOp::UnpackInts(buffer.read_u8()? as usize, buffer.read_u64::<LittleEndian>()? as usize),
+        13 => CodecOp::RunLength(tag_to_encoding_type(buffer.read_u8()?)?, buffer.read_u64::<LittleEndian>()? as usize),
+        tag => return Err(invalid_data(&format!("unknown codec op {}", tag))),
+    })
+}
+
+fn encoding_type_to_tag(t: EncodingType) -> u8 {
+    match t {
+        EncodingType::U8 => 0,
+        EncodingType::U16 => 1,
+        EncodingType::U32 => 2,
+        EncodingType::U64 => 3,
+        EncodingType::I64 => 4,
+        EncodingType::Null => 5,
+        _ => panic!("Trying to encode unsupported type {:?}", t)
+    }
+}
+
+fn tag_to_encoding_type(tag: u8) -> io::Result<EncodingType> {
+    Ok(match tag {
+        0 => EncodingType::U8,
+        1 => EncodingType::U16,
+        2 => EncodingType::U32,
+        3 => EncodingType::U64,
+        4 => EncodingType::I64,
+        5 => EncodingType::Null,
+        _ => return Err(invalid_data(&format!("unknown encoding type {}", tag))),
+    })
+}
+
+fn section_bytes(section: &DataSection) -> &[u8] {
+    fn bytes<T>(values: &[T]) -> &[u8] {
+        unsafe { ::std::slice::from_raw_parts(values.as_ptr() as *const u8, values.len() * ::std::mem::size_of::<T>()) }
+    }
+    match section {
+        DataSection::U8(x) => x,
+        DataSection::U16(x) => bytes(x),
+        DataSection::U32(x) => bytes(x),
+        DataSection::U64(x) => bytes(x),
+        DataSection::I64(x) => bytes(x),
+        DataSection::Null(_) => &[],
+        DataSection::Mapped(x) => x.bytes(),
+    }
+}
+
+fn write_str(buffer: &mut Vec<u8>, s: &str) {
+    buffer.write_u32::<LittleEndian>(s.len() as u32).unwrap();
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+fn read_str(buffer: &mut &[u8]) -> io::Result<String> {
+    let len = buffer.read_u32::<LittleEndian>()? as usize;
+    let mut bytes = vec![0; len];
+    buffer.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid_data("column name is not valid UTF-8"))
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 7) / 8 * 8
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ingest::raw_val::RawVal;
+    use mem_store::column_builder::build_column;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new("mmap_column_store").unwrap();
+        let store = MmapColumnStore::new(dir.path().to_str().unwrap());
+        let ints = (0..100).map(|i| RawVal::Int(i * 1000 - 7)).collect::<Vec<_>>();
+        let strs = (0..100).map(|i| RawVal::Str(format!("s{}", i % 7))).collect::<Vec<_>>();
+        let columns = vec![build_column("ints", ints.clone()), build_column("strs", strs.clone())];
+        let ingested_at = UNIX_EPOCH + Duration::new(1_500_000_000, 123);
+        store.store_partition(3, "default", ingested_at, &columns);
+
+        let metadata = store.load_metadata();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].id, 3);
+        assert_eq!(metadata[0].tablename, "default");
+        assert_eq!(metadata[0].len, 100);
+        assert_eq!(metadata[0].ingested_at, ingested_at);
+        assert_eq!(metadata[0].columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["ints", "strs"]);
+
+        let loaded = store.load_column(3, "ints");
+        match loaded.data()[0] {
+            DataSection::Mapped(_) => {}
+            ref section => panic!("Expected mapped section, got {:?}", section),
+        }
+        assert_eq!(loaded.range(), columns[0].range());
+        assert_eq!(loaded.decode().unwrap(), ints);
+        assert_eq!(store.load_column(3, "strs").decode().unwrap(), strs);
+    }
+
+    #[test]
+    fn test_store_column_delete_partition() {
+        let dir = TempDir::new("mmap_column_store").unwrap();
+        let store = MmapColumnStore::new(dir.path().to_str().unwrap());
+        let ints = (0..10).map(RawVal::Int).collect::<Vec<_>>();
+        store.store_partition(1, "default", UNIX_EPOCH, &[build_column("ints", ints.clone())]);
+        let strs = (0..10).map(|i| RawVal::Str(format!("s{}", i))).collect::<Vec<_>>();
+        store.store_column(1, &build_column("strs", strs.clone()));
+        store.store_column(2, &build_column("strs", strs.clone()));
+
+        let metadata = store.load_metadata();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["ints", "strs"]);
+        assert_eq!(store.load_column(1, "strs").decode().unwrap(), strs);
+
+        store.delete_partition(1);
+        store.delete_partition(2);
+        assert!(store.load_metadata().is_empty());
+    }
+
+    #[test]
+    fn test_replace_partitions() {
+        let dir = TempDir::new("mmap_column_store").unwrap();
+        let path = dir.path().to_str().unwrap();
+        let store = MmapColumnStore::new(path);
+        let column = |len: i64| build_column("ints", (0..len).map(RawVal::Int).collect());
+        let partitions = |store: &MmapColumnStore| store.load_metadata().iter().map(|md| (md.id, md.len)).collect::<Vec<_>>();
+        store.store_partition(1, "default", UNIX_EPOCH, &[column(3)]);
+        store.store_partition(2, "default", UNIX_EPOCH, &[column(4)]);
+        store.store_partition(3, "default", UNIX_EPOCH, &[column(5)]);
+        store.replace_partitions(&[1, 2], 4, "default", UNIX_EPOCH, &[column(7)]);
+        assert_eq!(partitions(&store), vec![(3, 5), (4, 7)]);
+
+        // Crash after the new partition has been written but before the partitions it replaces have been removed
+        store.write_partition(5, "default", UNIX_EPOCH, &[column(12)], &[3, 4]);
+        assert_eq!(partitions(&MmapColumnStore::new(path)), vec![(5, 12)]);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let dir = TempDir::new("mmap_column").unwrap();
+        let nullable = (0..100).map(|i| if i % 3 == 0 { RawVal::Null } else { RawVal::Int(100_000 + i) }).collect::<Vec<_>>();
+        let packed = (0..100).map(|i| RawVal::Str(format!("value{}", i))).collect::<Vec<_>>();
+        for (i, values) in [nullable, packed].iter().enumerate() {
+            let column = build_column("c", values.clone());
+            let path = dir.path().join(format!("{}.col", i));
+            column.save(&path).unwrap();
+            let loaded = Column::load(&path).unwrap();
+            assert_eq!(loaded.codec().ops(), column.codec().ops());
+            assert_eq!(loaded.range(), column.range());
+            assert_eq!(loaded.decode().unwrap(), *values);
+        }
+        assert!(Column::load(&dir.path().join("missing.col")).is_err());
+    }
+}
@@ -1,5 +1,8 @@
 pub mod interface;
 pub mod noop_storage;
 
+#[cfg(feature = "enable_mmap")]
+pub mod mmap;
+
 #[cfg(feature = "enable_rocksdb")]
 pub mod rocksdb;
\ No newline at end of file
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use mem_store::column::Column;
+use mem_store::column_aliases::ColumnAliases;
 use disk_store::interface::*;
 use scheduler::inner_locustdb::InnerLocustDB;
 
@@ -13,5 +16,10 @@ impl DiskStore for NoopStorage {
     }
     fn load_column_range(&self, _: PartitionID, _: PartitionID, _: &str, _: &InnerLocustDB) {}
     fn bulk_load(&self, _: &InnerLocustDB) {}
-    fn store_partition(&self, _: PartitionID, _: &str, _: &[Arc<Column>]) {}
+    fn store_partition(&self, _: PartitionID, _: &str, _: SystemTime, _: &[Arc<Column>]) {}
+    fn replace_partitions(&self, _: &[PartitionID], _: PartitionID, _: &str, _: SystemTime, _: &[Arc<Column>]) {}
+    fn store_column(&self, _: PartitionID, _: &Column) {}
+    fn delete_partition(&self, _: PartitionID) {}
+    fn store_column_aliases(&self, _: &str, _: &ColumnAliases) {}
+    fn load_column_aliases(&self) -> HashMap<String, ColumnAliases> { HashMap::new() }
 }
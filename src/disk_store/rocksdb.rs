@@ -1,16 +1,21 @@
 extern crate rocksdb;
 extern crate capnp;
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::str;
+use std::time::SystemTime;
 
 use byteorder::{ByteOrder, BigEndian};
 use capnp::{serialize, Word, message};
+use hex;
 use self::rocksdb::*;
 use storage_format_capnp::*;
 
 use disk_store::interface::*;
+use mem_store::bloom::BloomIndex;
 use mem_store::column::{Column, DataSection, DataSource};
+use mem_store::column_aliases::ColumnAliases;
 use scheduler::inner_locustdb::InnerLocustDB;
 use mem_store::codec::CodecOp;
 use engine::data_types::EncodingType as Type;
@@ -30,7 +35,7 @@ impl RocksDB {
         options.create_missing_column_families(true);
 
         let mut partitions_options = Options::default();
-        if cfg!(feature = "enable_lz4") {
+        if cfg!(feature = "enable_lz4") || cfg!(feature = "enable_zstd") {
             partitions_options.set_compression_type(DBCompressionType::None);
         }
         partitions_options.set_write_buffer_size(256 * 1024 * 1024);
@@ -45,6 +50,7 @@ impl RocksDB {
         let db = DB::open_cf_descriptors(&options, path, vec![
             ColumnFamilyDescriptor::new("metadata", Options::default()),
             ColumnFamilyDescriptor::new("partitions", partitions_options),
+            ColumnFamilyDescriptor::new("aliases", Options::default()),
         ]).unwrap();
         RocksDB { db }
     }
@@ -56,6 +62,47 @@ impl RocksDB {
     fn partitions(&self) -> ColumnFamily {
         self.db.cf_handle("partitions").unwrap()
     }
+
+    /// Column aliases keyed by table name, each alias is stored as the hex encoded new and old name on a line.
+    fn aliases(&self) -> ColumnFamily {
+        self.db.cf_handle("aliases").unwrap()
+    }
+
+    /// Adds the writes that store `columns` as the partition with the given id to `tx`.
+    fn put_partition(&self, tx: &mut WriteBatch, partition: PartitionID, tablename: &str, ingested_at: SystemTime, columns: &[Arc<Column>]) {
+        let key = partition_key(partition);
+        // Columns of a partition previously stored under the same id that are not part of the new partition
+        if let Some(md) = self.db.get_cf(self.metadata(), &key).unwrap() {
+            for column in deserialize_meta_data(&md, partition).columns {
+                if !columns.iter().any(|c| c.name() == column.name) {
+                    tx.delete_cf(self.partitions(), &column_key(partition, &column.name)).unwrap();
+                }
+            }
+        }
+        let column_metadata = columns.iter()
+            .map(|column| ColumnMetadata { name: column.name().to_string(), size_bytes: column.heap_size_of_children() })
+            .collect::<Vec<_>>();
+        let md = serialize_meta_data(tablename, columns[0].len(), ingested_at, &column_metadata);
+        tx.put_cf(self.metadata(), &key, &md).unwrap();
+        for column in columns {
+            let key = column_key(partition, column.name());
+            let data = serialize_column(column.as_ref());
+            tx.put_cf(self.partitions(), &key, &data).unwrap();
+        }
+    }
+
+    /// Adds the writes that remove the partition with the given id and all of its columns to `tx`.
+    fn remove_partition(&self, tx: &mut WriteBatch, partition: PartitionID) {
+        let key = partition_key(partition);
+        let md = match self.db.get_cf(self.metadata(), &key).unwrap() {
+            Some(md) => deserialize_meta_data(&md, partition),
+            None => return,
+        };
+        tx.delete_cf(self.metadata(), &key).unwrap();
+        for column in md.columns {
+            tx.delete_cf(self.partitions(), &column_key(partition, &column.name)).unwrap();
+        }
+    }
 }
 
 impl DiskStore for RocksDB {
@@ -113,20 +160,69 @@ impl DiskStore for RocksDB {
         }
     }
 
-    fn store_partition(&self, partition: PartitionID, tablename: &str, columns: &[Arc<Column>]) {
+    fn store_partition(&self, partition: PartitionID, tablename: &str, ingested_at: SystemTime, columns: &[Arc<Column>]) {
         let mut tx = WriteBatch::default();
-        let mut key = [0; 8];
-        BigEndian::write_u64(&mut key, partition as u64);
-        let md = serialize_meta_data(tablename, columns);
-        tx.put_cf(self.metadata(), &key, &md).unwrap();
-        for column in columns {
-            let key = column_key(partition, column.name());
-            let data = serialize_column(column.as_ref());
-            tx.put_cf(self.partitions(), &key, &data).unwrap();
+        self.put_partition(&mut tx, partition, tablename, ingested_at, columns);
+        self.db.write(tx).unwrap();
+    }
+
+    fn replace_partitions(&self, old: &[PartitionID], partition: PartitionID, tablename: &str, ingested_at: SystemTime, columns: &[Arc<Column>]) {
+        let mut tx = WriteBatch::default();
+        for &id in old {
+            self.remove_partition(&mut tx, id);
         }
+        self.put_partition(&mut tx, partition, tablename, ingested_at, columns);
+        self.db.write(tx).unwrap();
+    }
 
+    fn store_column(&self, partition: PartitionID, column: &Column) {
+        let key = partition_key(partition);
+        let mut md = match self.db.get_cf(self.metadata(), &key).unwrap() {
+            Some(md) => deserialize_meta_data(&md, partition),
+            None => return,
+        };
+        md.columns.retain(|c| c.name != column.name());
+        md.columns.push(ColumnMetadata { name: column.name().to_string(), size_bytes: column.heap_size_of_children() });
+        let mut tx = WriteBatch::default();
+        tx.put_cf(self.metadata(), &key, &serialize_meta_data(&md.tablename, md.len, md.ingested_at, &md.columns)).unwrap();
+        tx.put_cf(self.partitions(), &column_key(partition, column.name()), &serialize_column(column)).unwrap();
         self.db.write(tx).unwrap();
     }
+
+    fn delete_partition(&self, partition: PartitionID) {
+        let mut tx = WriteBatch::default();
+        self.remove_partition(&mut tx, partition);
+        self.db.write(tx).unwrap();
+    }
+
+    fn store_column_aliases(&self, tablename: &str, aliases: &ColumnAliases) {
+        let lines = aliases.iter()
+            .map(|(new, old)| format!("{} {}", hex::encode(new), hex::encode(old)))
+            .collect::<Vec<_>>();
+        self.db.put_cf(self.aliases(), tablename.as_bytes(), lines.join("\n").as_bytes()).unwrap();
+    }
+
+    fn load_column_aliases(&self) -> HashMap<String, ColumnAliases> {
+        let mut all = HashMap::new();
+        let iter = self.db.iterator_cf(self.aliases(), IteratorMode::Start).unwrap();
+        for (key, value) in iter {
+            let mut aliases = ColumnAliases::default();
+            for line in str::from_utf8(&value).unwrap().lines() {
+                let names = line.split(' ')
+                    .map(|name| String::from_utf8(hex::decode(name).unwrap()).unwrap())
+                    .collect::<Vec<_>>();
+                aliases.insert(&names[0], &names[1]);
+            }
+            all.insert(str::from_utf8(&key).unwrap().to_string(), aliases);
+        }
+        all
+    }
+}
+
+fn partition_key(id: PartitionID) -> [u8; 8] {
+    let mut key = [0; 8];
+    BigEndian::write_u64(&mut key, id as u64);
+    key
 }
 
 fn column_key(id: PartitionID, column_name: &str) -> Vec<u8> {
@@ -168,6 +264,14 @@ fn deserialize_column(data: &[u8]) -> Column {
                 CodecOp::Add(deserialize_type(add.get_type().unwrap()), add.get_amount())
             }
             Delta(delta) => CodecOp::Delta(deserialize_type(delta.unwrap())),
+            FrameOfReference(fr) => {
+                let fr = fr.unwrap();
+                CodecOp::FrameOfReference(deserialize_type(fr.get_type().unwrap()), fr.get_frame_size() as usize)
+            }
+            RunLength(rl) => {
+                let rl = rl.unwrap();
+                CodecOp::RunLength(deserialize_type(rl.get_type().unwrap()), rl.get_len() as usize)
+            }
             ToI64(toi64) => CodecOp::ToI64(deserialize_type(toi64.unwrap())),
             PushDataSection(section) => CodecOp::PushDataSection(section as usize),
             DictLookup(t) => CodecOp::DictLookup(deserialize_type(t.unwrap())),
@@ -175,6 +279,15 @@ fn deserialize_column(data: &[u8]) -> Column {
                 let lz4 = lz4.unwrap();
                 CodecOp::LZ4(deserialize_type(lz4.get_type().unwrap()), lz4.get_len_decoded() as usize)
             }
+            Zstd(zstd) => {
+                let zstd = zstd.unwrap();
+                CodecOp::Zstd(deserialize_type(zstd.get_type().unwrap()), zstd.get_len_decoded() as usize)
+            }
+            UnpackBits(len) => CodecOp::UnpackBits(len as usize),
+            UnpackInts(ui) => {
+                let ui = ui.unwrap();
+                CodecOp::UnpackInts(ui.get_bits() as usize, ui.get_len() as usize)
+            }
             UnpackStrings(_) => CodecOp::UnpackStrings,
             UnhexpackStrings(uhps) => {
                 let uhps = uhps.unwrap();
@@ -220,7 +333,15 @@ fn deserialize_column(data: &[u8]) -> Column {
         }
     }).collect::<Vec<_>>();
 
-    Column::new(name, len, range, codec, data_sections)
+    let mut result = Column::new(name, len, range, codec, data_sections);
+    if column.has_bloom() {
+        let bloom = column.get_bloom().unwrap();
+        let bits = bloom.get_bits().unwrap();
+        let mut buffer = Vec::with_capacity(bits.len() as usize);
+        buffer.extend(bits);
+        result.set_bloom_index(BloomIndex::from_raw_parts(buffer, bloom.get_hashes()));
+    }
+    result
 }
 
 fn deserialize_type(t: EncodingType) -> Type {
@@ -244,6 +365,8 @@ fn deserialize_meta_data(data: &[u8], partition_id: PartitionID) -> PartitionMet
         id: partition_id,
         len: meta_data.get_len() as usize,
         tablename: meta_data.get_tablename().unwrap().to_string(),
+        // Zero for partitions stored before ingest timestamps were persisted
+        ingested_at: from_timestamp_nanos(meta_data.get_ingested_at_nanos()),
         columns: meta_data.get_columns().unwrap().iter().map(|c| {
             ColumnMetadata {
                 name: c.get_name().unwrap().to_string(),
@@ -253,18 +376,19 @@ fn deserialize_meta_data(data: &[u8], partition_id: PartitionID) -> PartitionMet
     }
 }
 
-fn serialize_meta_data(tablename: &str, columns: &[Arc<Column>]) -> Vec<u8> {
+fn serialize_meta_data(tablename: &str, len: usize, ingested_at: SystemTime, columns: &[ColumnMetadata]) -> Vec<u8> {
     let mut builder = capnp::message::Builder::new_default();
     {
         let mut meta_data = builder.init_root::<meta_data::Builder>();
-        meta_data.set_len(columns[0].len() as u64);
+        meta_data.set_len(len as u64);
         meta_data.set_tablename(tablename);
+        meta_data.set_ingested_at_nanos(timestamp_nanos(ingested_at));
         {
             let mut cols = meta_data.reborrow().init_columns(columns.len() as u32);
             for (i, column) in columns.iter().enumerate() {
                 let mut col = cols.reborrow().get(i as u32);
-                col.set_name(column.name());
-                col.set_size_bytes(column.heap_size_of_children() as u64);
+                col.set_name(&column.name);
+                col.set_size_bytes(column.size_bytes as u64);
             }
         }
     }
@@ -302,6 +426,16 @@ fn serialize_column(col: &Column) -> Vec<u8> {
                         add.set_amount(amount);
                     }
                     CodecOp::Delta(t) => capnp_op.set_delta(encoding_type_to_capnp(t)),
+                    CodecOp::FrameOfReference(t, frame_size) => {
+                        let mut fr = capnp_op.init_frame_of_reference();
+                        fr.set_type(encoding_type_to_capnp(t));
+                        fr.set_frame_size(frame_size as u64);
+                    }
+                    CodecOp::RunLength(t, len) => {
+                        let mut rl = capnp_op.init_run_length();
+                        rl.set_type(encoding_type_to_capnp(t));
+                        rl.set_len(len as u64);
+                    }
                     CodecOp::ToI64(t) => capnp_op.set_to_i64(encoding_type_to_capnp(t)),
                     CodecOp::PushDataSection(section) => capnp_op.set_push_data_section(section as u64),
                     CodecOp::DictLookup(t) => capnp_op.set_dict_lookup(encoding_type_to_capnp(t)),
@@ -310,6 +444,17 @@ fn serialize_column(col: &Column) -> Vec<u8> {
                         lz4.set_type(encoding_type_to_capnp(t));
                         lz4.set_len_decoded(decoded_length as u64);
                     }
+                    CodecOp::Zstd(t, decoded_length) => {
+                        let mut zstd = capnp_op.init_zstd();
+                        zstd.set_type(encoding_type_to_capnp(t));
+                        zstd.set_len_decoded(decoded_length as u64);
+                    }
+                    CodecOp::UnpackBits(len) => capnp_op.set_unpack_bits(len as u64),
+                    CodecOp::UnpackInts(bits, len) => {
+                        let mut ui = capnp_op.init_unpack_ints();
+                        ui.set_bits(bits as u8);
+                        ui.set_len(len as u64);
+                    }
                     CodecOp::UnpackStrings => capnp_op.set_unpack_strings(()),
                     CodecOp::UnhexpackStrings(uppercase, total_bytes) => {
                         let mut uhps = capnp_op.init_unhexpack_strings();
@@ -320,10 +465,24 @@ fn serialize_column(col: &Column) -> Vec<u8> {
                 }
             }
         }
+        if let Some(bloom) = col.bloom_index() {
+            let mut bloom_builder = column.reborrow().init_bloom();
+            bloom_builder.set_hashes(bloom.hashes());
+            let mut bits = bloom_builder.init_bits(bloom.bits().len() as u32);
+            populate_primitive_list(&mut bits, bloom.bits());
+        }
         {
             let mut data_sections = column.reborrow().init_data(col.data().len() as u32);
             for (i, section) in col.data().iter().enumerate() {
                 let mut ds = data_sections.reborrow().get(i as u32);
+                let owned;
+                let section = match section {
+                    DataSection::Mapped(mapped) => {
+                        owned = mapped.to_owned();
+                        &owned
+                    }
+                    section => section,
+                };
                 match section {
                     DataSection::U8(x) => {
                         let mut builder = ds.init_u8(x.len() as u32);
@@ -346,6 +505,7 @@ fn serialize_column(col: &Column) -> Vec<u8> {
                         populate_primitive_list(&mut builder, x);
                     }
                     DataSection::Null(count) => ds.set_null(*count as u64),
+                    DataSection::Mapped(_) => unreachable!(),
                 }
             }
         }
@@ -1,6 +1,7 @@
 use std::cmp::min;
 use std::fmt::Write;
 use std::fmt;
+use std::mem;
 
 use hex;
 use itertools::Itertools;
@@ -33,6 +34,7 @@ impl<'a> Data<'a> for ByteSlices<'a> {
     fn len(&self) -> usize { self.data.len() / self.row_len }
     fn get_raw(&self, _i: usize) -> RawVal { panic!(self.type_error("get_raw")) }
     fn get_type(&self) -> EncodingType { EncodingType::ByteSlices(self.row_len) }
+    fn heap_size_of_children(&self) -> usize { self.data.capacity() * mem::size_of::<&[u8]>() }
 
     fn append_all(&mut self, _other: &Data<'a>, _count: usize) -> Option<BoxedData<'a>> {
         panic!(self.type_error("append_all"))
@@ -32,6 +32,7 @@ pub trait Data<'a>: Send + Sync {
     fn cast_ref_null_map(&self) -> &[u8] { panic!(self.type_error("cast_ref_null_map")) }
 
     fn cast_ref_u64(&self) -> &[u64] { panic!(self.type_error("cast_ref_u64")) }
+    fn cast_ref_i128(&self) -> &[i128] { panic!(self.type_error("cast_ref_i128")) }
     fn cast_ref_usize(&self) -> &[usize] { panic!(self.type_error("cast_ref_usize")) }
 
     fn cast_ref_mixed(&self) -> &[Val<'a>] { panic!(self.type_error("cast_ref_mixed")) }
@@ -42,6 +43,7 @@ pub trait Data<'a>: Send + Sync {
     fn cast_scalar_str(&self) -> &'a str { panic!(self.type_error("cast_scalar_str")) }
     fn cast_ref_byte_slices(&self) -> &ByteSlices<'a> { panic!(self.type_error("cast_ref_byte_slices")) }
     fn cast_ref_val_rows(&self) -> &ValRows<'a> { panic!(self.type_error("cast_ref_val_rows")) }
+    fn cast_ref_hyperloglogs(&self) -> &HyperLogLogs { panic!(self.type_error("cast_ref_hyperloglogs")) }
 
     fn cast_ref_mut_str(&mut self) -> &mut Vec<&'a str> { panic!(self.type_error("cast_ref_mut_str")) }
     fn cast_ref_mut_opt_str(&mut self) -> &mut Vec<Option<&'a str>> { panic!(self.type_error("cast_ref_mut_opt_str")) }
@@ -51,6 +53,7 @@ pub trait Data<'a>: Send + Sync {
     fn cast_ref_mut_u8(&mut self) -> &mut Vec<u8> { panic!(self.type_error("cast_ref_mut_u8")) }
 
     fn cast_ref_mut_u64(&mut self) -> &mut Vec<u64> { panic!(self.type_error("cast_ref_mut_u64")) }
+    fn cast_ref_mut_i128(&mut self) -> &mut Vec<i128> { panic!(self.type_error("cast_ref_mut_i128")) }
     fn cast_ref_mut_usize(&mut self) -> &mut Vec<usize> { panic!(self.type_error("cast_ref_mut_usize")) }
 
     fn cast_ref_mut_mixed(&mut self) -> &mut Vec<Val<'a>> { panic!(self.type_error("cast_ref_mut_mixed")) }
@@ -58,11 +61,15 @@ pub trait Data<'a>: Send + Sync {
     fn cast_ref_mut_premerge(&mut self) -> &mut Vec<Premerge> { panic!(self.type_error("cast_ref_mut_premerge_op")) }
     fn cast_ref_mut_byte_slices(&mut self) -> &mut ByteSlices<'a> { panic!(self.type_error("cast_ref_mut_byte_slices")) }
     fn cast_ref_mut_val_rows(&mut self) -> &mut ValRows<'a> { panic!(self.type_error("cast_ref_mut_val_rows")) }
+    fn cast_ref_mut_hyperloglogs(&mut self) -> &mut HyperLogLogs { panic!(self.type_error("cast_ref_mut_hyperloglogs")) }
 
     fn to_mixed(&self) -> Vec<Val<'a>> { panic!(self.type_error("to_mixed")) }
 
     fn make_nullable(&mut self, _present: &[u8]) -> BoxedData<'a> { panic!(self.type_error("nullable")) }
 
+    /// Number of bytes allocated on the heap that are owned by this buffer.
+    fn heap_size_of_children(&self) -> usize { 0 }
+
     fn display(&self) -> String;
 }
 
@@ -99,6 +106,7 @@ impl<'a, T: VecData<T> + 'a> Data<'a> for Vec<T> {
     fn get_raw(&self, i: usize) -> RawVal { T::wrap_one(self[i]) }
     fn get_type(&self) -> EncodingType { T::t() }
     fn type_error(&self, func_name: &str) -> String { format!("Vec<{:?}>.{}", T::t(), func_name) }
+    fn heap_size_of_children(&self) -> usize { self.capacity() * mem::size_of::<T>() }
     fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedData<'b> where 'a: 'b {
         let to = min(to, self.len());
         Box::new(&self[from..to])
@@ -184,6 +192,11 @@ impl<'a> Data<'a> for Vec<u64> {
     fn cast_ref_mut_u64(&mut self) -> &mut Vec<u64> { self }
 }
 
+impl<'a> Data<'a> for Vec<i128> {
+    fn cast_ref_i128(&self) -> &[i128] { self }
+    fn cast_ref_mut_i128(&mut self) -> &mut Vec<i128> { self }
+}
+
 impl<'a> Data<'a> for Vec<u32> {
     fn cast_ref_u32(&self) -> &[u32] { self }
     fn cast_ref_mut_u32(&mut self) -> &mut Vec<u32> { self }
@@ -231,6 +244,10 @@ impl<'a> Data<'a> for &'a [&'a str] {
     fn cast_ref_str(&self) -> &[&'a str] { self }
 }
 
+impl<'a> Data<'a> for &'a [Option<&'a str>] {
+    fn cast_ref_opt_str(&self) -> &[Option<&'a str>] { self }
+}
+
 impl<'a> Data<'a> for &'a [Val<'a>] {
     fn cast_ref_mixed(&self) -> &[Val<'a>] { self }
 }
@@ -247,6 +264,10 @@ impl<'a> Data<'a> for &'a [u64] {
     fn cast_ref_u64(&self) -> &[u64] { self }
 }
 
+impl<'a> Data<'a> for &'a [i128] {
+    fn cast_ref_i128(&self) -> &[i128] { self }
+}
+
 impl<'a> Data<'a> for &'a [u32] {
     fn cast_ref_u32(&self) -> &[u32] { self }
 }
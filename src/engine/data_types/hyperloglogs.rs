@@ -0,0 +1,62 @@
+use std::cmp::min;
+use std::mem;
+
+use engine::data_types::*;
+use hyperloglog::HyperLogLog;
+use ingest::raw_val::RawVal;
+
+
+/// One HyperLogLog sketch per group, used as the partial result of approximate count distinct aggregations.
+#[derive(Debug, Clone)]
+pub struct HyperLogLogs {
+    pub precision: u32,
+    pub sketches: Vec<HyperLogLog>,
+}
+
+impl HyperLogLogs {
+    pub fn new(precision: u32) -> HyperLogLogs {
+        HyperLogLogs { precision, sketches: Vec::new() }
+    }
+
+    pub fn resize(&mut self, len: usize) {
+        while self.sketches.len() < len {
+            self.sketches.push(HyperLogLog::new(self.precision));
+        }
+    }
+
+    pub fn estimates(&self) -> Vec<i64> {
+        self.sketches.iter().map(|sketch| sketch.estimate() as i64).collect()
+    }
+}
+
+impl<'a> Data<'a> for HyperLogLogs {
+    fn len(&self) -> usize { self.sketches.len() }
+    fn get_raw(&self, i: usize) -> RawVal { RawVal::Int(self.sketches[i].estimate() as i64) }
+    fn get_type(&self) -> EncodingType { EncodingType::HyperLogLog }
+    fn heap_size_of_children(&self) -> usize {
+        self.sketches.capacity() * mem::size_of::<HyperLogLog>()
+            + self.sketches.iter().map(|sketch| sketch.heap_size_of_children()).sum::<usize>()
+    }
+
+    fn append_all(&mut self, other: &Data<'a>, count: usize) -> Option<BoxedData<'a>> {
+        self.sketches.extend(other.cast_ref_hyperloglogs().sketches.iter().take(count).cloned());
+        None
+    }
+
+    fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedData<'b> where 'a: 'b {
+        let to = min(to, self.len());
+        Box::new(HyperLogLogs {
+            precision: self.precision,
+            sketches: self.sketches[from..to].to_vec(),
+        })
+    }
+
+    fn type_error(&self, func_name: &str) -> String { format!("HyperLogLogs.{}", func_name) }
+
+    fn display(&self) -> String {
+        format!("HyperLogLogs[{}]{:?}", self.precision, self.estimates())
+    }
+
+    fn cast_ref_hyperloglogs(&self) -> &HyperLogLogs { self }
+    fn cast_ref_mut_hyperloglogs(&mut self) -> &mut HyperLogLogs { self }
+}
@@ -1,5 +1,6 @@
 mod byte_slices;
 mod data;
+mod hyperloglogs;
 mod nullable_vec_data;
 mod scalar_data;
 mod types;
@@ -12,4 +13,5 @@ pub use self::vec_data::*;
 pub use self::byte_slices::*;
 pub use self::scalar_data::*;
 pub use self::val_rows::*;
+pub use self::hyperloglogs::*;
 pub use self::nullable_vec_data::*;
@@ -1,6 +1,7 @@
 use std::cmp::min;
 use std::fmt;
 use std::fmt::Write;
+use std::mem;
 
 use itertools::Itertools;
 
@@ -16,6 +17,9 @@ pub struct NullableVec<T> {
 
 impl<'a, T: VecData<T> + 'a> Data<'a> for NullableVec<T> {
     fn len(&self) -> usize { self.data.len() }
+    fn heap_size_of_children(&self) -> usize {
+        self.data.capacity() * mem::size_of::<T>() + self.present.capacity()
+    }
     fn get_raw(&self, i: usize) -> RawVal {
         if self.present.is_set(i) { T::wrap_one(self.data[i]) } else { RawVal::Null }
     }
@@ -9,6 +9,8 @@ pub enum EncodingType {
     U16,
     U32,
     U64,
+    /// Partial results of `SUM` aggregations, see `CheckedAggregate`.
+    I128,
 
     NullableStr,
     NullableI64,
@@ -28,6 +30,7 @@ pub enum EncodingType {
 
     ByteSlices(usize),
     ValRows,
+    HyperLogLog,
     Premerge,
     MergeOp,
 }
@@ -37,7 +40,9 @@ impl EncodingType {
         match self {
             EncodingType::Str => BasicType::String,
             EncodingType::I64 => BasicType::Integer,
+            EncodingType::U8 => BasicType::Boolean,
             EncodingType::NullableStr => BasicType::NullableString,
+            EncodingType::OptStr => BasicType::NullableString,
             EncodingType::NullableI64 => BasicType::NullableInteger,
             EncodingType::Val => BasicType::Val,
             EncodingType::Null => BasicType::Null,
@@ -108,6 +113,18 @@ impl EncodingType {
             }
         }
     }
+
+    /// Type of the non-null values that `Data::get_raw` returns for data of this type, `BasicType::Null` for types that
+    /// only contain nulls or are not returned as values.
+    pub fn value_type(&self) -> BasicType {
+        match self.non_nullable() {
+            EncodingType::Str | EncodingType::ScalarStr | EncodingType::ScalarString => BasicType::String,
+            EncodingType::I64 | EncodingType::U8 | EncodingType::U16 | EncodingType::U32 | EncodingType::U64 |
+            EncodingType::I128 | EncodingType::ScalarI64 | EncodingType::HyperLogLog => BasicType::Integer,
+            EncodingType::Val | EncodingType::ConstVal => BasicType::Val,
+            _ => BasicType::Null,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -25,12 +25,14 @@ pub trait VecData<T>: PartialEq + Ord + Copy + Debug + Sync + Send {
 impl VecData<u8> for u8 {
     fn unwrap<'a, 'b>(vec: &'b Data<'a>) -> &'b [u8] where u8: 'a { vec.cast_ref_u8() }
     fn unwrap_mut<'a, 'b>(vec: &'b mut Data<'a>) -> &'b mut Vec<u8> where u8: 'a { vec.cast_ref_mut_u8() }
+    fn wrap_one(value: u8) -> RawVal { RawVal::Int(i64::from(value)) }
     fn t() -> EncodingType { EncodingType::U8 }
 }
 
 impl VecData<u16> for u16 {
     fn unwrap<'a, 'b>(vec: &'b Data<'a>) -> &'b [u16] where u16: 'a { vec.cast_ref_u16() }
     fn unwrap_mut<'a, 'b>(vec: &'b mut Data<'a>) -> &'b mut Vec<u16> where u16: 'a { vec.cast_ref_mut_u16() }
+    fn wrap_one(value: u16) -> RawVal { RawVal::Int(i64::from(value)) }
     fn t() -> EncodingType { EncodingType::U16 }
 }
 
@@ -55,6 +57,21 @@ impl VecData<u64> for u64 {
     fn t() -> EncodingType { EncodingType::U64 }
 }
 
+impl VecData<i128> for i128 {
+    fn unwrap<'a, 'b>(vec: &'b Data<'a>) -> &'b [i128] where i128: 'a { vec.cast_ref_i128() }
+    fn unwrap_mut<'a, 'b>(vec: &'b mut Data<'a>) -> &'b mut Vec<i128> where i128: 'a { vec.cast_ref_mut_i128() }
+    // Values that don't fit into an i64 are only contained in partial results, which are narrowed before they are
+    // returned. Other uses represent them by their decimal string so that no precision is lost.
+    fn wrap_one(value: i128) -> RawVal {
+        if value >= i128::from(i64::MIN) && value <= i128::from(i64::MAX) {
+            RawVal::Int(value as i64)
+        } else {
+            RawVal::Str(value.to_string())
+        }
+    }
+    fn t() -> EncodingType { EncodingType::I128 }
+}
+
 impl VecData<usize> for usize {
     fn unwrap<'a, 'b>(vec: &'b Data<'a>) -> &'b [usize] where usize: 'a { vec.cast_ref_usize() }
     fn unwrap_mut<'a, 'b>(vec: &'b mut Data<'a>) -> &'b mut Vec<usize> where usize: 'a { vec.cast_ref_mut_usize() }
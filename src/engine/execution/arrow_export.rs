@@ -0,0 +1,175 @@
+extern crate arrow;
+
+use std::sync::Arc;
+
+use self::arrow::array::ArrayRef;
+use self::arrow::builder::{BinaryBuilder, BooleanBuilder, Int64Builder};
+use self::arrow::datatypes::{DataType, Field, Schema};
+use self::arrow::record_batch::RecordBatch;
+
+use engine::data_types::BasicType;
+use engine::execution::query_task::QueryOutput;
+use errors::QueryError;
+use ingest::raw_val::RawVal;
+
+
+impl QueryOutput {
+    /// Converts the result into an Arrow record batch that has one nullable field for each result column.
+    /// Integer columns become `Int64` arrays, string columns `Utf8` arrays and boolean columns `Boolean` arrays. Columns
+    /// that only contain nulls are exported as `Int64` arrays, columns that mix integers and strings can't be converted.
+    pub fn to_record_batch(&self) -> Result<RecordBatch, QueryError> {
+        let mut fields = Vec::with_capacity(self.colnames.len());
+        let mut columns = Vec::with_capacity(self.colnames.len());
+        for (i, colname) in self.colnames.iter().enumerate() {
+            let (data_type, column) = self.arrow_column(i)?;
+            fields.push(Field::new(colname, data_type, true));
+            columns.push(column);
+        }
+        Ok(RecordBatch::new(Arc::new(Schema::new(fields)), columns))
+    }
+
+    fn arrow_column(&self, index: usize) -> Result<(DataType, ArrayRef), QueryError> {
+        if self.coltypes.get(index) == Some(&BasicType::Boolean) {
+            let mut builder = BooleanBuilder::new(self.rows.len());
+            for row in &self.rows {
+                match row[index] {
+                    RawVal::Int(i) => builder.append_value(i != 0).unwrap(),
+                    RawVal::Null => builder.append_null().unwrap(),
+                    RawVal::Str(_) => bail!(QueryError::TypeError, "Boolean column {} contains strings", self.colnames[index]),
+                }
+            }
+            return Ok((DataType::Boolean, Arc::new(builder.finish())));
+        }
+        let is_string = self.rows.iter()
+            .map(|row| row[index].get_type())
+            .find(|&t| t != BasicType::Null) == Some(BasicType::String);
+        if is_string {
+            let mut builder = BinaryBuilder::new(self.rows.len());
+            for row in &self.rows {
+                match row[index] {
+                    RawVal::Str(ref s) => builder.append_string(s).unwrap(),
+                    RawVal::Null => builder.append_null().unwrap(),
+                    RawVal::Int(_) => bail!(QueryError::TypeError, "Column {} contains both strings and integers", self.colnames[index]),
+                }
+            }
+            Ok((DataType::Utf8, Arc::new(builder.finish())))
+        } else {
+            let mut builder = Int64Builder::new(self.rows.len());
+            for row in &self.rows {
+                match row[index] {
+                    RawVal::Int(i) => builder.append_value(i).unwrap(),
+                    RawVal::Null => builder.append_null().unwrap(),
+                    RawVal::Str(_) => bail!(QueryError::TypeError, "Column {} contains both integers and strings", self.colnames[index]),
+                }
+            }
+            Ok((DataType::Int64, Arc::new(builder.finish())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::arrow::array::{Array, BinaryArray, BooleanArray, Int64Array};
+    use std::collections::HashMap;
+    use std::time::SystemTime;
+    use futures_executor::block_on;
+    use engine::execution::query_task::QueryStats;
+    use ingest::input_column::InputColumn;
+    use locustdb::LocustDB;
+
+    fn rows(batch: &RecordBatch) -> Vec<Vec<RawVal>> {
+        (0..batch.num_rows())
+            .map(|i| (0..batch.num_columns())
+                .map(|c| {
+                    let column = batch.column(c);
+                    if column.is_null(i) {
+                        RawVal::Null
+                    } else if let Some(ints) = column.as_any().downcast_ref::<Int64Array>() {
+                        RawVal::Int(ints.value(i))
+                    } else if let Some(bools) = column.as_any().downcast_ref::<BooleanArray>() {
+                        RawVal::Int(bools.value(i) as i64)
+                    } else {
+                        let strings = column.as_any().downcast_ref::<BinaryArray>().unwrap();
+                        RawVal::Str(strings.get_string(i))
+                    }
+                })
+                .collect())
+            .collect()
+    }
+
+    fn assert_round_trip(locustdb: &LocustDB, query: &str, data_types: &[DataType]) {
+        let output = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+        let batch = output.to_record_batch().unwrap();
+        let schema = batch.schema();
+        assert_eq!(schema.fields().iter().map(|f| f.data_type().clone()).collect::<Vec<_>>(), data_types);
+        assert_eq!(schema.fields().iter().map(|f| f.name().to_string()).collect::<Vec<_>>(), output.colnames);
+        assert_eq!(rows(&batch), output.rows);
+    }
+
+    fn test_db() -> LocustDB {
+        let locustdb = LocustDB::memory_only();
+        let data = vec![
+            ("i".to_string(), InputColumn::Int(vec![3, -1, 1 << 40])),
+            ("s".to_string(), InputColumn::Str(vec!["a".to_string(), "".to_string(), "xyz".to_string()])),
+            ("n".to_string(), InputColumn::Null(3)),
+        ];
+        block_on(locustdb.ingest_with_timestamp("t", data, SystemTime::now())).unwrap().unwrap();
+        let data = vec![
+            ("i".to_string(), InputColumn::Int(vec![7])),
+            ("n".to_string(), InputColumn::Int(vec![4])),
+        ];
+        block_on(locustdb.ingest_with_timestamp("t", data, SystemTime::now())).unwrap().unwrap();
+        locustdb
+    }
+
+    #[test]
+    fn test_round_trip_integers() {
+        assert_round_trip(&test_db(), "SELECT i, i * 2 FROM t ORDER BY i", &[DataType::Int64, DataType::Int64]);
+    }
+
+    #[test]
+    fn test_round_trip_strings() {
+        assert_round_trip(&test_db(), "SELECT s FROM t WHERE i < 5 ORDER BY s", &[DataType::Utf8]);
+    }
+
+    #[test]
+    fn test_round_trip_nullable() {
+        let locustdb = test_db();
+        assert_round_trip(&locustdb, "SELECT i, s, n FROM t ORDER BY i", &[DataType::Int64, DataType::Utf8, DataType::Int64]);
+        assert_round_trip(&locustdb, "SELECT n FROM t WHERE i < 5", &[DataType::Int64]);
+    }
+
+    #[test]
+    fn test_round_trip_booleans() {
+        let locustdb = test_db();
+        assert_round_trip(&locustdb, "SELECT i > 2, s = 'a' FROM t WHERE i < 5 ORDER BY i", &[DataType::Boolean, DataType::Boolean]);
+        assert_round_trip(&locustdb, "SELECT i, n > 3 FROM t ORDER BY i", &[DataType::Int64, DataType::Boolean]);
+    }
+
+    #[test]
+    fn test_to_record_batch() {
+        let output = QueryOutput {
+            colnames: vec!["name".to_string(), "count".to_string()],
+            coltypes: vec![BasicType::String, BasicType::Integer],
+            rows: vec![
+                vec![RawVal::Str("a".to_string()), RawVal::Int(3)],
+                vec![RawVal::Null, RawVal::Int(-1)],
+            ],
+            query_plans: HashMap::default(),
+            stats: QueryStats::default(),
+            truncated: false,
+        };
+        let batch = output.to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema().field(0).name(), "name");
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::Utf8);
+        assert_eq!(batch.schema().field(1).data_type(), &DataType::Int64);
+
+        let names = batch.column(0).as_any().downcast_ref::<BinaryArray>().unwrap();
+        assert_eq!(names.get_string(0), "a");
+        assert!(names.is_null(1));
+        let counts = batch.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!((counts.value(0), counts.value(1)), (3, -1));
+    }
+}
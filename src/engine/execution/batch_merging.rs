@@ -1,8 +1,9 @@
 use engine::*;
 use errors::QueryError;
 use mem_store::column::DataSource;
-use std::cmp::min;
-use std::collections::HashMap;
+use std::cmp::{min, Ordering};
+use std::collections::{BinaryHeap, HashMap};
+use std::i64;
 use std::result::Result;
 use std::sync::Arc;
 use std::usize;
@@ -11,11 +12,15 @@ use std::usize;
 pub struct BatchResult<'a> {
     pub columns: Vec<BoxedData<'a>>,
     pub projection: Vec<usize>,
+    /// Positions in `projection` of the columns with planned type `BasicType::Boolean`, which hold the integers 0 and 1.
+    pub boolean_projections: Vec<usize>,
     pub aggregations: Vec<(usize, Aggregator)>,
-    pub order_by: Vec<(usize, bool)>,
+    pub order_by: Vec<(usize, bool, NullsOrder)>,
     pub level: u32,
     pub batch_count: usize,
     pub show: bool,
+    /// Set if some partitions were not processed because the query timed out.
+    pub truncated: bool,
     // Buffers that are referenced by query result - unsafe to drop before results are converted into owned values
     pub unsafe_referenced_buffers: Vec<BoxedData<'a>>,
 }
@@ -45,16 +50,47 @@ impl<'a> BatchResult<'a> {
         Ok(())
     }
 
-    pub fn into_columns(self) -> HashMap<String, Arc<DataSource + 'a>> {
+    /// Converts the wide partial sums of `Aggregator::Sum` and `Aggregator::SaturatingSum` to `i64` once the results
+    /// of all partitions have been combined. Sums that do not fit into an `i64` fail with `QueryError::Overflow`, or
+    /// are clamped to `-i64::MAX`/`i64::MAX` for `Aggregator::SaturatingSum`. `i64::MIN` is not used because it
+    /// represents null in `i64` results.
+    pub fn narrow_wide_sums(&mut self) -> Result<(), QueryError> {
+        for &(aggregation, aggregator) in &self.aggregations {
+            if self.columns[aggregation].get_type() == EncodingType::I128 {
+                let (min, max) = (i128::from(i64::MIN), i128::from(i64::MAX));
+                let narrowed = self.columns[aggregation].cast_ref_i128().iter()
+                    .map(|&sum| if sum >= min && sum <= max {
+                        Ok(sum as i64)
+                    } else if aggregator == Aggregator::SaturatingSum {
+                        Ok(if sum < min { -i64::MAX } else { i64::MAX })
+                    } else {
+                        Err(QueryError::Overflow)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.columns[aggregation] = Data::owned(narrowed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the result columns by the names that the final pass refers to them by, together with the buffers that
+    /// they reference, which have to be kept alive for as long as the columns are used.
+    pub fn into_columns(self) -> (HashMap<String, Arc<DataSource + 'a>>, Vec<BoxedData<'a>>) {
         let mut cols = HashMap::<String, Arc<DataSource>>::default();
         let columns = self.columns.into_iter().map(|c| Arc::new(c)).collect::<Vec<_>>();
         for projection in self.projection {
             cols.insert(format!("_cs{}", projection), columns[projection].clone());
         }
-        for (i, &(aggregation, _)) in self.aggregations.iter().enumerate() {
-            cols.insert(format!("_ca{}", i), columns[aggregation].clone());
+        for (i, &(aggregation, aggregator)) in self.aggregations.iter().enumerate() {
+            let column = match aggregator {
+                // Subsequent passes operate on the estimates rather than the sketches
+                Aggregator::ApproxCountDistinct(_) =>
+                    Arc::new(Data::owned(columns[aggregation].cast_ref_hyperloglogs().estimates())),
+                _ => columns[aggregation].clone(),
+            };
+            cols.insert(format!("_ca{}", i), column);
         }
-        cols
+        (cols, self.unsafe_referenced_buffers)
     }
 }
 
@@ -74,13 +110,14 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
         "Unequal number of aggregations in left ({:?}) and right ({:?}) batch result.",
         batch1.aggregations.len(), batch2.aggregations.len(),
     );
-
+    let boolean_projections = union(&batch1.boolean_projections, &batch2.boolean_projections);
 
     let mut qp = QueryPlanner::default();
     let mut data = Vec::new();
 
     if !batch1.aggregations.is_empty() {
         // Aggregation query
+        let (len1, len2) = (batch1.len(), batch2.len());
         let left = batch1.columns.into_iter()
             .map(|vec| {
                 let buffer = qp.constant_vec(data.len(), vec.encoding_type());
@@ -99,8 +136,14 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
         let lprojection = batch1.projection;
         let rprojection = batch2.projection;
         let (group_by_cols, ops) = if lprojection.len() == 0 {
+            // Without grouping there is at most one row, which is missing if no rows were selected
             let ops = qp.constant_vec(data.len(), EncodingType::MergeOp).merge_op()?;
-            data.push(Box::new(vec![MergeOp::TakeLeft, MergeOp::MergeRight]));
+            data.push(Box::new(match (len1, len2) {
+                (0, 0) => vec![],
+                (_, 0) => vec![MergeOp::TakeLeft],
+                (0, _) => vec![MergeOp::TakeRight],
+                _ => vec![MergeOp::TakeLeft, MergeOp::MergeRight],
+            }));
             (vec![], ops)
         } else if lprojection.len() == 1 {
             let (l, r) = unify_types(&mut qp, left[lprojection[0]], right[rprojection[0]]);
@@ -108,10 +151,10 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
             (vec![merged.any()], ops)
         } else {
             let (l, r) = unify_types(&mut qp, left[lprojection[0]], right[rprojection[0]]);
-            let mut partitioning = qp.partition(l, r, limit, false);
+            let mut partitioning = qp.partition(l, r, limit, false, true);
             for i in 1..(lprojection.len() - 1) {
                 let (l, r) = unify_types(&mut qp, left[lprojection[i]], right[rprojection[i]]);
-                partitioning = qp.subpartition(partitioning, l, r, false);
+                partitioning = qp.subpartition(partitioning, l, r, false, true);
             }
 
             let last = lprojection.len() - 1;
@@ -131,10 +174,16 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
 
         let mut aggregates = Vec::with_capacity(batch1.aggregations.len());
         for (&(ileft, aggregator), &(iright, _)) in batch1.aggregations.iter().zip(batch2.aggregations.iter()) {
-            let left = left[ileft].i64()?;
-            let right = right[iright].i64()?;
-            let aggregated = qp.merge_aggregate(ops, left, right, aggregator);
-            aggregates.push((aggregated.any(), aggregator));
+            let aggregated = match aggregator {
+                Aggregator::ApproxCountDistinct(_) => qp.merge_hyper_log_logs(ops, left[ileft], right[iright]).any(),
+                Aggregator::Sum | Aggregator::SaturatingSum => qp.merge_wide_sums(ops, left[ileft], right[iright]).any(),
+                _ => {
+                    let left = left[ileft].i64()?;
+                    let right = right[iright].i64()?;
+                    qp.merge_aggregate(ops, left, right, aggregator).any()
+                }
+            };
+            aggregates.push((aggregated, aggregator));
         }
 
         let mut executor = qp.prepare(data)?;
@@ -145,11 +194,13 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
         let result = BatchResult {
             columns,
             projection,
+            boolean_projections,
             aggregations,
             order_by: vec![],
             level: batch1.level + 1,
             batch_count: batch1.batch_count + batch2.batch_count,
             show: batch1.show && batch2.show,
+            truncated: batch1.truncated || batch2.truncated,
             unsafe_referenced_buffers: {
                 let mut urb = batch1.unsafe_referenced_buffers;
                 urb.extend(batch2.unsafe_referenced_buffers.into_iter());
@@ -177,30 +228,31 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                 })
                 .collect::<Vec<_>>();
 
-            let (final_sort_col_index1, final_desc) = *batch1.order_by.last().unwrap();
+            let (final_sort_col_index1, final_desc, final_nulls) = *batch1.order_by.last().unwrap();
             let final_sort_col_index2 = batch2.order_by.last().unwrap().0;
             let (merge_ops, merged_final_sort_col) = if batch1.order_by.len() == 1 {
-                let (index1, desc) = batch1.order_by[0];
-                let (index2, _) = batch2.order_by[0];
+                let (index1, desc, nulls) = batch1.order_by[0];
+                let (index2, _, _) = batch2.order_by[0];
                 let (left, right) = unify_types(&mut qp, left[index1], right[index2]);
-                qp.merge(left, right, limit, desc)
+                qp.merge(left, right, limit, desc, nulls == NullsOrder::First)
             } else {
-                let (first_sort_col_index1, desc) = batch1.order_by[0];
-                let (first_sort_col_index2, _) = batch2.order_by[0];
+                let (first_sort_col_index1, desc, nulls) = batch1.order_by[0];
+                let (first_sort_col_index2, _, _) = batch2.order_by[0];
                 let (l, r) = unify_types(&mut qp, left[first_sort_col_index1], right[first_sort_col_index2]);
-                let mut partitioning = qp.partition(l.clone(), r.clone(), limit, desc);
+                let mut partitioning = qp.partition(l.clone(), r.clone(), limit, desc, nulls == NullsOrder::First);
 
-                for i in 1..(left.len() - 1) {
-                    let (index1, desc) = batch1.order_by[i];
-                    let (index2, _) = batch1.order_by[i];
+                for i in 1..(batch1.order_by.len() - 1) {
+                    let (index1, desc, nulls) = batch1.order_by[i];
+                    let (index2, _, _) = batch2.order_by[i];
                     let (l, r) = unify_types(&mut qp, left[index1], right[index2]);
-                    partitioning = qp.subpartition(partitioning, l, r, desc);
+                    partitioning = qp.subpartition(partitioning, l, r, desc, nulls == NullsOrder::First);
                 }
                 let (l, r) = unify_types(&mut qp, left[final_sort_col_index1], right[final_sort_col_index2]);
                 qp.merge_partitioned(partitioning,
                                      l, r,
                                      limit,
-                                     batch1.order_by.last().unwrap().1)
+                                     final_desc,
+                                     final_nulls == NullsOrder::First)
             };
 
             let mut projection = Vec::new();
@@ -214,14 +266,14 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                 }
             }
             let mut order_by = vec![];
-            for (&(ileft, desc), &(iright, _)) in
+            for (&(ileft, desc, nulls), &(iright, _, _)) in
                 batch1.order_by[0..batch1.order_by.len() - 1].iter()
                     .zip(batch2.order_by.iter()) {
                 let (l, r) = unify_types(&mut qp, left[ileft], right[iright]);
                 let merged = qp.merge_keep(merge_ops, l, r);
-                order_by.push((merged.any(), desc));
+                order_by.push((merged.any(), desc, nulls));
             }
-            order_by.push((merged_final_sort_col.any(), final_desc));
+            order_by.push((merged_final_sort_col.any(), final_desc, final_nulls));
 
             let mut executor = qp.prepare(data)?;
             let mut results = executor.prepare_no_columns();
@@ -231,11 +283,13 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
             Ok(BatchResult {
                 columns,
                 projection,
+                boolean_projections,
                 order_by,
                 aggregations: vec![],
                 level: batch1.level + 1,
                 batch_count: batch1.batch_count + batch2.batch_count,
                 show: batch1.show && batch2.show,
+                truncated: batch1.truncated || batch2.truncated,
                 unsafe_referenced_buffers: {
                     let mut urb = batch1.unsafe_referenced_buffers;
                     urb.extend(batch2.unsafe_referenced_buffers.into_iter());
@@ -269,11 +323,13 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
             Ok(BatchResult {
                 columns: result,
                 projection: batch1.projection,
+                boolean_projections,
                 aggregations: vec![],
                 order_by: vec![],
                 level: batch1.level + 1,
                 batch_count: batch1.batch_count + batch2.batch_count,
                 show: batch1.show && batch2.show,
+                truncated: batch1.truncated || batch2.truncated,
                 unsafe_referenced_buffers: {
                     let mut urb = batch1.unsafe_referenced_buffers;
                     urb.extend(batch2.unsafe_referenced_buffers.into_iter());
@@ -284,6 +340,247 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
     }
 }
 
+/// Columns that are null in one batch have planned type `BasicType::Null`, so a column is boolean if it is boolean in
+/// either batch.
+fn union(positions1: &[usize], positions2: &[usize]) -> Vec<usize> {
+    let mut positions = positions1.to_vec();
+    positions.extend_from_slice(positions2);
+    positions.sort();
+    positions.dedup();
+    positions
+}
+
+/// Adds `batch` to `batches`, merging it with previous results of the same level to get O(n log n) complexity.
+/// Batches that can be merged by `merge_sorted` are kept as they are until `combine_all` merges them all at once.
+pub fn merge_incremental<'a>(batches: &mut Vec<BatchResult<'a>>, mut batch: BatchResult<'a>, limit: usize) -> Result<(), QueryError> {
+    if batches.last().map_or(false, |last| sorted_mergeable(last, &batch)) {
+        batches.push(batch);
+        return Ok(());
+    }
+    while let Some(br) = batches.pop() {
+        if br.level == batch.level {
+            batch = combine(br, batch, limit)?;
+        } else {
+            batches.push(br);
+            break;
+        }
+    }
+    batches.push(batch);
+    Ok(())
+}
+
+pub fn combine_all<'a>(batches: Vec<BatchResult<'a>>, limit: usize) -> Result<Option<BatchResult<'a>>, QueryError> {
+    if batches.len() > 2 {
+        if let Some(columns) = merge_sorted(&batches, limit) {
+            let mut batches = batches.into_iter();
+            let first = batches.next().unwrap();
+            let mut result = BatchResult {
+                columns,
+                projection: first.projection,
+                boolean_projections: first.boolean_projections,
+                aggregations: vec![],
+                order_by: first.order_by,
+                level: first.level + 1,
+                batch_count: first.batch_count,
+                show: first.show,
+                truncated: first.truncated,
+                unsafe_referenced_buffers: first.unsafe_referenced_buffers,
+            };
+            for batch in batches {
+                result.boolean_projections = union(&result.boolean_projections, &batch.boolean_projections);
+                result.level = result.level.max(batch.level + 1);
+                result.batch_count += batch.batch_count;
+                result.show &= batch.show;
+                result.truncated |= batch.truncated;
+                result.unsafe_referenced_buffers.extend(batch.unsafe_referenced_buffers.into_iter());
+            }
+            return Ok(Some(result));
+        }
+    }
+    let mut full_result = None;
+    for batch in batches {
+        if let Some(partial) = full_result {
+            full_result = Some(combine(partial, batch, limit)?);
+        } else {
+            full_result = Some(batch);
+        }
+    }
+    Ok(full_result)
+}
+
+/// Merges batches of a sort query that are each sorted by the same columns with a heap-based k-way merge and returns
+/// the columns of the first `limit` rows. Rows that are equal in all sort columns are taken from earlier batches first.
+/// Returns `None` unless all columns are integers or strings of the same type in every batch.
+fn merge_sorted<'a>(batches: &[BatchResult<'a>], limit: usize) -> Option<Vec<BoxedData<'a>>> {
+    let first = batches.first()?;
+    let mut columns = Vec::with_capacity(batches.len());
+    for batch in batches {
+        if !sorted_mergeable(first, batch) {
+            return None;
+        }
+        columns.push(batch.columns.iter()
+            .map(|column| SortedColumn::new(&**column))
+            .collect::<Option<Vec<_>>>()?);
+    }
+    let sort_columns = columns.iter()
+        .map(|columns| first.order_by.iter().map(|&(index, desc, _)| (columns[index], desc)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut heap = BinaryHeap::with_capacity(batches.len());
+    for (batch, sort_columns) in sort_columns.iter().enumerate() {
+        if batches[batch].len() > 0 {
+            heap.push(Cursor { sort_columns, batch, row: 0 });
+        }
+    }
+    let total = batches.iter().map(|batch| batch.len()).sum::<usize>();
+    let mut rows = Vec::with_capacity(min(limit, total));
+    while rows.len() < limit {
+        let mut cursor = match heap.pop() {
+            Some(cursor) => cursor,
+            None => break,
+        };
+        rows.push((cursor.batch, cursor.row));
+        cursor.row += 1;
+        if cursor.row < batches[cursor.batch].len() {
+            heap.push(cursor);
+        }
+    }
+
+    Some((0..first.columns.len())
+        .map(|i| {
+            let column: BoxedData<'a> = match columns[0][i] {
+                SortedColumn::Integers(_) => Box::new(
+                    rows.iter().map(|&(batch, row)| columns[batch][i].integer(row)).collect::<Vec<_>>()),
+                SortedColumn::Strings(_) => Box::new(
+                    rows.iter().map(|&(batch, row)| columns[batch][i].string(row)).collect::<Vec<_>>()),
+            };
+            column
+        })
+        .collect())
+}
+
+/// Whether `batch1` and `batch2` are sorted by the same columns and have the same integer and string columns.
+fn sorted_mergeable(batch1: &BatchResult, batch2: &BatchResult) -> bool {
+    batch1.aggregations.is_empty() && batch2.aggregations.is_empty()
+        && !batch1.order_by.is_empty() && batch1.order_by == batch2.order_by
+        && batch1.projection == batch2.projection
+        && batch1.columns.len() == batch2.columns.len()
+        && batch1.columns.iter().zip(batch2.columns.iter())
+        .all(|(column1, column2)| {
+            let t = column1.get_type();
+            t == column2.get_type() && (t == EncodingType::I64 || t == EncodingType::Str)
+        })
+}
+
+#[derive(Clone, Copy)]
+enum SortedColumn<'b, 'a: 'b> {
+    Integers(&'b [i64]),
+    Strings(&'b [&'a str]),
+}
+
+impl<'b, 'a: 'b> SortedColumn<'b, 'a> {
+    fn new(data: &'b Data<'a>) -> Option<SortedColumn<'b, 'a>> {
+        match data.get_type() {
+            EncodingType::I64 => Some(SortedColumn::Integers(data.cast_ref_i64())),
+            EncodingType::Str => Some(SortedColumn::Strings(data.cast_ref_str())),
+            _ => None,
+        }
+    }
+
+    fn integer(&self, row: usize) -> i64 {
+        match self {
+            SortedColumn::Integers(values) => values[row],
+            SortedColumn::Strings(_) => panic!("Expected integer column"),
+        }
+    }
+
+    fn string(&self, row: usize) -> &'a str {
+        match self {
+            SortedColumn::Strings(values) => values[row],
+            SortedColumn::Integers(_) => panic!("Expected string column"),
+        }
+    }
+
+    fn cmp(&self, row: usize, other: &SortedColumn, other_row: usize) -> Ordering {
+        match (self, other) {
+            (SortedColumn::Integers(left), SortedColumn::Integers(right)) => left[row].cmp(&right[other_row]),
+            (SortedColumn::Strings(left), SortedColumn::Strings(right)) => left[row].cmp(right[other_row]),
+            _ => panic!("Comparing integer with string column"),
+        }
+    }
+}
+
+/// Next row of a batch in `merge_sorted`, ordered such that the `BinaryHeap` yields the row that comes first.
+struct Cursor<'b, 'a: 'b> {
+    sort_columns: &'b [(SortedColumn<'b, 'a>, bool)],
+    batch: usize,
+    row: usize,
+}
+
+impl<'b, 'a: 'b> Ord for Cursor<'b, 'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for (&(left, desc), &(right, _)) in self.sort_columns.iter().zip(other.sort_columns.iter()) {
+            let ordering = left.cmp(self.row, &right, other.row);
+            if ordering != Ordering::Equal {
+                return if desc { ordering } else { ordering.reverse() };
+            }
+        }
+        other.batch.cmp(&self.batch)
+    }
+}
+
+impl<'b, 'a: 'b> PartialOrd for Cursor<'b, 'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'b, 'a: 'b> PartialEq for Cursor<'b, 'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'b, 'a: 'b> Eq for Cursor<'b, 'a> {}
+
+/// Owned copy of a batch result, e.g. to send it to another node. HyperLogLog sketches are sent as their estimates.
+/// Wide sums are sent as integers, or as decimal strings if they don't fit into an i64.
+#[cfg(feature = "enable_proto")]
+impl<'a, 'b> From<&'b BatchResult<'a>> for ::proto::BatchResult {
+    fn from(batch: &'b BatchResult<'a>) -> ::proto::BatchResult {
+        ::proto::BatchResult {
+            columns: batch.columns.iter().map(|column| typed_vec(&**column)).collect(),
+            projection: batch.projection.iter().map(|&column| column as u64).collect(),
+            boolean_projections: batch.boolean_projections.iter().map(|&position| position as u64).collect(),
+            aggregations: batch.aggregations.iter()
+                .map(|&(column, aggregator)| ::proto::AggregationColumn { column: column as u64, aggregator: Some(aggregator.into()) })
+                .collect(),
+            order_by: batch.order_by.iter()
+                .map(|&(column, desc, nulls)| ::proto::OrderByColumn { column: column as u64, desc, nulls: ::proto::NullsOrder::from(nulls) as i32 })
+                .collect(),
+            level: batch.level,
+            batch_count: batch.batch_count as u64,
+            show: batch.show,
+            truncated: batch.truncated,
+        }
+    }
+}
+
+#[cfg(feature = "enable_proto")]
+fn typed_vec(data: &Data) -> ::proto::TypedVec {
+    use proto::typed_vec::Values;
+    let values = match data.get_type() {
+        EncodingType::I64 => Values::Integers(::proto::Integers { values: data.cast_ref_i64().to_vec() }),
+        EncodingType::Str => Values::Strings(::proto::Strings {
+            values: data.cast_ref_str().iter().map(|s| s.to_string()).collect(),
+        }),
+        _ => Values::RawVals(::proto::RawVals {
+            values: (0..data.len()).map(|i| data.get_raw(i).into()).collect(),
+        }),
+    };
+    ::proto::TypedVec { values: Some(values) }
+}
+
 fn unify_types(qp: &mut QueryPlanner, mut left: TypedBufferRef, mut right: TypedBufferRef) -> (TypedBufferRef, TypedBufferRef) {
     let lub = left.tag.least_upper_bound(right.tag);
     if left.tag != lub {
@@ -0,0 +1,446 @@
+use std::cmp::min;
+use std::io::{self, Read};
+use std::str;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use engine::data_types::*;
+use engine::execution::BatchResult;
+use engine::operators::Aggregator;
+use engine::planning::NullsOrder;
+use errors::QueryError;
+use hyperloglog::{self, HyperLogLog};
+use ingest::raw_val::RawVal;
+use mem_store::value::Val;
+
+
+const MAGIC: &[u8; 8] = b"LOCUSTB2";
+
+impl<'a> BatchResult<'a> {
+    /// Serializes the batch result into a self-contained byte buffer that can be read back with `from_bytes`.
+    ///
+    /// Layout, all integers are little endian:
+    /// - magic bytes `LOCUSTB2`
+    /// - level as u32, batch count as u64, show and truncated as u8
+    /// - number of projections as u32 followed by the column index of each projection as u64
+    /// - number of boolean projections as u32 followed by the position of each in the projections as u64
+    /// - number of aggregations as u32, each a column index as u64, the aggregator tag as u8 (see `write_aggregator`)
+    ///   and the precision of `ApproxCountDistinct` as u8 (0 for other aggregators)
+    /// - number of order by columns as u32, each a column index as u64, desc as u8 and nulls first as u8
+    /// - number of columns as u32 followed by the columns, each an encoding type tag as u8 (see `write_column`),
+    ///   the number of rows as u64 and the values of the column stored one after another
+    ///
+    /// Columns of types without a tag of their own are written as mixed values.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(MAGIC);
+        buffer.write_u32::<LittleEndian>(self.level).unwrap();
+        buffer.write_u64::<LittleEndian>(self.batch_count as u64).unwrap();
+        buffer.write_u8(self.show as u8).unwrap();
+        buffer.write_u8(self.truncated as u8).unwrap();
+        buffer.write_u32::<LittleEndian>(self.projection.len() as u32).unwrap();
+        for &column in &self.projection {
+            buffer.write_u64::<LittleEndian>(column as u64).unwrap();
+        }
+        buffer.write_u32::<LittleEndian>(self.boolean_projections.len() as u32).unwrap();
+        for &position in &self.boolean_projections {
+            buffer.write_u64::<LittleEndian>(position as u64).unwrap();
+        }
+        buffer.write_u32::<LittleEndian>(self.aggregations.len() as u32).unwrap();
+        for &(column, aggregator) in &self.aggregations {
+            buffer.write_u64::<LittleEndian>(column as u64).unwrap();
+            write_aggregator(&mut buffer, aggregator);
+        }
+        buffer.write_u32::<LittleEndian>(self.order_by.len() as u32).unwrap();
+        for &(column, desc, nulls) in &self.order_by {
+            buffer.write_u64::<LittleEndian>(column as u64).unwrap();
+            buffer.write_u8(desc as u8).unwrap();
+            buffer.write_u8((nulls == NullsOrder::First) as u8).unwrap();
+        }
+        buffer.write_u32::<LittleEndian>(self.columns.len() as u32).unwrap();
+        for column in &self.columns {
+            write_column(&mut buffer, &**column);
+        }
+        buffer
+    }
+
+    /// Reads a batch result written by `to_bytes`. String values reference `bytes` instead of being copied.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<BatchResult<'a>, QueryError> {
+        read_batch_result(bytes).map_err(|err| fatal!("Failed to deserialize batch result: {}", err))
+    }
+}
+
+fn read_batch_result(mut buffer: &[u8]) -> io::Result<BatchResult> {
+    let mut magic = [0; 8];
+    buffer.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("wrong magic bytes"));
+    }
+    let level = buffer.read_u32::<LittleEndian>()?;
+    let batch_count = buffer.read_u64::<LittleEndian>()? as usize;
+    let show = buffer.read_u8()? == 1;
+    let truncated = buffer.read_u8()? == 1;
+    let projection_count = buffer.read_u32::<LittleEndian>()? as usize;
+    let mut projection = Vec::with_capacity(min(projection_count, buffer.len()));
+    for _ in 0..projection_count {
+        projection.push(buffer.read_u64::<LittleEndian>()? as usize);
+    }
+    let boolean_projection_count = buffer.read_u32::<LittleEndian>()? as usize;
+    let mut boolean_projections = Vec::with_capacity(min(boolean_projection_count, buffer.len()));
+    for _ in 0..boolean_projection_count {
+        let position = buffer.read_u64::<LittleEndian>()? as usize;
+        if position >= projection.len() {
+            return Err(invalid_data(&format!("boolean projection {} out of bounds", position)));
+        }
+        boolean_projections.push(position);
+    }
+    let aggregation_count = buffer.read_u32::<LittleEndian>()? as usize;
+    let mut aggregations = Vec::with_capacity(min(aggregation_count, buffer.len()));
+    for _ in 0..aggregation_count {
+        let column = buffer.read_u64::<LittleEndian>()? as usize;
+        aggregations.push((column, read_aggregator(&mut buffer)?));
+    }
+    let order_by_count = buffer.read_u32::<LittleEndian>()? as usize;
+    let mut order_by = Vec::with_capacity(min(order_by_count, buffer.len()));
+    for _ in 0..order_by_count {
+        let column = buffer.read_u64::<LittleEndian>()? as usize;
+        let desc = buffer.read_u8()? == 1;
+        let nulls = if buffer.read_u8()? == 1 { NullsOrder::First } else { NullsOrder::Last };
+        order_by.push((column, desc, nulls));
+    }
+    let column_count = buffer.read_u32::<LittleEndian>()? as usize;
+    let mut columns = Vec::with_capacity(min(column_count, buffer.len()));
+    for _ in 0..column_count {
+        columns.push(read_column(&mut buffer)?);
+    }
+    if !buffer.is_empty() {
+        return Err(invalid_data(&format!("{} trailing bytes", buffer.len())));
+    }
+    let batch = BatchResult {
+        columns,
+        projection,
+        boolean_projections,
+        aggregations,
+        order_by,
+        level,
+        batch_count,
+        show,
+        truncated,
+        unsafe_referenced_buffers: vec![],
+    };
+    batch.validate().map_err(|err| invalid_data(&err.to_string()))?;
+    for &column in batch.projection.iter().chain(batch.order_by.iter().map(|(column, _, _)| column)) {
+        if column >= batch.columns.len() {
+            return Err(invalid_data(&format!("column index {} out of bounds", column)));
+        }
+    }
+    Ok(batch)
+}
+
+fn write_aggregator(buffer: &mut Vec<u8>, aggregator: Aggregator) {
+    let (tag, precision) = match aggregator {
+        Aggregator::Sum => (0, 0),
+        Aggregator::SaturatingSum => (1, 0),
+        Aggregator::WrappingSum => (2, 0),
+        Aggregator::Count => (3, 0),
+        Aggregator::Max => (4, 0),
+        Aggregator::Min => (5, 0),
+        Aggregator::ApproxCountDistinct(precision) => (6, precision),
+    };
+    buffer.write_u8(tag).unwrap();
+    buffer.write_u8(precision).unwrap();
+}
+
+fn read_aggregator(buffer: &mut &[u8]) -> io::Result<Aggregator> {
+    let tag = buffer.read_u8()?;
+    let precision = buffer.read_u8()?;
+    Ok(match tag {
+        0 => Aggregator::Sum,
+        1 => Aggregator::SaturatingSum,
+        2 => Aggregator::WrappingSum,
+        3 => Aggregator::Count,
+        4 => Aggregator::Max,
+        5 => Aggregator::Min,
+        6 => Aggregator::ApproxCountDistinct(precision),
+        _ => return Err(invalid_data(&format!("unknown aggregator {}", tag))),
+    })
+}
+
+/// Encoding type tags and the layout of the values that follow the number of rows:
+/// - 0 `I64`: one i64 per row
+/// - 1 `Str`: one string per row, see `write_str`
+/// - 2 `NullableI64`, 3 `NullableStr`: the null map as u64 length followed by its bytes, then the values as for `I64`
+///   and `Str`
+/// - 4 `Val`: one value per row, each a u8 tag (0 null, 1 bool, 2 integer, 3 string) followed by the value
+/// - 5 `Null`: no values
+/// - 6 `HyperLogLog`: the precision as u8 followed by the `2^precision` registers of each sketch
+/// - 7 `I128`: one i128 per row, each as its lower and upper 64 bits
+///
+/// Tags of existing types must not change, new ones get the next unused tag.
+fn write_column(buffer: &mut Vec<u8>, data: &Data) {
+    let len = data.len();
+    match data.get_type() {
+        EncodingType::I64 => {
+            buffer.write_u8(0).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            write_i64s(buffer, data.cast_ref_i64());
+        }
+        EncodingType::Str => {
+            buffer.write_u8(1).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            write_strs(buffer, data.cast_ref_str());
+        }
+        EncodingType::NullableI64 => {
+            buffer.write_u8(2).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            write_null_map(buffer, data.cast_ref_null_map());
+            write_i64s(buffer, data.cast_ref_i64());
+        }
+        EncodingType::NullableStr => {
+            buffer.write_u8(3).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            write_null_map(buffer, data.cast_ref_null_map());
+            write_strs(buffer, data.cast_ref_str());
+        }
+        EncodingType::Null => {
+            buffer.write_u8(5).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+        }
+        EncodingType::HyperLogLog => {
+            let hlls = data.cast_ref_hyperloglogs();
+            buffer.write_u8(6).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            buffer.write_u8(hlls.precision as u8).unwrap();
+            for sketch in &hlls.sketches {
+                buffer.extend_from_slice(sketch.registers());
+            }
+        }
+        EncodingType::I128 => {
+            buffer.write_u8(7).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            for &value in data.cast_ref_i128() {
+                buffer.write_u64::<LittleEndian>(value as u64).unwrap();
+                buffer.write_i64::<LittleEndian>((value >> 64) as i64).unwrap();
+            }
+        }
+        EncodingType::Val => {
+            buffer.write_u8(4).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            for val in data.cast_ref_mixed() {
+                match *val {
+                    Val::Null => buffer.write_u8(0).unwrap(),
+                    Val::Bool(b) => {
+                        buffer.write_u8(1).unwrap();
+                        buffer.write_u8(b as u8).unwrap();
+                    }
+                    Val::Integer(i) => {
+                        buffer.write_u8(2).unwrap();
+                        buffer.write_i64::<LittleEndian>(i).unwrap();
+                    }
+                    Val::Str(s) => {
+                        buffer.write_u8(3).unwrap();
+                        write_str(buffer, s);
+                    }
+                }
+            }
+        }
+        _ => {
+            buffer.write_u8(4).unwrap();
+            buffer.write_u64::<LittleEndian>(len as u64).unwrap();
+            for i in 0..len {
+                match data.get_raw(i) {
+                    RawVal::Null => buffer.write_u8(0).unwrap(),
+                    RawVal::Int(x) => {
+                        buffer.write_u8(2).unwrap();
+                        buffer.write_i64::<LittleEndian>(x).unwrap();
+                    }
+                    RawVal::Str(s) => {
+                        buffer.write_u8(3).unwrap();
+                        write_str(buffer, &s);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn read_column<'a>(buffer: &mut &'a [u8]) -> io::Result<BoxedData<'a>> {
+    let tag = buffer.read_u8()?;
+    let len = buffer.read_u64::<LittleEndian>()? as usize;
+    let column: BoxedData<'a> = match tag {
+        0 => Data::owned(read_i64s(buffer, len)?),
+        1 => Data::owned(read_strs(buffer, len)?),
+        2 => {
+            let present = read_null_map(buffer)?;
+            Box::new(NullableVec { data: read_i64s(buffer, len)?, present })
+        }
+        3 => {
+            let present = read_null_map(buffer)?;
+            Box::new(NullableVec { data: read_strs(buffer, len)?, present })
+        }
+        4 => {
+            let mut vals = Vec::with_capacity(min(len, buffer.len()));
+            for _ in 0..len {
+                vals.push(match buffer.read_u8()? {
+                    0 => Val::Null,
+                    1 => Val::Bool(buffer.read_u8()? == 1),
+                    2 => Val::Integer(buffer.read_i64::<LittleEndian>()?),
+                    3 => Val::Str(read_str(buffer)?),
+                    tag => return Err(invalid_data(&format!("unknown value {}", tag))),
+                });
+            }
+            Data::owned(vals)
+        }
+        5 => Data::empty(len),
+        6 => {
+            let precision = u32::from(buffer.read_u8()?);
+            if precision < hyperloglog::MIN_PRECISION || precision > hyperloglog::MAX_PRECISION {
+                return Err(invalid_data(&format!("unsupported HyperLogLog precision {}", precision)));
+            }
+            let mut hlls = HyperLogLogs::new(precision);
+            for _ in 0..len {
+                let registers = read_bytes(buffer, 1 << precision)?.to_vec();
+                hlls.sketches.push(HyperLogLog::from_registers(registers, precision));
+            }
+            Box::new(hlls)
+        }
+        7 => {
+            let mut values = Vec::with_capacity(min(len, buffer.len() / 16));
+            for _ in 0..len {
+                let lower = buffer.read_u64::<LittleEndian>()?;
+                let upper = buffer.read_i64::<LittleEndian>()?;
+                values.push(i128::from(upper) << 64 | i128::from(lower));
+            }
+            Data::owned(values)
+        }
+        _ => return Err(invalid_data(&format!("unknown encoding type {}", tag))),
+    };
+    Ok(column)
+}
+
+fn write_i64s(buffer: &mut Vec<u8>, values: &[i64]) {
+    for &value in values {
+        buffer.write_i64::<LittleEndian>(value).unwrap();
+    }
+}
+
+fn read_i64s(buffer: &mut &[u8], len: usize) -> io::Result<Vec<i64>> {
+    let mut values = Vec::with_capacity(min(len, buffer.len() / 8));
+    for _ in 0..len {
+        values.push(buffer.read_i64::<LittleEndian>()?);
+    }
+    Ok(values)
+}
+
+fn write_strs(buffer: &mut Vec<u8>, values: &[&str]) {
+    for value in values {
+        write_str(buffer, value);
+    }
+}
+
+fn read_strs<'a>(buffer: &mut &'a [u8], len: usize) -> io::Result<Vec<&'a str>> {
+    let mut values = Vec::with_capacity(min(len, buffer.len()));
+    for _ in 0..len {
+        values.push(read_str(buffer)?);
+    }
+    Ok(values)
+}
+
+/// Strings are stored as their length as u32 followed by their UTF-8 bytes.
+fn write_str(buffer: &mut Vec<u8>, s: &str) {
+    buffer.write_u32::<LittleEndian>(s.len() as u32).unwrap();
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+fn read_str<'a>(buffer: &mut &'a [u8]) -> io::Result<&'a str> {
+    let len = buffer.read_u32::<LittleEndian>()? as usize;
+    str::from_utf8(read_bytes(buffer, len)?).map_err(|_| invalid_data("string is not valid UTF-8"))
+}
+
+fn write_null_map(buffer: &mut Vec<u8>, present: &[u8]) {
+    buffer.write_u64::<LittleEndian>(present.len() as u64).unwrap();
+    buffer.extend_from_slice(present);
+}
+
+fn read_null_map(buffer: &mut &[u8]) -> io::Result<Vec<u8>> {
+    let len = buffer.read_u64::<LittleEndian>()? as usize;
+    Ok(read_bytes(buffer, len)?.to_vec())
+}
+
+fn read_bytes<'a>(buffer: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if buffer.len() < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer"));
+    }
+    let (bytes, rest) = buffer.split_at(len);
+    *buffer = rest;
+    Ok(bytes)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch_result<'a>(strs: &[&'a str]) -> BatchResult<'a> {
+        let mut hlls = HyperLogLogs::new(4);
+        hlls.resize(3);
+        hlls.sketches[1].insert_int(7);
+        BatchResult {
+            columns: vec![
+                Data::owned(strs.to_vec()),
+                Data::owned(vec![1i64, -2, i64::max_value()]),
+                Box::new(NullableVec { data: vec![4i64, 0, -1], present: vec![0b101] }) as BoxedData,
+                Data::owned(vec![Val::Null, Val::Integer(3), Val::Str("mixed")]),
+                Data::empty(3),
+                Box::new(hlls) as BoxedData,
+                Data::owned(vec![Some("x"), None, Some("y")]),
+                Data::owned(vec![-1i128, i128::from(i64::max_value()) * 3, i128::from(i64::min_value()) * 5]),
+            ],
+            projection: vec![0, 0, 3],
+            boolean_projections: vec![1],
+            aggregations: vec![(7, Aggregator::Sum), (1, Aggregator::Max), (5, Aggregator::ApproxCountDistinct(4))],
+            order_by: vec![(2, true, NullsOrder::Last)],
+            level: 3,
+            batch_count: 8,
+            show: false,
+            truncated: true,
+            unsafe_referenced_buffers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let batch = batch_result(&["a", "", "ünïcödé"]);
+        let bytes = batch.to_bytes();
+        let restored = BatchResult::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.projection, batch.projection);
+        assert_eq!(restored.boolean_projections, vec![1]);
+        assert_eq!(restored.aggregations, batch.aggregations);
+        assert_eq!(restored.order_by, batch.order_by);
+        assert_eq!((restored.level, restored.batch_count), (3, 8));
+        assert!(!restored.show && restored.truncated);
+        for (restored, original) in restored.columns.iter().zip(batch.columns.iter()).take(6) {
+            assert_eq!(restored.get_type(), original.get_type());
+            assert_eq!((0..restored.len()).map(|i| restored.get_raw(i)).collect::<Vec<_>>(),
+                       (0..original.len()).map(|i| original.get_raw(i)).collect::<Vec<_>>());
+        }
+        assert_eq!(restored.columns[5].cast_ref_hyperloglogs().sketches, batch.columns[5].cast_ref_hyperloglogs().sketches);
+        // Optional strings have no tag of their own and are read back as mixed values
+        assert_eq!(restored.columns[6].get_type(), EncodingType::Val);
+        assert_eq!(restored.columns[6].cast_ref_mixed(), &[Val::Str("x"), Val::Null, Val::Str("y")]);
+        assert_eq!(restored.columns[7].cast_ref_i128(), batch.columns[7].cast_ref_i128());
+    }
+
+    #[test]
+    fn test_invalid_bytes() {
+        let bytes = batch_result(&["a", "b", "c"]).to_bytes();
+        assert!(BatchResult::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let trailing = [bytes.clone(), vec![0]].concat();
+        assert!(BatchResult::from_bytes(&trailing).is_err());
+        assert!(BatchResult::from_bytes(&bytes[1..]).is_err());
+        assert!(BatchResult::from_bytes(&[]).is_err());
+    }
+}
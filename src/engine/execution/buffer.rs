@@ -45,6 +45,7 @@ impl BufferRef<Any> {
     pub fn raw_val(self) -> BufferRef<RawVal> { self.transmute() }
     pub fn i64(self) -> BufferRef<i64> { self.transmute() }
     pub fn u64(self) -> BufferRef<u64> { self.transmute() }
+    pub fn i128(self) -> BufferRef<i128> { self.transmute() }
     pub fn u32(self) -> BufferRef<u32> { self.transmute() }
     pub fn u16(self) -> BufferRef<u16> { self.transmute() }
     pub fn u8(self) -> BufferRef<u8> { self.transmute() }
@@ -62,6 +63,7 @@ impl BufferRef<Any> {
     pub fn scalar_string<'a>(self) -> BufferRef<Scalar<String>> { self.transmute() }
 
     pub fn val_rows<'a>(self) -> BufferRef<ValRows<'a>> { self.transmute() }
+    pub fn hyperloglogs(self) -> BufferRef<HyperLogLogs> { self.transmute() }
     pub fn val<'a>(self) -> BufferRef<Val<'a>> { self.transmute() }
 
     pub fn string(self) -> BufferRef<String> { self.transmute() }
@@ -75,6 +77,10 @@ impl From<TypedBufferRef> for BufferRef<u32> {
     fn from(buffer: TypedBufferRef) -> BufferRef<u32> { buffer.u32().unwrap() }
 }
 
+impl From<TypedBufferRef> for BufferRef<u64> {
+    fn from(buffer: TypedBufferRef) -> BufferRef<u64> { buffer.u64().unwrap() }
+}
+
 impl From<TypedBufferRef> for BufferRef<i64> {
     fn from(buffer: TypedBufferRef) -> BufferRef<i64> { buffer.i64().unwrap() }
 }
@@ -92,6 +98,12 @@ impl From<BufferRef<u32>> for TypedBufferRef {
     }
 }
 
+impl From<BufferRef<u64>> for TypedBufferRef {
+    fn from(buffer: BufferRef<u64>) -> TypedBufferRef {
+        TypedBufferRef::new(buffer.any(), EncodingType::U64)
+    }
+}
+
 impl From<BufferRef<u8>> for TypedBufferRef {
     fn from(buffer: BufferRef<u8>) -> TypedBufferRef {
         TypedBufferRef::new(buffer.any(), EncodingType::U8)
@@ -227,6 +239,11 @@ impl TypedBufferRef {
         Ok(self.buffer.u64())
     }
 
+    pub fn i128(&self) -> Result<BufferRef<i128>, QueryError> {
+        ensure!(self.tag == EncodingType::I128, "{:?} != I128", self.tag);
+        Ok(self.buffer.i128())
+    }
+
     pub fn u32(&self) -> Result<BufferRef<u32>, QueryError> {
         ensure!(self.tag == EncodingType::U32, "{:?} != U32", self.tag);
         Ok(self.buffer.u32())
@@ -292,6 +309,11 @@ impl TypedBufferRef {
         Ok(self.buffer.val_rows())
     }
 
+    pub fn hyperloglogs(&self) -> Result<BufferRef<HyperLogLogs>, QueryError> {
+        ensure!(self.tag == EncodingType::HyperLogLog, "{:?} != HyperLogLog", self.tag);
+        Ok(self.buffer.hyperloglogs())
+    }
+
     pub fn val<'a>(&self) -> Result<BufferRef<Val<'a>>, QueryError> {
         ensure!(self.tag == EncodingType::Val, "{:?} != Val", self.tag);
         Ok(self.buffer.val())
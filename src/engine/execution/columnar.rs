@@ -0,0 +1,126 @@
+use engine::data_types::BasicType;
+use engine::execution::query_task::QueryOutput;
+use ingest::raw_val::RawVal;
+
+
+/// Query result stored by column rather than by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnarResult {
+    columns: Vec<ResultColumn>,
+    len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultColumn {
+    pub name: String,
+    /// Type of the result column as given by `QueryOutput::coltypes`.
+    pub basic_type: BasicType,
+    pub values: Vec<RawVal>,
+}
+
+impl QueryOutput {
+    /// Converts the result rows into one column per result column name.
+    pub fn into_columnar(self) -> ColumnarResult {
+        let len = self.rows.len();
+        let mut columns = self.colnames.into_iter().zip(self.coltypes)
+            .map(|(name, basic_type)| ResultColumn { name, basic_type, values: Vec::with_capacity(len) })
+            .collect::<Vec<_>>();
+        for row in self.rows {
+            for (column, value) in columns.iter_mut().zip(row) {
+                column.values.push(value);
+            }
+        }
+        ColumnarResult { columns, len }
+    }
+}
+
+impl ColumnarResult {
+    /// Number of rows.
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    pub fn columns(&self) -> &[ResultColumn] { &self.columns }
+
+    /// Returns the column with the given result column name, or `None` if there is no such column.
+    pub fn column(&self, name: &str) -> Option<&ResultColumn> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    pub fn colnames(&self) -> Vec<&str> {
+        self.columns.iter().map(|column| column.name.as_str()).collect()
+    }
+
+    /// Returns the values of row `index` in column order, panics if `index >= self.len()`.
+    pub fn row(&self, index: usize) -> Vec<RawVal> {
+        assert!(index < self.len, "Row {} out of bounds for result with {} rows", index, self.len);
+        self.columns.iter().map(|column| column.values[index].clone()).collect()
+    }
+
+    pub fn rows<'a>(&'a self) -> impl Iterator<Item=Vec<RawVal>> + 'a {
+        (0..self.len).map(move |index| self.row(index))
+    }
+
+    pub fn into_rows(self) -> Vec<Vec<RawVal>> {
+        let mut rows = (0..self.len).map(|_| Vec::with_capacity(self.columns.len())).collect::<Vec<_>>();
+        for column in self.columns {
+            for (row, value) in rows.iter_mut().zip(column.values) {
+                row.push(value);
+            }
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use engine::execution::query_task::QueryStats;
+
+    #[test]
+    fn test_into_columnar() {
+        let rows = vec![
+            vec![RawVal::Str("a".to_string()), RawVal::Int(3), RawVal::Null, RawVal::Int(1)],
+            vec![RawVal::Null, RawVal::Int(-1), RawVal::Null, RawVal::Str("b".to_string())],
+        ];
+        let output = QueryOutput {
+            colnames: vec!["name".to_string(), "count".to_string(), "nothing".to_string(), "mixed".to_string()],
+            coltypes: vec![BasicType::NullableString, BasicType::Integer, BasicType::Null, BasicType::Val],
+            rows: rows.clone(),
+            query_plans: HashMap::default(),
+            stats: QueryStats::default(),
+            truncated: false,
+        };
+        let result = output.into_columnar();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.colnames(), vec!["name", "count", "nothing", "mixed"]);
+        assert_eq!(result.columns().iter().map(|column| column.basic_type).collect::<Vec<_>>(),
+                   vec![BasicType::NullableString, BasicType::Integer, BasicType::Null, BasicType::Val]);
+        assert_eq!(result.column("count").unwrap().values, vec![RawVal::Int(3), RawVal::Int(-1)]);
+        assert!(result.column("missing").is_none());
+        assert_eq!(result.row(1), rows[1]);
+        assert_eq!(result.rows().collect::<Vec<_>>(), rows);
+        assert_eq!(result.into_rows(), rows);
+    }
+
+    #[test]
+    fn test_into_columnar_keeps_coltypes() {
+        let output = |rows: Vec<Vec<RawVal>>| QueryOutput {
+            colnames: vec!["is_error".to_string(), "name".to_string()],
+            coltypes: vec![BasicType::Boolean, BasicType::String],
+            rows,
+            query_plans: HashMap::default(),
+            stats: QueryStats::default(),
+            truncated: false,
+        };
+        let result = output(vec![vec![RawVal::Int(1), RawVal::Str("a".to_string())]]).into_columnar();
+        assert_eq!(result.columns().iter().map(|column| column.basic_type).collect::<Vec<_>>(),
+                   vec![BasicType::Boolean, BasicType::String]);
+        let result = output(vec![]).into_columnar();
+        assert!(result.is_empty());
+        assert_eq!(result.colnames(), vec!["is_error", "name"]);
+        assert_eq!(result.columns().iter().map(|column| column.basic_type).collect::<Vec<_>>(),
+                   vec![BasicType::Boolean, BasicType::String]);
+    }
+}
@@ -0,0 +1,51 @@
+extern crate csv;
+
+use std::io::{self, Write};
+
+use engine::execution::query_task::QueryOutput;
+use ingest::raw_val::RawVal;
+
+
+impl QueryOutput {
+    /// Writes the result as CSV with a header row of the column names.
+    /// Fields that contain delimiters, quotes or line breaks are quoted and null values are written as empty fields.
+    pub fn to_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(&self.colnames)?;
+        for row in &self.rows {
+            writer.write_record(row.iter().map(|value| match value {
+                RawVal::Int(i) => i.to_string(),
+                RawVal::Str(s) => s.to_string(),
+                RawVal::Null => String::new(),
+            }))?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use engine::data_types::BasicType;
+    use engine::execution::query_task::QueryStats;
+
+    #[test]
+    fn test_to_csv() {
+        let output = QueryOutput {
+            colnames: vec!["name".to_string(), "count".to_string()],
+            coltypes: vec![BasicType::String, BasicType::Integer],
+            rows: vec![
+                vec![RawVal::Str("a, \"b\"".to_string()), RawVal::Int(3)],
+                vec![RawVal::Null, RawVal::Int(-1)],
+                vec![RawVal::Str("line\nbreak".to_string()), RawVal::Null],
+            ],
+            query_plans: HashMap::default(),
+            stats: QueryStats::default(),
+            truncated: false,
+        };
+        let mut csv = Vec::new();
+        output.to_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "name,count\n\"a, \"\"b\"\"\",3\n,-1\n\"line\nbreak\",\n");
+    }
+}
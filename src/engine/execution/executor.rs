@@ -1,14 +1,24 @@
 use bitvec::BitVec;
 use engine::*;
+use engine::execution::query_task::QueryStats;
 use ingest::raw_val::RawVal;
 use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::marker::PhantomData;
+use time::precise_time_ns;
+
+/// Number of rows that streaming stages process at a time unless set with `QueryExecutor::set_batch_size`.
+pub const DEFAULT_BATCH_SIZE: usize = 1024;
 
 pub struct QueryExecutor<'a> {
     ops: Vec<Box<VecOperator<'a> + 'a>>,
     stages: Vec<ExecutorStage>,
+    stage_durations_ns: Vec<u64>,
+    memory_limit: Option<usize>,
+    batch_size: usize,
+    chunks_processed: usize,
+    peak_memory_bytes: usize,
     count: usize,
     last_buffer: TypedBufferRef,
     shared_buffers: HashMap<&'static str, TypedBufferRef>,
@@ -105,12 +115,42 @@ impl<'a> QueryExecutor<'a> {
     }
 
     pub fn run(&mut self, len: usize, scratchpad: &mut Scratchpad<'a>, show: bool) -> Result<(), QueryError> {
+        self.stage_durations_ns.clear();
+        self.chunks_processed = 0;
         for stage in 0..self.stages.len() {
+            let start_time_ns = precise_time_ns();
             self.run_stage(len, stage, scratchpad, show)?;
+            self.stage_durations_ns.push(precise_time_ns() - start_time_ns);
         }
         Ok(())
     }
 
+    /// Aborts `run` with `QueryError::MemoryLimitExceeded` once buffers and operators such as hash tables of groups
+    /// allocate more than `limit` bytes. Memory is checked after each batch of streaming stages.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) { self.memory_limit = limit; }
+
+    /// Sets the number of rows that streaming stages process at a time, which is rounded up to a multiple of 8 so that
+    /// the null maps of batches start at byte boundaries. Results don't depend on the batch size.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = cmp::max(8, (batch_size + 7) / 8 * 8);
+    }
+
+    /// Number of batches processed by the stages of the last `run`, non-streaming stages process a single batch.
+    pub fn chunks_processed(&self) -> usize { self.chunks_processed }
+
+    /// Largest total size of buffers observed after any stage.
+    pub fn peak_memory_bytes(&self) -> usize { self.peak_memory_bytes }
+
+    /// Records the wall-clock time of each stage of the last `run` in `stats`.
+    pub fn record_stage_durations(&self, stats: &mut QueryStats) {
+        for (i, &duration_ns) in self.stage_durations_ns.iter().enumerate() {
+            let ops = self.stages[i].ops.iter()
+                .map(|&(op, _)| self.ops[op].display(false))
+                .collect::<Vec<_>>();
+            stats.record(&format!("Stage {}: {}", i, ops.join(", ")), duration_ns);
+        }
+    }
+
     #[allow(clippy::cyclomatic_complexity)]
     fn partition(&self) -> Vec<ExecutorStage> {
         // Construct execution graph
@@ -417,7 +457,7 @@ impl<'a> QueryExecutor<'a> {
             max_input_length = column_length;
         }
         let batch_size = if self.stages[stage].stream {
-            1024
+            self.batch_size
         } else {
             max_input_length
         };
@@ -463,15 +503,28 @@ impl<'a> QueryExecutor<'a> {
                 has_more |= self.ops[op].has_more() && stream;
             }
             iters += 1;
+            self.check_memory(scratchpad)?;
         }
         for &(op, _) in &self.stages[stage].ops {
-            self.ops[op].finalize(scratchpad);
+            self.ops[op].finalize(scratchpad)?;
         }
+        self.check_memory(scratchpad)?;
+        self.chunks_processed += iters;
         if show && iters > 1 {
             println!("\n[{} more iterations]", iters - 1);
         }
         Ok(())
     }
+
+    fn check_memory(&mut self, scratchpad: &Scratchpad<'a>) -> Result<(), QueryError> {
+        let memory_bytes = scratchpad.heap_size_of_buffers()
+            + self.ops.iter().map(|op| op.heap_size_of_state()).sum::<usize>();
+        self.peak_memory_bytes = cmp::max(self.peak_memory_bytes, memory_bytes);
+        match self.memory_limit {
+            Some(limit) if memory_bytes > limit => Err(QueryError::MemoryLimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl<'a> Default for QueryExecutor<'a> {
@@ -479,6 +532,11 @@ impl<'a> Default for QueryExecutor<'a> {
         QueryExecutor {
             ops: vec![],
             stages: vec![],
+            stage_durations_ns: vec![],
+            memory_limit: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            chunks_processed: 0,
+            peak_memory_bytes: 0,
             count: 0,
             last_buffer: TypedBufferRef::new(error_buffer_ref("ERROR"), EncodingType::Null),
             shared_buffers: HashMap::default(),
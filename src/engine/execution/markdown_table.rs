@@ -0,0 +1,119 @@
+use std::cmp;
+
+use engine::execution::query_task::QueryOutput;
+use ingest::raw_val::RawVal;
+
+/// Maximum number of characters of string values in markdown tables, longer strings are truncated.
+pub const MAX_STRING_WIDTH: usize = 40;
+
+impl QueryOutput {
+    /// Formats the result with `markdown_table`.
+    pub fn to_markdown_table(&self) -> String {
+        markdown_table(&self.colnames, &self.rows)
+    }
+}
+
+/// Formats `rows` as a Markdown pipe table with a header row of the column names `colnames`.
+/// Integers are right-aligned and strings left-aligned, strings longer than `MAX_STRING_WIDTH` characters
+/// are truncated and end in `…`.
+pub fn markdown_table(colnames: &[String], rows: &[Vec<RawVal>]) -> String {
+    let header = colnames.iter().map(|name| escape(name)).collect::<Vec<_>>();
+    let cells = rows.iter()
+        .map(|row| row.iter()
+            .map(|value| match value {
+                RawVal::Int(i) => (i.to_string(), true),
+                RawVal::Str(s) => (escape(&truncate(s)), false),
+                RawVal::Null => ("null".to_string(), false),
+            })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let numeric = (0..header.len())
+        .map(|i| rows.iter().all(|row| match row[i] { RawVal::Str(_) => false, _ => true }) &&
+            rows.iter().any(|row| match row[i] { RawVal::Int(_) => true, _ => false }))
+        .collect::<Vec<_>>();
+    // Separator rows need at least three dashes
+    let widths = header.iter().enumerate()
+        .map(|(i, name)| cells.iter()
+            .map(|row| row[i].0.chars().count())
+            .fold(cmp::max(3, name.chars().count()), cmp::max))
+        .collect::<Vec<_>>();
+
+    let mut table = String::new();
+    write_row(&mut table, header.iter().zip(&numeric).map(|(name, &right)| (name.as_str(), right)), &widths);
+    table.push('|');
+    for (&width, &right) in widths.iter().zip(&numeric) {
+        if right {
+            table.push_str(&format!(" {}: |", "-".repeat(width - 1)));
+        } else {
+            table.push_str(&format!(" :{} |", "-".repeat(width - 1)));
+        }
+    }
+    table.push('\n');
+    for row in &cells {
+        write_row(&mut table, row.iter().map(|(cell, right)| (cell.as_str(), *right)), &widths);
+    }
+    table
+}
+
+fn write_row<'a>(table: &mut String, cells: impl Iterator<Item=(&'a str, bool)>, widths: &[usize]) {
+    table.push('|');
+    for ((cell, right), &width) in cells.zip(widths) {
+        let padding = " ".repeat(width - cell.chars().count());
+        if right {
+            table.push_str(&format!(" {}{} |", padding, cell));
+        } else {
+            table.push_str(&format!(" {}{} |", cell, padding));
+        }
+    }
+    table.push('\n');
+}
+
+fn truncate(s: &str) -> String {
+    if s.chars().count() > MAX_STRING_WIDTH {
+        let mut truncated = s.chars().take(MAX_STRING_WIDTH - 1).collect::<String>();
+        truncated.push('…');
+        truncated
+    } else {
+        s.to_string()
+    }
+}
+
+/// Escapes characters that would end the cell or the row of the table.
+fn escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use engine::data_types::BasicType;
+    use engine::execution::query_task::QueryStats;
+
+    #[test]
+    fn test_to_markdown_table() {
+        let output = QueryOutput {
+            colnames: vec!["name".to_string(), "count".to_string()],
+            coltypes: vec![BasicType::String, BasicType::Integer],
+            rows: vec![
+                vec![RawVal::Str("a|b".to_string()), RawVal::Int(3)],
+                vec![RawVal::Null, RawVal::Int(-1000)],
+                vec![RawVal::Str("x".repeat(50)), RawVal::Null],
+            ],
+            query_plans: HashMap::default(),
+            stats: QueryStats::default(),
+            truncated: false,
+        };
+        let long = format!("{}…", "x".repeat(MAX_STRING_WIDTH - 1));
+        let expected = format!(
+            "| name{pad} | count |\n\
+             | :{dashes} | ----: |\n\
+             | a\\|b{pad} |     3 |\n\
+             | null{pad} | -1000 |\n\
+             | {long} | null  |\n",
+            pad = " ".repeat(MAX_STRING_WIDTH - 4),
+            dashes = "-".repeat(MAX_STRING_WIDTH - 1),
+            long = long);
+        assert_eq!(output.to_markdown_table(), expected);
+    }
+}
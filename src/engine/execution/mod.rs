@@ -1,10 +1,20 @@
 pub mod query_task;
+pub mod update;
+pub mod union_all;
 mod buffer;
 mod executor;
 mod batch_merging;
+mod batch_serialization;
+mod columnar;
+mod csv_export;
+mod markdown_table;
 mod scratchpad;
+#[cfg(feature = "enable_arrow")]
+mod arrow_export;
 
 pub use self::buffer::*;
 pub use self::scratchpad::*;
 pub use self::executor::*;
-pub use self::batch_merging::{BatchResult, combine};
\ No newline at end of file
+pub use self::batch_merging::{BatchResult, combine, combine_all, merge_incremental};
+pub use self::columnar::{ColumnarResult, ResultColumn};
+pub use self::markdown_table::markdown_table;
\ No newline at end of file
@@ -6,12 +6,16 @@ use std::mem;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering};
+use std::time::Instant;
 
 use ::QueryError;
 use QueryResult;
 use engine::*;
+use hyperloglog::HyperLogLog;
 use ingest::raw_val::RawVal;
-use mem_store::partition::Partition;
+use mem_store::partition::{Partition, ROWNUM_COLUMN};
+use mem_store::batch_metadata::ColumnBounds;
+use mem_store::column_aliases::ColumnAliases;
 use mem_store::column::DataSource;
 use scheduler::*;
 use scheduler::disk_read_scheduler::DiskReadScheduler;
@@ -25,9 +29,15 @@ pub struct QueryTask {
     explain: bool,
     show: Vec<usize>,
     partitions: Vec<Arc<Partition>>,
+    /// Row number of the first row of each partition.
+    row_offsets: Vec<usize>,
+    /// Names under which the referenced columns are stored.
     referenced_cols: HashSet<String>,
+    /// Aliases of the referenced columns that have been renamed.
+    column_aliases: ColumnAliases,
     output_colnames: Vec<String>,
     start_time_ns: u64,
+    deadline: Option<Instant>,
     db: Arc<DiskReadScheduler>,
 
     // Lifetime is not actually static, but tied to the lifetime of this struct.
@@ -46,14 +56,45 @@ pub struct QueryState<'a> {
     explains: Vec<String>,
     rows_scanned: usize,
     rows_collected: usize,
+    stats: QueryStats,
     colstacks: Vec<Vec<HashMap<String, Arc<DataSource>>>>,
 }
 
 pub struct QueryOutput {
     pub colnames: Vec<String>,
+    /// Type of the non-null values of each result column according to the query plan, `BasicType::Null` if the type is
+    /// not known because no partition was queried. Values of `BasicType::Boolean` columns are the integers 0 and 1.
+    pub coltypes: Vec<BasicType>,
     pub rows: Vec<Vec<RawVal>>,
     pub query_plans: HashMap<String, u32>,
     pub stats: QueryStats,
+    /// Set if the query timed out and the result only covers part of the table.
+    pub truncated: bool,
+}
+
+impl QueryOutput {
+    /// Converts the result into a JSON object with the result column names `colnames`, the result `rows` and whether
+    /// the result was `truncated` by the timeout. Values of boolean columns are converted to `true` and `false`.
+    #[cfg(feature = "enable_serde")]
+    pub fn to_json(&self) -> ::serde_json::Value {
+        use serde_json::{Map, Value};
+        let rows = self.rows.iter()
+            .map(|row| Value::Array(row.iter()
+                .zip(self.coltypes.iter().chain(::std::iter::repeat(&BasicType::Null)))
+                .map(|(value, coltype)| match value {
+                    RawVal::Int(i) if *coltype == BasicType::Boolean => Value::from(*i != 0),
+                    RawVal::Int(i) => Value::from(*i),
+                    RawVal::Str(s) => Value::from(s.as_str()),
+                    RawVal::Null => Value::Null,
+                })
+                .collect()))
+            .collect();
+        let mut json = Map::new();
+        json.insert("colnames".to_string(), Value::from(self.colnames.clone()));
+        json.insert("rows".to_string(), Value::Array(rows));
+        json.insert("truncated".to_string(), Value::from(self.truncated));
+        Value::Object(json)
+    }
 }
 
 
@@ -61,6 +102,46 @@ pub struct QueryOutput {
 pub struct QueryStats {
     pub runtime_ns: u64,
     pub rows_scanned: usize,
+    /// Lowest precision of any `APPROX_COUNT_DISTINCT` aggregate in the query.
+    pub approx_distinct_precision: Option<u8>,
+    /// Total wall-clock time spent in each plan stage, only collected for `EXPLAIN ANALYZE` queries.
+    pub stages: Vec<(String, u64)>,
+    /// Largest total size of the buffers allocated while executing any one partition.
+    pub peak_memory_bytes: usize,
+    /// Columns whose values had to be decoded in at least one partition.
+    /// Columns that are only compared against constants in the filter are evaluated in encoded form and don't appear here.
+    pub decoded_columns: Vec<String>,
+    /// Number of column sections read, summed over all partitions.
+    /// Expressions that are referenced multiple times only read each section once per partition.
+    pub column_sections_read: usize,
+    /// Number of batches of rows processed by the stages of the query plan, summed over all partitions.
+    /// Depends on `Query::batch_size` for stages that stream their inputs.
+    pub chunks_processed: usize,
+}
+
+impl QueryStats {
+    /// Adds `duration_ns` to the time spent in the plan stage labeled `stage`.
+    pub fn record(&mut self, stage: &str, duration_ns: u64) {
+        let position = self.stages.iter().position(|entry| entry.0 == stage);
+        match position {
+            Some(i) => self.stages[i].1 += duration_ns,
+            None => self.stages.push((stage.to_string(), duration_ns)),
+        }
+    }
+
+    /// Adds `columns` to the set of decoded columns.
+    pub fn record_decoded(&mut self, columns: &[String]) {
+        for column in columns {
+            if !self.decoded_columns.contains(column) {
+                self.decoded_columns.push(column.clone());
+            }
+        }
+    }
+
+    /// Relative standard error of the least accurate approximate distinct count, if any.
+    pub fn approx_distinct_error(&self) -> Option<f64> {
+        self.approx_distinct_precision.map(|p| HyperLogLog::standard_error(u32::from(p)))
+    }
 }
 
 impl Default for QueryStats {
@@ -68,6 +149,12 @@ impl Default for QueryStats {
         QueryStats {
             runtime_ns: 0,
             rows_scanned: 0,
+            approx_distinct_precision: None,
+            stages: Vec::new(),
+            peak_memory_bytes: 0,
+            decoded_columns: Vec::new(),
+            column_sections_read: 0,
+            chunks_processed: 0,
         }
     }
 }
@@ -75,31 +162,74 @@ impl Default for QueryStats {
 
 impl QueryTask {
     pub fn new(mut query: Query, explain: bool, show: Vec<usize>,
+               sum_overflow: OverflowPolicy,
                source: Vec<Arc<Partition>>,
+               column_aliases: ColumnAliases,
                db: Arc<DiskReadScheduler>,
                sender: SharedSender<QueryResult>) -> Result<QueryTask, QueryError> {
         let start_time_ns = precise_time_ns();
         if query.is_select_star() {
-            query.select = find_all_cols(&source).into_iter().map(Expr::ColName).collect();
+            query.select = find_all_cols(&source, &column_aliases).into_iter().map(|col| (Expr::ColName(col), None)).collect();
         }
 
         let referenced_cols = query.find_referenced_cols();
+        ensure_cols_exist(&query, &referenced_cols, &source, &column_aliases)?;
+        for (new, old) in column_aliases.iter() {
+            if referenced_cols.contains(old) {
+                warn!("Query references column {} of table {} which has been renamed to {}", old, query.table, new);
+            }
+        }
+        let column_aliases = column_aliases.restrict(&referenced_cols);
+        let referenced_cols = column_aliases.stored_cols(&referenced_cols);
 
-        let (main_phase, final_pass) = query.normalize()?;
-        let output_colnames = match &final_pass {
-            Some(final_pass) => final_pass.result_column_names(),
-            None => main_phase.result_column_names(),
+        let (mut main_phase, mut final_pass) = query.normalize()?;
+        for aggregate in &mut main_phase.aggregate {
+            aggregate.0 = sum_overflow.apply(aggregate.0);
+        }
+        if let Some(ref mut reservoir) = main_phase.reservoir {
+            reservoir.seed = Some(reservoir.seed.unwrap_or(start_time_ns));
+        }
+        if let Some(ref sample) = main_phase.sample {
+            let table_len = source.iter().map(|p| p.len()).sum();
+            main_phase.sampled_rows = Some(sample.resolve(table_len, start_time_ns));
+        }
+        let mut source = source;
+        let row_offsets = source.iter()
+            .scan(0, |offset, partition| {
+                let start = *offset;
+                *offset += partition.len();
+                Some(start)
+            })
+            .collect::<Vec<_>>();
+        let output_colnames = if query.analyze {
+            vec!["plan".to_string()]
+        } else if query.explain {
+            // The plan differs between partitions only in details, so it is sufficient to explain the first one
+            source.truncate(1);
+            final_pass = None;
+            vec!["plan".to_string()]
+        } else {
+            match &final_pass {
+                Some(final_pass) => final_pass.result_column_names(),
+                None => main_phase.result_column_names(),
+            }
         };
 
-        Ok(QueryTask {
+        let task = QueryTask {
             main_phase,
             final_pass,
             explain,
             show,
+            row_offsets,
             partitions: source,
             referenced_cols,
+            column_aliases,
             output_colnames,
             start_time_ns,
+            // Timeouts beyond the range of `Instant` never expire
+            deadline: query.timeout
+                .filter(|timeout| timeout.as_secs() < u64::from(u32::max_value()))
+                .map(|timeout| Instant::now() + timeout),
             db,
 
             unsafe_state: Mutex::new(QueryState {
@@ -108,12 +238,26 @@ impl QueryTask {
                 explains: Vec::new(),
                 rows_scanned: 0,
                 rows_collected: 0,
+                stats: QueryStats::default(),
                 colstacks: Vec::new(),
             }),
             batch_index: AtomicUsize::new(0),
             completed: AtomicBool::new(false),
             sender,
-        })
+        };
+        // No partition would ever complete, e.g. because `as_of` precedes all partitions
+        if task.partitions.is_empty() {
+            task.completed.store(true, Ordering::SeqCst);
+            task.sender.send(Ok(QueryOutput {
+                colnames: task.output_colnames.clone(),
+                coltypes: vec![BasicType::Null; task.output_colnames.len()],
+                rows: Vec::new(),
+                query_plans: HashMap::default(),
+                stats: QueryStats::default(),
+                truncated: false,
+            }));
+        }
+        Ok(task)
     }
 
     pub fn run(&self) {
@@ -122,19 +266,36 @@ impl QueryTask {
         let mut colstack = Vec::new();
         let mut batch_results = Vec::<BatchResult>::new();
         let mut explains = Vec::new();
+        let mut stats = QueryStats::default();
+        let mut skipped_batches = 0;
+        let mut truncated = false;
         while let Some((partition, id)) = self.next_partition() {
             trace_start!("Batch {}", id);
             let show = self.show.iter().any(|&x| x == id);
-            let cols = partition.get_cols(&self.referenced_cols, &self.db);
+            let mut metadata = partition.metadata();
+            self.column_aliases.resolve(&mut metadata.columns);
+            // The first partition is never skipped to guarantee at least one batch result
+            if id != 0 && self.main_phase.filter_excludes_batch(&metadata, &HashMap::new()) {
+                skipped_batches += 1;
+                continue;
+            }
+            let mut cols = partition.get_cols(&self.referenced_cols, &self.db);
+            self.column_aliases.resolve(&mut cols);
+            // Bounds of columns that were just read from disk and bloom indices are only available after loading
+            metadata.columns.extend(cols.iter().map(|(name, col)| (name.to_string(), ColumnBounds::of(&**col))));
+            if id != 0 && self.main_phase.filter_excludes_batch(&metadata, &cols) {
+                skipped_batches += 1;
+                continue;
+            }
             rows_scanned += cols.iter().next().map_or(0, |c| c.1.len());
             let unsafe_cols = unsafe {
                 mem::transmute::<&HashMap<String, Arc<DataSource>>,
                     &'static HashMap<String, Arc<DataSource>>>(&cols)
             };
-            let (mut batch_result, explain) = match if self.main_phase.aggregate.is_empty() {
-                self.main_phase.run(unsafe_cols, self.explain, show, id, partition.len())
+            let (batch_result, explain) = match if self.main_phase.aggregate.is_empty() {
+                self.main_phase.run(unsafe_cols, self.explain, show, id, partition.len(), self.row_offsets[id], &mut stats)
             } else {
-                self.main_phase.run_aggregate(unsafe_cols, self.explain, show, id, partition.len())
+                self.main_phase.run_aggregate(unsafe_cols, self.explain, show, id, partition.len(), self.row_offsets[id], &mut stats)
             } {
                 Ok(result) => result,
                 Err(error) => {
@@ -148,22 +309,10 @@ impl QueryTask {
                 explains.push(explain);
             }
 
-            // Merge only with previous batch results of same level to get O(n log n) complexity
-            while let Some(br) = batch_results.pop() {
-                if br.level == batch_result.level {
-                    match combine(br, batch_result, self.combined_limit()) {
-                        Ok(result) => batch_result = result,
-                        Err(error) => {
-                            self.fail_with(error);
-                            return;
-                        }
-                    };
-                } else {
-                    batch_results.push(br);
-                    break;
-                }
+            if let Err(error) = merge_incremental(&mut batch_results, batch_result, self.combined_limit()) {
+                self.fail_with(error);
+                return;
             }
-            batch_results.push(batch_result);
 
             if self.completed.load(Ordering::SeqCst) {
                 return;
@@ -171,64 +320,100 @@ impl QueryTask {
             if self.sufficient_rows(rows_collected) {
                 break;
             }
+            if self.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                // Partitions that no thread has started on yet are skipped, at least one partition has been processed
+                let claimed = self.batch_index.swap(self.partitions.len(), Ordering::SeqCst);
+                skipped_batches += self.partitions.len().saturating_sub(claimed);
+                truncated = true;
+                break;
+            }
         }
 
-        match QueryTask::combine_results(batch_results, self.combined_limit()) {
-            Ok(Some(result)) => self.push_result(result, rows_scanned, rows_collected, explains),
+        match combine_all(batch_results, self.combined_limit()) {
+            Ok(None) if skipped_batches == 0 => {}
+            Ok(mut result) => {
+                if let Some(ref mut result) = result {
+                    result.truncated |= truncated;
+                }
+                self.push_result(result, skipped_batches, rows_scanned, rows_collected, explains, stats)
+            }
             Err(error) => self.fail_with(error),
-            _ => {}
         }
         // need to keep colstack alive, otherwise results may reference freed data
         self.push_colstack(colstack);
     }
 
-    fn combine_results(batch_results: Vec<BatchResult>, limit: usize) -> Result<Option<BatchResult>, QueryError> {
-        let mut full_result = None;
-        for batch_result in batch_results {
-            if let Some(partial) = full_result {
-                full_result = Some(combine(partial, batch_result, limit)?);
-            } else {
-                full_result = Some(batch_result);
-            }
-        }
-        Ok(full_result)
-    }
-
-    fn push_result(&self, result: BatchResult, rows_scanned: usize, rows_collected: usize, explains: Vec<String>) {
+    fn push_result(&self,
+                   result: Option<BatchResult>,
+                   skipped_batches: usize,
+                   rows_scanned: usize,
+                   rows_collected: usize,
+                   explains: Vec<String>,
+                   stats: QueryStats) {
         let mut state = self.unsafe_state.lock().unwrap();
         if self.completed.load(Ordering::SeqCst) { return; }
-        state.completed_batches += result.batch_count;
+        state.completed_batches += skipped_batches;
         state.explains.extend(explains);
         state.rows_scanned += rows_scanned;
         state.rows_collected += rows_collected;
-        unsafe {
-            let result = mem::transmute::<_, BatchResult<'static>>(result);
-            state.partial_results.push(result);
+        for (stage, duration_ns) in &stats.stages {
+            state.stats.record(stage, *duration_ns);
+        }
+        state.stats.peak_memory_bytes = cmp::max(state.stats.peak_memory_bytes, stats.peak_memory_bytes);
+        state.stats.record_decoded(&stats.decoded_columns);
+        state.stats.column_sections_read += stats.column_sections_read;
+        state.stats.chunks_processed += stats.chunks_processed;
+        if let Some(result) = result {
+            state.completed_batches += result.batch_count;
+            unsafe {
+                let result = mem::transmute::<_, BatchResult<'static>>(result);
+                state.partial_results.push(result);
+            }
         }
         if state.completed_batches == self.partitions.len() || self.sufficient_rows(state.rows_collected) {
             let mut owned_results = Vec::with_capacity(0);
             mem::swap(&mut owned_results, &mut state.partial_results);
-            let full_result = match QueryTask::combine_results(owned_results, self.combined_limit()) {
-                Ok(result) => result.unwrap(),
+            let full_result = match combine_all(owned_results, self.combined_limit()).and_then(|result| {
+                let mut result = result.unwrap();
+                result.narrow_wide_sums()?;
+                Ok(result)
+            }) {
+                Ok(result) => result,
                 Err(error) => {
                     self.fail_with_no_lock(error);
                     return;
                 }
             };
             let final_result = if let Some(final_pass) = &self.final_pass {
-                let data_sources = full_result.into_columns();
+                let truncated = full_result.truncated;
+                let (data_sources, _referenced_buffers) = full_result.into_columns();
                 let cols = unsafe {
                     mem::transmute::<&HashMap<String, Arc<DataSource>>,
                         &'static HashMap<String, Arc<DataSource>>>(&data_sources)
                 };
-                let full_result = final_pass.run(cols,
-                                                 self.explain,
-                                                 !self.show.is_empty(),
-                                                 0xdeadbeef,
-                                                 cols.iter().next().map(|(_, c)| c.len()).unwrap_or(0)).unwrap().0;
-                self.convert_to_output_format(&full_result, state.rows_scanned, &state.explains)
+                let mut final_pass_stats = QueryStats::default();
+                let mut full_result = match final_pass.run(cols,
+                                                       self.explain,
+                                                       !self.show.is_empty(),
+                                                       0xdeadbeef,
+                                                       cols.iter().next().map(|(_, c)| c.len()).unwrap_or(0),
+                                                       0,
+                                                       &mut final_pass_stats) {
+                    Ok((result, _)) => result,
+                    Err(error) => {
+                        self.fail_with_no_lock(error);
+                        return;
+                    }
+                };
+                for (stage, duration_ns) in &final_pass_stats.stages {
+                    state.stats.record(&format!("Final pass {}", stage), *duration_ns);
+                }
+                state.stats.peak_memory_bytes = cmp::max(state.stats.peak_memory_bytes, final_pass_stats.peak_memory_bytes);
+                state.stats.chunks_processed += final_pass_stats.chunks_processed;
+                full_result.truncated = truncated;
+                self.convert_to_output_format(&full_result, state.rows_scanned, &state.explains, &state.stats)
             } else {
-                self.convert_to_output_format(&full_result, state.rows_scanned, &state.explains)
+                self.convert_to_output_format(&full_result, state.rows_scanned, &state.explains, &state.stats)
             };
             self.sender.send(Ok(final_result));
             self.completed.store(true, Ordering::SeqCst);
@@ -253,7 +438,8 @@ impl QueryTask {
     }
 
     fn sufficient_rows(&self, rows_collected: usize) -> bool {
-        let unordered_select = self.main_phase.aggregate.is_empty() && self.main_phase.order_by.is_empty();
+        let unordered_select = self.main_phase.aggregate.is_empty() && self.main_phase.order_by.is_empty()
+            && self.main_phase.reservoir.is_none();
         unordered_select && self.combined_limit() < rows_collected
     }
 
@@ -265,21 +451,22 @@ impl QueryTask {
     fn convert_to_output_format(&self,
                                 full_result: &BatchResult,
                                 rows_scanned: usize,
-                                explains: &[String]) -> QueryOutput {
-        let limit = self.main_phase.limit.limit as usize;
-        let offset = self.main_phase.limit.offset as usize;
-        let mut result_rows = Vec::new();
-        let count = cmp::min(limit, full_result.len() - offset);
-        for i in offset..(count + offset) {
-            let mut record = Vec::with_capacity(self.output_colnames.len());
-            // TODO(#99): use column order of original query
-            for &j in &full_result.projection {
-                record.push(full_result.columns[j].get_raw(i));
-            }
-            for &(aggregation, _) in &full_result.aggregations {
-                record.push(full_result.columns[aggregation].get_raw(i));
+                                explains: &[String],
+                                stats: &QueryStats) -> QueryOutput {
+        // With a final pass, the limit of the main phase only ensures that no groups are dropped
+        let last_phase = self.final_pass.as_ref().unwrap_or(&self.main_phase);
+        let limit = if self.main_phase.analyze { 0 } else { last_phase.limit.limit as usize };
+        let mut result_rows = last_phase.output_rows(full_result, limit);
+        let coltypes = if self.main_phase.analyze || self.main_phase.explain_only {
+            vec![BasicType::String]
+        } else {
+            last_phase.result_types(full_result)
+        };
+
+        if self.main_phase.analyze {
+            for (stage, duration_ns) in &stats.stages {
+                result_rows.push(vec![RawVal::Str(format!("{} [{:.3}ms]", stage, *duration_ns as f64 / 1_000_000.0))]);
             }
-            result_rows.push(record);
         }
 
         let mut query_plans = HashMap::new();
@@ -287,19 +474,38 @@ impl QueryTask {
             *query_plans.entry(plan.to_owned()).or_insert(0) += 1
         }
 
+        let approx_distinct_precision = self.main_phase.aggregate.iter()
+            .filter_map(|&(aggregator, _, _)| match aggregator {
+                Aggregator::ApproxCountDistinct(precision) => Some(precision),
+                _ => None,
+            })
+            .min();
+
         QueryOutput {
             colnames: self.output_colnames.clone(),
+            coltypes,
             rows: result_rows,
             query_plans,
             stats: QueryStats {
                 runtime_ns: precise_time_ns() - self.start_time_ns,
                 rows_scanned,
+                approx_distinct_precision,
+                stages: stats.stages.clone(),
+                peak_memory_bytes: stats.peak_memory_bytes,
+                decoded_columns: {
+                    let mut decoded_columns = stats.decoded_columns.clone();
+                    decoded_columns.sort();
+                    decoded_columns
+                },
+                column_sections_read: stats.column_sections_read,
+                chunks_processed: stats.chunks_processed,
             },
+            truncated: full_result.truncated,
         }
     }
 
     fn combined_limit(&self) -> usize {
-        (self.main_phase.limit.limit + self.main_phase.limit.offset) as usize
+        self.main_phase.row_limit()
     }
 }
 
@@ -312,13 +518,42 @@ impl Task for QueryTask {
     fn multithreaded(&self) -> bool { true }
 }
 
-fn find_all_cols(source: &[Arc<Partition>]) -> Vec<String> {
+/// Names of all columns in `source`, renamed columns are listed under their new name.
+fn find_all_cols(source: &[Arc<Partition>], column_aliases: &ColumnAliases) -> Vec<String> {
     let mut cols = HashSet::new();
     for partition in source {
-        for name in partition.col_names() {
-            cols.insert(name.to_string());
+        let stored = partition.col_names();
+        for name in &stored {
+            cols.insert(column_aliases.current_name(name, &stored).to_string());
         }
     }
 
     cols.into_iter().collect()
 }
+
+/// Returns an error for the first referenced column (in alphabetical order) that is not stored in any partition under
+/// its current or previous name and is neither the row number nor an alias introduced by the query.
+/// Columns are only missing from some partitions if they were added later, which is not an error.
+fn ensure_cols_exist(query: &Query,
+                     referenced_cols: &HashSet<String>,
+                     source: &[Arc<Partition>],
+                     column_aliases: &ColumnAliases) -> Result<(), QueryError> {
+    if source.is_empty() {
+        return Ok(());
+    }
+    let mut available = find_all_cols(source, column_aliases);
+    available.extend(column_aliases.iter().map(|(_, old)| old.to_string()));
+    let aliases = query.select.iter().filter_map(|(_, alias)| alias.as_ref()).collect::<HashSet<_>>();
+    let mut missing = referenced_cols.iter()
+        .filter(|col| *col != "*" && *col != ROWNUM_COLUMN && !aliases.contains(col) && !available.contains(*col))
+        .collect::<Vec<_>>();
+    missing.sort();
+    match missing.first() {
+        Some(col) => {
+            available.sort();
+            available.dedup();
+            Err(QueryError::UnknownColumn(col.to_string(), available.join(", ")))
+        }
+        None => Ok(()),
+    }
+}
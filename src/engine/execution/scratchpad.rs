@@ -28,6 +28,11 @@ impl<'a> Scratchpad<'a> {
         }
     }
 
+    /// Total heap size of all buffers, excluding column data that is only borrowed.
+    pub fn heap_size_of_buffers(&self) -> usize {
+        self.buffers.iter().map(|b| b.borrow().heap_size_of_children()).sum()
+    }
+
     pub fn get_any(&self, index: BufferRef<Any>) -> Ref<Data<'a>> {
         Ref::map(self.buffer(index).borrow(), |x| x.as_ref())
     }
@@ -78,6 +83,14 @@ impl<'a> Scratchpad<'a> {
         RefMut::map(self.get_any_mut(index.any()), |x| x.cast_ref_mut_val_rows())
     }
 
+    pub fn get_hyperloglogs(&self, index: BufferRef<HyperLogLogs>) -> Ref<HyperLogLogs> {
+        Ref::map(self.get_any(index.any()), |x| x.cast_ref_hyperloglogs())
+    }
+
+    pub fn get_mut_hyperloglogs(&self, index: BufferRef<HyperLogLogs>) -> RefMut<HyperLogLogs> {
+        RefMut::map(self.get_any_mut(index.any()), |x| x.cast_ref_mut_hyperloglogs())
+    }
+
     pub fn get_mut_nullable<T: VecData<T> + 'a>(&self, index: BufferRef<Nullable<T>>) -> (RefMut<Vec<T>>, RefMut<Vec<u8>>) {
         (self.get_mut(index.cast_non_nullable()),
          self.get_mut(BufferRef {
@@ -139,8 +152,8 @@ impl<'a> Scratchpad<'a> {
     pub fn collect_aliased(&mut self,
                            projections: &[BufferRef<Any>],
                            aggregations: &[(BufferRef<Any>, Aggregator)],
-                           rankings: &[(BufferRef<Any>, bool)])
-                           -> (Vec<BoxedData<'a>>, Vec<usize>, Vec<(usize, Aggregator)>, Vec<(usize, bool)>) {
+                           rankings: &[(BufferRef<Any>, bool, NullsOrder)])
+                           -> (Vec<BoxedData<'a>>, Vec<usize>, Vec<(usize, Aggregator)>, Vec<(usize, bool, NullsOrder)>) {
         let mut collected_buffers = HashMap::<usize, usize>::default();
         let mut columns = Vec::new();
         let mut projection_indices = Vec::new();
@@ -166,13 +179,13 @@ impl<'a> Scratchpad<'a> {
             }
         }
         let mut ranking_indices = Vec::new();
-        for &(ranking, desc) in rankings {
+        for &(ranking, desc, nulls) in rankings {
             let i = self.resolve(&ranking);
             if collected_buffers.contains_key(&i) {
-                ranking_indices.push((collected_buffers[&i], desc));
+                ranking_indices.push((collected_buffers[&i], desc, nulls));
             } else {
                 collected_buffers.insert(i, columns.len());
-                ranking_indices.push((columns.len(), desc));
+                ranking_indices.push((columns.len(), desc, nulls));
                 columns.push(self.collect_one(ranking));
             }
         }
@@ -0,0 +1,147 @@
+use std::cmp::{self, Ordering};
+use std::iter;
+
+use engine::data_types::BasicType;
+use engine::execution::query_task::QueryOutput;
+use engine::planning::NullsOrder;
+use errors::QueryError;
+use ingest::raw_val::RawVal;
+use syntax::limit::LimitClause;
+
+/// Merges the rows of `left` and `right`, which are each sorted by the result columns named in `order_by`, and applies
+/// `limit`. Without `order_by`, the rows of `right` follow those of `left`.
+/// Fails with `QueryError::TypeError` unless both results have the same number of columns and every column has the same
+/// planned type in both results, where columns of unknown type are compatible with any type.
+pub fn union_all(left: QueryOutput,
+                 right: QueryOutput,
+                 order_by: &[(String, bool, NullsOrder)],
+                 limit: &LimitClause) -> Result<QueryOutput, QueryError> {
+    ensure_compatible(&left, &right)?;
+    let mut sort_columns = Vec::with_capacity(order_by.len());
+    for (name, desc, nulls) in order_by {
+        match left.colnames.iter().position(|colname| colname == name) {
+            Some(index) => sort_columns.push((index, *desc, *nulls)),
+            None => bail!(QueryError::NotImplemented, "ORDER BY {} does not refer to a result column of UNION ALL", name),
+        }
+    }
+    let merged = merge(left, right, &sort_columns, limit.limit.saturating_add(limit.offset));
+    Ok(apply_offset(merged, limit.offset))
+}
+
+/// Merges the results of the grouping sets of a `Rollup`, which are each sorted by the result columns at the indices in
+/// `order_by`, and applies `limit`. Each result is paired with the indices of the columns that were omitted from its
+/// query, which are filled with nulls. Column names are taken from the first result, which has no omitted columns.
+pub fn union_grouping_sets(results: Vec<(QueryOutput, Vec<usize>)>,
+                           order_by: &[(usize, bool, NullsOrder)],
+                           limit: &LimitClause) -> Result<QueryOutput, QueryError> {
+    let mut results = results.into_iter().map(|(mut output, nulls)| {
+        for row in &mut output.rows {
+            for &index in &nulls {
+                row.insert(index, RawVal::Null);
+            }
+        }
+        for &index in &nulls {
+            output.coltypes.insert(index, BasicType::Null);
+        }
+        output
+    });
+    let mut combined = match results.next() {
+        Some(output) => output,
+        None => return Err(fatal!("Rollup without grouping sets")),
+    };
+    let max_rows = limit.limit.saturating_add(limit.offset);
+    for output in results {
+        combined = merge(combined, output, order_by, max_rows);
+    }
+    Ok(apply_offset(combined, limit.offset))
+}
+
+/// Merges the sorted rows of `left` and `right` into at most `max_rows` rows and combines their statistics.
+/// Rows of `left` precede rows of `right` that compare equal.
+fn merge(left: QueryOutput, right: QueryOutput, sort_columns: &[(usize, bool, NullsOrder)], max_rows: u64) -> QueryOutput {
+    let QueryOutput { colnames, coltypes, rows, mut query_plans, mut stats, truncated } = left;
+    let max_rows = cmp::min(max_rows, (rows.len() + right.rows.len()) as u64) as usize;
+    let mut merged = Vec::with_capacity(max_rows);
+    let mut left_rows = rows.into_iter().peekable();
+    let mut right_rows = right.rows.into_iter().peekable();
+    while merged.len() < max_rows {
+        let take_left = match (left_rows.peek(), right_rows.peek()) {
+            (Some(l), Some(r)) => compare_rows(l, r, sort_columns) != Ordering::Greater,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        merged.extend(if take_left { left_rows.next() } else { right_rows.next() });
+    }
+    let coltypes = coltypes.iter()
+        .zip(right.coltypes.iter().cloned().chain(iter::repeat(BasicType::Null)))
+        .map(|(&t1, t2)| if t1 == BasicType::Null { t2 } else { t1 })
+        .collect();
+
+    for (plan, count) in right.query_plans {
+        *query_plans.entry(plan).or_insert(0) += count;
+    }
+    stats.runtime_ns = cmp::max(stats.runtime_ns, right.stats.runtime_ns);
+    stats.rows_scanned += right.stats.rows_scanned;
+    stats.approx_distinct_precision = match (stats.approx_distinct_precision, right.stats.approx_distinct_precision) {
+        (Some(p1), Some(p2)) => Some(cmp::min(p1, p2)),
+        (p1, p2) => p1.or(p2),
+    };
+    stats.stages.extend(right.stats.stages);
+    stats.peak_memory_bytes = cmp::max(stats.peak_memory_bytes, right.stats.peak_memory_bytes);
+    stats.chunks_processed += right.stats.chunks_processed;
+    stats.record_decoded(&right.stats.decoded_columns);
+    stats.decoded_columns.sort();
+
+    QueryOutput {
+        colnames,
+        coltypes,
+        rows: merged,
+        query_plans,
+        stats,
+        truncated: truncated || right.truncated,
+    }
+}
+
+fn apply_offset(mut output: QueryOutput, offset: u64) -> QueryOutput {
+    let offset = cmp::min(offset, output.rows.len() as u64) as usize;
+    output.rows.drain(..offset);
+    output
+}
+
+fn compare_rows(a: &[RawVal], b: &[RawVal], sort_columns: &[(usize, bool, NullsOrder)]) -> Ordering {
+    for &(index, desc, nulls) in sort_columns {
+        let ordering = compare(&a[index], &b[index], desc, nulls);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn compare(a: &RawVal, b: &RawVal, desc: bool, nulls: NullsOrder) -> Ordering {
+    let null_ordering = if nulls == NullsOrder::First { Ordering::Less } else { Ordering::Greater };
+    match (a, b) {
+        (RawVal::Null, RawVal::Null) => Ordering::Equal,
+        (RawVal::Null, _) => null_ordering,
+        (_, RawVal::Null) => null_ordering.reverse(),
+        _ if desc => b.cmp(a),
+        _ => a.cmp(b),
+    }
+}
+
+fn ensure_compatible(left: &QueryOutput, right: &QueryOutput) -> Result<(), QueryError> {
+    if left.colnames.len() != right.colnames.len() {
+        bail!(QueryError::TypeError, "UNION ALL of queries with {} and {} result columns",
+              left.colnames.len(), right.colnames.len())
+    }
+    for (i, colname) in left.colnames.iter().enumerate() {
+        let left_type = left.coltypes.get(i).cloned().unwrap_or(BasicType::Null);
+        let right_type = right.coltypes.get(i).cloned().unwrap_or(BasicType::Null);
+        let unknown = |t: BasicType| t == BasicType::Null || t == BasicType::Val;
+        if !unknown(left_type) && !unknown(right_type) && left_type != right_type {
+            bail!(QueryError::TypeError, "Column {} of UNION ALL has type {:?} in the first and {:?} in the second query",
+                  colname, left_type, right_type)
+        }
+    }
+    Ok(())
+}
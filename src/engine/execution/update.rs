@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use bitvec::BitVec;
+use engine::*;
+use engine::execution::query_task::QueryStats;
+use errors::QueryError;
+use ingest::raw_val::RawVal;
+use mem_store::column_builder::*;
+use mem_store::partition::{Partition, TOMBSTONE_COLUMN};
+use mem_store::*;
+use scheduler::disk_read_scheduler::DiskReadScheduler;
+use syntax::expression::*;
+use syntax::limit::LimitClause;
+
+/// Returns the column of `partition` with `column` set to `value` for all rows that satisfy `filter`, together with
+/// the number of updated rows, or `None` if no row matches. `row_offset` is the row number of the first row of the
+/// partition. The partition itself is left unchanged, the caller swaps in the updated column with `Partition::replace`.
+/// Column names are resolved through `aliases`, a renamed column is updated under the name it is stored under.
+pub fn update_partition(partition: &Partition,
+                        row_offset: usize,
+                        filter: &Expr,
+                        column: &str,
+                        value: &RawVal,
+                        aliases: &ColumnAliases,
+                        drs: &DiskReadScheduler) -> Result<Option<(Arc<Column>, usize)>, QueryError> {
+    let matches = matching_rows(partition, row_offset, filter, aliases, drs)?;
+    let updated = matches.iter().filter(|&&m| m).count();
+    if updated == 0 {
+        return Ok(None);
+    }
+    let column = match aliases.stored_name(column) {
+        Some(old) if !partition.col_names().contains(&column) => old,
+        _ => column,
+    };
+    let old_column = match partition.column(column, drs) {
+        Some(old_column) => old_column,
+        None => bail!(QueryError::NotImplemented, "Column {} was added after partition {} was created", column, partition.id()),
+    };
+    let mut values = old_column.decode()?;
+    for (v, &m) in values.iter_mut().zip(matches.iter()) {
+        if m {
+            *v = value.clone();
+        }
+    }
+    Ok(Some((rebuild_column(column, &[old_column.basic_type()], values), updated)))
+}
+
+/// Marks all rows of `partition` that satisfy `filter` as deleted and returns the number of deleted rows.
+pub fn delete_partition(partition: &Partition,
+                        row_offset: usize,
+                        filter: &Expr,
+                        aliases: &ColumnAliases,
+                        drs: &DiskReadScheduler) -> Result<usize, QueryError> {
+    let matches = matching_rows(partition, row_offset, filter, aliases, drs)?;
+    partition.delete_rows(&matches)
+}
+
+/// Returns the columns of `partition` with all deleted rows removed together with the number of removed rows,
+/// or `None` if no rows of the partition have been deleted.
+pub fn vacuum_partition(partition: &Partition, drs: &DiskReadScheduler) -> Result<Option<(Vec<Arc<Column>>, usize)>, QueryError> {
+    let deleted = match partition.deleted_rows()? {
+        Some(deleted) => deleted,
+        None => return Ok(None),
+    };
+    let mut columns = Vec::new();
+    for name in partition.col_names() {
+        let column = match partition.column(name, drs) {
+            Some(column) => column,
+            None => continue,
+        };
+        let values = column.decode()?
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, _)| !deleted.is_set(i))
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        columns.push(rebuild_column(name, &[column.basic_type()], values));
+    }
+    let count = (0..partition.len()).filter(|&i| deleted.is_set(i)).count();
+    Ok(Some((columns, count)))
+}
+
+/// Evaluates `filter` for every row of the partition by running it as the projection of a query.
+/// Rows that have been deleted never match.
+fn matching_rows(partition: &Partition,
+                 row_offset: usize,
+                 filter: &Expr,
+                 aliases: &ColumnAliases,
+                 drs: &DiskReadScheduler) -> Result<Vec<bool>, QueryError> {
+    let mut matches = filter_values(partition, row_offset, filter, aliases, drs)?;
+    if let Some(deleted) = partition.deleted_rows()? {
+        for (i, m) in matches.iter_mut().enumerate() {
+            *m = *m && !deleted.is_set(i);
+        }
+    }
+    Ok(matches)
+}
+
+fn filter_values(partition: &Partition,
+                 row_offset: usize,
+                 filter: &Expr,
+                 aliases: &ColumnAliases,
+                 drs: &DiskReadScheduler) -> Result<Vec<bool>, QueryError> {
+    if let Expr::Const(ref value) = *filter {
+        return Ok(vec![is_true(value); partition.len()]);
+    }
+    let query = NormalFormQuery {
+        projection: vec![filter.clone()],
+        filter: Expr::Const(RawVal::Int(1)),
+        aggregate: vec![],
+        order_by: vec![],
+        limit: LimitClause { limit: partition.len() as u64, offset: 0 },
+        limit_by: None,
+        sample: None,
+        sampled_rows: None,
+        explain_only: false,
+        analyze: false,
+        aliases: vec![None],
+        memory_limit_bytes: None,
+        batch_size: None,
+        reservoir: None,
+    };
+    let mut referenced_cols = HashSet::new();
+    filter.add_colnames(&mut referenced_cols);
+    let mut cols = partition.get_cols(&aliases.stored_cols(&referenced_cols), drs);
+    aliases.resolve(&mut cols);
+    // The filter has to be evaluated for all rows to keep the result aligned with the rows of the partition
+    cols.remove(TOMBSTONE_COLUMN);
+    let (result, _) = query.run(&cols, false, false, partition.id() as usize, partition.len(), row_offset,
+                                &mut QueryStats::default())?;
+    ensure!(result.len() == partition.len(),
+            "Filter returned {} values for partition with {} rows", result.len(), partition.len());
+    let mask = &result.columns[result.projection[0]];
+    Ok((0..partition.len()).map(|i| is_true(&mask.get_raw(i))).collect())
+}
+
+fn is_true(value: &RawVal) -> bool {
+    match *value {
+        RawVal::Int(i) => i != 0,
+        _ => false,
+    }
+}
@@ -11,37 +11,35 @@ pub trait Aggregator<T> {
     fn combine(accumulator1: i64, accumulator2: i64) -> i64;
 }
 
-pub trait CheckedAggregator<T>: Aggregator<T> {
-    #[inline]
-    fn accumulate_checked(accumulator: T, value: i64) -> (T, bool);
-    #[inline]
-    fn combine_checked(accumulator1: i64, accumulator2: i64) -> (i64, bool);
-}
-
-pub struct Sum;
+/// Sum that accumulates into an `i128`, which can't overflow for any realistic number of rows. Partial sums of
+/// partitions are merged as `i128` as well, so that only the final result has to fit into an `i64`.
+pub struct WideSum;
 
-impl Aggregator<i64> for Sum {
-    fn unit() -> i64 { 0 }
+impl Aggregator<i128> for WideSum {
+    fn unit() -> i128 { 0 }
     #[inline]
-    fn accumulate(accumulator: i64, value: i64) -> i64 { accumulator + value }
+    fn accumulate(accumulator: i128, value: i64) -> i128 { accumulator + i128::from(value) }
     #[inline]
     fn combine(accumulator1: i64, accumulator2: i64) -> i64 { accumulator1 + accumulator2 }
 }
 
-impl CheckedAggregator<i64> for Sum {
+pub struct WrappingSum;
+
+impl Aggregator<i64> for WrappingSum {
+    fn unit() -> i64 { 0 }
     #[inline]
-    fn accumulate_checked(acc: i64, value: i64) -> (i64, bool) { acc.overflowing_add(value) }
+    fn accumulate(accumulator: i64, value: i64) -> i64 { accumulator.wrapping_add(value) }
     #[inline]
-    fn combine_checked(acc1: i64, acc2: i64) -> (i64, bool) { acc1.overflowing_add(acc2) }
+    fn combine(accumulator1: i64, accumulator2: i64) -> i64 { accumulator1.wrapping_add(accumulator2) }
 }
 
 
 pub struct Count;
 
-impl Aggregator<u32> for Count {
-    fn unit() -> u32 { 0 }
+impl Aggregator<u64> for Count {
+    fn unit() -> u64 { 0 }
     #[inline]
-    fn accumulate(accumulator: u32, _: i64) -> u32 { accumulator + 1 }
+    fn accumulate(accumulator: u64, _: i64) -> u64 { accumulator + 1 }
     #[inline]
     fn combine(accumulator1: i64, accumulator2: i64) -> i64 { accumulator1 + accumulator2 }
 }
@@ -156,16 +154,52 @@ impl<'a, T, U, V, A: Aggregator<V>> VecOperator<'a> for AggregateNullable<T, U,
     fn display_output(&self) -> bool { false }
 }
 
-pub struct CheckedAggregate<T, U, V, A> {
-    pub input: BufferRef<T>,
-    pub grouping: BufferRef<U>,
+/// Aggregates a vector that only contains nulls. Like groups of nullable inputs without any values, each group yields
+/// the unit of the aggregator.
+pub struct AggregateNulls<V, A> {
     pub output: BufferRef<V>,
     pub max_index: BufferRef<Scalar<i64>>,
     pub a: PhantomData<A>,
 }
 
-impl<'a, T, U, V, A: CheckedAggregator<V>> VecOperator<'a> for CheckedAggregate<T, U, V, A> where
-    T: GenericIntVec<T> + Into<i64>, U: GenericIntVec<U>, V: GenericIntVec<V> {
+impl<'a, V, A: Aggregator<V>> VecOperator<'a> for AggregateNulls<V, A> where V: VecData<V> + 'a {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let len = scratchpad.get_scalar(&self.max_index) as usize + 1;
+        let mut accumulators = scratchpad.get_mut(self.output);
+        if len > accumulators.len() {
+            accumulators.resize(len, A::unit());
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Vec::with_capacity(0));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.max_index.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[0..{}] = unit", self.output, self.max_index)
+    }
+    fn display_output(&self) -> bool { false }
+}
+
+/// Sums `input` into wide accumulators, see `WideSum`. `offset` is added to every value, which sums offset encoded
+/// values without decoding them.
+pub struct CheckedAggregate<T, U> {
+    pub input: BufferRef<T>,
+    pub grouping: BufferRef<U>,
+    pub output: BufferRef<i128>,
+    pub max_index: BufferRef<Scalar<i64>>,
+    pub offset: i64,
+}
+
+impl<'a, T, U> VecOperator<'a> for CheckedAggregate<T, U> where
+    T: GenericIntVec<T> + Into<i64>, U: GenericIntVec<U> {
     fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
         let nums = scratchpad.get(self.input);
         let grouping = scratchpad.get(self.grouping);
@@ -173,18 +207,16 @@ impl<'a, T, U, V, A: CheckedAggregator<V>> VecOperator<'a> for CheckedAggregate<
 
         let len = scratchpad.get_scalar(&self.max_index) as usize + 1;
         if len > accumulators.len() {
-            accumulators.resize(len, A::unit());
+            accumulators.resize(len, WideSum::unit());
         }
 
-        let mut any_overflow = false;
+        let offset = i128::from(self.offset);
         for (i, n) in grouping.iter().zip(nums.iter()) {
             let i = i.cast_usize();
-            let (result, overflow) = A::accumulate_checked(accumulators[i], (*n).into());
-            any_overflow |= overflow;
-            accumulators[i] = result;
+            accumulators[i] = WideSum::accumulate(accumulators[i], (*n).into()) + offset;
         }
 
-        if any_overflow { Err(QueryError::Overflow) } else { Ok(()) }
+        Ok(())
     }
 
     fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
@@ -198,21 +230,25 @@ impl<'a, T, U, V, A: CheckedAggregator<V>> VecOperator<'a> for CheckedAggregate<
     fn allocates(&self) -> bool { true }
 
     fn display_op(&self, _: bool) -> String {
-        format!("{}[{}] += {}", self.output, self.grouping, self.input)
+        if self.offset == 0 {
+            format!("{}[{}] += {}", self.output, self.grouping, self.input)
+        } else {
+            format!("{}[{}] += {} + {}", self.output, self.grouping, self.input, self.offset)
+        }
     }
     fn display_output(&self) -> bool { false }
 }
 
-pub struct CheckedAggregateNullable<T, U, V, A> {
+pub struct CheckedAggregateNullable<T, U> {
     pub input: BufferRef<Nullable<T>>,
     pub grouping: BufferRef<U>,
-    pub output: BufferRef<V>,
+    pub output: BufferRef<i128>,
     pub max_index: BufferRef<Scalar<i64>>,
-    pub a: PhantomData<A>,
+    pub offset: i64,
 }
 
-impl<'a, T, U, V, A: CheckedAggregator<V>> VecOperator<'a> for CheckedAggregateNullable<T, U, V, A> where
-    T: GenericIntVec<T> + Into<i64>, U: GenericIntVec<U>, V: GenericIntVec<V> {
+impl<'a, T, U> VecOperator<'a> for CheckedAggregateNullable<T, U> where
+    T: GenericIntVec<T> + Into<i64>, U: GenericIntVec<U> {
     fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
         let (nums, present) = scratchpad.get_nullable(self.input);
         let grouping = scratchpad.get(self.grouping);
@@ -220,19 +256,17 @@ impl<'a, T, U, V, A: CheckedAggregator<V>> VecOperator<'a> for CheckedAggregateN
 
         let len = scratchpad.get_scalar(&self.max_index) as usize + 1;
         if len > accumulators.len() {
-            accumulators.resize(len, A::unit());
+            accumulators.resize(len, WideSum::unit());
         }
 
-        let mut any_overflow = false;
+        let offset = i128::from(self.offset);
         for i in 0..nums.len() {
             if (&*present).is_set(i) {
                 let g = grouping[i].cast_usize();
-                let (result, overflow) = A::accumulate_checked(accumulators[g], nums[i].into());
-                any_overflow |= overflow;
-                accumulators[g] = result;
+                accumulators[g] = WideSum::accumulate(accumulators[g], nums[i].into()) + offset;
             }
         }
-        if any_overflow { Err(QueryError::Overflow) } else { Ok(()) }
+        Ok(())
     }
 
     fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
@@ -246,7 +280,43 @@ impl<'a, T, U, V, A: CheckedAggregator<V>> VecOperator<'a> for CheckedAggregateN
     fn allocates(&self) -> bool { true }
 
     fn display_op(&self, _: bool) -> String {
-        format!("{}[{}] += {}", self.output, self.grouping, self.input)
+        if self.offset == 0 {
+            format!("{}[{}] += {}", self.output, self.grouping, self.input)
+        } else {
+            format!("{}[{}] += {} + {}", self.output, self.grouping, self.input, self.offset)
+        }
     }
     fn display_output(&self) -> bool { false }
 }
+
+/// Adds the wide sums of two aggregations over the same groups, or subtracts them if `subtract` is set.
+pub struct AddWideSums {
+    pub lhs: BufferRef<i128>,
+    pub rhs: BufferRef<i128>,
+    pub subtract: bool,
+    pub output: BufferRef<i128>,
+}
+
+impl<'a> VecOperator<'a> for AddWideSums {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let sums = {
+            let lhs = scratchpad.get(self.lhs);
+            let rhs = scratchpad.get(self.rhs);
+            lhs.iter().zip(rhs.iter())
+                .map(|(&l, &r)| if self.subtract { l - r } else { l + r })
+                .collect::<Vec<_>>()
+        };
+        scratchpad.set(self.output, sums);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.lhs.any(), self.rhs.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, if self.subtract { "-" } else { "+" }, self.rhs)
+    }
+}
@@ -1,20 +1,56 @@
 use engine::*;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
 pub enum Aggregator {
-    Sum = 0,
-    Count = 1,
-    Max = 2,
-    Min = 3,
+    /// Sum that fails the query with `QueryError::Overflow` if the result does not fit into an i64.
+    Sum,
+    /// Sum that clamps the exact result to `-i64::MAX`/`i64::MAX` if it does not fit into an i64, since `i64::MIN` represents null.
+    SaturatingSum,
+    /// Sum that wraps around on overflow.
+    WrappingSum,
+    /// Number of rows for which the aggregated expression is not null.
+    Count,
+    Max,
+    Min,
+    /// Approximate number of distinct values, estimated with a HyperLogLog sketch of the given precision.
+    ApproxCountDistinct(u8),
 }
 
 impl Aggregator {
     pub fn combine_i64(self, accumulator: i64, elem: i64) -> Result<i64, QueryError> {
         match self {
             Aggregator::Sum => accumulator.checked_add(elem).ok_or(QueryError::Overflow),
+            // Partial sums are merged as i128 by `merge_wide_sums` and only clamped once merging is complete
+            Aggregator::SaturatingSum => Err(fatal!("Partial sums of {:?} cannot be combined as i64", self)),
+            Aggregator::WrappingSum => Ok(accumulator.wrapping_add(elem)),
             Aggregator::Count => Ok(accumulator + elem),
             Aggregator::Max => Ok(std::cmp::max(accumulator, elem)),
             Aggregator::Min => Ok(std::cmp::min(accumulator, elem)),
+            Aggregator::ApproxCountDistinct(_) => Err(fatal!("Sketches of {:?} cannot be combined as i64", self)),
+        }
+    }
+}
+
+/// Determines the result of `SUM` aggregations that exceed the range of i64.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowPolicy {
+    /// Fail the query with `QueryError::Overflow`.
+    Error,
+    /// Clamp the exact result to `-i64::MAX`/`i64::MAX`, since `i64::MIN` represents null.
+    /// Partial sums are promoted to i128 while partitions are merged, so the result does not depend on partitioning.
+    Saturate,
+    /// Wrap around, which gives the exact result modulo 2^64.
+    Wrap,
+}
+
+impl OverflowPolicy {
+    /// Replaces `Aggregator::Sum` with the sum aggregator that implements this policy.
+    pub fn apply(self, aggregator: Aggregator) -> Aggregator {
+        match (aggregator, self) {
+            (Aggregator::Sum, OverflowPolicy::Saturate) => Aggregator::SaturatingSum,
+            (Aggregator::Sum, OverflowPolicy::Wrap) => Aggregator::WrappingSum,
+            _ => aggregator,
         }
     }
 }
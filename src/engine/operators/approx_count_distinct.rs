@@ -0,0 +1,263 @@
+use bitvec::BitVec;
+use engine::*;
+use hyperloglog::HyperLogLog;
+
+/// Values that can be inserted into a HyperLogLog sketch.
+/// Integers of all widths hash identically, so sketches of differently encoded batches can be merged.
+pub trait Sketchable {
+    fn insert_into(&self, sketch: &mut HyperLogLog);
+}
+
+impl Sketchable for u8 {
+    fn insert_into(&self, sketch: &mut HyperLogLog) { sketch.insert_int(i64::from(*self)) }
+}
+
+impl Sketchable for u16 {
+    fn insert_into(&self, sketch: &mut HyperLogLog) { sketch.insert_int(i64::from(*self)) }
+}
+
+impl Sketchable for u32 {
+    fn insert_into(&self, sketch: &mut HyperLogLog) { sketch.insert_int(i64::from(*self)) }
+}
+
+impl Sketchable for i64 {
+    fn insert_into(&self, sketch: &mut HyperLogLog) { sketch.insert_int(*self) }
+}
+
+impl<'a> Sketchable for &'a str {
+    fn insert_into(&self, sketch: &mut HyperLogLog) { sketch.insert_str(self) }
+}
+
+pub struct ApproxCountDistinct<T, U> {
+    pub input: BufferRef<T>,
+    pub grouping: BufferRef<U>,
+    pub max_index: BufferRef<Scalar<i64>>,
+    pub precision: u32,
+    pub output: BufferRef<HyperLogLogs>,
+}
+
+impl<'a, T: VecData<T> + Sketchable + 'a, U: GenericIntVec<U>> VecOperator<'a> for ApproxCountDistinct<T, U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let values = scratchpad.get(self.input);
+        let grouping = scratchpad.get(self.grouping);
+        let mut sketches = scratchpad.get_mut_hyperloglogs(self.output);
+
+        let len = scratchpad.get_scalar(&self.max_index) as usize + 1;
+        sketches.resize(len);
+
+        for (g, value) in grouping.iter().zip(values.iter()) {
+            value.insert_into(&mut sketches.sketches[g.cast_usize()]);
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_any(self.output.any(), Box::new(HyperLogLogs::new(self.precision)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.grouping.any(), self.input.any(), self.max_index.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] ∪= hll{}({})", self.output, self.grouping, self.precision, self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
+
+pub struct ApproxCountDistinctNullable<T, U> {
+    pub input: BufferRef<Nullable<T>>,
+    pub grouping: BufferRef<U>,
+    pub max_index: BufferRef<Scalar<i64>>,
+    pub precision: u32,
+    pub output: BufferRef<HyperLogLogs>,
+}
+
+impl<'a, T: VecData<T> + Sketchable + 'a, U: GenericIntVec<U>> VecOperator<'a> for ApproxCountDistinctNullable<T, U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let (values, present) = scratchpad.get_nullable(self.input);
+        let grouping = scratchpad.get(self.grouping);
+        let mut sketches = scratchpad.get_mut_hyperloglogs(self.output);
+
+        let len = scratchpad.get_scalar(&self.max_index) as usize + 1;
+        sketches.resize(len);
+
+        for i in 0..values.len() {
+            if (&*present).is_set(i) {
+                values[i].insert_into(&mut sketches.sketches[grouping[i].cast_usize()]);
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_any(self.output.any(), Box::new(HyperLogLogs::new(self.precision)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.grouping.any(), self.input.any(), self.max_index.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] ∪= hll{}({})", self.output, self.grouping, self.precision, self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
+
+/// Sketches of a vector that only contains nulls, each group yields an empty sketch.
+pub struct ApproxCountDistinctNulls {
+    pub max_index: BufferRef<Scalar<i64>>,
+    pub precision: u32,
+    pub output: BufferRef<HyperLogLogs>,
+}
+
+impl<'a> VecOperator<'a> for ApproxCountDistinctNulls {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let len = scratchpad.get_scalar(&self.max_index) as usize + 1;
+        scratchpad.get_mut_hyperloglogs(self.output).resize(len);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_any(self.output.any(), Box::new(HyperLogLogs::new(self.precision)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.max_index.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[0..{}] = hll{}()", self.output, self.max_index, self.precision)
+    }
+    fn display_output(&self) -> bool { false }
+}
+
+#[derive(Debug)]
+pub struct CompactHyperLogLogs<U> {
+    pub data: BufferRef<HyperLogLogs>,
+    pub select: BufferRef<U>,
+    pub compacted: BufferRef<HyperLogLogs>,
+}
+
+impl<'a, U: GenericIntVec<U>> VecOperator<'a> for CompactHyperLogLogs<U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let mut data = scratchpad.get_mut_hyperloglogs(self.data);
+        let select = scratchpad.get(self.select);
+        // Remove all unmodified entries
+        let mut j = 0;
+        for (i, &s) in select.iter().take(data.sketches.len()).enumerate() {
+            if s > U::zero() {
+                data.sketches.swap(i, j);
+                j += 1;
+            }
+        }
+        data.sketches.truncate(j);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.alias(self.data, self.compacted);
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.data.any(), self.select.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.compacted.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn mutates(&self, i: usize) -> bool { i == self.data.i }
+    fn allocates(&self) -> bool { false }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{} > 0]", self.data, self.select)
+    }
+}
+
+#[derive(Debug)]
+pub struct SelectHyperLogLogs {
+    pub input: BufferRef<HyperLogLogs>,
+    pub indices: BufferRef<usize>,
+    pub output: BufferRef<HyperLogLogs>,
+}
+
+impl<'a> VecOperator<'a> for SelectHyperLogLogs {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let selection = {
+            let data = scratchpad.get_hyperloglogs(self.input);
+            let indices = scratchpad.get(self.indices);
+            HyperLogLogs {
+                precision: data.precision,
+                sketches: indices.iter().map(|&i| data.sketches[i].clone()).collect(),
+            }
+        };
+        scratchpad.set_any(self.output.any(), Box::new(selection));
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any(), self.indices.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}]", self.input, self.indices)
+    }
+}
+
+#[derive(Debug)]
+pub struct MergeHyperLogLogs {
+    pub merge_ops: BufferRef<MergeOp>,
+    pub left: BufferRef<HyperLogLogs>,
+    pub right: BufferRef<HyperLogLogs>,
+    pub merged: BufferRef<HyperLogLogs>,
+}
+
+impl<'a> VecOperator<'a> for MergeHyperLogLogs {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let merged = {
+            let ops = scratchpad.get(self.merge_ops);
+            let left = scratchpad.get_hyperloglogs(self.left);
+            let right = scratchpad.get_hyperloglogs(self.right);
+            ensure!(left.precision == right.precision,
+                    "Unequal sketch precision in left ({}) and right ({}) batch result.",
+                    left.precision, right.precision);
+            let mut merged = HyperLogLogs::new(left.precision);
+            let mut i = 0;
+            let mut j = 0;
+            for op in ops.iter() {
+                match *op {
+                    MergeOp::TakeLeft => {
+                        merged.sketches.push(left.sketches[i].clone());
+                        i += 1;
+                    }
+                    MergeOp::TakeRight => {
+                        merged.sketches.push(right.sketches[j].clone());
+                        j += 1;
+                    }
+                    MergeOp::MergeRight => {
+                        merged.sketches.last_mut().unwrap().merge(&right.sketches[j]);
+                        j += 1;
+                    }
+                }
+            }
+            merged
+        };
+        scratchpad.set_any(self.merged.any(), Box::new(merged));
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.left.any(), self.right.any(), self.merge_ops.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.merged.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("merge_hyperloglogs({}; {}, {})", self.merge_ops, self.left, self.right)
+    }
+}
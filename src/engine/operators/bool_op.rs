@@ -23,6 +23,8 @@ impl<'a, T: BooleanOp + fmt::Debug + 'a> BooleanOperator<T> {
 
 impl<'a, T: BooleanOp + fmt::Debug> VecOperator<'a> for BooleanOperator<T> {
     fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        // Both operations are idempotent, and the buffer can't be borrowed mutably and immutably at the same time
+        if self.lhs == self.rhs { return Ok(()); }
         let mut result = scratchpad.get_mut(self.lhs);
         let rhs = scratchpad.get(self.rhs);
         T::evaluate(&mut result, &rhs);
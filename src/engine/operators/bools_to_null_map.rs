@@ -0,0 +1,66 @@
+use bitvec::*;
+use engine::*;
+
+#[derive(Debug)]
+pub struct BoolsToNullMap {
+    pub bools: BufferRef<u8>,
+    pub present: BufferRef<u8>,
+}
+
+impl<'a> VecOperator<'a> for BoolsToNullMap {
+    fn execute(&mut self, _streaming: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let bools = scratchpad.get(self.bools);
+        let mut present = scratchpad.get_mut(self.present);
+        present.clear();
+        present.resize(bools.len() / 8 + 1, 0);
+        for (i, &b) in bools.iter().enumerate() {
+            if b > 0 {
+                present.set(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.present, Vec::with_capacity(batch_size / 8 + 1));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.bools.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.present.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+    fn display_op(&self, _: bool) -> String { format!("null_map({} > 0)", self.bools) }
+}
+
+#[derive(Debug)]
+pub struct NullableBoolsToNullMap {
+    pub bools: BufferRef<Nullable<u8>>,
+    pub present: BufferRef<u8>,
+}
+
+impl<'a> VecOperator<'a> for NullableBoolsToNullMap {
+    fn execute(&mut self, _streaming: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let (bools, bools_present) = scratchpad.get_nullable(self.bools);
+        let mut present = scratchpad.get_mut(self.present);
+        present.clear();
+        present.resize(bools.len() / 8 + 1, 0);
+        for (i, &b) in bools.iter().enumerate() {
+            if b > 0 && (&*bools_present).is_set(i) {
+                present.set(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.present, Vec::with_capacity(batch_size / 8 + 1));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.bools.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.present.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+    fn display_op(&self, _: bool) -> String { format!("null_map({} > 0)", self.bools) }
+}
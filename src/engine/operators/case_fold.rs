@@ -0,0 +1,49 @@
+use engine::*;
+use std::str;
+
+/// Converts strings to lowercase (or uppercase if `upper` is set).
+/// The converted strings are written to `string_store`, which is pinned since the output borrows from it.
+/// Requires the entire input at once so that `string_store` is never reallocated after the output has been created.
+#[derive(Debug)]
+pub struct CaseFold<'a> {
+    pub input: BufferRef<&'a str>,
+    pub upper: bool,
+    pub string_store: BufferRef<u8>,
+    pub output: BufferRef<&'a str>,
+}
+
+impl<'a> VecOperator<'a> for CaseFold<'a> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let (string_store, ends) = {
+            let input = scratchpad.get(self.input);
+            let mut string_store = Vec::new();
+            let mut ends = Vec::with_capacity(input.len());
+            for s in input.iter() {
+                let folded = if self.upper { s.to_uppercase() } else { s.to_lowercase() };
+                string_store.extend_from_slice(folded.as_bytes());
+                ends.push(string_store.len());
+            }
+            (string_store, ends)
+        };
+        scratchpad.set(self.string_store, string_store);
+        let string_store = scratchpad.get_pinned(self.string_store);
+        let mut start = 0;
+        let mut output = Vec::with_capacity(ends.len());
+        for end in ends {
+            output.push(unsafe { str::from_utf8_unchecked(&string_store[start..end]) });
+            start = end;
+        }
+        scratchpad.set(self.output, output);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}({})", if self.upper { "upper" } else { "lower" }, self.input)
+    }
+}
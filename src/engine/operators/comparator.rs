@@ -1,3 +1,5 @@
+use std::marker::PhantomData;
+
 pub trait Comparator<T> {
     fn cmp(left: T, right: T) -> bool;
     fn cmp_eq(left: T, right: T) -> bool;
@@ -96,3 +98,63 @@ impl<'a> Comparator<Option<&'a str>> for CmpGreaterThan {
     fn cmp_eq(left: Option<&str>, right: Option<&str>) -> bool { left >= right }
     fn is_less_than() -> bool { false }
 }
+
+
+/// Orders like `C`, except that nulls of fused string sort keys (`None`) are placed at the other end.
+/// Integer sort keys represent nulls by a value that already sorts where nulls are placed, see `FuseNullsMax`.
+#[derive(Debug)]
+pub struct NullsReversed<C>(PhantomData<C>);
+
+impl<C: Comparator<u8>> Comparator<u8> for NullsReversed<C> {
+    fn cmp(left: u8, right: u8) -> bool { C::cmp(left, right) }
+    fn cmp_eq(left: u8, right: u8) -> bool { C::cmp_eq(left, right) }
+    fn is_less_than() -> bool { C::is_less_than() }
+}
+
+impl<C: Comparator<u16>> Comparator<u16> for NullsReversed<C> {
+    fn cmp(left: u16, right: u16) -> bool { C::cmp(left, right) }
+    fn cmp_eq(left: u16, right: u16) -> bool { C::cmp_eq(left, right) }
+    fn is_less_than() -> bool { C::is_less_than() }
+}
+
+impl<C: Comparator<u32>> Comparator<u32> for NullsReversed<C> {
+    fn cmp(left: u32, right: u32) -> bool { C::cmp(left, right) }
+    fn cmp_eq(left: u32, right: u32) -> bool { C::cmp_eq(left, right) }
+    fn is_less_than() -> bool { C::is_less_than() }
+}
+
+impl<C: Comparator<u64>> Comparator<u64> for NullsReversed<C> {
+    fn cmp(left: u64, right: u64) -> bool { C::cmp(left, right) }
+    fn cmp_eq(left: u64, right: u64) -> bool { C::cmp_eq(left, right) }
+    fn is_less_than() -> bool { C::is_less_than() }
+}
+
+impl<C: Comparator<i64>> Comparator<i64> for NullsReversed<C> {
+    fn cmp(left: i64, right: i64) -> bool { C::cmp(left, right) }
+    fn cmp_eq(left: i64, right: i64) -> bool { C::cmp_eq(left, right) }
+    fn is_less_than() -> bool { C::is_less_than() }
+}
+
+impl<'a, C: Comparator<&'a str>> Comparator<&'a str> for NullsReversed<C> {
+    fn cmp(left: &'a str, right: &'a str) -> bool { C::cmp(left, right) }
+    fn cmp_eq(left: &'a str, right: &'a str) -> bool { C::cmp_eq(left, right) }
+    fn is_less_than() -> bool { C::is_less_than() }
+}
+
+impl<'a, C: Comparator<Option<&'a str>>> Comparator<Option<&'a str>> for NullsReversed<C> {
+    fn cmp(left: Option<&'a str>, right: Option<&'a str>) -> bool {
+        match (left, right) {
+            (None, Some(_)) | (Some(_), None) => !C::cmp(left, right),
+            _ => C::cmp(left, right),
+        }
+    }
+
+    fn cmp_eq(left: Option<&'a str>, right: Option<&'a str>) -> bool {
+        match (left, right) {
+            (None, Some(_)) | (Some(_), None) => !C::cmp_eq(left, right),
+            _ => C::cmp_eq(left, right),
+        }
+    }
+
+    fn is_less_than() -> bool { C::is_less_than() }
+}
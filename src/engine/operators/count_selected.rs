@@ -0,0 +1,46 @@
+use engine::*;
+
+/// Counts the rows selected by a filter mask, which are the nonzero entries or the zero entries if `rejected` is set.
+/// Outputs a single count, or no count if no rows are selected, like an ungrouped `COUNT` aggregate.
+pub struct CountSelected {
+    pub select: BufferRef<u8>,
+    pub rejected: bool,
+    pub count: i64,
+    pub output: BufferRef<i64>,
+}
+
+impl<'a> VecOperator<'a> for CountSelected {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let select = scratchpad.get(self.select);
+        let nonzero = select.iter().filter(|&&selected| selected != 0).count();
+        let selected = if self.rejected { select.len() - nonzero } else { nonzero };
+        self.count += selected as i64;
+        Ok(())
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        if self.count > 0 {
+            scratchpad.get_mut(self.output).push(self.count);
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        self.count = 0;
+        scratchpad.set(self.output, Vec::with_capacity(1));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.select.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        if self.rejected {
+            format!("count({} == 0)", self.select)
+        } else {
+            format!("count({} != 0)", self.select)
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use engine::*;
+use num;
+use regex::Regex;
 use std::str;
 
 #[derive(Debug)]
@@ -83,3 +85,222 @@ impl<'a> VecOperator<'a> for InverseDictLookup<'a> {
         format!("inverse_dict_lookup({}, {}, {})", self.dict_indices, self.dict_data, self.constant)
     }
 }
+
+/// Determines the position of a string constant in a (sorted) dictionary.
+/// If the constant is not part of the dictionary, outputs the index of the first larger entry,
+/// or the index of the last smaller entry if `round_down` is set.
+#[derive(Debug)]
+pub struct DictRank<'a> {
+    pub dict_indices: BufferRef<u64>,
+    pub dict_data: BufferRef<u8>,
+    pub constant: BufferRef<Scalar<&'a str>>,
+    pub round_down: bool,
+    pub output: BufferRef<Scalar<i64>>,
+}
+
+impl<'a> VecOperator<'a> for DictRank<'a> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let result = {
+            let constant = scratchpad.get_scalar(&self.constant);
+            let constant = constant.as_bytes();
+            let dict_indices = scratchpad.get(self.dict_indices);
+            let dict_data = scratchpad.get(self.dict_data);
+            let search = dict_indices.binary_search_by(|offset_len| {
+                let offset = (offset_len >> 24) as usize;
+                let len = (offset_len & 0x00ff_ffff) as usize;
+                dict_data[offset..(offset + len)].cmp(constant)
+            });
+            match search {
+                Ok(index) => index as i64,
+                Err(insertion_point) if self.round_down => insertion_point as i64 - 1,
+                Err(insertion_point) => insertion_point as i64,
+            }
+        };
+        scratchpad.set_const(self.output, result);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.constant.any(), self.dict_indices.any(), self.dict_data.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { false }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("dict_rank({}, {}, {}, round_down={})", self.dict_indices, self.dict_data, self.constant, self.round_down)
+    }
+}
+
+/// Evaluates `regex` once for each entry of a dictionary.
+#[derive(Debug)]
+pub struct DictRegex {
+    pub dict_indices: BufferRef<u64>,
+    pub dict_data: BufferRef<u8>,
+    pub regex: Regex,
+    pub output: BufferRef<u8>,
+}
+
+impl<'a> VecOperator<'a> for DictRegex {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let matches = {
+            let dict_indices = scratchpad.get(self.dict_indices);
+            let dict_data = scratchpad.get(self.dict_data);
+            dict_indices.iter()
+                .map(|offset_len| {
+                    let offset = (offset_len >> 24) as usize;
+                    let len = (offset_len & 0x00ff_ffff) as usize;
+                    let string = unsafe {
+                        str::from_utf8_unchecked(&dict_data[offset..(offset + len)])
+                    };
+                    self.regex.is_match(string) as u8
+                })
+                .collect::<Vec<_>>()
+        };
+        scratchpad.set(self.output, matches);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.dict_indices.any(), self.dict_data.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("dict_regex({}, {}, /{}/)", self.dict_indices, self.dict_data, self.regex)
+    }
+}
+
+/// Converts each dictionary entry to lowercase (or uppercase if `upper` is set) and outputs the resulting dictionary,
+/// which is sorted and without duplicates, as well as the index of each original entry in the new dictionary.
+#[derive(Debug)]
+pub struct DictCaseFold {
+    pub dict_indices: BufferRef<u64>,
+    pub dict_data: BufferRef<u8>,
+    pub upper: bool,
+    pub folded_dict_indices: BufferRef<u64>,
+    pub folded_dict_data: BufferRef<u8>,
+    pub remap: BufferRef<u64>,
+}
+
+impl<'a> VecOperator<'a> for DictCaseFold {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let (folded_dict_indices, folded_dict_data, remap) = {
+            let dict_indices = scratchpad.get(self.dict_indices);
+            let dict_data = scratchpad.get(self.dict_data);
+            let mut folded = dict_indices.iter()
+                .enumerate()
+                .map(|(i, offset_len)| {
+                    let offset = (offset_len >> 24) as usize;
+                    let len = (offset_len & 0x00ff_ffff) as usize;
+                    let string = unsafe {
+                        str::from_utf8_unchecked(&dict_data[offset..(offset + len)])
+                    };
+                    let folded = if self.upper { string.to_uppercase() } else { string.to_lowercase() };
+                    (folded, i)
+                })
+                .collect::<Vec<_>>();
+            folded.sort();
+
+            let mut folded_dict_indices = Vec::new();
+            let mut folded_dict_data = Vec::new();
+            let mut remap = vec![0; folded.len()];
+            for (j, (string, i)) in folded.iter().enumerate() {
+                // Entries that fold to the same string are collapsed into a single entry
+                if j == 0 || folded[j - 1].0 != *string {
+                    folded_dict_indices.push(((folded_dict_data.len() as u64) << 24) | string.len() as u64);
+                    folded_dict_data.extend_from_slice(string.as_bytes());
+                }
+                remap[*i] = folded_dict_indices.len() as u64 - 1;
+            }
+            (folded_dict_indices, folded_dict_data, remap)
+        };
+        scratchpad.set(self.folded_dict_indices, folded_dict_indices);
+        scratchpad.set(self.folded_dict_data, folded_dict_data);
+        scratchpad.set(self.remap, remap);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.dict_indices.any(), self.dict_data.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> {
+        vec![self.folded_dict_indices.any(), self.folded_dict_data.any(), self.remap.any()]
+    }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("dict_case_fold({}, {}, {})", self.dict_indices, self.dict_data, if self.upper { "upper" } else { "lower" })
+    }
+}
+
+/// Maps each dictionary index to the corresponding entry of `remap`.
+#[derive(Debug)]
+pub struct DictRemap<T> {
+    pub indices: BufferRef<T>,
+    pub remap: BufferRef<u64>,
+    pub output: BufferRef<T>,
+}
+
+impl<'a, T: GenericIntVec<T>> VecOperator<'a> for DictRemap<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let indices = scratchpad.get(self.indices);
+        let remap = scratchpad.get(self.remap);
+        let mut output = scratchpad.get_mut(self.output);
+        if stream { output.clear(); }
+        for i in indices.iter() {
+            // Remapped indices are never larger than the number of dictionary entries and fit into `T`
+            output.push(num::cast(remap[i.cast_usize()]).unwrap());
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.indices.any(), self.remap.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, buffer: usize) -> bool { buffer == self.indices.i }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}]", self.remap, self.indices)
+    }
+}
+
+/// Looks up the entry of `dict_matches` for each dictionary index.
+#[derive(Debug)]
+pub struct DictMatchLookup<T> {
+    pub indices: BufferRef<T>,
+    pub dict_matches: BufferRef<u8>,
+    pub output: BufferRef<u8>,
+}
+
+impl<'a, T: GenericIntVec<T>> VecOperator<'a> for DictMatchLookup<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let indices = scratchpad.get(self.indices);
+        let dict_matches = scratchpad.get(self.dict_matches);
+        let mut output = scratchpad.get_mut(self.output);
+        if stream { output.clear(); }
+        for i in indices.iter() {
+            output.push(dict_matches[i.cast_usize()]);
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.indices.any(), self.dict_matches.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, buffer: usize) -> bool { buffer == self.indices.i }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}]", self.dict_matches, self.indices)
+    }
+}
@@ -9,12 +9,11 @@ pub struct EncodeIntConstant {
 }
 
 impl<'a> VecOperator<'a> for EncodeIntConstant {
-    fn execute(&mut self, _: bool, _: &mut Scratchpad<'a>) -> Result<(), QueryError> { Ok(()) }
-
-    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
         let constant = scratchpad.get_scalar(&self.constant);
-        let result = self.codec.encode_int(constant);
+        let result = self.codec.encode_int(constant)?;
         scratchpad.set_any(self.output.any(), Data::scalar_i64(result));
+        Ok(())
     }
 
     fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.constant.any()] }
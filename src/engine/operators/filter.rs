@@ -37,6 +37,41 @@ impl<'a, T: 'a> VecOperator<'a> for Filter<T> where T: VecData<T> {
     }
 }
 
+pub struct FilterNot<T> {
+    pub input: BufferRef<T>,
+    pub filter: BufferRef<u8>,
+    pub output: BufferRef<T>,
+}
+
+impl<'a, T: 'a> VecOperator<'a> for FilterNot<T> where T: VecData<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let data = scratchpad.get(self.input);
+        let filter = scratchpad.get(self.filter);
+        let mut filtered = scratchpad.get_mut(self.output);
+        if stream { filtered.clear(); }
+        for (d, &reject) in data.iter().zip(filter.iter()) {
+            if reject == 0 {
+                filtered.push(d.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any(), self.filter.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[!{}]", self.input, self.filter)
+    }
+}
+
 pub struct NullableFilter<T> {
     pub input: BufferRef<T>,
     pub filter: BufferRef<Nullable<u8>>,
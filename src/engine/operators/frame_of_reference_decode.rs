@@ -0,0 +1,40 @@
+use engine::*;
+
+#[derive(Debug)]
+pub struct FrameOfReferenceDecode<T> {
+    pub encoded: BufferRef<T>,
+    pub frame_minimums: BufferRef<i64>,
+    pub frame_size: usize,
+    pub decoded: BufferRef<i64>,
+    pub position: usize,
+}
+
+impl<'a, T: GenericIntVec<T>> VecOperator<'a> for FrameOfReferenceDecode<T> {
+    fn execute(&mut self, streaming: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let encoded = scratchpad.get(self.encoded);
+        let frame_minimums = scratchpad.get(self.frame_minimums);
+        let mut decoded = scratchpad.get_mut(self.decoded);
+        if streaming { decoded.clear(); }
+        let mut position = self.position;
+        for e in encoded.iter() {
+            decoded.push(e.to_i64().unwrap() + frame_minimums[position / self.frame_size]);
+            position += 1;
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.decoded, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.encoded.any(), self.frame_minimums.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.decoded.any()] }
+    fn can_stream_input(&self, buffer: usize) -> bool { buffer == self.encoded.i }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("frame_of_reference_decode({}, {}, {})", self.encoded, self.frame_minimums, self.frame_size)
+    }
+}
@@ -1,4 +1,6 @@
+use std::cmp;
 use std::i64;
+use std::iter;
 
 use chrono::{NaiveDateTime, Datelike};
 use regex;
@@ -43,3 +45,56 @@ impl<'a> MapOp<&'a str, i64> for Length {
     fn apply(&self, s: &'a str) -> i64 { s.len() as i64 }
     fn name() -> &'static str { "length" }
 }
+
+
+pub struct CharLength;
+
+impl<'a> MapOp<&'a str, i64> for CharLength {
+    fn apply(&self, s: &'a str) -> i64 { s.chars().count() as i64 }
+    fn name() -> &'static str { "char_length" }
+}
+
+
+/// Characters at the 1-based positions `start..start + length`, clamped to the bounds of the string.
+pub struct Substring {
+    pub start: i64,
+    pub length: i64,
+}
+
+impl<'a> MapOp<&'a str, &'a str> for Substring {
+    fn apply(&self, s: &'a str) -> &'a str {
+        let end = self.start.saturating_add(self.length);
+        let start = cmp::max(self.start, 1);
+        if end <= start {
+            return "";
+        }
+        let (from, to) = ((start - 1) as usize, (end - 1) as usize);
+        let mut offsets = s.char_indices().map(|(offset, _)| offset).chain(iter::once(s.len()));
+        let begin = match offsets.nth(from) {
+            Some(begin) => begin,
+            None => return "",
+        };
+        let finish = offsets.nth(to - from - 1).unwrap_or_else(|| s.len());
+        &s[begin..finish]
+    }
+    fn name() -> &'static str { "substring" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substring() {
+        let substring = |start, length, s| Substring { start, length }.apply(s);
+        assert_eq!(substring(1, 4, "/api/users"), "/api");
+        assert_eq!(substring(2, i64::MAX, "/api"), "api");
+        assert_eq!(substring(0, 3, "abc"), "ab");
+        assert_eq!(substring(-5, 3, "abc"), "");
+        assert_eq!(substring(3, 10, "abc"), "c");
+        assert_eq!(substring(4, 1, "abc"), "");
+        assert_eq!(substring(2, -1, "abc"), "");
+        assert_eq!(substring(2, 2, "äöüß"), "öü");
+        assert_eq!(CharLength.apply("äöüß"), 4);
+    }
+}
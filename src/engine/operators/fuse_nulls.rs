@@ -5,6 +5,8 @@ use std::i64;
 pub struct FuseNullsI64 {
     pub input: BufferRef<Nullable<i64>>,
     pub fused: BufferRef<i64>,
+    /// Value that represents null, i64::MIN unless the fused values are only used as sort keys.
+    pub null: i64,
 }
 
 impl<'a> VecOperator<'a> for FuseNullsI64 {
@@ -16,7 +18,7 @@ impl<'a> VecOperator<'a> for FuseNullsI64 {
             if (&*present).is_set(i) {
                 fused.push(input[i]);
             } else {
-                fused.push(i64::MIN);
+                fused.push(self.null);
             }
         }
         Ok(())
@@ -33,7 +35,11 @@ impl<'a> VecOperator<'a> for FuseNullsI64 {
     fn allocates(&self) -> bool { true }
 
     fn display_op(&self, _: bool) -> String {
-        format!("FuseNullsI64({})", self.input)
+        if self.null == i64::MIN {
+            format!("FuseNullsI64({})", self.input)
+        } else {
+            format!("FuseNullsI64({}; null={})", self.input, self.null)
+        }
     }
 }
 
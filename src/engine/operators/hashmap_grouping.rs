@@ -3,6 +3,7 @@ use fnv::FnvHashMap;
 use engine::*;
 use ingest::raw_val::RawVal;
 use std::hash::Hash;
+use std::mem;
 
 #[derive(Debug)]
 pub struct HashMapGrouping<T: VecData<T> + Hash> {
@@ -58,6 +59,8 @@ impl<'a, T: VecData<T> + Hash + 'a> VecOperator<'a> for HashMapGrouping<T> {
     fn can_stream_input(&self, _: usize) -> bool { true }
     fn can_stream_output(&self, output: usize) -> bool { output != self.unique_out.i }
     fn allocates(&self) -> bool { true }
+    // Each entry also stores its hash
+    fn heap_size_of_state(&self) -> usize { self.map.capacity() * (mem::size_of::<(T, u32)>() + mem::size_of::<u64>()) }
 
     fn display_op(&self, _: bool) -> String {
         format!("hashmap_grouping({})", self.input)
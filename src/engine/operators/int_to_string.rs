@@ -0,0 +1,48 @@
+use engine::*;
+use std::fmt::Write;
+use std::str;
+
+/// Formats integers as base 10 strings.
+/// The strings are written to `string_store`, which is pinned since the output borrows from it.
+/// Requires the entire input at once so that `string_store` is never reallocated after the output has been created.
+#[derive(Debug)]
+pub struct IntToString<'a> {
+    pub input: BufferRef<i64>,
+    pub string_store: BufferRef<u8>,
+    pub output: BufferRef<&'a str>,
+}
+
+impl<'a> VecOperator<'a> for IntToString<'a> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let (string_store, ends) = {
+            let input = scratchpad.get(self.input);
+            let mut string_store = String::new();
+            let mut ends = Vec::with_capacity(input.len());
+            for i in input.iter() {
+                write!(string_store, "{}", i).unwrap();
+                ends.push(string_store.len());
+            }
+            (string_store.into_bytes(), ends)
+        };
+        scratchpad.set(self.string_store, string_store);
+        let string_store = scratchpad.get_pinned(self.string_store);
+        let mut start = 0;
+        let mut output = Vec::with_capacity(ends.len());
+        for end in ends {
+            output.push(unsafe { str::from_utf8_unchecked(&string_store[start..end]) });
+            start = end;
+        }
+        scratchpad.set(self.output, output);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("to_string({})", self.input)
+    }
+}
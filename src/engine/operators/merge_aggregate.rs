@@ -15,7 +15,8 @@ impl<'a> VecOperator<'a> for MergeAggregate {
             let ops = scratchpad.get(self.merge_ops);
             let left = scratchpad.get(self.left);
             let right = scratchpad.get(self.right);
-            merge_aggregate(&ops, &left, &right, self.aggregator)
+            let aggregator = self.aggregator;
+            merge_aggregate(&*ops, &*left, &*right, |accumulator, elem| aggregator.combine_i64(accumulator, elem))
         };
         scratchpad.set(self.aggregated, aggregated?);
         Ok(())
@@ -32,7 +33,40 @@ impl<'a> VecOperator<'a> for MergeAggregate {
     }
 }
 
-fn merge_aggregate(ops: &[MergeOp], left: &[i64], right: &[i64], aggregator: Aggregator) -> Result<Vec<i64>, QueryError> {
+/// Merges wide partial sums, see `WideSum`.
+#[derive(Debug)]
+pub struct MergeWideSums {
+    pub merge_ops: BufferRef<MergeOp>,
+    pub left: BufferRef<i128>,
+    pub right: BufferRef<i128>,
+    pub merged: BufferRef<i128>,
+}
+
+impl<'a> VecOperator<'a> for MergeWideSums {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let merged = {
+            let ops = scratchpad.get(self.merge_ops);
+            let left = scratchpad.get(self.left);
+            let right = scratchpad.get(self.right);
+            merge_aggregate(&*ops, &*left, &*right, |accumulator, elem| Ok(accumulator + elem))
+        };
+        scratchpad.set(self.merged, merged?);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.left.any(), self.right.any(), self.merge_ops.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.merged.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("merge_wide_sums({}, {}, {})", self.merge_ops, self.left, self.right)
+    }
+}
+
+fn merge_aggregate<T, F>(ops: &[MergeOp], left: &[T], right: &[T], combine: F) -> Result<Vec<T>, QueryError>
+    where T: Copy, F: Fn(T, T) -> Result<T, QueryError> {
     let mut result = Vec::with_capacity(ops.len());
     let mut i = 0;
     let mut j = 0;
@@ -54,7 +88,7 @@ fn merge_aggregate(ops: &[MergeOp], left: &[i64], right: &[i64], aggregator: Agg
             }
             MergeOp::MergeRight => {
                 let last = result.len() - 1;
-                result[last] = aggregator.combine_i64(result[last], right[j])?;
+                result[last] = combine(result[last], right[j])?;
                 j += 1;
             }
         }
@@ -6,10 +6,13 @@ pub mod vector_operator;
 pub mod comparator;
 
 mod aggregate;
+mod approx_count_distinct;
 mod assemble_nullable;
 mod binary_operator;
 mod bit_unpack;
 mod bool_op;
+mod bools_to_null_map;
+mod case_fold;
 mod column_ops;
 mod combine_null_maps;
 mod compact;
@@ -17,11 +20,13 @@ mod comparison_operators;
 mod constant;
 mod constant_expand;
 mod constant_vec;
+mod count_selected;
 mod delta_decode;
 mod dict_lookup;
 mod encode_const;
 mod exists;
 mod filter;
+mod frame_of_reference_decode;
 mod functions;
 mod fuse_nulls;
 mod get_null_map;
@@ -30,6 +35,7 @@ mod hashmap_grouping_byte_slices;
 mod hashmap_grouping_val_rows;
 mod identity;
 mod indices;
+mod int_to_string;
 mod is_null;
 mod make_nullable;
 mod map_operator;
@@ -45,22 +51,36 @@ mod null_vec;
 mod numeric_operators;
 mod parameterized_vec_vec_int_op;
 mod propagate_nullability;
+mod row_numbers;
+mod run_length_decode;
+mod sample_keys;
+mod sample_mask;
 mod scalar_i64;
 mod scalar_str;
 mod select;
+mod short_circuit;
+mod simd_comparison;
 mod sort_by;
 mod sort_by_slices;
 mod sort_by_val_rows;
+mod sort_grouping;
+mod sorted_range;
+mod strided_mask;
 mod to_val;
 mod top_n;
 mod type_conversion;
 mod unhexpack_strings;
+mod unpack_bits;
+mod unpack_ints;
 mod unpack_strings;
 mod val_rows_pack;
 mod val_rows_unpack;
 #[cfg(feature = "enable_lz4")]
 mod lz4_decode;
+#[cfg(feature = "enable_zstd")]
+mod zstd_decode;
 mod merge_deduplicate_partitioned;
+mod parse_int;
 mod partition;
 mod subpartition;
 mod slice_pack;
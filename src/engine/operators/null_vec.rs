@@ -1,3 +1,4 @@
+use bitvec::BitVec;
 use engine::*;
 
 #[derive(Debug)]
@@ -20,3 +21,115 @@ impl<'a> VecOperator<'a> for NullVec {
     fn allocates(&self) -> bool { false }
     fn display_op(&self, _: bool) -> String { format!("NullVec") }
 }
+
+/// Filters a vector of nulls, which only requires counting the selected elements.
+#[derive(Debug)]
+pub struct FilterNulls {
+    pub filter: BufferRef<u8>,
+    pub output: BufferRef<Any>,
+}
+
+impl<'a> VecOperator<'a> for FilterNulls {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let selected = scratchpad.get(self.filter).iter().filter(|&&select| select > 0).count();
+        append_nulls(self.output, selected, stream, scratchpad);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_any(self.output, Data::empty(0));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.filter.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { false }
+    fn display_op(&self, _: bool) -> String { format!("NullVec[{}]", self.filter) }
+}
+
+/// Filters a vector of nulls by the complement of the filter.
+#[derive(Debug)]
+pub struct FilterNotNulls {
+    pub filter: BufferRef<u8>,
+    pub output: BufferRef<Any>,
+}
+
+impl<'a> VecOperator<'a> for FilterNotNulls {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let selected = scratchpad.get(self.filter).iter().filter(|&&reject| reject == 0).count();
+        append_nulls(self.output, selected, stream, scratchpad);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_any(self.output, Data::empty(0));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.filter.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { false }
+    fn display_op(&self, _: bool) -> String { format!("NullVec[!{}]", self.filter) }
+}
+
+#[derive(Debug)]
+pub struct NullableFilterNulls {
+    pub filter: BufferRef<Nullable<u8>>,
+    pub output: BufferRef<Any>,
+}
+
+impl<'a> VecOperator<'a> for NullableFilterNulls {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let selected = {
+            let (filter, present) = scratchpad.get_nullable(self.filter);
+            (0..filter.len()).filter(|&i| filter[i] > 0 && (&*present).is_set(i)).count()
+        };
+        append_nulls(self.output, selected, stream, scratchpad);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_any(self.output, Data::empty(0));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.filter.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { false }
+    fn display_op(&self, _: bool) -> String { format!("NullVec[{}]", self.filter) }
+}
+
+/// Selects elements from a vector of nulls, which yields one null for each index.
+#[derive(Debug)]
+pub struct SelectNulls {
+    pub indices: BufferRef<usize>,
+    pub output: BufferRef<Any>,
+}
+
+impl<'a> VecOperator<'a> for SelectNulls {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let selected = scratchpad.get(self.indices).len();
+        append_nulls(self.output, selected, stream, scratchpad);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_any(self.output, Data::empty(0));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.indices.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { false }
+    fn display_op(&self, _: bool) -> String { format!("NullVec[{}]", self.indices) }
+}
+
+/// Sets `output` to `count` nulls, in addition to the existing nulls unless streaming.
+fn append_nulls<'a>(output: BufferRef<Any>, count: usize, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+    let len = if stream { count } else { scratchpad.get_any(output).len() + count };
+    scratchpad.set_any(output, Data::empty(len));
+}
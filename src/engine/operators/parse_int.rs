@@ -0,0 +1,44 @@
+use bitvec::*;
+use engine::*;
+
+/// Parses strings as base 10 integers, strings that are not valid integers become null.
+#[derive(Debug)]
+pub struct ParseInt<'a> {
+    pub input: BufferRef<&'a str>,
+    pub output: BufferRef<Nullable<i64>>,
+}
+
+impl<'a> VecOperator<'a> for ParseInt<'a> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let input = scratchpad.get(self.input);
+        let (mut data, mut present) = scratchpad.get_mut_nullable(self.output);
+        if stream {
+            data.clear();
+            present.clear();
+        }
+        for (i, s) in input.iter().enumerate() {
+            match s.trim().parse::<i64>() {
+                Ok(value) => {
+                    data.push(value);
+                    present.set(i);
+                }
+                Err(_) => data.push(0),
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set_nullable(self.output, Vec::with_capacity(batch_size), Vec::with_capacity(batch_size / 8 + 1));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("parse_int({})", self.input)
+    }
+}
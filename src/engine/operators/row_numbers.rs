@@ -0,0 +1,41 @@
+use std::cmp;
+
+use engine::*;
+
+#[derive(Debug)]
+pub struct RowNumbers {
+    pub offset: usize,
+    pub len: usize,
+    pub output: BufferRef<i64>,
+
+    pub current_index: usize,
+    pub batch_size: usize,
+}
+
+impl<'a> VecOperator<'a> for RowNumbers {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let mut output = scratchpad.get_mut(self.output);
+        output.clear();
+        let end = cmp::min(self.current_index + self.batch_size, self.len);
+        output.extend((self.current_index..end).map(|i| (self.offset + i) as i64));
+        self.current_index = end;
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        self.batch_size = batch_size;
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+    fn is_streaming_producer(&self) -> bool { true }
+    fn has_more(&self) -> bool { self.current_index < self.len }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("rownum({}..{})", self.offset, self.offset + self.len)
+    }
+}
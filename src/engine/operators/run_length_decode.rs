@@ -0,0 +1,39 @@
+use engine::*;
+
+
+#[derive(Debug)]
+pub struct RunLengthDecode<T> {
+    pub values: BufferRef<T>,
+    pub run_lengths: BufferRef<u32>,
+    pub len: usize,
+    pub decoded: BufferRef<T>,
+}
+
+impl<'a, T: GenericIntVec<T>> VecOperator<'a> for RunLengthDecode<T> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let values = scratchpad.get(self.values);
+        let run_lengths = scratchpad.get(self.run_lengths);
+        let mut decoded = scratchpad.get_mut(self.decoded);
+        for (&value, &run_length) in values.iter().zip(run_lengths.iter()) {
+            for _ in 0..run_length {
+                decoded.push(value);
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.decoded, Vec::with_capacity(self.len));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.values.any(), self.run_lengths.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.decoded.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+    fn custom_output_len(&self) -> Option<usize> { Some(self.len) }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("run_length_decode({}, {})", self.values, self.run_lengths)
+    }
+}
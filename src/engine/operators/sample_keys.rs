@@ -0,0 +1,47 @@
+use std::cmp;
+
+use engine::*;
+use super::sample_mask::mix;
+
+#[derive(Debug)]
+pub struct SampleKeys {
+    pub offset: usize,
+    pub len: usize,
+    pub seed: u64,
+    pub output: BufferRef<i64>,
+
+    pub current_index: usize,
+    pub batch_size: usize,
+}
+
+impl<'a> VecOperator<'a> for SampleKeys {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let mut output = scratchpad.get_mut(self.output);
+        output.clear();
+        let end = cmp::min(self.current_index + self.batch_size, self.len);
+        for i in self.current_index..end {
+            // Keys are derived from the row number so that the sample does not depend on how rows are partitioned
+            let row = (self.offset + i) as u64;
+            output.push((mix(self.seed.wrapping_add(mix(row))) >> 1) as i64);
+        }
+        self.current_index = end;
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        self.batch_size = batch_size;
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+    fn is_streaming_producer(&self) -> bool { true }
+    fn has_more(&self) -> bool { self.current_index < self.len }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("sample_keys({})", self.seed)
+    }
+}
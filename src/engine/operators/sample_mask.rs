@@ -0,0 +1,58 @@
+use std::cmp;
+
+use engine::*;
+use syntax::sample::SampledRows;
+
+#[derive(Debug)]
+pub struct SampleMask {
+    pub offset: usize,
+    pub len: usize,
+    pub rows: SampledRows,
+    pub output: BufferRef<u8>,
+
+    pub current_index: usize,
+    pub batch_size: usize,
+}
+
+impl<'a> VecOperator<'a> for SampleMask {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let mut output = scratchpad.get_mut(self.output);
+        output.clear();
+        let end = cmp::min(self.current_index + self.batch_size, self.len);
+        for i in self.current_index..end {
+            let row = (self.offset + i) as u64;
+            let sampled = match self.rows.threshold {
+                Some(threshold) => mix(self.rows.seed.wrapping_add(mix(row))) < threshold,
+                None => true,
+            };
+            output.push(sampled as u8);
+        }
+        self.current_index = end;
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        self.batch_size = batch_size;
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+    fn is_streaming_producer(&self) -> bool { true }
+    fn has_more(&self) -> bool { self.current_index < self.len }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("sample({:.2}% of {} rows)", self.rows.probability() * 100.0, self.len)
+    }
+}
+
+/// SplitMix64 finalizer, cheap hash with good avalanche properties.
+pub fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,98 @@
+use engine::*;
+use bitvec::BitVec;
+
+
+/// Combines `select` with `condition`, which only holds an entry for each row that is selected by `select`, or for
+/// each row that is not selected by `select` if `disjunction` is set.
+pub struct ShortCircuit {
+    pub select: BufferRef<u8>,
+    pub condition: BufferRef<u8>,
+    pub disjunction: bool,
+    /// Index of the entry of `condition` that corresponds to the next row that has to be combined with it.
+    pub position: usize,
+    pub output: BufferRef<u8>,
+}
+
+impl<'a> VecOperator<'a> for ShortCircuit {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let select = scratchpad.get(self.select);
+        let condition = scratchpad.get(self.condition);
+        let mut output = scratchpad.get_mut(self.output);
+        if stream { output.clear(); }
+        for &selected in select.iter() {
+            if (selected != 0) == self.disjunction {
+                output.push(self.disjunction as u8);
+            } else {
+                output.push((condition[self.position] != 0) as u8);
+                self.position += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        self.position = 0;
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.select.any(), self.condition.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, buffer: usize) -> bool { buffer == self.select.i }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        if self.disjunction {
+            format!("{} || {}[!{}]", self.select, self.condition, self.select)
+        } else {
+            format!("{} && {}[{}]", self.select, self.condition, self.select)
+        }
+    }
+}
+
+pub struct NullableShortCircuit {
+    pub select: BufferRef<u8>,
+    pub condition: BufferRef<Nullable<u8>>,
+    pub disjunction: bool,
+    pub position: usize,
+    pub output: BufferRef<u8>,
+}
+
+impl<'a> VecOperator<'a> for NullableShortCircuit {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let select = scratchpad.get(self.select);
+        let (condition, present) = scratchpad.get_nullable(self.condition);
+        let mut output = scratchpad.get_mut(self.output);
+        if stream { output.clear(); }
+        for &selected in select.iter() {
+            if (selected != 0) == self.disjunction {
+                output.push(self.disjunction as u8);
+            } else {
+                // Rows for which the condition is null are not selected
+                let holds = condition[self.position] != 0 && (&*present).is_set(self.position);
+                output.push(holds as u8);
+                self.position += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        self.position = 0;
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.select.any(), self.condition.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, buffer: usize) -> bool { buffer == self.select.i }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        if self.disjunction {
+            format!("{} || {}[!{}]", self.select, self.condition, self.select)
+        } else {
+            format!("{} && {}[{}]", self.select, self.condition, self.select)
+        }
+    }
+}
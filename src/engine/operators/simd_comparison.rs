@@ -0,0 +1,282 @@
+use engine::*;
+
+/// Comparison of an integer column with a constant, evaluated with SIMD instructions where available.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanEquals,
+    GreaterThan,
+    GreaterThanEquals,
+}
+
+impl Comparison {
+    /// Reduces the comparison to one of the three kernels and whether the result of the kernel has to be negated.
+    fn kernel(self) -> (Kernel, bool) {
+        match self {
+            Comparison::Equals => (Kernel::Equals, false),
+            Comparison::NotEquals => (Kernel::Equals, true),
+            Comparison::LessThanEquals => (Kernel::LessThanEquals, false),
+            Comparison::GreaterThan => (Kernel::LessThanEquals, true),
+            Comparison::GreaterThanEquals => (Kernel::GreaterThanEquals, false),
+            Comparison::LessThan => (Kernel::GreaterThanEquals, true),
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Equals => "=",
+            Comparison::NotEquals => "<>",
+            Comparison::LessThan => "<",
+            Comparison::LessThanEquals => "<=",
+            Comparison::GreaterThan => ">",
+            Comparison::GreaterThanEquals => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kernel {
+    Equals,
+    LessThanEquals,
+    GreaterThanEquals,
+}
+
+pub struct SimdCompareVS<T> {
+    pub lhs: BufferRef<T>,
+    pub rhs: BufferRef<Scalar<i64>>,
+    pub output: BufferRef<u8>,
+    pub comparison: Comparison,
+}
+
+impl<'a, T: VecData<T> + SimdCompare + 'a> VecOperator<'a> for SimdCompareVS<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let lhs = scratchpad.get(self.lhs);
+        let rhs = scratchpad.get_scalar(&self.rhs);
+        let mut output = scratchpad.get_mut(self.output);
+        if stream { output.clear(); }
+        let start = output.len();
+        output.resize(start + lhs.len(), 0);
+        compare(&lhs, rhs, self.comparison, &mut output[start..]);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.lhs.any(), self.rhs.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { true }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {} [simd]", self.lhs, self.comparison.symbol(), self.rhs)
+    }
+}
+
+fn compare<T: SimdCompare>(data: &[T], constant: i64, comparison: Comparison, output: &mut [u8]) {
+    let (kernel, negate) = comparison.kernel();
+    // Constants outside of the range of `T` give the same result for all elements
+    let uniform = match kernel {
+        Kernel::Equals if constant < 0 || constant > T::MAX => Some(false),
+        Kernel::LessThanEquals if constant < 0 => Some(false),
+        Kernel::LessThanEquals if constant >= T::MAX => Some(true),
+        Kernel::GreaterThanEquals if constant <= 0 => Some(true),
+        Kernel::GreaterThanEquals if constant > T::MAX => Some(false),
+        _ => None,
+    };
+    match uniform {
+        Some(result) => {
+            let value = (result ^ negate) as u8;
+            for o in output.iter_mut() {
+                *o = value;
+            }
+        }
+        None => T::compare(data, T::from_i64(constant), kernel, negate, output),
+    }
+}
+
+pub trait SimdCompare: Copy + PartialOrd + Sized {
+    const MAX: i64;
+    /// Converts `value`, which has to be within the range of `Self`.
+    fn from_i64(value: i64) -> Self;
+    fn compare(data: &[Self], constant: Self, kernel: Kernel, negate: bool, output: &mut [u8]);
+}
+
+// Without SIMD support, the comparisons are performed by loops that the compiler is usually able to vectorize
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+macro_rules! simd_compare {
+    ($t:ty, $max:expr, $avx2:ident) => {
+        impl SimdCompare for $t {
+            const MAX: i64 = $max as i64;
+            fn from_i64(value: i64) -> $t { value as $t }
+            fn compare(data: &[$t], constant: $t, kernel: Kernel, negate: bool, output: &mut [u8]) {
+                compare_scalar(data, constant, kernel, negate, output);
+            }
+        }
+    };
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+macro_rules! simd_compare {
+    ($t:ty, $max:expr, $avx2:ident) => {
+        impl SimdCompare for $t {
+            const MAX: i64 = $max as i64;
+            fn from_i64(value: i64) -> $t { value as $t }
+            fn compare(data: &[$t], constant: $t, kernel: Kernel, negate: bool, output: &mut [u8]) {
+                if is_x86_feature_detected!("avx2") {
+                    let processed = unsafe { avx2::$avx2(data, constant, kernel, negate, output) };
+                    compare_scalar(&data[processed..], constant, kernel, negate, &mut output[processed..]);
+                } else {
+                    compare_scalar(data, constant, kernel, negate, output);
+                }
+            }
+        }
+    };
+}
+
+simd_compare!(u8, ::std::u8::MAX, compare_u8);
+simd_compare!(u16, ::std::u16::MAX, compare_u16);
+simd_compare!(u32, ::std::u32::MAX, compare_u32);
+
+fn compare_scalar<T: Copy + PartialOrd>(data: &[T], constant: T, kernel: Kernel, negate: bool, output: &mut [u8]) {
+    let negate = negate as u8;
+    match kernel {
+        Kernel::Equals => for (o, &x) in output.iter_mut().zip(data) {
+            *o = (x == constant) as u8 ^ negate
+        },
+        Kernel::LessThanEquals => for (o, &x) in output.iter_mut().zip(data) {
+            *o = (x <= constant) as u8 ^ negate
+        },
+        Kernel::GreaterThanEquals => for (o, &x) in output.iter_mut().zip(data) {
+            *o = (x >= constant) as u8 ^ negate
+        },
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::Kernel;
+
+    // AVX2 only has signed comparisons, unsigned `<=`/`>=` are computed as `min(x, c) == x`/`max(x, c) == x`
+    macro_rules! kernel {
+        ($x:expr, $c:expr, $kernel:expr, $cmpeq:ident, $min:ident, $max:ident) => {
+            match $kernel {
+                Kernel::Equals => $cmpeq($x, $c),
+                Kernel::LessThanEquals => $cmpeq($min($x, $c), $x),
+                Kernel::GreaterThanEquals => $cmpeq($max($x, $c), $x),
+            }
+        };
+    }
+
+    /// Turns a mask of all ones/zeros per byte into 1/0 and stores it at `output[i..i + 32]`.
+    #[target_feature(enable = "avx2")]
+    unsafe fn store_mask(mask: __m256i, negate: bool, output: &mut [u8], i: usize) {
+        let mask = if negate { _mm256_xor_si256(mask, _mm256_set1_epi8(-1)) } else { mask };
+        let result = _mm256_and_si256(mask, _mm256_set1_epi8(1));
+        _mm256_storeu_si256(output.as_mut_ptr().add(i) as *mut __m256i, result);
+    }
+
+    /// Returns the number of elements that were processed, the remainder has to be handled by the caller.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn compare_u8(data: &[u8], constant: u8, kernel: Kernel, negate: bool, output: &mut [u8]) -> usize {
+        let c = _mm256_set1_epi8(constant as i8);
+        let len = data.len() - data.len() % 32;
+        let mut i = 0;
+        while i < len {
+            let x = _mm256_loadu_si256(data.as_ptr().add(i) as *const __m256i);
+            let mask = kernel!(x, c, kernel, _mm256_cmpeq_epi8, _mm256_min_epu8, _mm256_max_epu8);
+            store_mask(mask, negate, output, i);
+            i += 32;
+        }
+        len
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn compare_u16(data: &[u16], constant: u16, kernel: Kernel, negate: bool, output: &mut [u8]) -> usize {
+        let c = _mm256_set1_epi16(constant as i16);
+        let len = data.len() - data.len() % 32;
+        let mut i = 0;
+        while i < len {
+            let x0 = _mm256_loadu_si256(data.as_ptr().add(i) as *const __m256i);
+            let x1 = _mm256_loadu_si256(data.as_ptr().add(i + 16) as *const __m256i);
+            let mask0 = kernel!(x0, c, kernel, _mm256_cmpeq_epi16, _mm256_min_epu16, _mm256_max_epu16);
+            let mask1 = kernel!(x1, c, kernel, _mm256_cmpeq_epi16, _mm256_min_epu16, _mm256_max_epu16);
+            // Packing operates on 128 bit lanes, which interleaves the 64 bit blocks of both masks
+            let packed = _mm256_packs_epi16(mask0, mask1);
+            let mask = _mm256_permute4x64_epi64(packed, 0b11_01_10_00);
+            store_mask(mask, negate, output, i);
+            i += 32;
+        }
+        len
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn compare_u32(data: &[u32], constant: u32, kernel: Kernel, negate: bool, output: &mut [u8]) -> usize {
+        let c = _mm256_set1_epi32(constant as i32);
+        let order = _mm256_setr_epi32(0, 4, 1, 5, 2, 6, 3, 7);
+        let len = data.len() - data.len() % 32;
+        let mut i = 0;
+        while i < len {
+            let mut masks = [_mm256_setzero_si256(); 4];
+            for (j, mask) in masks.iter_mut().enumerate() {
+                let x = _mm256_loadu_si256(data.as_ptr().add(i + 8 * j) as *const __m256i);
+                *mask = kernel!(x, c, kernel, _mm256_cmpeq_epi32, _mm256_min_epu32, _mm256_max_epu32);
+            }
+            let packed = _mm256_packs_epi16(_mm256_packs_epi32(masks[0], masks[1]),
+                                            _mm256_packs_epi32(masks[2], masks[3]));
+            let mask = _mm256_permutevar8x32_epi32(packed, order);
+            store_mask(mask, negate, output, i);
+            i += 32;
+        }
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<T: SimdCompare + Into<i64>>(data: &[T], constant: i64) {
+        for &comparison in &[Comparison::Equals, Comparison::NotEquals, Comparison::LessThan,
+            Comparison::LessThanEquals, Comparison::GreaterThan, Comparison::GreaterThanEquals] {
+            let mut output = vec![0; data.len()];
+            compare(data, constant, comparison, &mut output);
+            let expected = data.iter()
+                .map(|&x| {
+                    let x: i64 = x.into();
+                    (match comparison {
+                        Comparison::Equals => x == constant,
+                        Comparison::NotEquals => x != constant,
+                        Comparison::LessThan => x < constant,
+                        Comparison::LessThanEquals => x <= constant,
+                        Comparison::GreaterThan => x > constant,
+                        Comparison::GreaterThanEquals => x >= constant,
+                    }) as u8
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(output, expected, "{:?} {}", comparison, constant);
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let bytes = (0..100i64).map(|i| (i * 37 % 256) as u8).collect::<Vec<_>>();
+        for &c in &[-1, 0, 37, 128, 255, 256] {
+            check(&bytes, c);
+        }
+        let shorts = (0..100i64).map(|i| (i * 4099 % 65536) as u16).collect::<Vec<_>>();
+        for &c in &[-1, 0, 4099, 40_000, 65_535, 65_536] {
+            check(&shorts, c);
+        }
+        let ints = (0..100i64).map(|i| (i * 123_456_789 % 4_294_967_296) as u32).collect::<Vec<_>>();
+        for &c in &[-1, 0, 123_456_789, 3_000_000_000, 4_294_967_295, 4_294_967_296] {
+            check(&ints, c);
+        }
+    }
+}
@@ -48,6 +48,7 @@ pub struct SortByNullable<T> {
     pub output: BufferRef<usize>,
     pub descending: bool,
     pub stable: bool,
+    pub nulls_first: bool,
 }
 
 impl<'a, T: VecData<T> + 'a> VecOperator<'a> for SortByNullable<T> {
@@ -56,19 +57,21 @@ impl<'a, T: VecData<T> + 'a> VecOperator<'a> for SortByNullable<T> {
         let (ranking, ranking_present) = scratchpad.get_nullable(self.ranking);
         let present = &*ranking_present;
         let mut indices = scratchpad.get_mut(self.indices);
+        // Position of null values relative to present values, independent of sort direction
+        let nulls = if self.nulls_first { Ordering::Less } else { Ordering::Greater };
         if self.descending {
             if self.stable {
                 indices.sort_by(|&j, &i| match (present.is_set(i), present.is_set(j)) {
                     (true, true) => ranking[i].cmp(&ranking[j]),
-                    (false, true) => Ordering::Less,
-                    (true, false) => Ordering::Greater,
+                    (false, true) => nulls.reverse(),
+                    (true, false) => nulls,
                     (false, false) => Ordering::Equal,
                 })
             } else {
                 indices.sort_unstable_by(|&j, &i| match (present.is_set(i), present.is_set(j)) {
                     (true, true) => ranking[i].cmp(&ranking[j]),
-                    (false, true) => Ordering::Less,
-                    (true, false) => Ordering::Greater,
+                    (false, true) => nulls.reverse(),
+                    (true, false) => nulls,
                     (false, false) => Ordering::Equal,
                 })
             }
@@ -76,15 +79,15 @@ impl<'a, T: VecData<T> + 'a> VecOperator<'a> for SortByNullable<T> {
             if self.stable {
                 indices.sort_by(|&i, &j| match (present.is_set(i), present.is_set(j)) {
                     (true, true) => ranking[i].cmp(&ranking[j]),
-                    (false, true) => Ordering::Less,
-                    (true, false) => Ordering::Greater,
+                    (false, true) => nulls,
+                    (true, false) => nulls.reverse(),
                     (false, false) => Ordering::Equal,
                 })
             } else {
                 indices.sort_unstable_by(|&i, &j| match (present.is_set(i), present.is_set(j)) {
                     (true, true) => ranking[i].cmp(&ranking[j]),
-                    (false, true) => Ordering::Less,
-                    (true, false) => Ordering::Greater,
+                    (false, true) => nulls,
+                    (true, false) => nulls.reverse(),
                     (false, false) => Ordering::Equal,
                 })
             }
@@ -99,6 +102,6 @@ impl<'a, T: VecData<T> + 'a> VecOperator<'a> for SortByNullable<T> {
     fn allocates(&self) -> bool { true }
 
     fn display_op(&self, _: bool) -> String {
-        format!("sort_by({}, {}; desc={}, stable={})", self.ranking, self.indices, self.descending, self.stable)
+        format!("sort_by({}, {}; desc={}, stable={}, nulls_first={})", self.ranking, self.indices, self.descending, self.stable, self.nulls_first)
     }
 }
@@ -0,0 +1,63 @@
+use engine::*;
+use ingest::raw_val::RawVal;
+
+/// Assigns each row the rank of its value among the distinct values of the grouping key, so unlike the hash map
+/// grouping the grouping key is order preserving.
+/// Values that are equal to the value in the previous row reuse its rank, which makes sorted input cheap to group.
+#[derive(Debug)]
+pub struct SortGrouping<T> {
+    input: BufferRef<T>,
+    unique_out: BufferRef<T>,
+    grouping_key_out: BufferRef<u32>,
+    cardinality_out: BufferRef<Scalar<i64>>,
+}
+
+impl<'a, T: VecData<T> + 'a> SortGrouping<T> {
+    pub fn boxed(input: BufferRef<T>,
+                 unique_out: BufferRef<T>,
+                 grouping_key_out: BufferRef<u32>,
+                 cardinality_out: BufferRef<Scalar<i64>>) -> BoxedOperator<'a> {
+        Box::new(SortGrouping::<T> {
+            input,
+            unique_out,
+            grouping_key_out,
+            cardinality_out,
+        })
+    }
+}
+
+impl<'a, T: VecData<T> + 'a> VecOperator<'a> for SortGrouping<T> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let (unique, grouping) = {
+            let raw_grouping_key = scratchpad.get(self.input);
+            let mut unique = raw_grouping_key.to_vec();
+            unique.sort_unstable();
+            unique.dedup();
+            let mut grouping = Vec::with_capacity(raw_grouping_key.len());
+            let mut previous = None;
+            for &value in raw_grouping_key.iter() {
+                let rank = match previous {
+                    Some((previous_value, rank)) if previous_value == value => rank,
+                    _ => unique.binary_search(&value).unwrap() as u32,
+                };
+                grouping.push(rank);
+                previous = Some((value, rank));
+            }
+            (unique, grouping)
+        };
+        scratchpad.set_any(self.cardinality_out.any(), Data::constant(RawVal::Int(unique.len() as i64)));
+        scratchpad.set(self.unique_out, unique);
+        scratchpad.set(self.grouping_key_out, grouping);
+        Ok(())
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.unique_out.any(), self.grouping_key_out.any(), self.cardinality_out.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("sort_grouping({})", self.input)
+    }
+}
@@ -0,0 +1,72 @@
+use std::cmp;
+use std::cmp::Ordering;
+
+use engine::*;
+
+/// Outputs a vector that is 1 for the elements of the sorted `input` that lie in `[lower, upper]`.
+/// Matching elements form a contiguous range, so only the bounds of the range are computed by binary search instead
+/// of comparing each element. The mask is streamed, since the consumers of the mask usually stream the column that
+/// is filtered.
+#[derive(Debug)]
+pub struct SortedRange<T> {
+    pub input: BufferRef<T>,
+    pub lower: i64,
+    pub upper: i64,
+    pub output: BufferRef<u8>,
+
+    pub range: Option<(usize, usize)>,
+    pub len: usize,
+    pub current_index: usize,
+    pub batch_size: usize,
+}
+
+impl<'a, T: GenericIntVec<T>> VecOperator<'a> for SortedRange<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        if self.range.is_none() {
+            let input = scratchpad.get(self.input);
+            // Comparisons never return `Equal`, so both searches return the index at which the bound would be inserted
+            let (lower, upper) = (self.lower, self.upper);
+            let start = input.binary_search_by(|x| match x.to_i64() {
+                Some(x) if x < lower => Ordering::Less,
+                _ => Ordering::Greater,
+            }).unwrap_err();
+            let end = input.binary_search_by(|x| match x.to_i64() {
+                Some(x) if x <= upper => Ordering::Less,
+                _ => Ordering::Greater,
+            }).unwrap_err();
+            self.range = Some((start, end));
+            self.len = input.len();
+        }
+        let (start, end) = self.range.unwrap();
+        let (batch_start, batch_end) = if stream {
+            (self.current_index, cmp::min(self.current_index + self.batch_size, self.len))
+        } else {
+            (0, self.len)
+        };
+        let mut output = scratchpad.get_mut(self.output);
+        output.clear();
+        output.resize(batch_end - batch_start, 0);
+        for i in cmp::max(start, batch_start)..cmp::min(end, batch_end) {
+            output[i - batch_start] = 1;
+        }
+        self.current_index = batch_end;
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        self.batch_size = batch_size;
+        scratchpad.set(self.output, Vec::with_capacity(batch_size));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.output.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { true }
+    fn allocates(&self) -> bool { true }
+    fn is_streaming_producer(&self) -> bool { true }
+    fn has_more(&self) -> bool { self.range.is_none() || self.current_index < self.len }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} in [{}, {}] (sorted)", self.input, self.lower, self.upper)
+    }
+}
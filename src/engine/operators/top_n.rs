@@ -56,7 +56,7 @@ impl<'a, T: VecData<T> + 'a, C: Comparator<T> + fmt::Debug> VecOperator<'a> for
         Ok(())
     }
 
-    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
         let output = {
             let indices = scratchpad.get_mut(self.indices);
             let keys = scratchpad.get_mut(self.keys);
@@ -73,6 +73,7 @@ impl<'a, T: VecData<T> + 'a, C: Comparator<T> + fmt::Debug> VecOperator<'a> for
             output
         };
         scratchpad.set(self.indices, output);
+        Ok(())
     }
 
     fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.input.any()] }
@@ -0,0 +1,36 @@
+use bitvec::*;
+use engine::*;
+
+
+#[derive(Debug)]
+pub struct UnpackBits {
+    pub bits: BufferRef<u8>,
+    pub len: usize,
+    pub unpacked: BufferRef<u8>,
+}
+
+impl<'a> VecOperator<'a> for UnpackBits {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let bits = scratchpad.get(self.bits);
+        let mut unpacked = scratchpad.get_mut(self.unpacked);
+        for i in 0..self.len {
+            unpacked.push((&*bits).is_set(i) as u8);
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.unpacked, Vec::with_capacity(self.len));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.bits.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.unpacked.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+    fn custom_output_len(&self) -> Option<usize> { Some(self.len) }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("unpack_bits({})", self.bits)
+    }
+}
@@ -0,0 +1,44 @@
+use engine::*;
+
+
+#[derive(Debug)]
+pub struct UnpackInts {
+    pub packed: BufferRef<u8>,
+    pub bits: usize,
+    pub len: usize,
+    pub unpacked: BufferRef<u8>,
+}
+
+impl<'a> VecOperator<'a> for UnpackInts {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let packed = scratchpad.get(self.packed);
+        let mut unpacked = scratchpad.get_mut(self.unpacked);
+        let mask = (1u16 << self.bits) - 1;
+        for i in 0..self.len {
+            // Integers have at most 8 bits and so span at most two bytes
+            let offset = i * self.bits;
+            let byte = offset / 8;
+            let mut window = u16::from(packed[byte]);
+            if byte + 1 < packed.len() {
+                window |= u16::from(packed[byte + 1]) << 8;
+            }
+            unpacked.push(((window >> (offset % 8)) & mask) as u8);
+        }
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.unpacked, Vec::with_capacity(self.len));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.packed.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.unpacked.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+    fn custom_output_len(&self) -> Option<usize> { Some(self.len) }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("unpack_ints({}, {})", self.packed, self.bits)
+    }
+}
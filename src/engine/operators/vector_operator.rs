@@ -11,24 +11,30 @@ use std::fmt::Write;
 use std::intrinsics::type_name;
 use std::marker::PhantomData;
 use std::result::Result;
+use syntax::sample::SampledRows;
 
 use super::aggregate::*;
+use super::approx_count_distinct::*;
 use super::assemble_nullable::AssembleNullable;
 use super::binary_operator::*;
 use super::bit_unpack::BitUnpackOperator;
 use super::bool_op::*;
+use super::bools_to_null_map::*;
 use super::column_ops::*;
 use super::combine_null_maps::CombineNullMaps;
 use super::compact::Compact;
+use super::case_fold::CaseFold;
 use super::comparison_operators::*;
 use super::constant::Constant;
 use super::constant_expand::ConstantExpand;
 use super::constant_vec::ConstantVec;
+use super::count_selected::CountSelected;
 use super::delta_decode::*;
 use super::dict_lookup::*;
 use super::encode_const::*;
 use super::exists::Exists;
-use super::filter::{Filter, NullableFilter};
+use super::filter::{Filter, FilterNot, NullableFilter};
+use super::frame_of_reference_decode::FrameOfReferenceDecode;
 use super::functions::*;
 use super::fuse_nulls::*;
 use super::get_null_map::GetNullMap;
@@ -37,11 +43,12 @@ use super::hashmap_grouping_byte_slices::HashMapGroupingByteSlices;
 use super::hashmap_grouping_val_rows::HashMapGroupingValRows;
 use super::identity::Identity;
 use super::indices::Indices;
+use super::int_to_string::IntToString;
 use super::is_null::*;
 use super::make_nullable::MakeNullable;
 use super::map_operator::MapOperator;
 use super::merge::Merge;
-use super::merge_aggregate::MergeAggregate;
+use super::merge_aggregate::{MergeAggregate, MergeWideSums};
 use super::merge_deduplicate::MergeDeduplicate;
 use super::merge_deduplicate_partitioned::MergeDeduplicatePartitioned;
 use super::merge_drop::MergeDrop;
@@ -49,24 +56,36 @@ use super::merge_keep::*;
 use super::merge_partitioned::MergePartitioned;
 use super::nonzero_compact::NonzeroCompact;
 use super::nonzero_indices::NonzeroIndices;
-use super::null_vec::NullVec;
+use super::null_vec::*;
 use super::numeric_operators::*;
 use super::parameterized_vec_vec_int_op::*;
+use super::parse_int::ParseInt;
 use super::partition::Partition;
 use super::propagate_nullability::PropagateNullability;
+use super::row_numbers::RowNumbers;
+use super::run_length_decode::RunLengthDecode;
+use super::sample_keys::SampleKeys;
+use super::sample_mask::SampleMask;
 use super::scalar_i64::ScalarI64;
 use super::scalar_str::ScalarStr;
 use super::select::*;
+use super::short_circuit::*;
+use super::simd_comparison::*;
 use super::slice_pack::*;
 use super::slice_unpack::*;
 use super::sort_by::*;
 use super::sort_by_slices::SortBySlices;
 use super::sort_by_val_rows::SortByValRows;
+use super::sort_grouping::SortGrouping;
+use super::sorted_range::SortedRange;
+use super::strided_mask::StridedMask;
 use super::subpartition::SubPartition;
 use super::to_val::*;
 use super::top_n::TopN;
 use super::type_conversion::TypeConversionOperator;
 use super::unhexpack_strings::UnhexpackStrings;
+use super::unpack_bits::UnpackBits;
+use super::unpack_ints::UnpackInts;
 use super::unpack_strings::UnpackStrings;
 use super::val_rows_pack::*;
 use super::val_rows_unpack::*;
@@ -75,7 +94,8 @@ pub type BoxedOperator<'a> = Box<VecOperator<'a> + 'a>;
 
 pub trait VecOperator<'a> {
     fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError>;
-    fn finalize(&mut self, _scratchpad: &mut Scratchpad<'a>) {}
+    /// Called once after the last call to `execute` of the stage.
+    fn finalize(&mut self, _scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> { Ok(()) }
     fn init(&mut self, _total_count: usize, _batch_size: usize, _scratchpad: &mut Scratchpad<'a>) {}
 
     fn inputs(&self) -> Vec<BufferRef<Any>>;
@@ -87,6 +107,9 @@ pub trait VecOperator<'a> {
     fn is_streaming_producer(&self) -> bool { false }
     fn has_more(&self) -> bool { false }
     fn custom_output_len(&self) -> Option<usize> { None }
+    /// Number of bytes allocated on the heap by the operator itself rather than in its output buffers, e.g. for hash
+    /// tables that are retained between batches.
+    fn heap_size_of_state(&self) -> usize { 0 }
 
     fn display(&self, full: bool) -> String {
         let mut s = String::new();
@@ -109,6 +132,10 @@ fn short_type_name<T: ?Sized>() -> String {
     re.replace_all(full_name, "").into_owned()
 }
 
+fn compile_regex(r: &str) -> Result<Regex, QueryError> {
+    Regex::new(r).map_err(|e| QueryError::TypeError(format!("`{}` is not a valid regex: {}", r, e)))
+}
+
 
 impl<'a> VecOperator<'a> {
     pub fn read_column_data(colname: String,
@@ -129,6 +156,8 @@ impl<'a> VecOperator<'a> {
                     nullable_data: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
         match data.tag {
             EncodingType::U8 => Ok(Box::new(AssembleNullable { data: data.u8()?, present, nullable_data: nullable_data.nullable_u8()? })),
+            EncodingType::U16 => Ok(Box::new(AssembleNullable { data: data.u16()?, present, nullable_data: nullable_data.nullable_u16()? })),
+            EncodingType::U32 => Ok(Box::new(AssembleNullable { data: data.u32()?, present, nullable_data: nullable_data.nullable_u32()? })),
             EncodingType::I64 => Ok(Box::new(AssembleNullable { data: data.i64()?, present, nullable_data: nullable_data.nullable_i64()? })),
             EncodingType::Str => Ok(Box::new(AssembleNullable { data: data.str()?, present, nullable_data: nullable_data.nullable_str()? })),
             _ => Err(fatal!("nullable not implemented for type {:?}", data.tag)),
@@ -168,14 +197,30 @@ impl<'a> VecOperator<'a> {
         Box::new(GetNullMap { from: nullability, present })
     }
 
+    pub fn bools_to_null_map(bools: TypedBufferRef, present: BufferRef<u8>) -> Result<BoxedOperator<'a>, QueryError> {
+        if bools.is_nullable() {
+            Ok(Box::new(NullableBoolsToNullMap { bools: bools.nullable_u8()?, present }))
+        } else {
+            Ok(Box::new(BoolsToNullMap { bools: bools.u8()?, present }))
+        }
+    }
+
     pub fn fuse_nulls(input: TypedBufferRef, fused: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
         if input.tag == EncodingType::NullableI64 {
-            Ok(Box::new(FuseNullsI64 { input: input.nullable_i64()?, fused: fused.i64()? }))
+            Ok(Box::new(FuseNullsI64 { input: input.nullable_i64()?, fused: fused.i64()?, null: i64::MIN }))
         } else {
             Ok(Box::new(FuseNullsStr { input: input.nullable_str()?, fused: fused.opt_str()? }))
         }
     }
 
+    pub fn fuse_nulls_max(input: TypedBufferRef, fused: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        if input.tag == EncodingType::NullableI64 {
+            Ok(Box::new(FuseNullsI64 { input: input.nullable_i64()?, fused: fused.i64()?, null: i64::MAX }))
+        } else {
+            VecOperator::fuse_nulls(input, fused)
+        }
+    }
+
     pub fn fuse_int_nulls(offset: i64, input: TypedBufferRef, fused: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
         match fused.tag {
             EncodingType::U8 => Ok(Box::new(FuseIntNulls { offset: offset as u8, input: input.nullable_u8()?, fused: fused.u8()? })),
@@ -258,6 +303,33 @@ impl<'a> VecOperator<'a> {
         panic!("LZ4 is not enabled in this build of LocustDB. Recompile with `features enable_lz4`")
     }
 
+    #[cfg(feature = "enable_zstd")]
+    pub fn zstd_decode(encoded: BufferRef<u8>,
+                       decoded_len: usize,
+                       decoded: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        use super::zstd_decode::ZstdDecode;
+        reify_types! {
+            "zstd_decode";
+            decoded: Integer;
+            Ok(Box::new(ZstdDecode { encoded, decoded, decoded_len }))
+        }
+    }
+
+    #[cfg(not(feature = "enable_zstd"))]
+    pub fn zstd_decode(_: BufferRef<u8>,
+                       _: usize,
+                       _: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        panic!("Zstd is not enabled in this build of LocustDB. Recompile with `features enable_zstd`")
+    }
+
+    pub fn unpack_bits(bits: BufferRef<u8>, len: usize, unpacked: BufferRef<u8>) -> BoxedOperator<'a> {
+        Box::new(UnpackBits { bits, len, unpacked })
+    }
+
+    pub fn unpack_ints(packed: BufferRef<u8>, bits: usize, len: usize, unpacked: BufferRef<u8>) -> BoxedOperator<'a> {
+        Box::new(UnpackInts { packed, bits, len, unpacked })
+    }
+
     pub fn unpack_strings(packed: BufferRef<u8>, unpacked: BufferRef<&'a str>) -> BoxedOperator<'a> {
         Box::new(UnpackStrings::<'a> { packed, unpacked, iterator: None, has_more: true })
     }
@@ -278,6 +350,28 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn run_length_decode(values: TypedBufferRef,
+                             run_lengths: BufferRef<u32>,
+                             len: usize,
+                             decoded: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        reify_types! {
+            "run_length_decode";
+            values, decoded: Integer;
+            Ok(Box::new(RunLengthDecode { values, run_lengths, len, decoded }))
+        }
+    }
+
+    pub fn frame_of_reference_decode(encoded: TypedBufferRef,
+                                     frame_minimums: BufferRef<i64>,
+                                     frame_size: usize,
+                                     decoded: BufferRef<i64>) -> Result<BoxedOperator<'a>, QueryError> {
+        reify_types! {
+            "frame_of_reference_decode";
+            encoded: Integer;
+            Ok(Box::new(FrameOfReferenceDecode { encoded, frame_minimums, frame_size, decoded, position: 0 }))
+        }
+    }
+
     pub fn inverse_dict_lookup(dict_indices: BufferRef<u64>,
                                dict_data: BufferRef<u8>,
                                constant: BufferRef<Scalar<&'a str>>,
@@ -285,6 +379,50 @@ impl<'a> VecOperator<'a> {
         Box::new(InverseDictLookup { dict_indices, dict_data, constant, output })
     }
 
+    pub fn dict_rank(dict_indices: BufferRef<u64>,
+                     dict_data: BufferRef<u8>,
+                     constant: BufferRef<Scalar<&'a str>>,
+                     round_down: bool,
+                     output: BufferRef<Scalar<i64>>) -> BoxedOperator<'a> {
+        Box::new(DictRank { dict_indices, dict_data, constant, round_down, output })
+    }
+
+    pub fn dict_regex(dict_indices: BufferRef<u64>,
+                      dict_data: BufferRef<u8>,
+                      r: &str,
+                      output: BufferRef<u8>) -> Result<BoxedOperator<'a>, QueryError> {
+        Ok(Box::new(DictRegex { dict_indices, dict_data, regex: compile_regex(r)?, output }))
+    }
+
+    pub fn dict_case_fold(dict_indices: BufferRef<u64>,
+                          dict_data: BufferRef<u8>,
+                          upper: bool,
+                          folded_dict_indices: BufferRef<u64>,
+                          folded_dict_data: BufferRef<u8>,
+                          remap: BufferRef<u64>) -> BoxedOperator<'a> {
+        Box::new(DictCaseFold { dict_indices, dict_data, upper, folded_dict_indices, folded_dict_data, remap })
+    }
+
+    pub fn dict_remap(indices: TypedBufferRef,
+                      remap: BufferRef<u64>,
+                      output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        reify_types! {
+            "dict_remap";
+            indices, output: Integer;
+            Ok(Box::new(DictRemap { indices, remap, output }))
+        }
+    }
+
+    pub fn dict_match_lookup(indices: TypedBufferRef,
+                             dict_matches: BufferRef<u8>,
+                             output: BufferRef<u8>) -> Result<BoxedOperator<'a>, QueryError> {
+        reify_types![
+            "dict_match_lookup";
+            indices: Integer;
+            Ok(Box::new(DictMatchLookup { indices, dict_matches, output }))
+        ]
+    }
+
     pub fn encode_int_const(constant: BufferRef<Scalar<i64>>,
                             codec: Codec,
                             output: BufferRef<Scalar<i64>>) -> BoxedOperator<'a> {
@@ -294,6 +432,9 @@ impl<'a> VecOperator<'a> {
     pub fn filter(input: TypedBufferRef,
                   filter: BufferRef<u8>,
                   output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        if input.tag == EncodingType::Null {
+            return Ok(Box::new(FilterNulls { filter, output: output.any() }));
+        }
         reify_types! {
             "filter";
             input, output: PrimitiveUSize;
@@ -301,9 +442,25 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn filter_not(input: TypedBufferRef,
+                      filter: BufferRef<u8>,
+                      output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        if input.tag == EncodingType::Null {
+            return Ok(Box::new(FilterNotNulls { filter, output: output.any() }));
+        }
+        reify_types! {
+            "filter_not";
+            input, output: PrimitiveUSize;
+            Ok(Box::new(FilterNot { input, filter, output }))
+        }
+    }
+
     pub fn nullable_filter(input: TypedBufferRef,
                            filter: BufferRef<Nullable<u8>>,
                            output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        if input.tag == EncodingType::Null {
+            return Ok(Box::new(NullableFilterNulls { filter, output: output.any() }));
+        }
         reify_types! {
             "nullable_filter";
             input, output: PrimitiveUSize;
@@ -314,12 +471,20 @@ impl<'a> VecOperator<'a> {
     pub fn select(input: TypedBufferRef,
                   indices: BufferRef<usize>,
                   output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
-        reify_types! {
-            "select";
-            input, output: PrimitiveUSize;
-            Ok(Box::new(Select { input, indices, output }));
-            input, output: NullablePrimitive;
-            Ok(Box::new(SelectNullable { input, indices, output }))
+        if input.tag == EncodingType::Null {
+            Ok(Box::new(SelectNulls { indices, output: output.any() }))
+        } else if input.tag == EncodingType::I128 {
+            Ok(Box::new(Select { input: input.i128()?, indices, output: output.i128()? }))
+        } else if input.tag == EncodingType::HyperLogLog {
+            Ok(Box::new(SelectHyperLogLogs { input: input.hyperloglogs()?, indices, output: output.hyperloglogs()? }))
+        } else {
+            reify_types! {
+                "select";
+                input, output: PrimitiveUSize;
+                Ok(Box::new(Select { input, indices, output }));
+                input, output: NullablePrimitive;
+                Ok(Box::new(SelectNullable { input, indices, output }))
+            }
         }
     }
 
@@ -339,6 +504,30 @@ impl<'a> VecOperator<'a> {
         Box::new(NullVec { len, output })
     }
 
+    pub fn sample_mask(offset: usize, len: usize, rows: SampledRows, output: BufferRef<u8>) -> BoxedOperator<'a> {
+        Box::new(SampleMask { offset, len, rows, output, current_index: 0, batch_size: 0 })
+    }
+
+    pub fn sample_keys(offset: usize, len: usize, seed: u64, output: BufferRef<i64>) -> BoxedOperator<'a> {
+        Box::new(SampleKeys { offset, len, seed, output, current_index: 0, batch_size: 0 })
+    }
+
+    pub fn row_numbers(offset: usize, len: usize, output: BufferRef<i64>) -> BoxedOperator<'a> {
+        Box::new(RowNumbers { offset, len, output, current_index: 0, batch_size: 0 })
+    }
+
+    pub fn strided_mask(offset: usize, len: usize, stride: usize, phase: usize, output: BufferRef<u8>) -> BoxedOperator<'a> {
+        Box::new(StridedMask { offset, len, stride, phase, output, current_index: 0, batch_size: 0 })
+    }
+
+    pub fn sorted_range(input: TypedBufferRef, lower: i64, upper: i64, output: BufferRef<u8>) -> Result<BoxedOperator<'a>, QueryError> {
+        reify_types! {
+            "sorted_range";
+            input: Integer;
+            Ok(Box::new(SortedRange { input, lower, upper, output, range: None, len: 0, current_index: 0, batch_size: 0 }))
+        }
+    }
+
     pub fn constant_expand(val: i64, len: usize, output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
         match output.tag {
             EncodingType::U8 => Ok(Box::new(ConstantExpand {
@@ -366,9 +555,13 @@ impl<'a> VecOperator<'a> {
             lhs: Str, rhs: Str;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<LessThan> }));
 
-            lhs: IntegerNoU64, rhs: ScalarI64;
+            lhs: SmallInteger, rhs: ScalarI64;
+            Ok(Box::new(SimdCompareVS { lhs, rhs, output, comparison: Comparison::LessThan }));
+            lhs: I64, rhs: ScalarI64;
             Ok(Box::new(BinaryVSOperator { lhs, rhs, output, op: PhantomData::<LessThan> }));
-            lhs: ScalarI64, rhs: IntegerNoU64;
+            lhs: ScalarI64, rhs: SmallInteger;
+            Ok(Box::new(SimdCompareVS { lhs: rhs, rhs: lhs, output, comparison: Comparison::GreaterThan }));
+            lhs: ScalarI64, rhs: I64;
             Ok(Box::new(BinarySVOperator { lhs, rhs, output, op: PhantomData::<LessThan> }));
             lhs: IntegerNoU64, rhs: IntegerNoU64;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<LessThan> }))
@@ -385,9 +578,13 @@ impl<'a> VecOperator<'a> {
             lhs: Str, rhs: Str;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<LessThanEquals> }));
 
-            lhs: IntegerNoU64, rhs: ScalarI64;
+            lhs: SmallInteger, rhs: ScalarI64;
+            Ok(Box::new(SimdCompareVS { lhs, rhs, output, comparison: Comparison::LessThanEquals }));
+            lhs: I64, rhs: ScalarI64;
             Ok(Box::new(BinaryVSOperator { lhs, rhs, output, op: PhantomData::<LessThanEquals> }));
-            lhs: ScalarI64, rhs: IntegerNoU64;
+            lhs: ScalarI64, rhs: SmallInteger;
+            Ok(Box::new(SimdCompareVS { lhs: rhs, rhs: lhs, output, comparison: Comparison::GreaterThanEquals }));
+            lhs: ScalarI64, rhs: I64;
             Ok(Box::new(BinarySVOperator { lhs, rhs, output, op: PhantomData::<LessThanEquals> }));
             lhs: IntegerNoU64, rhs: IntegerNoU64;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<LessThanEquals> }))
@@ -406,9 +603,13 @@ impl<'a> VecOperator<'a> {
             lhs: Str, rhs: Str;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<Equals> }));
 
-            lhs: IntegerNoU64, rhs: ScalarI64;
+            lhs: SmallInteger, rhs: ScalarI64;
+            Ok(Box::new(SimdCompareVS { lhs, rhs, output, comparison: Comparison::Equals }));
+            lhs: I64, rhs: ScalarI64;
             Ok(Box::new(BinaryVSOperator { lhs, rhs, output, op: PhantomData::<Equals> }));
-            lhs: ScalarI64, rhs: IntegerNoU64;
+            lhs: ScalarI64, rhs: SmallInteger;
+            Ok(Box::new(SimdCompareVS { lhs: rhs, rhs: lhs, output, comparison: Comparison::Equals }));
+            lhs: ScalarI64, rhs: I64;
             Ok(Box::new(BinaryVSOperator { lhs: rhs, rhs: lhs, output, op: PhantomData::<Equals> }));
             lhs: IntegerNoU64, rhs: IntegerNoU64;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<Equals> }))
@@ -427,9 +628,13 @@ impl<'a> VecOperator<'a> {
             lhs: Str, rhs: Str;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<NotEquals> }));
 
-            lhs: IntegerNoU64, rhs: ScalarI64;
+            lhs: SmallInteger, rhs: ScalarI64;
+            Ok(Box::new(SimdCompareVS { lhs, rhs, output, comparison: Comparison::NotEquals }));
+            lhs: I64, rhs: ScalarI64;
             Ok(Box::new(BinaryVSOperator { lhs, rhs, output, op: PhantomData::<NotEquals> }));
-            lhs: ScalarI64, rhs: IntegerNoU64;
+            lhs: ScalarI64, rhs: SmallInteger;
+            Ok(Box::new(SimdCompareVS { lhs: rhs, rhs: lhs, output, comparison: Comparison::NotEquals }));
+            lhs: ScalarI64, rhs: I64;
             Ok(Box::new(BinaryVSOperator { lhs: rhs, rhs: lhs, output, op: PhantomData::<NotEquals> }));
             lhs: IntegerNoU64, rhs: IntegerNoU64;
             Ok(Box::new(BinaryOperator { lhs, rhs, output, op: PhantomData::<NotEquals> }))
@@ -659,6 +864,19 @@ impl<'a> VecOperator<'a> {
         BooleanOperator::<BooleanAnd>::compare(lhs, rhs, output)
     }
 
+    pub fn short_circuit(select: BufferRef<u8>,
+                         condition: TypedBufferRef,
+                         disjunction: bool,
+                         output: BufferRef<u8>) -> Result<BoxedOperator<'a>, QueryError> {
+        if condition.is_nullable() {
+            let condition = condition.nullable_u8()?;
+            Ok(Box::new(NullableShortCircuit { select, condition, disjunction, position: 0, output }))
+        } else {
+            let condition = condition.u8()?;
+            Ok(Box::new(ShortCircuit { select, condition, disjunction, position: 0, output }))
+        }
+    }
+
     pub fn bit_shift_left_add(lhs: BufferRef<i64>,
                               rhs: BufferRef<i64>,
                               output: BufferRef<i64>,
@@ -760,14 +978,34 @@ impl<'a> VecOperator<'a> {
         Box::new(MapOperator { input, output, map: ToYear })
     }
 
-    pub fn regex(input: BufferRef<&'a str>, r: &str, output: BufferRef<u8>) -> BoxedOperator<'a> {
-        Box::new(MapOperator { input, output, map: RegexMatch { r: regex::Regex::new(r).unwrap() } })
+    pub fn regex(input: BufferRef<&'a str>, r: &str, output: BufferRef<u8>) -> Result<BoxedOperator<'a>, QueryError> {
+        Ok(Box::new(MapOperator { input, output, map: RegexMatch { r: compile_regex(r)? } }))
     }
 
     pub fn length(input: BufferRef<&'a str>, output: BufferRef<i64>) -> BoxedOperator<'a> {
         Box::new(MapOperator { input, output, map: Length })
     }
 
+    pub fn case_fold(input: BufferRef<&'a str>, upper: bool, string_store: BufferRef<u8>, output: BufferRef<&'a str>) -> BoxedOperator<'a> {
+        Box::new(CaseFold { input, upper, string_store, output })
+    }
+
+    pub fn parse_int(input: BufferRef<&'a str>, output: BufferRef<Nullable<i64>>) -> BoxedOperator<'a> {
+        Box::new(ParseInt { input, output })
+    }
+
+    pub fn int_to_string(input: BufferRef<i64>, string_store: BufferRef<u8>, output: BufferRef<&'a str>) -> BoxedOperator<'a> {
+        Box::new(IntToString { input, string_store, output })
+    }
+
+    pub fn char_length(input: BufferRef<&'a str>, output: BufferRef<i64>) -> BoxedOperator<'a> {
+        Box::new(MapOperator { input, output, map: CharLength })
+    }
+
+    pub fn substring(input: BufferRef<&'a str>, start: i64, length: i64, output: BufferRef<&'a str>) -> BoxedOperator<'a> {
+        Box::new(MapOperator { input, output, map: Substring { start, length } })
+    }
+
     pub fn aggregate(input: TypedBufferRef,
                      grouping: TypedBufferRef,
                      max_index: BufferRef<Scalar<i64>>,
@@ -788,24 +1026,81 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn aggregate_nulls(max_index: BufferRef<Scalar<i64>>,
+                           aggregator: Aggregator,
+                           output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
+        if output.tag == EncodingType::I128 {
+            return Ok(Box::new(AggregateNulls { output: output.i128()?, max_index, a: PhantomData::<WideSum> }));
+        }
+        reify_types! {
+            "aggregate_nulls";
+            aggregator: Aggregator;
+            Ok(Box::new(AggregateNulls { output: output.into(), max_index, a: aggregator }))
+        }
+    }
+
+    pub fn approx_count_distinct(input: TypedBufferRef,
+                                 grouping: TypedBufferRef,
+                                 max_index: BufferRef<Scalar<i64>>,
+                                 precision: u8,
+                                 output: BufferRef<HyperLogLogs>) -> Result<BoxedOperator<'a>, QueryError> {
+        let precision = u32::from(precision);
+        if input.tag == EncodingType::Null {
+            Ok(Box::new(ApproxCountDistinctNulls { max_index, precision, output }))
+        } else if input.is_nullable() {
+            reify_types! {
+                "nullable_approx_count_distinct";
+                input: NullablePrimitive, grouping: Integer;
+                Ok(Box::new(ApproxCountDistinctNullable { input, grouping, max_index, precision, output }))
+            }
+        } else {
+            reify_types! {
+                "approx_count_distinct";
+                input: PrimitiveNoU64, grouping: Integer;
+                Ok(Box::new(ApproxCountDistinct { input, grouping, max_index, precision, output }))
+            }
+        }
+    }
+
     pub fn checked_aggregate(input: TypedBufferRef,
                              grouping: TypedBufferRef,
                              max_index: BufferRef<Scalar<i64>>,
                              aggregator: Aggregator,
+                             offset: i64,
                              output: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
-        assert!(aggregator == Aggregator::Sum);
-        if input.is_nullable() {
-            reify_types! {
-                "checked_nullable_aggregation";
-                input: NullableInteger, grouping: Integer;
-                Ok(Box::new(CheckedAggregateNullable { input, grouping, output: output.into(), max_index, a: PhantomData::<Sum> }))
+        if aggregator != Aggregator::Sum && aggregator != Aggregator::SaturatingSum && offset != 0 {
+            return Err(fatal!("{:?} of offset encoded values", aggregator));
+        }
+        match (aggregator, input.is_nullable()) {
+            // Saturating sums are accumulated in full and only clamped once all partitions have been merged
+            (Aggregator::Sum, true) | (Aggregator::SaturatingSum, true) => {
+                let output = output.i128()?;
+                reify_types! {
+                    "checked_nullable_aggregation";
+                    input: NullableInteger, grouping: Integer;
+                    Ok(Box::new(CheckedAggregateNullable { input, grouping, output, max_index, offset }))
+                }
             }
-        } else {
-            reify_types! {
-                "checked_aggregation";
-                input: IntegerNoU64, grouping: Integer;
-                Ok(Box::new(CheckedAggregate { input, grouping, output: output.into(), max_index, a: PhantomData::<Sum> }))
+            (Aggregator::Sum, false) | (Aggregator::SaturatingSum, false) => {
+                let output = output.i128()?;
+                reify_types! {
+                    "checked_aggregation";
+                    input: IntegerNoU64, grouping: Integer;
+                    Ok(Box::new(CheckedAggregate { input, grouping, output, max_index, offset }))
+                }
             }
+            // Overflow is handled by the aggregator itself
+            (Aggregator::WrappingSum, true) => reify_types! {
+                "wrapping_nullable_aggregation";
+                input: NullableInteger, grouping: Integer;
+                Ok(Box::new(AggregateNullable { input, grouping, output: output.into(), max_index, a: PhantomData::<WrappingSum> }))
+            },
+            (Aggregator::WrappingSum, false) => reify_types! {
+                "wrapping_aggregation";
+                input: IntegerNoU64, grouping: Integer;
+                Ok(Box::new(Aggregate { input, grouping, output: output.into(), max_index, a: PhantomData::<WrappingSum> }))
+            },
+            _ => Err(fatal!("checked_aggregate not supported for {:?}", aggregator)),
         }
     }
 
@@ -817,6 +1112,14 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn add_wide_sums(lhs: BufferRef<i128>, rhs: BufferRef<i128>, subtract: bool, output: BufferRef<i128>) -> BoxedOperator<'a> {
+        Box::new(AddWideSums { lhs, rhs, subtract, output })
+    }
+
+    pub fn count_selected(select: BufferRef<u8>, rejected: bool, output: BufferRef<i64>) -> BoxedOperator<'a> {
+        Box::new(CountSelected { select, rejected, count: 0, output })
+    }
+
     pub fn nonzero_compact(data: TypedBufferRef, compacted: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
         reify_types! {
             "nonzero_compact";
@@ -834,10 +1137,26 @@ impl<'a> VecOperator<'a> {
     }
 
     pub fn compact(data: TypedBufferRef, select: TypedBufferRef, compacted: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
-        reify_types! {
-            "compact";
-            data, compacted: Integer, select: Integer;
-            Ok(Box::new(Compact { data, select, compacted }))
+        if data.tag == EncodingType::I128 {
+            let (data, compacted) = (data.i128()?, compacted.i128()?);
+            reify_types! {
+                "compact_wide_sums";
+                select: Integer;
+                Ok(Box::new(Compact { data, select, compacted }))
+            }
+        } else if data.tag == EncodingType::HyperLogLog {
+            let (data, compacted) = (data.hyperloglogs()?, compacted.hyperloglogs()?);
+            reify_types! {
+                "compact_hyperloglogs";
+                select: Integer;
+                Ok(Box::new(CompactHyperLogLogs { data, select, compacted }))
+            }
+        } else {
+            reify_types! {
+                "compact";
+                data, compacted: Integer, select: Integer;
+                Ok(Box::new(Compact { data, select, compacted }))
+            }
         }
     }
 
@@ -857,6 +1176,17 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn sort_grouping(raw_grouping_key: TypedBufferRef,
+                         unique_out: TypedBufferRef,
+                         grouping_key_out: BufferRef<u32>,
+                         cardinality_out: BufferRef<Scalar<i64>>) -> Result<BoxedOperator<'a>, QueryError> {
+        reify_types! {
+            "sort_grouping";
+            raw_grouping_key, unique_out: Integer;
+            Ok(SortGrouping::boxed(raw_grouping_key, unique_out, grouping_key_out, cardinality_out))
+        }
+    }
+
     pub fn hash_map_grouping_val_rows(raw_grouping_key: BufferRef<ValRows<'a>>,
                                       columns: usize,
                                       _max_cardinality: usize,
@@ -874,6 +1204,7 @@ impl<'a> VecOperator<'a> {
                    indices: BufferRef<usize>,
                    descending: bool,
                    stable: bool,
+                   nulls_first: bool,
                    output: BufferRef<usize>) -> Result<BoxedOperator<'a>, QueryError> {
         if let EncodingType::ByteSlices(_) = ranking.tag {
             return Ok(Box::new(
@@ -887,7 +1218,7 @@ impl<'a> VecOperator<'a> {
             reify_types! {
                 "sort_indices";
                 ranking: NullablePrimitive;
-                Ok(Box::new(SortByNullable { ranking, output, indices, descending, stable }))
+                Ok(Box::new(SortByNullable { ranking, output, indices, descending, stable, nulls_first }))
             }
         } else {
             reify_types! {
@@ -930,19 +1261,34 @@ impl<'a> VecOperator<'a> {
 
     pub fn partition(left: TypedBufferRef,
                      right: TypedBufferRef,
-                     limit: usize, desc: bool,
+                     limit: usize, desc: bool, nulls_first: bool,
                      partition_out: BufferRef<Premerge>) -> Result<BoxedOperator<'a>, QueryError> {
+        // Fused string keys are `None` for nulls, which `CmpLessThan` places first and `CmpGreaterThan` last
         if desc {
+            if nulls_first {
+                reify_types! {
+                    "partition";
+                    left, right: Primitive;
+                    Ok(Box::new(Partition { left, right, partitioning: partition_out, limit, c: PhantomData::<NullsReversed<CmpGreaterThan>> }))
+                }
+            } else {
+                reify_types! {
+                    "partition";
+                    left, right: Primitive;
+                    Ok(Box::new(Partition { left, right, partitioning: partition_out, limit, c: PhantomData::<CmpGreaterThan> }))
+                }
+            }
+        } else if nulls_first {
             reify_types! {
                 "partition";
                 left, right: Primitive;
-                Ok(Box::new(Partition { left, right, partitioning: partition_out, limit, c: PhantomData::<CmpGreaterThan> }))
+                Ok(Box::new(Partition { left, right, partitioning: partition_out, limit, c: PhantomData::<CmpLessThan> }))
             }
         } else {
             reify_types! {
                 "partition";
                 left, right: Primitive;
-                Ok(Box::new(Partition { left, right, partitioning: partition_out, limit, c: PhantomData::<CmpLessThan> }))
+                Ok(Box::new(Partition { left, right, partitioning: partition_out, limit, c: PhantomData::<NullsReversed<CmpLessThan>> }))
             }
         }
     }
@@ -951,19 +1297,33 @@ impl<'a> VecOperator<'a> {
     pub fn subpartition(partitioning: BufferRef<Premerge>,
                         left: TypedBufferRef,
                         right: TypedBufferRef,
-                        desc: bool,
+                        desc: bool, nulls_first: bool,
                         subpartition_out: BufferRef<Premerge>) -> Result<BoxedOperator<'a>, QueryError> {
         if desc {
+            if nulls_first {
+                reify_types! {
+                    "subpartition";
+                    left, right: Primitive;
+                    Ok(Box::new(SubPartition { partitioning, left, right, sub_partitioning: subpartition_out, c: PhantomData::<NullsReversed<CmpGreaterThan>> }))
+                }
+            } else {
+                reify_types! {
+                    "subpartition";
+                    left, right: Primitive;
+                    Ok(Box::new(SubPartition { partitioning, left, right, sub_partitioning: subpartition_out, c: PhantomData::<CmpGreaterThan> }))
+                }
+            }
+        } else if nulls_first {
             reify_types! {
                 "subpartition";
                 left, right: Primitive;
-                Ok(Box::new(SubPartition { partitioning, left, right, sub_partitioning: subpartition_out, c: PhantomData::<CmpGreaterThan> }))
+                Ok(Box::new(SubPartition { partitioning, left, right, sub_partitioning: subpartition_out, c: PhantomData::<CmpLessThan> }))
             }
         } else {
             reify_types! {
                 "subpartition";
                 left, right: Primitive;
-                Ok(Box::new(SubPartition { partitioning, left, right, sub_partitioning: subpartition_out, c: PhantomData::<CmpLessThan> }))
+                Ok(Box::new(SubPartition { partitioning, left, right, sub_partitioning: subpartition_out, c: PhantomData::<NullsReversed<CmpLessThan>> }))
             }
         }
     }
@@ -999,23 +1359,51 @@ impl<'a> VecOperator<'a> {
         Box::new(MergeAggregate { merge_ops, left, right, aggregated: aggregated_out, aggregator })
     }
 
+    pub fn merge_wide_sums(merge_ops: BufferRef<MergeOp>,
+                           left: BufferRef<i128>,
+                           right: BufferRef<i128>,
+                           merged_out: BufferRef<i128>) -> BoxedOperator<'a> {
+        Box::new(MergeWideSums { merge_ops, left, right, merged: merged_out })
+    }
+
+    pub fn merge_hyperloglogs(merge_ops: BufferRef<MergeOp>,
+                              left: BufferRef<HyperLogLogs>,
+                              right: BufferRef<HyperLogLogs>,
+                              merged_out: BufferRef<HyperLogLogs>) -> BoxedOperator<'a> {
+        Box::new(MergeHyperLogLogs { merge_ops, left, right, merged: merged_out })
+    }
+
     pub fn merge_partitioned(partitioning: BufferRef<Premerge>,
                              left: TypedBufferRef,
                              right: TypedBufferRef,
-                             limit: usize, desc: bool,
+                             limit: usize, desc: bool, nulls_first: bool,
                              ops_out: BufferRef<u8>,
                              merged_out: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
         if desc {
+            if nulls_first {
+                reify_types! {
+                    "merge_partitioned_desc";
+                    left, right, merged_out: Primitive;
+                    Ok(Box::new(MergePartitioned { partitioning, left, right, merged: merged_out, take_left: ops_out, limit, c: PhantomData::<NullsReversed<CmpGreaterThan>> }))
+                }
+            } else {
+                reify_types! {
+                    "merge_partitioned_desc";
+                    left, right, merged_out: Primitive;
+                    Ok(Box::new(MergePartitioned { partitioning, left, right, merged: merged_out, take_left: ops_out, limit, c: PhantomData::<CmpGreaterThan> }))
+                }
+            }
+        } else if nulls_first {
             reify_types! {
-                "merge_partitioned_desc";
+                "merge_partitioned_asc";
                 left, right, merged_out: Primitive;
-                Ok(Box::new(MergePartitioned { partitioning, left, right, merged: merged_out, take_left: ops_out, limit, c: PhantomData::<CmpGreaterThan> }))
+                Ok(Box::new(MergePartitioned { partitioning, left, right, merged: merged_out, take_left: ops_out, limit, c: PhantomData::<CmpLessThan> }))
             }
         } else {
             reify_types! {
                 "merge_partitioned_asc";
                 left, right, merged_out: Primitive;
-                Ok(Box::new(MergePartitioned { partitioning, left, right, merged: merged_out, take_left: ops_out, limit, c: PhantomData::<CmpLessThan> }))
+                Ok(Box::new(MergePartitioned { partitioning, left, right, merged: merged_out, take_left: ops_out, limit, c: PhantomData::<NullsReversed<CmpLessThan>> }))
             }
         }
     }
@@ -1024,19 +1412,34 @@ impl<'a> VecOperator<'a> {
                  right: TypedBufferRef,
                  limit: usize,
                  desc: bool,
+                 nulls_first: bool,
                  ops_out: BufferRef<u8>,
                  merged_out: TypedBufferRef) -> Result<BoxedOperator<'a>, QueryError> {
         if desc {
+            if nulls_first {
+                reify_types! {
+                    "merge_desc";
+                    left, right, merged_out: Primitive;
+                    Ok(Box::new(Merge { left, right, merged: merged_out, merge_ops: ops_out, limit, c: PhantomData::<NullsReversed<CmpGreaterThan>> }))
+                }
+            } else {
+                reify_types! {
+                    "merge_desc";
+                    left, right, merged_out: Primitive;
+                    Ok(Box::new(Merge { left, right, merged: merged_out, merge_ops: ops_out, limit, c: PhantomData::<CmpGreaterThan> }))
+                }
+            }
+        } else if nulls_first {
             reify_types! {
-                "merge_desc";
+                "merge_asc";
                 left, right, merged_out: Primitive;
-                Ok(Box::new(Merge { left, right, merged: merged_out, merge_ops: ops_out, limit, c: PhantomData::<CmpGreaterThan> }))
+                Ok(Box::new(Merge { left, right, merged: merged_out, merge_ops: ops_out, limit, c: PhantomData::<CmpLessThan> }))
             }
         } else {
             reify_types! {
-                "merge_desc";
+                "merge_asc";
                 left, right, merged_out: Primitive;
-                Ok(Box::new(Merge { left, right, merged: merged_out, merge_ops: ops_out, limit, c: PhantomData::<CmpLessThan> }))
+                Ok(Box::new(Merge { left, right, merged: merged_out, merge_ops: ops_out, limit, c: PhantomData::<NullsReversed<CmpLessThan>> }))
             }
         }
     }
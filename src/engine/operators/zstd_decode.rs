@@ -0,0 +1,36 @@
+use engine::*;
+use mem_store::zstd;
+
+
+#[derive(Debug)]
+pub struct ZstdDecode<T> {
+    pub encoded: BufferRef<u8>,
+    pub decoded: BufferRef<T>,
+    pub decoded_len: usize,
+}
+
+impl<'a, T: GenericIntVec<T>> VecOperator<'a> for ZstdDecode<T> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) -> Result<(), QueryError> {
+        let encoded = scratchpad.get(self.encoded);
+        let mut decoded = scratchpad.get_mut(self.decoded);
+        let len = zstd::decode(&encoded, &mut decoded);
+        decoded.truncate(len);
+        Ok(())
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        // Zstd blocks can only be decompressed as a whole, so the entire column is decoded at once
+        scratchpad.set(self.decoded, vec![T::zero(); self.decoded_len]);
+    }
+
+    fn inputs(&self) -> Vec<BufferRef<Any>> { vec![self.encoded.any()] }
+    fn outputs(&self) -> Vec<BufferRef<Any>> { vec![self.decoded.any()] }
+    fn can_stream_input(&self, _: usize) -> bool { false }
+    fn can_stream_output(&self, _: usize) -> bool { false }
+    fn allocates(&self) -> bool { true }
+    fn custom_output_len(&self) -> Option<usize> { Some(self.decoded_len) }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("zstd_decode({})", self.encoded)
+    }
+}
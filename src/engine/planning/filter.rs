@@ -1,11 +1,16 @@
 use engine::{BufferRef, Nullable};
 
-#[derive(Clone, Copy)]
+/// Rows selected by the query filter. Only holds references to the filter buffers, which are computed once per
+/// partition and shared by all expressions compiled with the filter.
+#[derive(Clone)]
 pub enum Filter {
     None,
     U8(BufferRef<u8>),
     NullableU8(BufferRef<Nullable<u8>>),
     Indices(BufferRef<usize>),
+    /// Selects the rows that are not selected by the inner filter. The complement of `Filter::U8` selects the rows for
+    /// which the mask is zero, which saves computing the complement of negated conditions.
+    Not(Box<Filter>),
 }
 
 impl Default for Filter {
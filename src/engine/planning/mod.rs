@@ -8,3 +8,6 @@ pub use self::planner::QueryPlanner;
 pub use self::filter::Filter;
 pub use self::query::Query;
 pub use self::query::NormalFormQuery;
+pub use self::query::NullsOrder;
+pub use self::query::Rollup;
+pub use self::query::UnionAll;
@@ -16,6 +16,11 @@ pub struct QueryPlanner {
     checkpoint: usize,
     cache_checkpoint: HashMap<[u8; 16], Vec<TypedBufferRef>>,
     pub buffer_provider: BufferProvider,
+    /// Names of the columns that are decoded by some operation in the plan.
+    pub decoded_columns: Vec<String>,
+    decoded_columns_checkpoint: usize,
+    /// Number of rows in the partitions that precede the current one, which is the row number of its first row.
+    pub row_offset: usize,
 }
 
 impl QueryPlanner {
@@ -33,11 +38,29 @@ impl QueryPlanner {
     pub fn checkpoint(&mut self) {
         self.checkpoint = self.operations.len();
         self.cache_checkpoint = self.cache.clone();
+        self.decoded_columns_checkpoint = self.decoded_columns.len();
     }
 
     pub fn reset(&mut self) {
         self.operations.truncate(self.checkpoint);
         std::mem::swap(&mut self.cache, &mut self.cache_checkpoint);
+        self.decoded_columns.truncate(self.decoded_columns_checkpoint);
+    }
+
+    pub fn record_decode(&mut self, column_name: &str) {
+        if !self.decoded_columns.iter().any(|name| name == column_name) {
+            self.decoded_columns.push(column_name.to_string());
+        }
+    }
+
+    /// Number of column sections read by the plan, each section is read at most once.
+    pub fn column_sections_read(&self) -> usize {
+        self.operations.iter()
+            .filter(|operation| match operation {
+                ColumnSection { .. } => true,
+                _ => false,
+            })
+            .count()
     }
 
     pub fn resolve(&self, buffer: &TypedBufferRef) -> &QueryPlan {
@@ -46,6 +69,8 @@ impl QueryPlanner {
         &self.operations[op_index]
     }
 
+    /// Operations with identical inputs are only added once, which means that e.g. a column referenced by multiple
+    /// expressions of a query is only read and decoded once per partition.
     pub fn enable_common_subexpression_elimination(&self) -> bool { true }
 
 
@@ -200,24 +225,40 @@ fn propagate_nullability(operation: &QueryPlan, bp: &mut BufferProvider) -> Rewr
             ];
             Rewrite::ReplaceWith(ops)
         }
+        // `false AND null` is false, so the result is only null if neither operand is known to be false
         And { lhs, rhs, and } if and.is_nullable() => {
-            let and_non_null = bp.named_buffer("and_non_null", and.tag.non_nullable());
-            let mut ops = vec![And {
-                lhs: lhs.forget_nullability(),
-                rhs: rhs.forget_nullability(),
-                and: and_non_null,
-            }];
-            ops.extend(combine_nulls(bp, lhs, rhs, and_non_null, and));
+            let (mut ops, lhs_true, lhs_false) = known_truth_values(bp, lhs);
+            let (rhs_ops, rhs_true, rhs_false) = known_truth_values(bp, rhs);
+            ops.extend(rhs_ops);
+            let and_non_null = bp.buffer_u8("and_non_null");
+            let either_false = bp.buffer_u8("either_false");
+            let is_present = bp.buffer_u8("is_present");
+            let present = bp.buffer_u8("present");
+            ops.extend(vec![
+                And { lhs: lhs_true.into(), rhs: rhs_true.into(), and: and_non_null.into() },
+                Or { lhs: lhs_false.into(), rhs: rhs_false.into(), or: either_false.into() },
+                Or { lhs: either_false.into(), rhs: and_non_null.into(), or: is_present.into() },
+                BoolsToNullMap { bools: is_present.into(), present },
+                AssembleNullable { data: and_non_null.into(), present, nullable: and },
+            ]);
             Rewrite::ReplaceWith(ops)
         }
+        // `true OR null` is true, so the result is only null if neither operand is known to be true
         Or { lhs, rhs, or } if or.is_nullable() => {
-            let or_non_null = bp.named_buffer("or_non_null", or.tag.non_nullable());
-            let mut ops = vec![Or {
-                lhs: lhs.forget_nullability(),
-                rhs: rhs.forget_nullability(),
-                or: or_non_null,
-            }];
-            ops.extend(combine_nulls(bp, lhs, rhs, or_non_null, or));
+            let (mut ops, lhs_true, lhs_false) = known_truth_values(bp, lhs);
+            let (rhs_ops, rhs_true, rhs_false) = known_truth_values(bp, rhs);
+            ops.extend(rhs_ops);
+            let or_non_null = bp.buffer_u8("or_non_null");
+            let both_false = bp.buffer_u8("both_false");
+            let is_present = bp.buffer_u8("is_present");
+            let present = bp.buffer_u8("present");
+            ops.extend(vec![
+                Or { lhs: lhs_true.into(), rhs: rhs_true.into(), or: or_non_null.into() },
+                And { lhs: lhs_false.into(), rhs: rhs_false.into(), and: both_false.into() },
+                Or { lhs: both_false.into(), rhs: or_non_null.into(), or: is_present.into() },
+                BoolsToNullMap { bools: is_present.into(), present },
+                AssembleNullable { data: or_non_null.into(), present, nullable: or },
+            ]);
             Rewrite::ReplaceWith(ops)
         }
         LessThan { lhs, rhs, less_than } if less_than.is_nullable() => {
@@ -295,10 +336,58 @@ fn propagate_nullability(operation: &QueryPlan, bp: &mut BufferProvider) -> Rewr
                 },
             ])
         }
+        QueryPlan::Filter { plan, select, filtered } if plan.is_nullable() =>
+            Rewrite::ReplaceWith(filter_nullable(bp, plan, filtered, |plan, filtered| QueryPlan::Filter { plan, select, filtered })),
+        FilterNot { plan, select, filtered } if plan.is_nullable() =>
+            Rewrite::ReplaceWith(filter_nullable(bp, plan, filtered, |plan, filtered| FilterNot { plan, select, filtered })),
+        NullableFilter { plan, select, filtered } if plan.is_nullable() =>
+            Rewrite::ReplaceWith(filter_nullable(bp, plan, filtered, |plan, filtered| NullableFilter { plan, select, filtered })),
         _ => Rewrite::None,
     }
 }
 
+/// Returns plans for the elements of the boolean vector `plan` that are known to be true and known to be false.
+/// `And` and `Or` overwrite their left operand, so each buffer is used as left operand only once and not read after.
+fn known_truth_values(bp: &mut BufferProvider, plan: TypedBufferRef) -> (Vec<QueryPlan>, BufferRef<u8>, BufferRef<u8>) {
+    let mut ops = Vec::new();
+    let (is_true, is_false) = if plan.is_nullable() {
+        let is_present = bp.buffer_u8("is_present");
+        let is_present_copy = bp.buffer_u8("is_present");
+        let not_data = bp.buffer_u8("not_data");
+        let is_true = bp.buffer_u8("is_true");
+        let is_false = bp.buffer_u8("is_false");
+        ops.push(IsNotNull { plan: plan.nullable_any().unwrap(), is_not_null: is_present });
+        ops.push(IsNotNull { plan: plan.nullable_any().unwrap(), is_not_null: is_present_copy });
+        ops.push(And { lhs: is_present.into(), rhs: plan.forget_nullability(), and: is_true.into() });
+        ops.push(Not { input: plan.forget_nullability().u8().unwrap(), not: not_data });
+        ops.push(And { lhs: not_data.into(), rhs: is_present_copy.into(), and: is_false.into() });
+        (is_true, is_false)
+    } else {
+        let is_false = bp.buffer_u8("is_false");
+        ops.push(Not { input: plan.u8().unwrap(), not: is_false });
+        (plan.u8().unwrap(), is_false)
+    };
+    (ops, is_true, is_false)
+}
+
+/// Applies `filter` to both the data and the null map of `plan`, the latter of which is filtered as a vector of bools.
+fn filter_nullable<F>(bp: &mut BufferProvider,
+                      plan: TypedBufferRef,
+                      filtered: TypedBufferRef,
+                      filter: F) -> Vec<QueryPlan> where F: Fn(TypedBufferRef, TypedBufferRef) -> QueryPlan {
+    let filtered_non_null = bp.named_buffer("filtered_non_null", filtered.tag.non_nullable());
+    let is_present = bp.buffer_u8("is_present");
+    let filtered_is_present = bp.buffer_u8("filtered_is_present");
+    let present = bp.buffer_u8("present");
+    vec![
+        filter(plan.forget_nullability(), filtered_non_null),
+        IsNotNull { plan: plan.nullable_any().unwrap(), is_not_null: is_present },
+        filter(is_present.into(), filtered_is_present.into()),
+        BoolsToNullMap { bools: filtered_is_present.into(), present },
+        AssembleNullable { data: filtered_non_null, present, nullable: filtered },
+    ]
+}
+
 fn combine_nulls(bp: &mut BufferProvider,
                  lhs: TypedBufferRef,
                  rhs: TypedBufferRef,
@@ -376,6 +465,10 @@ impl BufferProvider {
         self.named_buffer(name, EncodingType::U32).u32().unwrap()
     }
 
+    pub fn buffer_u64(&mut self, name: &'static str) -> BufferRef<u64> {
+        self.named_buffer(name, EncodingType::U64).u64().unwrap()
+    }
+
     pub fn buffer_u8(&mut self, name: &'static str) -> BufferRef<u8> {
         self.named_buffer(name, EncodingType::U8).u8().unwrap()
     }
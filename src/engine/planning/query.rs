@@ -1,14 +1,29 @@
 use ::QueryError;
 use engine::*;
+use engine::execution::query_task::{QueryOutput, QueryStats};
 use ingest::raw_val::RawVal;
-use mem_store::column::DataSource;
+use mem_store::batch_metadata::BatchMetadata;
+use mem_store::column::{Column, DataSource};
+use mem_store::column_aliases::ColumnAliases;
+use mem_store::partition::{ROWNUM_COLUMN, TOMBSTONE_COLUMN};
+use mem_store::table::TableSchema;
+use mem_store::zone_map::ZoneMap;
+use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::iter::Iterator;
+use std::mem;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use std::u64;
+use std::usize;
 use syntax::expression::*;
 use syntax::limit::*;
+use syntax::sample::*;
+use time::precise_time_ns;
+
+/// Sort grouping is used if the estimated average number of rows per group is at most this.
+const SORT_GROUPING_MAX_ROWS_PER_GROUP: usize = 4;
 
 /// NormalFormQuery observes the following invariants:
 /// - none of the expressions contain aggregation functions
@@ -17,20 +32,134 @@ use syntax::limit::*;
 pub struct NormalFormQuery {
     pub projection: Vec<Expr>,
     pub filter: Expr,
-    pub aggregate: Vec<(Aggregator, Expr)>,
-    pub order_by: Vec<(Expr, bool)>,
+    /// Aggregators with their input expression and an optional filter that is applied in addition to `filter`.
+    pub aggregate: Vec<(Aggregator, Expr, Option<Expr>)>,
+    pub order_by: Vec<(Expr, bool, NullsOrder)>,
     pub limit: LimitClause,
+    /// Retains at most `.0` rows for each distinct combination of values in the result columns with indices `.1`,
+    /// which is applied to the result before `limit`.
+    pub limit_by: Option<(u64, Vec<usize>)>,
+    pub sample: Option<SampleClause>,
+    /// Rows of the table that are part of `sample`, which are selected once the length of the table is known.
+    pub sampled_rows: Option<SampledRows>,
+    /// Return the query plan instead of executing it.
+    pub explain_only: bool,
+    /// Record the time spent in each stage of the query plan.
+    pub analyze: bool,
+    /// User specified names for the result columns, in the same order as `result_column_names`.
+    pub aliases: Vec<Option<String>>,
+    /// Maximum number of bytes the buffers of a single partition may allocate.
+    pub memory_limit_bytes: Option<usize>,
+    /// Number of rows that streaming stages process at a time, `DEFAULT_BATCH_SIZE` if `None`.
+    pub batch_size: Option<usize>,
+    /// Keep only a random sample of `limit` rows, implies that `order_by` is empty.
+    pub reservoir: Option<ReservoirSample>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
 pub struct Query {
-    pub select: Vec<Expr>,
+    /// Selected expressions with an optional alias that determines the name of the result column.
+    pub select: Vec<(Expr, Option<String>)>,
     pub table: String,
     pub filter: Expr,
-    pub order_by: Vec<(Expr, bool)>,
+    /// Expressions of an explicit `GROUP BY` clause. Queries with aggregates are grouped by all selected expressions
+    /// that are not aggregates, so this is only needed to group by expressions that are not selected.
+    pub group_by: Vec<Expr>,
+    /// Condition on the aggregated rows, analogous to SQL `HAVING`.
+    pub having: Option<Expr>,
+    /// Sort keys, whether they are descending and where they place null values.
+    pub order_by: Vec<(Expr, bool, NullsOrder)>,
+    pub limit: LimitClause,
+    /// Applied to the ordered result before `limit`.
+    pub limit_by: Option<LimitBy>,
+    pub sample: Option<SampleClause>,
+    /// Returns a random sample of the rows that satisfy the filter instead of the first `limit` rows.
+    pub reservoir: Option<ReservoirSample>,
+    /// Analogous to SQL `EXPLAIN`, the query returns its plan rather than any results.
+    pub explain: bool,
+    /// Analogous to SQL `EXPLAIN ANALYZE`, the query is executed but returns the time spent in each stage of the plan.
+    pub analyze: bool,
+    /// Fails the query with `QueryError::MemoryLimitExceeded` if executing it requires more than this many bytes of buffers.
+    pub memory_limit_bytes: Option<usize>,
+    /// Number of rows that are processed at a time by the stages of the query plan that stream their inputs.
+    /// Smaller batches use less memory for intermediate buffers, results are the same for any batch size.
+    pub batch_size: Option<usize>,
+    /// Stops processing further partitions once this much time has passed, and returns a result marked as truncated.
+    pub timeout: Option<Duration>,
+    /// Analogous to SQL `FOR SYSTEM_TIME AS OF`, the query only reads partitions that were ingested at or before this time.
+    pub as_of: Option<SystemTime>,
+}
+
+/// Two queries with the same number and types of result columns whose results are concatenated, like SQL `UNION ALL`.
+/// With `order_by`, both queries are sorted by the corresponding selected expressions and their results are merged.
+#[derive(Debug, Clone)]
+pub struct UnionAll {
+    pub left: Query,
+    pub right: Query,
+    /// Names of the result columns by which the combined result is sorted.
+    pub order_by: Vec<(String, bool, NullsOrder)>,
+    pub limit: LimitClause,
+}
+
+/// Position of null values in a sorted result, analogous to SQL `NULLS FIRST` and `NULLS LAST`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
+impl NullsOrder {
+    /// Unless specified otherwise, SQL sorts nulls as if they were larger than all other values.
+    pub fn default_for(desc: bool) -> NullsOrder {
+        if desc { NullsOrder::First } else { NullsOrder::Last }
+    }
+}
+
+/// Query with `GROUP BY ROLLUP(<exprs>)`, analogous to SQL `ROLLUP`. In addition to the groups of all grouping
+/// expressions, returns subtotals for each prefix of the rolled up expressions and a grand total. Rolled up expressions
+/// are null in the rows of subtotals, which follow the detailed rows unless the result is ordered.
+#[derive(Debug, Clone)]
+pub struct Rollup {
+    /// Query grouped by all expressions and sorted by the selected expressions that `order_by` refers to.
+    pub query: Query,
+    /// Number of expressions at the end of `query.group_by` that are rolled up.
+    pub rolled_up: usize,
+    /// Indices of the result columns by which the combined result is sorted.
+    pub order_by: Vec<(usize, bool, NullsOrder)>,
     pub limit: LimitClause,
 }
 
+impl Rollup {
+    /// Query for each grouping set, from most to least grouping expressions, together with the indices of the result
+    /// columns that are rolled up in that grouping set. These columns are omitted from the query.
+    /// Fails if more expressions are rolled up than the query is grouped by.
+    pub fn grouping_sets(&self) -> Result<Vec<(Query, Vec<usize>)>, QueryError> {
+        let fixed = match self.query.group_by.len().checked_sub(self.rolled_up) {
+            Some(fixed) => fixed,
+            None => bail!(QueryError::ParseError, "Cannot roll up {} of {} grouping expressions",
+                          self.rolled_up, self.query.group_by.len()),
+        };
+        Ok((0..self.rolled_up + 1).rev()
+            .map(|kept| {
+                let removed = &self.query.group_by[fixed + kept..];
+                let mut query = self.query.clone();
+                query.group_by.truncate(fixed + kept);
+                let nulls = query.select.iter()
+                    .enumerate()
+                    .filter(|&(_, &(ref expr, _))| removed.contains(expr))
+                    .map(|(i, _)| i)
+                    .collect();
+                query.select.retain(|&(ref expr, _)| !removed.contains(expr));
+                // Rolled up expressions are null in all rows of this grouping set
+                query.order_by.retain(|&(ref expr, _, _)| !removed.contains(expr));
+                (query, nulls)
+            })
+            .collect())
+    }
+}
+
 impl NormalFormQuery {
     #[inline(never)] // produces more useful profiles
     pub fn run<'a>(&self,
@@ -38,99 +167,133 @@ impl NormalFormQuery {
                    explain: bool,
                    show: bool,
                    partition: usize,
-                   partition_len: usize) -> Result<(BatchResult<'a>, Option<String>), QueryError> {
-        let limit = (self.limit.limit + self.limit.offset) as usize;
+                   partition_len: usize,
+                   row_offset: usize,
+                   stats: &mut QueryStats) -> Result<(BatchResult<'a>, Option<String>), QueryError> {
+        let limit = self.row_limit();
         let mut planner = QueryPlanner::default();
+        planner.row_offset = row_offset;
 
-        let (filter_plan, _) = QueryPlan::compile_expr(&self.filter, Filter::None, columns, partition_len, &mut planner)?;
-        let mut filter = match filter_plan.tag {
-            EncodingType::U8 => Filter::U8(filter_plan.u8()?),
-            EncodingType::NullableU8 => Filter::NullableU8(filter_plan.nullable_u8()?),
-            _ => Filter::None,
-        };
+        let mut filter = self.compile_filter(columns, partition_len, &mut planner)?;
 
         // Sorting
         let mut sort_indices = None;
-        for (plan, desc) in self.order_by.iter().rev() {
+        let mut sample_keys = None;
+        if let Some(ref reservoir) = self.reservoir {
+            // Keeping the rows with the smallest random keys yields a uniform sample of the rows that satisfy the filter,
+            // and the samples of different partitions are merged like results ordered by the keys
+            let keys: TypedBufferRef = planner.sample_keys(row_offset, partition_len, reservoir.seed.unwrap_or(0)).into();
+            let ranking = query_plan::apply_filter(keys, &filter, &mut planner)?;
+            sort_indices = Some(planner.top_n(ranking, limit, false));
+            sample_keys = Some(keys);
+        }
+        for (plan, desc, nulls) in self.order_by.iter().rev() {
             let (ranking, _) = query_plan::order_preserving(
-                QueryPlan::compile_expr(&plan, filter, columns, partition_len, &mut planner)?, &mut planner);
+                QueryPlan::compile_expr(&plan, &filter, columns, partition_len, &mut planner)?, &mut planner);
+            let nulls_first = *nulls == NullsOrder::First;
 
             // PERF: better criterion for using top_n
             // PERF: top_n for multiple columns?
-            sort_indices = Some(if limit < partition_len / 2 && self.order_by.len() == 1 {
+            sort_indices = Some(if limit < partition_len / 2 && self.order_by.len() == 1 && !ranking.is_nullable() {
                 planner.top_n(ranking, limit, *desc)
             } else {
                 // PERF: sort directly if only single column selected
                 match sort_indices {
                     None => {
                         let indices = planner.indices(ranking);
-                        planner.sort_by(ranking, indices,
-                                        *desc, false /* unstable sort */)
+                        // Stable, so that ties are returned in row order for both ASC and DESC
+                        planner.sort_by(ranking, indices, *desc, true /* stable sort */, nulls_first)
                     }
-                    Some(indices) => planner.sort_by(ranking, indices, *desc, true /* stable sort */)
+                    Some(indices) => planner.sort_by(ranking, indices, *desc, true /* stable sort */, nulls_first)
                 }
             });
         }
         if let Some(sort_indices) = sort_indices {
             filter = match filter {
-                Filter::U8(where_true) => {
-                    let buffer = planner.null_vec(partition_len, EncodingType::Null);
-                    let indices = planner.indices(buffer).into();
-                    let filter = planner.filter(indices, where_true);
-                    Filter::Indices(planner.select(filter, sort_indices).usize()?)
-                }
-                Filter::NullableU8(where_true) => {
+                Filter::None => Filter::Indices(sort_indices),
+                Filter::Indices(_) => return Err(fatal!("Filter already selects sorted indices")),
+                _ => {
                     let buffer = planner.null_vec(partition_len, EncodingType::Null);
                     let indices = planner.indices(buffer).into();
-                    let filter = planner.nullable_filter(indices, where_true);
-                    Filter::Indices(planner.select(filter, sort_indices).usize()?)
+                    let selected = query_plan::apply_filter(indices, &filter, &mut planner)?;
+                    Filter::Indices(planner.select(selected, sort_indices).usize()?)
                 }
-                Filter::None => Filter::Indices(sort_indices),
-                Filter::Indices(_) => unreachable!(),
             };
         }
 
         let mut select = Vec::new();
-        for expr in &self.projection {
-            let (mut plan, plan_type) = QueryPlan::compile_expr(expr, filter, columns, partition_len, &mut planner)?;
+        let mut boolean_projections = Vec::new();
+        for (i, expr) in self.projection.iter().enumerate() {
+            let (mut plan, plan_type) = QueryPlan::compile_expr(expr, &filter, columns, partition_len, &mut planner)?;
             if let Some(codec) = plan_type.codec {
                 plan = codec.decode(plan, &mut planner);
             }
+            // Booleans are stored as integers 0 and 1, which nullable booleans require to represent nulls
+            if plan_type.decoded == BasicType::Boolean {
+                boolean_projections.push(i);
+                if !plan_type.is_scalar {
+                    plan = planner.cast(plan, EncodingType::I64);
+                }
+            }
             if plan.is_nullable() {
                 plan = planner.fuse_nulls(plan);
             }
             select.push(plan.any());
         }
         let mut order_by = Vec::new();
-        for (expr, desc) in &self.order_by {
-            let (mut plan, plan_type) = QueryPlan::compile_expr(expr, filter, columns, partition_len, &mut planner)?;
+        for (expr, desc, nulls) in &self.order_by {
+            let (mut plan, plan_type) = QueryPlan::compile_expr(expr, &filter, columns, partition_len, &mut planner)?;
             if let Some(codec) = plan_type.codec {
                 plan = codec.decode(plan, &mut planner);
             }
             if plan.is_nullable() {
-                plan = planner.fuse_nulls(plan);
+                // Integer sort keys represent nulls by the smallest or largest value, depending on where nulls are placed
+                plan = if (*nulls == NullsOrder::First) == *desc {
+                    planner.fuse_nulls_max(plan)
+                } else {
+                    planner.fuse_nulls(plan)
+                };
             }
-            order_by.push((plan.any(), *desc));
+            order_by.push((plan.any(), *desc, *nulls));
         };
+        if let (Some(keys), &Filter::Indices(indices)) = (sample_keys, &filter) {
+            order_by.push((planner.select(keys, indices).any(), false, NullsOrder::First));
+        }
 
         for c in columns {
             debug!("{}: {:?}", partition, c);
         }
         let mut executor = planner.prepare(vec![])?;
+        if self.explain_only {
+            return Ok((NormalFormQuery::explain_result(&planner, show), None));
+        }
         let mut results = executor.prepare(NormalFormQuery::column_data(columns));
         debug!("{:#}", &executor);
+        executor.set_memory_limit(self.memory_limit_bytes);
+        if let Some(batch_size) = self.batch_size {
+            executor.set_batch_size(batch_size);
+        }
         executor.run(partition_len, &mut results, show)?;
+        stats.peak_memory_bytes = cmp::max(stats.peak_memory_bytes, executor.peak_memory_bytes());
+        stats.chunks_processed += executor.chunks_processed();
+        stats.record_decoded(&planner.decoded_columns);
+        stats.column_sections_read += planner.column_sections_read();
+        if self.analyze {
+            executor.record_stage_durations(stats);
+        }
         let (columns, projection, _, order_by) = results.collect_aliased(&select, &[], &order_by);
 
         Ok(
             (BatchResult {
                 columns,
                 projection,
+                boolean_projections,
                 aggregations: vec![],
                 order_by,
                 level: 0,
                 batch_count: 1,
                 show,
+                truncated: false,
                 unsafe_referenced_buffers: results.collect_pinned(),
             },
              if explain { Some(format!("{}", executor)) } else { None }))
@@ -142,26 +305,52 @@ impl NormalFormQuery {
                              explain: bool,
                              show: bool,
                              partition: usize,
-                             partition_len: usize)
+                             partition_len: usize,
+                             row_offset: usize,
+                             stats: &mut QueryStats)
                              -> Result<(BatchResult<'a>, Option<String>), QueryError> {
         trace_start!("run_aggregate");
 
         let mut qp = QueryPlanner::default();
+        qp.row_offset = row_offset;
 
         // Filter
-        let (filter_plan, filter_type) = QueryPlan::compile_expr(&self.filter, Filter::None, columns, partition_len, &mut qp)?;
-        let filter = match filter_type.encoding_type() {
-            EncodingType::U8 => Filter::U8(filter_plan.u8()?),
-            EncodingType::NullableU8 => Filter::NullableU8(filter_plan.nullable_u8()?),
-            _ => Filter::None,
-        };
+        let filter = self.compile_filter(columns, partition_len, &mut qp)?;
+
+        // Counting all rows without grouping only requires the number of rows that pass the filter
+        if self.projection.is_empty() && self.counts_rows() {
+            let count = match filter {
+                Filter::None if !self.explain_only => {
+                    let count = if partition_len > 0 { vec![partition_len as i64] } else { vec![] };
+                    let batch = BatchResult {
+                        columns: vec![Data::owned(count)],
+                        projection: vec![],
+                        boolean_projections: vec![],
+                        aggregations: vec![(0, Aggregator::Count)],
+                        order_by: vec![],
+                        level: 0,
+                        batch_count: 1,
+                        show,
+                        truncated: false,
+                        unsafe_referenced_buffers: vec![],
+                    };
+                    return Ok((batch, None));
+                }
+                Filter::U8(select) => Some(qp.count_selected(select, false)),
+                Filter::Not(box Filter::U8(select)) => Some(qp.count_selected(select, true)),
+                _ => None,
+            };
+            if let Some(count) = count {
+                return self.run_count(count, columns, explain, show, partition_len, qp, stats);
+            }
+        }
 
         // Combine all group by columns into a single decodable grouping key
         let ((raw_grouping_key, is_raw_grouping_key_order_preserving),
             max_grouping_key,
             decode_plans,
             encoded_group_by_placeholder) =
-            query_plan::compile_grouping_key(&self.projection, filter, columns, partition_len, &mut qp)?;
+            query_plan::compile_grouping_key(&self.projection, &filter, columns, partition_len, &mut qp)?;
 
         // Reduce cardinality of grouping key if necessary and perform grouping
         // PERF: also determine and use is_dense. always true for hashmap, depends on group by columns for raw.
@@ -176,6 +365,8 @@ impl NormalFormQuery {
                  raw_grouping_key,
                  is_raw_grouping_key_order_preserving,
                  max_grouping_key_buf)
+            } else if self.prefer_sort_grouping(columns, partition_len, raw_grouping_key.tag, is_raw_grouping_key_order_preserving) {
+                query_plan::prepare_sort_grouping(raw_grouping_key, is_raw_grouping_key_order_preserving, &mut qp)
             } else {
                 query_plan::prepare_hashmap_grouping(
                     raw_grouping_key,
@@ -188,8 +379,35 @@ impl NormalFormQuery {
         let mut aggregation_results = Vec::new();
         let mut selector = None;
         let mut selector_index = None;
-        for (i, &(aggregator, ref expr)) in self.aggregate.iter().enumerate() {
-            let (plan, plan_type) = QueryPlan::compile_expr(expr, filter, columns, partition_len, &mut qp)?;
+        // Aggregates over the same expression share its plan, e.g. `SUM(x), MIN(x)` compile `x` only once
+        let mut compiled_exprs = Vec::<(&Expr, (TypedBufferRef, Type))>::new();
+        for (i, &(aggregator, ref expr, ref aggregate_filter)) in self.aggregate.iter().enumerate() {
+            // sum(a + b) = sum(a) + sum(b), which allows each column to be summed without decoding it
+            if (aggregator == Aggregator::Sum || aggregator == Aggregator::SaturatingSum) && aggregate_filter.is_none() {
+                if let Some(terms) = query_plan::summands(expr, columns, partition_len) {
+                    let (aggregate, t) = query_plan::prepare_sum_of_columns(
+                        &terms, &filter, grouping_key, aggregation_cardinality, columns, partition_len, &mut qp)?;
+                    aggregation_results.push((aggregator, aggregate, t, false));
+                    continue;
+                }
+            }
+            let compiled = compiled_exprs.iter()
+                .find(|&&(compiled_expr, _)| compiled_expr == expr)
+                .map(|&(_, ref compiled)| compiled.clone());
+            let (mut plan, plan_type) = match compiled {
+                Some(compiled) => compiled,
+                None => {
+                    let compiled = QueryPlan::compile_expr(expr, &filter, columns, partition_len, &mut qp)?;
+                    compiled_exprs.push((expr, compiled.clone()));
+                    compiled
+                }
+            };
+            if let Some(aggregate_filter) = aggregate_filter {
+                // Rows excluded by the aggregate filter are masked out as null rather than being removed,
+                // which keeps the aggregation input aligned with the grouping key
+                let (condition, _) = QueryPlan::compile_expr(aggregate_filter, &filter, columns, partition_len, &mut qp)?;
+                plan = query_plan::mask_aggregation_input(plan, condition, grouping_key, aggregator, &mut qp)?;
+            }
             let (aggregate, t) = query_plan::prepare_aggregation(
                 plan,
                 plan_type,
@@ -197,12 +415,14 @@ impl NormalFormQuery {
                 aggregation_cardinality,
                 aggregator,
                 &mut qp)?;
+            // Counts of inputs that may contain nulls can be zero for nonempty groups, so they can't serve as selector
+            let input_nullable = plan.is_nullable() || plan.tag == EncodingType::Null;
             // PERF: if summation column is strictly positive, can use sum as well
-            if aggregator == Aggregator::Count && !plan.is_nullable() {
+            if aggregator == Aggregator::Count && !input_nullable {
                 selector = Some((aggregate, t.encoding_type()));
                 selector_index = Some(i)
             }
-            aggregation_results.push((aggregator, aggregate, t, plan.is_nullable()))
+            aggregation_results.push((aggregator, aggregate, t, input_nullable))
         }
 
         // Determine selector
@@ -227,7 +447,9 @@ impl NormalFormQuery {
                                       input_nullable: bool| {
                 let compacted = match aggregator {
                     // PERF: if summation column is strictly positive, can use NonzeroCompact
-                    Aggregator::Sum | Aggregator::Max | Aggregator::Min => qp.compact(aggregate, selector),
+                    Aggregator::Sum | Aggregator::SaturatingSum | Aggregator::WrappingSum |
+                    Aggregator::Max | Aggregator::Min | Aggregator::ApproxCountDistinct(_) =>
+                        qp.compact(aggregate, selector),
                     Aggregator::Count => if input_nullable {
                         qp.compact(aggregate, selector)
                     } else {
@@ -258,7 +480,11 @@ impl NormalFormQuery {
 
         //  Reconstruct all group by columns from grouping
         let mut grouping_columns = Vec::with_capacity(decode_plans.len());
-        for (decode_plan, _t) in decode_plans {
+        let mut boolean_projections = Vec::new();
+        for (i, (decode_plan, t)) in decode_plans.into_iter().enumerate() {
+            if t.decoded == BasicType::Boolean {
+                boolean_projections.push(i);
+            }
             let decoded = decode_plan.clone();
             grouping_columns.push(decoded);
         }
@@ -271,7 +497,8 @@ impl NormalFormQuery {
                 qp.sort_by(encoded_group_by_column,
                            indices,
                            false /* desc */,
-                           false /* stable */)
+                           false /* stable */,
+                           true /* nulls first */)
             } else {
                 if grouping_columns.len() != 1 {
                     bail!(QueryError::NotImplemented,
@@ -282,7 +509,8 @@ impl NormalFormQuery {
                 qp.sort_by(grouping_columns[0],
                            indices,
                            false /* desc */,
-                           false /* stable */)
+                           false /* stable */,
+                           true /* nulls first */)
             };
 
             let mut aggregations2 = Vec::new();
@@ -310,9 +538,23 @@ impl NormalFormQuery {
             debug!("{}: {:?}", partition, c);
         }
         let mut executor = qp.prepare(vec![])?;
+        if self.explain_only {
+            return Ok((NormalFormQuery::explain_result(&qp, show), None));
+        }
         let mut results = executor.prepare(NormalFormQuery::column_data(columns));
         debug!("{:#}", &executor);
+        executor.set_memory_limit(self.memory_limit_bytes);
+        if let Some(batch_size) = self.batch_size {
+            executor.set_batch_size(batch_size);
+        }
         executor.run(partition_len, &mut results, show)?;
+        stats.peak_memory_bytes = cmp::max(stats.peak_memory_bytes, executor.peak_memory_bytes());
+        stats.chunks_processed += executor.chunks_processed();
+        stats.record_decoded(&qp.decoded_columns);
+        stats.column_sections_read += qp.column_sections_read();
+        if self.analyze {
+            executor.record_stage_durations(stats);
+        }
         let (columns, projection, aggregations, _) = results.collect_aliased(
             &grouping_columns.iter().map(|s| s.any()).collect::<Vec<_>>(),
             &aggregation_cols.iter().map(|&(s, aggregator)| (s.any(), aggregator)).collect::<Vec<_>>(),
@@ -321,11 +563,13 @@ impl NormalFormQuery {
         let batch = BatchResult {
             columns,
             projection,
+            boolean_projections,
             aggregations,
             order_by: vec![],
             level: 0,
             batch_count: 1,
             show,
+            truncated: false,
             unsafe_referenced_buffers: results.collect_pinned(),
         };
         if let Err(err) = batch.validate() {
@@ -340,62 +584,483 @@ impl NormalFormQuery {
         }
     }
 
+    /// Returns true if the only aggregate counts all rows, e.g. `COUNT(0)`.
+    fn counts_rows(&self) -> bool {
+        self.aggregate.len() == 1 && match self.aggregate[0] {
+            (Aggregator::Count, Expr::Const(ref value), None) => *value != RawVal::Null,
+            _ => false,
+        }
+    }
+
+    /// Executes a plan that computes the ungrouped row `count` without grouping and aggregation operators.
+    fn run_count<'a>(&self,
+                     count: BufferRef<i64>,
+                     columns: &'a HashMap<String, Arc<DataSource>>,
+                     explain: bool,
+                     show: bool,
+                     partition_len: usize,
+                     mut qp: QueryPlanner,
+                     stats: &mut QueryStats)
+                     -> Result<(BatchResult<'a>, Option<String>), QueryError> {
+        let mut executor = qp.prepare(vec![])?;
+        if self.explain_only {
+            return Ok((NormalFormQuery::explain_result(&qp, show), None));
+        }
+        let mut results = executor.prepare(NormalFormQuery::column_data(columns));
+        executor.set_memory_limit(self.memory_limit_bytes);
+        if let Some(batch_size) = self.batch_size {
+            executor.set_batch_size(batch_size);
+        }
+        executor.run(partition_len, &mut results, show)?;
+        stats.peak_memory_bytes = cmp::max(stats.peak_memory_bytes, executor.peak_memory_bytes());
+        stats.chunks_processed += executor.chunks_processed();
+        stats.record_decoded(&qp.decoded_columns);
+        stats.column_sections_read += qp.column_sections_read();
+        if self.analyze {
+            executor.record_stage_durations(stats);
+        }
+        let (columns, projection, aggregations, _) = results.collect_aliased(&[], &[(count.any(), Aggregator::Count)], &[]);
+        let batch = BatchResult {
+            columns,
+            projection,
+            boolean_projections: vec![],
+            aggregations,
+            order_by: vec![],
+            level: 0,
+            batch_count: 1,
+            show,
+            truncated: false,
+            unsafe_referenced_buffers: results.collect_pinned(),
+        };
+        Ok((batch, if explain { Some(format!("{}", executor)) } else { None }))
+    }
+
+    /// Returns the `Debug` output of the (rewritten) query plan as a single string value.
+    fn explain_result<'a>(planner: &QueryPlanner, show: bool) -> BatchResult<'a> {
+        let plan: BoxedData<'a> = Box::new(RawVal::Str(format!("{:#?}", planner.operations)));
+        // TODO(#96): fix unsafety
+        let plan_str = unsafe { mem::transmute::<&str, &'a str>(plan.cast_ref_scalar_string().as_str()) };
+        BatchResult {
+            columns: vec![Data::owned(vec![plan_str])],
+            projection: vec![0],
+            boolean_projections: vec![],
+            aggregations: vec![],
+            order_by: vec![],
+            level: 0,
+            batch_count: 1,
+            show,
+            truncated: false,
+            unsafe_referenced_buffers: vec![plan],
+        }
+    }
+
+    /// Compiles the query filter, restricted to the rows that are part of the sample (if any) and have not been deleted.
+    fn compile_filter(&self,
+                      columns: &HashMap<String, Arc<DataSource>>,
+                      partition_len: usize,
+                      planner: &mut QueryPlanner) -> Result<Filter, QueryError> {
+        if self.sampled_rows.is_none() {
+            if let Some(rejected) = self.compile_rejected(columns, partition_len, planner)? {
+                return Ok(Filter::Not(Box::new(Filter::U8(rejected))));
+            }
+        }
+        let (mut filter_plan, filter_type) = query_plan::compile_condition(&self.filter, columns, partition_len, planner)?;
+        // Constants select all rows, and null results from missing columns select none
+        if filter_type.decoded != BasicType::Boolean && filter_type.decoded != BasicType::Null && !filter_type.is_scalar {
+            bail!(QueryError::TypeError, "Expected boolean expression in WHERE clause, found {:?}", filter_type.decoded)
+        }
+        if filter_type.decoded == BasicType::Null && !filter_type.is_scalar {
+            filter_plan = planner.constant_expand(0, partition_len, EncodingType::U8);
+        }
+        if let Some(rows) = self.sampled_rows {
+            if rows.threshold.is_some() {
+                let offset = planner.row_offset;
+                let mask: TypedBufferRef = planner.sample_mask(offset, partition_len, rows).into();
+                filter_plan = match filter_plan.tag {
+                    EncodingType::U8 | EncodingType::NullableU8 => planner.and(filter_plan, mask),
+                    _ => mask,
+                };
+            }
+        }
+        if let Some(deleted) = NormalFormQuery::compile_tombstone(columns, planner)? {
+            let alive: TypedBufferRef = planner.not(deleted).into();
+            filter_plan = match filter_plan.tag {
+                EncodingType::U8 | EncodingType::NullableU8 => planner.and(filter_plan, alive),
+                _ => alive,
+            };
+        }
+        Ok(match filter_plan.tag {
+            EncodingType::U8 => Filter::U8(filter_plan.u8()?),
+            EncodingType::NullableU8 => Filter::NullableU8(filter_plan.nullable_u8()?),
+            _ => Filter::None,
+        })
+    }
+
+    /// Compiles a mask of the rows that don't pass the filter if the filter is a negated condition or only excludes
+    /// deleted rows, which avoids computing the complement of the condition and of the tombstones.
+    fn compile_rejected(&self,
+                        columns: &HashMap<String, Arc<DataSource>>,
+                        partition_len: usize,
+                        planner: &mut QueryPlanner) -> Result<Option<BufferRef<u8>>, QueryError> {
+        let mut rejected = match self.filter {
+            Expr::Func1(Func1Type::Not, box ref condition) => {
+                let (plan, plan_type) = QueryPlan::compile_expr(condition, &Filter::None, columns, partition_len, planner)?;
+                // Other conditions are compiled with `compile_filter`, which reuses the plan of the condition
+                if plan.tag != EncodingType::U8 || plan_type.decoded != BasicType::Boolean
+                    || plan_type.is_encoded() || plan_type.is_scalar {
+                    return Ok(None);
+                }
+                Some(plan.u8()?)
+            }
+            _ => None,
+        };
+        if let Some(deleted) = NormalFormQuery::compile_tombstone(columns, planner)? {
+            rejected = Some(match rejected {
+                Some(rejected) => planner.or(rejected.into(), deleted.into()).u8()?,
+                None if self.filter == Expr::Const(RawVal::Int(1)) => deleted,
+                None => return Ok(None),
+            });
+        }
+        Ok(rejected)
+    }
+
+    /// Unpacks the tombstone of the partition into one byte per row that is 1 for deleted rows, `None` if no row has
+    /// been deleted.
+    fn compile_tombstone(columns: &HashMap<String, Arc<DataSource>>,
+                         planner: &mut QueryPlanner) -> Result<Option<BufferRef<u8>>, QueryError> {
+        match columns.get(TOMBSTONE_COLUMN) {
+            Some(tombstone) => {
+                let bits = planner.column_section(TOMBSTONE_COLUMN, 0, None, EncodingType::U8);
+                Ok(Some(tombstone.codec().decode(bits, planner).u8()?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Hash map grouping is fastest when there are few groups. With many groups relative to the number of rows the
+    /// hash map becomes large and the groups have to be sorted afterwards anyway, so sorting the (integer) grouping key
+    /// directly is preferable if that preserves the order of the groups.
+    fn prefer_sort_grouping(&self,
+                            columns: &HashMap<String, Arc<DataSource>>,
+                            partition_len: usize,
+                            raw_grouping_key: EncodingType,
+                            is_raw_grouping_key_order_preserving: bool) -> bool {
+        let is_integer = match raw_grouping_key {
+            EncodingType::U8 | EncodingType::U16 | EncodingType::U32 | EncodingType::U64 | EncodingType::I64 => true,
+            _ => false,
+        };
+        if !is_integer || !is_raw_grouping_key_order_preserving || self.projection.len() != 1 {
+            return false;
+        }
+        let cardinality = match self.projection[0] {
+            Expr::ColName(ref name) => columns.get(name).and_then(|column| column.approx_cardinality()),
+            _ => None,
+        };
+        match cardinality {
+            Some(cardinality) => cardinality.value() * SORT_GROUPING_MAX_ROWS_PER_GROUP >= partition_len,
+            None => false,
+        }
+    }
+
+    /// Returns true if the null counts and value ranges in `metadata` or the bloom indices of `columns` guarantee that
+    /// no row in the batch satisfies the filter.
+    pub fn filter_excludes_batch(&self, metadata: &BatchMetadata, columns: &HashMap<String, Arc<DataSource>>) -> bool {
+        let zone_map = metadata.zone_map();
+        NormalFormQuery::excluded_by_indices(&self.filter, metadata, columns, &zone_map)
+    }
+
+    fn excluded_by_indices(expr: &Expr,
+                           metadata: &BatchMetadata,
+                           columns: &HashMap<String, Arc<DataSource>>,
+                           zone_map: &ZoneMap) -> bool {
+        match expr {
+            Expr::Func2(Func2Type::And, lhs, rhs) =>
+                NormalFormQuery::excluded_by_indices(lhs, metadata, columns, zone_map)
+                    || NormalFormQuery::excluded_by_indices(rhs, metadata, columns, zone_map),
+            Expr::Func2(Func2Type::Or, lhs, rhs) =>
+                NormalFormQuery::excluded_by_indices(lhs, metadata, columns, zone_map)
+                    && NormalFormQuery::excluded_by_indices(rhs, metadata, columns, zone_map),
+            Expr::Func1(Func1Type::IsNull, box Expr::ColName(name)) => metadata.no_nulls(name),
+            Expr::Func1(Func1Type::IsNotNull, box Expr::ColName(name)) => metadata.all_null(name),
+            Expr::Func2(op, box Expr::ColName(name), box Expr::Const(value)) =>
+                NormalFormQuery::excluded_by_comparison(name, *op, value, columns, zone_map),
+            Expr::Func2(op, box Expr::Const(value), box Expr::ColName(name)) => match op.flip_comparison() {
+                Some(op) => NormalFormQuery::excluded_by_comparison(name, op, value, columns, zone_map),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn excluded_by_comparison(name: &str,
+                              op: Func2Type,
+                              value: &RawVal,
+                              columns: &HashMap<String, Arc<DataSource>>,
+                              zone_map: &ZoneMap) -> bool {
+        if let RawVal::Int(i) = *value {
+            if zone_map.excludes(name, op, i) {
+                return true;
+            }
+        }
+        op == Func2Type::Equals && match columns.get(name).and_then(|c| c.bloom_index()) {
+            Some(bloom) => !bloom.may_contain(value),
+            None => false,
+        }
+    }
+
     fn column_data(columns: &HashMap<String, Arc<DataSource>>) -> HashMap<String, Vec<&Data>> {
         columns.iter()
             .map(|(name, column)| (name.to_string(), column.data_sections()))
             .collect()
     }
 
+    /// Extracts at most `limit` rows of the query output from `result`, after applying `limit_by` and the offset.
+    pub fn output_rows(&self, result: &BatchResult, limit: usize) -> Vec<Vec<RawVal>> {
+        let result_columns = NormalFormQuery::result_columns(result);
+        let mut counts = HashMap::<Vec<RawVal>, u64>::new();
+        let rows = (0..result.len()).filter(|&i| match self.limit_by {
+            Some((limit_by, ref by)) => {
+                let key = by.iter().map(|&j| result.columns[result_columns[j]].get_raw(i)).collect();
+                let count = counts.entry(key).or_insert(0);
+                *count += 1;
+                *count <= limit_by
+            }
+            None => true,
+        });
+        rows.skip(self.limit.offset as usize)
+            .take(limit)
+            .map(|i| result_columns.iter().map(|&j| result.columns[j].get_raw(i)).collect())
+            .collect()
+    }
+
+    /// Type of the values of each result column as determined by the query plan of `result`, which is known even if
+    /// `result` is empty.
+    pub fn result_types(&self, result: &BatchResult) -> Vec<BasicType> {
+        NormalFormQuery::result_columns(result).into_iter()
+            .enumerate()
+            .map(|(position, i)| if result.boolean_projections.contains(&position) {
+                BasicType::Boolean
+            } else {
+                result.columns[i].encoding_type().value_type()
+            })
+            .collect()
+    }
+
+    fn result_columns(result: &BatchResult) -> Vec<usize> {
+        // TODO(#99): use column order of original query
+        result.projection.iter()
+            .cloned()
+            .chain(result.aggregations.iter().map(|&(aggregation, _)| aggregation))
+            .collect()
+    }
+
+    /// Number of rows that have to be retained when combining batches, which is all rows with `limit_by` since it
+    /// removes rows before the limit applies.
+    pub fn row_limit(&self) -> usize {
+        match self.limit_by {
+            Some(_) => usize::MAX,
+            None => (self.limit.limit + self.limit.offset) as usize,
+        }
+    }
+
     pub fn result_column_names(&self) -> Vec<String> {
-        let mut anon_columns = -1;
+        let mut anon_columns = 0usize;
         let select_cols = self.projection
             .iter()
             .map(|expr| match *expr {
                 Expr::ColName(ref name) => name.clone(),
                 _ => {
-                    anon_columns += 1;
                     // TODO(#101): collision with existing columns
-                    format!("col_{}", anon_columns)
+                    let name = format!("col_{}", anon_columns);
+                    anon_columns += 1;
+                    name
                 }
             });
-        let mut anon_aggregates = -1;
         let aggregate_cols = self.aggregate
             .iter()
-            .map(|&(agg, _)| {
-                anon_aggregates += 1;
-                match agg {
-                    Aggregator::Count => format!("count_{}", anon_aggregates),
-                    Aggregator::Sum => format!("sum_{}", anon_aggregates),
-                    Aggregator::Min => format!("min_{}", anon_aggregates),
-                    Aggregator::Max => format!("max_{}", anon_aggregates),
-                }
+            .enumerate()
+            .map(|(anon_aggregates, &(agg, _, _))| match agg {
+                Aggregator::Count => format!("count_{}", anon_aggregates),
+                Aggregator::Sum | Aggregator::SaturatingSum | Aggregator::WrappingSum =>
+                    format!("sum_{}", anon_aggregates),
+                Aggregator::Min => format!("min_{}", anon_aggregates),
+                Aggregator::Max => format!("max_{}", anon_aggregates),
+                Aggregator::ApproxCountDistinct(_) => format!("approx_count_distinct_{}", anon_aggregates),
             });
 
-        select_cols.chain(aggregate_cols).collect()
+        select_cols.chain(aggregate_cols)
+            .enumerate()
+            .map(|(i, name)| match self.aliases.get(i) {
+                Some(Some(alias)) => alias.clone(),
+                _ => name,
+            })
+            .collect()
     }
 }
 
 impl Query {
+    /// Runs the query on each batch and merges the partial results in the same way as the partitions of a table,
+    /// so grouping, ordering, offset and limit apply to the merged result rather than to individual batches.
+    pub fn run_batches<'b>(&self, batches: &[HashMap<&str, &'b Column>]) -> Result<QueryOutput, QueryError> {
+        let start_time_ns = precise_time_ns();
+        let mut query = self.clone();
+        if query.is_select_star() {
+            let mut cols = batches.iter()
+                .flat_map(|batch| batch.keys())
+                .filter(|name| **name != TOMBSTONE_COLUMN)
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>();
+            cols.sort();
+            cols.dedup();
+            query.select = cols.into_iter().map(|col| (Expr::ColName(col), None)).collect();
+        }
+        let (mut main_phase, final_pass) = query.normalize()?;
+        if let Some(ref mut reservoir) = main_phase.reservoir {
+            reservoir.seed = Some(reservoir.seed.unwrap_or(start_time_ns));
+        }
+        if let Some(ref sample) = main_phase.sample {
+            let table_len = batches.iter().map(|batch| batch.values().next().map_or(0, |c| c.len())).sum();
+            main_phase.sampled_rows = Some(sample.resolve(table_len, start_time_ns));
+        }
+        let data_sources = batches.iter()
+            .map(|batch| batch.iter()
+                .map(|(&name, &column)| (name.to_string(), Arc::new(column) as Arc<DataSource + 'b>))
+                .collect::<HashMap<_, _>>())
+            .collect::<Vec<_>>();
+
+        let mut stats = QueryStats::default();
+        let mut batch_results = Vec::new();
+        let mut row_offset = 0;
+        for (partition, columns) in data_sources.iter().enumerate() {
+            // TODO(#96): fix unsafety
+            let columns = unsafe {
+                mem::transmute::<&HashMap<String, Arc<DataSource + 'b>>, &'static HashMap<String, Arc<DataSource>>>(columns)
+            };
+            let partition_len = columns.values().next().map_or(0, |c| c.len());
+            let (batch_result, _) = if main_phase.aggregate.is_empty() {
+                main_phase.run(columns, false, false, partition, partition_len, row_offset, &mut stats)?
+            } else {
+                main_phase.run_aggregate(columns, false, false, partition, partition_len, row_offset, &mut stats)?
+            };
+            row_offset += partition_len;
+            merge_incremental(&mut batch_results, batch_result, main_phase.row_limit())?;
+        }
+
+        let (colnames, coltypes, rows) = match combine_all(batch_results, main_phase.row_limit())? {
+            None => {
+                let colnames = final_pass.as_ref().unwrap_or(&main_phase).result_column_names();
+                let coltypes = vec![BasicType::Null; colnames.len()];
+                (colnames, coltypes, vec![])
+            }
+            Some(mut full_result) => {
+                full_result.narrow_wide_sums()?;
+                match final_pass {
+                    Some(final_pass) => {
+                        let (cols, _referenced_buffers) = full_result.into_columns();
+                        let cols = unsafe {
+                            mem::transmute::<&HashMap<String, Arc<DataSource>>, &'static HashMap<String, Arc<DataSource>>>(&cols)
+                        };
+                        let len = cols.values().next().map_or(0, |c| c.len());
+                        let (result, _) = final_pass.run(cols, false, false, 0, len, 0, &mut stats)?;
+                        (final_pass.result_column_names(),
+                         final_pass.result_types(&result),
+                         final_pass.output_rows(&result, final_pass.limit.limit as usize))
+                    }
+                    None => (main_phase.result_column_names(),
+                             main_phase.result_types(&full_result),
+                             main_phase.output_rows(&full_result, main_phase.limit.limit as usize)),
+                }
+            }
+        };
+        stats.runtime_ns = precise_time_ns() - start_time_ns;
+        stats.rows_scanned = row_offset;
+        Ok(QueryOutput {
+            colnames,
+            coltypes,
+            rows,
+            query_plans: HashMap::default(),
+            stats,
+            truncated: false,
+        })
+    }
+
     pub fn normalize(&self) -> Result<(NormalFormQuery, Option<NormalFormQuery>), QueryError> {
+        if let Some(ref reservoir) = self.reservoir {
+            return self.normalize_reservoir(reservoir);
+        }
         let mut final_projection = Vec::new();
         let mut select = Vec::new();
         let mut aggregate = Vec::new();
         let mut aggregate_colnames = Vec::new();
         let mut select_colnames = Vec::new();
-        for expr in &self.select {
+        let mut select_aliases = Vec::new();
+        let mut aggregate_aliases = Vec::new();
+        // Index in `select` of each selected expression that is not an aggregate
+        let mut selected_index = Vec::new();
+        for (expr, alias) in &self.select {
             let (full_expr, aggregates) = Query::extract_aggregators(expr, &mut aggregate_colnames)?;
+            selected_index.push(if aggregates.is_empty() { Some(select.len()) } else { None });
             if aggregates.is_empty() {
                 let column_name = format!("_cs{}", select_colnames.len());
                 select_colnames.push(column_name.clone());
                 select.push(full_expr);
+                select_aliases.push(alias.clone());
                 final_projection.push(Expr::ColName(column_name));
             } else {
+                // Only used without final pass, in which case each aggregate corresponds to one selected expression
+                aggregate_aliases.push(alias.clone());
                 aggregate.extend(aggregates);
                 final_projection.push(full_expr);
             }
         }
 
+        let mut grouping_changed = false;
+        if !self.group_by.is_empty() {
+            for expr in &select {
+                if !self.group_by.contains(expr) {
+                    bail!(QueryError::TypeError, "{:?} has to appear in GROUP BY or be used in an aggregate", expr)
+                }
+            }
+            for expr in &self.group_by {
+                if !select.contains(expr) {
+                    let colname = format!("_cs{}", select_colnames.len());
+                    select_colnames.push(colname);
+                    select.push(expr.clone());
+                    grouping_changed = true;
+                }
+            }
+            if aggregate.is_empty() {
+                // Grouping without aggregates returns each distinct combination of grouping keys once
+                let colname = format!("_ca{}", aggregate_colnames.len());
+                aggregate_colnames.push(colname);
+                aggregate.push((Aggregator::Count, Expr::Const(RawVal::Int(0)), None));
+                grouping_changed = true;
+            }
+        }
+
+        // Conditions without aggregates are equivalent to filtering the rows before grouping
+        let mut filter = self.filter.clone();
+        let mut final_filter = Expr::Const(RawVal::Int(1));
+        if let Some(ref having) = self.having {
+            for conjunct in conjuncts(having) {
+                let (full_expr, aggregates) = Query::extract_aggregators(conjunct, &mut aggregate_colnames)?;
+                if aggregates.is_empty() {
+                    filter = conjunction(filter, conjunct.clone());
+                    continue;
+                }
+                let mut colnames = HashSet::new();
+                full_expr.add_colnames(&mut colnames);
+                if colnames.iter().any(|colname| !aggregate_colnames.contains(colname)) {
+                    bail!(QueryError::NotImplemented, "HAVING condition that mixes aggregates and columns: {:?}", conjunct)
+                }
+                aggregate.extend(aggregates);
+                final_filter = conjunction(final_filter, full_expr);
+            }
+        }
+
         let require_final_pass = (!aggregate.is_empty() && !self.order_by.is_empty())
+            || grouping_changed
+            || final_filter != Expr::Const(RawVal::Int(1))
             || final_projection.iter()
             .any(|expr| match expr {
                 Expr::ColName(_) => false,
@@ -403,56 +1068,172 @@ impl Query {
             });
 
         Ok(if require_final_pass {
+            let grouping_colnames = select_colnames.clone();
             let mut final_order_by = Vec::new();
-            for (expr, desc) in &self.order_by {
+            for (expr, desc, nulls) in &self.order_by {
                 let (full_expr, aggregates) = Query::extract_aggregators(expr, &mut aggregate_colnames)?;
                 if aggregates.is_empty() {
-                    let column_name = format!("_cs{}", select_colnames.len());
-                    select_colnames.push(column_name.clone());
-                    select.push(full_expr);
-                    final_order_by.push((Expr::ColName(column_name), *desc));
+                    // Grouping keys that are already selected are not added again
+                    let selected = select.iter().position(|selected| *selected == full_expr);
+                    let column_name = match selected {
+                        Some(index) => select_colnames[index].clone(),
+                        None => {
+                            let column_name = format!("_cs{}", select_colnames.len());
+                            select_colnames.push(column_name.clone());
+                            select.push(full_expr);
+                            column_name
+                        }
+                    };
+                    final_order_by.push((Expr::ColName(column_name), *desc, *nulls));
                 } else {
                     aggregate.extend(aggregates);
-                    final_order_by.push((full_expr, *desc));
+                    final_order_by.push((full_expr, *desc, *nulls));
+                }
+            }
+            // Groups that are equal in all sort keys are ordered by their grouping keys, which makes the order of
+            // the result and the rows retained by `LIMIT` independent of the order in which partitions complete
+            if !aggregate.is_empty() && !final_order_by.is_empty() {
+                for colname in grouping_colnames {
+                    let key = Expr::ColName(colname);
+                    if !final_order_by.iter().any(|(expr, _, _)| *expr == key) {
+                        final_order_by.push((key, false, NullsOrder::Last));
+                    }
                 }
             }
             (
                 NormalFormQuery {
                     projection: select,
-                    filter: self.filter.clone(),
+                    filter,
                     aggregate,
                     order_by: vec![],
                     limit: LimitClause { limit: u64::MAX, offset: 0 },
+                    limit_by: None,
+                    sample: self.sample.clone(),
+                    sampled_rows: None,
+                    explain_only: self.explain,
+                    analyze: self.analyze,
+                    aliases: vec![],
+                    memory_limit_bytes: self.memory_limit_bytes,
+                    batch_size: self.batch_size,
+                    reservoir: None,
                 },
                 Some(NormalFormQuery {
                     projection: final_projection,
-                    filter: Expr::Const(RawVal::Int(1)),
+                    filter: final_filter,
                     aggregate: vec![],
                     order_by: final_order_by,
                     limit: self.limit.clone(),
+                    limit_by: self.resolve_limit_by(|i| Some(i))?,
+                    sample: None,
+                    sampled_rows: None,
+                    explain_only: false,
+                    analyze: self.analyze,
+                    // The final pass reads selected columns under their `_cs` names, so they are aliased to the original ones
+                    aliases: self.select.iter()
+                        .map(|(expr, alias)| match (alias, expr) {
+                            (Some(alias), _) => Some(alias.clone()),
+                            (None, Expr::ColName(name)) => Some(name.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    memory_limit_bytes: self.memory_limit_bytes,
+                    batch_size: self.batch_size,
+                    reservoir: None,
                 }),
             )
         } else {
             (
                 NormalFormQuery {
                     projection: select,
-                    filter: self.filter.clone(),
+                    filter,
                     aggregate,
                     order_by: self.order_by.clone(),
                     limit: self.limit.clone(),
+                    limit_by: self.resolve_limit_by(|i| selected_index[i])?,
+                    sample: self.sample.clone(),
+                    sampled_rows: None,
+                    explain_only: self.explain,
+                    analyze: self.analyze,
+                    aliases: select_aliases.into_iter().chain(aggregate_aliases).collect(),
+                    memory_limit_bytes: self.memory_limit_bytes,
+                    batch_size: self.batch_size,
+                    reservoir: None,
                 },
                 None,
             )
         })
     }
 
-    pub fn extract_aggregators(expr: &Expr, column_names: &mut Vec<String>) -> Result<(Expr, Vec<(Aggregator, Expr)>), QueryError> {
+    /// Sampled queries are ordered by a random key internally, so they can't have an `ORDER BY` clause or aggregates.
+    /// The `LIMIT` clause is replaced by the size of the sample.
+    fn normalize_reservoir(&self, reservoir: &ReservoirSample) -> Result<(NormalFormQuery, Option<NormalFormQuery>), QueryError> {
+        if !self.order_by.is_empty() {
+            bail!(QueryError::NotImplemented, "ORDER BY in query with SAMPLE {}", reservoir.rows)
+        }
+        if self.limit_by.is_some() {
+            bail!(QueryError::NotImplemented, "LIMIT BY in query with SAMPLE {}", reservoir.rows)
+        }
+        if !self.group_by.is_empty() || self.having.is_some() {
+            bail!(QueryError::NotImplemented, "GROUP BY in query with SAMPLE {}", reservoir.rows)
+        }
+        for (expr, _) in &self.select {
+            let (_, aggregates) = Query::extract_aggregators(expr, &mut Vec::new())?;
+            if !aggregates.is_empty() {
+                bail!(QueryError::NotImplemented, "Aggregation in query with SAMPLE {}", reservoir.rows)
+            }
+        }
+        Ok((
+            NormalFormQuery {
+                projection: self.select.iter().map(|(expr, _)| expr.clone()).collect(),
+                filter: self.filter.clone(),
+                aggregate: vec![],
+                order_by: vec![],
+                limit: LimitClause { limit: reservoir.rows, offset: 0 },
+                limit_by: None,
+                sample: self.sample.clone(),
+                sampled_rows: None,
+                explain_only: self.explain,
+                analyze: self.analyze,
+                aliases: self.select.iter().map(|(_, alias)| alias.clone()).collect(),
+                memory_limit_bytes: self.memory_limit_bytes,
+                batch_size: self.batch_size,
+                reservoir: Some(reservoir.clone()),
+            },
+            None,
+        ))
+    }
+
+    /// Resolves each expression of the `LIMIT BY` clause to the selected expression it is equal to, `result_index`
+    /// maps the position of that expression in `select` to the index of its result column.
+    fn resolve_limit_by<F: Fn(usize) -> Option<usize>>(&self, result_index: F) -> Result<Option<(u64, Vec<usize>)>, QueryError> {
+        let limit_by = match self.limit_by {
+            Some(ref limit_by) => limit_by,
+            None => return Ok(None),
+        };
+        let mut columns = Vec::with_capacity(limit_by.by.len());
+        for expr in &limit_by.by {
+            let column = self.select.iter()
+                .position(|(selected, _)| selected == expr)
+                .filter(|_| Query::ensure_no_aggregates(expr).is_ok())
+                .and_then(|i| result_index(i));
+            match column {
+                Some(column) => columns.push(column),
+                None => bail!(QueryError::TypeError, "LIMIT BY expression {:?} has to be selected and can't contain aggregates.", expr),
+            }
+        }
+        Ok(Some((limit_by.limit, columns)))
+    }
+
+    pub fn extract_aggregators(expr: &Expr, column_names: &mut Vec<String>) -> Result<(Expr, Vec<(Aggregator, Expr, Option<Expr>)>), QueryError> {
         Ok(match expr {
-            Expr::Aggregate(aggregator, expr) => {
+            Expr::Aggregate(aggregator, expr, filter) => {
                 let column_name = format!("_ca{}", column_names.len());
                 column_names.push(column_name.clone());
                 Query::ensure_no_aggregates(expr)?;
-                (Expr::ColName(column_name), vec![(*aggregator, *expr.clone())])
+                if let Some(filter) = filter {
+                    Query::ensure_no_aggregates(filter)?;
+                }
+                (Expr::ColName(column_name), vec![(*aggregator, *expr.clone(), filter.as_ref().map(|f| *f.clone()))])
             }
             Expr::Func1(t, expr) => {
                 let (expr, aggregates) = Query::extract_aggregators(expr, column_names)?;
@@ -470,7 +1251,7 @@ impl Query {
 
     pub fn ensure_no_aggregates(expr: &Expr) -> Result<(), QueryError> {
         match expr {
-            Expr::Aggregate(_, _) => {
+            Expr::Aggregate(_, _, _) => {
                 bail!(QueryError::TypeError, "Nested aggregates found.")
             }
             Expr::Func1(_, expr) => {
@@ -485,9 +1266,108 @@ impl Query {
         Ok(())
     }
 
+    /// Checks the query against the columns of `schema` without running it and returns all problems that were found:
+    /// columns that don't exist, nested aggregates, aggregates in `WHERE` or `GROUP BY`, `HAVING` without aggregates,
+    /// `ORDER BY` expressions of aggregate queries that are neither selected nor grouped by, `LIMIT BY` expressions
+    /// that are not selected, and limits that overflow.
+    /// Renamed columns can be referenced by their old name, which `schema` doesn't list, as long as `column_aliases`
+    /// contains it.
+    pub fn validate(&self, schema: &TableSchema, column_aliases: &ColumnAliases) -> Vec<QueryError> {
+        let mut errors = Vec::new();
+        let aliases = self.select.iter().filter_map(|(_, alias)| alias.as_ref()).collect::<HashSet<_>>();
+        let available = schema.columns.iter().map(|(name, _, _)| name.as_str()).collect::<Vec<_>>();
+        let is_stored = |col: &str| available.contains(&col) || column_aliases.new_name(col).is_some();
+        let mut missing = self.find_referenced_cols().into_iter()
+            .filter(|col| col != "*" && col != ROWNUM_COLUMN && !aliases.contains(col) && !is_stored(col))
+            .collect::<Vec<_>>();
+        missing.sort();
+        for col in missing {
+            errors.push(QueryError::UnknownColumn(col, available.join(", ")));
+        }
+
+        let aggregates = self.select.iter().map(|(expr, _)| expr)
+            .chain(self.order_by.iter().map(|(expr, _, _)| expr))
+            .chain(self.having.iter());
+        for expr in aggregates {
+            if let Err(err) = Query::ensure_no_nested_aggregates(expr) {
+                errors.push(err);
+            }
+        }
+        if Query::ensure_no_aggregates(&self.filter).is_err() {
+            errors.push(QueryError::TypeError("Aggregates are not allowed in WHERE.".to_string()));
+        }
+        if self.group_by.iter().any(|expr| Query::ensure_no_aggregates(expr).is_err()) {
+            errors.push(QueryError::TypeError("Aggregates are not allowed in GROUP BY.".to_string()));
+        }
+
+        let is_aggregation = self.select.iter().any(|(expr, _)| Query::ensure_no_aggregates(expr).is_err());
+        if !is_aggregation && self.having.is_some() {
+            errors.push(QueryError::TypeError("HAVING requires a query with aggregates.".to_string()));
+        }
+        if is_aggregation {
+            for (expr, _, _) in &self.order_by {
+                let is_selected = self.select.iter().any(|(selected, alias)| {
+                    selected == expr || match (alias, expr) {
+                        (Some(alias), Expr::ColName(name)) => alias == name,
+                        _ => false,
+                    }
+                });
+                if Query::ensure_no_aggregates(expr).is_ok() && !is_selected && !self.group_by.contains(expr) {
+                    errors.push(QueryError::TypeError(
+                        format!("ORDER BY expression {:?} is neither selected nor grouped by.", expr)));
+                }
+            }
+        }
+
+        if let Some(ref limit_by) = self.limit_by {
+            for expr in &limit_by.by {
+                if Query::ensure_no_aggregates(expr).is_err() || !self.select.iter().any(|(selected, _)| selected == expr) {
+                    errors.push(QueryError::TypeError(
+                        format!("LIMIT BY expression {:?} has to be selected and can't contain aggregates.", expr)));
+                }
+            }
+        }
+
+        if self.limit.limit.checked_add(self.limit.offset).is_none() {
+            errors.push(QueryError::NotImplemented(
+                format!("LIMIT {} OFFSET {} exceeds the maximum number of rows.", self.limit.limit, self.limit.offset)));
+        }
+        errors
+    }
+
+    /// Fails if any aggregate in `expr` contains another aggregate.
+    fn ensure_no_nested_aggregates(expr: &Expr) -> Result<(), QueryError> {
+        match expr {
+            Expr::Aggregate(_, expr, filter) => {
+                Query::ensure_no_aggregates(expr)?;
+                if let Some(filter) = filter {
+                    Query::ensure_no_aggregates(filter)?;
+                }
+            }
+            Expr::Func1(_, expr) => Query::ensure_no_nested_aggregates(expr)?,
+            Expr::Func2(_, expr1, expr2) => {
+                Query::ensure_no_nested_aggregates(expr1)?;
+                Query::ensure_no_nested_aggregates(expr2)?;
+            }
+            Expr::Const(_) | Expr::ColName(_) => (),
+        };
+        Ok(())
+    }
+
+    /// Names the result columns of selected expressions that are keys of `aliases` by the corresponding value, which
+    /// takes precedence over an alias given in the query. Other columns keep their alias or generated name.
+    pub fn with_column_aliases(mut self, aliases: HashMap<Expr, String>) -> Query {
+        for (expr, alias) in &mut self.select {
+            if let Some(name) = aliases.get(&*expr) {
+                *alias = Some(name.to_string());
+            }
+        }
+        self
+    }
+
     pub fn is_select_star(&self) -> bool {
         if self.select.len() == 1 {
-            match self.select[0] {
+            match self.select[0].0 {
                 Expr::ColName(ref colname) if colname == "*" => true,
                 _ => false,
             }
@@ -498,15 +1378,130 @@ impl Query {
 
     pub fn find_referenced_cols(&self) -> HashSet<String> {
         let mut colnames = HashSet::new();
-        for expr in &self.select {
+        for (expr, _) in &self.select {
             expr.add_colnames(&mut colnames);
         }
         for expr in &self.order_by {
             expr.0.add_colnames(&mut colnames);
         }
+        for expr in &self.group_by {
+            expr.add_colnames(&mut colnames);
+        }
+        if let Some(ref having) = self.having {
+            having.add_colnames(&mut colnames);
+        }
         self.filter.add_colnames(&mut colnames);
         colnames
     }
+
+    /// Serializes the query as JSON, which allows queries to be sent to a server over HTTP.
+    #[cfg(feature = "enable_serde")]
+    pub fn to_json(&self) -> String {
+        ::serde_json::to_string(self).expect("Queries only contain JSON serializable values")
+    }
+
+    /// Inverse of `to_json`.
+    #[cfg(feature = "enable_serde")]
+    pub fn from_json(json: &str) -> Result<Query, QueryError> {
+        ::serde_json::from_str(json).map_err(|e| QueryError::ParseError(format!("Invalid query JSON: {}", e)))
+    }
+}
+
+/// Splits `expr` into the operands of its top level conjunction.
+fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Func2(Func2Type::And, lhs, rhs) => {
+            let mut result = conjuncts(lhs);
+            result.extend(conjuncts(rhs));
+            result
+        }
+        _ => vec![expr],
+    }
+}
+
+/// `lhs AND rhs`, where a constant true `lhs` is left out.
+fn conjunction(lhs: Expr, rhs: Expr) -> Expr {
+    match lhs {
+        Expr::Const(RawVal::Int(1)) => rhs,
+        lhs => Expr::func(Func2Type::And, lhs, rhs),
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_executor::block_on;
+    use ingest::input_column::InputColumn;
+    use locustdb::LocustDB;
+    use mem_store::column_builder::build_column;
+    use mem_store::integers::IntegerColumn;
+    use syntax::parser;
+
+    #[test]
+    fn test_with_column_aliases() {
+        let mut aliases = HashMap::new();
+        aliases.insert(Expr::func(Func2Type::Add, Expr::ColName("a".to_string()), Expr::Const(RawVal::Int(1))), "next".to_string());
+        aliases.insert(Expr::Aggregate(Aggregator::Sum, Box::new(Expr::ColName("c".to_string())), None), "total".to_string());
+        aliases.insert(Expr::ColName("missing".to_string()), "unused".to_string());
+        let query = parser::parse_query("SELECT a + 1, b AS x, a, SUM(c) FROM t;").unwrap().with_column_aliases(aliases);
+        let (main_phase, final_pass) = query.normalize().unwrap();
+        assert_eq!(final_pass.unwrap_or(main_phase).result_column_names(), vec!["next", "x", "a", "total"]);
+    }
+
+    #[test]
+    fn test_result_column_names() {
+        let query = parser::parse_query("SELECT a + 1, b, a * 2, SUM(c), COUNT(0), MAX(c), SUM(d) FROM t;").unwrap();
+        let (main_phase, final_pass) = query.normalize().unwrap();
+        assert_eq!(final_pass.unwrap_or(main_phase).result_column_names(),
+                   vec!["col_0", "b", "col_1", "sum_0", "count_1", "max_2", "sum_3"]);
+    }
+
+    #[test]
+    fn test_run_batches() {
+        let locustdb = LocustDB::memory_only();
+        let mut batches = Vec::new();
+        for batch in 0..3 {
+            let x = (0..100).map(|i| (i * 37 + batch * 11) % 150).collect::<Vec<i64>>();
+            let s = x.iter().map(|x| format!("s{}", x % 7)).collect::<Vec<_>>();
+            block_on(locustdb.ingest_with_timestamp("t",
+                                                    vec![("x".to_string(), InputColumn::Int(x.clone())),
+                                                         ("s".to_string(), InputColumn::Str(s.clone()))],
+                                                    SystemTime::now())).unwrap().unwrap();
+            batches.push(vec![
+                build_column("x", x.into_iter().map(RawVal::Int).collect()),
+                build_column("s", s.into_iter().map(RawVal::Str).collect()),
+            ]);
+        }
+        let batches = batches.iter()
+            .map(|columns| columns.iter().map(|column| (column.name(), &**column)).collect::<HashMap<_, _>>())
+            .collect::<Vec<_>>();
+        for query in &["SELECT x, s FROM t ORDER BY x DESC, s LIMIT 10 OFFSET 5;",
+                       "SELECT s, COUNT(0), SUM(x) FROM t ORDER BY s LIMIT 3 OFFSET 2;",
+                       "SELECT s, x FROM t WHERE x > 100 ORDER BY x LIMIT 1 BY s LIMIT 4 OFFSET 1;",
+                       "SELECT COUNT(0), MAX(x) FROM t;"] {
+            let expected = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+            let output = parser::parse_query(query).unwrap().run_batches(&batches).unwrap();
+            assert_eq!(output.colnames, expected.colnames, "{}", query);
+            assert_eq!(output.rows, expected.rows, "{}", query);
+        }
+        assert!(parser::parse_query("SELECT x FROM t;").unwrap().run_batches(&[]).unwrap().rows.is_empty());
+    }
 
+    #[test]
+    fn test_negated_filter() {
+        let mut columns = HashMap::<String, Arc<DataSource>>::default();
+        columns.insert("a".to_string(), IntegerColumn::new_boxed("a", (0..100).collect(), 0, 99, false, None, 0));
+        let mut query = parser::parse_query("SELECT a FROM t WHERE a < 10;").unwrap();
+        query.filter = Expr::Func1(Func1Type::Not, Box::new(query.filter));
+        let (main_phase, _) = query.normalize().unwrap();
+        let mut planner = QueryPlanner::default();
+        match main_phase.compile_filter(&columns, 100, &mut planner).unwrap() {
+            Filter::Not(box Filter::U8(_)) => {}
+            _ => panic!("Expected filter that selects the rows for which a < 10 is false"),
+        }
+        assert!(!planner.operations.iter().any(|operation| match operation {
+            QueryPlan::Not { .. } => true,
+            _ => false,
+        }));
+    }
+}
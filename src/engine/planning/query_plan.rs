@@ -8,12 +8,20 @@ use engine::*;
 use ingest::raw_val::RawVal;
 use mem_store::*;
 use mem_store::column::DataSource;
+use mem_store::partition::ROWNUM_COLUMN;
 use mem_store::value::Val;
+use std::cmp;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::i64;
 use std::result::Result;
 use std::sync::Arc;
 use syntax::expression::*;
+use syntax::sample::SampledRows;
+
+/// Remaining operands of `AND` and `OR` in WHERE clauses are only evaluated for the rows that the first operand
+/// doesn't determine the result for if these are estimated to be at most this fraction of all rows.
+const SHORT_CIRCUIT_MAX_UNDETERMINED: f64 = 0.25;
 
 #[derive(Debug, Clone, ASTBuilder)]
 pub enum QueryPlan {
@@ -56,6 +64,12 @@ pub enum QueryPlan {
         #[output]
         present: BufferRef<u8>,
     },
+    /// Creates a null map in which all elements are present for which `bools` is nonzero (and not null).
+    BoolsToNullMap {
+        bools: TypedBufferRef,
+        #[output]
+        present: BufferRef<u8>,
+    },
     /// Combines a vector with a null map where none of the elements are null.
     MakeNullable {
         data: TypedBufferRef,
@@ -71,6 +85,13 @@ pub enum QueryPlan {
         #[output(t = "base=nullable;null=_fused")]
         fused: TypedBufferRef,
     },
+    /// Like `FuseNulls`, but represents nulls of NullableI64 by i64::MAX so that they sort after all other integers.
+    /// Only used for sort keys, which are never unfused.
+    FuseNullsMax {
+        nullable: TypedBufferRef,
+        #[output(t = "base=nullable;null=_fused")]
+        fused: TypedBufferRef,
+    },
     /// Converts Nullable integer types into a representation where nulls are encoded as value `null`.
     FuseIntNulls {
         offset: i64,
@@ -125,6 +146,49 @@ pub enum QueryPlan {
         #[output]
         decoded: BufferRef<Scalar<i64>>,
     },
+    /// Determines the position of a string constant in a sorted dictionary, see `Codec::encode_str_bound`.
+    DictRank {
+        offset_len: BufferRef<u64>,
+        backing_store: BufferRef<u8>,
+        constant: BufferRef<Scalar<&'static str>>,
+        round_down: bool,
+        #[output]
+        rank: BufferRef<Scalar<i64>>,
+    },
+    /// Determines for each dictionary entry whether it matches `regex`.
+    DictRegex {
+        offset_len: BufferRef<u64>,
+        backing_store: BufferRef<u8>,
+        regex: String,
+        #[output]
+        dict_matches: BufferRef<u8>,
+    },
+    /// Case folds each dictionary entry, see `DictCaseFold`.
+    DictCaseFold {
+        offset_len: BufferRef<u64>,
+        backing_store: BufferRef<u8>,
+        upper: bool,
+        #[output]
+        folded_offset_len: BufferRef<u64>,
+        #[output]
+        folded_backing_store: BufferRef<u8>,
+        #[output]
+        remap: BufferRef<u64>,
+    },
+    /// Maps dictionary indices to the corresponding entry in `remap`.
+    DictRemap {
+        indices: TypedBufferRef,
+        remap: BufferRef<u64>,
+        #[output(t = "base=indices")]
+        remapped: TypedBufferRef,
+    },
+    /// Resolves dictionary indices to the corresponding entry in `dict_matches`.
+    DictMatchLookup {
+        indices: TypedBufferRef,
+        dict_matches: BufferRef<u8>,
+        #[output]
+        matches: BufferRef<u8>,
+    },
     /// Casts `input` to the specified type.
     Cast {
         input: TypedBufferRef,
@@ -138,6 +202,28 @@ pub enum QueryPlan {
         #[output(t = "base=provided")]
         decoded: TypedBufferRef,
     },
+    /// Zstd decodes `bytes` into `decoded_len` elements of type `t`.
+    ZstdDecode {
+        bytes: BufferRef<u8>,
+        decoded_len: usize,
+        #[output(t = "base=provided")]
+        decoded: TypedBufferRef,
+    },
+    /// Expands the first `len` bits of `bits` into one byte per bit that is 1 if the bit is set and 0 otherwise.
+    UnpackBits {
+        bits: BufferRef<u8>,
+        len: usize,
+        #[output]
+        unpacked: BufferRef<u8>,
+    },
+    /// Unpacks `len` integers that are stored with `bits` bits each in `packed` into one byte per integer.
+    UnpackInts {
+        packed: BufferRef<u8>,
+        bits: usize,
+        len: usize,
+        #[output]
+        unpacked: BufferRef<u8>,
+    },
     /// Decodes a byte array of tightly packed strings.
     UnpackStrings {
         bytes: BufferRef<u8>,
@@ -160,6 +246,22 @@ pub enum QueryPlan {
         #[output]
         delta_decoded: BufferRef<i64>,
     },
+    /// Adds `frame_minimums[i / frame_size]` to the `i`th element of `plan`.
+    FrameOfReferenceDecode {
+        plan: TypedBufferRef,
+        frame_minimums: BufferRef<i64>,
+        frame_size: usize,
+        #[output]
+        decoded: BufferRef<i64>,
+    },
+    /// Repeats the `i`th element of `plan` `run_lengths[i]` times, which yields `len` elements in total.
+    RunLengthDecode {
+        plan: TypedBufferRef,
+        run_lengths: BufferRef<u32>,
+        len: usize,
+        #[output(t = "base=plan")]
+        decoded: TypedBufferRef,
+    },
     HashMapGrouping {
         raw_grouping_key: TypedBufferRef,
         max_cardinality: usize,
@@ -170,6 +272,16 @@ pub enum QueryPlan {
         #[output]
         cardinality: BufferRef<Scalar<i64>>,
     },
+    /// Grouping with an order preserving grouping key, see `SortGrouping`.
+    SortGrouping {
+        raw_grouping_key: TypedBufferRef,
+        #[output(t = "base=raw_grouping_key")]
+        unique: TypedBufferRef,
+        #[output]
+        grouping_key: BufferRef<u32>,
+        #[output]
+        cardinality: BufferRef<Scalar<i64>>,
+    },
     HashMapGroupingValRows {
         raw_grouping_key: BufferRef<ValRows<'static>>,
         columns: usize,
@@ -189,6 +301,13 @@ pub enum QueryPlan {
         #[output]
         exists: BufferRef<u8>,
     },
+    /// Number of nonzero entries in `select`, or of zero entries if `rejected` is set. Outputs no count if it is zero.
+    CountSelected {
+        select: BufferRef<u8>,
+        rejected: bool,
+        #[output]
+        count: BufferRef<i64>,
+    },
     /// Deletes all zero entries from `plan`.
     NonzeroCompact {
         plan: TypedBufferRef,
@@ -260,14 +379,41 @@ pub enum QueryPlan {
         #[output(t = "base=provided")]
         aggregate: TypedBufferRef,
     },
+    /// Sums `plan` plus `offset` for each group in `grouping_key`. Checked sums are accumulated as `I128`, see
+    /// `CheckedAggregate`.
     CheckedAggregate {
         plan: TypedBufferRef,
         grouping_key: TypedBufferRef,
+        max_index: BufferRef<Scalar<i64>>,
+        aggregator: Aggregator,
+        offset: i64,
+        #[output(t = "base=provided")]
+        aggregate: TypedBufferRef,
+    },
+    /// Adds the wide sums in `rhs` to those in `lhs`, or subtracts them if `subtract` is set.
+    AddWideSums {
+        lhs: TypedBufferRef,
+        rhs: TypedBufferRef,
+        subtract: bool,
+        #[output(t = "base=lhs")]
+        sum: TypedBufferRef,
+    },
+    /// Aggregates a vector of nulls, see `AggregateNulls`.
+    AggregateNulls {
         max_index: BufferRef<Scalar<i64>>,
         aggregator: Aggregator,
         #[output(t = "base=provided")]
         aggregate: TypedBufferRef,
     },
+    /// Accumulates a HyperLogLog sketch of the values in `plan` for each group in `grouping_key`.
+    ApproxCountDistinct {
+        plan: TypedBufferRef,
+        grouping_key: TypedBufferRef,
+        max_index: BufferRef<Scalar<i64>>,
+        precision: u8,
+        #[output(t = "base=provided")]
+        sketches: TypedBufferRef,
+    },
     LessThan {
         lhs: TypedBufferRef,
         rhs: TypedBufferRef,
@@ -399,6 +545,16 @@ pub enum QueryPlan {
         #[output(t = "base=u8;null=lhs,rhs")]
         or: TypedBufferRef,
     },
+    /// `select AND condition`, or `select OR condition` if `disjunction` is set, where `condition` was only evaluated
+    /// for the rows that can still change the result, i.e. the rows selected by `select` or not selected if
+    /// `disjunction` is set. Rows for which `condition` is null are not selected.
+    ShortCircuit {
+        select: BufferRef<u8>,
+        condition: TypedBufferRef,
+        disjunction: bool,
+        #[output]
+        combined: BufferRef<u8>,
+    },
     Not {
         input: BufferRef<u8>,
         #[output]
@@ -420,18 +576,53 @@ pub enum QueryPlan {
         #[output]
         length: BufferRef<i64>,
     },
+    CaseFold {
+        string: BufferRef<&'static str>,
+        upper: bool,
+        #[internal]
+        string_store: BufferRef<u8>,
+        #[output]
+        folded: BufferRef<&'static str>,
+    },
+    /// Parses strings as integers, strings that don't contain an integer are null.
+    ParseInt {
+        string: BufferRef<&'static str>,
+        #[output]
+        parsed: BufferRef<Nullable<i64>>,
+    },
+    IntToString {
+        int: BufferRef<i64>,
+        #[internal]
+        string_store: BufferRef<u8>,
+        #[output]
+        string: BufferRef<&'static str>,
+    },
+    CharLength {
+        string: BufferRef<&'static str>,
+        #[output]
+        length: BufferRef<i64>,
+    },
+    Substring {
+        string: BufferRef<&'static str>,
+        start: i64,
+        length: i64,
+        #[output]
+        substring: BufferRef<&'static str>,
+    },
     /// Outputs a vector of indices from `0..plan.len()`
     Indices {
         plan: TypedBufferRef,
         #[output]
         indices: BufferRef<usize>,
     },
-    /// Outputs a permutation of `indices` under which `ranking` is sorted.
+    /// Outputs a permutation of `indices` under which `ranking` is sorted, with null values placed before all other
+    /// values if `nulls_first` is set and after them otherwise.
     SortBy {
         ranking: TypedBufferRef,
         indices: BufferRef<usize>,
         desc: bool,
         stable: bool,
+        nulls_first: bool,
         #[output]
         permutation: BufferRef<usize>,
     },
@@ -459,6 +650,13 @@ pub enum QueryPlan {
         #[output(t = "base=plan")]
         filtered: TypedBufferRef,
     },
+    /// Outputs all elements in `plan` for which the corresponding entry in `select` is zero.
+    FilterNot {
+        plan: TypedBufferRef,
+        select: BufferRef<u8>,
+        #[output(t = "base=plan")]
+        filtered: TypedBufferRef,
+    },
     /// Outputs all elements in `plan` for which the corresponding entry in `select` is nonzero and not null.
     NullableFilter {
         plan: TypedBufferRef,
@@ -496,12 +694,55 @@ pub enum QueryPlan {
         #[output(t = "base=provided")]
         expanded: TypedBufferRef,
     },
+    /// Outputs a vector of length `len` in which the elements for the row numbers `offset..offset + len` that are
+    /// selected by `rows` are set to 1.
+    SampleMask {
+        offset: usize,
+        len: usize,
+        #[nohash]
+        rows: SampledRows,
+        #[output]
+        mask: BufferRef<u8>,
+    },
+    /// Outputs a pseudorandom nonnegative key for each of the row numbers `offset..offset + len`.
+    SampleKeys {
+        offset: usize,
+        len: usize,
+        seed: u64,
+        #[output]
+        keys: BufferRef<i64>,
+    },
+    /// Outputs the row numbers `offset..offset + len`.
+    RowNumbers {
+        offset: usize,
+        len: usize,
+        #[output]
+        row_numbers: BufferRef<i64>,
+    },
+    /// Outputs a vector of length `len` that is 1 for the rows whose row number `offset + i` satisfies `(offset + i) % stride == phase`.
+    StridedMask {
+        offset: usize,
+        len: usize,
+        stride: usize,
+        phase: usize,
+        #[output]
+        mask: BufferRef<u8>,
+    },
+    /// Outputs a vector that is 1 for the elements of the sorted `plan` that lie in `[lower, upper]`.
+    SortedRange {
+        plan: TypedBufferRef,
+        lower: i64,
+        upper: i64,
+        #[output]
+        mask: BufferRef<u8>,
+    },
     /// Merges `lhs` and `rhs` and outputs a merge plan .
     Merge {
         lhs: TypedBufferRef,
         rhs: TypedBufferRef,
         limit: usize,
         desc: bool,
+        nulls_first: bool,
         #[output]
         merge_ops: BufferRef<u8>,
         #[output(t = "base=lhs;null=lhs,rhs")]
@@ -514,6 +755,7 @@ pub enum QueryPlan {
         rhs: TypedBufferRef,
         limit: usize,
         desc: bool,
+        nulls_first: bool,
         #[output]
         take_left: BufferRef<u8>,
         #[output(t = "base=lhs")]
@@ -546,6 +788,7 @@ pub enum QueryPlan {
         rhs: TypedBufferRef,
         limit: usize,
         desc: bool,
+        nulls_first: bool,
         #[output]
         partitioning: BufferRef<Premerge>,
     },
@@ -556,6 +799,7 @@ pub enum QueryPlan {
         lhs: TypedBufferRef,
         rhs: TypedBufferRef,
         desc: bool,
+        nulls_first: bool,
         #[output]
         subpartitioning: BufferRef<Premerge>,
     },
@@ -584,6 +828,22 @@ pub enum QueryPlan {
         #[output]
         merged: BufferRef<i64>,
     },
+    /// Merges the wide sums in `lhs` and `rhs` according to `merge_ops`, adding up duplicates.
+    MergeWideSums {
+        merge_ops: BufferRef<MergeOp>,
+        lhs: TypedBufferRef,
+        rhs: TypedBufferRef,
+        #[output(t = "base=lhs")]
+        merged: TypedBufferRef,
+    },
+    /// Merges the HyperLogLog sketches in `lhs` and `rhs` according to `merge_ops`, taking the union of duplicates.
+    MergeHyperLogLogs {
+        merge_ops: BufferRef<MergeOp>,
+        lhs: TypedBufferRef,
+        rhs: TypedBufferRef,
+        #[output(t = "base=lhs")]
+        merged: TypedBufferRef,
+    },
 }
 
 pub fn prepare_hashmap_grouping(raw_grouping_key: TypedBufferRef,
@@ -607,6 +867,20 @@ pub fn prepare_hashmap_grouping(raw_grouping_key: TypedBufferRef,
         cardinality_out))
 }
 
+pub fn prepare_sort_grouping(raw_grouping_key: TypedBufferRef,
+                             is_raw_grouping_key_order_preserving: bool,
+                             planner: &mut QueryPlanner)
+                             -> (Option<TypedBufferRef>,
+                                 TypedBufferRef,
+                                 bool,
+                                 BufferRef<Scalar<i64>>) {
+    let (unique_out, grouping_key_out, cardinality_out) = planner.sort_grouping(raw_grouping_key);
+    (Some(unique_out),
+     grouping_key_out.into(),
+     is_raw_grouping_key_order_preserving,
+     cardinality_out)
+}
+
 pub fn prepare_aggregation(mut plan: TypedBufferRef,
                            plan_type: Type,
                            grouping_key: TypedBufferRef,
@@ -614,29 +888,322 @@ pub fn prepare_aggregation(mut plan: TypedBufferRef,
                            aggregator: Aggregator,
                            planner: &mut QueryPlanner)
                            -> Result<(TypedBufferRef, Type), QueryError> {
+    let name = match aggregator {
+        Aggregator::Sum | Aggregator::SaturatingSum | Aggregator::WrappingSum => Some("sum"),
+        Aggregator::Max => Some("max"),
+        Aggregator::Min => Some("min"),
+        Aggregator::Count | Aggregator::ApproxCountDistinct(_) => None,
+    };
+    if let Some(name) = name {
+        match plan_type.decoded {
+            BasicType::String | BasicType::NullableString | BasicType::Val =>
+                bail!(QueryError::TypeError, "{} requires numeric input, found {:?}", name, plan_type.decoded),
+            _ => {}
+        }
+    }
+    if plan.tag == EncodingType::Null {
+        return Ok(match aggregator {
+            Aggregator::Count => (planner.aggregate_nulls(max_index, Aggregator::Count, EncodingType::U64),
+                                  Type::encoded(Codec::integer_cast(EncodingType::U64))),
+            Aggregator::Sum | Aggregator::SaturatingSum =>
+                (planner.aggregate_nulls(max_index, Aggregator::Sum, EncodingType::I128), Type::unencoded(BasicType::Integer)),
+            Aggregator::WrappingSum =>
+                (planner.aggregate_nulls(max_index, Aggregator::Sum, EncodingType::I64), Type::unencoded(BasicType::Integer)),
+            Aggregator::Max | Aggregator::Min =>
+                (planner.aggregate_nulls(max_index, aggregator, EncodingType::I64), Type::unencoded(BasicType::Integer)),
+            Aggregator::ApproxCountDistinct(precision) =>
+                (planner.approx_count_distinct(plan, grouping_key, max_index, precision, EncodingType::HyperLogLog),
+                 Type::unencoded(BasicType::Integer)),
+        });
+    }
     Ok(match aggregator {
         Aggregator::Count => {
-            let plan = if plan.tag == EncodingType::ScalarI64 { grouping_key.clone() } else { plan };
-            (planner.aggregate(plan, grouping_key, max_index, Aggregator::Count, EncodingType::U32),
-             Type::encoded(Codec::integer_cast(EncodingType::U32)))
+            // The count does not depend on values, so the grouping key is counted in place of the input.
+            // Nullable inputs pass on their null map, which makes the count skip null values.
+            let plan = if plan.is_nullable() {
+                let present = planner.get_null_map(plan);
+                planner.assemble_nullable(grouping_key.clone(), present)
+            } else {
+                grouping_key.clone()
+            };
+            (planner.aggregate(plan, grouping_key, max_index, Aggregator::Count, EncodingType::U64),
+             Type::encoded(Codec::integer_cast(EncodingType::U64)))
         }
-        Aggregator::Sum => {
+        // Partial sums are kept as `I128` until all partitions have been merged, see `BatchResult::narrow_wide_sums`
+        Aggregator::Sum | Aggregator::SaturatingSum => if let Some(offset) = plan_type.codec.as_ref().and_then(|c| c.additive_offset()).filter(|&o| o != 0) {
+            // Adds the offset to each value while summing, so offset encoded values can be summed without decoding them
+            (planner.checked_aggregate(plan, grouping_key, max_index, aggregator, offset, EncodingType::I128),
+             Type::unencoded(BasicType::Integer))
+        } else {
+            if !plan_type.is_summation_preserving() {
+                plan = plan_type.codec.clone().unwrap().decode(plan, planner);
+            }
+            (planner.checked_aggregate(plan, grouping_key, max_index, aggregator, 0, EncodingType::I128),
+             Type::unencoded(BasicType::Integer))
+        },
+        Aggregator::WrappingSum => {
+            // Partial sums are wrapped, so they have to decode nonzero offsets to get the same result
             if !plan_type.is_summation_preserving() {
                 plan = plan_type.codec.clone().unwrap().decode(plan, planner);
             }
             // PERF: determine dense groupings
-            (planner.checked_aggregate(plan, grouping_key, max_index, Aggregator::Sum, EncodingType::I64),
+            (planner.checked_aggregate(plan, grouping_key, max_index, aggregator, 0, EncodingType::I64),
              Type::unencoded(BasicType::Integer))
         }
         Aggregator::Max | Aggregator::Min => {
             // PERF: don't always have to decode before taking max/min, and after is more efficient (e.g. dict encoded strings)
-            plan = plan_type.codec.clone().unwrap().decode(plan, planner);
+            if plan_type.is_encoded() {
+                plan = plan_type.codec.clone().unwrap().decode(plan, planner);
+            }
             (planner.aggregate(plan, grouping_key, max_index, aggregator, EncodingType::I64),
              Type::unencoded(BasicType::Integer))
         }
+        Aggregator::ApproxCountDistinct(precision) => {
+            // Sketches are merged across partitions, so they have to be built from decoded values
+            if plan_type.is_encoded() {
+                plan = plan_type.codec.clone().unwrap().decode(plan, planner);
+            }
+            (planner.approx_count_distinct(plan, grouping_key, max_index, precision, EncodingType::HyperLogLog),
+             Type::unencoded(BasicType::Integer))
+        }
+    })
+}
+
+/// If `expr` adds and subtracts columns without nulls, returns the columns and whether they are subtracted.
+/// Then `sum(expr)` can be computed from the sums of the individual columns, each of which can be computed on the
+/// encoded values. Only holds if no intermediate result can overflow, which is ensured by requiring that the
+/// absolute values of all columns summed over `len` rows fit into an `i64`.
+pub fn summands<'a>(expr: &'a Expr, columns: &HashMap<String, Arc<DataSource>>, len: usize) -> Option<Vec<(&'a str, bool)>> {
+    fn collect<'a>(expr: &'a Expr, negated: bool, terms: &mut Vec<(&'a str, bool)>) -> bool {
+        match *expr {
+            Expr::ColName(ref name) => {
+                terms.push((name, negated));
+                true
+            }
+            Expr::Func2(Func2Type::Add, ref lhs, ref rhs) =>
+                collect(lhs, negated, terms) && collect(rhs, negated, terms),
+            Expr::Func2(Func2Type::Subtract, ref lhs, ref rhs) =>
+                collect(lhs, negated, terms) && collect(rhs, !negated, terms),
+            _ => false,
+        }
+    }
+    match *expr {
+        Expr::Func2(Func2Type::Add, _, _) | Expr::Func2(Func2Type::Subtract, _, _) => {}
+        _ => return None,
+    }
+    let mut terms = Vec::new();
+    if !collect(expr, false, &mut terms) {
+        return None;
+    }
+    let mut bound = 0i128;
+    for &(name, _) in &terms {
+        let column = columns.get(name)?;
+        if column.null_count() != Some(0) {
+            return None;
+        }
+        let (min, max) = column.value_range()?;
+        bound += cmp::max(i128::from(min).abs(), i128::from(max).abs()) * len as i128;
+    }
+    if bound > i128::from(i64::MAX) {
+        return None;
+    }
+    Some(terms)
+}
+
+/// Computes `sum(expr)` for the `terms` returned by `summands` by summing each column independently.
+pub fn prepare_sum_of_columns(terms: &[(&str, bool)],
+                              filter: &Filter,
+                              grouping_key: TypedBufferRef,
+                              max_index: BufferRef<Scalar<i64>>,
+                              columns: &HashMap<String, Arc<DataSource>>,
+                              len: usize,
+                              planner: &mut QueryPlanner) -> Result<(TypedBufferRef, Type), QueryError> {
+    let mut result: Option<TypedBufferRef> = None;
+    for &(name, negated) in terms {
+        let (plan, t) = QueryPlan::compile_expr(&Expr::ColName(name.to_string()), filter, columns, len, planner)?;
+        let (sum, _) = prepare_aggregation(plan, t, grouping_key, max_index, Aggregator::Sum, planner)?;
+        result = Some(match result {
+            // The leftmost term is never negated
+            None => sum,
+            Some(result) => planner.add_wide_sums(result, sum, negated),
+        });
+    }
+    match result {
+        Some(result) => Ok((result, Type::unencoded(BasicType::Integer))),
+        None => Err(fatal!("Sum of no columns")),
+    }
+}
+
+/// Sets all elements of `plan` to null for which `condition` does not hold.
+/// The nullable aggregation operators skip null elements, so this restricts the aggregation to the matching rows.
+pub fn mask_aggregation_input(plan: TypedBufferRef,
+                              condition: TypedBufferRef,
+                              grouping_key: TypedBufferRef,
+                              aggregator: Aggregator,
+                              planner: &mut QueryPlanner) -> Result<TypedBufferRef, QueryError> {
+    if condition.tag != EncodingType::U8 && condition.tag != EncodingType::NullableU8 {
+        bail!(QueryError::TypeError, "Aggregate filter is not a boolean expression: {:?}", condition.tag)
+    }
+    let plan = if plan.tag == EncodingType::ScalarI64 {
+        if aggregator != Aggregator::Count {
+            bail!(QueryError::NotImplemented, "Filtered {:?} of constant expression", aggregator)
+        }
+        // Count does not depend on values, so this is equivalent to counting the constant
+        grouping_key
+    } else {
+        plan
+    };
+    let present = planner.bools_to_null_map(condition);
+    Ok(if plan.is_nullable() {
+        let condition = planner.assemble_nullable(condition.forget_nullability(), present);
+        let present = planner.combine_null_maps(plan, condition);
+        planner.assemble_nullable(plan.forget_nullability(), present)
+    } else {
+        planner.assemble_nullable(plan, present)
     })
 }
 
+/// Splits a conjunction into its operands, ordered so that the most selective ones are evaluated first.
+/// Operands without a selectivity estimate retain their relative order and come last.
+fn order_conjuncts<'a>(expr: &'a Expr, columns: &HashMap<String, Arc<DataSource>>) -> Vec<&'a Expr> {
+    order_operands(expr, Func2Type::And, columns)
+}
+
+/// Splits a disjunction into its operands, ordered so that the least selective ones are evaluated first.
+/// Operands without a selectivity estimate retain their relative order and come last.
+fn order_disjuncts<'a>(expr: &'a Expr, columns: &HashMap<String, Arc<DataSource>>) -> Vec<&'a Expr> {
+    order_operands(expr, Func2Type::Or, columns)
+}
+
+/// Splits nested applications of `AND` or `OR` into their operands, ordered by the estimated fraction of rows that
+/// each operand doesn't determine the result for.
+fn order_operands<'a>(expr: &'a Expr, function: Func2Type, columns: &HashMap<String, Arc<DataSource>>) -> Vec<&'a Expr> {
+    fn collect_operands<'a>(expr: &'a Expr, function: Func2Type, operands: &mut Vec<&'a Expr>) {
+        match expr {
+            Expr::Func2(f, lhs, rhs) if *f == function => {
+                collect_operands(lhs, function, operands);
+                collect_operands(rhs, function, operands);
+            }
+            _ => operands.push(expr),
+        }
+    }
+    let mut operands = Vec::new();
+    collect_operands(expr, function, &mut operands);
+    let mut estimates = operands.into_iter()
+        .map(|operand| (undetermined_fraction(operand, function, columns).unwrap_or(1.0), operand))
+        .collect::<Vec<_>>();
+    estimates.sort_by(|(s1, _), (s2, _)| s1.partial_cmp(s2).unwrap_or(Ordering::Equal));
+    estimates.into_iter().map(|(_, operand)| operand).collect()
+}
+
+/// Estimates the fraction of rows for which the result of `function`, i.e. `AND` or `OR`, still depends on the other
+/// operands after evaluating `operand`.
+fn undetermined_fraction(operand: &Expr, function: Func2Type, columns: &HashMap<String, Arc<DataSource>>) -> Option<f64> {
+    let selectivity = estimate_selectivity(operand, columns)?;
+    Some(if function == Func2Type::Or { 1.0 - selectivity } else { selectivity })
+}
+
+/// Compiles the condition of a WHERE clause. If the first operand of `AND` or `OR` is estimated to determine the
+/// result for most rows, the remaining operands are only evaluated for the rows that can still change the result.
+pub fn compile_condition(expr: &Expr,
+                         columns: &HashMap<String, Arc<DataSource>>,
+                         column_len: usize,
+                         planner: &mut QueryPlanner) -> Result<(TypedBufferRef, Type), QueryError> {
+    let (function, operands) = match expr {
+        Expr::Func2(Func2Type::And, _, _) => (Func2Type::And, order_conjuncts(expr, columns)),
+        Expr::Func2(Func2Type::Or, _, _) => (Func2Type::Or, order_disjuncts(expr, columns)),
+        _ => return QueryPlan::compile_expr(expr, &Filter::None, columns, column_len, planner),
+    };
+    let short_circuits = undetermined_fraction(operands[0], function, columns)
+        .map_or(false, |fraction| fraction <= SHORT_CIRCUIT_MAX_UNDETERMINED);
+    if !short_circuits {
+        return QueryPlan::compile_expr(expr, &Filter::None, columns, column_len, planner);
+    }
+    let (mut combined, t) = QueryPlan::compile_expr(operands[0], &Filter::None, columns, column_len, planner)?;
+    if combined.tag != EncodingType::U8 || t.decoded != BasicType::Boolean || t.is_scalar {
+        // Reuses the plan of the first operand
+        return QueryPlan::compile_expr(expr, &Filter::None, columns, column_len, planner);
+    }
+    for &operand in &operands[1..] {
+        let select = combined.u8()?;
+        let disjunction = function == Func2Type::Or;
+        let filter = if disjunction { Filter::Not(Box::new(Filter::U8(select))) } else { Filter::U8(select) };
+        let (condition, t) = QueryPlan::compile_expr(operand, &filter, columns, column_len, planner)?;
+        if t.decoded != BasicType::Boolean {
+            bail!(QueryError::TypeError, "Found {:?} in {}, expected bool", t.decoded, if disjunction { "OR" } else { "AND" })
+        }
+        combined = if t.is_scalar {
+            if disjunction { planner.or(combined, condition) } else { planner.and(combined, condition) }
+        } else {
+            planner.short_circuit(select, condition, disjunction).into()
+        };
+    }
+    Ok((combined, Type::bit_vec()))
+}
+
+/// Restricts `plan` to the rows selected by `filter`.
+pub fn apply_filter(plan: TypedBufferRef, filter: &Filter, planner: &mut QueryPlanner) -> Result<TypedBufferRef, QueryError> {
+    Ok(match *filter {
+        Filter::U8(filter) => planner.filter(plan, filter),
+        Filter::NullableU8(filter) => planner.nullable_filter(plan, filter),
+        Filter::Indices(indices) => planner.select(plan, indices),
+        Filter::None => plan,
+        Filter::Not(box Filter::U8(filter)) => planner.filter_not(plan, filter),
+        Filter::Not(box Filter::Not(ref inner)) => apply_filter(plan, inner, planner)?,
+        Filter::Not(_) => bail!(QueryError::NotImplemented, "Complement of filter that is not a boolean mask"),
+    })
+}
+
+/// Estimates the fraction of rows satisfying comparisons between a column and an integer constant.
+fn estimate_selectivity(expr: &Expr, columns: &HashMap<String, Arc<DataSource>>) -> Option<f64> {
+    match expr {
+        Expr::Func2(op, box Expr::ColName(name), box Expr::Const(RawVal::Int(value))) =>
+            columns.get(name)?.selectivity(*op, *value),
+        Expr::Func2(op, box Expr::Const(RawVal::Int(value)), box Expr::ColName(name)) =>
+            columns.get(name)?.selectivity(op.flip_comparison()?, *value),
+        _ => None,
+    }
+}
+
+/// For `col <op> constant` with a string constant that is not part of the dictionary of `col`,
+/// returns whether the constant has to be mapped to the next smaller rather than the next larger dictionary index.
+/// Returns `None` for equality comparisons, which require an exact match.
+fn string_bound_rounding(op: Func2Type) -> Option<bool> {
+    match op {
+        Func2Type::LT | Func2Type::GTE => Some(false),
+        Func2Type::LTE | Func2Type::GT => Some(true),
+        _ => None,
+    }
+}
+
+fn is_sorted(columns: &HashMap<String, Arc<DataSource>>, name: &str) -> bool {
+    columns.get(name).map_or(false, |c| c.is_sorted())
+}
+
+/// Returns the inclusive range of values `x` that satisfy `x <op> value`, or `None` if `op` is not a comparison that
+/// selects a contiguous range. Empty ranges have a lower bound that is larger than the upper bound.
+fn comparison_bounds(op: Func2Type, value: i64) -> Option<(i64, i64)> {
+    match op {
+        Func2Type::Equals => Some((value, value)),
+        Func2Type::LT => Some(value.checked_sub(1).map_or((1, 0), |upper| (i64::MIN, upper))),
+        Func2Type::LTE => Some((i64::MIN, value)),
+        Func2Type::GT => Some(value.checked_add(1).map_or((1, 0), |lower| (lower, i64::MAX))),
+        Func2Type::GTE => Some((value, i64::MAX)),
+        _ => None,
+    }
+}
+
+/// Returns whether `lhs` and `rhs` are integer columns encoded with the same offset and encoding type, in which case
+/// comparisons between them can be evaluated on the encoded values.
+fn same_integer_offset(lhs: &Type, rhs: &Type) -> bool {
+    match (&lhs.codec, &rhs.codec) {
+        (Some(lhs), Some(rhs)) => lhs.additive_offset().is_some() &&
+            lhs.additive_offset() == rhs.additive_offset() &&
+            lhs.encoding_type() == rhs.encoding_type(),
+        _ => false,
+    }
+}
+
 pub fn order_preserving((plan, t): (TypedBufferRef, Type),
                         planner: &mut QueryPlanner) -> (TypedBufferRef, Type) {
     if t.is_order_preserving() {
@@ -732,7 +1299,7 @@ fn function2_registry() -> HashMap<Func2Type, Vec<Function2>> {
 impl QueryPlan {
     pub fn compile_expr(
         expr: &Expr,
-        filter: Filter,
+        filter: &Filter,
         columns: &HashMap<String, Arc<DataSource>>,
         column_len: usize,
         planner: &mut QueryPlanner) -> Result<(TypedBufferRef, Type), QueryError> {
@@ -748,31 +1315,61 @@ impl QueryPlan {
                         t = Type::encoded(codec);
                         plan = fixed_width;
                     }
-                    plan = match filter {
-                        Filter::U8(filter) => planner.filter(plan, filter),
-                        Filter::NullableU8(filter) => planner.nullable_filter(plan, filter),
-                        Filter::Indices(indices) => planner.select(plan, indices),
-                        Filter::None => plan,
-                    };
+                    plan = apply_filter(plan, filter, planner)?;
                     (plan, t)
                 }
-                None => (planner.null_vec(column_len, EncodingType::Null), Type::new(BasicType::Null, None)),
+                None if name == ROWNUM_COLUMN => {
+                    let offset = planner.row_offset;
+                    let plan = planner.row_numbers(offset, column_len).into();
+                    let plan = apply_filter(plan, filter, planner)?;
+                    (plan, Type::unencoded(BasicType::Integer))
+                }
+                None => {
+                    let plan = planner.null_vec(column_len, EncodingType::Null);
+                    let plan = apply_filter(plan, filter, planner)?;
+                    (plan, Type::new(BasicType::Null, None))
+                }
+            }
+            // `rownum % stride = phase` selects every `stride`th row, which doesn't require materializing row numbers
+            Func2(Equals, box Func2(Modulo, box ColName(ref name), box Const(RawVal::Int(stride))), box Const(RawVal::Int(phase)))
+            if name == ROWNUM_COLUMN && !columns.contains_key(name) && stride > 0 && phase >= 0 => {
+                let offset = planner.row_offset;
+                let plan = planner.strided_mask(offset, column_len, stride as usize, phase as usize).into();
+                let plan = apply_filter(plan, filter, planner)?;
+                (plan, Type::bit_vec())
+            }
+            // Rows of a sorted column that satisfy a comparison with a constant are contiguous and found by binary search
+            Func2(op, box ColName(ref name), box Const(RawVal::Int(value)))
+            if is_sorted(columns, name) && comparison_bounds(op, value).is_some() => {
+                let (lower, upper) = comparison_bounds(op, value).unwrap();
+                QueryPlan::compile_sorted_range(name, lower, upper, filter, columns, planner)?
+            }
+            Func2(op, box Const(RawVal::Int(value)), box ColName(ref name))
+            if is_sorted(columns, name) && op.flip_comparison().and_then(|op| comparison_bounds(op, value)).is_some() => {
+                let (lower, upper) = op.flip_comparison().and_then(|op| comparison_bounds(op, value)).unwrap();
+                QueryPlan::compile_sorted_range(name, lower, upper, filter, columns, planner)?
             }
             Func2(Or, ref lhs, ref rhs) => {
                 let (plan_lhs, type_lhs) = QueryPlan::compile_expr(lhs, filter, columns, column_len, planner)?;
                 let (plan_rhs, type_rhs) = QueryPlan::compile_expr(rhs, filter, columns, column_len, planner)?;
                 if type_lhs.decoded != BasicType::Boolean || type_rhs.decoded != BasicType::Boolean {
-                    bail!(QueryError::TypeError, "Found {} OR {}, expected bool OR bool")
+                    bail!(QueryError::TypeError, "Found {:?} OR {:?}, expected bool OR bool", type_lhs.decoded, type_rhs.decoded)
                 }
                 (planner.or(plan_lhs, plan_rhs), Type::bit_vec())
             }
-            Func2(And, ref lhs, ref rhs) => {
-                let (plan_lhs, type_lhs) = QueryPlan::compile_expr(lhs, filter, columns, column_len, planner)?;
-                let (plan_rhs, type_rhs) = QueryPlan::compile_expr(rhs, filter, columns, column_len, planner)?;
-                if type_lhs.decoded != BasicType::Boolean || type_rhs.decoded != BasicType::Boolean {
-                    bail!(QueryError::TypeError, "Found {} AND {}, expected bool AND bool")
+            Func2(And, _, _) => {
+                let mut conjunction = None;
+                for conjunct in order_conjuncts(expr, columns) {
+                    let (plan, t) = QueryPlan::compile_expr(conjunct, filter, columns, column_len, planner)?;
+                    if t.decoded != BasicType::Boolean {
+                        bail!(QueryError::TypeError, "Found {:?} in AND, expected bool", t.decoded)
+                    }
+                    conjunction = Some(match conjunction {
+                        Some(conjunction) => planner.and(conjunction, plan),
+                        None => plan,
+                    });
                 }
-                (planner.and(plan_lhs, plan_rhs), Type::bit_vec())
+                (conjunction.unwrap(), Type::bit_vec())
             }
             Func2(Like, ref expr, ref pattern) => {
                 match pattern {
@@ -798,7 +1395,7 @@ impl QueryPlan {
                         if let Some(codec) = t.codec.clone() {
                             plan = codec.decode(plan, planner);
                         }
-                        (planner.regex(plan.str()?, &pattern).into(), t)
+                        (planner.regex(plan.str()?, &pattern).into(), Type::bit_vec())
                     }
                     _ => bail!(QueryError::TypeError,
                                "Expected string constant as second argument to `LIKE`, actual: {:?}", pattern),
@@ -809,14 +1406,22 @@ impl QueryPlan {
                     box Const(RawVal::Str(regex)) => {
                         Regex::new(&regex).map_err(|e| QueryError::TypeError(
                             format!("`{}` is not a valid regex: {}", regex, e)))?;
-                        let (mut plan, t) = QueryPlan::compile_expr(expr, filter, columns, column_len, planner)?;
+                        let (plan, t) = QueryPlan::compile_expr(expr, filter, columns, column_len, planner)?;
                         if t.decoded != BasicType::String {
                             bail!(QueryError::TypeError, "Expected expression of type `String` as first argument to regex. Actual: {:?}", t)
                         }
-                        if let Some(codec) = t.codec.clone() {
-                            plan = codec.decode(plan, planner);
-                        }
-                        (planner.regex(plan.str()?, regex).into(), t)
+                        let matches = match t.codec {
+                            // Dictionary encoded columns only have to evaluate the regex once for each distinct value
+                            Some(ref codec) => match codec.dict_regex(plan, regex, planner) {
+                                Some(matches) => matches,
+                                None => {
+                                    let decoded = codec.decode(plan, planner);
+                                    planner.regex(decoded.str()?, regex)
+                                }
+                            },
+                            None => planner.regex(plan.str()?, regex),
+                        };
+                        (matches.into(), Type::bit_vec())
                     }
                     _ => bail!(QueryError::TypeError, "Expected string constant as second argument to `regex`, actual: {:?}", regex),
                 }
@@ -825,6 +1430,16 @@ impl QueryPlan {
                 let (mut plan_lhs, mut type_lhs) = QueryPlan::compile_expr(lhs, filter, columns, column_len, planner)?;
                 let (mut plan_rhs, mut type_rhs) = QueryPlan::compile_expr(rhs, filter, columns, column_len, planner)?;
 
+                // Arithmetic and comparisons with a vector of nulls yield only nulls, which is the vector itself
+                if function != And && function != Or {
+                    if type_lhs.decoded == BasicType::Null && !type_lhs.is_scalar {
+                        return Ok((plan_lhs, type_lhs));
+                    }
+                    if type_rhs.decoded == BasicType::Null && !type_rhs.is_scalar {
+                        return Ok((plan_rhs, type_rhs));
+                    }
+                }
+
                 let declarations = match FUNCTION2_REGISTRY.get(&function) {
                     Some(patterns) => patterns,
                     None => bail!(QueryError::NotImplemented, "function {:?}", function),
@@ -842,27 +1457,38 @@ impl QueryPlan {
                 if declaration.encoding_invariance && type_lhs.is_scalar && type_rhs.is_encoded() {
                     plan_lhs = if type_rhs.decoded == BasicType::Integer {
                         if let QueryPlan::ScalarI64 { value, .. } = *planner.resolve(&plan_lhs) {
-                            planner.scalar_i64(type_rhs.codec.unwrap().encode_int(value), true).into()
+                            planner.scalar_i64(type_rhs.codec.unwrap().encode_int(value)?, true).into()
                         } else {
-                            panic!("whoops");
+                            return Err(fatal!("Expected integer constant, found {:?}", planner.resolve(&plan_lhs)));
                         }
                     } else if type_rhs.decoded == BasicType::String {
-                        type_rhs.codec.clone().unwrap().encode_str(plan_lhs.scalar_str()?, planner).into()
+                        let codec = type_rhs.codec.clone().unwrap();
+                        // Constant is on the left hand side, so `c < col` requires the same rounding as `col > c`
+                        match string_bound_rounding(function.flip_comparison().unwrap_or(function)) {
+                            Some(round_down) => codec.encode_str_bound(plan_lhs.scalar_str()?, round_down, planner)?.into(),
+                            None => codec.encode_str(plan_lhs.scalar_str()?, planner)?.into(),
+                        }
                     } else {
-                        panic!("whoops");
+                        bail!(QueryError::TypeError, "Can't encode constant for comparison with {:?}", type_rhs);
                     };
                 } else if declaration.encoding_invariance && type_rhs.is_scalar && type_lhs.is_encoded() {
                     plan_rhs = if type_lhs.decoded == BasicType::Integer {
                         if let QueryPlan::ScalarI64 { value, .. } = *planner.resolve(&plan_rhs) {
-                            planner.scalar_i64(type_lhs.codec.unwrap().encode_int(value), true).into()
+                            planner.scalar_i64(type_lhs.codec.unwrap().encode_int(value)?, true).into()
                         } else {
-                            panic!("whoops");
+                            return Err(fatal!("Expected integer constant, found {:?}", planner.resolve(&plan_rhs)));
                         }
                     } else if type_lhs.decoded == BasicType::String {
-                        type_lhs.codec.clone().unwrap().encode_str(plan_rhs.scalar_str()?, planner).into()
+                        let codec = type_lhs.codec.clone().unwrap();
+                        match string_bound_rounding(function) {
+                            Some(round_down) => codec.encode_str_bound(plan_rhs.scalar_str()?, round_down, planner)?.into(),
+                            None => codec.encode_str(plan_rhs.scalar_str()?, planner)?.into(),
+                        }
                     } else {
-                        panic!("whoops");
+                        bail!(QueryError::TypeError, "Can't encode constant for comparison with {:?}", type_lhs);
                     };
+                } else if declaration.encoding_invariance && same_integer_offset(&type_lhs, &type_rhs) {
+                    // Both sides are shifted by the same offset, which doesn't change the result of the comparison
                 } else {
                     if let Some(codec) = type_lhs.codec {
                         plan_lhs = codec.decode(plan_lhs, planner);
@@ -875,8 +1501,74 @@ impl QueryPlan {
                 let plan = (declaration.factory)(planner, plan_lhs, plan_rhs);
                 (plan, declaration.type_out.clone())
             }
+            // The null map of the column determines the result, the values don't have to be read
+            Func1(ftype, box ColName(ref name))
+            if (ftype == Func1Type::IsNull || ftype == Func1Type::IsNotNull) &&
+                columns.get::<str>(name.as_ref()).and_then(|c| c.codec().null_map_section()).is_some() => {
+                let section = columns[name.as_str()].codec().null_map_section().unwrap();
+                let present = planner.column_section(name, section, None, EncodingType::U8);
+                let mut plan = planner.unpack_bits(present.u8()?, column_len);
+                if ftype == Func1Type::IsNull {
+                    plan = planner.not(plan);
+                }
+                let plan = apply_filter(plan.into(), filter, planner)?;
+                (plan, Type::unencoded(BasicType::Boolean).mutable())
+            }
+            Func1(ftype, ref inner) if ftype == Func1Type::Lower || ftype == Func1Type::Upper => {
+                let (plan, t) = QueryPlan::compile_expr(inner, filter, columns, column_len, planner)?;
+                if t.decoded == BasicType::Null {
+                    return Ok((plan, t));
+                }
+                let upper = ftype == Func1Type::Upper;
+                if t.decoded != BasicType::String {
+                    let name = if upper { "upper" } else { "lower" };
+                    bail!(QueryError::TypeError, "Found {}({:?}), expected {}(string)", name, &t, name)
+                }
+                match t.codec {
+                    // Dictionary encoded columns only have to convert each distinct value once and stay dictionary encoded
+                    Some(ref codec) => match codec.dict_fold_case(plan, upper, planner) {
+                        Some((indices, codec)) => (indices, Type::encoded(codec).mutable()),
+                        None => {
+                            let decoded = codec.decode(plan, planner);
+                            (planner.case_fold(decoded.str()?, upper).into(), Type::unencoded(BasicType::String).mutable())
+                        }
+                    },
+                    None => (planner.case_fold(plan.str()?, upper).into(), Type::unencoded(BasicType::String).mutable()),
+                }
+            }
+            Func1(ftype, ref inner) if ftype == Func1Type::CastInt || ftype == Func1Type::CastString => {
+                let (plan, t) = QueryPlan::compile_expr(inner, filter, columns, column_len, planner)?;
+                let target = if ftype == Func1Type::CastInt { BasicType::Integer } else { BasicType::String };
+                if t.decoded == BasicType::Null || t.decoded.non_nullable() == target {
+                    return Ok((plan, t));
+                }
+                if t.is_nullable() || t.is_scalar {
+                    bail!(QueryError::NotImplemented, "CAST of {:?} to {:?}", t, target)
+                }
+                let decoded = match t.codec.clone() {
+                    Some(codec) => codec.decode(plan, planner),
+                    None => plan,
+                };
+                match (t.decoded, target) {
+                    // Integers that don't fit into an `i64` are null
+                    (BasicType::String, BasicType::Integer) =>
+                        (planner.parse_int(decoded.str()?).into(), Type::unencoded(BasicType::NullableInteger).mutable()),
+                    (BasicType::Boolean, BasicType::Integer) =>
+                        (planner.cast(decoded, EncodingType::I64), Type::unencoded(BasicType::Integer).mutable()),
+                    (BasicType::Integer, BasicType::String) => {
+                        let decoded = if decoded.tag == EncodingType::I64 { decoded } else { planner.cast(decoded, EncodingType::I64) };
+                        let decoded = decoded.i64()?;
+                        (planner.int_to_string(decoded).into(), Type::unencoded(BasicType::String).mutable())
+                    }
+                    _ => bail!(QueryError::TypeError, "Can't CAST {:?} to {:?}", t, target),
+                }
+            }
             Func1(ftype, ref inner) => {
                 let (plan, t) = QueryPlan::compile_expr(inner, filter, columns, column_len, planner)?;
+                // Functions of nulls are null, except for null checks
+                if t.decoded == BasicType::Null && ftype != Func1Type::IsNull && ftype != Func1Type::IsNotNull {
+                    return Ok((plan, t));
+                }
                 let plan = match ftype {
                     Func1Type::ToYear => {
                         let decoded = match t.codec.clone() {
@@ -898,6 +1590,26 @@ impl QueryPlan {
                         }
                         planner.length(decoded.str()?).into()
                     }
+                    Func1Type::CharLength => {
+                        let decoded = match t.codec.clone() {
+                            Some(codec) => codec.decode(plan, planner),
+                            None => plan,
+                        };
+                        if t.decoded != BasicType::String {
+                            bail!(QueryError::TypeError, "Found char_length({:?}), expected char_length(string)", &t)
+                        }
+                        planner.char_length(decoded.str()?).into()
+                    }
+                    Func1Type::Substring { start, length } => {
+                        let decoded = match t.codec.clone() {
+                            Some(codec) => codec.decode(plan, planner),
+                            None => plan,
+                        };
+                        if t.decoded != BasicType::String {
+                            bail!(QueryError::TypeError, "Found substring({:?}), expected substring(string)", &t)
+                        }
+                        planner.substring(decoded.str()?, start, length).into()
+                    }
                     Func1Type::Not => {
                         let decoded = match t.codec.clone() {
                             Some(codec) => codec.decode(plan, planner),
@@ -911,24 +1623,66 @@ impl QueryPlan {
                     Func1Type::IsNull => if plan.is_nullable() {
                         planner.is_null(plan.nullable_any()?).into()
                     } else {
-                        planner.constant_expand((false as u8) as i64, column_len, EncodingType::U8)
+                        let is_null = t.decoded == BasicType::Null;
+                        let constant = planner.constant_expand((is_null as u8) as i64, column_len, EncodingType::U8);
+                        apply_filter(constant, filter, planner)?
                     }
                     Func1Type::IsNotNull => if plan.is_nullable() {
                         planner.is_not_null(plan.nullable_any()?).into()
                     } else {
-                        planner.constant_expand((true as u8) as i64, column_len, EncodingType::U8)
+                        let is_not_null = t.decoded != BasicType::Null;
+                        let constant = planner.constant_expand((is_not_null as u8) as i64, column_len, EncodingType::U8);
+                        apply_filter(constant, filter, planner)?
                     }
                     Func1Type::Negate => {
                         bail!(QueryError::TypeError, "Found negate({:?}), expected negate(integer)", &t)
                     }
+                    Func1Type::Lower | Func1Type::Upper | Func1Type::CastInt | Func1Type::CastString =>
+                        return Err(fatal!("{:?} is compiled separately", ftype)),
+                };
+                let type_out = match ftype {
+                    Func1Type::Length | Func1Type::CharLength => Type::unencoded(BasicType::Integer).mutable(),
+                    Func1Type::Substring { .. } => Type::unencoded(BasicType::String).mutable(),
+                    Func1Type::Not | Func1Type::IsNull | Func1Type::IsNotNull => Type::bit_vec(),
+                    _ => t.decoded(),
                 };
-                (plan, t.decoded())
+                (plan, type_out)
             }
             Const(RawVal::Int(i)) => (planner.scalar_i64(i, false).into(), Type::scalar(BasicType::Integer)),
             Const(RawVal::Str(ref s)) => (planner.scalar_str(s).into(), Type::scalar(BasicType::String)),
             ref x => bail!(QueryError::NotImplemented, "{:?}.compile_vec()", x),
         })
     }
+
+    /// Compiles the mask of rows of the sorted column `name` with decoded values in `[lower, upper]`.
+    fn compile_sorted_range(
+        name: &str,
+        lower: i64,
+        upper: i64,
+        filter: &Filter,
+        columns: &HashMap<String, Arc<DataSource>>,
+        planner: &mut QueryPlanner) -> Result<(TypedBufferRef, Type), QueryError> {
+        let column = &columns[name];
+        let (min, max) = column.value_range().unwrap_or((i64::MIN, i64::MAX));
+        let (lower, upper) = (cmp::max(lower, min), cmp::min(upper, max));
+        let plan = planner.column_section(name, 0, column.range(), column.encoding_type());
+        let (codec, plan) = column.codec().ensure_fixed_width(plan, planner);
+        let (plan, lower, upper) = if lower > upper {
+            // No value lies in the range, any empty range yields the same mask
+            (plan, 1, 0)
+        } else if codec.is_identity() {
+            (plan, lower, upper)
+        } else {
+            match (codec.encode_int(lower), codec.encode_int(upper)) {
+                (Ok(lower), Ok(upper)) => (plan, lower, upper),
+                // Encoded values are not ordered in the same way as decoded values
+                _ => (codec.decode(plan, planner), lower, upper),
+            }
+        };
+        let mask = planner.sorted_range(plan, lower, upper).into();
+        let mask = apply_filter(mask, filter, planner)?;
+        Ok((mask, Type::bit_vec()))
+    }
 }
 
 fn encoding_range(plan: &TypedBufferRef, qp: &QueryPlanner) -> Option<(i64, i64)> {
@@ -942,7 +1696,7 @@ fn encoding_range(plan: &TypedBufferRef, qp: &QueryPlanner) -> Option<(i64, i64)
             (i64::from(NaiveDateTime::from_timestamp(min, 0).year()),
              i64::from(NaiveDateTime::from_timestamp(max, 0).year()))
         ),
-        Filter { ref plan, .. } => encoding_range(plan, qp),
+        Filter { ref plan, .. } | FilterNot { ref plan, .. } => encoding_range(plan, qp),
         Divide { ref lhs, ref rhs, .. } => if let ScalarI64 { value: c, .. } = qp.resolve(rhs) {
             encoding_range(lhs, qp).map(|(min, max)|
                 if *c > 0 { (min / *c, max / *c) } else { (max / *c, min / *c) })
@@ -955,6 +1709,13 @@ fn encoding_range(plan: &TypedBufferRef, qp: &QueryPlanner) -> Option<(i64, i64)
         } else {
             None
         },
+        Modulo { ref lhs, ref rhs, .. } |
+        CheckedModulo { ref lhs, ref rhs, .. } |
+        NullableCheckedModulo { ref lhs, ref rhs, .. } => if let ScalarI64 { value: c, .. } = qp.resolve(rhs) {
+            encoding_range(lhs, qp).and_then(|range| modulo_range(range, *c))
+        } else {
+            None
+        },
         Add { ref lhs, ref rhs, .. } => if let ScalarI64 { value: c, .. } = qp.resolve(rhs) {
             encoding_range(lhs, qp).map(|(min, max)| (min + *c, max + *c))
         } else {
@@ -962,9 +1723,16 @@ fn encoding_range(plan: &TypedBufferRef, qp: &QueryPlanner) -> Option<(i64, i64)
         },
         Cast { ref input, .. } => encoding_range(input, qp),
         LZ4Decode { bytes, .. } => encoding_range(&bytes.into(), qp),
+        ZstdDecode { bytes, .. } => encoding_range(&bytes.into(), qp),
         DeltaDecode { ref plan, .. } => encoding_range(plan, qp),
+        RunLengthDecode { ref plan, .. } => encoding_range(plan, qp),
+        // Remapped indices are smaller than the number of dictionary entries, which can only decrease
+        DictRemap { ref indices, .. } => encoding_range(indices, qp).map(|(_, max)| (0, max)),
+        UnpackInts { bits, .. } => Some((0, (1 << bits) - 1)),
         AssembleNullable { ref data, .. } => encoding_range(data, qp),
-        UnpackStrings { .. } | UnhexpackStrings { .. } | Length { .. } => None,
+        UnpackBits { .. } | StridedMask { .. } | SortedRange { .. } => Some((0, 1)),
+        RowNumbers { offset, len, .. } => Some((offset as i64, (offset + len) as i64 - 1)),
+        UnpackStrings { .. } | UnhexpackStrings { .. } | Length { .. } | CaseFold { .. } | ParseInt { .. } | IntToString { .. } | CharLength { .. } | Substring { .. } | FrameOfReferenceDecode { .. } | SampleKeys { .. } => None,
         ref plan => {
             error!("encoding_range not implement for {:?}", plan);
             None
@@ -972,21 +1740,30 @@ fn encoding_range(plan: &TypedBufferRef, qp: &QueryPlanner) -> Option<(i64, i64)
     }
 }
 
+/// Range of `x % modulus` for `x` in `(min, max)`. The result takes the sign of `x`.
+fn modulo_range((min, max): (i64, i64), modulus: i64) -> Option<(i64, i64)> {
+    if modulus == 0 || modulus == i64::MIN {
+        return None;
+    }
+    let bound = modulus.abs() - 1;
+    if min >= -bound && max <= bound {
+        Some((min, max))
+    } else {
+        Some((if min < 0 { -bound } else { 0 },
+              if max > 0 { bound } else { 0 }))
+    }
+}
+
 pub fn compile_grouping_key(
     exprs: &[Expr],
-    filter: Filter,
+    filter: &Filter,
     columns: &HashMap<String, Arc<DataSource>>,
     partition_len: usize,
     planner: &mut QueryPlanner)
     -> Result<((TypedBufferRef, bool), i64, Vec<(TypedBufferRef, Type)>, TypedBufferRef), QueryError> {
     if exprs.is_empty() {
         let mut plan = planner.constant_expand(0, partition_len, EncodingType::U8);
-        plan = match filter {
-            Filter::U8(filter) => planner.filter(plan, filter),
-            Filter::NullableU8(filter) => planner.nullable_filter(plan, filter),
-            Filter::Indices(indices) => planner.select(plan, indices),
-            Filter::None => plan,
-        };
+        plan = apply_filter(plan, filter, planner)?;
         Ok((
             (plan, true),
             1,
@@ -1108,7 +1885,7 @@ pub fn compile_grouping_key(
 
 fn try_bitpacking(
     exprs: &[Expr],
-    filter: Filter,
+    filter: &Filter,
     columns: &HashMap<String, Arc<DataSource>>,
     partition_len: usize,
     planner: &mut QueryPlanner)
@@ -1136,12 +1913,13 @@ fn try_bitpacking(
             let adjusted_max = if query_plan.is_nullable() { max - min + 1 } else if subtract_offset { max - min } else { max };
             order_preserving = order_preserving && plan_type.is_order_preserving();
             let adjusted_query_plan = if query_plan.is_nullable() {
-                let fused = planner.fuse_int_nulls(-min + 1, query_plan);
-                if fused.tag != EncodingType::I64 {
-                    planner.cast(fused, EncodingType::I64).i64()?
+                // The offset is negative if all values are greater than one, so it is only applied after widening
+                let widened = if query_plan.tag != EncodingType::NullableI64 {
+                    planner.cast(query_plan, EncodingType::NullableI64)
                 } else {
-                    fused.i64()?
-                }
+                    query_plan
+                };
+                planner.fuse_int_nulls(-min + 1, widened).i64()?
             } else if subtract_offset {
                 let offset = planner.scalar_i64(-min, true);
                 planner.add(query_plan, offset.into()).i64()?
@@ -1202,11 +1980,14 @@ pub(super) fn prepare<'a>(plan: QueryPlan, constant_vecs: &mut Vec<BoxedData<'a>
         QueryPlan::PropagateNullability { nullable, data, nullable_data } => VecOperator::propagate_nullability(nullable.nullable_any()?, data, nullable_data)?,
         QueryPlan::CombineNullMaps { lhs, rhs, present } => VecOperator::combine_null_maps(lhs, rhs, present)?,
         QueryPlan::GetNullMap { nullable, present } => VecOperator::get_null_map(nullable.nullable_any()?, present),
+        QueryPlan::BoolsToNullMap { bools, present } => VecOperator::bools_to_null_map(bools, present)?,
         QueryPlan::FuseNulls { nullable, fused } => VecOperator::fuse_nulls(nullable, fused)?,
+        QueryPlan::FuseNullsMax { nullable, fused } => VecOperator::fuse_nulls_max(nullable, fused)?,
         QueryPlan::FuseIntNulls { offset, nullable, fused } => VecOperator::fuse_int_nulls(offset, nullable, fused)?,
         QueryPlan::UnfuseNulls { fused, data, present, unfused } => VecOperator::unfuse_nulls(fused, data, present, unfused)?,
         QueryPlan::UnfuseIntNulls { offset, fused, data, present, unfused } => VecOperator::unfuse_int_nulls(offset, fused, data, present, unfused)?,
         QueryPlan::Filter { plan, select, filtered } => VecOperator::filter(plan, select, filtered)?,
+        QueryPlan::FilterNot { plan, select, filtered } => VecOperator::filter_not(plan, select, filtered)?,
         QueryPlan::NullableFilter { plan, select, filtered } => VecOperator::nullable_filter(plan, select, filtered)?,
         QueryPlan::IsNull { plan, is_null } => VecOperator::is_null(plan, is_null),
         QueryPlan::IsNotNull { plan, is_not_null } => VecOperator::is_not_null(plan, is_not_null),
@@ -1214,18 +1995,41 @@ pub(super) fn prepare<'a>(plan: QueryPlan, constant_vecs: &mut Vec<BoxedData<'a>
         QueryPlan::ScalarStr { value, pinned_string, scalar_str } => VecOperator::scalar_str(value.to_string(), pinned_string, scalar_str),
         QueryPlan::NullVec { len, nulls } => VecOperator::null_vec(len, nulls.any()),
         QueryPlan::ConstantExpand { value, len, expanded } => VecOperator::constant_expand(value, len, expanded)?,
+        QueryPlan::SampleMask { offset, len, rows, mask } => VecOperator::sample_mask(offset, len, rows, mask),
+        QueryPlan::SampleKeys { offset, len, seed, keys } => VecOperator::sample_keys(offset, len, seed, keys),
+        QueryPlan::RowNumbers { offset, len, row_numbers } => VecOperator::row_numbers(offset, len, row_numbers),
+        QueryPlan::StridedMask { offset, len, stride, phase, mask } => VecOperator::strided_mask(offset, len, stride, phase, mask),
+        QueryPlan::SortedRange { plan, lower, upper, mask } => VecOperator::sorted_range(plan, lower, upper, mask)?,
         QueryPlan::DictLookup { indices, offset_len, backing_store, decoded } => VecOperator::dict_lookup(indices, offset_len, backing_store, decoded.str()?)?,
         QueryPlan::InverseDictLookup { offset_len, backing_store, constant, decoded } => VecOperator::inverse_dict_lookup(offset_len, backing_store, constant, decoded),
+        QueryPlan::DictRank { offset_len, backing_store, constant, round_down, rank } => VecOperator::dict_rank(offset_len, backing_store, constant, round_down, rank),
+        QueryPlan::DictRegex { offset_len, backing_store, regex, dict_matches } => VecOperator::dict_regex(offset_len, backing_store, &regex, dict_matches)?,
+        QueryPlan::DictCaseFold { offset_len, backing_store, upper, folded_offset_len, folded_backing_store, remap } =>
+            VecOperator::dict_case_fold(offset_len, backing_store, upper, folded_offset_len, folded_backing_store, remap),
+        QueryPlan::DictRemap { indices, remap, remapped } => VecOperator::dict_remap(indices, remap, remapped)?,
+        QueryPlan::DictMatchLookup { indices, dict_matches, matches } => VecOperator::dict_match_lookup(indices, dict_matches, matches)?,
         QueryPlan::Cast { input, casted } => VecOperator::type_conversion(input, casted)?,
         QueryPlan::DeltaDecode { plan, delta_decoded } => VecOperator::delta_decode(plan, delta_decoded)?,
+        QueryPlan::FrameOfReferenceDecode { plan, frame_minimums, frame_size, decoded } =>
+            VecOperator::frame_of_reference_decode(plan, frame_minimums, frame_size, decoded)?,
         QueryPlan::LZ4Decode { bytes, decoded_len, decoded } => VecOperator::lz4_decode(bytes, decoded_len, decoded)?,
+        QueryPlan::ZstdDecode { bytes, decoded_len, decoded } => VecOperator::zstd_decode(bytes, decoded_len, decoded)?,
+        QueryPlan::UnpackBits { bits, len, unpacked } => VecOperator::unpack_bits(bits, len, unpacked),
+        QueryPlan::UnpackInts { packed, bits, len, unpacked } => VecOperator::unpack_ints(packed, bits, len, unpacked),
+        QueryPlan::RunLengthDecode { plan, run_lengths, len, decoded } =>
+            VecOperator::run_length_decode(plan, run_lengths, len, decoded)?,
         QueryPlan::UnpackStrings { bytes, unpacked_strings } => VecOperator::unpack_strings(bytes, unpacked_strings),
         QueryPlan::UnhexpackStrings { bytes, uppercase, total_bytes, string_store, unpacked_strings } => VecOperator::unhexpack_strings(bytes, uppercase, total_bytes, string_store, unpacked_strings),
         QueryPlan::HashMapGrouping { raw_grouping_key, max_cardinality, unique, grouping_key, cardinality } => VecOperator::hash_map_grouping(raw_grouping_key, max_cardinality, unique, grouping_key, cardinality)?,
+        QueryPlan::SortGrouping { raw_grouping_key, unique, grouping_key, cardinality } => VecOperator::sort_grouping(raw_grouping_key, unique, grouping_key, cardinality)?,
         QueryPlan::HashMapGroupingValRows { raw_grouping_key, max_cardinality, columns, unique, grouping_key, cardinality } => VecOperator::hash_map_grouping_val_rows(raw_grouping_key, columns, max_cardinality, unique, grouping_key, cardinality)?,
         QueryPlan::Aggregate { plan, grouping_key, max_index, aggregator, aggregate } => VecOperator::aggregate(plan, grouping_key, max_index, aggregator, aggregate)?,
-        QueryPlan::CheckedAggregate { plan, grouping_key, max_index, aggregator, aggregate } => VecOperator::checked_aggregate(plan, grouping_key, max_index, aggregator, aggregate)?,
+        QueryPlan::AggregateNulls { max_index, aggregator, aggregate } => VecOperator::aggregate_nulls(max_index, aggregator, aggregate)?,
+        QueryPlan::CheckedAggregate { plan, grouping_key, max_index, aggregator, offset, aggregate } => VecOperator::checked_aggregate(plan, grouping_key, max_index, aggregator, offset, aggregate)?,
+        QueryPlan::AddWideSums { lhs, rhs, subtract, sum } => VecOperator::add_wide_sums(lhs.i128()?, rhs.i128()?, subtract, sum.i128()?),
+        QueryPlan::ApproxCountDistinct { plan, grouping_key, max_index, precision, sketches } => VecOperator::approx_count_distinct(plan, grouping_key, max_index, precision, sketches.hyperloglogs()?)?,
         QueryPlan::Exists { indices, max_index, exists } => VecOperator::exists(indices, max_index, exists)?,
+        QueryPlan::CountSelected { select, rejected, count } => VecOperator::count_selected(select, rejected, count),
         QueryPlan::Compact { plan, select, compacted } => VecOperator::compact(plan, select, compacted)?,
         QueryPlan::NonzeroIndices { plan, nonzero_indices } => VecOperator::nonzero_indices(plan, nonzero_indices)?,
         QueryPlan::NonzeroCompact { plan, compacted } => VecOperator::nonzero_compact(plan, compacted)?,
@@ -1256,25 +2060,98 @@ pub(super) fn prepare<'a>(plan: QueryPlan, constant_vecs: &mut Vec<BoxedData<'a>
         QueryPlan::NullableCheckedModulo { lhs, rhs, present, modulo } => VecOperator::nullable_checked_modulo(lhs, rhs, present, modulo)?,
         QueryPlan::Or { lhs, rhs, or } => VecOperator::or(lhs.u8()?, rhs.u8()?, or.u8()?),
         QueryPlan::And { lhs, rhs, and } => VecOperator::and(lhs.u8()?, rhs.u8()?, and.u8()?),
+        QueryPlan::ShortCircuit { select, condition, disjunction, combined } => VecOperator::short_circuit(select, condition, disjunction, combined)?,
         QueryPlan::Not { input, not } => VecOperator::not(input, not),
         QueryPlan::ToYear { timestamp, year } => VecOperator::to_year(timestamp.i64()?, year.i64()?),
-        QueryPlan::Regex { plan, regex, matches } => VecOperator::regex(plan, &regex, matches),
+        QueryPlan::Regex { plan, regex, matches } => VecOperator::regex(plan, &regex, matches)?,
         QueryPlan::Length { string, length } => VecOperator::length(string, length),
+        QueryPlan::CaseFold { string, upper, string_store, folded } => VecOperator::case_fold(string, upper, string_store, folded),
+        QueryPlan::ParseInt { string, parsed } => VecOperator::parse_int(string, parsed),
+        QueryPlan::IntToString { int, string_store, string } => VecOperator::int_to_string(int, string_store, string),
+        QueryPlan::CharLength { string, length } => VecOperator::char_length(string, length),
+        QueryPlan::Substring { string, start, length, substring } => VecOperator::substring(string, start, length, substring),
         QueryPlan::Indices { plan, indices } => VecOperator::indices(plan, indices),
-        QueryPlan::SortBy { ranking, indices, desc, stable, permutation } => VecOperator::sort_by(ranking, indices, desc, stable, permutation)?,
+        QueryPlan::SortBy { ranking, indices, desc, stable, nulls_first, permutation } => VecOperator::sort_by(ranking, indices, desc, stable, nulls_first, permutation)?,
         QueryPlan::TopN { ranking, n, desc, tmp_keys, top_n } => VecOperator::top_n(ranking, tmp_keys, n, desc, top_n)?,
         QueryPlan::Connect { input, output } => VecOperator::identity(input, output),
-        QueryPlan::Merge { lhs, rhs, limit, desc, merge_ops, merged } => VecOperator::merge(lhs, rhs, limit, desc, merge_ops, merged)?,
-        QueryPlan::MergePartitioned { partitioning, lhs, rhs, limit, desc, take_left, merged } => VecOperator::merge_partitioned(partitioning, lhs, rhs, limit, desc, take_left, merged)?,
+        QueryPlan::Merge { lhs, rhs, limit, desc, nulls_first, merge_ops, merged } => VecOperator::merge(lhs, rhs, limit, desc, nulls_first, merge_ops, merged)?,
+        QueryPlan::MergePartitioned { partitioning, lhs, rhs, limit, desc, nulls_first, take_left, merged } => VecOperator::merge_partitioned(partitioning, lhs, rhs, limit, desc, nulls_first, take_left, merged)?,
         QueryPlan::MergeDeduplicate { lhs, rhs, merge_ops, merged } => VecOperator::merge_deduplicate(lhs, rhs, merge_ops, merged)?,
         QueryPlan::MergeDeduplicatePartitioned { partitioning, lhs, rhs, merge_ops, merged } => VecOperator::merge_deduplicate_partitioned(partitioning, lhs, rhs, merge_ops, merged)?,
-        QueryPlan::Partition { lhs, rhs, limit, desc, partitioning } => VecOperator::partition(lhs, rhs, limit, desc, partitioning)?,
-        QueryPlan::Subpartition { partitioning, lhs, rhs, desc, subpartitioning } => VecOperator::subpartition(partitioning, lhs, rhs, desc, subpartitioning)?,
+        QueryPlan::Partition { lhs, rhs, limit, desc, nulls_first, partitioning } => VecOperator::partition(lhs, rhs, limit, desc, nulls_first, partitioning)?,
+        QueryPlan::Subpartition { partitioning, lhs, rhs, desc, nulls_first, subpartitioning } => VecOperator::subpartition(partitioning, lhs, rhs, desc, nulls_first, subpartitioning)?,
         QueryPlan::MergeDrop { merge_ops, lhs, rhs, merged } => VecOperator::merge_drop(merge_ops, lhs, rhs, merged)?,
         QueryPlan::MergeKeep { take_left, lhs, rhs, merged } => VecOperator::merge_keep(take_left, lhs, rhs, merged)?,
         QueryPlan::MergeAggregate { merge_ops, lhs, rhs, aggregator, merged } => VecOperator::merge_aggregate(merge_ops, lhs, rhs, aggregator, merged),
+        QueryPlan::MergeWideSums { merge_ops, lhs, rhs, merged } => VecOperator::merge_wide_sums(merge_ops, lhs.i128()?, rhs.i128()?, merged.i128()?),
+        QueryPlan::MergeHyperLogLogs { merge_ops, lhs, rhs, merged } => VecOperator::merge_hyperloglogs(merge_ops, lhs.hyperloglogs()?, rhs.hyperloglogs()?, merged.hyperloglogs()?),
         QueryPlan::ConstantVec { index, constant_vec } => VecOperator::constant_vec(std::mem::replace(&mut constant_vecs[index], Data::empty(1)), constant_vec.any()),
     };
     result.push(operation);
     Ok(result.last_buffer())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mem_store::integers::IntegerColumn;
+
+    fn compare(op: Func2Type, column: &str, value: i64) -> Expr {
+        Expr::Func2(op, Box::new(Expr::ColName(column.to_string())), Box::new(Expr::Const(RawVal::Int(value))))
+    }
+
+    #[test]
+    fn test_order_conjuncts() {
+        let mut columns = HashMap::<String, Arc<DataSource>>::default();
+        columns.insert("a".to_string(), IntegerColumn::new_boxed("a", (0..1000).collect(), 0, 999, false, None, 0));
+        let broad = compare(Func2Type::GT, "a", 10);
+        let selective = compare(Func2Type::LT, "a", 5);
+        let unknown = compare(Func2Type::Equals, "b", 1);
+        let expr = Expr::Func2(Func2Type::And,
+                               Box::new(Expr::Func2(Func2Type::And, Box::new(unknown.clone()), Box::new(broad.clone()))),
+                               Box::new(selective.clone()));
+        assert_eq!(format!("{:?}", order_conjuncts(&expr, &columns)),
+                   format!("{:?}", vec![&selective, &broad, &unknown]));
+        let expr = Expr::Func2(Func2Type::Or,
+                               Box::new(Expr::Func2(Func2Type::Or, Box::new(unknown.clone()), Box::new(selective.clone()))),
+                               Box::new(broad.clone()));
+        assert_eq!(format!("{:?}", order_disjuncts(&expr, &columns)),
+                   format!("{:?}", vec![&broad, &selective, &unknown]));
+    }
+
+    #[test]
+    fn test_compile_condition_short_circuits() {
+        let mut columns = HashMap::<String, Arc<DataSource>>::default();
+        columns.insert("a".to_string(), IntegerColumn::new_boxed("a", (0..1000).collect(), 0, 999, false, None, 0));
+        // No selectivity estimate for `a % 7 = 3`, which is therefore evaluated last
+        let unknown = Expr::Func2(Func2Type::Equals,
+                                  Box::new(Expr::Func2(Func2Type::Modulo, Box::new(Expr::ColName("a".to_string())), Box::new(Expr::Const(RawVal::Int(7))))),
+                                  Box::new(Expr::Const(RawVal::Int(3))));
+        let short_circuits = |function: Func2Type, lhs: Expr| {
+            let expr = Expr::Func2(function, Box::new(unknown.clone()), Box::new(lhs));
+            let mut planner = QueryPlanner::default();
+            compile_condition(&expr, &columns, 1000, &mut planner).unwrap();
+            format!("{:?}", planner.operations).contains("ShortCircuit")
+        };
+        assert!(short_circuits(Func2Type::And, compare(Func2Type::LT, "a", 5)));
+        assert!(!short_circuits(Func2Type::And, compare(Func2Type::GT, "a", 5)));
+        assert!(short_circuits(Func2Type::Or, compare(Func2Type::GT, "a", 5)));
+        assert!(!short_circuits(Func2Type::Or, compare(Func2Type::LT, "a", 5)));
+    }
+
+    #[test]
+    fn test_decode_shared_between_expressions() {
+        let mut columns = HashMap::<String, Arc<DataSource>>::default();
+        columns.insert("a".to_string(), IntegerColumn::new_boxed("a", (1000..1100).collect(), 1000, 1099, false, None, 0));
+        let decode = |planner: &mut QueryPlanner| {
+            let (plan, plan_type) = QueryPlan::compile_expr(&Expr::ColName("a".to_string()), &Filter::None, &columns, 100, planner).unwrap();
+            plan_type.codec.unwrap().decode(plan, planner)
+        };
+        let mut planner = QueryPlanner::default();
+        let first = decode(&mut planner);
+        let operations = planner.operations.len();
+        let second = decode(&mut planner);
+        assert_eq!(first, second);
+        assert_eq!(planner.operations.len(), operations);
+    }
+}
@@ -14,8 +14,14 @@ pub enum QueryError {
     NotImplemented(String),
     #[fail(display = "Type error: {}", _0)]
     TypeError(String),
+    #[fail(display = "Column {} does not exist! Available columns: {}", _0, _1)]
+    UnknownColumn(String, String),
+    #[fail(display = "Table {} does not exist!", _0)]
+    UnknownTable(String),
     #[fail(display = "Overflow or division by zero")]
     Overflow,
+    #[fail(display = "Query exceeded memory limit of {} bytes", _0)]
+    MemoryLimitExceeded(usize),
 }
 
 #[macro_export]
@@ -0,0 +1,174 @@
+extern crate futures;
+extern crate tokio_threadpool;
+extern crate warp;
+
+use std::cmp;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_executor::block_on;
+use serde_json::{self, Map, Value};
+use self::futures::{future, Future};
+use self::warp::{Filter, Rejection};
+use self::warp::http::{Response, StatusCode};
+
+use engine::Query;
+use locustdb::LocustDB;
+use QueryError;
+
+/// Serves the HTTP API on `addr` until the process exits:
+/// - `POST /query` runs the JSON encoded `Query` in the request body and returns a JSON object with the result columns
+///   `colnames`, the result `rows` and whether the result was `truncated` by the timeout. Runtime, rows scanned and
+///   peak memory of the query are reported in the `x-locustdb-*` response headers.
+/// - `GET /tables` returns name, number of rows and columns of every table.
+///
+/// Queries run for at most `max_timeout`, or less if the query sets a lower timeout.
+/// Requests wait for their results on the blocking pool of the HTTP server, which keeps its other threads free to accept
+/// and serve new requests.
+pub fn serve(locustdb: LocustDB, addr: SocketAddr, max_timeout: Duration) {
+    let locustdb = Arc::new(locustdb);
+    let query_db = locustdb.clone();
+    let query = warp::post2()
+        .and(warp::path("query"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and_then(move |query: Query| {
+            let locustdb = query_db.clone();
+            in_blocking_pool(move || run_query(&locustdb, query, max_timeout))
+        });
+    let tables = warp::get2()
+        .and(warp::path("tables"))
+        .and(warp::path::end())
+        .and_then(move || {
+            let locustdb = locustdb.clone();
+            in_blocking_pool(move || list_tables(&locustdb))
+        });
+    warp::serve(query.or(tables)).run(addr);
+}
+
+/// Runs `handler` once the blocking pool of the tokio runtime has capacity, and responds with an error if the future is
+/// polled outside of a tokio thread pool.
+fn in_blocking_pool<F>(handler: F) -> impl Future<Item=Response<String>, Error=Rejection>
+    where F: FnOnce() -> Response<String> {
+    let mut handler = Some(handler);
+    future::poll_fn(move || tokio_threadpool::blocking(|| (handler.take().unwrap())()))
+        .or_else(|_| Ok::<_, Rejection>(error_response(&fatal!("No thread available to run the request"))))
+}
+
+fn run_query(locustdb: &LocustDB, mut query: Query, max_timeout: Duration) -> Response<String> {
+    query.timeout = Some(query.timeout.map_or(max_timeout, |timeout| cmp::min(timeout, max_timeout)));
+    match block_on(locustdb.run_parsed_query(query, false, vec![])) {
+        Ok((Ok(output), _)) => Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "application/json")
+            .header("x-locustdb-runtime-ns", output.stats.runtime_ns.to_string().as_str())
+            .header("x-locustdb-rows-scanned", output.stats.rows_scanned.to_string().as_str())
+            .header("x-locustdb-peak-memory-bytes", output.stats.peak_memory_bytes.to_string().as_str())
+            .body(output.to_json().to_string())
+            .unwrap(),
+        Ok((Err(err), _)) => error_response(&err),
+        Err(_) => error_response(&fatal!("Query was canceled")),
+    }
+}
+
+fn list_tables(locustdb: &LocustDB) -> Response<String> {
+    let stats = match block_on(locustdb.table_stats()) {
+        Ok(stats) => stats,
+        Err(_) => return error_response(&fatal!("Listing tables was canceled")),
+    };
+    let tables = stats.into_iter()
+        .map(|table| {
+            let columns = locustdb.schema(&table.name)
+                .map(|schema| schema.columns.into_iter()
+                    .map(|(name, basic_type, encoding_type)| {
+                        let mut column = Map::new();
+                        column.insert("name".to_string(), Value::from(name));
+                        column.insert("type".to_string(), Value::from(format!("{:?}", basic_type)));
+                        column.insert("encoding".to_string(), Value::from(format!("{:?}", encoding_type)));
+                        Value::Object(column)
+                    })
+                    .collect::<Vec<_>>())
+                .unwrap_or_default();
+            let mut json = Map::new();
+            json.insert("name".to_string(), Value::from(table.name));
+            json.insert("rows".to_string(), Value::from(table.rows));
+            json.insert("columns".to_string(), Value::Array(columns));
+            Value::Object(json)
+        })
+        .collect::<Vec<_>>();
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Value::Array(tables).to_string())
+        .unwrap()
+}
+
+fn error_response(err: &QueryError) -> Response<String> {
+    let status = match err {
+        QueryError::FatalError(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    let mut json = Map::new();
+    json.insert("error".to_string(), Value::from(err.to_string()));
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Value::Object(json).to_string())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+    use self::tokio_threadpool::ThreadPool;
+    use ingest::input_column::InputColumn;
+    use ingest::transaction::Transaction;
+    use syntax::parser;
+
+    #[test]
+    fn test_run_query() {
+        let locustdb = LocustDB::memory_only();
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("a", InputColumn::Int(vec![3, 1, 2]));
+        transaction.add_column("b", InputColumn::Str(vec!["x".to_string(), "y".to_string(), "z".to_string()]));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+        let query = Query::from_json(&parser::parse_query("SELECT a, b FROM default ORDER BY a").unwrap().to_json()).unwrap();
+        let response = run_query(&locustdb, query, Duration::from_secs(10));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-locustdb-rows-scanned"], "3");
+        let json = serde_json::from_str::<Value>(response.body()).unwrap();
+        assert_eq!(json["colnames"].to_string(), r#"["a","b"]"#);
+        assert_eq!(json["rows"].to_string(), r#"[[1,"y"],[2,"z"],[3,"x"]]"#);
+
+        let query = parser::parse_query("SELECT a, a > 1 FROM default ORDER BY a").unwrap();
+        let json = serde_json::from_str::<Value>(run_query(&locustdb, query, Duration::from_secs(10)).body()).unwrap();
+        assert_eq!(json["rows"].to_string(), "[[1,false],[2,true],[3,true]]");
+
+        let query = parser::parse_query("SELECT c FROM missing").unwrap();
+        assert_eq!(run_query(&locustdb, query, Duration::from_secs(10)).status(), StatusCode::BAD_REQUEST);
+
+        let tables = serde_json::from_str::<Value>(list_tables(&locustdb).body()).unwrap();
+        assert_eq!(tables[0]["name"], "default");
+        assert_eq!(tables[0]["rows"], 3);
+        assert_eq!(tables[0]["columns"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_in_blocking_pool() {
+        let pool = ThreadPool::new();
+        let (sender, receiver) = channel();
+        pool.spawn(in_blocking_pool(|| error_response(&QueryError::NotImplemented("x".to_string())))
+            .then(move |response| {
+                sender.send(response.unwrap().status()).unwrap();
+                Ok(())
+            }));
+        assert_eq!(receiver.recv().unwrap(), StatusCode::BAD_REQUEST);
+
+        let outside_pool = in_blocking_pool(|| error_response(&QueryError::NotImplemented("x".to_string()))).wait();
+        assert_eq!(outside_pool.unwrap().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
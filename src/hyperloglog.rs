@@ -0,0 +1,137 @@
+use std::cmp;
+use std::hash::Hasher;
+
+use seahash::SeaHasher;
+
+use ingest::raw_val::RawVal;
+
+pub const DEFAULT_PRECISION: u32 = 12;
+pub const MIN_PRECISION: u32 = 4;
+pub const MAX_PRECISION: u32 = 18;
+
+/// Cardinality estimator with a standard error of roughly `1.04 / sqrt(2^precision)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+    precision: u32,
+}
+
+impl HyperLogLog {
+    pub fn new(precision: u32) -> HyperLogLog {
+        assert!(precision >= MIN_PRECISION && precision <= MAX_PRECISION, "Unsupported HyperLogLog precision {}", precision);
+        HyperLogLog {
+            registers: vec![0; 1 << precision],
+            precision,
+        }
+    }
+
+    /// Reconstructs a sketch from the `2^precision` registers returned by `registers`.
+    pub fn from_registers(registers: Vec<u8>, precision: u32) -> HyperLogLog {
+        assert_eq!(registers.len(), 1 << precision, "Expected {} registers for precision {}", 1 << precision, precision);
+        HyperLogLog { registers, precision }
+    }
+
+    pub fn insert(&mut self, value: &RawVal) {
+        match value {
+            RawVal::Int(i) => self.insert_int(*i),
+            RawVal::Str(s) => self.insert_str(s),
+            RawVal::Null => {}
+        }
+    }
+
+    pub fn insert_int(&mut self, value: i64) {
+        let mut hasher = SeaHasher::new();
+        hasher.write_i64(value);
+        self.insert_hash(hasher.finish());
+    }
+
+    pub fn insert_str(&mut self, value: &str) {
+        let mut hasher = SeaHasher::new();
+        hasher.write(value.as_bytes());
+        self.insert_hash(hasher.finish());
+    }
+
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - self.precision)) as usize;
+        let rest = hash << self.precision;
+        let rank = cmp::min(rest.leading_zeros(), 64 - self.precision) as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        assert_eq!(self.precision, other.precision);
+        for (r1, &r2) in self.registers.iter_mut().zip(other.registers.iter()) {
+            if r2 > *r1 {
+                *r1 = r2;
+            }
+        }
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum = self.registers.iter().map(|&r| 2f64.powi(-i32::from(r))).sum::<f64>();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+
+    /// Relative standard error of the estimate for sketches with the given precision.
+    pub fn standard_error(precision: u32) -> f64 {
+        1.04 / f64::from(1u32 << precision).sqrt()
+    }
+
+    pub fn registers(&self) -> &[u8] { &self.registers }
+    pub fn precision(&self) -> u32 { self.precision }
+    pub fn heap_size_of_children(&self) -> usize { self.registers.capacity() }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> HyperLogLog {
+        HyperLogLog::new(DEFAULT_PRECISION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        for &n in &[0i64, 10, 1000, 100_000] {
+            let mut hll = HyperLogLog::default();
+            for i in 0..n {
+                hll.insert_int(i);
+                hll.insert_int(i);
+            }
+            let estimate = hll.estimate() as f64;
+            assert!((estimate - n as f64).abs() <= 0.05 * n as f64 + 1.0, "n = {}, estimate = {}", n, estimate);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut hll1 = HyperLogLog::default();
+        let mut hll2 = HyperLogLog::default();
+        for i in 0..5000 {
+            hll1.insert_int(i);
+            hll2.insert_int(i + 2500);
+        }
+        hll1.merge(&hll2);
+        let estimate = hll1.estimate() as f64;
+        assert!((estimate - 7500.0).abs() < 375.0, "estimate = {}", estimate);
+    }
+
+    #[test]
+    fn test_standard_error() {
+        assert!((HyperLogLog::standard_error(12) - 0.01625).abs() < 1e-9);
+        assert!(HyperLogLog::standard_error(MIN_PRECISION) > HyperLogLog::standard_error(MAX_PRECISION));
+    }
+}
@@ -40,6 +40,7 @@ impl Buffer {
             match input_col {
                 InputColumn::Int(vec) => buffered_col.push_ints(vec),
                 InputColumn::Str(vec) => buffered_col.push_strings(vec),
+                InputColumn::Bool(vec) => buffered_col.push_ints(vec.into_iter().map(|b| b as i64).collect()),
                 InputColumn::Null(c) => buffered_col.push_nulls(c),
             }
             new_length = cmp::max(new_length, buffered_col.len())
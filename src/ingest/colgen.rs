@@ -255,7 +255,7 @@ impl GenTable {
     }
 }
 
-fn seeded_rng(seed: u64) -> rand::XorShiftRng {
+pub(crate) fn seeded_rng(seed: u64) -> rand::XorShiftRng {
     let mut seed_bytes = [0u8; 16];
     let mut hasher = Md5::new();
     hasher.input(&seed.to_ne_bytes());
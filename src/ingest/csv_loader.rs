@@ -12,6 +12,7 @@ use std::fs::File;
 use std::ops::BitOr;
 use std::str;
 use std::sync::Arc;
+use std::time::SystemTime;
 use stringpack::*;
 
 use super::extractor;
@@ -30,6 +31,7 @@ pub struct Options {
     always_string: HashSet<usize>,
     allow_nulls: HashSet<usize>,
     allow_nulls_all_columns: bool,
+    histogram_buckets: usize,
     unzip: bool,
 }
 
@@ -45,6 +47,7 @@ impl Options {
             always_string: HashSet::new(),
             allow_nulls: HashSet::new(),
             allow_nulls_all_columns: false,
+            histogram_buckets: 0,
             unzip: filename.ends_with(".gz"),
         }
     }
@@ -118,6 +121,12 @@ impl Options {
         self.allow_nulls_all_columns = true;
         self
     }
+
+    /// Stores a histogram with `buckets` buckets for every integer column, which is used to estimate the selectivity of predicates.
+    pub fn with_histograms(mut self, buckets: usize) -> Options {
+        self.histogram_buckets = buckets;
+        self
+    }
 }
 
 pub fn ingest_file(ldb: &InnerLocustDB, opts: &Options) -> Result<(), String> {
@@ -151,8 +160,10 @@ fn auto_ingest<T>(ldb: &InnerLocustDB, records: T, colnames: &[String], opts: &O
     let ignore = (0..colnames.len()).map(|x| opts.ignore_cols.contains(&x)).collect::<Vec<_>>();
     let string = (0..colnames.len()).map(|x| opts.always_string.contains(&x)).collect::<Vec<_>>();
     let mut raw_cols = (0..colnames.len()).map(|x|
-        RawCol::new(opts.allow_nulls_all_columns || opts.allow_nulls.contains(&x))).collect::<Vec<_>>();
+        RawCol::new(opts.allow_nulls_all_columns || opts.allow_nulls.contains(&x), opts.histogram_buckets)).collect::<Vec<_>>();
     let mut row_num = 0usize;
+    // All partitions of a file are ingested at the same time, so that they can be compacted with each other
+    let ingested_at = SystemTime::now();
     for row in records {
         for (i, val) in row.iter().enumerate() {
             if !ignore[i] {
@@ -162,14 +173,14 @@ fn auto_ingest<T>(ldb: &InnerLocustDB, records: T, colnames: &[String], opts: &O
 
         if row_num % opts.partition_size == opts.partition_size - 1 {
             let partition = create_batch(&mut raw_cols, colnames, &opts.extractors, &ignore, &string);
-            ldb.store_partition(&opts.tablename, partition);
+            ldb.store_partition_at(&opts.tablename, partition, ingested_at);
         }
         row_num += 1;
     }
 
     if row_num % opts.partition_size != 0 {
         let partition = create_batch(&mut raw_cols, colnames, &opts.extractors, &ignore, &string);
-        ldb.store_partition(&opts.tablename, partition);
+        ldb.store_partition_at(&opts.tablename, partition, ingested_at);
     }
     Ok(())
 }
@@ -224,10 +235,11 @@ struct RawCol {
     allow_null: bool,
     present: Vec<u8>,
     any_null: bool,
+    histogram_buckets: usize,
 }
 
 impl RawCol {
-    fn new(allow_null: bool) -> RawCol {
+    fn new(allow_null: bool, histogram_buckets: usize) -> RawCol {
         RawCol {
             types: ColType::nothing(),
             values: IndexedPackedStrings::default(),
@@ -237,6 +249,7 @@ impl RawCol {
             allow_null,
             present: Vec::new(),
             any_null: false,
+            histogram_buckets,
         }
     }
 
@@ -263,7 +276,7 @@ impl RawCol {
             fast_build_string_column(name, self.values.iter(), self.values.len(),
                                      self.lhex, self.uhex, self.string_bytes, present)
         } else if self.types.contains_int {
-            let mut builder = IntColBuilder::default();
+            let mut builder = IntColBuilder::default().with_histogram(self.histogram_buckets);
             for s in self.values.iter() {
                 let int = if s.is_empty() {
                     if self.allow_null { None } else { Some(0) }
@@ -285,7 +298,7 @@ impl RawCol {
     }
 
     fn extract(&mut self, name: &str, extractor: extractor::Extractor) -> Arc<Column> {
-        let mut builder = IntColBuilder::default();
+        let mut builder = IntColBuilder::default().with_histogram(self.histogram_buckets);
         for s in self.values.iter() {
             if self.allow_null {
                 if s.is_empty() {
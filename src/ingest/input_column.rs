@@ -2,6 +2,7 @@
 pub enum InputColumn {
     Int(Vec<i64>),
     Str(Vec<String>),
+    Bool(Vec<bool>),
     Null(usize),
 }
 
@@ -6,4 +6,7 @@ pub mod extractor;
 pub mod nyc_taxi_data;
 pub mod colgen;
 pub mod schema;
+pub mod transaction;
+#[cfg(feature = "enable_parquet")]
+pub mod parquet_loader;
 mod alias_method_fork;
\ No newline at end of file
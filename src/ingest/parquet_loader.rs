@@ -0,0 +1,158 @@
+extern crate parquet;
+
+use std::fs::File;
+use std::str;
+
+use self::parquet::file::reader::{FileReader, SerializedFileReader};
+use self::parquet::record::api::Field;
+
+use ingest::raw_val::RawVal;
+use mem_store::column_builder::build_column;
+use scheduler::inner_locustdb::InnerLocustDB;
+
+
+/// Adds every row group of the Parquet file `filename` to `tablename` as a separate partition.
+/// Returns the number of rows ingested.
+///
+/// Dictionary encoded column chunks are read as plain values and encoded again by `build_column`, which stores string
+/// columns with few distinct values in a dictionary codec. The file's dictionaries are not reused because they are
+/// specific to each column chunk and may contain values that are not referenced by any row.
+pub fn ingest_file(ldb: &InnerLocustDB, filename: &str, tablename: &str) -> Result<usize, String> {
+    let file = File::open(filename).map_err(|err| err.to_string())?;
+    let reader = SerializedFileReader::new(file).map_err(|err| err.to_string())?;
+    let mut rows_total = 0;
+    for i in 0..reader.num_row_groups() {
+        let row_group = reader.get_row_group(i).map_err(|err| err.to_string())?;
+        let mut columns = Vec::<(String, Vec<RawVal>)>::new();
+        for row in row_group.get_row_iter(None).map_err(|err| err.to_string())? {
+            for (j, (name, field)) in row.get_column_iter().enumerate() {
+                if j == columns.len() {
+                    columns.push((name.to_string(), Vec::new()));
+                }
+                columns[j].1.push(convert_field(name, field)?);
+            }
+            rows_total += 1;
+        }
+        if !columns.is_empty() {
+            let partition = columns.into_iter()
+                .map(|(name, values)| build_column(&name, values))
+                .collect();
+            ldb.store_partition(tablename, partition);
+        }
+    }
+    Ok(rows_total)
+}
+
+/// Integers, booleans, dates and timestamps are ingested as integers and strings as strings. Floating point
+/// numbers are not supported by the query engine and are stored as strings to retain their value.
+fn convert_field(name: &str, field: &Field) -> Result<RawVal, String> {
+    Ok(match *field {
+        Field::Null => RawVal::Null,
+        Field::Bool(b) => RawVal::Int(b as i64),
+        Field::Byte(i) => RawVal::Int(i64::from(i)),
+        Field::Short(i) => RawVal::Int(i64::from(i)),
+        Field::Int(i) => RawVal::Int(i64::from(i)),
+        Field::Long(i) => RawVal::Int(i),
+        Field::Date(days) => RawVal::Int(i64::from(days)),
+        Field::Timestamp(millis) => RawVal::Int(millis as i64),
+        Field::Float(f) => RawVal::Str(f.to_string()),
+        Field::Double(f) => RawVal::Str(f.to_string()),
+        Field::Str(ref s) => RawVal::Str(s.clone()),
+        Field::Bytes(ref bytes) => match str::from_utf8(bytes.data()) {
+            Ok(s) => RawVal::Str(s.to_string()),
+            Err(_) => return Err(format!("Column {} contains binary data that is not valid UTF-8", name)),
+        },
+        _ => return Err(format!("Column {} has a nested type, which is not supported", name)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use super::parquet::column::writer::ColumnWriter;
+    use super::parquet::data_type::ByteArray;
+    use super::parquet::file::properties::WriterProperties;
+    use super::parquet::file::writer::{FileWriter, RowGroupWriter, SerializedFileWriter};
+    use super::parquet::schema::parser::parse_message_type;
+    use futures_executor::block_on;
+    use engine::{BasicType, EncodingType};
+    use locustdb::LocustDB;
+    use tempdir::TempDir;
+
+    /// Writes a file with two row groups of an optional INT64 column `i` and a dictionary encoded UTF8 column `s`.
+    /// The first row group holds the rows `(null, "x"), (1, "y"), (2, "x"), ..., (9, "y")`, the second `(100, "z")`.
+    fn write_fixture(filename: &str) {
+        let schema = Rc::new(parse_message_type("
+            message schema {
+                OPTIONAL INT64 i;
+                REQUIRED BYTE_ARRAY s (UTF8);
+            }").unwrap());
+        let props = Rc::new(WriterProperties::builder().set_dictionary_enabled(true).build());
+        let mut writer = SerializedFileWriter::new(File::create(filename).unwrap(), schema, props).unwrap();
+        let row_groups = vec![
+            ((1..10).collect::<Vec<i64>>(), (0..10).map(|r| (r != 0) as i16).collect::<Vec<_>>(),
+             (0..10).map(|r| if r % 2 == 0 { "x" } else { "y" }).collect::<Vec<_>>()),
+            (vec![100], vec![1], vec!["z"]),
+        ];
+        for (ints, definition_levels, strings) in row_groups {
+            let mut row_group_writer = writer.next_row_group().unwrap();
+            while let Some(mut column_writer) = row_group_writer.next_column().unwrap() {
+                match column_writer {
+                    ColumnWriter::Int64ColumnWriter(ref mut typed) => {
+                        typed.write_batch(&ints, Some(&definition_levels), None).unwrap();
+                    }
+                    ColumnWriter::ByteArrayColumnWriter(ref mut typed) => {
+                        let values = strings.iter().map(|&s| ByteArray::from(s)).collect::<Vec<_>>();
+                        typed.write_batch(&values, None, None).unwrap();
+                    }
+                    _ => unreachable!(),
+                }
+                row_group_writer.close_column(column_writer).unwrap();
+            }
+            writer.close_row_group(row_group_writer).unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn query(locustdb: &LocustDB, query: &str) -> Vec<Vec<RawVal>> {
+        block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows
+    }
+
+    #[test]
+    fn test_ingest_file() {
+        let dir = TempDir::new("parquet_loader").unwrap();
+        let filename = dir.path().join("fixture.parquet").to_str().unwrap().to_string();
+        write_fixture(&filename);
+
+        let locustdb = LocustDB::memory_only();
+        assert_eq!(block_on(locustdb.load_parquet(&filename, "t")).unwrap(), Ok(11));
+        let stats = block_on(locustdb.table_stats()).unwrap();
+        assert_eq!((stats[0].rows, stats[0].batches), (11, 2));
+        let schema = locustdb.schema("t").unwrap();
+        // Strings are stored as indices into a dictionary
+        assert!(schema.columns.contains(&("s".to_string(), BasicType::String, EncodingType::U8)));
+
+        assert_eq!(query(&locustdb, "SELECT i, s FROM t WHERE i > 7 ORDER BY i"), vec![
+            vec![RawVal::Int(8), RawVal::Str("x".to_string())],
+            vec![RawVal::Int(9), RawVal::Str("y".to_string())],
+            vec![RawVal::Int(100), RawVal::Str("z".to_string())],
+        ]);
+        assert_eq!(query(&locustdb, "SELECT s FROM t WHERE i IS NULL"), vec![vec![RawVal::Str("x".to_string())]]);
+        assert_eq!(query(&locustdb, "SELECT s, COUNT(0) FROM t ORDER BY s"), vec![
+            vec![RawVal::Str("x".to_string()), RawVal::Int(5)],
+            vec![RawVal::Str("y".to_string()), RawVal::Int(5)],
+            vec![RawVal::Str("z".to_string()), RawVal::Int(1)],
+        ]);
+    }
+
+    #[test]
+    fn test_convert_field() {
+        assert_eq!(convert_field("a", &Field::Long(-3)), Ok(RawVal::Int(-3)));
+        assert_eq!(convert_field("a", &Field::Bool(true)), Ok(RawVal::Int(1)));
+        assert_eq!(convert_field("a", &Field::Null), Ok(RawVal::Null));
+        assert_eq!(convert_field("a", &Field::Str("x".to_string())), Ok(RawVal::Str("x".to_string())));
+        assert_eq!(convert_field("a", &Field::Double(0.5)), Ok(RawVal::Str("0.5".to_string())));
+    }
+}
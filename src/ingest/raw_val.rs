@@ -4,6 +4,7 @@ use engine::data_types::BasicType;
 
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
 pub enum RawVal {
     Int(i64),
     Str(String),
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use ingest::input_column::InputColumn;
+use mem_store::booleans::BooleanColumn;
+use mem_store::column_builder::*;
+use mem_store::*;
+use mem_store::partition::TOMBSTONE_COLUMN;
+
+
+/// Accumulates the columns of a batch that is added to a table as a single partition.
+/// Nothing is ingested until the transaction is committed, and a commit either adds all columns or none of them.
+pub struct Transaction {
+    table: String,
+    columns: Vec<(String, InputColumn)>,
+}
+
+impl Transaction {
+    pub fn new(table: &str) -> Transaction {
+        Transaction {
+            table: table.to_string(),
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn add_column(&mut self, name: &str, column: InputColumn) {
+        self.columns.push((name.to_string(), column));
+    }
+
+    /// Checks that the batch is nonempty and that all columns have distinct names and the same length.
+    /// Returns the number of rows in the batch.
+    pub fn validate(&self) -> Result<usize, String> {
+        let len = match self.columns.first() {
+            Some((_, column)) => column_len(column),
+            None => return Err(format!("Transaction on table {} contains no columns", self.table)),
+        };
+        if len == 0 {
+            return Err(format!("Transaction on table {} contains no rows", self.table));
+        }
+        for (i, (name, column)) in self.columns.iter().enumerate() {
+            if name.is_empty() || name.as_str() == TOMBSTONE_COLUMN {
+                return Err(format!("Invalid column name `{}`", name));
+            }
+            if self.columns[..i].iter().any(|(other, _)| other == name) {
+                return Err(format!("Column {} occurs more than once", name));
+            }
+            if column_len(column) != len {
+                return Err(format!("Column {} has {} rows, expected {}", name, column_len(column), len));
+            }
+        }
+        Ok(len)
+    }
+
+    /// Validates the batch and encodes all of its columns.
+    /// The returned columns can be added to the table without any further checks.
+    pub fn prepare(self) -> Result<(String, Vec<Arc<Column>>), String> {
+        self.validate()?;
+        let columns = self.columns.into_iter()
+            .map(|(name, column)| match column {
+                InputColumn::Int(values) => {
+                    let mut builder = IntColBuilder::default();
+                    for value in values {
+                        builder.push(&Some(value));
+                    }
+                    builder.finalize(&name, None)
+                }
+                InputColumn::Str(values) => {
+                    let mut builder = StringColBuilder::default();
+                    for value in &values {
+                        builder.push(value);
+                    }
+                    ColumnBuilder::<String>::finalize(builder, &name, None)
+                }
+                InputColumn::Bool(values) => BooleanColumn::new_boxed(&name, &values),
+                InputColumn::Null(len) => Arc::new(Column::null(&name, len)),
+            })
+            .collect();
+        Ok((self.table, columns))
+    }
+}
+
+fn column_len(column: &InputColumn) -> usize {
+    match *column {
+        InputColumn::Int(ref values) => values.len(),
+        InputColumn::Str(ref values) => values.len(),
+        InputColumn::Bool(ref values) => values.len(),
+        InputColumn::Null(len) => len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut transaction = Transaction::new("default");
+        assert!(transaction.validate().is_err());
+        transaction.add_column("a", InputColumn::Int(vec![1, 2, 3]));
+        transaction.add_column("b", InputColumn::Str(vec!["x".to_string(), "y".to_string(), "z".to_string()]));
+        assert_eq!(transaction.validate(), Ok(3));
+        transaction.add_column("c", InputColumn::Null(2));
+        assert!(transaction.validate().is_err());
+
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("a", InputColumn::Int(vec![1]));
+        transaction.add_column("a", InputColumn::Int(vec![2]));
+        assert!(transaction.validate().is_err());
+    }
+}
@@ -1,6 +1,8 @@
 #![feature(fn_traits, integer_atomics, refcell_replace_swap, specialization, trait_alias, core_intrinsics, box_patterns, proc_macro_hygiene)]
 extern crate aliasmethod;
 extern crate byteorder;
+#[cfg(feature = "enable_proto")]
+extern crate bytes;
 #[cfg(feature = "enable_rocksdb")]
 extern crate capnp;
 extern crate chrono;
@@ -23,27 +25,47 @@ extern crate log;
 extern crate lru;
 extern crate num;
 extern crate num_cpus;
+#[cfg(feature = "enable_proto")]
+extern crate prost;
+#[cfg(feature = "enable_proto")]
+#[macro_use]
+extern crate prost_derive;
 extern crate rand;
 extern crate regex;
 extern crate seahash;
+#[cfg(feature = "enable_serde")]
+extern crate serde;
+#[cfg(feature = "enable_serde")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "enable_serde")]
+extern crate serde_json;
 extern crate sqlparser;
 extern crate std_semaphore;
 extern crate tempdir;
+#[cfg(not(all(feature = "enable_wasm", target_arch = "wasm32")))]
 extern crate time;
 
 
 pub use disk_store::noop_storage::NoopStorage;
+pub use engine::data_types::{BasicType, EncodingType};
+pub use engine::OverflowPolicy;
+pub use engine::{ColumnarResult, ResultColumn};
 pub use engine::query_task::QueryOutput;
+pub use engine::markdown_table;
 pub use errors::QueryError;
 pub use ingest::colgen;
 pub use ingest::csv_loader::Options as LoadOptions;
 pub use ingest::extractor;
+pub use ingest::input_column::InputColumn;
 pub use ingest::nyc_taxi_data;
 pub use ingest::raw_val::RawVal as Value;
 pub use ingest::raw_val::syntax as value_syntax;
+pub use ingest::transaction::Transaction;
 pub use locustdb::LocustDB as LocustDB;
 pub use locustdb::Options as Options;
-pub use mem_store::table::TableStats;
+pub use mem_store::Cardinality;
+pub use mem_store::table::{ColumnDescription, ColumnMetadata, TableSchema, TableStats};
 #[doc(hidden)]
 pub use trace::_replace;
 #[doc(hidden)]
@@ -62,7 +84,14 @@ mod locustdb;
 mod disk_store;
 mod stringpack;
 mod bitvec;
+mod hyperloglog;
+#[cfg(feature = "enable_http")]
+pub mod http_server;
 pub mod unit_fmt;
+#[cfg(feature = "enable_wasm")]
+pub mod wasm;
+#[cfg(all(feature = "enable_wasm", target_arch = "wasm32"))]
+use wasm::time;
 
 pub type QueryResult = Result<QueryOutput, QueryError>;
 
@@ -72,3 +101,10 @@ pub(crate) mod storage_format_capnp {
     include!(concat!(env!("OUT_DIR"), "/storage_format_capnp.rs"));
 }
 
+/// Protocol Buffers messages for queries and partial results generated from `src/query.proto`.
+#[allow(warnings)]
+#[cfg(feature = "enable_proto")]
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/locustdb.rs"));
+}
+
@@ -1,5 +1,8 @@
+use std::cmp;
+use std::collections::HashMap;
 use std::str;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 use futures_channel::oneshot;
 use futures_core::*;
@@ -12,11 +15,21 @@ use QueryError;
 use QueryResult;
 use disk_store::interface::*;
 use disk_store::noop_storage::NoopStorage;
-use engine::query_task::QueryTask;
+use engine::{BasicType, OverflowPolicy};
+use engine::{Query, Rollup, UnionAll};
+use engine::query_task::{QueryOutput, QueryStats, QueryTask};
+use engine::union_all::{union_all, union_grouping_sets};
 use ingest::colgen::GenTable;
 use ingest::csv_loader::{CSVIngestionTask, Options as LoadOptions};
+use ingest::input_column::InputColumn;
+use ingest::raw_val::RawVal;
+use ingest::transaction::Transaction;
 use mem_store::*;
+use mem_store::table::ColumnMetadata;
+#[cfg(feature = "enable_wasm")]
+use mem_store::column_builder::build_column;
 use scheduler::*;
+use syntax::limit::LimitClause;
 use syntax::parser;
 use trace::{Trace, TraceBuilder};
 
@@ -32,7 +45,11 @@ impl LocustDB {
 
     pub fn new(opts: &Options) -> LocustDB {
         let disk_store = opts.db_path.as_ref()
-            .map(|path| LocustDB::persistent_storage(path))
+            .map(|path| if opts.mmap_storage {
+                LocustDB::mmap_storage(path)
+            } else {
+                LocustDB::persistent_storage(path)
+            })
             .unwrap_or_else(|| Arc::new(NoopStorage));
         let locustdb = Arc::new(InnerLocustDB::new(disk_store, opts));
         InnerLocustDB::start_worker_threads(&locustdb);
@@ -40,17 +57,43 @@ impl LocustDB {
     }
 
     pub fn run_query(&self, query: &str, explain: bool, show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
-        let (sender, receiver) = oneshot::channel();
+        if let Some(delete) = parser::parse_delete(query) {
+            return match delete {
+                Ok((table, filter)) => self.run_statement("deleted", move |inner| inner.delete(&table, &filter)),
+                Err(err) => Box::new(future::ok((Err(err), TraceBuilder::new("empty".to_owned()).finalize()))),
+            };
+        }
+        if let Some(table) = parser::parse_vacuum(query) {
+            return self.run_statement("removed", move |inner| inner.vacuum(&table));
+        }
+        if let Some(union) = parser::parse_union(query) {
+            return match union {
+                Ok(union) => self.run_union(union, explain, show),
+                Err(err) => Box::new(future::ok((Err(err), TraceBuilder::new("empty".to_owned()).finalize()))),
+            };
+        }
+        if let Some(rollup) = parser::parse_rollup(query) {
+            return match rollup {
+                Ok(rollup) => self.run_rollup(rollup, explain, show),
+                Err(err) => Box::new(future::ok((Err(err), TraceBuilder::new("empty".to_owned()).finalize()))),
+            };
+        }
 
         // PERF: perform compilation and table snapshot in asynchronous task?
-        let query = match parser::parse_query(query) {
-            Ok(query) => query,
-            Err(err) => {
-                return Box::new(future::ok(
-                    (Err(err),
-                     TraceBuilder::new("empty".to_owned()).finalize())));
-            }
-        };
+        match parser::parse_query(query) {
+            Ok(query) => self.run_parsed_query(query, explain, show),
+            Err(err) => Box::new(future::ok(
+                (Err(err),
+                 TraceBuilder::new("empty".to_owned()).finalize()))),
+        }
+    }
+
+    pub(crate) fn run_parsed_query(&self, mut query: Query, explain: bool, show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
+        let (sender, receiver) = oneshot::channel();
+
+        query.memory_limit_bytes = query.memory_limit_bytes.or(self.inner_locustdb.opts().query_memory_limit);
+        query.batch_size = query.batch_size.or(self.inner_locustdb.opts().query_batch_size);
+        query.timeout = query.timeout.or(self.inner_locustdb.opts().query_timeout);
 
         let mut data = match self.inner_locustdb.snapshot(&query.table) {
             Some(data) => data,
@@ -58,11 +101,15 @@ impl LocustDB {
                 Err(QueryError::NotImplemented(format!("Table {} does not exist!", &query.table))),
                 TraceBuilder::new("empty".to_owned()).finalize()))),
         };
+        if let Some(as_of) = query.as_of {
+            data.retain(|partition| partition.ingested_at() <= as_of);
+        }
 
+        let column_aliases = self.inner_locustdb.column_aliases(&query.table);
         if self.inner_locustdb.opts().seq_disk_read {
             self.inner_locustdb.disk_read_scheduler()
                 .schedule_sequential_read(&mut data,
-                                          &query.find_referenced_cols(),
+                                          &column_aliases.stored_cols(&query.find_referenced_cols()),
                                           self.inner_locustdb.opts().readahead);
             let ldb = self.inner_locustdb.clone();
             let (read_data, _) = Task::from_fn(move || ldb.disk_read_scheduler().service_reads(&ldb));
@@ -70,7 +117,7 @@ impl LocustDB {
         }
 
         match QueryTask::new(
-            query, explain, show, data,
+            query, explain, show, self.inner_locustdb.opts().sum_overflow, data, column_aliases,
             self.inner_locustdb.disk_read_scheduler().clone(),
             SharedSender::new(sender)) {
                 Ok(task) => {
@@ -83,6 +130,65 @@ impl LocustDB {
             }
     }
 
+    /// Runs both queries of `union` and merges their results.
+    /// Each query returns enough rows to compute the top `limit` rows of the combined result.
+    fn run_union(&self, mut union: UnionAll, explain: bool, show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
+        for query in &mut [&mut union.left, &mut union.right] {
+            query.limit = combined_result_limit(&union.limit);
+        }
+        let UnionAll { left, right, order_by, limit } = union;
+        Box::new(self.run_parsed_query(left, explain, show.clone())
+            .join(self.run_parsed_query(right, explain, show))
+            .map(move |((left, trace), (right, _))| {
+                let result = left.and_then(|left| right.and_then(|right| union_all(left, right, &order_by, &limit)));
+                (result, trace)
+            }))
+    }
+
+    /// Runs the query of each grouping set of `rollup` and merges their results.
+    fn run_rollup(&self, rollup: Rollup, explain: bool, show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
+        let grouping_sets = match rollup.grouping_sets() {
+            Ok(grouping_sets) => grouping_sets,
+            Err(err) => return Box::new(future::ok((Err(err), TraceBuilder::new("empty".to_owned()).finalize()))),
+        };
+        let query_limit = combined_result_limit(&rollup.limit);
+        let mut results: Box<Future<Item=(Vec<(QueryResult, Vec<usize>)>, Option<Trace>), Error=oneshot::Canceled>> =
+            Box::new(future::ok((vec![], None)));
+        for (mut query, nulls) in grouping_sets {
+            query.limit = query_limit.clone();
+            results = Box::new(results.join(self.run_parsed_query(query, explain, show.clone()))
+                .map(move |((mut results, trace), (result, query_trace))| {
+                    results.push((result, nulls));
+                    (results, trace.or(Some(query_trace)))
+                }));
+        }
+        let Rollup { order_by, limit, .. } = rollup;
+        Box::new(results.map(move |(results, trace)| {
+            let result = results.into_iter()
+                .map(|(result, nulls)| result.map(|output| (output, nulls)))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|results| union_grouping_sets(results, &order_by, &limit));
+            (result, trace.unwrap_or_else(|| TraceBuilder::new("empty".to_owned()).finalize()))
+        }))
+    }
+
+    /// Executes a statement that modifies the database and returns the number of affected rows as a single column.
+    fn run_statement<F>(&self, colname: &str, statement: F) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>>
+        where F: Fn(&InnerLocustDB) -> Result<usize, QueryError> + Sync + Send + 'static {
+        let inner = self.inner_locustdb.clone();
+        let colname = colname.to_string();
+        let (task, receiver) = Task::from_fn(move || statement(&inner).map(|count| QueryOutput {
+            colnames: vec![colname.clone()],
+            coltypes: vec![BasicType::Integer],
+            rows: vec![vec![RawVal::Int(count as i64)]],
+            query_plans: HashMap::default(),
+            stats: QueryStats::default(),
+            truncated: false,
+        }));
+        let trace_receiver = self.schedule(task);
+        Box::new(receiver.join(trace_receiver))
+    }
+
     pub fn load_csv(&self, options: LoadOptions) -> impl Future<Item=Result<(), String>, Error=oneshot::Canceled> {
         let (sender, receiver) = oneshot::channel();
         let task = CSVIngestionTask::new(
@@ -93,6 +199,71 @@ impl LocustDB {
         receiver
     }
 
+    /// Ingests the Parquet file `filename` into `tablename`, one partition per row group, and returns the number of rows added.
+    #[cfg(feature = "enable_parquet")]
+    pub fn load_parquet(&self, filename: &str, tablename: &str) -> impl Future<Item=Result<usize, String>, Error=oneshot::Canceled> {
+        use ingest::parquet_loader;
+        let inner = self.inner_locustdb.clone();
+        let filename = filename.to_string();
+        let tablename = tablename.to_string();
+        let (task, receiver) = Task::from_fn(move || parquet_loader::ingest_file(&inner, &filename, &tablename));
+        self.schedule(task);
+        receiver
+    }
+
+    /// Adds the columns of `transaction` to its table as a single partition and returns the number of rows added.
+    /// All columns are validated and encoded before the partition is added, so if the commit fails none of them are ingested.
+    pub fn commit(&self, transaction: Transaction) -> impl Future<Item=Result<usize, String>, Error=oneshot::Canceled> {
+        self.commit_at(transaction, SystemTime::now())
+    }
+
+    /// Adds `data` to `table` as a single partition like `commit`, but records `ts` as its ingest timestamp.
+    /// Queries with a `FOR SYSTEM_TIME AS OF` clause earlier than `ts` will not see any of the rows.
+    pub fn ingest_with_timestamp(&self, table: &str, data: Vec<(String, InputColumn)>, ts: SystemTime) -> impl Future<Item=Result<usize, String>, Error=oneshot::Canceled> {
+        let mut transaction = Transaction::new(table);
+        for (name, column) in data {
+            transaction.add_column(&name, column);
+        }
+        self.commit_at(transaction, ts)
+    }
+
+    fn commit_at(&self, transaction: Transaction, ts: SystemTime) -> impl Future<Item=Result<usize, String>, Error=oneshot::Canceled> {
+        let inner = self.inner_locustdb.clone();
+        let prepared = transaction.prepare();
+        let (task, receiver) = Task::from_fn(move || match prepared {
+            Ok((ref table, ref columns)) => {
+                inner.store_partition_at(table, columns.clone(), ts);
+                Ok(columns[0].len())
+            }
+            Err(ref error) => Err(error.clone()),
+        });
+        self.schedule(task);
+        receiver
+    }
+
+    /// Adds `columns` to `table` as a single partition, the columns may contain any mix of integers, strings and nulls.
+    #[cfg(feature = "enable_wasm")]
+    pub(crate) fn ingest_columns_at(&self, table: &str, columns: Vec<(String, Vec<RawVal>)>, ts: SystemTime) -> impl Future<Item=usize, Error=oneshot::Canceled> {
+        let inner = self.inner_locustdb.clone();
+        let table = table.to_string();
+        let (task, receiver) = Task::from_fn(move || {
+            let len = columns.first().map_or(0, |(_, values)| values.len());
+            let columns = columns.iter()
+                .map(|(name, values)| build_column(name, values.clone()))
+                .collect();
+            inner.store_partition_at(&table, columns, ts);
+            len
+        });
+        self.schedule(task);
+        receiver
+    }
+
+    /// Executes scheduled tasks on the calling thread, in place of the worker threads that the browser doesn't support.
+    #[cfg(feature = "enable_wasm")]
+    pub(crate) fn run_pending_tasks(&self) {
+        self.inner_locustdb.run_pending_tasks();
+    }
+
     pub fn gen_table(&self, opts: GenTable) -> impl Future<Item=(), Error=oneshot::Canceled> {
         let mut receivers = Vec::new();
         let opts = Arc::new(opts);
@@ -151,6 +322,79 @@ impl LocustDB {
         receiver
     }
 
+    /// Returns the columns of `table_name`, or `None` if the table does not exist.
+    pub fn schema(&self, table_name: &str) -> Option<TableSchema> {
+        self.inner_locustdb.schema(table_name)
+    }
+
+    /// Returns the type, value range and approximate cardinality of each column of `table_name`, or `None` if the
+    /// table does not exist. Unlike `describe`, this only reads metadata and does not scan any columns.
+    pub fn column_metadata(&self, table_name: &str) -> Option<Vec<ColumnMetadata>> {
+        self.inner_locustdb.column_metadata(table_name)
+    }
+
+    /// Checks `query` against the schema of its table without running it and returns all problems that were found.
+    /// See `Query::validate` for the checks that are performed.
+    pub fn validate(&self, query: &str) -> Vec<QueryError> {
+        let query = match parser::parse_query(query) {
+            Ok(query) => query,
+            Err(err) => return vec![err],
+        };
+        match self.schema(&query.table) {
+            Some(schema) => query.validate(&schema, &self.inner_locustdb.column_aliases(&query.table)),
+            None => vec![QueryError::UnknownTable(query.table)],
+        }
+    }
+
+    /// Returns row count, null count, min, max, mean and approximate distinct count of every column of `table_name`.
+    pub fn describe(&self, table_name: &str) -> impl Future<Item=Result<Vec<ColumnDescription>, QueryError>, Error=oneshot::Canceled> {
+        let inner = self.inner_locustdb.clone();
+        let table_name = table_name.to_string();
+        let (task, receiver) = Task::from_fn(move || inner.describe(&table_name));
+        self.schedule(task);
+        receiver
+    }
+
+    /// Makes column `old` of `table_name` available under the name `new`, without rewriting the stored data.
+    /// Queries can still refer to the column by its old name, but a warning is logged when they do.
+    pub fn rename_column(&self, table_name: &str, old: &str, new: &str) -> Result<(), QueryError> {
+        self.inner_locustdb.rename_column(table_name, old, new)
+    }
+
+    /// Sets `column` to `value` in all rows of `table_name` that satisfy `filter` and returns the number of updated rows.
+    /// `filter` is an SQL expression, e.g. `"id > 4"`, and `value` an SQL literal, e.g. `"'abc'"` or `"NULL"`.
+    pub fn update(&self, table_name: &str, filter: &str, column: &str, value: &str) -> impl Future<Item=Result<usize, QueryError>, Error=oneshot::Canceled> {
+        let inner = self.inner_locustdb.clone();
+        let table_name = table_name.to_string();
+        let filter = filter.to_string();
+        let column = column.to_string();
+        let value = value.to_string();
+        let (task, receiver) = Task::from_fn(move || {
+            let (filter, value) = parser::parse_update(&table_name, &filter, &value)?;
+            inner.update(&table_name, &filter, &column, &value)
+        });
+        self.schedule(task);
+        receiver
+    }
+
+    /// Merges partitions with fewer than `threshold` rows and returns the number of partitions that were merged.
+    pub fn compact(&self, threshold: usize) -> impl Future<Item=Result<usize, QueryError>, Error=oneshot::Canceled> {
+        let inner = self.inner_locustdb.clone();
+        let (task, receiver) = Task::from_fn(move || inner.compact(threshold));
+        self.schedule(task);
+        receiver
+    }
+
+    /// Copies all tables of `other` into this database and returns the number of rows that were added.
+    /// Tables that already exist keep their partitions, the imported partitions are appended to them.
+    pub fn import_from(&self, other: &LocustDB) -> impl Future<Item=Result<usize, QueryError>, Error=oneshot::Canceled> {
+        let inner = self.inner_locustdb.clone();
+        let other = other.inner_locustdb.clone();
+        let (task, receiver) = Task::from_fn(move || inner.import_from(&other));
+        self.schedule(task);
+        receiver
+    }
+
     fn schedule<T: Task + 'static>(&self, task: T) -> impl Future<Item=Trace, Error=oneshot::Canceled> {
         self.inner_locustdb.schedule(task)
     }
@@ -165,6 +409,17 @@ impl LocustDB {
     pub fn persistent_storage(_: &str) -> Arc<DiskStore> {
         panic!("RocksDB storage backend is not enabled in this build of LocustDB. Create db with `memory_only`, or set the `enable_rocksdb` feature.")
     }
+
+    #[cfg(feature = "enable_mmap")]
+    pub fn mmap_storage(db_path: &str) -> Arc<DiskStore> {
+        use disk_store::mmap;
+        Arc::new(mmap::MmapColumnStore::new(db_path))
+    }
+
+    #[cfg(not(feature = "enable_mmap"))]
+    pub fn mmap_storage(_: &str) -> Arc<DiskStore> {
+        panic!("Memory-mapped storage backend is not enabled in this build of LocustDB. Set the `enable_mmap` feature.")
+    }
 }
 
 impl Drop for LocustDB {
@@ -173,6 +428,12 @@ impl Drop for LocustDB {
     }
 }
 
+/// Limit for a query whose result is merged with the results of other queries before applying `limit`.
+/// Results are merged in order, so no query has to return more than the rows up to the end of `limit`.
+fn combined_result_limit(limit: &LimitClause) -> LimitClause {
+    LimitClause { limit: cmp::max(limit.limit.saturating_add(limit.offset), 1), offset: 0 }
+}
+
 #[derive(Clone)]
 pub struct Options {
     pub threads: usize,
@@ -182,6 +443,18 @@ pub struct Options {
     pub mem_lz4: bool,
     pub readahead: usize,
     pub seq_disk_read: bool,
+    /// Behavior of `SUM` aggregations whose result does not fit into an i64.
+    pub sum_overflow: OverflowPolicy,
+    /// Maximum number of bytes of intermediate buffers a query may allocate per partition.
+    pub query_memory_limit: Option<usize>,
+    /// Number of rows that queries process at a time unless the query sets its own batch size.
+    pub query_batch_size: Option<usize>,
+    /// Maximum time a query may spend processing partitions before a partial result is returned.
+    pub query_timeout: Option<Duration>,
+    /// Partitions with fewer rows are periodically merged in the background, disabled if `None`.
+    pub compaction_threshold: Option<usize>,
+    /// Store columns in memory-mapped files under `db_path` instead of RocksDB.
+    pub mmap_storage: bool,
 }
 
 impl Default for Options {
@@ -194,6 +467,12 @@ impl Default for Options {
             mem_lz4: true,
             readahead: 256 * 1024 * 1024, // 256 MiB
             seq_disk_read: false,
+            sum_overflow: OverflowPolicy::Error,
+            query_memory_limit: None,
+            query_batch_size: None,
+            query_timeout: None,
+            compaction_threshold: None,
+            mmap_storage: false,
         }
     }
 }
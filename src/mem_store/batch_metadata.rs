@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use mem_store::column::DataSource;
+use mem_store::zone_map::ZoneMap;
+
+/// Number of rows, ingest time and bounds of the columns of a batch.
+/// Partitions retain the bounds of evicted columns, which allows queries to skip batches without reading them from disk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchMetadata {
+    pub len: usize,
+    pub ingested_at: SystemTime,
+    pub columns: HashMap<String, ColumnBounds>,
+}
+
+/// Decoded min/max value and number of nulls of a column, `None` if not known.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ColumnBounds {
+    pub range: Option<(i64, i64)>,
+    pub null_count: Option<usize>,
+}
+
+impl ColumnBounds {
+    pub fn of<T: DataSource + ?Sized>(column: &T) -> ColumnBounds {
+        ColumnBounds {
+            range: column.value_range(),
+            null_count: column.null_count(),
+        }
+    }
+}
+
+impl BatchMetadata {
+    /// Returns true if every row of the batch is null in `column`.
+    pub fn all_null(&self, column: &str) -> bool {
+        self.columns.get(column).map_or(false, |bounds| bounds.null_count == Some(self.len))
+    }
+
+    /// Returns true if no row of the batch is null in `column`.
+    pub fn no_nulls(&self, column: &str) -> bool {
+        self.columns.get(column).map_or(false, |bounds| bounds.null_count == Some(0))
+    }
+
+    pub fn zone_map(&self) -> ZoneMap {
+        ZoneMap::from_ranges(self.columns.iter()
+            .filter_map(|(name, bounds)| bounds.range.map(|range| (name.to_string(), range)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+    use syntax::expression::Func2Type;
+
+    #[test]
+    fn test_batch_metadata() {
+        let mut columns = HashMap::new();
+        columns.insert("a".to_string(), ColumnBounds { range: Some((-10, 20)), null_count: Some(0) });
+        columns.insert("b".to_string(), ColumnBounds { range: None, null_count: Some(5) });
+        let metadata = BatchMetadata { len: 5, ingested_at: UNIX_EPOCH, columns };
+        assert!(metadata.no_nulls("a"));
+        assert!(metadata.all_null("b"));
+        assert!(!metadata.all_null("a"));
+        assert!(!metadata.no_nulls("d"));
+        assert!(metadata.zone_map().excludes("a", Func2Type::GT, 20));
+        assert!(!metadata.zone_map().excludes("b", Func2Type::GT, 20));
+    }
+}
@@ -0,0 +1,161 @@
+use std::cmp;
+use std::collections::HashSet;
+use std::hash::Hasher;
+use std::mem;
+
+use seahash::SeaHasher;
+
+use ingest::raw_val::RawVal;
+
+const BITS_PER_ELEMENT: usize = 8;
+const NUM_HASHES: u32 = 5;
+/// Bloom indices are only built for columns with at most one distinct value per `MAX_DISTINCT_RATIO` rows. Columns
+/// with more distinct values would need an index of similar size as the column itself.
+pub const MAX_DISTINCT_RATIO: usize = 2;
+
+/// Probabilistic set of the values contained in a column, used to skip partitions during equality filters.
+/// May return false positives, but never false negatives.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BloomIndex {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BloomIndex {
+    pub fn with_capacity(elements: usize) -> BloomIndex {
+        let words = cmp::max(1, (elements * BITS_PER_ELEMENT + 63) / 64);
+        BloomIndex {
+            bits: vec![0; words],
+            hashes: NUM_HASHES,
+        }
+    }
+
+    pub fn from_raw_parts(bits: Vec<u64>, hashes: u32) -> BloomIndex {
+        BloomIndex { bits, hashes }
+    }
+
+    /// `distinct` is an upper bound on the number of distinct values and determines the size of the index.
+    pub fn from_ints(values: &[i64], distinct: usize) -> BloomIndex {
+        let mut bloom = BloomIndex::with_capacity(cmp::min(values.len(), distinct));
+        for &value in values {
+            bloom.insert_int(value);
+        }
+        bloom
+    }
+
+    /// Returns `None` if `values` contain more than `values.len() / MAX_DISTINCT_RATIO` distinct values.
+    /// `distinct` is an upper bound on the number of distinct values, values are only counted if it exceeds the limit.
+    pub fn from_selective_ints(values: &[i64], distinct: u64) -> Option<BloomIndex> {
+        let max_distinct = values.len() / MAX_DISTINCT_RATIO;
+        let distinct = if distinct <= max_distinct as u64 {
+            distinct as usize
+        } else {
+            let mut unique = HashSet::new();
+            for &value in values {
+                if unique.insert(value) && unique.len() > max_distinct {
+                    return None;
+                }
+            }
+            unique.len()
+        };
+        Some(BloomIndex::from_ints(values, distinct))
+    }
+
+    pub fn from_strs<'a, T: Iterator<Item=&'a str>>(values: T, distinct: usize) -> BloomIndex {
+        let mut bloom = BloomIndex::with_capacity(distinct);
+        for value in values {
+            bloom.insert_str(value);
+        }
+        bloom
+    }
+
+    pub fn insert_int(&mut self, value: i64) {
+        self.insert(hash_int(value));
+    }
+
+    pub fn insert_str(&mut self, value: &str) {
+        self.insert(hash_str(value));
+    }
+
+    pub fn may_contain(&self, value: &RawVal) -> bool {
+        match value {
+            RawVal::Int(i) => self.contains(hash_int(*i)),
+            RawVal::Str(s) => self.contains(hash_str(s)),
+            RawVal::Null => true,
+        }
+    }
+
+    pub fn bits(&self) -> &[u64] { &self.bits }
+    pub fn hashes(&self) -> u32 { self.hashes }
+
+    pub fn heap_size_of_children(&self) -> usize {
+        self.bits.capacity() * mem::size_of::<u64>()
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for bit in self.bit_positions(hash) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.bit_positions(hash).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn bit_positions(&self, hash: u64) -> impl Iterator<Item=usize> {
+        // Double hashing, derives all bit positions from the two halves of a single hash
+        let h1 = hash & 0xffff_ffff;
+        let h2 = (hash >> 32) | 1;
+        let len = self.bits.len() as u64 * 64;
+        (0..u64::from(self.hashes)).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+fn hash_int(value: i64) -> u64 {
+    let mut hasher = SeaHasher::new();
+    hasher.write_i64(value);
+    hasher.finish()
+}
+
+fn hash_str(value: &str) -> u64 {
+    let mut hasher = SeaHasher::new();
+    hasher.write(value.as_bytes());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives() {
+        let values = (0..1000).map(|i| i * 7 - 300).collect::<Vec<i64>>();
+        let bloom = BloomIndex::from_ints(&values, values.len());
+        for &v in &values {
+            assert!(bloom.may_contain(&RawVal::Int(v)));
+        }
+
+        let strings = vec!["aa", "bb", "cc", ""];
+        let bloom = BloomIndex::from_strs(strings.iter().cloned(), strings.len());
+        for s in strings {
+            assert!(bloom.may_contain(&RawVal::Str(s.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_selective_ints() {
+        let values = (0..1000).map(|i| i % 100 * 1_000_000).collect::<Vec<i64>>();
+        let bloom = BloomIndex::from_selective_ints(&values, u64::max_value()).unwrap();
+        assert_eq!(bloom, BloomIndex::from_ints(&values, 100));
+        assert!(BloomIndex::from_selective_ints(&(0..1000).collect::<Vec<_>>(), 1000).is_none());
+        assert!(BloomIndex::from_selective_ints(&[], 0).is_some());
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let values = (0..10_000).collect::<Vec<i64>>();
+        let bloom = BloomIndex::from_ints(&values, values.len());
+        let false_positives = (10_000..20_000).filter(|&v| bloom.may_contain(&RawVal::Int(v))).count();
+        assert!(false_positives < 500, "false_positives = {}", false_positives);
+    }
+}
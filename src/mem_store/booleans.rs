@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use bitvec::BitVecMut;
+use mem_store::*;
+
+pub struct BooleanColumn;
+
+impl BooleanColumn {
+    /// Stores `values` as a bit vector, which takes one bit per row.
+    pub fn new_boxed(name: &str, values: &[bool]) -> Arc<Column> {
+        let mut bits = vec![0u8; (values.len() + 7) / 8];
+        for (i, &value) in values.iter().enumerate() {
+            if value {
+                bits.set(i);
+            }
+        }
+        BooleanColumn::from_bits(name, values.len(), bits)
+    }
+
+    /// Column of `len` booleans that are true where the corresponding bit of `bits` is set.
+    pub fn from_bits(name: &str, len: usize, bits: Vec<u8>) -> Arc<Column> {
+        Arc::new(Column::new(
+            name,
+            len,
+            None,
+            vec![CodecOp::UnpackBits(len)],
+            vec![DataSection::U8(bits)]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::data_types::BasicType;
+    use ingest::raw_val::RawVal;
+
+    #[test]
+    fn test_round_trip() {
+        for &len in &[1, 7, 8, 9, 64, 131] {
+            let values = (0..len).map(|i| i % 3 == 0 || i % 7 == 5).collect::<Vec<_>>();
+            let column = BooleanColumn::new_boxed("flag", &values);
+            assert_eq!(column.basic_type(), BasicType::Boolean);
+            assert_eq!(column.data()[0].len(), (len + 7) / 8);
+            let decoded = column.decode().unwrap();
+            let expected = values.iter().map(|&v| RawVal::Int(v as i64)).collect::<Vec<_>>();
+            assert_eq!(decoded, expected);
+        }
+    }
+}
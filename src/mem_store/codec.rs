@@ -1,6 +1,7 @@
 use engine::*;
 use engine::data_types::*;
 use engine::planning::QueryPlanner;
+use errors::QueryError;
 
 #[derive(Debug, Clone)]
 pub struct Codec {
@@ -11,6 +12,10 @@ pub struct Codec {
     is_summation_preserving: bool,
     is_order_preserving: bool,
     is_fixed_width: bool,
+    /// Replaces the dictionary sections of the column with a dictionary computed by the query, see `dict_fold_case`.
+    dictionary: Option<(BufferRef<u64>, BufferRef<u8>)>,
+    /// Smallest and largest frame minimum of a `FrameOfReference` column, used to bound the decoded values.
+    frame_minimums_range: Option<(i64, i64)>,
 }
 
 impl Codec {
@@ -27,6 +32,8 @@ impl Codec {
             is_summation_preserving,
             is_order_preserving,
             is_fixed_width,
+            dictionary: None,
+            frame_minimums_range: None,
         }
     }
 
@@ -39,6 +46,8 @@ impl Codec {
             is_summation_preserving: true,
             is_order_preserving: true,
             is_fixed_width: true,
+            dictionary: None,
+            frame_minimums_range: None,
         }
     }
 
@@ -67,11 +76,22 @@ impl Codec {
             is_summation_preserving,
             is_order_preserving,
             is_fixed_width,
+            dictionary: None,
+            frame_minimums_range: None,
         }
     }
 
     pub fn with_lz4(&self, decoded_length: usize) -> Codec {
-        let mut ops = vec![CodecOp::LZ4(self.section_types[0], decoded_length)];
+        self.with_compression(CodecOp::LZ4(self.section_types[0], decoded_length))
+    }
+
+    pub fn with_zstd(&self, decoded_length: usize) -> Codec {
+        self.with_compression(CodecOp::Zstd(self.section_types[0], decoded_length))
+    }
+
+    /// Prepends `compression`, which decompresses the first data section into the section type of `self`.
+    fn with_compression(&self, compression: CodecOp) -> Codec {
+        let mut ops = vec![compression];
         for &op in &self.ops {
             ops.push(op);
         }
@@ -79,6 +99,7 @@ impl Codec {
         section_types[0] = EncodingType::U8;
         let mut codec = Codec::new(ops, section_types);
         codec.set_column_name(&self.column_name);
+        codec.frame_minimums_range = self.frame_minimums_range;
         codec
     }
 
@@ -102,15 +123,46 @@ impl Codec {
             Codec::new(ops, section_types)
         };
         codec.set_column_name(&self.column_name);
+        codec.frame_minimums_range = self.frame_minimums_range;
         codec
     }
 
     pub fn decode(&self,
                   plan: TypedBufferRef,
                   planner: &mut QueryPlanner) -> TypedBufferRef {
+        if !self.ops.is_empty() {
+            planner.record_decode(&self.column_name);
+        }
         self.decode_ops(&self.ops, plan, planner)
     }
 
+    /// Converts the range of the encoded values into a range of decoded values.
+    /// Returns `None` for non-integer columns and codecs that don't map ranges to ranges.
+    /// The range of a frame of reference column is widened by the range of its frame minimums, so its upper bound is not tight.
+    pub fn decode_range(&self, range: Option<(i64, i64)>) -> Option<(i64, i64)> {
+        if self.decoded_type != BasicType::Integer && self.decoded_type != BasicType::NullableInteger {
+            return None;
+        }
+        let (mut min, mut max) = range?;
+        for op in &self.ops {
+            match *op {
+                CodecOp::Add(_, x) => {
+                    min = min.checked_add(x)?;
+                    max = max.checked_add(x)?;
+                }
+                CodecOp::FrameOfReference(_, _) => {
+                    let (lowest, highest) = self.frame_minimums_range?;
+                    min = min.checked_add(lowest)?;
+                    max = max.checked_add(highest)?;
+                }
+                CodecOp::ToI64(_) | CodecOp::PushDataSection(_) | CodecOp::Nullable | CodecOp::LZ4(_, _) | CodecOp::Zstd(_, _) |
+                CodecOp::UnpackInts(_, _) | CodecOp::RunLength(_, _) => {}
+                _ => return None,
+            }
+        }
+        Some((min, max))
+    }
+
     fn decode_ops(&self,
                   ops: &[CodecOp],
                   plan: TypedBufferRef,
@@ -129,13 +181,26 @@ impl Codec {
                     planner.add(lhs, rhs).into()
                 }
                 CodecOp::Delta(_) => planner.delta_decode(stack.pop().unwrap()).into(),
+                CodecOp::FrameOfReference(_, frame_size) => {
+                    let frame_minimums = stack.pop().unwrap().i64().unwrap();
+                    let encoded = stack.pop().unwrap();
+                    planner.frame_of_reference_decode(encoded, frame_minimums, frame_size).into()
+                }
+                CodecOp::RunLength(_, len) => {
+                    let run_lengths = stack.pop().unwrap().u32().unwrap();
+                    let values = stack.pop().unwrap();
+                    planner.run_length_decode(values, run_lengths, len)
+                }
                 CodecOp::ToI64(_) => planner.cast(stack.pop().unwrap(), EncodingType::I64),
-                CodecOp::PushDataSection(section_index) =>
-                    planner.column_section(
+                CodecOp::PushDataSection(section_index) => match (self.dictionary, section_index) {
+                    (Some((offset_len, _)), 1) => offset_len.into(),
+                    (Some((_, backing_store)), 2) => backing_store.into(),
+                    _ => planner.column_section(
                         &self.column_name,
                         section_index,
                         None,
                         self.section_types[section_index]),
+                },
                 CodecOp::DictLookup(_t) => {
                     let dict_data = stack.pop().unwrap();
                     let dict_indices = stack.pop().unwrap();
@@ -144,6 +209,10 @@ impl Codec {
                 }
                 CodecOp::LZ4(t, decoded_length) =>
                     planner.lz4_decode(stack.pop().unwrap().u8().unwrap(), decoded_length, t),
+                CodecOp::Zstd(t, decoded_length) =>
+                    planner.zstd_decode(stack.pop().unwrap().u8().unwrap(), decoded_length, t),
+                CodecOp::UnpackBits(len) => planner.unpack_bits(stack.pop().unwrap().u8().unwrap(), len).into(),
+                CodecOp::UnpackInts(bits, len) => planner.unpack_ints(stack.pop().unwrap().u8().unwrap(), bits, len).into(),
                 CodecOp::UnpackStrings => planner.unpack_strings(stack.pop().unwrap().u8().unwrap()).into(),
                 CodecOp::UnhexpackStrings(upper, total_bytes) =>
                     planner.unhexpack_strings(stack.pop().unwrap().u8().unwrap(), upper, total_bytes).into(),
@@ -165,6 +234,7 @@ impl Codec {
             Codec::new(rest, self.section_types.clone())
         };
         new_codec.set_column_name(&self.column_name);
+        new_codec.frame_minimums_range = self.frame_minimums_range;
         (new_codec, self.decode_ops(&fixed_width, plan, planner))
     }
 
@@ -179,29 +249,117 @@ impl Codec {
 
     pub fn encode_str(&self,
                       string_const: BufferRef<Scalar<&'static str>>,
-                      planner: &mut QueryPlanner) -> BufferRef<Scalar<i64>> {
+                      planner: &mut QueryPlanner) -> Result<BufferRef<Scalar<i64>>, QueryError> {
         match self.ops[..] {
             [CodecOp::PushDataSection(1), CodecOp::PushDataSection(2), CodecOp::DictLookup(_)] => {
-                let offset_len = planner.column_section(&self.column_name, 1, None, EncodingType::U64).u64().unwrap();
-                let backing_store = planner.column_section(&self.column_name, 2, None, EncodingType::U8).u8().unwrap();
-                planner.inverse_dict_lookup(offset_len, backing_store, string_const)
+                let (offset_len, backing_store) = self.dictionary_sections(planner)?;
+                Ok(planner.inverse_dict_lookup(offset_len, backing_store, string_const))
             }
-            _ => panic!("encode_str not supported for {:?}", &self.ops),
+            _ => bail!(QueryError::NotImplemented, "encode_str for {:?}", &self.ops),
         }
     }
 
-    pub fn encode_int(&self, x: i64) -> i64 {
-        if let CodecOp::Add(_, y) = self.ops[0] {
-            assert_eq!(self.ops.len(), 1);
-            x - y
-        } else if let CodecOp::ToI64(_) = self.ops[0] {
-            assert_eq!(self.ops.len(), 1);
-            x
-        } else {
-            panic!("encode_int not supported for {:?}", &self.ops)
+    /// Encodes a string constant for use in range comparisons with dictionary indices.
+    /// Since dictionaries are sorted, comparing indices is equivalent to comparing strings as long as constants
+    /// that are not part of the dictionary are mapped to the adjacent larger index (or smaller if `round_down`).
+    pub fn encode_str_bound(&self,
+                            string_const: BufferRef<Scalar<&'static str>>,
+                            round_down: bool,
+                            planner: &mut QueryPlanner) -> Result<BufferRef<Scalar<i64>>, QueryError> {
+        match self.ops[..] {
+            [CodecOp::PushDataSection(1), CodecOp::PushDataSection(2), CodecOp::DictLookup(_)] => {
+                let (offset_len, backing_store) = self.dictionary_sections(planner)?;
+                Ok(planner.dict_rank(offset_len, backing_store, string_const, round_down))
+            }
+            _ => bail!(QueryError::NotImplemented, "encode_str_bound for {:?}", &self.ops),
+        }
+    }
+
+    /// For dictionary encoded strings, returns whether the string corresponding to each of the `indices` matches `regex`.
+    /// Returns `None` for all other codecs.
+    pub fn dict_regex(&self,
+                      indices: TypedBufferRef,
+                      regex: &str,
+                      planner: &mut QueryPlanner) -> Option<BufferRef<u8>> {
+        match self.ops[..] {
+            [CodecOp::PushDataSection(1), CodecOp::PushDataSection(2), CodecOp::DictLookup(_)] => {
+                let (offset_len, backing_store) = self.dictionary_sections(planner).unwrap();
+                let dict_matches = planner.dict_regex(offset_len, backing_store, regex);
+                Some(planner.dict_match_lookup(indices, dict_matches))
+            }
+            _ => None,
+        }
+    }
+
+    /// For dictionary encoded strings, converts each dictionary entry to lowercase (or uppercase if `upper` is set)
+    /// and maps `indices` to the converted dictionary, in which entries that convert to the same string are merged.
+    /// Returns the mapped indices and the codec that decodes them, or `None` for all other codecs.
+    pub fn dict_fold_case(&self,
+                          indices: TypedBufferRef,
+                          upper: bool,
+                          planner: &mut QueryPlanner) -> Option<(TypedBufferRef, Codec)> {
+        match self.ops[..] {
+            [CodecOp::PushDataSection(1), CodecOp::PushDataSection(2), CodecOp::DictLookup(_)] => {
+                let (offset_len, backing_store) = self.dictionary_sections(planner).unwrap();
+                let (folded_offset_len, folded_backing_store, remap) =
+                    planner.dict_case_fold(offset_len, backing_store, upper);
+                let mut codec = self.clone();
+                codec.dictionary = Some((folded_offset_len, folded_backing_store));
+                Some((planner.dict_remap(indices, remap), codec))
+            }
+            _ => None,
+        }
+    }
+
+    fn dictionary_sections(&self, planner: &mut QueryPlanner) -> Result<(BufferRef<u64>, BufferRef<u8>), QueryError> {
+        match self.dictionary {
+            Some(dictionary) => Ok(dictionary),
+            None => Ok((planner.column_section(&self.column_name, 1, None, EncodingType::U64).u64()?,
+                        planner.column_section(&self.column_name, 2, None, EncodingType::U8).u8()?)),
         }
     }
 
+    /// Fails with `QueryError::Overflow` if the encoded value of `x` does not fit into an i64.
+    pub fn encode_int(&self, x: i64) -> Result<i64, QueryError> {
+        match self.ops[..] {
+            [CodecOp::Add(_, y)] => x.checked_sub(y).ok_or(QueryError::Overflow),
+            [CodecOp::ToI64(_)] => Ok(x),
+            _ => bail!(QueryError::NotImplemented, "encode_int for {:?}", &self.ops),
+        }
+    }
+
+    /// Returns `offset` if values are decoded by adding `offset` to each encoded value.
+    pub fn additive_offset(&self) -> Option<i64> {
+        match self.ops[..] {
+            [CodecOp::Add(_, offset)] => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the data section that holds the bitmap of non-null rows, if the column is nullable.
+    pub fn null_map_section(&self) -> Option<usize> {
+        self.ops.windows(2)
+            .filter_map(|ops| match *ops {
+                [CodecOp::PushDataSection(section), CodecOp::Nullable] => Some(section),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Returns the index of the data section that holds the frame minimums, if the column is frame of reference encoded.
+    pub fn frame_minimums_section(&self) -> Option<usize> {
+        self.ops.windows(2)
+            .filter_map(|ops| match *ops {
+                [CodecOp::PushDataSection(section), CodecOp::FrameOfReference(_, _)] => Some(section),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub(in mem_store) fn set_frame_minimums_range(&mut self, range: Option<(i64, i64)>) {
+        self.frame_minimums_range = range;
+    }
+
     pub(in mem_store) fn set_column_name(&mut self, name: &str) {
         self.column_name = name.to_string();
     }
@@ -270,10 +428,22 @@ pub enum CodecOp {
     Nullable,
     Add(EncodingType, i64),
     Delta(EncodingType),
+    /// Adds the minimum of each frame of the given number of elements, which are stored in the next data section.
+    FrameOfReference(EncodingType, usize),
+    /// Repeats each value the number of times given by the corresponding element of the next data section, which
+    /// yields the given number of elements in total.
+    RunLength(EncodingType, usize),
     ToI64(EncodingType),
     PushDataSection(usize),
     DictLookup(EncodingType),
     LZ4(EncodingType, usize),
+    /// Zstd compressed data section that decodes into the given number of elements of the given type.
+    Zstd(EncodingType, usize),
+    /// Unpacks a bit vector with the given number of elements into one byte per element.
+    UnpackBits(usize),
+    /// Unpacks the given number of integers that are stored with the given number of bits each into one byte per
+    /// integer.
+    UnpackInts(usize, usize),
     UnpackStrings,
     UnhexpackStrings(bool, usize),
     Unknown,
@@ -294,6 +464,15 @@ impl CodecOp {
                     } else {
                         EncodingType::I64
                     },
+                CodecOp::FrameOfReference(_, _) => {
+                    type_stack.pop();
+                    type_stack.pop();
+                    EncodingType::I64
+                }
+                CodecOp::RunLength(_, _) => {
+                    type_stack.pop();
+                    type_stack.pop().unwrap()
+                }
                 CodecOp::DictLookup(_) => {
                     type_stack.pop();
                     type_stack.pop();
@@ -303,7 +482,8 @@ impl CodecOp {
                         EncodingType::Str
                     }
                 }
-                CodecOp::LZ4(t, _) => *t,
+                CodecOp::LZ4(t, _) | CodecOp::Zstd(t, _) => *t,
+                CodecOp::UnpackBits(_) | CodecOp::UnpackInts(_, _) => EncodingType::U8,
                 CodecOp::UnpackStrings => EncodingType::Str,
                 CodecOp::UnhexpackStrings(_, _) => EncodingType::Str,
                 CodecOp::PushDataSection(i) => section_types[*i],
@@ -319,10 +499,15 @@ impl CodecOp {
             CodecOp::Nullable => false,
             CodecOp::Add(_, x) => *x == 0,
             CodecOp::Delta(_) => false,
+            CodecOp::FrameOfReference(_, _) => false,
+            CodecOp::RunLength(_, _) => false,
             CodecOp::ToI64(_) => true,
             CodecOp::PushDataSection(_) => true,
             CodecOp::DictLookup(_) => false,
             CodecOp::LZ4(_, _) => false,
+            CodecOp::Zstd(_, _) => false,
+            CodecOp::UnpackBits(_) => false,
+            CodecOp::UnpackInts(_, _) => false,
             CodecOp::UnpackStrings => false,
             CodecOp::UnhexpackStrings(_, _) => false,
             CodecOp::Unknown => panic!("Unknown.is_summation_preserving()"),
@@ -334,10 +519,15 @@ impl CodecOp {
             CodecOp::Nullable => false,
             CodecOp::Add(_, _) => true,
             CodecOp::Delta(_) => false,
+            CodecOp::FrameOfReference(_, _) => false,
+            CodecOp::RunLength(_, _) => false,
             CodecOp::ToI64(_) => true,
             CodecOp::PushDataSection(_) => true,
             CodecOp::DictLookup(_) => true,
             CodecOp::LZ4(_, _) => false,
+            CodecOp::Zstd(_, _) => false,
+            CodecOp::UnpackBits(_) => false,
+            CodecOp::UnpackInts(_, _) => false,
             CodecOp::UnpackStrings => false,
             CodecOp::UnhexpackStrings(_, _) => false,
             CodecOp::Unknown => panic!("Unknown.is_order_preserving()"),
@@ -349,10 +539,15 @@ impl CodecOp {
             CodecOp::Nullable => false,
             CodecOp::Add(_, _) => true,
             CodecOp::Delta(_) => false,
+            CodecOp::FrameOfReference(_, _) => false,
+            CodecOp::RunLength(_, _) => false,
             CodecOp::ToI64(_) => true,
             CodecOp::PushDataSection(_) => true,
             CodecOp::DictLookup(_) => true,
             CodecOp::LZ4(_, _) => false,
+            CodecOp::Zstd(_, _) => false,
+            CodecOp::UnpackBits(_) => false,
+            CodecOp::UnpackInts(_, _) => false,
             CodecOp::UnpackStrings => false,
             CodecOp::UnhexpackStrings(_, _) => false,
             CodecOp::Unknown => panic!("Unknown.is_fixed_width()"),
@@ -364,10 +559,15 @@ impl CodecOp {
             CodecOp::Nullable => 0,
             CodecOp::Add(_, _) => 1,
             CodecOp::Delta(_) => 1,
+            CodecOp::FrameOfReference(_, _) => 2,
+            CodecOp::RunLength(_, _) => 2,
             CodecOp::ToI64(_) => 1,
             CodecOp::PushDataSection(_) => 0,
             CodecOp::DictLookup(_) => 3,
             CodecOp::LZ4(_, _) => 1,
+            CodecOp::Zstd(_, _) => 1,
+            CodecOp::UnpackBits(_) => 1,
+            CodecOp::UnpackInts(_, _) => 1,
             CodecOp::UnpackStrings => 1,
             CodecOp::UnhexpackStrings(_, _) => 1,
             CodecOp::Unknown => panic!("Unknown.is_fixed_width()"),
@@ -383,6 +583,12 @@ impl CodecOp {
                 format!("Add({:?})", t)
             }
             CodecOp::Delta(t) => format!("Delta({:?})", t),
+            CodecOp::FrameOfReference(t, frame_size) => if alternate {
+                format!("FOR({:?}, {})", t, frame_size)
+            } else {
+                format!("FOR({:?})", t)
+            }
+            CodecOp::RunLength(t, _) => format!("RLE({:?})", t),
             CodecOp::ToI64(t) => format!("ToI64({:?})", t),
             CodecOp::PushDataSection(i) => format!("Data({})", i),
             CodecOp::DictLookup(t) => format!("Dict({:?})", t),
@@ -391,6 +597,13 @@ impl CodecOp {
             } else {
                 format!("LZ4({:?})", t)
             }
+            CodecOp::Zstd(t, decoded_len) => if alternate {
+                format!("Zstd({:?}, {})", t, decoded_len)
+            } else {
+                format!("Zstd({:?})", t)
+            }
+            CodecOp::UnpackBits(_) => "UnpackBits".to_string(),
+            CodecOp::UnpackInts(bits, _) => format!("UnpackInts({})", bits),
             CodecOp::UnpackStrings => "StrUnpack".to_string(),
             CodecOp::UnhexpackStrings(_, _) => "StrHexUnpack".to_string(),
             CodecOp::Unknown => "Unknown".to_string(),
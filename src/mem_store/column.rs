@@ -1,10 +1,22 @@
+use std::cmp;
+use std::collections::HashMap;
 use std::fmt;
 use std::mem;
+use std::slice;
 use std::sync::Arc;
 
 use mem_store::*;
+use mem_store::column_builder::rebuild_column;
+use mem_store::integers::IntegerColumn;
+use mem_store::column_stats::{Cardinality, ColumnStats, HistogramBucket, histogram_selectivity, range_selectivity};
 use engine::data_types::*;
+use engine::planning::QueryPlanner;
+use errors::QueryError;
+use hyperloglog::HyperLogLog;
+use ingest::raw_val::RawVal;
 use mem_store::lz4;
+use mem_store::zstd;
+use syntax::expression::Func2Type;
 
 pub struct Column {
     name: String,
@@ -12,6 +24,11 @@ pub struct Column {
     range: Option<(i64, i64)>,
     codec: Codec,
     data: Vec<DataSection>,
+    bloom: Option<BloomIndex>,
+    histogram: Option<Vec<HistogramBucket>>,
+    sketch: Option<HyperLogLog>,
+    null_count: Option<usize>,
+    sorted: bool,
 }
 
 pub trait DataSource: fmt::Debug + Sync + Send {
@@ -21,6 +38,14 @@ pub trait DataSource: fmt::Debug + Sync + Send {
     fn len(&self) -> usize;
     fn data_sections(&self) -> Vec<&Data>;
     fn full_type(&self) -> Type;
+    fn bloom_index(&self) -> Option<&BloomIndex> { None }
+    fn value_range(&self) -> Option<(i64, i64)> { None }
+    /// Estimated fraction of rows that satisfy `column <op> value`.
+    fn selectivity(&self, _op: Func2Type, _value: i64) -> Option<f64> { None }
+    fn approx_cardinality(&self) -> Option<Cardinality> { None }
+    fn null_count(&self) -> Option<usize> { None }
+    /// True if the column has no nulls and its values are in non-decreasing order.
+    fn is_sorted(&self) -> bool { false }
 }
 
 impl<T: DataSource> DataSource for Arc<T> {
@@ -30,6 +55,27 @@ impl<T: DataSource> DataSource for Arc<T> {
     fn len(&self) -> usize { (**self).len() }
     fn data_sections(&self) -> Vec<&Data> { (**self).data_sections() }
     fn full_type(&self) -> Type { (**self).full_type() }
+    fn bloom_index(&self) -> Option<&BloomIndex> { (**self).bloom_index() }
+    fn value_range(&self) -> Option<(i64, i64)> { (**self).value_range() }
+    fn selectivity(&self, op: Func2Type, value: i64) -> Option<f64> { (**self).selectivity(op, value) }
+    fn approx_cardinality(&self) -> Option<Cardinality> { (**self).approx_cardinality() }
+    fn null_count(&self) -> Option<usize> { (**self).null_count() }
+    fn is_sorted(&self) -> bool { (**self).is_sorted() }
+}
+
+impl<'b, T: DataSource> DataSource for &'b T {
+    fn encoding_type(&self) -> EncodingType { (**self).encoding_type() }
+    fn range(&self) -> Option<(i64, i64)> { (**self).range() }
+    fn codec(&self) -> Codec { (**self).codec() }
+    fn len(&self) -> usize { (**self).len() }
+    fn data_sections(&self) -> Vec<&Data> { (**self).data_sections() }
+    fn full_type(&self) -> Type { (**self).full_type() }
+    fn bloom_index(&self) -> Option<&BloomIndex> { (**self).bloom_index() }
+    fn value_range(&self) -> Option<(i64, i64)> { (**self).value_range() }
+    fn selectivity(&self, op: Func2Type, value: i64) -> Option<f64> { (**self).selectivity(op, value) }
+    fn approx_cardinality(&self) -> Option<Cardinality> { (**self).approx_cardinality() }
+    fn null_count(&self) -> Option<usize> { (**self).null_count() }
+    fn is_sorted(&self) -> bool { (**self).is_sorted() }
 }
 
 impl DataSource for Column {
@@ -45,6 +91,19 @@ impl DataSource for Column {
         }
     }
     fn full_type(&self) -> Type { Type::new(self.basic_type(), Some(self.codec())) }
+    fn bloom_index(&self) -> Option<&BloomIndex> { self.bloom.as_ref() }
+    /// Smallest and largest (decoded) value in the column, if known.
+    fn value_range(&self) -> Option<(i64, i64)> { self.codec.decode_range(self.range) }
+    /// Uses the histogram of the column if present and otherwise assumes that values are uniformly distributed over the range of the column.
+    fn selectivity(&self, op: Func2Type, value: i64) -> Option<f64> {
+        match self.histogram {
+            Some(ref histogram) => histogram_selectivity(histogram, self.len, op, value),
+            None => self.value_range().and_then(|range| range_selectivity(range, op, value)),
+        }
+    }
+    fn approx_cardinality(&self) -> Option<Cardinality> { Some(Column::approx_cardinality(self)) }
+    fn null_count(&self) -> Option<usize> { self.null_count }
+    fn is_sorted(&self) -> bool { self.sorted }
 }
 
 impl Column {
@@ -59,12 +118,21 @@ impl Column {
             Codec::new(codec, data.iter().map(DataSection::encoding_type).collect())
         };
         codec.set_column_name(name);
+        if let Some(section) = codec.frame_minimums_section() {
+            codec.set_frame_minimums_range(Column::frame_minimums_range(&data[section]));
+        }
+        let null_count = Column::count_nulls(len, &codec, &data);
         Column {
             name: name.to_string(),
             len,
             range,
             codec,
             data,
+            bloom: None,
+            histogram: None,
+            sketch: None,
+            null_count,
+            sorted: false,
         }
     }
 
@@ -75,6 +143,52 @@ impl Column {
             range: None,
             codec: Codec::identity(BasicType::Null),
             data: vec![DataSection::Null(len)],
+            bloom: None,
+            histogram: None,
+            sketch: None,
+            null_count: Some(len),
+            sorted: false,
+        }
+    }
+
+    /// Counts the rows that are not set in the null map, `None` if the null map is not stored as an uncompressed
+    /// bitmap.
+    fn count_nulls(len: usize, codec: &Codec, data: &[DataSection]) -> Option<usize> {
+        match codec.null_map_section() {
+            None => Some(0),
+            Some(section) => match data[section] {
+                DataSection::U8(ref present) => Some(len - present.iter().map(|byte| byte.count_ones() as usize).sum::<usize>()),
+                DataSection::Mapped(ref mapped) if mapped.encoding_type() == EncodingType::U8 =>
+                    Some(len - mapped.bytes().iter().map(|byte| byte.count_ones() as usize).sum::<usize>()),
+                _ => None,
+            },
+        }
+    }
+
+    fn frame_minimums_range(frame_minimums: &DataSection) -> Option<(i64, i64)> {
+        if frame_minimums.encoding_type() != EncodingType::I64 {
+            return None;
+        }
+        let frame_minimums = frame_minimums.to_any_vec().cast_ref_i64();
+        Some((*frame_minimums.iter().min()?, *frame_minimums.iter().max()?))
+    }
+
+    /// Compresses the first data section with LZ4, or with Zstd if only Zstd is enabled.
+    pub fn compress(&mut self) {
+        if cfg!(feature = "enable_lz4") {
+            self.lz4_encode();
+        } else {
+            self.zstd_encode();
+        }
+    }
+
+    pub fn zstd_encode(&mut self) {
+        if cfg!(feature = "enable_zstd") {
+            let (encoded, worth_it) = self.data[0].zstd_encode();
+            if worth_it {
+                self.codec = self.codec.with_zstd(self.data[0].len());
+                self.data[0] = encoded;
+            }
         }
     }
 
@@ -90,14 +204,142 @@ impl Column {
 
     #[cfg(feature = "enable_lz4")]
     pub fn lz4_decode(&mut self) {
-        if let Some(CodecOp::LZ4(decoded_type, _)) = self.codec.ops().get(0).map(|c| *c) {
+        if let Some(CodecOp::LZ4(decoded_type, decoded_len)) = self.codec.ops().get(0).map(|c| *c) {
             trace!("lz4_decode before: {:?}", self);
             self.codec = self.codec.without_lz4();
-            self.data[0] = self.data[0].lz4_decode(decoded_type, self.len);
+            self.data[0] = self.data[0].lz4_decode(decoded_type, decoded_len);
             trace!("lz4_decode after: {:?}", self);
         }
     }
 
+    pub fn set_bloom_index(&mut self, bloom: BloomIndex) {
+        self.bloom = Some(bloom);
+    }
+
+    /// Marks the column as sorted, which allows range filters to binary search for the matching rows.
+    /// Only valid if the decoded values are non-decreasing and none of them are null.
+    pub fn set_sorted(&mut self) {
+        self.sorted = true;
+    }
+
+    /// Sets an equal width histogram over the decoded values of the column.
+    pub fn set_histogram(&mut self, histogram: Vec<HistogramBucket>) {
+        self.histogram = Some(histogram);
+    }
+
+    pub fn histogram(&self) -> Option<&[HistogramBucket]> {
+        self.histogram.as_ref().map(|h| &h[..])
+    }
+
+    /// Sets a sketch of the distinct non-null values, only worthwhile for columns without dictionary or range.
+    pub fn set_sketch(&mut self, sketch: HyperLogLog) {
+        self.sketch = Some(sketch);
+    }
+
+    /// Number of distinct non-null values in the column.
+    /// Exact for dictionary encoded columns without nulls, bounded by the dictionary size or the width of the value
+    /// range for other dictionary and integer columns and estimated from the sketch where that gives a lower value.
+    /// The result never exceeds the length of the column.
+    pub fn approx_cardinality(&self) -> Cardinality {
+        match self.basic_type() {
+            BasicType::Null => return Cardinality::Exact(0),
+            BasicType::Boolean => return Cardinality::AtMost(cmp::min(self.len, 2)),
+            _ => {}
+        }
+        if self.codec.ops().iter().any(|op| match *op { CodecOp::DictLookup(_) => true, _ => false }) {
+            let dict_size = self.data[1].len();
+            // Null rows are stored as some placeholder string which may not occur elsewhere
+            return if self.basic_type() == BasicType::NullableString {
+                Cardinality::AtMost(dict_size)
+            } else {
+                Cardinality::Exact(dict_size)
+            };
+        }
+        let bound = match self.value_range() {
+            Some((min, max)) if self.basic_type() == BasicType::Integer || self.basic_type() == BasicType::NullableInteger =>
+                cmp::min(self.len as u64, (max as i128 - min as i128 + 1) as u64) as usize,
+            _ => self.len,
+        };
+        match self.sketch {
+            Some(ref sketch) if (sketch.estimate() as usize) < bound => Cardinality::Estimate(sketch.estimate() as usize),
+            _ => Cardinality::AtMost(bound),
+        }
+    }
+
+    /// Decodes all values in the column.
+    pub fn decode(&self) -> Result<Vec<RawVal>, QueryError> {
+        let mut values = Vec::with_capacity(self.len);
+        self.decode_into(&mut values)?;
+        Ok(values)
+    }
+
+    /// Decodes all values in the column into `out`, replacing its previous contents.
+    /// Reusing `out` across calls avoids allocating a new vector each time the column is decoded.
+    pub fn decode_into(&self, out: &mut Vec<RawVal>) -> Result<(), QueryError> {
+        out.clear();
+        if self.basic_type() == BasicType::Null {
+            out.resize(self.len, RawVal::Null);
+            return Ok(());
+        }
+        let mut planner = QueryPlanner::default();
+        let mut plan = planner.column_section(&self.name, 0, self.range, self.codec.encoding_type());
+        plan = self.codec.decode(plan, &mut planner);
+        // Booleans decode to bytes, which are returned as the integers 0 and 1 like in query results
+        if self.basic_type() == BasicType::Boolean {
+            plan = planner.cast(plan, EncodingType::I64);
+        }
+        if plan.is_nullable() {
+            plan = planner.fuse_nulls(plan);
+        }
+        let mut executor = planner.prepare(vec![])?;
+        let mut columns = HashMap::new();
+        columns.insert(self.name.clone(), self.data_sections());
+        let mut results = executor.prepare(columns);
+        executor.run(self.len, &mut results, false)?;
+        let (decoded, _, _, _) = results.collect_aliased(&[plan.any()], &[], &[]);
+        out.extend((0..self.len).map(|i| decoded[0].get_raw(i)));
+        Ok(())
+    }
+
+    /// Returns a column that contains the values of `self` followed by the values of `other`.
+    /// Columns with the same elementwise integer encoding are merged by concatenating their data, all other columns
+    /// are decoded and encoded again so that e.g. the integer width fits the combined range.
+    /// Bloom index and histogram are only retained if the column is encoded again.
+    pub fn merge(&self, other: &Column) -> Result<Column, QueryError> {
+        if self.basic_type() == BasicType::Null && other.basic_type() == BasicType::Null {
+            return Ok(Column::null(&self.name, self.len + other.len));
+        }
+        let concatenable = self.codec.ops() == other.codec.ops() && self.data.len() == 1 && other.data.len() == 1 &&
+            self.codec.ops().iter().all(|op| match *op {
+                CodecOp::Add(_, _) | CodecOp::ToI64(_) => true,
+                _ => false,
+            });
+        if concatenable {
+            if let Some(data) = self.data[0].concat(&other.data[0]) {
+                let range = match (self.range, other.range) {
+                    (Some((min1, max1)), Some((min2, max2))) => Some((cmp::min(min1, min2), cmp::max(max1, max2))),
+                    _ => None,
+                };
+                return Ok(Column::new(&self.name, self.len + other.len, range, self.codec.ops().to_vec(), vec![data]));
+            }
+        }
+        let mut values = self.decode()?;
+        values.extend(other.decode()?);
+        // `rebuild_column` returns the only reference to the new column
+        Ok(Arc::try_unwrap(rebuild_column(&self.name, &[self.basic_type(), other.basic_type()], values)).unwrap())
+    }
+
+    /// Builds a column of non-null integers with the most compact of the encodings in `IntegerEncoding`.
+    /// `IntegerColumn::choose_encoding` returns the encoding that is used for `values`.
+    pub fn build_integer(name: &str, values: Vec<i64>) -> Arc<Column> {
+        IntegerColumn::build(name, values)
+    }
+
+    /// Computes summary statistics and a histogram with (at most) `buckets` buckets.
+    pub fn stats(&self, buckets: usize) -> Result<ColumnStats, QueryError> {
+        ColumnStats::compute(self, buckets)
+    }
+
     pub fn name(&self) -> &str { &self.name }
     pub fn data(&self) -> &[DataSection] { &self.data }
     pub fn basic_type(&self) -> BasicType { self.codec.decoded_type() }
@@ -105,7 +347,10 @@ impl Column {
 
 
     pub fn heap_size_of_children(&self) -> usize {
-        self.data.iter().map(|section| section.heap_size_of_children()).sum()
+        self.data.iter().map(|section| section.heap_size_of_children()).sum::<usize>()
+            + self.bloom.as_ref().map_or(0, |bloom| bloom.heap_size_of_children())
+            + self.histogram.as_ref().map_or(0, |histogram| histogram.capacity() * mem::size_of::<HistogramBucket>())
+            + self.sketch.as_ref().map_or(0, |sketch| sketch.registers().len())
     }
 
     pub fn mem_tree(&self, tree: &mut MemTreeColumn, depth: usize) {
@@ -162,6 +407,7 @@ pub enum DataSection {
     U64(Vec<u64>),
     I64(Vec<i64>),
     Null(usize),
+    Mapped(MappedSection),
 }
 
 impl DataSection {
@@ -173,6 +419,7 @@ impl DataSection {
             DataSection::U64(ref x) => x,
             DataSection::I64(ref x) => x,
             DataSection::Null(ref x) => x,
+            DataSection::Mapped(ref x) => x.to_any_vec(),
         }
     }
 
@@ -184,6 +431,7 @@ impl DataSection {
             DataSection::U64(ref x) => x.len(),
             DataSection::I64(ref x) => x.len(),
             DataSection::Null(ref x) => *x,
+            DataSection::Mapped(ref x) => x.len(),
         }
     }
 
@@ -195,6 +443,20 @@ impl DataSection {
             DataSection::U64(ref x) => x.capacity(),
             DataSection::I64(ref x) => x.capacity(),
             DataSection::Null(ref x) => *x,
+            DataSection::Mapped(ref x) => x.len(),
+        }
+    }
+
+    /// Concatenates two owned sections of the same type, returns `None` if the types differ or either section is mapped.
+    pub fn concat(&self, other: &DataSection) -> Option<DataSection> {
+        match (self, other) {
+            (DataSection::U8(ref x), DataSection::U8(ref y)) => Some(DataSection::U8([&x[..], &y[..]].concat())),
+            (DataSection::U16(ref x), DataSection::U16(ref y)) => Some(DataSection::U16([&x[..], &y[..]].concat())),
+            (DataSection::U32(ref x), DataSection::U32(ref y)) => Some(DataSection::U32([&x[..], &y[..]].concat())),
+            (DataSection::U64(ref x), DataSection::U64(ref y)) => Some(DataSection::U64([&x[..], &y[..]].concat())),
+            (DataSection::I64(ref x), DataSection::I64(ref y)) => Some(DataSection::I64([&x[..], &y[..]].concat())),
+            (DataSection::Null(x), DataSection::Null(y)) => Some(DataSection::Null(x + y)),
+            _ => None,
         }
     }
 
@@ -206,6 +468,7 @@ impl DataSection {
             DataSection::U64(_) => EncodingType::U64,
             DataSection::I64(_) => EncodingType::I64,
             DataSection::Null(_) => EncodingType::Null,
+            DataSection::Mapped(ref x) => x.encoding_type(),
         }
     }
 
@@ -242,10 +505,26 @@ impl DataSection {
                 let len = encoded.len();
                 (DataSection::U8(encoded), len * 100 < x.len() * 8 * min_reduction)
             }
-            DataSection::Null(ref x) => (DataSection::Null(*x), false)
+            DataSection::Null(ref x) => (DataSection::Null(*x), false),
+            DataSection::Mapped(ref x) => x.to_owned().lz4_encode(),
         }
     }
 
+    pub fn zstd_encode(&self) -> (DataSection, bool) {
+        let min_reduction = 90;
+        let (encoded, size_bytes) = match self {
+            DataSection::U8(ref x) => (zstd::encode(&x), x.len()),
+            DataSection::U16(ref x) => (zstd::encode(&x), x.len() * 2),
+            DataSection::U32(ref x) => (zstd::encode(&x), x.len() * 4),
+            DataSection::U64(ref x) => (zstd::encode(&x), x.len() * 8),
+            DataSection::I64(ref x) => (zstd::encode(&x), x.len() * 8),
+            DataSection::Null(ref x) => return (DataSection::Null(*x), false),
+            DataSection::Mapped(ref x) => return x.to_owned().zstd_encode(),
+        };
+        let len = encoded.len();
+        (DataSection::U8(encoded), len * 100 < size_bytes * min_reduction)
+    }
+
     #[cfg(feature = "enable_lz4")]
     pub fn lz4_decode(&self, decoded_type: EncodingType, len: usize) -> DataSection {
         match self {
@@ -277,6 +556,7 @@ impl DataSection {
                 }
                 t => panic!("Unexpected type {:?} for lz4 decode", t),
             }
+            DataSection::Mapped(ref x) => x.to_owned().lz4_decode(decoded_type, len),
             _ => panic!("Trying to lz4 encode non u8 data section")
         }
     }
@@ -289,7 +569,7 @@ impl DataSection {
                 DataSection::U32(ref mut x) => x.shrink_to_fit(),
                 DataSection::U64(ref mut x) => x.shrink_to_fit(),
                 DataSection::I64(ref mut x) => x.shrink_to_fit(),
-                DataSection::Null(_) => {}
+                DataSection::Null(_) | DataSection::Mapped(_) => {}
             }
         }
     }
@@ -301,11 +581,98 @@ impl DataSection {
             DataSection::U32(ref x) => x.capacity() * mem::size_of::<u32>(),
             DataSection::U64(ref x) => x.capacity() * mem::size_of::<u64>(),
             DataSection::I64(ref x) => x.capacity() * mem::size_of::<i64>(),
-            DataSection::Null(_) => 0,
+            DataSection::Null(_) | DataSection::Mapped(_) => 0,
+        }
+    }
+}
+
+/// Data section that borrows its values from a shared buffer, e.g. a memory-mapped file, instead of owning them.
+/// The buffer must not be modified while the section exists.
+pub struct MappedSection {
+    values: Box<Data<'static>>,
+    encoding_type: EncodingType,
+    len: usize,
+    offset: usize,
+    size_bytes: usize,
+    // Keeps the memory referenced by `values` alive
+    buffer: Arc<AsRef<[u8]> + Send + Sync>,
+}
+
+impl MappedSection {
+    /// Section with `len` values of type `encoding_type` that starts at byte `offset` of `buffer`.
+    pub fn new(buffer: Arc<AsRef<[u8]> + Send + Sync>,
+               offset: usize,
+               len: usize,
+               encoding_type: EncodingType) -> Result<MappedSection, String> {
+        let width = match encoding_type {
+            EncodingType::U8 => 1,
+            EncodingType::U16 => 2,
+            EncodingType::U32 => 4,
+            EncodingType::U64 | EncodingType::I64 => 8,
+            t => return Err(format!("Type {:?} is not supported for mapped data sections", t)),
+        };
+        let size_bytes = match len.checked_mul(width) {
+            Some(size_bytes) => size_bytes,
+            None => return Err(format!("Section of {} values of {} bytes each overflows", len, width)),
+        };
+        let ptr = {
+            let bytes = (*buffer).as_ref();
+            match size_bytes.checked_add(offset) {
+                Some(end) if end <= bytes.len() => {}
+                _ => return Err(format!("Section of {} bytes at offset {} exceeds buffer of {} bytes",
+                                        size_bytes, offset, bytes.len())),
+            }
+            bytes[offset..].as_ptr()
+        };
+        if ptr as usize % width != 0 {
+            return Err(format!("Section at offset {} is not aligned to {} bytes", offset, width));
+        }
+        // Bounds and alignment are checked above and `buffer` outlives `values`
+        let values: Box<Data<'static>> = unsafe {
+            match encoding_type {
+                EncodingType::U8 => Box::new(slice::from_raw_parts(ptr, len)),
+                EncodingType::U16 => Box::new(slice::from_raw_parts(ptr as *const u16, len)),
+                EncodingType::U32 => Box::new(slice::from_raw_parts(ptr as *const u32, len)),
+                EncodingType::U64 => Box::new(slice::from_raw_parts(ptr as *const u64, len)),
+                _ => Box::new(slice::from_raw_parts(ptr as *const i64, len)),
+            }
+        };
+        Ok(MappedSection { values, encoding_type, len, offset, size_bytes, buffer })
+    }
+
+    pub fn encoding_type(&self) -> EncodingType { self.encoding_type }
+    pub fn len(&self) -> usize { self.len }
+
+    fn to_any_vec(&self) -> &Data {
+        unsafe { mem::transmute::<&Data<'static>, &Data>(&*self.values) }
+    }
+
+    /// Values of the section in native byte order.
+    pub fn bytes(&self) -> &[u8] {
+        &(*self.buffer).as_ref()[self.offset..self.offset + self.size_bytes]
+    }
+
+    /// Copies the values into an owned data section.
+    pub fn to_owned(&self) -> DataSection {
+        let bytes = self.bytes();
+        unsafe {
+            match self.encoding_type {
+                EncodingType::U8 => DataSection::U8(bytes.to_vec()),
+                EncodingType::U16 => DataSection::U16(slice::from_raw_parts(bytes.as_ptr() as *const u16, self.len).to_vec()),
+                EncodingType::U32 => DataSection::U32(slice::from_raw_parts(bytes.as_ptr() as *const u32, self.len).to_vec()),
+                EncodingType::U64 => DataSection::U64(slice::from_raw_parts(bytes.as_ptr() as *const u64, self.len).to_vec()),
+                _ => DataSection::I64(slice::from_raw_parts(bytes.as_ptr() as *const i64, self.len).to_vec()),
+            }
         }
     }
 }
 
+impl fmt::Debug for MappedSection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mapped({:?}, {})", self.encoding_type, self.len)
+    }
+}
+
 impl From<Vec<u8>> for DataSection {
     fn from(vec: Vec<u8>) -> Self {
         assert_eq!(vec.len(), vec.capacity());
@@ -341,3 +708,78 @@ impl From<Vec<i64>> for DataSection {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mem_store::column_builder::build_column;
+
+    fn int_column(values: Vec<i64>) -> Arc<Column> {
+        let (min, max) = (*values.iter().min().unwrap(), *values.iter().max().unwrap());
+        IntegerColumn::new_boxed("a", values, min, max, false, None, 0)
+    }
+
+    #[test]
+    fn test_mapped_section_bounds() {
+        let buffer: Arc<AsRef<[u8]> + Send + Sync> = Arc::new((0..16).collect::<Vec<u8>>());
+        let section = MappedSection::new(buffer.clone(), 10, 6, EncodingType::U8).unwrap();
+        assert_eq!(section.bytes(), &[10, 11, 12, 13, 14, 15]);
+        assert!(MappedSection::new(buffer.clone(), 10, 7, EncodingType::U8).is_err());
+        assert!(MappedSection::new(buffer.clone(), usize::max_value(), 2, EncodingType::U8).is_err());
+        assert!(MappedSection::new(buffer.clone(), 8, usize::max_value() / 4 + 1, EncodingType::U64).is_err());
+        assert!(MappedSection::new(buffer, 8, usize::max_value(), EncodingType::U16).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = int_column(vec![3, 1, 2]).merge(&int_column(vec![5, 4])).unwrap();
+        assert_eq!(merged.codec().ops(), &[CodecOp::ToI64(EncodingType::U8)]);
+        assert_eq!(merged.range(), Some((1, 5)));
+        assert_eq!(merged.decode().unwrap(), [3, 1, 2, 5, 4].iter().map(|&i| RawVal::Int(i)).collect::<Vec<_>>());
+
+        let merged = int_column(vec![3, 1, 2]).merge(&int_column(vec![1000])).unwrap();
+        assert_eq!(merged.encoding_type(), EncodingType::U16);
+        assert_eq!(merged.decode().unwrap(), [3, 1, 2, 1000].iter().map(|&i| RawVal::Int(i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_approx_cardinality() {
+        assert_eq!(Column::approx_cardinality(&int_column(vec![3, 1, 2, 3, 3])), Cardinality::AtMost(3));
+        assert_eq!(Column::null("n", 10).approx_cardinality(), Cardinality::Exact(0));
+
+        let dictionary = build_column("s", ["a", "b", "a", "b", "a", "b"].iter().map(|s| RawVal::Str(s.to_string())).collect());
+        assert_eq!(Column::approx_cardinality(&dictionary), Cardinality::Exact(2));
+
+        let packed = build_column("s", (0..100).map(|i| RawVal::Str(format!("value{}", i))).collect());
+        let cardinality = Column::approx_cardinality(&packed).value();
+        assert!(cardinality >= 90 && cardinality <= 100, "{}", cardinality);
+
+        let wide = int_column((0..100).map(|i| i << 40).collect());
+        let cardinality = Column::approx_cardinality(&wide).value();
+        assert!(cardinality >= 90 && cardinality <= 100, "{}", cardinality);
+    }
+
+    #[test]
+    fn test_null_count() {
+        assert_eq!(int_column(vec![3, 1, 2]).null_count(), Some(0));
+        assert_eq!(Column::null("n", 10).null_count(), Some(10));
+        let ints = build_column("a", vec![RawVal::Int(1), RawVal::Null, RawVal::Int(3), RawVal::Null, RawVal::Null]);
+        assert_eq!(ints.null_count(), Some(3));
+        let strings = build_column("s", (0..20).map(|i| if i % 4 == 0 { RawVal::Null } else { RawVal::Str(format!("{}", i % 2)) }).collect());
+        assert_eq!(strings.null_count(), Some(5));
+    }
+
+    #[test]
+    fn test_decode_into() {
+        let mut values = Vec::new();
+        let ints = build_column("a", vec![RawVal::Int(1), RawVal::Null, RawVal::Int(-3)]);
+        ints.decode_into(&mut values).unwrap();
+        assert_eq!(values, vec![RawVal::Int(1), RawVal::Null, RawVal::Int(-3)]);
+        let capacity = values.capacity();
+        int_column(vec![7, 8]).decode_into(&mut values).unwrap();
+        assert_eq!(values, vec![RawVal::Int(7), RawVal::Int(8)]);
+        assert_eq!(values.capacity(), capacity);
+        Column::null("n", 2).decode_into(&mut values).unwrap();
+        assert_eq!(values, vec![RawVal::Null, RawVal::Null]);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet};
+
+
+/// Maps the new names of renamed columns to the names they are stored under, see `Table::rename_column`.
+/// Partitions that contain a column under the new name, e.g. because it was ingested after the rename, use that
+/// column instead of the renamed one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColumnAliases {
+    aliases: HashMap<String, String>,
+}
+
+impl ColumnAliases {
+    pub fn insert(&mut self, new: &str, old: &str) {
+        self.aliases.insert(new.to_string(), old.to_string());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Pairs of new and stored name, sorted by new name.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item=(&'a str, &'a str)> {
+        let mut aliases = self.aliases.iter()
+            .map(|(new, old)| (new.as_str(), old.as_str()))
+            .collect::<Vec<_>>();
+        aliases.sort();
+        aliases.into_iter()
+    }
+
+    /// Name the column `new` is stored under in partitions that don't contain it, if it has been renamed.
+    pub fn stored_name(&self, new: &str) -> Option<&str> {
+        self.aliases.get(new).map(|old| old.as_str())
+    }
+
+    /// Name the stored column `old` has been renamed to, if any.
+    pub fn new_name(&self, old: &str) -> Option<&str> {
+        self.aliases.iter().find(|(_, stored)| *stored == old).map(|(new, _)| new.as_str())
+    }
+
+    /// Name under which the column `name` of a partition that stores the columns `stored` is read.
+    /// A renamed column keeps its old name if the partition also contains a column under the new name.
+    pub fn current_name<'a>(&'a self, name: &'a str, stored: &[&str]) -> &'a str {
+        match self.new_name(name) {
+            Some(new) if !stored.contains(&new) => new,
+            _ => name,
+        }
+    }
+
+    /// Adds the name each renamed column in `cols` is stored under.
+    pub fn stored_cols(&self, cols: &HashSet<String>) -> HashSet<String> {
+        let mut stored = cols.clone();
+        for col in cols {
+            if let Some(old) = self.stored_name(col) {
+                stored.insert(old.to_string());
+            }
+        }
+        stored
+    }
+
+    /// The aliases of the columns in `cols`.
+    pub fn restrict(&self, cols: &HashSet<String>) -> ColumnAliases {
+        ColumnAliases {
+            aliases: self.aliases.iter()
+                .filter(|(new, _)| cols.contains(*new))
+                .map(|(new, old)| (new.to_string(), old.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Makes the entries of renamed columns available under their new name, unless `entries` already contains it.
+    /// `entries` holds the columns or column bounds of a single partition keyed by stored name.
+    pub fn resolve<T: Clone>(&self, entries: &mut HashMap<String, T>) {
+        for (new, old) in &self.aliases {
+            if !entries.contains_key(new) {
+                if let Some(entry) = entries.get(old).cloned() {
+                    entries.insert(new.to_string(), entry);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_aliases() {
+        let mut aliases = ColumnAliases::default();
+        aliases.insert("label", "name");
+        aliases.insert("total", "sum");
+        assert_eq!(aliases.iter().collect::<Vec<_>>(), vec![("label", "name"), ("total", "sum")]);
+        assert_eq!((aliases.stored_name("label"), aliases.stored_name("name")), (Some("name"), None));
+        assert_eq!((aliases.new_name("name"), aliases.new_name("label")), (Some("label"), None));
+        let stored = ["id", "name", "sum", "total"];
+        assert_eq!(stored.iter().map(|name| aliases.current_name(name, &stored)).collect::<Vec<_>>(),
+                   vec!["id", "label", "sum", "total"]);
+
+        let cols = ["id", "label"].iter().map(|col| col.to_string()).collect::<HashSet<_>>();
+        let mut stored = aliases.stored_cols(&cols).into_iter().collect::<Vec<_>>();
+        stored.sort();
+        assert_eq!(stored, vec!["id", "label", "name"]);
+        let restricted = aliases.restrict(&cols);
+        assert_eq!(restricted.iter().collect::<Vec<_>>(), vec![("label", "name")]);
+
+        let mut entries = HashMap::new();
+        entries.insert("name".to_string(), 1);
+        entries.insert("sum".to_string(), 2);
+        entries.insert("total".to_string(), 3);
+        aliases.resolve(&mut entries);
+        assert_eq!((entries["label"], entries["total"], entries.len()), (1, 3, 4));
+    }
+}
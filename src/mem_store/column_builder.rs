@@ -2,6 +2,10 @@ use std::cmp;
 use std::i64;
 use std::sync::Arc;
 
+use bitvec::BitVecMut;
+use engine::data_types::BasicType;
+use ingest::raw_val::RawVal;
+use mem_store::booleans::BooleanColumn;
 use mem_store::integers::*;
 use mem_store::column::*;
 use mem_store::strings::*;
@@ -57,6 +61,7 @@ pub struct IntColBuilder {
     increasing: u64,
     allow_delta_encode: bool,
     last: i64,
+    histogram_buckets: usize,
 }
 
 impl Default for IntColBuilder {
@@ -68,10 +73,19 @@ impl Default for IntColBuilder {
             increasing: 0,
             allow_delta_encode: true,
             last: i64::MIN,
+            histogram_buckets: 0,
         }
     }
 }
 
+impl IntColBuilder {
+    /// Makes the column store a histogram with the given number of buckets, which improves selectivity estimates.
+    pub fn with_histogram(mut self, buckets: usize) -> IntColBuilder {
+        self.histogram_buckets = buckets;
+        self
+    }
+}
+
 impl ColumnBuilder<Option<i64>> for IntColBuilder {
     fn new() -> IntColBuilder { IntColBuilder::default() }
 
@@ -99,11 +113,76 @@ impl ColumnBuilder<Option<i64>> for IntColBuilder {
                                  self.min,
                                  self.max,
                                  delta_encode,
-                                 present)
+                                 present,
+                                 self.histogram_buckets)
+    }
+}
+
+
+/// Builds a column from decoded values, choosing the encoding like ingestion would and preserving nulls.
+pub fn build_column(name: &str, values: Vec<RawVal>) -> Arc<Column> {
+    let mut present = Vec::new();
+    let mut any_null = false;
+    let mut any_int = false;
+    let mut any_string = false;
+    for (i, value) in values.iter().enumerate() {
+        match *value {
+            RawVal::Null => any_null = true,
+            RawVal::Int(_) => {
+                any_int = true;
+                present.set(i);
+            }
+            RawVal::Str(_) => {
+                any_string = true;
+                present.set(i);
+            }
+        }
     }
+    let present = if any_null { Some(present) } else { None };
+    if any_string {
+        let mut builder = StringColBuilder::default();
+        for value in &values {
+            match *value {
+                RawVal::Str(ref s) => builder.push(s),
+                RawVal::Int(i) => builder.push(&i.to_string()),
+                RawVal::Null => builder.push(&""),
+            }
+        }
+        ColumnBuilder::<String>::finalize(builder, name, present)
+    } else if any_int {
+        let mut builder = IntColBuilder::default();
+        for value in &values {
+            match *value {
+                RawVal::Int(i) => builder.push(&Some(i)),
+                _ => builder.push(&None),
+            }
+        }
+        builder.finalize(name, present)
+    } else {
+        Arc::new(Column::null(name, values.len()))
+    }
+}
+
+/// Builds a column from values decoded from columns of the types in `decoded_from`. Booleans decode to the integers
+/// 0 and 1, which are stored as a boolean column again if all source columns are booleans and no value is null.
+pub fn rebuild_column(name: &str, decoded_from: &[BasicType], values: Vec<RawVal>) -> Arc<Column> {
+    if !decoded_from.is_empty() && decoded_from.iter().all(|&t| t == BasicType::Boolean) {
+        let booleans = values.iter()
+            .map(|value| match *value {
+                RawVal::Int(0) => Some(false),
+                RawVal::Int(1) => Some(true),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(booleans) = booleans {
+            return BooleanColumn::new_boxed(name, &booleans);
+        }
+    }
+    build_column(name, values)
 }
 
 
+
 fn is_lowercase_hex(string: &str) -> bool {
     string.len() & 1 == 0 && string.chars().all(|c| {
         c == '0' || c == '1' || c == '2' || c == '3' ||
@@ -0,0 +1,349 @@
+use std::cmp;
+
+use bitvec::BitVec;
+use errors::QueryError;
+use hyperloglog::HyperLogLog;
+use ingest::raw_val::RawVal;
+use mem_store::*;
+use syntax::expression::Func2Type;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    pub len: usize,
+    pub null_count: usize,
+    pub min: RawVal,
+    pub max: RawVal,
+    pub approx_distinct: u64,
+    /// Sum of all values, `None` for non-integer columns.
+    pub sum: Option<i128>,
+    /// Equal width histogram over the range of the column, empty for non-integer columns.
+    pub histogram: Vec<HistogramBucket>,
+    /// Sketch that `approx_distinct` is estimated from.
+    pub sketch: HyperLogLog,
+}
+
+/// Number of distinct values in a column, see `Column::approx_cardinality`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cardinality {
+    Exact(usize),
+    /// Upper bound derived from the encoding of the column.
+    AtMost(usize),
+    /// Estimate obtained from a HyperLogLog sketch.
+    Estimate(usize),
+}
+
+impl Cardinality {
+    pub fn value(&self) -> usize {
+        match *self {
+            Cardinality::Exact(n) | Cardinality::AtMost(n) | Cardinality::Estimate(n) => n,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramBucket {
+    /// Inclusive lower bound of the bucket.
+    pub lower: i64,
+    /// Inclusive upper bound of the bucket.
+    pub upper: i64,
+    pub count: usize,
+}
+
+impl ColumnStats {
+    pub fn compute(column: &Column, buckets: usize) -> Result<ColumnStats, QueryError> {
+        if let Some(offset) = integer_offset(column.codec().ops()) {
+            let present = match column.data().get(1) {
+                Some(DataSection::U8(present)) => Some(&present[..]),
+                _ => None,
+            };
+            return Ok(match column.data()[0] {
+                DataSection::U8(ref data) => integer_stats(data, present, offset, buckets),
+                DataSection::U16(ref data) => integer_stats(data, present, offset, buckets),
+                DataSection::U32(ref data) => integer_stats(data, present, offset, buckets),
+                DataSection::I64(ref data) => integer_stats(data, present, offset, buckets),
+                _ => value_stats(&column.decode()?, buckets),
+            });
+        }
+        Ok(value_stats(&column.decode()?, buckets))
+    }
+
+    /// Combines the stats of two columns, e.g. the same column in different partitions.
+    /// Since bucket boundaries generally differ between columns, the combined stats do not have a histogram.
+    pub fn merge(&mut self, other: &ColumnStats) {
+        self.len += other.len;
+        self.null_count += other.null_count;
+        if other.min != RawVal::Null && (self.min == RawVal::Null || other.min < self.min) {
+            self.min = other.min.clone();
+        }
+        if other.max != RawVal::Null && (self.max == RawVal::Null || other.max > self.max) {
+            self.max = other.max.clone();
+        }
+        self.sum = match (self.sum, other.sum) {
+            (Some(sum1), Some(sum2)) => Some(sum1 + sum2),
+            _ => None,
+        };
+        self.sketch.merge(&other.sketch);
+        self.approx_distinct = self.sketch.estimate();
+        self.histogram = vec![];
+    }
+
+    /// Mean of all non-null values, `None` for non-integer columns and columns without values.
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.len - self.null_count;
+        match self.sum {
+            Some(sum) if count > 0 => Some(sum as f64 / count as f64),
+            _ => None,
+        }
+    }
+
+    /// Estimated fraction of rows that satisfy `column <op> value`, assuming a uniform distribution within each bucket.
+    /// Returns `None` for non-integer columns.
+    pub fn selectivity(&self, op: Func2Type, value: i64) -> Option<f64> {
+        histogram_selectivity(&self.histogram, self.len, op, value)
+    }
+}
+
+/// Equal width histogram with at most `buckets` buckets over the range `[min, max]`, skipping null values.
+pub fn histogram(values: &[i64], present: Option<&[u8]>, min: i64, max: i64, buckets: usize) -> Vec<HistogramBucket> {
+    if values.is_empty() || min > max {
+        return vec![];
+    }
+    let (width, mut counts) = empty_histogram(min, max, buckets);
+    for (i, &x) in values.iter().enumerate() {
+        if present.map_or(true, |p| p.is_set(i)) {
+            counts[bucket_index(x, min, width)] += 1;
+        }
+    }
+    histogram_buckets(min, max, width, counts)
+}
+
+/// Estimated fraction of `len` rows that satisfy `x <op> value`, assuming a uniform distribution within each bucket.
+pub fn histogram_selectivity(histogram: &[HistogramBucket], len: usize, op: Func2Type, value: i64) -> Option<f64> {
+    if histogram.is_empty() || len == 0 {
+        return None;
+    }
+    let mut matching = 0.0;
+    for bucket in histogram {
+        matching += bucket.count as f64 * range_selectivity((bucket.lower, bucket.upper), op, value)?;
+    }
+    Some(matching / len as f64)
+}
+
+/// Estimated fraction of values in `[min, max]` that satisfy `x <op> value`, assuming a uniform distribution.
+/// Returns `None` for operators other than comparisons.
+pub fn range_selectivity((min, max): (i64, i64), op: Func2Type, value: i64) -> Option<f64> {
+    let (min, max, value) = (i128::from(min), i128::from(max), i128::from(value));
+    let span = max - min + 1;
+    // Number of integers in `[min, max]` that are smaller than `x`
+    let below = |x: i128| cmp::max(0, cmp::min(x, max + 1) - min);
+    let matching = match op {
+        Func2Type::LT => below(value),
+        Func2Type::LTE => below(value + 1),
+        Func2Type::GT => span - below(value + 1),
+        Func2Type::GTE => span - below(value),
+        Func2Type::Equals => below(value + 1) - below(value),
+        Func2Type::NotEquals => span - below(value + 1) + below(value),
+        _ => return None,
+    };
+    Some(matching as f64 / span as f64)
+}
+
+/// Returns the offset for integer columns that can be read directly from the first data section.
+fn integer_offset(ops: &[CodecOp]) -> Option<i64> {
+    let mut offset = 0;
+    for op in ops {
+        match *op {
+            CodecOp::Add(_, x) => offset = x,
+            CodecOp::ToI64(_) | CodecOp::PushDataSection(1) | CodecOp::Nullable => {}
+            _ => return None,
+        }
+    }
+    // Identity codec on a non-integer column (e.g. null column) is not a valid integer encoding
+    if ops.is_empty() { None } else { Some(offset) }
+}
+
+/// Computes stats in the encoded domain, `offset` has to be added to obtain decoded values.
+fn integer_stats<T: Copy + Into<i64>>(data: &[T], present: Option<&[u8]>, offset: i64, buckets: usize) -> ColumnStats {
+    let is_present = |i: usize| present.map_or(true, |p| p.is_set(i));
+    let mut null_count = 0;
+    let mut min = None;
+    let mut max = None;
+    let mut sum = 0i128;
+    let mut hll = HyperLogLog::default();
+    for (i, &x) in data.iter().enumerate() {
+        if !is_present(i) {
+            null_count += 1;
+            continue;
+        }
+        let x = x.into();
+        min = Some(min.map_or(x, |m| cmp::min(m, x)));
+        max = Some(max.map_or(x, |m| cmp::max(m, x)));
+        sum += i128::from(x + offset);
+        hll.insert_int(x + offset);
+    }
+
+    let histogram = match (min, max) {
+        (Some(min), Some(max)) => {
+            let (width, mut counts) = empty_histogram(min, max, buckets);
+            for (i, &x) in data.iter().enumerate() {
+                if is_present(i) {
+                    counts[bucket_index(x.into(), min, width)] += 1;
+                }
+            }
+            histogram_buckets(min + offset, max + offset, width, counts)
+        }
+        _ => vec![],
+    };
+
+    ColumnStats {
+        len: data.len(),
+        null_count,
+        min: min.map_or(RawVal::Null, |m| RawVal::Int(m + offset)),
+        max: max.map_or(RawVal::Null, |m| RawVal::Int(m + offset)),
+        approx_distinct: hll.estimate(),
+        sum: Some(sum),
+        histogram,
+        sketch: hll,
+    }
+}
+
+fn value_stats(values: &[RawVal], buckets: usize) -> ColumnStats {
+    let mut hll = HyperLogLog::default();
+    let mut null_count = 0;
+    let mut min = RawVal::Null;
+    let mut max = RawVal::Null;
+    let mut sum = Some(0i128);
+    for value in values {
+        match *value {
+            RawVal::Null => {
+                null_count += 1;
+                continue;
+            }
+            RawVal::Int(x) => sum = sum.map(|sum| sum + i128::from(x)),
+            RawVal::Str(_) => sum = None,
+        }
+        hll.insert(value);
+        if min == RawVal::Null || *value < min {
+            min = value.clone();
+        }
+        if max == RawVal::Null || *value > max {
+            max = value.clone();
+        }
+    }
+
+    let histogram = match (&min, &max) {
+        (&RawVal::Int(min), &RawVal::Int(max)) => {
+            let (width, mut counts) = empty_histogram(min, max, buckets);
+            for value in values {
+                if let RawVal::Int(x) = *value {
+                    counts[bucket_index(x, min, width)] += 1;
+                }
+            }
+            histogram_buckets(min, max, width, counts)
+        }
+        _ => vec![],
+    };
+
+    ColumnStats {
+        len: values.len(),
+        null_count,
+        min,
+        max,
+        approx_distinct: hll.estimate(),
+        sum,
+        histogram,
+        sketch: hll,
+    }
+}
+
+fn empty_histogram(min: i64, max: i64, buckets: usize) -> (i128, Vec<usize>) {
+    let span = i128::from(max) - i128::from(min) + 1;
+    let buckets = cmp::max(1, cmp::min(buckets as i128, span));
+    let width = (span + buckets - 1) / buckets;
+    let count = (span + width - 1) / width;
+    (width, vec![0; count as usize])
+}
+
+fn bucket_index(x: i64, min: i64, width: i128) -> usize {
+    ((i128::from(x) - i128::from(min)) / width) as usize
+}
+
+fn histogram_buckets(min: i64, max: i64, width: i128, counts: Vec<usize>) -> Vec<HistogramBucket> {
+    counts.into_iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let lower = i128::from(min) + i as i128 * width;
+            let upper = cmp::min(lower + width - 1, i128::from(max));
+            HistogramBucket { lower: lower as i64, upper: upper as i64, count }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mem_store::integers::IntegerColumn;
+
+    #[test]
+    fn test_offset_encoded_stats() {
+        let values = (1000..1100).collect::<Vec<i64>>();
+        let column = IntegerColumn::new_boxed("a", values, 1000, 1099, false, None, 0);
+        let stats = column.stats(4).unwrap();
+        assert_eq!(stats.len, 100);
+        assert_eq!(stats.null_count, 0);
+        assert_eq!(stats.min, RawVal::Int(1000));
+        assert_eq!(stats.max, RawVal::Int(1099));
+        assert_eq!(stats.histogram, vec![
+            HistogramBucket { lower: 1000, upper: 1024, count: 25 },
+            HistogramBucket { lower: 1025, upper: 1049, count: 25 },
+            HistogramBucket { lower: 1050, upper: 1074, count: 25 },
+            HistogramBucket { lower: 1075, upper: 1099, count: 25 },
+        ]);
+        assert!(stats.approx_distinct >= 95 && stats.approx_distinct <= 105);
+        assert_eq!(stats.selectivity(Func2Type::LT, 1025), Some(0.25));
+        assert_eq!(stats.selectivity(Func2Type::Equals, 2000), Some(0.0));
+    }
+
+    #[test]
+    fn test_range_selectivity() {
+        assert_eq!(range_selectivity((0, 99), Func2Type::LT, 10), Some(0.1));
+        assert_eq!(range_selectivity((0, 99), Func2Type::LTE, 10), Some(0.11));
+        assert_eq!(range_selectivity((0, 99), Func2Type::GT, 89), Some(0.1));
+        assert_eq!(range_selectivity((0, 99), Func2Type::GTE, -5), Some(1.0));
+        assert_eq!(range_selectivity((0, 99), Func2Type::Equals, 50), Some(0.01));
+        assert_eq!(range_selectivity((0, 99), Func2Type::NotEquals, 100), Some(1.0));
+        assert_eq!(range_selectivity((::std::i64::MIN, ::std::i64::MAX), Func2Type::LT, 0), Some(0.5));
+        assert_eq!(range_selectivity((0, 99), Func2Type::Add, 1), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let column1 = IntegerColumn::new_boxed("a", (0..100).collect(), 0, 99, false, None, 0);
+        let column2 = IntegerColumn::new_boxed("a", (50..250).collect(), 50, 249, false, None, 0);
+        let mut stats = column1.stats(4).unwrap();
+        stats.merge(&column2.stats(4).unwrap());
+        assert_eq!(stats.len, 300);
+        assert_eq!(stats.min, RawVal::Int(0));
+        assert_eq!(stats.max, RawVal::Int(249));
+        assert_eq!(stats.sum, Some(4950 + 29900));
+        assert!(stats.approx_distinct >= 240 && stats.approx_distinct <= 260);
+        assert!(stats.histogram.is_empty());
+    }
+
+    #[test]
+    fn test_column_histogram() {
+        // 90 values in [0, 9] and 10 values in [90, 99]
+        let values = (0..90).map(|i| i % 10).chain(90..100).collect::<Vec<i64>>();
+        let uniform = IntegerColumn::new_boxed("a", values.clone(), 0, 99, false, None, 0);
+        assert_eq!(uniform.histogram(), None);
+        assert_eq!(uniform.selectivity(Func2Type::LT, 10), Some(0.1));
+
+        let column = IntegerColumn::new_boxed("a", values, 0, 99, false, None, 10);
+        let histogram = column.histogram().unwrap();
+        assert_eq!(histogram.len(), 10);
+        assert_eq!(histogram[0], HistogramBucket { lower: 0, upper: 9, count: 90 });
+        assert_eq!(histogram[9], HistogramBucket { lower: 90, upper: 99, count: 10 });
+        assert_eq!(column.selectivity(Func2Type::LT, 10), Some(0.9));
+        assert_eq!(column.selectivity(Func2Type::GTE, 90), Some(0.1));
+    }
+}
@@ -1,23 +1,56 @@
+use bitvec::BitVec;
 use engine::data_types::*;
+use hyperloglog::{self, HyperLogLog};
 use mem_store::*;
-use std::{u16, u32, u8};
+use mem_store::column_stats;
+use std::{u16, u32, u64, u8};
+use std::cmp;
+use std::mem;
 use std::convert::From;
 use std::sync::Arc;
 
+/// Number of values that share the same offset in frame of reference encoded columns.
+pub const FRAME_SIZE: usize = 1024;
+
+/// Encodings for non-nullable integer columns that `IntegerColumn::build` chooses from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntegerEncoding {
+    /// Encoding chosen by `IntegerColumn::new_boxed`, which stores the difference of each value to the minimum in the
+    /// narrowest integer type that fits all differences (or to the minimum of each frame if that is smaller).
+    Offset,
+    /// Difference of each value to the minimum packed into the given number of bits.
+    BitPacked(usize),
+    /// Each run of equal consecutive values is stored once as its difference to the minimum, together with the
+    /// length of the run.
+    RunLength,
+    /// Difference of each value to the previous value, only for non-decreasing values.
+    Delta,
+}
+
 pub struct IntegerColumn;
 
 impl IntegerColumn {
+    /// Computes a histogram with `histogram_buckets` buckets over `[min, max]` unless `histogram_buckets` is 0.
     pub fn new_boxed(name: &str,
                      mut values: Vec<i64>,
                      mut min: i64,
                      mut max: i64,
                      delta_encode: bool,
-                     null: Option<Vec<u8>>) -> Arc<Column> {
+                     null: Option<Vec<u8>>,
+                     histogram_buckets: usize) -> Arc<Column> {
         let null = null.map(|mut n| {
             n.shrink_to_fit();
             n
         });
+        let histogram = if histogram_buckets > 0 {
+            Some(column_stats::histogram(&values, null.as_ref().map(|n| &n[..]), min, max, histogram_buckets))
+        } else {
+            None
+        };
         let original_range = Some((min, max));
+        let distinct = cmp::min(max as i128 - min as i128 + 1, i128::from(u64::MAX)) as u64;
+        let bloom = BloomIndex::from_selective_ints(&values, distinct);
+        let sorted = null.is_none() && IntegerColumn::is_sorted(&values);
         let min0 = min;
         let max0 = max;
         if delta_encode && !values.is_empty() {
@@ -37,7 +70,14 @@ impl IntegerColumn {
         } else {
             (max - min) as u64
         };
-        let mut column = if min >= 0 && max <= From::from(u8::MAX) {
+        let frame_of_reference = if delta_encode || null.is_some() {
+            None
+        } else {
+            IntegerColumn::frame_of_reference(name, &values, min, max, FRAME_SIZE)
+        };
+        let mut column = if let Some(column) = frame_of_reference {
+            column
+        } else if min >= 0 && max <= From::from(u8::MAX) {
             IntegerColumn::create_col::<u8>(name, values, 0, min0, max0, delta_encode, null, EncodingType::U8)
         } else if interval <= From::from(u8::MAX) {
             IntegerColumn::create_col::<u8>(name, values, min, min0, max0, delta_encode, null, EncodingType::U8)
@@ -51,7 +91,19 @@ impl IntegerColumn {
             IntegerColumn::create_col::<u32>(name, values, min, min0, max0, delta_encode, null, EncodingType::U32)
         } else {
             values.shrink_to_fit();
-            match null {
+            // The range of the values is too wide to usefully bound their number of distinct values
+            let sketch = if delta_encode {
+                None
+            } else {
+                let mut sketch = HyperLogLog::new(hyperloglog::DEFAULT_PRECISION);
+                for (i, &value) in values.iter().enumerate() {
+                    if null.as_ref().map_or(true, |n| n.is_set(i)) {
+                        sketch.insert_int(value);
+                    }
+                }
+                Some(sketch)
+            };
+            let mut column = match null {
                 Some(present) => if delta_encode {
                     Column::new(
                         name,
@@ -82,12 +134,174 @@ impl IntegerColumn {
                         vec![],
                         vec![DataSection::I64(values)])
                 }
+            };
+            if let Some(sketch) = sketch {
+                column.set_sketch(sketch);
+            }
+            column
+        };
+        if let Some(bloom) = bloom {
+            column.set_bloom_index(bloom);
+        }
+        if sorted {
+            column.set_sorted();
+        }
+        if let Some(histogram) = histogram {
+            column.set_histogram(histogram);
+        }
+        column.compress();
+        Arc::new(column)
+    }
+
+    /// Encodes `values` with the `IntegerEncoding` returned by `choose_encoding`.
+    pub fn build(name: &str, values: Vec<i64>) -> Arc<Column> {
+        if values.is_empty() {
+            return IntegerColumn::new_boxed(name, values, 0, 0, false, None, 0);
+        }
+        let min = *values.iter().min().unwrap();
+        let max = *values.iter().max().unwrap();
+        let interval = max as i128 - min as i128;
+        let mut column = match IntegerColumn::choose_encoding(&values) {
+            IntegerEncoding::Offset => return IntegerColumn::new_boxed(name, values, min, max, false, None, 0),
+            IntegerEncoding::BitPacked(bits) => IntegerColumn::bit_packed(name, &values, min, max, bits),
+            IntegerEncoding::RunLength => match IntegerColumn::width(interval) {
+                1 => IntegerColumn::run_length::<u8>(name, &values, min, max, EncodingType::U8),
+                2 => IntegerColumn::run_length::<u16>(name, &values, min, max, EncodingType::U16),
+                _ => IntegerColumn::run_length::<u32>(name, &values, min, max, EncodingType::U32),
+            },
+            IntegerEncoding::Delta => {
+                let max_delta = values.windows(2).map(|w| w[1] - w[0]).max().unwrap_or(0);
+                match IntegerColumn::width(i128::from(max_delta)) {
+                    1 => IntegerColumn::delta::<u8>(name, &values, max_delta, EncodingType::U8),
+                    2 => IntegerColumn::delta::<u16>(name, &values, max_delta, EncodingType::U16),
+                    _ => IntegerColumn::delta::<u32>(name, &values, max_delta, EncodingType::U32),
+                }
             }
         };
-        column.lz4_encode();
+        if let Some(bloom) = BloomIndex::from_selective_ints(&values, cmp::min(interval + 1, i128::from(u64::MAX)) as u64) {
+            column.set_bloom_index(bloom);
+        }
+        if IntegerColumn::is_sorted(&values) {
+            column.set_sorted();
+        }
+        column.compress();
         Arc::new(column)
     }
 
+    /// Picks the encoding that stores `values` in the fewest bytes, preferring the order of `IntegerEncoding` on ties.
+    /// Offset encoding is the only one that can be decoded elementwise, the other encodings have to be fully decoded
+    /// before most operations.
+    pub fn choose_encoding(values: &[i64]) -> IntegerEncoding {
+        if values.is_empty() {
+            return IntegerEncoding::Offset;
+        }
+        let mut min = values[0];
+        let mut max = values[0];
+        let mut runs = 1;
+        let mut non_decreasing = true;
+        let mut max_delta = 0;
+        for w in values.windows(2) {
+            min = cmp::min(min, w[1]);
+            max = cmp::max(max, w[1]);
+            if w[0] != w[1] {
+                runs += 1;
+            }
+            if w[1] < w[0] {
+                non_decreasing = false;
+            } else {
+                max_delta = cmp::max(max_delta, w[1] as i128 - w[0] as i128);
+            }
+        }
+        let interval = max as i128 - min as i128;
+        let width = IntegerColumn::width(interval);
+
+        let mut best = (IntegerEncoding::Offset, values.len() * width);
+        let mut candidates = Vec::with_capacity(3);
+        if interval <= i128::from(u8::MAX) {
+            let bits = cmp::max(1, 128 - interval.leading_zeros() as usize);
+            candidates.push((IntegerEncoding::BitPacked(bits), (values.len() * bits + 7) / 8));
+        }
+        // Run lengths are stored as u32 and neither run values nor deltas use more than 32 bits
+        if values.len() <= u32::MAX as usize && width <= 4 {
+            candidates.push((IntegerEncoding::RunLength, runs * (width + mem::size_of::<u32>())));
+        }
+        if non_decreasing && max_delta <= i128::from(u32::MAX) {
+            candidates.push((IntegerEncoding::Delta, values.len() * IntegerColumn::width(max_delta)));
+        }
+        for candidate in candidates {
+            if candidate.1 < best.1 {
+                best = candidate;
+            }
+        }
+        best.0
+    }
+
+    fn bit_packed(name: &str, values: &[i64], min: i64, max: i64, bits: usize) -> Column {
+        let mut packed = vec![0u8; (values.len() * bits + 7) / 8];
+        for (i, &value) in values.iter().enumerate() {
+            let offset = i * bits;
+            let encoded = (value - min) as u16;
+            let shifted = encoded << (offset % 8);
+            packed[offset / 8] |= shifted as u8;
+            if shifted > u16::from(u8::MAX) {
+                packed[offset / 8 + 1] |= (shifted >> 8) as u8;
+            }
+        }
+        let decode = if min == 0 {
+            CodecOp::ToI64(EncodingType::U8)
+        } else {
+            CodecOp::Add(EncodingType::U8, min)
+        };
+        Column::new(
+            name,
+            values.len(),
+            Some((0, max - min)),
+            vec![CodecOp::UnpackInts(bits, values.len()), decode],
+            vec![packed.into()])
+    }
+
+    fn run_length<T>(name: &str, values: &[i64], min: i64, max: i64, t: EncodingType) -> Column
+        where T: GenericIntVec<T>, Vec<T>: Into<DataSection> {
+        let mut run_values = Vec::<T>::new();
+        let mut run_lengths = Vec::<u32>::new();
+        let mut previous = None;
+        for &value in values {
+            if previous == Some(value) {
+                *run_lengths.last_mut().unwrap() += 1;
+            } else {
+                run_values.push(T::from(value - min).unwrap());
+                run_lengths.push(1);
+                previous = Some(value);
+            }
+        }
+        let decode = if min == 0 { CodecOp::ToI64(t) } else { CodecOp::Add(t, min) };
+        Column::new(
+            name,
+            values.len(),
+            Some((0, max - min)),
+            vec![CodecOp::PushDataSection(1), CodecOp::RunLength(t, values.len()), decode],
+            vec![run_values.into(), run_lengths.into()])
+    }
+
+    /// Stores the first value as an offset so that all deltas are non-negative and small.
+    fn delta<T>(name: &str, values: &[i64], max_delta: i64, t: EncodingType) -> Column
+        where T: GenericIntVec<T>, Vec<T>: Into<DataSection> {
+        let mut deltas = Vec::<T>::with_capacity(values.len());
+        deltas.push(T::zero());
+        deltas.extend(values.windows(2).map(|w| T::from(w[1] - w[0]).unwrap()));
+        let codec = if values[0] == 0 {
+            vec![CodecOp::Delta(t)]
+        } else {
+            vec![CodecOp::Delta(t), CodecOp::Add(EncodingType::I64, values[0])]
+        };
+        Column::new(
+            name,
+            values.len(),
+            Some((0, max_delta)),
+            codec,
+            vec![deltas.into()])
+    }
+
     pub fn create_col<T>(name: &str,
                          values: Vec<i64>,
                          offset: i64,
@@ -122,6 +336,65 @@ impl IntegerColumn {
             if let Some(present) = null_map { vec![values.into(), present.into()] } else { vec![values.into()] })
     }
 
+    /// Splits `values` into frames of `frame_size` elements and stores each value as its difference to the minimum of
+    /// its frame. Returns `None` unless this takes less space than subtracting the minimum `min` of all values.
+    pub fn frame_of_reference(name: &str, values: &[i64], min: i64, max: i64, frame_size: usize) -> Option<Column> {
+        if values.len() <= frame_size {
+            return None;
+        }
+        let frame_minimums = values.chunks(frame_size)
+            .map(|frame| *frame.iter().min().unwrap())
+            .collect::<Vec<_>>();
+        let max_interval = values.chunks(frame_size).zip(frame_minimums.iter())
+            .map(|(frame, &frame_min)| *frame.iter().max().unwrap() as i128 - frame_min as i128)
+            .max().unwrap();
+        let width = IntegerColumn::width(max_interval);
+        let size_bytes = values.len() * width + frame_minimums.len() * mem::size_of::<i64>();
+        if size_bytes >= values.len() * IntegerColumn::width(max as i128 - min as i128) {
+            return None;
+        }
+        Some(match width {
+            1 => IntegerColumn::create_frame_of_reference_col::<u8>(name, values, frame_minimums, frame_size, max_interval as i64, EncodingType::U8),
+            2 => IntegerColumn::create_frame_of_reference_col::<u16>(name, values, frame_minimums, frame_size, max_interval as i64, EncodingType::U16),
+            _ => IntegerColumn::create_frame_of_reference_col::<u32>(name, values, frame_minimums, frame_size, max_interval as i64, EncodingType::U32),
+        })
+    }
+
+    fn create_frame_of_reference_col<T>(name: &str,
+                                        values: &[i64],
+                                        frame_minimums: Vec<i64>,
+                                        frame_size: usize,
+                                        max_interval: i64,
+                                        t: EncodingType) -> Column
+        where T: GenericIntVec<T>, Vec<T>: Into<DataSection> {
+        let encoded = values.iter().enumerate()
+            .map(|(i, &v)| T::from(v - frame_minimums[i / frame_size]).unwrap())
+            .collect::<Vec<T>>();
+        Column::new(
+            name,
+            values.len(),
+            Some((0, max_interval)),
+            vec![CodecOp::PushDataSection(1), CodecOp::FrameOfReference(t, frame_size)],
+            vec![encoded.into(), frame_minimums.into()])
+    }
+
+    /// Number of bytes per value required to store values from an interval of length `interval`.
+    fn width(interval: i128) -> usize {
+        if interval <= i128::from(u8::MAX) {
+            1
+        } else if interval <= i128::from(u16::MAX) {
+            2
+        } else if interval <= i128::from(u32::MAX) {
+            4
+        } else {
+            8
+        }
+    }
+
+    fn is_sorted(values: &[i64]) -> bool {
+        values.windows(2).all(|w| w[0] <= w[1])
+    }
+
     pub fn encode<T: GenericIntVec<T>>(values: Vec<i64>, offset: i64) -> Vec<T> {
         let mut encoded_vals = Vec::with_capacity(values.len());
         for v in values {
@@ -134,3 +407,75 @@ impl IntegerColumn {
         encoded_vals
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ingest::raw_val::RawVal;
+
+    #[test]
+    fn test_negative_offset() {
+        let column = IntegerColumn::new_boxed("a", (-1000..1001).collect(), -1000, 1000, false, None, 0);
+        assert!(column.codec().ops().contains(&CodecOp::Add(EncodingType::U16, -1000)));
+        assert_eq!(column.range(), Some((0, 2000)));
+        assert_eq!(column.value_range(), Some((-1000, 1000)));
+
+        let column = IntegerColumn::new_boxed("a", (-100..101).collect(), -100, 100, false, None, 0);
+        assert!(column.codec().ops().contains(&CodecOp::Add(EncodingType::U8, -100)));
+        assert_eq!(column.value_range(), Some((-100, 100)));
+    }
+
+    #[test]
+    fn test_frame_of_reference() {
+        // Values span more than u16 but differ by less than 256 within each frame
+        let values = (0..10 * FRAME_SIZE as i64).map(|i| i / FRAME_SIZE as i64 * 100_000 + i % 200).collect::<Vec<_>>();
+        let max = *values.iter().max().unwrap();
+        let column = IntegerColumn::new_boxed("a", values.clone(), 0, max, false, None, 0);
+        assert!(column.codec().ops().contains(&CodecOp::FrameOfReference(EncodingType::U8, FRAME_SIZE)));
+        assert_eq!(column.data()[1].len(), 10);
+        let (lowest, highest) = column.value_range().unwrap();
+        assert_eq!(lowest, 0);
+        assert!(highest >= max && highest < max + 200);
+        let expected = values.iter().map(|&v| RawVal::Int(v)).collect::<Vec<_>>();
+        assert_eq!(column.decode().unwrap(), expected);
+
+        // Random values don't benefit from per frame offsets
+        let values = (0..10 * FRAME_SIZE as i64).map(|i| (i * 7919) % 60000).collect::<Vec<_>>();
+        let column = IntegerColumn::new_boxed("a", values, 0, 59999, false, None, 0);
+        assert!(!column.codec().ops().iter().any(|op| match op { CodecOp::FrameOfReference(_, _) => true, _ => false }));
+    }
+
+    #[test]
+    fn test_build_integer() {
+        let constant = vec![42; 1000];
+        assert_eq!(IntegerColumn::choose_encoding(&constant), IntegerEncoding::RunLength);
+        let column = Column::build_integer("a", constant.clone());
+        assert!(column.codec().ops().contains(&CodecOp::RunLength(EncodingType::U8, 1000)));
+        assert_eq!(column.value_range(), Some((42, 42)));
+        assert_eq!(column.decode().unwrap(), constant.iter().map(|&v| RawVal::Int(v)).collect::<Vec<_>>());
+
+        let monotonic = (0..10_000).map(|i| 1_000_000 + 3 * i).collect::<Vec<i64>>();
+        assert_eq!(IntegerColumn::choose_encoding(&monotonic), IntegerEncoding::Delta);
+        let column = Column::build_integer("a", monotonic.clone());
+        assert!(column.codec().ops().ends_with(&[CodecOp::Delta(EncodingType::U8), CodecOp::Add(EncodingType::I64, 1_000_000)]));
+        assert_eq!(column.decode().unwrap(), monotonic.iter().map(|&v| RawVal::Int(v)).collect::<Vec<_>>());
+
+        let small_range = (0..10_000).map(|i| 100 + (i * 7) % 16).collect::<Vec<i64>>();
+        assert_eq!(IntegerColumn::choose_encoding(&small_range), IntegerEncoding::BitPacked(4));
+        let column = Column::build_integer("a", small_range.clone());
+        assert!(column.codec().ops().contains(&CodecOp::UnpackInts(4, 10_000)));
+        assert_eq!(column.value_range(), Some((100, 115)));
+        assert_eq!(column.decode().unwrap(), small_range.iter().map(|&v| RawVal::Int(v)).collect::<Vec<_>>());
+
+        let random = (0..10_000).map(|i| (i * 7919) % 60000).collect::<Vec<i64>>();
+        assert_eq!(IntegerColumn::choose_encoding(&random), IntegerEncoding::Offset);
+    }
+
+    #[test]
+    fn test_sorted() {
+        assert!(Column::build_integer("a", (0..1000).map(|i| i / 3).collect()).is_sorted());
+        assert!(!Column::build_integer("a", (0..1000).map(|i| (i * 7919) % 60000).collect()).is_sorted());
+        assert!(IntegerColumn::new_boxed("a", vec![-5, 0, 0, 7], -5, 7, false, None, 0).is_sorted());
+        assert!(!IntegerColumn::new_boxed("a", vec![-5, 0, 0, 7], -5, 7, false, Some(vec![0b1011]), 0).is_sorted());
+    }
+}
@@ -1,5 +1,10 @@
+pub mod batch_metadata;
+pub mod bloom;
+pub mod booleans;
 pub mod codec;
 pub mod column;
+pub mod column_aliases;
+pub mod column_stats;
 pub mod column_builder;
 pub mod integers;
 pub mod partition;
@@ -8,17 +13,25 @@ pub mod strings;
 pub mod table;
 pub mod tree;
 pub mod value;
+pub mod zone_map;
 #[cfg(feature = "enable_lz4")]
 pub mod lz4;
+#[cfg(feature = "enable_zstd")]
+pub mod zstd;
 mod mixed_column;
 pub(crate) mod lru;
 
+pub use self::batch_metadata::{BatchMetadata, ColumnBounds};
+pub use self::bloom::BloomIndex;
 pub use self::column::{Column, DataSection, DataSource};
+pub use self::column_aliases::ColumnAliases;
+pub use self::column_stats::{Cardinality, ColumnStats, HistogramBucket};
 pub use self::codec::{Codec, CodecOp};
 pub use self::tree::*;
-pub use self::table::TableStats;
+pub use self::table::{ColumnDescription, ColumnMetadata, TableSchema, TableStats};
 pub use self::lru::LRU;
 pub use self::value::Val;
+pub use self::zone_map::ZoneMap;
 
 
 #[cfg(not(feature = "enable_lz4"))]
@@ -29,3 +42,10 @@ pub mod lz4 {
         panic!("lz4 not supported in this build of LocustDB. Recompile with --features enable_lz4.")
     }
 }
+
+#[cfg(not(feature = "enable_zstd"))]
+pub mod zstd {
+    pub fn encode<T>(_: &[T]) -> Vec<u8> {
+        panic!("zstd not supported in this build of LocustDB. Recompile with --features enable_zstd.")
+    }
+}
@@ -2,24 +2,42 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::SystemTime;
 
+use bitvec::{BitVec, BitVecMut};
 use disk_store::interface::*;
+use disk_store::interface::ColumnMetadata;
+use engine::data_types::{BasicType, EncodingType};
+use errors::QueryError;
 use ingest::buffer::Buffer;
 use mem_store::*;
+use mem_store::booleans::BooleanColumn;
 use scheduler::disk_read_scheduler::DiskReadScheduler;
 
 
 pub type ColumnKey = (PartitionID, String);
 
+/// Name under which the tombstone of a partition is passed to queries alongside the regular columns.
+pub const TOMBSTONE_COLUMN: &str = "$tombstone";
+
+/// Name of the virtual column that holds the position of each row, numbered continuously across the partitions of a
+/// table in order of partition id. Deleted rows keep their numbers until the partition is vacuumed.
+/// A stored column with the same name takes precedence.
+pub const ROWNUM_COLUMN: &str = "rownum";
+
 pub struct Partition {
     id: PartitionID,
     len: usize,
     cols: Vec<ColumnHandle>,
+    /// Boolean column stored as a bit vector in which the bits of deleted rows are set, `None` if no row has been deleted.
+    tombstone: Mutex<Option<Arc<Column>>>,
+    /// Time at which the rows of the partition were ingested, queries with `as_of` set only read partitions ingested before then.
+    ingested_at: SystemTime,
     lru: LRU,
 }
 
 impl Partition {
-    pub fn new(id: PartitionID, cols: Vec<Arc<Column>>, lru: LRU) -> (Partition, Vec<ColumnKey>) {
+    pub fn new(id: PartitionID, cols: Vec<Arc<Column>>, ingested_at: SystemTime, lru: LRU) -> (Partition, Vec<ColumnKey>) {
         let mut keys = Vec::with_capacity(cols.len());
         (Partition {
             id,
@@ -32,17 +50,23 @@ impl Partition {
                     ColumnHandle::resident(id, c)
                 })
                 .collect(),
+            tombstone: Mutex::new(None),
+            ingested_at,
             lru,
         }, keys)
     }
 
-    pub fn nonresident(id: PartitionID, len: usize, cols: &[ColumnMetadata], lru: LRU) -> Partition {
+    pub fn nonresident(id: PartitionID, len: usize, cols: &[ColumnMetadata], ingested_at: SystemTime, lru: LRU) -> Partition {
         Partition {
             id,
             len,
             cols: cols.iter()
+                // The tombstone is stored alongside the regular columns and restored with `restore_tombstone`
+                .filter(|c| c.name != TOMBSTONE_COLUMN)
                 .map(|c| ColumnHandle::non_resident(id, c.name.to_string(), c.size_bytes))
                 .collect(),
+            tombstone: Mutex::new(None),
+            ingested_at,
             lru,
         }
     }
@@ -53,6 +77,7 @@ impl Partition {
             buffer.buffer.into_iter()
                 .map(|(name, raw_col)| raw_col.finalize(&name))
                 .collect(),
+            SystemTime::now(),
             lru)
     }
 
@@ -64,6 +89,9 @@ impl Partition {
                 columns.insert(handle.name().to_string(), Arc::new(column));
             }
         }
+        if let Some(ref tombstone) = *self.tombstone.lock().unwrap() {
+            columns.insert(TOMBSTONE_COLUMN.to_string(), Arc::new(tombstone.clone()));
+        }
         columns
     }
 
@@ -117,6 +145,7 @@ impl Partition {
                     self.lru.put(handle.key.clone());
                 }
                 *maybe_column = Some(col.clone());
+                handle.update_summary(col);
                 handle.resident.store(true, Ordering::SeqCst);
                 handle.load_scheduled.store(false, Ordering::SeqCst);
             }
@@ -137,8 +166,81 @@ impl Partition {
         0
     }
 
+    /// Returns the column with the given name, loading it from disk if necessary.
+    pub fn column(&self, name: &str, drs: &DiskReadScheduler) -> Option<Arc<Column>> {
+        self.cols.iter()
+            .find(|handle| handle.name() == name)
+            .map(|handle| drs.get_or_load(handle))
+    }
+
+    /// Replaces the column with the same name as `col`.
+    /// The new column has to be stored on disk first, since it is evicted like any other column.
+    pub fn replace(&self, col: Arc<Column>) {
+        for handle in &self.cols {
+            if handle.name() == col.name() {
+                let mut maybe_column = handle.col.lock().unwrap();
+                handle.size_bytes.store(col.heap_size_of_children(), Ordering::SeqCst);
+                *maybe_column = Some(col.clone());
+                handle.update_summary(&col);
+                handle.resident.store(true, Ordering::SeqCst);
+                self.lru.put(handle.key.clone());
+            }
+        }
+    }
+
+    /// Marks the given rows as deleted and returns the number of rows that had not been deleted before.
+    pub fn delete_rows(&self, rows: &[bool]) -> Result<usize, QueryError> {
+        let mut tombstone = self.tombstone.lock().unwrap();
+        let mut deleted = match *tombstone {
+            Some(ref tombstone) => tombstone_bits(tombstone)?,
+            None => vec![0; (self.len + 7) / 8],
+        };
+        let mut count = 0;
+        for (i, &row) in rows.iter().enumerate().take(self.len) {
+            if row && !deleted.is_set(i) {
+                deleted.set(i);
+                count += 1;
+            }
+        }
+        if count > 0 {
+            *tombstone = Some(BooleanColumn::from_bits(TOMBSTONE_COLUMN, self.len, deleted));
+        }
+        Ok(count)
+    }
+
+    /// Returns a bit vector in which the bits of deleted rows are set, or `None` if no row has been deleted.
+    pub fn deleted_rows(&self) -> Result<Option<Vec<u8>>, QueryError> {
+        match *self.tombstone.lock().unwrap() {
+            Some(ref tombstone) => Ok(Some(tombstone_bits(tombstone)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the column that marks deleted rows, or `None` if no row has been deleted.
+    pub fn tombstone(&self) -> Option<Arc<Column>> {
+        self.tombstone.lock().unwrap().clone()
+    }
+
+    /// Sets the tombstone of a partition restored from disk.
+    pub fn restore_tombstone(&self, tombstone: Column) {
+        *self.tombstone.lock().unwrap() = Some(Arc::new(tombstone));
+    }
+
+    /// Number of rows, ingest time and the bounds of all columns that have been in memory since the partition was
+    /// created or restored from disk.
+    pub fn metadata(&self) -> BatchMetadata {
+        BatchMetadata {
+            len: self.len,
+            ingested_at: self.ingested_at,
+            columns: self.cols.iter()
+                .filter_map(|handle| handle.summary().map(|summary| (handle.name().to_string(), summary.bounds)))
+                .collect(),
+        }
+    }
+
     pub fn id(&self) -> u64 { self.id }
     pub fn len(&self) -> usize { self.len }
+    pub fn ingested_at(&self) -> SystemTime { self.ingested_at }
 
     pub fn mem_tree(&self, coltrees: &mut HashMap<String, MemTreeColumn>, depth: usize) {
         if depth == 0 { return; }
@@ -160,6 +262,17 @@ impl Partition {
         }
     }
 
+    /// Type, length and bounds of every column of the partition. Columns that have not been in memory since the
+    /// partition was restored from disk are loaded first.
+    pub fn column_summaries(&self, drs: &DiskReadScheduler) -> Vec<(&str, ColumnSummary)> {
+        self.cols.iter()
+            .map(|handle| {
+                let summary = handle.summary().unwrap_or_else(|| ColumnSummary::of(&drs.get_or_load(handle)));
+                (handle.name(), summary)
+            })
+            .collect()
+    }
+
     pub fn heap_size_per_column(&self) -> Vec<(String, usize)> {
         self.cols.iter()
             .map(|handle| {
@@ -185,12 +298,47 @@ impl Partition {
     }
 }
 
+fn tombstone_bits(tombstone: &Column) -> Result<Vec<u8>, QueryError> {
+    match tombstone.data()[0] {
+        DataSection::U8(ref deleted) => Ok(deleted.clone()),
+        DataSection::Mapped(ref mapped) => match mapped.to_owned() {
+            DataSection::U8(deleted) => Ok(deleted),
+            section => Err(fatal!("Tombstone has to be stored as U8, found {:?}", section.encoding_type())),
+        },
+        ref section => Err(fatal!("Tombstone has to be stored as U8, found {:?}", section.encoding_type())),
+    }
+}
+
 pub struct ColumnHandle {
     key: (PartitionID, String),
     size_bytes: AtomicUsize,
     resident: AtomicBool,
     load_scheduled: AtomicBool,
     col: Mutex<Option<Arc<Column>>>,
+    /// Summary of the column, retained when it is evicted. `None` if the column hasn't been loaded since restart.
+    summary: Mutex<Option<ColumnSummary>>,
+}
+
+/// Type, length and bounds of a column, which remain known after the column is evicted.
+#[derive(Clone, Copy, Debug)]
+pub struct ColumnSummary {
+    pub basic_type: BasicType,
+    pub encoding_type: EncodingType,
+    pub len: usize,
+    pub bounds: ColumnBounds,
+    pub approx_cardinality: Cardinality,
+}
+
+impl ColumnSummary {
+    pub fn of(col: &Column) -> ColumnSummary {
+        ColumnSummary {
+            basic_type: col.basic_type(),
+            encoding_type: col.encoding_type(),
+            len: col.len(),
+            bounds: ColumnBounds::of(col),
+            approx_cardinality: col.approx_cardinality(),
+        }
+    }
 }
 
 impl ColumnHandle {
@@ -200,6 +348,7 @@ impl ColumnHandle {
             size_bytes: AtomicUsize::new(col.heap_size_of_children()),
             resident: AtomicBool::new(true),
             load_scheduled: AtomicBool::new(false),
+            summary: Mutex::new(Some(ColumnSummary::of(&*col))),
             col: Mutex::new(Some(col)),
         }
     }
@@ -211,6 +360,7 @@ impl ColumnHandle {
             resident: AtomicBool::new(false),
             load_scheduled: AtomicBool::new(false),
             col: Mutex::new(None),
+            summary: Mutex::new(None),
         }
     }
 
@@ -246,6 +396,14 @@ impl ColumnHandle {
         self.size_bytes.load(Ordering::SeqCst)
     }
 
+    pub fn summary(&self) -> Option<ColumnSummary> {
+        *self.summary.lock().unwrap()
+    }
+
+    pub fn update_summary(&self, col: &Column) {
+        *self.summary.lock().unwrap() = Some(ColumnSummary::of(col));
+    }
+
     pub fn update_size_bytes(&self, size_bytes: usize) {
         self.size_bytes.store(size_bytes, Ordering::SeqCst)
     }
@@ -1,7 +1,9 @@
 use hex;
 use seahash::SeaHasher;
 
+use bitvec::BitVec;
 use engine::data_types::*;
+use hyperloglog::{self, HyperLogLog};
 use mem_store::*;
 use std::{u16, u32, u8};
 use std::collections::hash_set::HashSet;
@@ -30,6 +32,12 @@ pub fn fast_build_string_column<'a, T>(name: &str,
         // PERF: is 2 the right constant? and should probably also depend on the length of the strings
         // TODO(#103): len > 1000 || name == "string_packed" is a hack to make tests use dictionary encoding. Remove once we are able to group by string packed columns.
         if unique_values.len() == len / DICTIONARY_RATIO {
+            let mut sketch = HyperLogLog::new(hyperloglog::DEFAULT_PRECISION);
+            for (i, s) in strings.clone().enumerate() {
+                if present.as_ref().map_or(true, |p| p.is_set(i)) {
+                    sketch.insert_str(s);
+                }
+            }
             let (mut codec, data) = if (lhex || uhex) && total_bytes / len > 5 {
                 let packed = PackedBytes::from_iterator(strings.map(|s| hex::decode(s).unwrap()));
                 (vec![CodecOp::UnhexpackStrings(uhex, total_bytes)], DataSection::U8(packed.into_vec()))
@@ -44,14 +52,19 @@ pub fn fast_build_string_column<'a, T>(name: &str,
             } else {
                 Column::new(name, len, None, codec, vec![data])
             };
-            column.lz4_encode();
+            column.set_sketch(sketch);
+            column.compress();
             return Arc::new(column);
         }
     }
 
     let dict_size = unique_values.len();
+    // Dictionary indices are assigned in lexical order, so sorting and range comparisons can operate directly on the
+    // indices (see `Codec::encode_str_bound`). The tradeoff is that the full set of values has to be known up front,
+    // adding new values to an existing dictionary would require remapping all indices.
     let mut mapping = unique_values.into_iter().collect::<Vec<_>>();
     mapping.sort();
+    let bloom = BloomIndex::from_strs(mapping.iter().cloned(), dict_size);
     let mut packed_mapping = IndexedPackedStrings::default();
     for s in mapping {
         packed_mapping.push(s);
@@ -106,7 +119,8 @@ pub fn fast_build_string_column<'a, T>(name: &str,
         data_sections.push(DataSection::U8(present));
     }
     let mut column = Column::new(name, len, range, codec, data_sections);
-    column.lz4_encode();
+    column.set_bloom_index(bloom);
+    column.compress();
     Arc::new(column)
 }
 
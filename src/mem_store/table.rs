@@ -1,15 +1,21 @@
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::ops::DerefMut;
 use std::str;
 use std::sync::Arc;
 use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
 
 use disk_store::interface::*;
 use ingest::buffer::Buffer;
 use ingest::input_column::InputColumn;
 use ingest::raw_val::RawVal;
-use mem_store::partition::{Partition, ColumnKey};
+use mem_store::partition::{Partition, ColumnKey, TOMBSTONE_COLUMN};
+use engine::data_types::{BasicType, EncodingType};
+use errors::QueryError;
+use mem_store::column_stats::{Cardinality, ColumnStats};
 use mem_store::*;
+use scheduler::disk_read_scheduler::DiskReadScheduler;
 
 
 pub struct Table {
@@ -18,6 +24,7 @@ pub struct Table {
     partitions: RwLock<HashMap<PartitionID, Arc<Partition>>>,
     buffer: Mutex<Buffer>,
     lru: LRU,
+    column_aliases: RwLock<ColumnAliases>,
 }
 
 impl Table {
@@ -28,6 +35,7 @@ impl Table {
             partitions: RwLock::new(HashMap::new()),
             buffer: Mutex::new(Buffer::default()),
             lru,
+            column_aliases: RwLock::new(ColumnAliases::default()),
         }
     }
 
@@ -35,9 +43,12 @@ impl Table {
         &self.name
     }
 
+    /// Returns the partitions of the table ordered by id, which determines the row numbers of their rows.
     pub fn snapshot(&self) -> Vec<Arc<Partition>> {
         let partitions = self.partitions.read().unwrap();
-        partitions.values().cloned().collect()
+        let mut snapshot = partitions.values().cloned().collect::<Vec<_>>();
+        snapshot.sort_by_key(|partition| partition.id());
+        snapshot
     }
 
     pub fn load_table_metadata(batch_size: usize, storage: &DiskStore, lru: &LRU) -> HashMap<String, Table> {
@@ -46,7 +57,12 @@ impl Table {
             let table = tables
                 .entry(md.tablename.clone())
                 .or_insert_with(|| Table::new(batch_size, &md.tablename, lru.clone()));
-            table.insert_nonresident_partition(&md);
+            table.insert_nonresident_partition(&md, storage);
+        }
+        for (tablename, aliases) in storage.load_column_aliases() {
+            if let Some(table) = tables.get(&tablename) {
+                *table.column_aliases.write().unwrap() = aliases;
+            }
         }
         tables
     }
@@ -61,8 +77,12 @@ impl Table {
         partitions.get(&key.0).map(|p| p.evict(&key.1)).unwrap_or(0)
     }
 
-    pub fn insert_nonresident_partition(&self, md: &PartitionMetadata) {
-        let partition = Arc::new(Partition::nonresident(md.id, md.len, &md.columns, self.lru.clone()));
+    pub fn insert_nonresident_partition(&self, md: &PartitionMetadata, storage: &DiskStore) {
+        let partition = Arc::new(Partition::nonresident(md.id, md.len, &md.columns, md.ingested_at, self.lru.clone()));
+        // Every query reads the tombstone, so it is loaded right away rather than on demand
+        if md.columns.iter().any(|c| c.name == TOMBSTONE_COLUMN) {
+            partition.restore_tombstone(storage.load_column(md.id, TOMBSTONE_COLUMN));
+        }
         let mut partitions = self.partitions.write().unwrap();
         partitions.insert(md.id, partition);
     }
@@ -84,6 +104,31 @@ impl Table {
         self.batch_if_needed(&mut buffer);
     }
 
+    /// Replaces the partition with the given id by a partition that consists of `columns`, or removes it if `columns` is empty.
+    /// Returns the keys of the new columns, which have to be added to the LRU once the columns are stored on disk.
+    pub fn replace_partition(&self, id: PartitionID, columns: Vec<Arc<Column>>) -> Vec<ColumnKey> {
+        self.merge_partitions(&[id], id, columns)
+    }
+
+    /// Atomically replaces the partitions with ids `old` by a single partition with id `id` that consists of `columns`.
+    /// The new partition takes on the latest ingest timestamp of the partitions it replaces.
+    pub fn merge_partitions(&self, old: &[PartitionID], id: PartitionID, columns: Vec<Arc<Column>>) -> Vec<ColumnKey> {
+        let mut partitions = self.partitions.write().unwrap();
+        let mut ingested_at = None;
+        for old_id in old {
+            if let Some(partition) = partitions.remove(old_id) {
+                ingested_at = ingested_at.max(Some(partition.ingested_at()));
+            }
+        }
+        if columns.is_empty() || columns[0].len() == 0 {
+            return vec![];
+        }
+        let ingested_at = ingested_at.unwrap_or_else(SystemTime::now);
+        let (partition, keys) = Partition::new(id, columns, ingested_at, self.lru.clone());
+        partitions.insert(id, Arc::new(partition));
+        keys
+    }
+
     pub fn load_partition(&self, partition: Partition) {
         let mut partitions = self.partitions.write().unwrap();
         partitions.insert(partition.id(), Arc::new(partition));
@@ -146,6 +191,144 @@ impl Table {
         }
     }
 
+    /// Column types are taken from the first partition that contains the column, renamed columns are listed under
+    /// their new name.
+    pub fn schema(&self, drs: &DiskReadScheduler) -> TableSchema {
+        let aliases = self.column_aliases();
+        let mut columns = HashMap::new();
+        for partition in self.snapshot() {
+            let stored = partition.col_names();
+            for (name, summary) in partition.column_summaries(drs) {
+                columns.entry(aliases.current_name(name, &stored).to_string())
+                    .or_insert((summary.basic_type, summary.encoding_type));
+            }
+        }
+        let mut columns = columns.into_iter()
+            .map(|(name, (basic_type, encoding_type))| (name, basic_type, encoding_type))
+            .collect::<Vec<_>>();
+        columns.sort_by(|a, b| a.0.cmp(&b.0));
+        TableSchema {
+            name: self.name().to_string(),
+            columns,
+        }
+    }
+
+    /// Type, value range and cardinality of each column, derived from the encoding and bounds of the column in each
+    /// partition without reading any values. These are retained when a column is evicted, only columns that have not
+    /// been in memory since a restart are loaded from disk.
+    pub fn column_metadata(&self, drs: &DiskReadScheduler) -> Vec<ColumnMetadata> {
+        let aliases = self.column_aliases();
+        let mut columns = HashMap::<String, ColumnMetadata>::new();
+        for partition in self.snapshot() {
+            let stored = partition.col_names();
+            for (name, summary) in partition.column_summaries(drs) {
+                let name = aliases.current_name(name, &stored);
+                let metadata = ColumnMetadata {
+                    name: name.to_string(),
+                    basic_type: summary.basic_type,
+                    rows: summary.len,
+                    null_count: summary.bounds.null_count,
+                    range: summary.bounds.range,
+                    approx_cardinality: summary.approx_cardinality,
+                };
+                match columns.entry(name.to_string()) {
+                    Entry::Occupied(mut entry) => entry.get_mut().merge(&metadata),
+                    Entry::Vacant(entry) => { entry.insert(metadata); }
+                }
+            }
+        }
+        let mut columns = columns.into_iter().map(|(_, metadata)| metadata).collect::<Vec<_>>();
+        columns.sort_by(|a, b| a.name.cmp(&b.name));
+        columns
+    }
+
+    /// Summary statistics for each column, combined over all partitions. Columns that are not in memory are loaded.
+    pub fn describe(&self, drs: &DiskReadScheduler) -> Result<Vec<ColumnDescription>, QueryError> {
+        let aliases = self.column_aliases();
+        let mut stats = HashMap::<String, ColumnStats>::new();
+        for partition in self.snapshot() {
+            let stored = partition.col_names();
+            for &name in &stored {
+                let column = match partition.column(name, drs) {
+                    Some(column) => column,
+                    None => continue,
+                };
+                let column_stats = column.stats(1)?;
+                match stats.entry(aliases.current_name(name, &stored).to_string()) {
+                    Entry::Occupied(mut entry) => entry.get_mut().merge(&column_stats),
+                    Entry::Vacant(entry) => { entry.insert(column_stats); }
+                }
+            }
+        }
+        let mut descriptions = stats.into_iter()
+            .map(|(name, stats)| ColumnDescription {
+                mean: stats.mean(),
+                name,
+                rows: stats.len,
+                null_count: stats.null_count,
+                min: stats.min,
+                max: stats.max,
+                approx_distinct: stats.approx_distinct,
+            })
+            .collect::<Vec<_>>();
+        descriptions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(descriptions)
+    }
+
+    /// Makes the data of column `old` available under the name `new` without rewriting any partitions.
+    /// Partitions that contain a column named `new`, e.g. because it was ingested after the rename, use that column
+    /// instead. The aliases of the table are stored in `storage`.
+    pub fn rename_column(&self, old: &str, new: &str, storage: &DiskStore) -> Result<(), QueryError> {
+        let mut aliases = self.column_aliases.write().unwrap();
+        if let Some(renamed) = aliases.new_name(old) {
+            bail!(QueryError::NotImplemented, "Column {} has been renamed to {}!", old, renamed);
+        }
+        if aliases.stored_name(old).is_some() {
+            bail!(QueryError::NotImplemented, "Column {} has been renamed before, renaming it again is not supported!", old);
+        }
+        let partitions = self.snapshot();
+        let stored = |name: &str| partitions.iter().any(|partition| partition.col_names().contains(&name));
+        if !stored(old) {
+            bail!(QueryError::NotImplemented, "Column {} does not exist!", old);
+        }
+        if aliases.stored_name(new).is_some() || stored(new) {
+            bail!(QueryError::NotImplemented, "Column {} already exists!", new);
+        }
+        aliases.insert(new, old);
+        // Stored while holding the lock so that concurrent renames are not stored out of order
+        storage.store_column_aliases(&self.name, &aliases);
+        Ok(())
+    }
+
+    /// Adds the aliases `other` of a table with the same name in another database, see `InnerLocustDB::import_from`.
+    /// Fails without adding any alias if a column has been renamed differently in both tables.
+    pub fn import_column_aliases(&self, other: &ColumnAliases, storage: &DiskStore) -> Result<(), QueryError> {
+        let mut aliases = self.column_aliases.write().unwrap();
+        Table::ensure_compatible_aliases(&self.name, &aliases, other)?;
+        for (new, old) in other.iter() {
+            aliases.insert(new, old);
+        }
+        storage.store_column_aliases(&self.name, &aliases);
+        Ok(())
+    }
+
+    /// Fails if a column has been renamed differently in `aliases` and `other`.
+    pub fn ensure_compatible_aliases(table: &str, aliases: &ColumnAliases, other: &ColumnAliases) -> Result<(), QueryError> {
+        for (new, old) in other.iter() {
+            let stored_name = aliases.stored_name(new).unwrap_or(old);
+            let new_name = aliases.new_name(old).unwrap_or(new);
+            if stored_name != old || new_name != new {
+                bail!(QueryError::NotImplemented,
+                      "Column {} of table {} has been renamed to {} in one database but not in the other!", old, table, new);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn column_aliases(&self) -> ColumnAliases {
+        self.column_aliases.read().unwrap().clone()
+    }
+
     pub fn heap_size_of_children(&self) -> usize {
         let batches_size: usize = {
             let batches = self.partitions.read().unwrap();
@@ -195,4 +378,59 @@ pub struct TableStats {
     pub size_per_column: Vec<(String, usize)>,
 }
 
+/// Name, decoded type and encoding of each column of a table, sorted by column name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<(String, BasicType, EncodingType)>,
+}
+
+/// Metadata of a single column as returned by `LocustDB::column_metadata`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMetadata {
+    pub name: String,
+    pub basic_type: BasicType,
+    pub rows: usize,
+    /// Number of nulls, `None` if not known for some partition.
+    pub null_count: Option<usize>,
+    /// Decoded min/max value of integer columns, `None` if not known for some partition.
+    pub range: Option<(i64, i64)>,
+    /// Number of distinct values. Cardinalities of different partitions are added, which overestimates the number of
+    /// values that occur in more than one partition.
+    pub approx_cardinality: Cardinality,
+}
+
+impl ColumnMetadata {
+    fn merge(&mut self, other: &ColumnMetadata) {
+        self.rows += other.rows;
+        self.null_count = match (self.null_count, other.null_count) {
+            (Some(n1), Some(n2)) => Some(n1 + n2),
+            _ => None,
+        };
+        self.range = match (self.range, other.range) {
+            (Some((min1, max1)), Some((min2, max2))) => Some((min1.min(min2), max1.max(max2))),
+            _ => None,
+        };
+        let cardinality = self.approx_cardinality.value() + other.approx_cardinality.value();
+        self.approx_cardinality = match (self.approx_cardinality, other.approx_cardinality) {
+            (Cardinality::Estimate(_), _) | (_, Cardinality::Estimate(_)) => Cardinality::Estimate(cardinality),
+            (Cardinality::Exact(0), c) | (c, Cardinality::Exact(0)) => c,
+            _ => Cardinality::AtMost(cardinality),
+        };
+    }
+}
+
+/// Summary statistics of a single column as returned by `LocustDB::describe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDescription {
+    pub name: String,
+    pub rows: usize,
+    pub null_count: usize,
+    pub min: RawVal,
+    pub max: RawVal,
+    /// Mean of all non-null values, `None` for non-integer columns.
+    pub mean: Option<f64>,
+    pub approx_distinct: u64,
+}
+
 
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use syntax::expression::Func2Type;
+
+/// Decoded min/max values of the integer columns of a batch.
+/// Used to skip batches for which no row can satisfy a comparison with a constant.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ZoneMap {
+    ranges: HashMap<String, (i64, i64)>,
+}
+
+impl ZoneMap {
+    pub fn from_ranges(ranges: HashMap<String, (i64, i64)>) -> ZoneMap {
+        ZoneMap { ranges }
+    }
+
+    pub fn range(&self, column: &str) -> Option<(i64, i64)> {
+        self.ranges.get(column).cloned()
+    }
+
+    /// Returns true if no value of `column` can satisfy `column <op> value`.
+    pub fn excludes(&self, column: &str, op: Func2Type, value: i64) -> bool {
+        match self.range(column) {
+            Some((min, max)) => match op {
+                Func2Type::Equals => value < min || value > max,
+                Func2Type::LT => min >= value,
+                Func2Type::LTE => min > value,
+                Func2Type::GT => max <= value,
+                Func2Type::GTE => max < value,
+                _ => false,
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excludes() {
+        let mut ranges = HashMap::new();
+        ranges.insert("a".to_string(), (-10, 20));
+        let zone_map = ZoneMap { ranges };
+        assert!(zone_map.excludes("a", Func2Type::Equals, 21));
+        assert!(!zone_map.excludes("a", Func2Type::Equals, -10));
+        assert!(zone_map.excludes("a", Func2Type::LT, -10));
+        assert!(!zone_map.excludes("a", Func2Type::LTE, -10));
+        assert!(zone_map.excludes("a", Func2Type::GT, 20));
+        assert!(!zone_map.excludes("a", Func2Type::GTE, 20));
+        assert!(!zone_map.excludes("a", Func2Type::NotEquals, 0));
+        assert!(!zone_map.excludes("b", Func2Type::Equals, 100));
+    }
+}
@@ -0,0 +1,43 @@
+extern crate zstd;
+
+use std::mem;
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+
+pub fn encode<T>(data: &[T]) -> Vec<u8> {
+    let data_u8: &[u8] = unsafe {
+        from_raw_parts(data.as_ptr() as *const u8, data.len() * mem::size_of::<T>())
+    };
+    let mut result = zstd::block::compress(data_u8, COMPRESSION_LEVEL).unwrap();
+    result.shrink_to_fit();
+    result
+}
+
+/// Decompresses all of `src` into `dst`, which has to be large enough to hold the decompressed values.
+/// Returns the number of decoded elements.
+pub fn decode<T>(src: &[u8], dst: &mut [T]) -> usize {
+    let dst_u8: &mut [u8] = unsafe {
+        from_raw_parts_mut(dst.as_mut_ptr() as *mut u8, dst.len() * mem::size_of::<T>())
+    };
+    let read = zstd::block::decompress_to_buffer(src, dst_u8).unwrap();
+    assert_eq!(read % mem::size_of::<T>(), 0);
+    read / mem::size_of::<T>()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let data = vec![10i64, 12095, -51235, 3, 0, 0, 12353, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10];
+        let encoded = encode(&data);
+        let mut decoded = vec![0i64; data.len()];
+        let count = decode(&encoded, &mut decoded);
+        assert_eq!(count, data.len());
+        assert_eq!(decoded, data);
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitvec::BitVec;
+use errors::QueryError;
+use ingest::raw_val::RawVal;
+use mem_store::column_builder::rebuild_column;
+use mem_store::partition::Partition;
+use mem_store::*;
+use scheduler::disk_read_scheduler::DiskReadScheduler;
+
+/// Merges partitions with fewer than `threshold` rows into larger partitions.
+pub struct Compactor {
+    threshold: usize,
+}
+
+impl Compactor {
+    pub fn new(threshold: usize) -> Compactor {
+        Compactor { threshold }
+    }
+
+    /// Groups the partitions below the threshold by ascending id, each group has at least two partitions and adds up
+    /// to at least `threshold` rows unless it consists of the remaining small partitions ingested in the same second.
+    /// Only partitions that are ingested in the same second are merged, since `AS OF` queries could tell them apart
+    /// otherwise.
+    pub fn plan(&self, partitions: &[Arc<Partition>]) -> Vec<Vec<Arc<Partition>>> {
+        let mut small = partitions.iter()
+            .filter(|partition| partition.len() < self.threshold)
+            .cloned()
+            .collect::<Vec<_>>();
+        small.sort_by_key(|partition| partition.id());
+
+        let mut groups = Vec::new();
+        let mut group = Vec::<Arc<Partition>>::new();
+        let mut rows = 0;
+        for partition in small {
+            let same_second = group.first()
+                .map_or(true, |first| as_of_second(first.ingested_at()) == as_of_second(partition.ingested_at()));
+            if !same_second {
+                if group.len() > 1 {
+                    groups.push(group);
+                }
+                group = Vec::new();
+                rows = 0;
+            }
+            rows += partition.len();
+            group.push(partition);
+            if rows >= self.threshold {
+                groups.push(group);
+                group = Vec::new();
+                rows = 0;
+            }
+        }
+        if group.len() > 1 {
+            groups.push(group);
+        }
+        groups
+    }
+
+    /// Concatenates the columns of `partitions`, which must contain at least two partitions, leaving out deleted rows.
+    /// Columns that are present in all partitions are combined with `Column::merge` unless rows have been deleted.
+    /// A renamed column is merged under its new name if some partition stores it under that name already.
+    pub fn merge(partitions: &[Arc<Partition>], aliases: &ColumnAliases, drs: &DiskReadScheduler) -> Result<Vec<Arc<Column>>, QueryError> {
+        let mut names = Vec::<String>::new();
+        for partition in partitions {
+            let stored = partition.col_names();
+            for name in &stored {
+                let name = aliases.current_name(name, &stored);
+                if !names.iter().any(|existing| existing == name) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        let column = |partition: &Partition, name: &str| partition.column(name, drs)
+            .or_else(|| aliases.stored_name(name).and_then(|old| partition.column(old, drs)));
+
+        let mut deleted_rows = Vec::with_capacity(partitions.len());
+        for partition in partitions {
+            deleted_rows.push(partition.deleted_rows()?);
+        }
+        let any_deleted = deleted_rows.iter().any(Option::is_some);
+        let mut columns = Vec::with_capacity(names.len());
+        for name in &names {
+            let name = match aliases.stored_name(name) {
+                Some(old) if !partitions.iter().any(|partition| partition.col_names().contains(&name.as_str())) => old,
+                _ => name.as_str(),
+            };
+            if !any_deleted {
+                let parts = partitions.iter()
+                    .filter_map(|partition| column(partition, name))
+                    .filter(|part| part.name() == name)
+                    .collect::<Vec<_>>();
+                if parts.len() == partitions.len() {
+                    let mut merged = parts[0].merge(&parts[1])?;
+                    for part in &parts[2..] {
+                        merged = merged.merge(part)?;
+                    }
+                    columns.push(Arc::new(merged));
+                    continue;
+                }
+            }
+            let mut values = Vec::new();
+            let mut types = Vec::with_capacity(partitions.len());
+            for (partition, deleted) in partitions.iter().zip(deleted_rows.iter()) {
+                let decoded = match column(partition, name) {
+                    Some(column) => {
+                        types.push(column.basic_type());
+                        column.decode()?
+                    }
+                    None => vec![RawVal::Null; partition.len()],
+                };
+                match *deleted {
+                    Some(ref deleted) => values.extend(decoded.into_iter()
+                        .enumerate()
+                        .filter(|&(i, _)| !deleted.is_set(i))
+                        .map(|(_, value)| value)),
+                    None => values.extend(decoded),
+                }
+            }
+            columns.push(rebuild_column(name, &types, values));
+        }
+        Ok(columns)
+    }
+}
+
+/// Earliest `AS OF` timestamp, in whole seconds since the epoch, at which a partition ingested at `ingested_at` is visible.
+fn as_of_second(ingested_at: SystemTime) -> u64 {
+    let since_epoch = ingested_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_secs() + (since_epoch.subsec_nanos() > 0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use mem_store::integers::IntegerColumn;
+
+    fn partition(id: u64, len: usize) -> Arc<Partition> {
+        partition_at(id, len, UNIX_EPOCH + Duration::from_secs(1000))
+    }
+
+    fn partition_at(id: u64, len: usize, ingested_at: SystemTime) -> Arc<Partition> {
+        let column = IntegerColumn::new_boxed("a", vec![0; len], 0, 0, false, None, 0);
+        Arc::new(Partition::new(id, vec![column], ingested_at, LRU::default()).0)
+    }
+
+    #[test]
+    fn test_plan() {
+        let compactor = Compactor::new(10);
+        let partitions = vec![partition(3, 4), partition(1, 6), partition(2, 20), partition(4, 5), partition(5, 2)];
+        let groups = compactor.plan(&partitions).iter()
+            .map(|group| group.iter().map(|partition| partition.id()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![vec![1, 3], vec![4, 5]]);
+    }
+
+    #[test]
+    fn test_plan_ingest_seconds() {
+        let compactor = Compactor::new(10);
+        let at = |nanos: u64| UNIX_EPOCH + Duration::from_nanos(nanos);
+        let partitions = vec![
+            partition_at(1, 2, at(1_000_000_001)),
+            partition_at(2, 2, at(2_000_000_000)),
+            partition_at(3, 2, at(2_000_000_001)),
+            partition_at(4, 2, at(2_999_999_999)),
+            partition_at(5, 2, at(3_000_000_000)),
+        ];
+        let groups = compactor.plan(&partitions).iter()
+            .map(|group| group.iter().map(|partition| partition.id()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // AS OF 2 includes partitions 1 and 2 but not 3, AS OF 3 includes 3, 4 and 5
+        assert_eq!(groups, vec![vec![1, 2], vec![3, 4, 5]]);
+    }
+}
@@ -151,6 +151,7 @@ impl DiskReadScheduler {
                         handle.update_size_bytes(column.heap_size_of_children());
                     }
                 }
+                handle.update_summary(&column);
                 let column = Arc::new(column);
                 *maybe_column = Some(column.clone());
                 handle.set_resident();
@@ -4,13 +4,15 @@ use std::str;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use futures_core::*;
 use futures_channel::oneshot;
 use time;
 
 use disk_store::interface::*;
+use engine::execution::update;
+use errors::QueryError;
 use ingest::colgen::GenTable;
 use ingest::input_column::InputColumn;
 use ingest::raw_val::RawVal;
@@ -18,8 +20,11 @@ use locustdb::Options;
 use mem_store::*;
 use mem_store::partition::Partition;
 use mem_store::table::*;
+use mem_store::table::ColumnMetadata;
 use scheduler::*;
+use scheduler::compactor::Compactor;
 use scheduler::disk_read_scheduler::DiskReadScheduler;
+use syntax::expression::Expr;
 use trace::*;
 
 
@@ -33,6 +38,8 @@ pub struct InnerLocustDB {
 
     next_partition_id: AtomicUsize,
     running: AtomicBool,
+    /// Held by statements that modify existing partitions, so that they don't overwrite each other's changes.
+    mutation_lock: Mutex<()>,
     idle_queue: Condvar,
     task_queue: Mutex<VecDeque<Arc<TaskState>>>,
 }
@@ -67,6 +74,7 @@ impl InnerLocustDB {
             storage,
             disk_read_scheduler,
             running: AtomicBool::new(true),
+            mutation_lock: Mutex::new(()),
 
             opts: opts.clone(),
 
@@ -77,12 +85,20 @@ impl InnerLocustDB {
     }
 
     pub fn start_worker_threads(locustdb: &Arc<InnerLocustDB>) {
+        // Threads can't be spawned in the browser, tasks are executed by `run_pending_tasks` instead
+        if cfg!(target_arch = "wasm32") {
+            return;
+        }
         for id in 0..locustdb.opts.threads {
             let cloned = locustdb.clone();
             thread::spawn(move || InnerLocustDB::worker_loop(cloned, id));
         }
         let cloned = locustdb.clone();
         thread::spawn(move || InnerLocustDB::enforce_mem_limit(&cloned));
+        if let Some(threshold) = locustdb.opts.compaction_threshold {
+            let cloned = locustdb.clone();
+            thread::spawn(move || InnerLocustDB::compact_periodically(&cloned, threshold));
+        }
     }
 
     pub fn snapshot(&self, table: &str) -> Option<Vec<Arc<Partition>>> {
@@ -121,6 +137,27 @@ impl InnerLocustDB {
         drop(locustdb) // Make clippy happy
     }
 
+    /// Executes scheduled tasks on the calling thread until the task queue is empty.
+    #[cfg(feature = "enable_wasm")]
+    pub fn run_pending_tasks(&self) {
+        loop {
+            let task = match self.task_queue.lock().unwrap().pop_front() {
+                Some(task) => task,
+                None => return,
+            };
+            if task.task.completed() {
+                continue;
+            }
+            if let Some(ref tb) = *task.trace_builder.read().unwrap() {
+                tb.activate();
+            }
+            task.task.execute();
+            if let Some(ref mut tb) = *task.trace_builder.write().unwrap() {
+                tb.collect();
+            }
+        }
+    }
+
     fn await_task(ldb: &Arc<InnerLocustDB>) -> Option<Arc<TaskState>> {
         let mut task_queue = ldb.task_queue.lock().unwrap();
         while task_queue.is_empty() {
@@ -158,12 +195,17 @@ impl InnerLocustDB {
     }
 
     pub fn store_partition(&self, tablename: &str, partition: Vec<Arc<Column>>) {
+        self.store_partition_at(tablename, partition, SystemTime::now())
+    }
+
+    /// Adds `partition` to `tablename` with `ingested_at` as its ingest timestamp.
+    pub fn store_partition_at(&self, tablename: &str, partition: Vec<Arc<Column>>, ingested_at: SystemTime) {
         self.create_if_empty(tablename);
         let tables = self.tables.read().unwrap();
         let table = tables.get(tablename).unwrap();
         let pid = self.next_partition_id.fetch_add(1, Ordering::SeqCst) as u64;
-        self.storage.store_partition(pid, tablename, &partition);
-        let (new_partition, keys) = Partition::new(pid, partition, self.lru.clone());
+        self.storage.store_partition(pid, tablename, ingested_at, &partition);
+        let (new_partition, keys) = Partition::new(pid, partition, ingested_at, self.lru.clone());
         table.load_partition(new_partition);
         for key in keys { self.lru.put(key); }
     }
@@ -205,6 +247,204 @@ impl InnerLocustDB {
         tables.values().map(|table| { table.mem_tree(depth) }).collect()
     }
 
+    pub fn schema(&self, table: &str) -> Option<TableSchema> {
+        let tables = self.tables.read().unwrap();
+        tables.get(table).map(|table| table.schema(&self.disk_read_scheduler))
+    }
+
+    pub fn column_metadata(&self, table: &str) -> Option<Vec<ColumnMetadata>> {
+        let tables = self.tables.read().unwrap();
+        tables.get(table).map(|table| table.column_metadata(&self.disk_read_scheduler))
+    }
+
+    pub fn describe(&self, table: &str) -> Result<Vec<ColumnDescription>, QueryError> {
+        let tables = self.tables.read().unwrap();
+        match tables.get(table) {
+            Some(table) => table.describe(&self.disk_read_scheduler),
+            None => Err(QueryError::NotImplemented(format!("Table {} does not exist!", table))),
+        }
+    }
+
+    pub fn rename_column(&self, table: &str, old: &str, new: &str) -> Result<(), QueryError> {
+        let tables = self.tables.read().unwrap();
+        match tables.get(table) {
+            Some(table) => table.rename_column(old, new, self.storage.as_ref()),
+            None => Err(QueryError::NotImplemented(format!("Table {} does not exist!", table))),
+        }
+    }
+
+    pub fn column_aliases(&self, table: &str) -> ColumnAliases {
+        let tables = self.tables.read().unwrap();
+        tables.get(table).map(|table| table.column_aliases()).unwrap_or_default()
+    }
+
+    /// Sets `column` to `value` in all rows of `table` that satisfy `filter` and returns the number of updated rows.
+    /// Updated columns are stored on disk with the partition. All partitions are updated only once the new columns
+    /// have been built for each of them, so an update that fails leaves the table unchanged. Concurrent queries may
+    /// still observe a partially applied update.
+    pub fn update(&self, table: &str, filter: &Expr, column: &str, value: &RawVal) -> Result<usize, QueryError> {
+        let _mutation = self.mutation_lock.lock().unwrap();
+        let partitions = match self.snapshot(table) {
+            Some(partitions) => partitions,
+            None => bail!(QueryError::UnknownTable, table),
+        };
+        let aliases = self.column_aliases(table);
+        let stored_name = aliases.stored_name(column).unwrap_or(column);
+        if !partitions.iter().any(|partition| partition.col_names().iter().any(|&name| name == column || name == stored_name)) {
+            let mut available = partitions.iter()
+                .flat_map(|partition| {
+                    let stored = partition.col_names();
+                    stored.iter().map(|name| aliases.current_name(name, &stored).to_string()).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            available.sort();
+            available.dedup();
+            return Err(QueryError::UnknownColumn(column.to_string(), available.join(", ")));
+        }
+        let mut updated_columns = Vec::new();
+        let mut row_offset = 0;
+        for partition in &partitions {
+            if let Some(update) = update::update_partition(partition, row_offset, filter, column, value, &aliases, &self.disk_read_scheduler)? {
+                updated_columns.push((partition, update));
+            }
+            row_offset += partition.len();
+        }
+        let mut updated = 0;
+        for (partition, (updated_column, count)) in updated_columns {
+            self.storage.store_column(partition.id(), &updated_column);
+            partition.replace(updated_column);
+            updated += count;
+        }
+        Ok(updated)
+    }
+
+    /// Marks all rows of `table` that satisfy `filter` as deleted and returns the number of deleted rows.
+    /// The tombstones that mark deleted rows are stored on disk with the partition.
+    pub fn delete(&self, table: &str, filter: &Expr) -> Result<usize, QueryError> {
+        let _mutation = self.mutation_lock.lock().unwrap();
+        let partitions = match self.snapshot(table) {
+            Some(partitions) => partitions,
+            None => bail!(QueryError::UnknownTable, table),
+        };
+        let aliases = self.column_aliases(table);
+        let mut deleted = 0;
+        let mut row_offset = 0;
+        for partition in &partitions {
+            let count = update::delete_partition(partition, row_offset, filter, &aliases, &self.disk_read_scheduler)?;
+            if count > 0 {
+                if let Some(tombstone) = partition.tombstone() {
+                    self.storage.store_column(partition.id(), &tombstone);
+                }
+            }
+            deleted += count;
+            row_offset += partition.len();
+        }
+        Ok(deleted)
+    }
+
+    /// Rewrites all partitions of `table` that contain deleted rows and returns the number of removed rows.
+    /// Rewritten partitions replace the original ones on disk, partitions without any remaining rows are removed.
+    pub fn vacuum(&self, table_name: &str) -> Result<usize, QueryError> {
+        // Rows deleted between reading the tombstone and replacing the partition would be restored otherwise
+        let _mutation = self.mutation_lock.lock().unwrap();
+        let tables = self.tables.read().unwrap();
+        let table = match tables.get(table_name) {
+            Some(table) => table,
+            None => bail!(QueryError::UnknownTable, table_name),
+        };
+        let mut removed = 0;
+        for partition in table.snapshot() {
+            if let Some((columns, count)) = update::vacuum_partition(&partition, &self.disk_read_scheduler)? {
+                if columns.is_empty() || columns[0].len() == 0 {
+                    self.storage.delete_partition(partition.id());
+                } else {
+                    self.storage.store_partition(partition.id(), table_name, partition.ingested_at(), &columns);
+                }
+                for key in table.replace_partition(partition.id(), columns) { self.lru.put(key); }
+                removed += count;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Merges partitions with fewer than `threshold` rows into larger ones and returns the number of merged partitions.
+    /// Merged partitions replace the partitions they were created from on disk.
+    pub fn compact(&self, threshold: usize) -> Result<usize, QueryError> {
+        // Rows that are deleted or updated in a partition while it is being merged would be restored otherwise
+        let _mutation = self.mutation_lock.lock().unwrap();
+        let compactor = Compactor::new(threshold);
+        let table_names = self.tables.read().unwrap().keys().cloned().collect::<Vec<_>>();
+        let mut merged = 0;
+        for table_name in table_names {
+            let partitions = match self.snapshot(&table_name) {
+                Some(partitions) => partitions,
+                None => continue,
+            };
+            for group in compactor.plan(&partitions) {
+                let columns = Compactor::merge(&group, &self.column_aliases(&table_name), &self.disk_read_scheduler)?;
+                let pid = self.next_partition_id.fetch_add(1, Ordering::SeqCst) as u64;
+                let old_ids = group.iter().map(|partition| partition.id()).collect::<Vec<_>>();
+                let ingested_at = group.iter().map(|partition| partition.ingested_at()).max().unwrap();
+                let tables = self.tables.read().unwrap();
+                if let Some(table) = tables.get(&table_name) {
+                    if columns.is_empty() || columns[0].len() == 0 {
+                        for &id in &old_ids {
+                            self.storage.delete_partition(id);
+                        }
+                    } else {
+                        self.storage.replace_partitions(&old_ids, pid, &table_name, ingested_at, &columns);
+                    }
+                    for key in table.merge_partitions(&old_ids, pid, columns) { self.lru.put(key); }
+                    merged += old_ids.len();
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Adds all partitions of all tables in `other` to the tables of the same name and returns the number of rows added.
+    /// Columns are shared with `other` rather than copied. Partitions with deleted rows are rewritten without them.
+    /// Renamed columns are renamed in the tables they are added to as well, nothing is imported if a column has been
+    /// renamed differently in both databases.
+    pub fn import_from(&self, other: &InnerLocustDB) -> Result<usize, QueryError> {
+        // Rows deleted or updated in `other` during the import would otherwise be imported in some partitions only
+        let _mutation = other.mutation_lock.lock().unwrap();
+        let table_names = other.tables.read().unwrap().keys().cloned().collect::<Vec<_>>();
+        for table_name in &table_names {
+            Table::ensure_compatible_aliases(table_name, &self.column_aliases(table_name), &other.column_aliases(table_name))?;
+        }
+        let mut imported = 0;
+        for table_name in table_names {
+            let partitions = match other.snapshot(&table_name) {
+                Some(partitions) => partitions,
+                None => continue,
+            };
+            let aliases = other.column_aliases(&table_name);
+            for partition in partitions {
+                let columns = match update::vacuum_partition(&partition, &other.disk_read_scheduler)? {
+                    Some((columns, deleted)) => {
+                        imported += partition.len() - deleted;
+                        columns
+                    }
+                    None => {
+                        imported += partition.len();
+                        partition.col_names().into_iter()
+                            .filter_map(|name| partition.column(name, &other.disk_read_scheduler))
+                            .collect()
+                    }
+                };
+                self.store_partition_at(&table_name, columns, partition.ingested_at());
+            }
+            if !aliases.is_empty() {
+                let tables = self.tables.read().unwrap();
+                if let Some(table) = tables.get(&table_name) {
+                    table.import_column_aliases(&aliases, self.storage.as_ref())?;
+                }
+            }
+        }
+        Ok(imported)
+    }
+
     pub fn stats(&self) -> Vec<TableStats> {
         let tables = self.tables.read().unwrap();
         tables.values().map(|table| table.stats()).collect()
@@ -227,7 +467,7 @@ impl InnerLocustDB {
                     Table::new(1 << 20, table, self.lru.clone()));
             }
             self.ingest("_meta_tables", vec![
-                ("timestamp".to_string(), RawVal::Int(time::now().to_timespec().sec)),
+                ("timestamp".to_string(), RawVal::Int(time::get_time().sec)),
                 ("name".to_string(), RawVal::Str(table.to_string())),
             ]);
         }
@@ -263,6 +503,17 @@ impl InnerLocustDB {
         }
     }
 
+    fn compact_periodically(ldb: &Arc<InnerLocustDB>, threshold: usize) {
+        while ldb.running.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(1000));
+            match ldb.compact(threshold) {
+                Ok(0) => {}
+                Ok(merged) => info!("Compacted {} partitions", merged),
+                Err(err) => warn!("Compaction failed: {:?}", err),
+            }
+        }
+    }
+
     pub fn max_partition_id(&self) -> u64 {
         self.next_partition_id.load(Ordering::SeqCst) as u64
     }
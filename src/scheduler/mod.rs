@@ -1,4 +1,5 @@
 mod shared_sender;
+mod compactor;
 mod task;
 pub(crate) mod disk_read_scheduler;
 pub(crate) mod inner_locustdb;
@@ -3,16 +3,19 @@ use self::Expr::*;
 use std::collections::HashSet;
 use engine::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
 pub enum Expr {
     ColName(String),
     Const(RawVal),
     Func1(Func1Type, Box<Expr>),
     Func2(Func2Type, Box<Expr>, Box<Expr>),
-    Aggregate(Aggregator, Box<Expr>),
+    /// Aggregation with an optional filter that restricts the aggregate to rows for which it is true.
+    Aggregate(Aggregator, Box<Expr>, Option<Box<Expr>>),
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
 pub enum Func2Type {
     Equals,
     NotEquals,
@@ -31,7 +34,23 @@ pub enum Func2Type {
     Like,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Func2Type {
+    /// Returns the comparison `op'` such that `a op b` is equivalent to `b op' a`.
+    pub fn flip_comparison(self) -> Option<Func2Type> {
+        match self {
+            Func2Type::Equals => Some(Func2Type::Equals),
+            Func2Type::NotEquals => Some(Func2Type::NotEquals),
+            Func2Type::LT => Some(Func2Type::GT),
+            Func2Type::LTE => Some(Func2Type::GTE),
+            Func2Type::GT => Some(Func2Type::LT),
+            Func2Type::GTE => Some(Func2Type::LTE),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
 pub enum Func1Type {
     Negate,
     ToYear,
@@ -39,6 +58,15 @@ pub enum Func1Type {
     IsNull,
     IsNotNull,
     Length,
+    CharLength,
+    Lower,
+    Upper,
+    /// Characters at the 1-based positions `start..start + length`.
+    Substring { start: i64, length: i64 },
+    /// `CAST(expr AS INTEGER)`, strings that are not integers become null.
+    CastInt,
+    /// `CAST(expr AS VARCHAR)`.
+    CastString,
 }
 
 impl Expr {
@@ -52,7 +80,12 @@ impl Expr {
                 expr2.add_colnames(result);
             }
             Func1(_, ref expr) => expr.add_colnames(result),
-            Aggregate(_, ref expr) => expr.add_colnames(result),
+            Aggregate(_, ref expr, ref filter) => {
+                expr.add_colnames(result);
+                if let Some(filter) = filter {
+                    filter.add_colnames(result);
+                }
+            }
             Const(_) => {}
         }
     }
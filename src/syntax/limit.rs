@@ -1,5 +1,17 @@
+use syntax::expression::Expr;
+
 #[derive(Clone, Debug, Hash, PartialEq)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
 pub struct LimitClause {
     pub limit: u64,
     pub offset: u64,
 }
+
+/// Retains at most `limit` rows for each distinct combination of values of the `by` expressions, analogous to
+/// `LIMIT <limit> BY <by>` in ClickHouse. Each expression has to be selected by the query.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
+pub struct LimitBy {
+    pub limit: u64,
+    pub by: Vec<Expr>,
+}
@@ -1,3 +1,6 @@
 pub mod expression;
 pub mod limit;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+#[cfg(feature = "enable_proto")]
+pub mod proto;
+pub mod sample;
\ No newline at end of file
@@ -3,59 +3,614 @@ extern crate sqlparser;
 use sqlparser::sqlparser::*;
 use sqlparser::sqlast::*;
 use engine::*;
+use hyperloglog;
 use syntax::expression::*;
 use ingest::raw_val::RawVal;
 use syntax::limit::*;
+use syntax::sample::*;
 use sqlparser::dialect::GenericSqlDialect;
 use QueryError;
+use regex::{Captures, Regex};
+use std::i64;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // Convert sqlparser-rs `ASTNode` to LocustDB's `Query`
 pub fn parse_query(query: &str) -> Result<Query, QueryError> {
+    let (query, explain) = strip_keyword(query, "EXPLAIN");
+    let (query, analyze) = if explain { strip_keyword(query, "ANALYZE") } else { (query, false) };
+    let (query, sample, reservoir) = extract_sample_clause(query)?;
+    let (query, as_of) = extract_as_of_clause(&query)?;
+    let (query, offset) = extract_offset_clause(&query)?;
+    let (query, limit_by) = extract_limit_by_clause(&query)?;
+    let (query, mut aliases) = extract_aliases(&query);
+    let (query, nulls_orders) = extract_nulls_orders(&query);
+    let query = rewrite_not_operators(&rewrite_negative_literals(&rewrite_regex_operators(&query)));
     let dialect = GenericSqlDialect {};
-    let ast = Parser::parse_sql(&dialect, query.to_string())
+    let ast = Parser::parse_sql(&dialect, query)
         .map_err(|e| match e {
             ParserError::ParserError(e_str) => QueryError::ParseError(e_str),
             _ => fatal!("{:?}", e),
         })?;
 
-    let (projection, relation, selection, order_by, limit) = get_query_components(ast)?;
+    let (projection, relation, selection, group_by, having, order_by, limit) = get_query_components(ast)?;
     let projection = get_projection(projection)?;
+    aliases.resize(projection.len(), None);
+    let select = projection.into_iter().zip(aliases).collect::<Vec<_>>();
     let table = get_table_name(relation)?;
     let filter = match selection {
         Some(ref s) => *expr(s)?,
         None => Expr::Const(RawVal::Int(1)),
     };
-    let order_by = get_order_by(order_by)?;
-    let limit_clause = LimitClause { limit: get_limit(limit)?, offset: 0 };
+    let group_by = match group_by {
+        Some(group_by) => group_by.iter()
+            .map(|node| Ok((*expr(node)?, false)))
+            .collect::<Result<Vec<_>, QueryError>>()?,
+        None => vec![],
+    };
+    let group_by = resolve_aliases(group_by, &select).into_iter().map(|(expr, _)| expr).collect();
+    let having = match having {
+        Some(ref having) => Some(*expr(having)?),
+        None => None,
+    };
+    let order_by = resolve_aliases(get_order_by(order_by)?, &select).into_iter()
+        .enumerate()
+        .map(|(i, (expr, desc))| {
+            let nulls = nulls_orders.get(i).cloned().and_then(|nulls| nulls);
+            (expr, desc, nulls.unwrap_or_else(|| NullsOrder::default_for(desc)))
+        })
+        .collect();
+    let limit_clause = LimitClause { limit: get_limit(limit)?, offset };
+    let limit_by = match limit_by {
+        Some((limit, by)) => {
+            let by = parse_query(&format!("SELECT {} FROM {}", by, table))?.select.into_iter()
+                .map(|(expr, _)| (expr, false))
+                .collect();
+            Some(LimitBy { limit, by: resolve_aliases(by, &select).into_iter().map(|(expr, _)| expr).collect() })
+        }
+        None => None,
+    };
 
     Ok(Query {
-        select: projection,
+        select,
         table,
         filter,
+        group_by,
+        having,
         order_by,
         limit: limit_clause,
+        limit_by,
+        sample,
+        reservoir,
+        explain: explain && !analyze,
+        analyze,
+        memory_limit_bytes: None,
+        batch_size: None,
+        timeout: None,
+        as_of,
+    })
+}
+
+/// Parses the `filter` and the new `value` of an update to `table`, `value` has to be a literal.
+pub fn parse_update(table: &str, filter: &str, value: &str) -> Result<(Expr, RawVal), QueryError> {
+    let query = parse_query(&format!("SELECT {} FROM {} WHERE {}", value, table, filter))?;
+    let value = match query.select.get(0) {
+        Some(&(Expr::Const(ref value), _)) if query.select.len() == 1 => value.clone(),
+        _ => bail!(QueryError::NotImplemented, "Only literal values are supported in updates, got {}", value),
+    };
+    Ok((query.filter, value))
+}
+
+/// Parses `DELETE FROM <table> [WHERE <filter>]` into table and filter, returns `None` for other statements.
+pub fn parse_delete(statement: &str) -> Option<Result<(String, Expr), QueryError>> {
+    let (query, delete) = strip_keyword(statement, "DELETE");
+    if !delete {
+        return None;
+    }
+    Some(parse_query(&format!("SELECT 1 {}", query)).map(|query| (query.table, query.filter)))
+}
+
+/// Parses `VACUUM <table>` into the table name, returns `None` for other statements.
+pub fn parse_vacuum(statement: &str) -> Option<String> {
+    let (table, vacuum) = strip_keyword(statement, "VACUUM");
+    if vacuum { Some(table.trim().trim_end_matches(';').trim_end().to_string()) } else { None }
+}
+
+/// Parses `<query> UNION ALL <query>`, returns `None` for statements that don't contain `UNION`.
+/// `ORDER BY` and `LIMIT` clauses after the second query apply to the combined result.
+pub fn parse_union(statement: &str) -> Option<Result<UnionAll, QueryError>> {
+    let offsets = top_level_offsets(statement);
+    let union = find_keyword(statement, &offsets, "UNION", 0)?;
+    Some(parse_union_at(statement, &offsets, union))
+}
+
+fn parse_union_at(statement: &str, offsets: &[usize], union: usize) -> Result<UnionAll, QueryError> {
+    let (rest, all) = strip_keyword(&statement[union + "UNION".len()..], "ALL");
+    if !all {
+        bail!(QueryError::NotImplemented, "UNION without ALL")
+    }
+    let right_start = statement.len() - rest.len();
+    let clauses_start = ["ORDER", "LIMIT"].iter()
+        .filter_map(|keyword| find_keyword(statement, offsets, keyword, right_start))
+        .min()
+        .unwrap_or_else(|| statement.len());
+    let left = parse_query(&statement[..union])?;
+    let right = parse_query(&statement[right_start..clauses_start])?;
+    if left.explain || left.analyze {
+        bail!(QueryError::NotImplemented, "EXPLAIN for UNION ALL")
+    }
+    let clauses = parse_query(&format!("SELECT * FROM union_all {}", &statement[clauses_start..]))?;
+    let order_by = clauses.order_by.into_iter()
+        .map(|(expr, desc, nulls)| match expr {
+            Expr::ColName(name) => Ok((name, desc, nulls)),
+            expr => Err(QueryError::NotImplemented(
+                format!("ORDER BY of UNION ALL has to refer to a result column by name, found {:?}", expr))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let (left, right) = push_down_order_by(left, right, &order_by)?;
+    Ok(UnionAll { left, right, order_by, limit: clauses.limit })
+}
+
+// Sorts both queries of a `UNION ALL` by the selected expressions that its `ORDER BY` refers to, so that their results
+// only have to be merged
+fn push_down_order_by(mut left: Query, mut right: Query, order_by: &[(String, bool, NullsOrder)]) -> Result<(Query, Query), QueryError> {
+    if order_by.is_empty() {
+        return Ok((left, right));
+    }
+    if left.is_select_star() || right.is_select_star() {
+        bail!(QueryError::NotImplemented, "ORDER BY of UNION ALL with SELECT *")
+    }
+    let mut left_keys = Vec::with_capacity(order_by.len());
+    let mut right_keys = Vec::with_capacity(order_by.len());
+    for (name, desc, nulls) in order_by {
+        let index = left.select.iter().position(|(expr, alias)| match alias {
+            Some(alias) => alias == name,
+            None => *expr == Expr::ColName(name.to_string()),
+        });
+        let index = match index {
+            Some(index) => index,
+            None => bail!(QueryError::NotImplemented,
+                          "ORDER BY of UNION ALL has to refer to a selected column or alias, found {}", name),
+        };
+        match right.select.get(index) {
+            Some((expr, _)) => right_keys.push((expr.clone(), *desc, *nulls)),
+            None => bail!(QueryError::TypeError, "UNION ALL of queries with {} and {} result columns",
+                          left.select.len(), right.select.len()),
+        }
+        left_keys.push((left.select[index].0.clone(), *desc, *nulls));
+    }
+    left.order_by = left_keys;
+    right.order_by = right_keys;
+    Ok((left, right))
+}
+
+/// Parses a query with `GROUP BY ROLLUP(<exprs>)`, returns `None` for statements that don't contain `ROLLUP(`.
+/// `ORDER BY` and `LIMIT` apply to the combined result, and `ORDER BY` has to refer to selected expressions.
+/// `EXPLAIN` would have to show a plan for each grouping set and fails with `QueryError::NotImplemented`.
+pub fn parse_rollup(statement: &str) -> Option<Result<Rollup, QueryError>> {
+    let offsets = top_level_offsets(statement);
+    let rollup = find_keyword(statement, &offsets, "ROLLUP", 0)?;
+    let rest = statement[rollup + "ROLLUP".len()..].trim_start();
+    if !rest.starts_with('(') {
+        return None;
+    }
+    let open = statement.len() - rest.len();
+    Some(parse_rollup_at(statement, &offsets, rollup, open))
+}
+
+fn parse_rollup_at(statement: &str, offsets: &[usize], rollup: usize, open: usize) -> Result<Rollup, QueryError> {
+    // The first top level character after the opening parenthesis follows the closing parenthesis
+    let end = offsets.iter().cloned().find(|&i| i > open).unwrap_or_else(|| statement.len());
+    if !statement[..end].ends_with(')') {
+        bail!(QueryError::ParseError, "Unbalanced parentheses in ROLLUP")
+    }
+    let args = &statement[open + 1..end - 1];
+    let query = parse_query(&format!("{}{}{}", &statement[..rollup], args, &statement[end..]))?;
+    if query.explain || query.analyze {
+        bail!(QueryError::NotImplemented, "EXPLAIN for ROLLUP")
+    }
+    let rolled_up = parse_query(&format!("SELECT {} FROM {}", args, query.table))?.select.into_iter()
+        .map(|(expr, _)| (expr, false))
+        .collect();
+    let rolled_up = resolve_aliases(rolled_up, &query.select).into_iter().map(|(expr, _)| expr).collect::<Vec<_>>();
+    if !query.group_by.ends_with(&rolled_up) {
+        bail!(QueryError::NotImplemented, "ROLLUP has to be the last item of GROUP BY")
+    }
+    if query.select.iter().all(|(expr, _)| query.group_by.contains(expr)) {
+        bail!(QueryError::NotImplemented, "ROLLUP without aggregates")
+    }
+    let order_by = query.order_by.iter()
+        .map(|(expr, desc, nulls)| match query.select.iter().position(|(selected, _)| selected == expr) {
+            Some(index) => Ok((index, *desc, *nulls)),
+            None => Err(QueryError::NotImplemented(
+                format!("ORDER BY of ROLLUP has to refer to a selected expression, found {:?}", expr))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let limit = query.limit.clone();
+    Ok(Rollup { query, rolled_up: rolled_up.len(), order_by, limit })
+}
+
+// Removes a leading `keyword`, which sqlparser-rs would not be able to parse
+fn strip_keyword<'a>(query: &'a str, keyword: &str) -> (&'a str, bool) {
+    let trimmed = query.trim_start();
+    let len = keyword.len();
+    match trimmed.get(..len) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(keyword) && trimmed[len..].starts_with(char::is_whitespace) =>
+            (&trimmed[len..], true),
+        _ => (query, false),
+    }
+}
+
+// sqlparser-rs does not support `NULLS FIRST` and `NULLS LAST`, so they are removed from the `ORDER BY` clause before
+// parsing. Returns the null order of each `ORDER BY` expression, if specified.
+fn extract_nulls_orders(query: &str) -> (String, Vec<Option<NullsOrder>>) {
+    lazy_static! {
+        static ref NULLS_ORDER: Regex = Regex::new(r"(?i)\s+NULLS\s+(FIRST|LAST)(\s*)$").unwrap();
+    }
+    let offsets = top_level_offsets(query);
+    let start = match find_keyword(query, &offsets, "ORDER", 0) {
+        Some(order) => order + "ORDER".len(),
+        None => return (query.to_string(), vec![]),
+    };
+    let end = find_keyword(query, &offsets, "LIMIT", start)
+        .unwrap_or_else(|| query.trim_end().trim_end_matches(';').len());
+    let item_ends = offsets.iter().cloned()
+        .filter(|&i| i > start && i < end && query[i..].starts_with(','))
+        .chain(Some(end));
+    let mut rewritten = query[..start].to_string();
+    let mut nulls_orders = Vec::new();
+    let mut item_start = start;
+    for item_end in item_ends {
+        let item = &query[item_start..item_end];
+        match NULLS_ORDER.captures(item) {
+            Some(captures) => {
+                nulls_orders.push(Some(if captures[1].eq_ignore_ascii_case("FIRST") {
+                    NullsOrder::First
+                } else {
+                    NullsOrder::Last
+                }));
+                rewritten.push_str(&item[..captures.get(0).unwrap().start()]);
+                // Keeps the whitespace that separates the last item from the next clause
+                rewritten.push_str(&captures[2]);
+            }
+            None => {
+                nulls_orders.push(None);
+                rewritten.push_str(item);
+            }
+        }
+        item_start = item_end;
+    }
+    rewritten.push_str(&query[end..]);
+    (rewritten, nulls_orders)
+}
+
+// sqlparser-rs does not support aliases, so `<expr> AS <alias>` in the projection is replaced by `<expr>` before parsing.
+// Returns the alias of each projection item, if any.
+fn extract_aliases(query: &str) -> (String, Vec<Option<String>>) {
+    lazy_static! {
+        static ref ALIAS: Regex = Regex::new(r"(?is)^(.*\S)\s+AS\s+([A-Za-z_][A-Za-z0-9_]*)\s*$").unwrap();
+    }
+    let offsets = top_level_offsets(query);
+    let start = match find_keyword(query, &offsets, "SELECT", 0) {
+        Some(select) => select + "SELECT".len(),
+        None => return (query.to_string(), vec![]),
+    };
+    let end = find_keyword(query, &offsets, "FROM", start).unwrap_or_else(|| query.len());
+    let separators = offsets.iter()
+        .cloned()
+        .filter(|&i| i > start && i < end && query[i..].starts_with(','))
+        .chain(Some(end));
+
+    let mut rewritten = query[..start].to_string();
+    let mut aliases = Vec::new();
+    let mut item_start = start;
+    for separator in separators {
+        let item = &query[item_start..separator];
+        match ALIAS.captures(item) {
+            Some(captures) => {
+                rewritten.push_str(&captures[1]);
+                rewritten.push(' ');
+                aliases.push(Some(captures[2].to_string()));
+            }
+            None => {
+                rewritten.push_str(item);
+                aliases.push(None);
+            }
+        }
+        if separator < end {
+            rewritten.push(',');
+        }
+        item_start = separator + 1;
+    }
+    rewritten.push_str(&query[end..]);
+    (rewritten, aliases)
+}
+
+// sqlparser-rs does not support the `~` operator, so `<column> ~ '<pattern>'` is replaced by `regex(<column>, '<pattern>')`
+fn rewrite_regex_operators(query: &str) -> String {
+    lazy_static! {
+        static ref REGEX_OPERATOR: Regex = Regex::new(r"\b([A-Za-z_][A-Za-z0-9_]*)\s*~\s*('[^']*')").unwrap();
+    }
+    let quoted = quoted_offsets(query);
+    REGEX_OPERATOR.replace_all(query, |captures: &Captures| {
+        if quoted.binary_search(&captures.get(0).unwrap().start()).is_ok() {
+            captures[0].to_string()
+        } else {
+            format!("regex({}, {})", &captures[1], &captures[2])
+        }
+    }).into_owned()
+}
+
+// sqlparser-rs does not support unary minus, so negative integer literals are replaced by `(0 - <literal>)`, which
+// `expr` folds back into a constant. A minus sign is taken to be unary if it follows an operator, an opening
+// parenthesis, a comma or a keyword.
+fn rewrite_negative_literals(query: &str) -> String {
+    lazy_static! {
+        static ref NEGATIVE_LITERAL: Regex = Regex::new(
+            r"([=<>(,+\-*/%]|\b(?i:SELECT|WHERE|AND|OR|BY|HAVING|WHEN|THEN|ELSE))(\s*)-(\d+)\b").unwrap();
+    }
+    let quoted = quoted_offsets(query);
+    NEGATIVE_LITERAL.replace_all(query, |captures: &Captures| {
+        if quoted.binary_search(&captures.get(0).unwrap().start()).is_ok() {
+            captures[0].to_string()
+        } else {
+            format!("{}{}(0 - {})", &captures[1], &captures[2], &captures[3])
+        }
+    }).into_owned()
+}
+
+// Byte offsets of all characters that are part of a string literal, including the closing quote
+fn quoted_offsets(query: &str) -> Vec<usize> {
+    let mut quote = None;
+    query.char_indices()
+        .filter_map(|(i, c)| {
+            let is_quoted = quote.is_some();
+            match quote {
+                Some(q) => if c == q { quote = None },
+                None => if c == '\'' || c == '"' { quote = Some(c) },
+            }
+            if is_quoted { Some(i) } else { None }
+        })
+        .collect()
+}
+
+// Rewrites `NOT expr` into `logical_not(expr)`, since the SQL parser does not support unary operators.
+// `expr` extends up to the next `AND`, `OR`, comma or clause keyword that is not enclosed in parentheses, which makes
+// `NOT` bind weaker than comparisons and stronger than `AND`. The `NOT` of `IS NOT NULL` is left as is.
+fn rewrite_not_operators(query: &str) -> String {
+    const OPERAND_END: [&str; 10] = ["AND", "OR", "FROM", "WHERE", "GROUP", "ORDER", "HAVING", "LIMIT", "ASC", "DESC"];
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let is_keyword = |i: usize, keyword: &str| {
+        query.get(i..i + keyword.len()).map_or(false, |word| word.eq_ignore_ascii_case(keyword))
+            && !query[..i].chars().next_back().map_or(false, is_ident)
+            && !query[i + keyword.len()..].chars().next().map_or(false, is_ident)
+    };
+    let follows_is = |i: usize| {
+        let before = query[..i].trim_end();
+        before.len() >= 2 && before.get(before.len() - 2..).map_or(false, |word| word.eq_ignore_ascii_case("IS"))
+            && !before[..before.len() - 2].chars().next_back().map_or(false, is_ident)
+    };
+    let ends_operand = |i: usize| OPERAND_END.iter().any(|&keyword| is_keyword(i, keyword));
+
+    let mut quote = None;
+    let mut not = None;
+    for (i, c) in query.char_indices() {
+        match quote {
+            Some(q) => if c == q { quote = None },
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None => if is_keyword(i, "NOT") && !follows_is(i) {
+                not = Some(i);
+                break;
+            },
+        }
+    }
+    let start = match not {
+        Some(not) => not + "NOT".len(),
+        None => return query.to_string(),
+    };
+
+    let mut end = query.len();
+    let mut depth = 0;
+    for (i, c) in query[start..].char_indices().map(|(i, c)| (i + start, c)) {
+        match quote {
+            Some(q) => if c == q { quote = None },
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ')' | ',' | ';' if depth == 0 => {
+                    end = i;
+                    break;
+                }
+                _ if depth == 0 && ends_operand(i) => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+    format!("{}logical_not({}) {}",
+            &query[..start - "NOT".len()],
+            rewrite_not_operators(query[start..end].trim()),
+            rewrite_not_operators(&query[end..]))
+}
+
+// Byte offsets of all characters that are neither enclosed in parentheses nor part of a string literal
+fn top_level_offsets(query: &str) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in query.char_indices() {
+        match quote {
+            Some(q) => if c == q { quote = None },
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ if depth == 0 => offsets.push(i),
+                _ => {}
+            }
+        }
+    }
+    offsets
+}
+
+fn find_keyword(query: &str, offsets: &[usize], keyword: &str, from: usize) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    offsets.iter().cloned().find(|&i| {
+        i >= from
+            && query.get(i..i + keyword.len()).map_or(false, |word| word.eq_ignore_ascii_case(keyword))
+            && !query[..i].chars().next_back().map_or(false, is_ident)
+            && !query[i + keyword.len()..].chars().next().map_or(false, is_ident)
     })
 }
 
+// Replaces `ORDER BY` expressions that refer to an alias with the aliased expression
+fn resolve_aliases(order_by: Vec<(Expr, bool)>, select: &[(Expr, Option<String>)]) -> Vec<(Expr, bool)> {
+    order_by.into_iter()
+        .map(|(expr, desc)| {
+            let aliased = match expr {
+                Expr::ColName(ref name) => select.iter()
+                    .find(|(_, alias)| alias.as_ref() == Some(name))
+                    .map(|(aliased, _)| aliased.clone()),
+                _ => None,
+            };
+            (aliased.unwrap_or(expr), desc)
+        })
+        .collect()
+}
+
+// sqlparser-rs does not support table modifiers, so `SAMPLE` clauses are removed before parsing.
+// `SAMPLE n ROWS` and `SAMPLE n PERCENT` sample the table, `SAMPLE n` samples the rows that satisfy the filter.
+fn extract_sample_clause(query: &str) -> Result<(String, Option<SampleClause>, Option<ReservoirSample>), QueryError> {
+    lazy_static! {
+        static ref SAMPLE: Regex = Regex::new(
+            r"(?i)^SAMPLE\s+([0-9]+(?:\.[0-9]*)?)(?:\s+(ROWS|PERCENT))?(?:\s+SEED\s+([0-9]+))?\b").unwrap();
+    }
+    let offsets = top_level_offsets(query);
+    let mut from = 0;
+    let (start, captures) = loop {
+        match find_keyword(query, &offsets, "SAMPLE", from) {
+            Some(start) => match SAMPLE.captures(&query[start..]) {
+                Some(captures) => break (start, captures),
+                None => from = start + 1,
+            },
+            None => return Ok((query.to_string(), None, None)),
+        }
+    };
+    let n = &captures[1];
+    let seed = match captures.get(3) {
+        Some(seed) => Some(seed.as_str().parse::<u64>()
+            .map_err(|_| QueryError::ParseError(format!("Invalid seed in SAMPLE clause: {}", seed.as_str())))?),
+        None => None,
+    };
+    let matched = captures.get(0).unwrap();
+    let mut stripped = query.to_string();
+    stripped.replace_range(start + matched.start()..start + matched.end(), " ");
+    let unit = match captures.get(2) {
+        Some(unit) => unit.as_str(),
+        None => {
+            let rows = n.parse::<u64>()
+                .map_err(|_| QueryError::ParseError(format!("Invalid number of rows in SAMPLE clause: {}", n)))?;
+            return Ok((stripped, None, Some(ReservoirSample { rows, seed })));
+        }
+    };
+    let size = if unit.eq_ignore_ascii_case("ROWS") {
+        SampleSize::Rows(n.parse::<u64>()
+            .map_err(|_| QueryError::ParseError(format!("Invalid number of rows in SAMPLE clause: {}", n)))?)
+    } else {
+        let percent = n.parse::<f64>()
+            .map_err(|_| QueryError::ParseError(format!("Invalid percentage in SAMPLE clause: {}", n)))?;
+        if percent > 100.0 {
+            return Err(QueryError::ParseError(format!("SAMPLE percentage exceeds 100: {}", n)));
+        }
+        SampleSize::Percent(percent)
+    };
+    Ok((stripped, Some(SampleClause { size, seed }), None))
+}
+
+// sqlparser-rs does not support `OFFSET`, so a trailing `OFFSET <n>` is removed before parsing
+fn extract_offset_clause(query: &str) -> Result<(String, u64), QueryError> {
+    lazy_static! {
+        static ref OFFSET: Regex = Regex::new(r"(?i)^OFFSET\s+([0-9]+)\s*(;?)\s*$").unwrap();
+    }
+    let offsets = top_level_offsets(query);
+    let mut from = 0;
+    let (start, captures) = loop {
+        match find_keyword(query, &offsets, "OFFSET", from) {
+            Some(start) => match OFFSET.captures(&query[start..]) {
+                Some(captures) => break (start, captures),
+                None => from = start + 1,
+            },
+            None => return Ok((query.to_string(), 0)),
+        }
+    };
+    let offset = captures[1].parse::<u64>()
+        .map_err(|_| QueryError::ParseError(format!("Invalid OFFSET: {}", &captures[1])))?;
+    let mut stripped = query.to_string();
+    stripped.replace_range(start.., &captures[2]);
+    Ok((stripped, offset))
+}
+
+// sqlparser-rs does not support `LIMIT <n> BY <expressions>`, so the clause is removed before parsing and the
+// expressions are returned unparsed. They extend up to a subsequent `LIMIT`, which limits the total number of rows.
+fn extract_limit_by_clause(query: &str) -> Result<(String, Option<(u64, String)>), QueryError> {
+    lazy_static! {
+        static ref LIMIT_BY: Regex = Regex::new(r"(?i)^LIMIT\s+([0-9]+)\s+BY\s").unwrap();
+    }
+    let offsets = top_level_offsets(query);
+    let mut from = 0;
+    while let Some(start) = find_keyword(query, &offsets, "LIMIT", from) {
+        if let Some(captures) = LIMIT_BY.captures(&query[start..]) {
+            let limit = captures[1].parse::<u64>()
+                .map_err(|_| QueryError::ParseError(format!("Invalid LIMIT BY: {}", &captures[1])))?;
+            let by_start = start + captures.get(0).unwrap().end();
+            let by_end = find_keyword(query, &offsets, "LIMIT", by_start)
+                .unwrap_or_else(|| query.trim_end().trim_end_matches(';').len());
+            let mut stripped = query.to_string();
+            stripped.replace_range(start..by_end, " ");
+            return Ok((stripped, Some((limit, query[by_start..by_end].to_string()))));
+        }
+        from = start + 1;
+    }
+    Ok((query.to_string(), None))
+}
+
+// sqlparser-rs does not support temporal queries, so `FOR SYSTEM_TIME AS OF <seconds since epoch>` is removed before parsing
+fn extract_as_of_clause(query: &str) -> Result<(String, Option<SystemTime>), QueryError> {
+    lazy_static! {
+        static ref AS_OF: Regex = Regex::new(r"(?i)^FOR\s+SYSTEM(?:_|\s+)TIME\s+AS\s+OF\s+([0-9]+)\b").unwrap();
+    }
+    let offsets = top_level_offsets(query);
+    let mut from = 0;
+    while let Some(start) = find_keyword(query, &offsets, "FOR", from) {
+        if let Some(captures) = AS_OF.captures(&query[start..]) {
+            // `SystemTime` represents the seconds as i64
+            let seconds = captures[1].parse::<i64>()
+                .map_err(|_| QueryError::ParseError(format!("Invalid timestamp in AS OF clause: {}", &captures[1])))? as u64;
+            let mut stripped = query.to_string();
+            stripped.replace_range(start..start + captures.get(0).unwrap().end(), " ");
+            return Ok((stripped, Some(UNIX_EPOCH + Duration::from_secs(seconds))));
+        }
+        from = start + 1;
+    }
+    Ok((query.to_string(), None))
+}
+
 fn get_query_components(ast: ASTNode)
                         -> Result<(
                             Vec<ASTNode>,
                             Option<Box<ASTNode>>,
                             Option<Box<ASTNode>>,
+                            Option<Vec<ASTNode>>,
+                            Option<Box<ASTNode>>,
                             Option<Vec<SQLOrderByExpr>>,
                             Option<Box<ASTNode>>),
                             QueryError>
 {
     match ast {
-        ASTNode::SQLSelect { projection, relation, selection, order_by, group_by, having, limit } => {
-            if group_by.is_some() {
-                Err(QueryError::NotImplemented(format!("Group By")))
-            } else if having.is_some() {
-                Err(QueryError::NotImplemented(format!("Having")))
-            } else {
-                Ok((projection, relation, selection, order_by, limit))
-            }
-        }
+        ASTNode::SQLSelect { projection, relation, selection, order_by, group_by, having, limit } =>
+            Ok((projection, relation, selection, group_by, having, order_by, limit)),
         _ => Err(QueryError::NotImplemented(format!("{:?}", ast))),
     }
 }
@@ -100,6 +655,9 @@ fn get_limit(limit: Option<Box<ASTNode>>) -> Result<u64, QueryError> {
 
 fn expr(node: &ASTNode) -> Result<Box<Expr>, QueryError> {
     Ok(Box::new(match node {
+        // Negative literals, see `rewrite_negative_literals`
+        ASTNode::SQLBinaryExpr { left: box ASTNode::SQLValue(Value::Long(0)), op: SQLOperator::Minus, right: box ASTNode::SQLValue(Value::Long(int)) } =>
+            Expr::Const(RawVal::Int(-int)),
         ASTNode::SQLBinaryExpr { ref left, ref op, ref right } =>
             Expr::Func2(map_operator(op)?, expr(left)?, expr(right)?),
         ASTNode::SQLValue(ref literal) => Expr::Const(get_raw_val(literal)?),
@@ -119,6 +677,13 @@ fn expr(node: &ASTNode) -> Result<Box<Expr>, QueryError> {
                 }
                 Expr::Func2(Func2Type::RegexMatch, expr(&args[0])?, expr(&args[1])?)
             }
+            "LOGICAL_NOT" => {
+                if args.len() != 1 {
+                    return Err(QueryError::ParseError(
+                        "Expected one argument in logical_not function".to_string()));
+                }
+                Expr::Func1(Func1Type::Not, expr(&args[0])?)
+            }
             "LENGTH" => {
                 if args.len() != 1 {
                     return Err(QueryError::ParseError(
@@ -126,19 +691,56 @@ fn expr(node: &ASTNode) -> Result<Box<Expr>, QueryError> {
                 }
                 Expr::Func1(Func1Type::Length, expr(&args[0])?)
             }
+            "LOWER" => {
+                if args.len() != 1 {
+                    return Err(QueryError::ParseError(
+                        "Expected one argument in lower function".to_string()));
+                }
+                Expr::Func1(Func1Type::Lower, expr(&args[0])?)
+            }
+            "UPPER" => {
+                if args.len() != 1 {
+                    return Err(QueryError::ParseError(
+                        "Expected one argument in upper function".to_string()));
+                }
+                Expr::Func1(Func1Type::Upper, expr(&args[0])?)
+            }
+            "CHAR_LENGTH" | "CHARACTER_LENGTH" => {
+                if args.len() != 1 {
+                    return Err(QueryError::ParseError(
+                        "Expected one argument in char_length function".to_string()));
+                }
+                Expr::Func1(Func1Type::CharLength, expr(&args[0])?)
+            }
+            "SUBSTRING" | "SUBSTR" => {
+                if args.len() != 2 && args.len() != 3 {
+                    return Err(QueryError::ParseError(
+                        "Expected two or three arguments in substring function".to_string()));
+                }
+                let start = get_integer_argument(&args[1], "substring")?;
+                let length = match args.get(2) {
+                    Some(arg) => get_integer_argument(arg, "substring")?,
+                    None => i64::MAX,
+                };
+                Expr::Func1(Func1Type::Substring { start, length }, expr(&args[0])?)
+            }
             "COUNT" => {
                 if args.len() != 1 {
                     return Err(QueryError::ParseError(
                         "Expected one argument in COUNT function".to_string()));
                 }
-                Expr::Aggregate(Aggregator::Count, expr(&args[0])?)
+                match args[0] {
+                    // COUNT(*) counts all rows, which is the same as counting a constant that is never null
+                    ASTNode::SQLWildcard => Expr::Aggregate(Aggregator::Count, Box::new(Expr::Const(RawVal::Int(1))), None),
+                    ref arg => Expr::Aggregate(Aggregator::Count, expr(arg)?, None),
+                }
             }
             "SUM" => {
                 if args.len() != 1 {
                     return Err(QueryError::ParseError(
                         "Expected one argument in SUM function".to_string()));
                 }
-                Expr::Aggregate(Aggregator::Sum, expr(&args[0])?)
+                Expr::Aggregate(Aggregator::Sum, expr(&args[0])?, None)
             }
             "AVG" => {
                 if args.len() != 1 {
@@ -146,31 +748,94 @@ fn expr(node: &ASTNode) -> Result<Box<Expr>, QueryError> {
                         "Expected one argument in AVG function".to_string()));
                 }
                 Expr::Func2(Func2Type::Divide,
-                            Box::new(Expr::Aggregate(Aggregator::Sum, expr(&args[0])?)),
-                            Box::new(Expr::Aggregate(Aggregator::Count, expr(&args[0])?)))
+                            Box::new(Expr::Aggregate(Aggregator::Sum, expr(&args[0])?, None)),
+                            Box::new(Expr::Aggregate(Aggregator::Count, expr(&args[0])?, None)))
             }
             "MAX" => {
                 if args.len() != 1 {
                     return Err(QueryError::ParseError(
                         "Expected one argument in MAX function".to_string()));
                 }
-                Expr::Aggregate(Aggregator::Max, expr(&args[0])?)
+                Expr::Aggregate(Aggregator::Max, expr(&args[0])?, None)
             }
             "MIN" => {
                 if args.len() != 1 {
                     return Err(QueryError::ParseError(
                         "Expected one argument in MIN function".to_string()));
                 }
-                Expr::Aggregate(Aggregator::Min, expr(&args[0])?)
+                Expr::Aggregate(Aggregator::Min, expr(&args[0])?, None)
+            }
+            "COUNT_IF" => {
+                if args.len() != 1 {
+                    return Err(QueryError::ParseError(
+                        "Expected one argument in COUNT_IF function".to_string()));
+                }
+                Expr::Aggregate(Aggregator::Count, Box::new(Expr::Const(RawVal::Int(1))), Some(expr(&args[0])?))
+            }
+            "SUM_IF" => {
+                if args.len() != 2 {
+                    return Err(QueryError::ParseError(
+                        "Expected two arguments in SUM_IF function".to_string()));
+                }
+                Expr::Aggregate(Aggregator::Sum, expr(&args[0])?, Some(expr(&args[1])?))
+            }
+            "MAX_IF" => {
+                if args.len() != 2 {
+                    return Err(QueryError::ParseError(
+                        "Expected two arguments in MAX_IF function".to_string()));
+                }
+                Expr::Aggregate(Aggregator::Max, expr(&args[0])?, Some(expr(&args[1])?))
+            }
+            "MIN_IF" => {
+                if args.len() != 2 {
+                    return Err(QueryError::ParseError(
+                        "Expected two arguments in MIN_IF function".to_string()));
+                }
+                Expr::Aggregate(Aggregator::Min, expr(&args[0])?, Some(expr(&args[1])?))
+            }
+            "APPROX_COUNT_DISTINCT" => {
+                let precision = match args.len() {
+                    1 => hyperloglog::DEFAULT_PRECISION as u8,
+                    2 => approx_distinct_precision(&args[1])?,
+                    _ => return Err(QueryError::ParseError(
+                        "Expected one or two arguments in APPROX_COUNT_DISTINCT function".to_string())),
+                };
+                Expr::Aggregate(Aggregator::ApproxCountDistinct(precision), expr(&args[0])?, None)
             }
             _ => return Err(QueryError::NotImplemented(format!("Function {:?}", id))),
         }
+        ASTNode::SQLCast { expr: ref node, ref data_type } => {
+            let cast = match data_type {
+                SQLType::SmallInt | SQLType::Int | SQLType::BigInt => Func1Type::CastInt,
+                SQLType::Char(_) | SQLType::Varchar(_) | SQLType::Text => Func1Type::CastString,
+                // The SQL parser does not recognize TEXT as a keyword
+                SQLType::Custom(name) if name.eq_ignore_ascii_case("TEXT") => Func1Type::CastString,
+                _ => return Err(QueryError::NotImplemented(format!("CAST to {:?}", data_type))),
+            };
+            match (cast, *expr(node)?) {
+                // Constants are cast right away
+                (Func1Type::CastInt, Expr::Const(RawVal::Str(s))) =>
+                    Expr::Const(s.trim().parse::<i64>().map(RawVal::Int).unwrap_or(RawVal::Null)),
+                (Func1Type::CastString, Expr::Const(RawVal::Int(i))) => Expr::Const(RawVal::Str(i.to_string())),
+                (cast, inner) => Expr::Func1(cast, Box::new(inner)),
+            }
+        }
         ASTNode::SQLIsNull(ref node) => Expr::Func1(Func1Type::IsNull, expr(node)?),
         ASTNode::SQLIsNotNull(ref node) => Expr::Func1(Func1Type::IsNotNull, expr(node)?),
         _ => return Err(QueryError::NotImplemented(format!("{:?}", node))),
     }))
 }
 
+fn approx_distinct_precision(node: &ASTNode) -> Result<u8, QueryError> {
+    match node {
+        ASTNode::SQLValue(Value::Long(p))
+        if *p >= i64::from(hyperloglog::MIN_PRECISION) && *p <= i64::from(hyperloglog::MAX_PRECISION) => Ok(*p as u8),
+        _ => Err(QueryError::ParseError(format!(
+            "Precision of APPROX_COUNT_DISTINCT has to be an integer between {} and {}, got {:?}",
+            hyperloglog::MIN_PRECISION, hyperloglog::MAX_PRECISION, node))),
+    }
+}
+
 fn map_operator(o: &SQLOperator) -> Result<Func2Type, QueryError> {
     Ok(match o {
         SQLOperator::And => Func2Type::And,
@@ -191,6 +856,17 @@ fn map_operator(o: &SQLOperator) -> Result<Func2Type, QueryError> {
 }
 
 
+fn get_integer_argument(node: &ASTNode, function: &str) -> Result<i64, QueryError> {
+    match node {
+        ASTNode::SQLValue(Value::Long(int)) => Ok(*int),
+        // Negative literals, see `rewrite_negative_literals`
+        ASTNode::SQLBinaryExpr { left: box ASTNode::SQLValue(Value::Long(0)), op: SQLOperator::Minus, right: box ASTNode::SQLValue(Value::Long(int)) } =>
+            Ok(-int),
+        _ => Err(QueryError::NotImplemented(
+            format!("Expected integer constant as argument of {} function, found {:?}", function, node))),
+    }
+}
+
 // Fn to map sqlparser-rs `Value` to LocustDB's `RawVal`.
 fn get_raw_val(constant: &Value) -> Result<RawVal, QueryError> {
     match constant {
@@ -213,13 +889,229 @@ mod tests {
     fn test_select_star() {
         assert_eq!(
             format!("{:?}", parse_query("select * from default")),
-            "Ok(Query { select: [ColName(\"*\")], table: \"default\", filter: Const(Int(1)), order_by: [], limit: LimitClause { limit: 100, offset: 0 } })");
+            "Ok(Query { select: [(ColName(\"*\"), None)], table: \"default\", filter: Const(Int(1)), group_by: [], having: None, order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, sample: None, reservoir: None, explain: false, analyze: false, memory_limit_bytes: None, batch_size: None, timeout: None, as_of: None })");
     }
 
     #[test]
     fn test_to_year() {
         assert_eq!(
             format!("{:?}", parse_query("select to_year(ts) from default")),
-            "Ok(Query { select: [Func1(ToYear, ColName(\"ts\"))], table: \"default\", filter: Const(Int(1)), order_by: [], limit: LimitClause { limit: 100, offset: 0 } })");
+            "Ok(Query { select: [(Func1(ToYear, ColName(\"ts\")), None)], table: \"default\", filter: Const(Int(1)), group_by: [], having: None, order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, sample: None, reservoir: None, explain: false, analyze: false, memory_limit_bytes: None, batch_size: None, timeout: None, as_of: None })");
+    }
+
+    #[test]
+    fn test_substring_arguments() {
+        let substring = |query: &str| match *parse_query(query).unwrap().select[0].0 {
+            Expr::Func1(Func1Type::Substring { start, length }, _) => (start, length),
+            ref other => panic!("Expected substring, found {:?}", other),
+        };
+        assert_eq!(substring("SELECT substring(s, 2, 3) FROM t"), (2, 3));
+        assert_eq!(substring("SELECT substr(s, -2, 4) FROM t"), (-2, 4));
+        assert_eq!(substring("SELECT substring(s, -2) FROM t"), (-2, i64::MAX));
+        assert!(parse_query("SELECT substring(s, 1 + 1) FROM t").is_err());
+    }
+
+    #[test]
+    fn test_extract_aliases() {
+        let (query, aliases) = extract_aliases("SELECT a AS x, sum(b) as total, regex(c, 'a as b'), d FROM t ORDER BY total");
+        assert_eq!(query, "SELECT a , sum(b) , regex(c, 'a as b'), d FROM t ORDER BY total");
+        assert_eq!(aliases, vec![Some("x".to_string()), Some("total".to_string()), None, None]);
+    }
+
+    #[test]
+    fn test_rewrite_regex_operators() {
+        assert_eq!(rewrite_regex_operators("SELECT path FROM t WHERE path ~ '^/api/v[12]/' AND x~'a'"),
+                   "SELECT path FROM t WHERE regex(path, '^/api/v[12]/') AND regex(x, 'a')");
+        assert_eq!(rewrite_regex_operators("SELECT a FROM t WHERE b = 'c ~ 'd"), "SELECT a FROM t WHERE b = 'c ~ 'd");
+    }
+
+    #[test]
+    fn test_rewrite_negative_literals() {
+        assert_eq!(rewrite_negative_literals("SELECT -1, a-2 FROM t WHERE a = -40 AND b > (-3) OR c - -4 < 'x = -5'"),
+                   "SELECT (0 - 1), a-2 FROM t WHERE a = (0 - 40) AND b > ((0 - 3)) OR c - (0 - 4) < 'x = -5'");
+        let query = parse_query("SELECT a FROM t WHERE a = -40;").unwrap();
+        assert_eq!(query.filter, Expr::func(Func2Type::Equals, Expr::ColName("a".to_string()), Expr::Const(RawVal::Int(-40))));
+    }
+
+    #[test]
+    fn test_rewrite_not_operators() {
+        assert_eq!(rewrite_not_operators("SELECT a FROM t WHERE NOT a = 1 AND NOT (b OR NOT c) OR d IS NOT NULL;"),
+                   "SELECT a FROM t WHERE logical_not(a = 1) AND logical_not((b OR logical_not(c) )) OR d IS NOT NULL;");
+        assert_eq!(rewrite_not_operators("SELECT NOT a, b FROM t WHERE c = 'NOT d' ORDER BY NOT e DESC"),
+                   "SELECT logical_not(a) , b FROM t WHERE c = 'NOT d' ORDER BY logical_not(e) DESC");
+        let query = parse_query("SELECT a FROM t WHERE NOT a < 10 AND b;").unwrap();
+        assert_eq!(query.filter, Expr::func(Func2Type::And,
+                                            Expr::Func1(Func1Type::Not, Box::new(Expr::func(
+                                                Func2Type::LT, Expr::ColName("a".to_string()), Expr::Const(RawVal::Int(10))))),
+                                            Expr::ColName("b".to_string())));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let query = parse_query("SELECT a AS x, COUNT(0) FROM t GROUP BY x, b HAVING COUNT(0) > 1 LIMIT 10 OFFSET 5;").unwrap();
+        assert_eq!(query.group_by, vec![Expr::ColName("a".to_string()), Expr::ColName("b".to_string())]);
+        assert!(query.having.is_some());
+        assert_eq!(query.limit, LimitClause { limit: 10, offset: 5 });
+        assert_eq!(parse_query("SELECT a FROM t OFFSET 3").unwrap().limit, LimitClause { limit: 100, offset: 3 });
+    }
+
+    #[cfg(feature = "enable_serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let queries = [
+            "SELECT * FROM default",
+            "SELECT a AS x, COUNT(0), SUM(b * 2) FROM t WHERE a = 'foo' AND b < 3 AND c IS NULL \
+             GROUP BY x HAVING SUM(c) > 10 ORDER BY x DESC LIMIT 10 OFFSET 3",
+            "SELECT approx_count_distinct(a), to_year(ts) FROM t SAMPLE 12.5 PERCENT SEED 42 FOR SYSTEM_TIME AS OF 1500000000",
+            "SELECT length(a) FROM t SAMPLE 1000 WHERE a LIKE '%x_' AND a ~ '^b'",
+            "EXPLAIN SELECT max(a) FROM t",
+        ];
+        for &query in &queries {
+            let parsed = parse_query(query).unwrap();
+            let json = parsed.to_json();
+            let deserialized = Query::from_json(&json).unwrap();
+            assert_eq!(format!("{:?}", deserialized), format!("{:?}", parsed), "{}", json);
+        }
+        assert!(Query::from_json("{\"select\": []}").is_err());
+    }
+
+    #[test]
+    fn test_reservoir_sample() {
+        let query = parse_query("SELECT a FROM t SAMPLE 1000 SEED 7 WHERE a > 1").unwrap();
+        assert_eq!(query.reservoir, Some(ReservoirSample { rows: 1000, seed: Some(7) }));
+        assert_eq!(query.sample, None);
+        let query = parse_query("SELECT a FROM t SAMPLE 10 ROWS").unwrap();
+        assert_eq!(query.reservoir, None);
+        assert_eq!(query.sample, Some(SampleClause { size: SampleSize::Rows(10), seed: None }));
+        assert!(parse_query("SELECT a FROM t SAMPLE 0.5").is_err());
+    }
+
+    #[test]
+    fn test_clauses_in_string_literals() {
+        let query = parse_query("SELECT a FROM t WHERE b = 'SAMPLE 5 ROWS' LIMIT 3 OFFSET 2").unwrap();
+        assert_eq!(query.sample, None);
+        assert_eq!(query.filter, Expr::func(Func2Type::Equals,
+                                            Expr::ColName("b".to_string()),
+                                            Expr::Const(RawVal::Str("SAMPLE 5 ROWS".to_string()))));
+        assert_eq!(query.limit, LimitClause { limit: 3, offset: 2 });
+        let query = parse_query("SELECT a FROM t SAMPLE 5 ROWS WHERE b = 'SAMPLE 7 PERCENT OFFSET 1'").unwrap();
+        assert_eq!(query.sample, Some(SampleClause { size: SampleSize::Rows(5), seed: None }));
+        assert_eq!(query.limit.offset, 0);
+    }
+
+    #[test]
+    fn test_as_of() {
+        let query = parse_query("SELECT a FROM t FOR SYSTEM_TIME AS OF 1500000000 WHERE a > 1").unwrap();
+        assert_eq!(query.as_of, Some(UNIX_EPOCH + Duration::from_secs(1_500_000_000)));
+        assert_eq!(query.table, "t");
+        let query = parse_query("SELECT a FROM t for system time as of 0").unwrap();
+        assert_eq!(query.as_of, Some(UNIX_EPOCH));
+        assert_eq!(parse_query("SELECT a FROM t").unwrap().as_of, None);
+        assert!(parse_query("SELECT a FROM t FOR SYSTEM_TIME AS OF 9223372036854775808").is_err());
+        let query = parse_query("SELECT a FROM t WHERE b = 'x FOR SYSTEM_TIME AS OF 5'").unwrap();
+        assert_eq!(query.as_of, None);
+        assert_eq!(query.filter, Expr::Func2(Func2Type::Equals,
+                                             Box::new(Expr::ColName("b".to_string())),
+                                             Box::new(Expr::Const(RawVal::Str("x FOR SYSTEM_TIME AS OF 5".to_string())))));
+    }
+
+    #[test]
+    fn test_limit_by() {
+        let query = parse_query("SELECT host, path AS p, hits FROM t ORDER BY hits DESC LIMIT 3 BY host, p LIMIT 10 OFFSET 2;").unwrap();
+        assert_eq!(query.limit_by, Some(LimitBy {
+            limit: 3,
+            by: vec![Expr::ColName("host".to_string()), Expr::ColName("path".to_string())],
+        }));
+        assert_eq!(query.limit, LimitClause { limit: 10, offset: 2 });
+        let query = parse_query("SELECT host FROM t LIMIT 1 BY host").unwrap();
+        assert_eq!(query.limit_by.map(|limit_by| limit_by.limit), Some(1));
+        assert_eq!(query.limit.limit, 100);
+        assert_eq!(parse_query("SELECT a FROM t LIMIT 5").unwrap().limit_by, None);
+    }
+
+    #[test]
+    fn test_cast() {
+        let query = parse_query("SELECT CAST(a AS INT), CAST(b AS VARCHAR(10)), CAST('12' AS BIGINT), CAST(3 AS TEXT) FROM t").unwrap();
+        let select = query.select.into_iter().map(|(expr, _)| expr).collect::<Vec<_>>();
+        assert_eq!(select, vec![
+            Expr::Func1(Func1Type::CastInt, Box::new(Expr::ColName("a".to_string()))),
+            Expr::Func1(Func1Type::CastString, Box::new(Expr::ColName("b".to_string()))),
+            Expr::Const(RawVal::Int(12)),
+            Expr::Const(RawVal::Str("3".to_string())),
+        ]);
+        assert_eq!(parse_query("SELECT CAST('x' AS INT) FROM t").unwrap().select[0].0, Expr::Const(RawVal::Null));
+        assert!(parse_query("SELECT CAST(a AS DOUBLE) FROM t").is_err());
+    }
+
+    #[test]
+    fn test_parse_union() {
+        let union = parse_union("SELECT a FROM t UNION ALL SELECT b FROM s WHERE b > 1 ORDER BY a DESC LIMIT 5;")
+            .unwrap().unwrap();
+        assert_eq!(union.left.table, "t");
+        assert_eq!(union.right.table, "s");
+        assert_eq!(union.order_by, vec![("a".to_string(), true, NullsOrder::First)]);
+        assert_eq!(union.limit.limit, 5);
+        assert_eq!(union.left.order_by, vec![(Expr::ColName("a".to_string()), true, NullsOrder::First)]);
+        assert_eq!(union.right.order_by, vec![(Expr::ColName("b".to_string()), true, NullsOrder::First)]);
+        assert!(parse_union("SELECT a FROM t UNION ALL SELECT b FROM s ORDER BY b;").unwrap().is_err());
+        assert!(parse_union("SELECT * FROM t UNION ALL SELECT * FROM s ORDER BY a;").unwrap().is_err());
+        assert!(parse_union("SELECT a FROM t UNION SELECT b FROM s;").unwrap().is_err());
+        assert!(parse_union("SELECT 'UNION' FROM t;").is_none());
+    }
+
+    #[test]
+    fn test_nulls_order() {
+        let (query, nulls_orders) = extract_nulls_orders("SELECT a FROM t ORDER BY a NULLS FIRST, f(b, c) DESC, c nulls last LIMIT 3;");
+        assert_eq!(query, "SELECT a FROM t ORDER BY a, f(b, c) DESC, c LIMIT 3;");
+        assert_eq!(nulls_orders, vec![Some(NullsOrder::First), None, Some(NullsOrder::Last)]);
+
+        let a = || Expr::ColName("a".to_string());
+        let order_by = |query: &str| parse_query(query).unwrap().order_by;
+        assert_eq!(order_by("SELECT a FROM t ORDER BY a"), vec![(a(), false, NullsOrder::Last)]);
+        assert_eq!(order_by("SELECT a FROM t ORDER BY a DESC"), vec![(a(), true, NullsOrder::First)]);
+        assert_eq!(order_by("SELECT a FROM t ORDER BY a NULLS FIRST"), vec![(a(), false, NullsOrder::First)]);
+        assert_eq!(order_by("SELECT a FROM t ORDER BY a DESC NULLS LAST;"), vec![(a(), true, NullsOrder::Last)]);
+    }
+
+    #[test]
+    fn test_parse_rollup() {
+        let rollup = parse_rollup("SELECT country, city AS c, SUM(x) FROM t GROUP BY ROLLUP(country, c) ORDER BY c LIMIT 5;")
+            .unwrap().unwrap();
+        assert_eq!(rollup.query.group_by, vec![Expr::ColName("country".to_string()), Expr::ColName("city".to_string())]);
+        assert_eq!(rollup.rolled_up, 2);
+        assert_eq!(rollup.order_by, vec![(1, false, NullsOrder::Last)]);
+        assert_eq!(rollup.limit.limit, 5);
+        let city = Expr::ColName("city".to_string());
+        assert_eq!(rollup.query.order_by, vec![(city, false, NullsOrder::Last)]);
+        let grouping_sets = rollup.grouping_sets().unwrap();
+        assert_eq!(grouping_sets.iter().map(|(_, nulls)| nulls.clone()).collect::<Vec<_>>(),
+                   vec![vec![], vec![1], vec![0, 1]]);
+        assert_eq!(grouping_sets[1].0.group_by, vec![Expr::ColName("country".to_string())]);
+        assert!(grouping_sets[1].0.order_by.is_empty());
+        assert_eq!(grouping_sets[2].0.select.len(), 1);
+
+        let rollup = parse_rollup("SELECT a, b, COUNT(0) FROM t GROUP BY a, ROLLUP(b);").unwrap().unwrap();
+        assert_eq!(rollup.grouping_sets().unwrap().iter().map(|(_, nulls)| nulls.clone()).collect::<Vec<_>>(),
+                   vec![vec![], vec![1]]);
+        let mut invalid = rollup.clone();
+        invalid.rolled_up = 3;
+        assert!(invalid.grouping_sets().is_err());
+        assert!(parse_rollup("SELECT a, b, COUNT(0) FROM t GROUP BY ROLLUP(a), b;").unwrap().is_err());
+        assert!(parse_rollup("SELECT a, b FROM t GROUP BY ROLLUP(a, b);").unwrap().is_err());
+        assert!(parse_rollup("SELECT rollup FROM t;").is_none());
+        match parse_rollup("EXPLAIN SELECT a, COUNT(0) FROM t GROUP BY ROLLUP(a);") {
+            Some(Err(QueryError::NotImplemented(_))) => {}
+            other => panic!("Expected NotImplemented, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_delete() {
+        assert_eq!(
+            format!("{:?}", parse_delete("DELETE FROM default WHERE id > 3;")),
+            "Some(Ok((\"default\", Func2(GT, ColName(\"id\"), Const(Int(3))))))");
+        assert!(parse_delete("SELECT * FROM default").is_none());
+        assert_eq!(parse_vacuum("vacuum default;"), Some("default".to_string()));
+        assert_eq!(parse_vacuum("SELECT * FROM default"), None);
     }
 }
@@ -0,0 +1,582 @@
+use std::cmp;
+use std::cmp::Ordering;
+use std::i64;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use engine::*;
+use hyperloglog;
+use ingest::raw_val::RawVal;
+use proto;
+use syntax::expression::*;
+use syntax::limit::*;
+use syntax::sample::*;
+use QueryError;
+
+impl From<Query> for proto::Query {
+    fn from(query: Query) -> proto::Query {
+        proto::Query {
+            select: query.select.into_iter()
+                .map(|(expr, alias)| proto::SelectExpr { expr: Some(expr.into()), alias: alias.unwrap_or_default() })
+                .collect(),
+            table: query.table,
+            filter: Some(query.filter.into()),
+            group_by: query.group_by.into_iter().map(Into::into).collect(),
+            having: query.having.map(Into::into),
+            order_by: query.order_by.into_iter()
+                .map(|(expr, desc, nulls)| proto::OrderBy { expr: Some(expr.into()), desc, nulls: proto::NullsOrder::from(nulls) as i32 })
+                .collect(),
+            limit: Some(proto::LimitClause { limit: query.limit.limit, offset: query.limit.offset }),
+            limit_by: query.limit_by.map(|limit_by| proto::LimitBy {
+                limit: limit_by.limit,
+                by: limit_by.by.into_iter().map(Into::into).collect(),
+            }),
+            sample: query.sample.map(|sample| proto::SampleClause {
+                size: Some(match sample.size {
+                    SampleSize::Rows(rows) => proto::sample_clause::Size::Rows(rows),
+                    SampleSize::Percent(percent) => proto::sample_clause::Size::Percent(percent),
+                }),
+                seed: sample.seed.map(|value| proto::UInt64Value { value }),
+            }),
+            reservoir: query.reservoir.map(|reservoir| proto::ReservoirSample {
+                rows: reservoir.rows,
+                seed: reservoir.seed.map(|value| proto::UInt64Value { value }),
+            }),
+            explain: query.explain,
+            analyze: query.analyze,
+            memory_limit_bytes: query.memory_limit_bytes.unwrap_or(0) as u64,
+            batch_size: query.batch_size.unwrap_or(0) as u64,
+            timeout: query.timeout.map(to_proto_duration),
+            as_of: query.as_of.map(|as_of| to_proto_duration(as_of.duration_since(UNIX_EPOCH).unwrap_or_default())),
+        }
+    }
+}
+
+impl From<Expr> for proto::Expr {
+    fn from(expr: Expr) -> proto::Expr {
+        use proto::expr::Expr as E;
+        let expr = match expr {
+            Expr::ColName(name) => E::ColName(name),
+            Expr::Const(value) => E::Constant(value.into()),
+            Expr::Func1(op, box expr) => {
+                let (start, length) = match op {
+                    Func1Type::Substring { start, length } => (start, length),
+                    _ => (0, 0),
+                };
+                E::Func1(proto::Func1 {
+                    op: to_proto_func1_type(op) as i32,
+                    expr: Some(Box::new(expr.into())),
+                    start,
+                    length,
+                })
+            }
+            Expr::Func2(op, box lhs, box rhs) => E::Func2(proto::Func2 {
+                op: to_proto_func2_type(op) as i32,
+                lhs: Some(Box::new(lhs.into())),
+                rhs: Some(Box::new(rhs.into())),
+            }),
+            Expr::Aggregate(aggregator, box expr, filter) => E::Aggregate(proto::Aggregate {
+                aggregator: Some(aggregator.into()),
+                expr: Some(Box::new(expr.into())),
+                filter: filter.map(|box filter| Box::new(filter.into())),
+            }),
+        };
+        proto::Expr { expr: Some(expr) }
+    }
+}
+
+impl From<Aggregator> for proto::Aggregator {
+    fn from(aggregator: Aggregator) -> proto::Aggregator {
+        let (kind, precision) = match aggregator {
+            Aggregator::Sum => (proto::AggregatorType::Sum, 0),
+            Aggregator::SaturatingSum => (proto::AggregatorType::SaturatingSum, 0),
+            Aggregator::WrappingSum => (proto::AggregatorType::WrappingSum, 0),
+            Aggregator::Count => (proto::AggregatorType::Count, 0),
+            Aggregator::Max => (proto::AggregatorType::Max, 0),
+            Aggregator::Min => (proto::AggregatorType::Min, 0),
+            Aggregator::ApproxCountDistinct(precision) => (proto::AggregatorType::ApproxCountDistinct, u32::from(precision)),
+        };
+        proto::Aggregator { kind: kind as i32, precision }
+    }
+}
+
+impl From<NullsOrder> for proto::NullsOrder {
+    fn from(nulls: NullsOrder) -> proto::NullsOrder {
+        match nulls {
+            NullsOrder::First => proto::NullsOrder::First,
+            NullsOrder::Last => proto::NullsOrder::Last,
+        }
+    }
+}
+
+impl From<RawVal> for proto::RawVal {
+    fn from(value: RawVal) -> proto::RawVal {
+        proto::RawVal {
+            value: Some(match value {
+                RawVal::Int(i) => proto::raw_val::Value::Int(i),
+                RawVal::Str(s) => proto::raw_val::Value::Str(s),
+                RawVal::Null => proto::raw_val::Value::Null(true),
+            }),
+        }
+    }
+}
+
+/// Converts a query received as protobuf message, which fails if the message is missing required fields or contains
+/// unknown enum values. Missing filter and limit have the same defaults as in SQL queries.
+pub fn parse_query(query: proto::Query) -> Result<Query, QueryError> {
+    let mut select = Vec::with_capacity(query.select.len());
+    for select_expr in query.select {
+        let alias = if select_expr.alias.is_empty() { None } else { Some(select_expr.alias) };
+        select.push((expr(select_expr.expr)?, alias));
+    }
+    let filter = match query.filter {
+        Some(filter) => expr(Some(filter))?,
+        None => Expr::Const(RawVal::Int(1)),
+    };
+    let mut order_by = Vec::with_capacity(query.order_by.len());
+    for order in query.order_by {
+        let nulls = match proto::NullsOrder::from_i32(order.nulls) {
+            Some(proto::NullsOrder::Unspecified) => NullsOrder::default_for(order.desc),
+            Some(proto::NullsOrder::First) => NullsOrder::First,
+            Some(proto::NullsOrder::Last) => NullsOrder::Last,
+            None => bail!(QueryError::ParseError, "Unknown nulls order {}", order.nulls),
+        };
+        order_by.push((expr(order.expr)?, order.desc, nulls));
+    }
+    let sample = match query.sample {
+        Some(sample) => Some(SampleClause {
+            size: match sample.size {
+                Some(proto::sample_clause::Size::Rows(rows)) => SampleSize::Rows(rows),
+                Some(proto::sample_clause::Size::Percent(percent)) => SampleSize::Percent(percent),
+                None => bail!(QueryError::ParseError, "Sample clause without size"),
+            },
+            seed: sample.seed.map(|seed| seed.value),
+        }),
+        None => None,
+    };
+    Ok(Query {
+        select,
+        table: query.table,
+        filter,
+        group_by: query.group_by.into_iter()
+            .map(|group_by| expr(Some(group_by)))
+            .collect::<Result<Vec<_>, QueryError>>()?,
+        having: match query.having {
+            Some(having) => Some(expr(Some(having))?),
+            None => None,
+        },
+        order_by,
+        limit: match query.limit {
+            Some(limit) => LimitClause { limit: limit.limit, offset: limit.offset },
+            None => LimitClause { limit: 100, offset: 0 },
+        },
+        limit_by: match query.limit_by {
+            Some(limit_by) => Some(LimitBy {
+                limit: limit_by.limit,
+                by: limit_by.by.into_iter()
+                    .map(|by| expr(Some(by)))
+                    .collect::<Result<Vec<_>, QueryError>>()?,
+            }),
+            None => None,
+        },
+        sample,
+        reservoir: query.reservoir.map(|reservoir| ReservoirSample {
+            rows: reservoir.rows,
+            seed: reservoir.seed.map(|seed| seed.value),
+        }),
+        explain: query.explain && !query.analyze,
+        analyze: query.analyze,
+        memory_limit_bytes: if query.memory_limit_bytes == 0 { None } else { Some(query.memory_limit_bytes as usize) },
+        batch_size: if query.batch_size == 0 { None } else { Some(query.batch_size as usize) },
+        timeout: match query.timeout {
+            Some(timeout) => Some(from_proto_duration(timeout)?),
+            None => None,
+        },
+        as_of: match query.as_of {
+            Some(as_of) => Some(from_proto_timestamp(as_of)?),
+            None => None,
+        },
+    })
+}
+
+impl proto::TypedVec {
+    /// Values of the vector independent of how they are represented, e.g. `Integers` and `RawVals` that only contain
+    /// integers have the same values.
+    pub fn raw_vals(&self) -> Result<Vec<RawVal>, QueryError> {
+        use proto::typed_vec::Values;
+        Ok(match self.values {
+            Some(Values::Integers(ref integers)) => integers.values.iter().map(|&i| RawVal::Int(i)).collect(),
+            Some(Values::Strings(ref strings)) => strings.values.iter().map(|s| RawVal::Str(s.clone())).collect(),
+            Some(Values::RawVals(ref raw_vals)) => raw_vals.values.iter()
+                .map(|value| raw_val(value.clone()))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => bail!(QueryError::ParseError, "Missing typed vector values"),
+        })
+    }
+
+    /// Concatenates `vecs`, e.g. the columns of consecutive batches of a query result.
+    /// All nonempty vectors have to use the same representation, empty vectors are compatible with any representation.
+    pub fn concat(vecs: Vec<proto::TypedVec>) -> Result<proto::TypedVec, QueryError> {
+        use proto::typed_vec::Values;
+        let mut result: Option<Values> = None;
+        for vec in vecs {
+            let values = match vec.values {
+                Some(values) => values,
+                None => bail!(QueryError::ParseError, "Missing typed vector values"),
+            };
+            result = Some(match result {
+                None => values,
+                Some(result) => if values_len(&values) == 0 {
+                    result
+                } else if values_len(&result) == 0 {
+                    values
+                } else {
+                    match (result, values) {
+                        (Values::Integers(mut lhs), Values::Integers(rhs)) => {
+                            lhs.values.extend(rhs.values);
+                            Values::Integers(lhs)
+                        }
+                        (Values::Strings(mut lhs), Values::Strings(rhs)) => {
+                            lhs.values.extend(rhs.values);
+                            Values::Strings(lhs)
+                        }
+                        (Values::RawVals(mut lhs), Values::RawVals(rhs)) => {
+                            lhs.values.extend(rhs.values);
+                            Values::RawVals(lhs)
+                        }
+                        (lhs, rhs) => bail!(QueryError::TypeError, "Can't concatenate {} with {}",
+                                            values_type(&lhs), values_type(&rhs)),
+                    }
+                },
+            });
+        }
+        Ok(proto::TypedVec { values: Some(result.unwrap_or_else(|| Values::Integers(proto::Integers { values: vec![] }))) })
+    }
+
+    /// Copies the values at positions `start..end`, which is clamped to the length of the vector.
+    pub fn slice(&self, start: usize, end: usize) -> proto::TypedVec {
+        use proto::typed_vec::Values;
+        fn slice<T: Clone>(values: &[T], start: usize, end: usize) -> Vec<T> {
+            let end = cmp::min(end, values.len());
+            values[cmp::min(start, end)..end].to_vec()
+        }
+        proto::TypedVec {
+            values: self.values.as_ref().map(|values| match *values {
+                Values::Integers(ref integers) => Values::Integers(proto::Integers { values: slice(&integers.values, start, end) }),
+                Values::Strings(ref strings) => Values::Strings(proto::Strings { values: slice(&strings.values, start, end) }),
+                Values::RawVals(ref raw_vals) => Values::RawVals(proto::RawVals { values: slice(&raw_vals.values, start, end) }),
+            }),
+        }
+    }
+}
+
+fn values_len(values: &proto::typed_vec::Values) -> usize {
+    use proto::typed_vec::Values;
+    match *values {
+        Values::Integers(ref integers) => integers.values.len(),
+        Values::Strings(ref strings) => strings.values.len(),
+        Values::RawVals(ref raw_vals) => raw_vals.values.len(),
+    }
+}
+
+fn values_type(values: &proto::typed_vec::Values) -> &'static str {
+    use proto::typed_vec::Values;
+    match *values {
+        Values::Integers(_) => "integers",
+        Values::Strings(_) => "strings",
+        Values::RawVals(_) => "raw values",
+    }
+}
+
+/// Orders vectors lexicographically by their values.
+/// Vectors with the same values but a different representation are unordered, since they are not equal.
+impl PartialOrd for proto::TypedVec {
+    fn partial_cmp(&self, other: &proto::TypedVec) -> Option<Ordering> {
+        if self == other {
+            return Some(Ordering::Equal);
+        }
+        match (self.raw_vals(), other.raw_vals()) {
+            (Ok(lhs), Ok(rhs)) => match lhs.cmp(&rhs) {
+                Ordering::Equal => None,
+                ordering => Some(ordering),
+            },
+            _ => None,
+        }
+    }
+}
+
+fn expr(expr: Option<proto::Expr>) -> Result<Expr, QueryError> {
+    use proto::expr::Expr as E;
+    Ok(match expr.and_then(|expr| expr.expr) {
+        Some(E::ColName(name)) => Expr::ColName(name),
+        Some(E::Constant(value)) => Expr::Const(raw_val(value)?),
+        Some(E::Func1(func1)) => {
+            let op = match proto::Func1Type::from_i32(func1.op) {
+                Some(op) => from_proto_func1_type(op, func1.start, func1.length),
+                None => bail!(QueryError::ParseError, "Unknown function type {}", func1.op),
+            };
+            Expr::Func1(op, boxed_expr(func1.expr)?)
+        }
+        Some(E::Func2(func2)) => {
+            let op = match proto::Func2Type::from_i32(func2.op) {
+                Some(op) => from_proto_func2_type(op),
+                None => bail!(QueryError::ParseError, "Unknown function type {}", func2.op),
+            };
+            Expr::Func2(op, boxed_expr(func2.lhs)?, boxed_expr(func2.rhs)?)
+        }
+        Some(E::Aggregate(aggregate)) => {
+            let filter = match aggregate.filter {
+                Some(filter) => Some(boxed_expr(Some(filter))?),
+                None => None,
+            };
+            Expr::Aggregate(aggregator(aggregate.aggregator)?, boxed_expr(aggregate.expr)?, filter)
+        }
+        None => bail!(QueryError::ParseError, "Missing expression"),
+    })
+}
+
+fn boxed_expr(expr_: Option<Box<proto::Expr>>) -> Result<Box<Expr>, QueryError> {
+    expr(expr_.map(|box expr| expr)).map(Box::new)
+}
+
+fn aggregator(aggregator: Option<proto::Aggregator>) -> Result<Aggregator, QueryError> {
+    let aggregator = match aggregator {
+        Some(aggregator) => aggregator,
+        None => bail!(QueryError::ParseError, "Missing aggregator"),
+    };
+    Ok(match proto::AggregatorType::from_i32(aggregator.kind) {
+        Some(proto::AggregatorType::Sum) => Aggregator::Sum,
+        Some(proto::AggregatorType::SaturatingSum) => Aggregator::SaturatingSum,
+        Some(proto::AggregatorType::WrappingSum) => Aggregator::WrappingSum,
+        Some(proto::AggregatorType::Count) => Aggregator::Count,
+        Some(proto::AggregatorType::Max) => Aggregator::Max,
+        Some(proto::AggregatorType::Min) => Aggregator::Min,
+        Some(proto::AggregatorType::ApproxCountDistinct) => {
+            let precision = aggregator.precision;
+            if precision < hyperloglog::MIN_PRECISION || precision > hyperloglog::MAX_PRECISION {
+                bail!(QueryError::ParseError, "Precision of APPROX_COUNT_DISTINCT has to be between {} and {}, got {}",
+                      hyperloglog::MIN_PRECISION, hyperloglog::MAX_PRECISION, precision);
+            }
+            Aggregator::ApproxCountDistinct(precision as u8)
+        }
+        None => bail!(QueryError::ParseError, "Unknown aggregator {}", aggregator.kind),
+    })
+}
+
+fn raw_val(value: proto::RawVal) -> Result<RawVal, QueryError> {
+    Ok(match value.value {
+        Some(proto::raw_val::Value::Int(i)) => RawVal::Int(i),
+        Some(proto::raw_val::Value::Str(s)) => RawVal::Str(s),
+        Some(proto::raw_val::Value::Null(_)) => RawVal::Null,
+        None => bail!(QueryError::ParseError, "Missing constant value"),
+    })
+}
+
+fn to_proto_func1_type(op: Func1Type) -> proto::Func1Type {
+    match op {
+        Func1Type::Negate => proto::Func1Type::Negate,
+        Func1Type::ToYear => proto::Func1Type::ToYear,
+        Func1Type::Not => proto::Func1Type::Not,
+        Func1Type::IsNull => proto::Func1Type::IsNull,
+        Func1Type::IsNotNull => proto::Func1Type::IsNotNull,
+        Func1Type::Length => proto::Func1Type::Length,
+        Func1Type::CharLength => proto::Func1Type::CharLength,
+        Func1Type::Lower => proto::Func1Type::Lower,
+        Func1Type::Upper => proto::Func1Type::Upper,
+        Func1Type::Substring { .. } => proto::Func1Type::Substring,
+        Func1Type::CastInt => proto::Func1Type::CastInt,
+        Func1Type::CastString => proto::Func1Type::CastString,
+    }
+}
+
+fn from_proto_func1_type(op: proto::Func1Type, start: i64, length: i64) -> Func1Type {
+    match op {
+        proto::Func1Type::Negate => Func1Type::Negate,
+        proto::Func1Type::ToYear => Func1Type::ToYear,
+        proto::Func1Type::Not => Func1Type::Not,
+        proto::Func1Type::IsNull => Func1Type::IsNull,
+        proto::Func1Type::IsNotNull => Func1Type::IsNotNull,
+        proto::Func1Type::Length => Func1Type::Length,
+        proto::Func1Type::CharLength => Func1Type::CharLength,
+        proto::Func1Type::Lower => Func1Type::Lower,
+        proto::Func1Type::Upper => Func1Type::Upper,
+        proto::Func1Type::Substring => Func1Type::Substring { start, length },
+        proto::Func1Type::CastInt => Func1Type::CastInt,
+        proto::Func1Type::CastString => Func1Type::CastString,
+    }
+}
+
+fn to_proto_func2_type(op: Func2Type) -> proto::Func2Type {
+    match op {
+        Func2Type::Equals => proto::Func2Type::Equals,
+        Func2Type::NotEquals => proto::Func2Type::NotEquals,
+        Func2Type::LT => proto::Func2Type::Lt,
+        Func2Type::LTE => proto::Func2Type::Lte,
+        Func2Type::GT => proto::Func2Type::Gt,
+        Func2Type::GTE => proto::Func2Type::Gte,
+        Func2Type::And => proto::Func2Type::And,
+        Func2Type::Or => proto::Func2Type::Or,
+        Func2Type::Add => proto::Func2Type::Add,
+        Func2Type::Subtract => proto::Func2Type::Subtract,
+        Func2Type::Multiply => proto::Func2Type::Multiply,
+        Func2Type::Divide => proto::Func2Type::Divide,
+        Func2Type::Modulo => proto::Func2Type::Modulo,
+        Func2Type::RegexMatch => proto::Func2Type::RegexMatch,
+        Func2Type::Like => proto::Func2Type::Like,
+    }
+}
+
+fn from_proto_func2_type(op: proto::Func2Type) -> Func2Type {
+    match op {
+        proto::Func2Type::Equals => Func2Type::Equals,
+        proto::Func2Type::NotEquals => Func2Type::NotEquals,
+        proto::Func2Type::Lt => Func2Type::LT,
+        proto::Func2Type::Lte => Func2Type::LTE,
+        proto::Func2Type::Gt => Func2Type::GT,
+        proto::Func2Type::Gte => Func2Type::GTE,
+        proto::Func2Type::And => Func2Type::And,
+        proto::Func2Type::Or => Func2Type::Or,
+        proto::Func2Type::Add => Func2Type::Add,
+        proto::Func2Type::Subtract => Func2Type::Subtract,
+        proto::Func2Type::Multiply => Func2Type::Multiply,
+        proto::Func2Type::Divide => Func2Type::Divide,
+        proto::Func2Type::Modulo => Func2Type::Modulo,
+        proto::Func2Type::RegexMatch => Func2Type::RegexMatch,
+        proto::Func2Type::Like => Func2Type::Like,
+    }
+}
+
+fn to_proto_duration(duration: Duration) -> proto::Duration {
+    proto::Duration { seconds: duration.as_secs(), nanos: duration.subsec_nanos() }
+}
+
+/// Fails with `QueryError::ParseError` unless `nanos` is less than a second.
+pub fn from_proto_duration(duration: proto::Duration) -> Result<Duration, QueryError> {
+    if duration.nanos >= 1_000_000_000 {
+        bail!(QueryError::ParseError, "Duration with {} nanoseconds, which must be less than a second", duration.nanos)
+    }
+    Ok(Duration::new(duration.seconds, duration.nanos))
+}
+
+// Time since the epoch, `SystemTime` represents the seconds as i64
+fn from_proto_timestamp(duration: proto::Duration) -> Result<SystemTime, QueryError> {
+    let duration = from_proto_duration(duration)?;
+    if duration.as_secs() > i64::MAX as u64 {
+        bail!(QueryError::ParseError, "Timestamp {} seconds after the epoch is out of range", duration.as_secs())
+    }
+    Ok(UNIX_EPOCH + duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax::parser;
+
+    #[test]
+    fn test_round_trip() {
+        let queries = [
+            "SELECT * FROM default",
+            "SELECT a AS x, COUNT(0), SUM(b * 2) FROM t WHERE a = 'foo' AND b < 3 AND c IS NULL \
+             GROUP BY x HAVING SUM(c) > 10 ORDER BY x DESC LIMIT 10 OFFSET 3",
+            "SELECT approx_count_distinct(a), to_year(ts) FROM t SAMPLE 12.5 PERCENT SEED 42 FOR SYSTEM_TIME AS OF 1500000000",
+            "SELECT length(a) FROM t SAMPLE 1000 WHERE a LIKE '%x_' AND a ~ '^b'",
+            "SELECT substring(a, 2, 3), substr(a, 4), char_length(a), lower(a), upper(b) FROM t",
+            "SELECT a, b, c FROM t ORDER BY c DESC LIMIT 2 BY a, b LIMIT 10",
+            "SELECT a, b FROM t ORDER BY a NULLS FIRST, b DESC NULLS LAST",
+            "SELECT CAST(a AS INT), CAST(b AS VARCHAR) FROM t",
+            "EXPLAIN SELECT max(a) FROM t",
+        ];
+        for &query in &queries {
+            let parsed = parser::parse_query(query).unwrap();
+            let message = proto::Query::from(parsed.clone());
+            let converted = parse_query(message).unwrap();
+            assert_eq!(format!("{:?}", converted), format!("{:?}", parsed), "{}", query);
+        }
+
+        let mut message = proto::Query::from(parser::parse_query("SELECT a FROM t").unwrap());
+        message.select[0].expr = None;
+        assert!(parse_query(message).is_err());
+    }
+
+    #[test]
+    fn test_unspecified_nulls_order() {
+        let mut message = proto::Query::from(parser::parse_query("SELECT a FROM t ORDER BY a, b DESC").unwrap());
+        for order in &mut message.order_by {
+            order.nulls = proto::NullsOrder::Unspecified as i32;
+        }
+        let nulls = parse_query(message.clone()).unwrap().order_by.iter().map(|&(_, _, nulls)| nulls).collect::<Vec<_>>();
+        assert_eq!(nulls, vec![NullsOrder::Last, NullsOrder::First]);
+        message.order_by[0].nulls = 3;
+        assert!(parse_query(message).is_err());
+    }
+
+    #[test]
+    fn test_invalid_durations() {
+        let mut message = proto::Query::from(parser::parse_query("SELECT a FROM t").unwrap());
+        message.timeout = Some(proto::Duration { seconds: u64::max_value(), nanos: 1_000_000_000 });
+        assert!(parse_query(message.clone()).is_err());
+        message.timeout = Some(proto::Duration { seconds: u64::max_value(), nanos: 999_999_999 });
+        assert_eq!(parse_query(message.clone()).unwrap().timeout, Some(Duration::new(u64::max_value(), 999_999_999)));
+        message.as_of = Some(proto::Duration { seconds: u64::max_value(), nanos: 0 });
+        assert!(parse_query(message).is_err());
+    }
+
+    fn typed_vec(values: Vec<RawVal>) -> proto::TypedVec {
+        use proto::typed_vec::Values;
+        let values = if values.iter().all(|value| match value { RawVal::Int(_) => true, _ => false }) {
+            Values::Integers(proto::Integers {
+                values: values.into_iter().map(|value| if let RawVal::Int(i) = value { i } else { unreachable!() }).collect(),
+            })
+        } else if values.iter().all(|value| match value { RawVal::Str(_) => true, _ => false }) {
+            Values::Strings(proto::Strings {
+                values: values.into_iter().map(|value| if let RawVal::Str(s) = value { s } else { unreachable!() }).collect(),
+            })
+        } else {
+            Values::RawVals(proto::RawVals { values: values.into_iter().map(Into::into).collect() })
+        };
+        proto::TypedVec { values: Some(values) }
+    }
+
+    #[test]
+    fn test_typed_vec_ordering() {
+        let ints = typed_vec(vec![RawVal::Int(1), RawVal::Int(2)]);
+        let mixed = typed_vec(vec![RawVal::Int(1), RawVal::Null]);
+        let strs = typed_vec(vec![RawVal::Str("a".to_string())]);
+        assert_eq!(ints.partial_cmp(&ints.clone()), Some(Ordering::Equal));
+        assert!(mixed > ints);
+        assert!(strs > ints);
+
+        let raw_ints = proto::TypedVec {
+            values: Some(proto::typed_vec::Values::RawVals(proto::RawVals {
+                values: vec![RawVal::Int(1).into(), RawVal::Int(2).into()],
+            })),
+        };
+        assert_ne!(raw_ints, ints);
+        assert_eq!(raw_ints.raw_vals().unwrap(), ints.raw_vals().unwrap());
+        assert_eq!(raw_ints.partial_cmp(&ints), None);
+    }
+
+    #[test]
+    fn test_typed_vec_concat() {
+        let ints = |values: Vec<i64>| typed_vec(values.into_iter().map(RawVal::Int).collect());
+        let concatenated = proto::TypedVec::concat(vec![ints(vec![1, 2]), typed_vec(vec![]), ints(vec![3])]).unwrap();
+        assert_eq!(concatenated, ints(vec![1, 2, 3]));
+
+        let strs = typed_vec(vec![RawVal::Str("a".to_string())]);
+        assert_eq!(proto::TypedVec::concat(vec![typed_vec(vec![]), strs.clone()]).unwrap(), strs);
+        assert_eq!(proto::TypedVec::concat(vec![]).unwrap(), typed_vec(vec![]));
+        let mixed = typed_vec(vec![RawVal::Int(1), RawVal::Null]);
+        assert_eq!(proto::TypedVec::concat(vec![mixed.clone(), mixed]).unwrap().raw_vals().unwrap(),
+                   vec![RawVal::Int(1), RawVal::Null, RawVal::Int(1), RawVal::Null]);
+        assert!(proto::TypedVec::concat(vec![ints(vec![1]), strs]).is_err());
+    }
+
+    #[test]
+    fn test_typed_vec_slice() {
+        let ints = typed_vec(vec![RawVal::Int(1), RawVal::Int(2), RawVal::Int(3)]);
+        assert_eq!(ints.slice(1, 2), typed_vec(vec![RawVal::Int(2)]));
+        assert_eq!(ints.slice(1, 10), typed_vec(vec![RawVal::Int(2), RawVal::Int(3)]));
+        assert_eq!(ints.slice(5, 10), typed_vec(vec![]));
+        let mixed = typed_vec(vec![RawVal::Null, RawVal::Str("a".to_string()), RawVal::Int(1)]);
+        assert_eq!(mixed.slice(0, 2).raw_vals().unwrap(), vec![RawVal::Null, RawVal::Str("a".to_string())]);
+    }
+}
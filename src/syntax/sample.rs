@@ -0,0 +1,79 @@
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
+pub enum SampleSize {
+    Rows(u64),
+    Percent(f64),
+}
+
+/// Restricts a query to a random subset of the rows in the table.
+/// Every row is part of the sample independently with the same probability, which is `min(1, n / table_len)` for
+/// `SAMPLE n ROWS` and `p / 100` for `SAMPLE p PERCENT`. The number of sampled rows is therefore only `n` (or
+/// `p * table_len / 100`) in expectation.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
+pub struct SampleClause {
+    pub size: SampleSize,
+    /// Queries with the same seed sample the same rows.
+    pub seed: Option<u64>,
+}
+
+impl SampleClause {
+    /// Probability with which each row of a table with `table_len` rows is part of the sample.
+    pub fn probability(&self, table_len: usize) -> f64 {
+        match self.size {
+            SampleSize::Rows(rows) => if rows >= table_len as u64 { 1.0 } else { rows as f64 / table_len as f64 },
+            SampleSize::Percent(percent) => percent.max(0.0).min(100.0) / 100.0,
+        }
+    }
+
+    /// Determines which rows of a table with `table_len` rows are part of the sample.
+    pub fn resolve(&self, table_len: usize, default_seed: u64) -> SampledRows {
+        let probability = self.probability(table_len);
+        SampledRows {
+            seed: self.seed.unwrap_or(default_seed),
+            threshold: if probability >= 1.0 { None } else { Some((probability * 2f64.powi(64)) as u64) },
+        }
+    }
+}
+
+/// Selects the rows of a `SampleClause` without materializing them. The row with row number `row` is sampled if a
+/// pseudorandom key derived from `seed` and `row` is smaller than `threshold`, so that the sample does not depend on
+/// how the table is partitioned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampledRows {
+    pub seed: u64,
+    /// `None` samples all rows.
+    pub threshold: Option<u64>,
+}
+
+impl SampledRows {
+    /// Probability with which each row is part of the sample.
+    pub fn probability(&self) -> f64 {
+        self.threshold.map_or(1.0, |threshold| threshold as f64 / 2f64.powi(64))
+    }
+}
+
+/// Restricts the result of a query to a uniform random sample of at most `rows` of the rows that satisfy the filter.
+/// Unlike `SampleClause`, the sample is taken after filtering and its size does not depend on the size of the table.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
+pub struct ReservoirSample {
+    pub rows: u64,
+    /// Queries with the same seed on the same table return the same sample.
+    pub seed: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampled_rows() {
+        let sample = SampleClause { size: SampleSize::Percent(10.0), seed: Some(3) }.resolve(95, 0);
+        assert_eq!(sample.seed, 3);
+        assert!((sample.probability() - 0.1).abs() < 1e-9);
+        assert_eq!(SampleClause { size: SampleSize::Rows(200), seed: None }.resolve(95, 7), SampledRows { seed: 7, threshold: None });
+        assert_eq!(SampleClause { size: SampleSize::Rows(0), seed: None }.resolve(95, 7).threshold, Some(0));
+        assert_eq!(SampleClause { size: SampleSize::Rows(19), seed: None }.probability(95), 0.2);
+    }
+}
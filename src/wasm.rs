@@ -0,0 +1,137 @@
+extern crate js_sys;
+extern crate wasm_bindgen;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use futures_executor::block_on;
+use serde_json::{Map, Value};
+use self::js_sys::{Date, Promise};
+use self::wasm_bindgen::prelude::*;
+
+use engine::Query;
+use ingest::raw_val::RawVal;
+use locustdb;
+
+/// Stands in for the `time` crate, which has no clock in the browser.
+#[cfg(target_arch = "wasm32")]
+pub mod time {
+    use super::js_sys::Date;
+
+    pub struct Timespec {
+        pub sec: i64,
+        pub nsec: i32,
+    }
+
+    pub fn precise_time_ns() -> u64 {
+        (Date::now() * 1_000_000.0) as u64
+    }
+
+    pub fn get_time() -> Timespec {
+        let ms = Date::now() as i64;
+        Timespec { sec: ms / 1000, nsec: (ms % 1000) as i32 * 1_000_000 }
+    }
+}
+
+/// JavaScript API of an in-memory database that runs all tasks on the calling thread.
+/// Files can't be loaded or persisted, data is ingested from JavaScript objects with `ingestJson`.
+#[wasm_bindgen]
+pub struct LocustDB {
+    locustdb: locustdb::LocustDB,
+}
+
+#[wasm_bindgen]
+impl LocustDB {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> LocustDB {
+        let opts = locustdb::Options {
+            threads: 1,
+            read_threads: 1,
+            mem_lz4: false,
+            ..locustdb::Options::default()
+        };
+        LocustDB { locustdb: locustdb::LocustDB::new(&opts) }
+    }
+
+    /// Adds the objects in the array `rows` to `table` as a single partition and returns the number of rows added.
+    /// Each property becomes a column, rows that don't have the property are null in that column.
+    #[wasm_bindgen(js_name = ingestJson)]
+    pub fn ingest_json(&self, table: &str, rows: &JsValue) -> Result<usize, JsValue> {
+        let rows = rows.into_serde::<Vec<Map<String, Value>>>()
+            .map_err(|err| JsValue::from_str(&format!("Expected array of objects: {}", err)))?;
+        let columns = json_columns(&rows).map_err(|err| JsValue::from_str(&err))?;
+        let ts = UNIX_EPOCH + Duration::from_millis(Date::now() as u64);
+        let receiver = self.locustdb.ingest_columns_at(table, columns, ts);
+        self.locustdb.run_pending_tasks();
+        block_on(receiver).map_err(|_| JsValue::from_str("Ingestion was canceled"))
+    }
+
+    /// Runs the JSON encoded `Query` and resolves to an object with the result column names `colnames`, the result
+    /// `rows` and `truncated`. Timeouts are ignored, since there is no clock to enforce them.
+    pub fn query(&self, query: &str) -> Promise {
+        let mut query = match Query::from_json(query) {
+            Ok(query) => query,
+            Err(err) => return Promise::reject(&JsValue::from_str(&err.to_string())),
+        };
+        query.timeout = None;
+        let receiver = self.locustdb.run_parsed_query(query, false, vec![]);
+        self.locustdb.run_pending_tasks();
+        match block_on(receiver) {
+            Ok((Ok(output), _)) => Promise::resolve(&JsValue::from_serde(&output.to_json()).unwrap()),
+            Ok((Err(err), _)) => Promise::reject(&JsValue::from_str(&err.to_string())),
+            Err(_) => Promise::reject(&JsValue::from_str("Query was canceled")),
+        }
+    }
+}
+
+/// Converts rows of JSON values into columns in order of first appearance of each property.
+/// Booleans are stored as 0 and 1, other values have to be integers, strings or null.
+fn json_columns(rows: &[Map<String, Value>]) -> Result<Vec<(String, Vec<RawVal>)>, String> {
+    let mut columns = Vec::<(String, Vec<RawVal>)>::new();
+    for (i, row) in rows.iter().enumerate() {
+        for (name, value) in row {
+            let value = match value {
+                Value::Null => RawVal::Null,
+                Value::Bool(b) => RawVal::Int(*b as i64),
+                Value::Number(n) => match n.as_i64() {
+                    Some(n) => RawVal::Int(n),
+                    None => return Err(format!("Value {} of {} is not a 64 bit integer", n, name)),
+                },
+                Value::String(s) => RawVal::Str(s.to_string()),
+                _ => return Err(format!("Value {} of {} is not an integer, string or null", value, name)),
+            };
+            match columns.iter().position(|(existing, _)| existing == name) {
+                Some(index) => columns[index].1.push(value),
+                None => {
+                    let mut values = vec![RawVal::Null; i];
+                    values.push(value);
+                    columns.push((name.to_string(), values));
+                }
+            }
+        }
+        for (_, values) in &mut columns {
+            if values.len() == i {
+                values.push(RawVal::Null);
+            }
+        }
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_columns() {
+        let rows = ::serde_json::from_str::<Vec<Map<String, Value>>>(
+            r#"[{"a": 1, "b": "x"}, {"b": null, "c": true}, {"a": -3}]"#).unwrap();
+        assert_eq!(json_columns(&rows).unwrap(), vec![
+            ("a".to_string(), vec![RawVal::Int(1), RawVal::Null, RawVal::Int(-3)]),
+            ("b".to_string(), vec![RawVal::Str("x".to_string()), RawVal::Null, RawVal::Null]),
+            ("c".to_string(), vec![RawVal::Null, RawVal::Int(1), RawVal::Null]),
+        ]);
+
+        let rows = ::serde_json::from_str::<Vec<Map<String, Value>>>(r#"[{"a": 1.5}]"#).unwrap();
+        assert!(json_columns(&rows).is_err());
+    }
+}
@@ -10,7 +10,9 @@ use locustdb::*;
 use locustdb::nyc_taxi_data;
 use locustdb::Value;
 use std::cmp::min;
+use std::i64;
 use std::env;
+use std::time::{Duration, UNIX_EPOCH};
 use value_syntax::*;
 
 fn test_query(query: &str, expected_rows: &[Vec<Value>]) {
@@ -31,6 +33,21 @@ fn test_query(query: &str, expected_rows: &[Vec<Value>]) {
     assert_eq!(result.0.unwrap().rows, expected_rows);
 }
 
+/// Loads `test_data/edge_cases.csv` into the table `default`, split into partitions of `partition_size` rows.
+fn load_edge_cases(locustdb: &LocustDB, partition_size: usize) {
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/edge_cases.csv", "default")
+            .with_partition_size(partition_size)
+            .allow_nulls_all_columns()));
+}
+
+/// Loads `test_data/edge_cases.csv` into a single partition of the table `default`.
+fn load_edge_cases_unpartitioned(locustdb: &LocustDB) {
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/edge_cases.csv", "default")
+            .allow_nulls_all_columns()));
+}
+
 fn test_query_ec(query: &str, expected_rows: &[Vec<Value>]) {
     let _ = env_logger::try_init();
     #[allow(unused_mut)]
@@ -39,10 +56,7 @@ fn test_query_ec(query: &str, expected_rows: &[Vec<Value>]) {
         opts.threads = 1;
     }
     let locustdb = LocustDB::new(&opts);
-    let _ = block_on(locustdb.load_csv(
-        LoadOptions::new("test_data/edge_cases.csv", "default")
-            .with_partition_size(3)
-            .allow_nulls_all_columns()));
+    load_edge_cases(&locustdb, 3);
     let result = if env::var("DEBUG_TESTS").is_ok() {
         block_on(locustdb.run_query(query, false, vec![0, 1, 2, 3])).unwrap()
     } else {
@@ -59,10 +73,7 @@ fn test_query_ec_err(query: &str, _expected_err: QueryError) {
         opts.threads = 1;
     }
     let locustdb = LocustDB::new(&opts);
-    let _ = block_on(locustdb.load_csv(
-        LoadOptions::new("test_data/edge_cases.csv", "default")
-            .with_partition_size(3)
-            .allow_nulls_all_columns()));
+    load_edge_cases(&locustdb, 3);
     let result = if env::var("DEBUG_TESTS").is_ok() {
         block_on(locustdb.run_query(query, false, vec![0, 1, 2, 3])).unwrap()
     } else {
@@ -322,6 +333,27 @@ fn test_regex() {
     );
 }
 
+#[test]
+fn test_regex_operator() {
+    // `tld` is dictionary encoded
+    test_query(
+        "SELECT tld, COUNT(1) FROM default WHERE tld ~ '^(gov|mil)$';",
+        &[vec![Str("gov"), Int(5)], vec![Str("mil"), Int(11)]],
+    );
+    test_query(
+        "SELECT first_name FROM default WHERE first_name ~ '^C.+h.a';",
+        &[vec![Str("Cynthia")]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE string_packed ~ '^a' AND id > 2;",
+        &[vec![Int(4)], vec![Int(6)]],
+    );
+    test_query_ec_err(
+        "SELECT id FROM default WHERE string_packed ~ '(';",
+        QueryError::TypeError(String::new()),
+    );
+}
+
 #[test]
 fn test_like() {
     test_query(
@@ -573,6 +605,34 @@ fn test_string_length() {
     )
 }
 
+#[test]
+fn test_substring() {
+    test_query_ec(
+        "SELECT substring(string_packed, 1, 1), COUNT(0)
+         FROM default;",
+        &[
+            vec![Str("$"), Int(1)],
+            vec![Str("A"), Int(1)],
+            vec![Str("_"), Int(1)],
+            vec![Str("a"), Int(4)],
+            vec![Str("t"), Int(1)],
+            vec![Str("x"), Int(1)],
+            vec![Str("😈"), Int(1)],
+        ],
+    );
+    test_query_ec(
+        "SELECT id, substring(string_packed, 0, 3), substring(string_packed, 2), length(string_packed), char_length(string_packed)
+         FROM default
+         WHERE id >= 7
+         ORDER BY id;",
+        &[
+            vec![Int(7), Str("_f"), Str("f"), Int(2), Int(2)],
+            vec![Int(8), Str("t"), Str(""), Int(1), Int(1)],
+            vec![Int(9), Str("😈"), Str(""), Int(4), Int(1)],
+        ],
+    );
+}
+
 #[test]
 fn test_group_by_negative_expression() {
     test_query_ec(
@@ -625,6 +685,27 @@ fn test_order_by_multiple() {
     )
 }
 
+#[test]
+fn test_order_by_mixed_directions() {
+    test_query_ec(
+        "SELECT enum, non_dense_ints, id
+         FROM default
+         ORDER BY enum ASC, non_dense_ints DESC, id ASC;",
+        &[
+            vec![Str("aa"), Int(3), Int(2)],
+            vec![Str("aa"), Int(2), Int(1)],
+            vec![Str("aa"), Int(1), Int(7)],
+            vec![Str("aa"), Int(0), Int(0)],
+            vec![Str("aa"), Int(0), Int(5)],
+            vec![Str("bb"), Int(4), Int(4)],
+            vec![Str("bb"), Int(3), Int(9)],
+            vec![Str("bb"), Int(1), Int(3)],
+            vec![Str("cc"), Int(2), Int(6)],
+            vec![Str("cc"), Int(2), Int(8)],
+        ],
+    )
+}
+
 #[test]
 fn test_null_aggregators() {
     test_query_ec(
@@ -638,6 +719,61 @@ fn test_null_aggregators() {
     );
 }
 
+#[test]
+fn test_count_nullable_column() {
+    test_query_ec(
+        "SELECT COUNT(*), COUNT(1), COUNT(country), COUNT(nullable_int) FROM default;",
+        &[vec![Int(10), Int(10), Int(6), Int(5)]],
+    );
+    test_query_ec(
+        "SELECT id/5, COUNT(*), COUNT(country) FROM default ORDER BY id/5;",
+        &[
+            vec![Int(0), Int(5), Int(4)],
+            vec![Int(1), Int(5), Int(2)],
+        ],
+    );
+}
+
+#[test]
+fn test_order_by_many_partitions() {
+    let locustdb = LocustDB::memory_only();
+    let partitions = [
+        (vec![1, 4, 7, 10], vec!["d", "c", "b", "a"]),
+        (vec![2, 4, 8, 9], vec!["a", "a", "c", "d"]),
+        (vec![3, 5, 6, 10], vec!["b", "d", "a", "c"]),
+        (vec![0, 7, 11, 12], vec!["c", "c", "a", "b"]),
+    ];
+    for (ts, names) in &partitions {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("ts", InputColumn::Int(ts.clone()));
+        transaction.add_column("name", InputColumn::Str(names.iter().map(|name| name.to_string()).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT ts, name FROM default ORDER BY ts DESC, name LIMIT 5;"),
+               vec![vec![Int(12), Str("b")], vec![Int(11), Str("a")], vec![Int(10), Str("a")],
+                    vec![Int(10), Str("c")], vec![Int(9), Str("d")]]);
+    assert_eq!(query("SELECT ts, name FROM default ORDER BY name, ts DESC LIMIT 4;"),
+               vec![vec![Int(11), Str("a")], vec![Int(10), Str("a")], vec![Int(6), Str("a")], vec![Int(4), Str("a")]]);
+    assert_eq!(query("SELECT ts FROM default ORDER BY ts LIMIT 3 OFFSET 4;"), vec![vec![Int(4)], vec![Int(4)], vec![Int(5)]]);
+    assert_eq!(query("SELECT name FROM default WHERE ts > 8 ORDER BY name DESC;"),
+               vec![vec![Str("d")], vec![Str("c")], vec![Str("b")], vec![Str("a")], vec![Str("a")]]);
+}
+
+#[test]
+fn test_order_by_ties() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int((0..30).collect()));
+    transaction.add_column("key", InputColumn::Int((0..30).map(|i| i % 3).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    let ids = |key: i64| (0..30).filter(|i| i % 3 == key).map(|i| vec![Int(i)]).collect::<Vec<_>>();
+    assert_eq!(query("SELECT id FROM default ORDER BY key;"), [ids(0), ids(1), ids(2)].concat());
+    assert_eq!(query("SELECT id FROM default ORDER BY key DESC;"), [ids(2), ids(1), ids(0)].concat());
+    assert_eq!(query("SELECT id FROM default ORDER BY key DESC LIMIT 20;"), [ids(2), ids(1)].concat());
+}
+
 #[test]
 fn test_sort_by_nullable() {
     test_query_ec(
@@ -645,16 +781,16 @@ fn test_sort_by_nullable() {
          FROM default
          ORDER BY nullable_int, nullable_int2 DESC, country;",
         &[
-            vec![Null, Int(6), Null],
-            vec![Null, Int(1), Null],
-            vec![Null, Int(0), Null],
-            vec![Null, Null, Str("France")],
-            vec![Null, Null, Str("Turkey")],
             vec![Int(-40), Int(-40), Str("USA")],
             vec![Int(-1), Null, Str("Germany")],
             vec![Int(10), Int(9), Str("France")],
             vec![Int(13), Int(14), Str("Germany")],
             vec![Int(20), Null, Null],
+            vec![Null, Null, Str("France")],
+            vec![Null, Null, Str("Turkey")],
+            vec![Null, Int(6), Null],
+            vec![Null, Int(1), Null],
+            vec![Null, Int(0), Null],
         ],
     );
     test_query_ec(
@@ -662,16 +798,62 @@ fn test_sort_by_nullable() {
          FROM default
          ORDER BY nullable_int2, country DESC;",
         &[
-            vec![Null, Str("Turkey")],
-            vec![Null, Str("Germany")],
-            vec![Null, Str("France")],
-            vec![Null, Null],
             vec![Int(-40), Str("USA")],
             vec![Int(0), Null],
             vec![Int(1), Null],
             vec![Int(6), Null],
             vec![Int(9), Str("France")],
             vec![Int(14), Str("Germany")],
+            vec![Null, Null],
+            vec![Null, Str("Turkey")],
+            vec![Null, Str("Germany")],
+            vec![Null, Str("France")],
+        ],
+    );
+}
+
+#[test]
+fn test_order_by_nulls_order() {
+    test_query_ec(
+        "SELECT nullable_int FROM default ORDER BY nullable_int NULLS FIRST;",
+        &[
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Int(-40)],
+            vec![Int(-1)],
+            vec![Int(10)],
+            vec![Int(13)],
+            vec![Int(20)],
+        ],
+    );
+    test_query_ec(
+        "SELECT nullable_int FROM default ORDER BY nullable_int DESC NULLS LAST LIMIT 6;",
+        &[
+            vec![Int(20)],
+            vec![Int(13)],
+            vec![Int(10)],
+            vec![Int(-1)],
+            vec![Int(-40)],
+            vec![Null],
+        ],
+    );
+    test_query_ec(
+        "SELECT nullable_int2 FROM default UNION ALL SELECT nullable_int FROM default
+         ORDER BY nullable_int2 NULLS FIRST LIMIT 10;",
+        &[
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Null],
+            vec![Int(-40)],
         ],
     );
 }
@@ -831,70 +1013,1670 @@ fn test_is_null() {
 }
 
 #[test]
-fn test_overflow() {
-    test_query_ec_err(
-        "SELECT largenum + non_dense_ints FROM default;",
-        QueryError::Overflow,
+fn test_is_null_reads_only_null_map() {
+    let locustdb = LocustDB::memory_only();
+    load_edge_cases(&locustdb, 3);
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    let output = query("SELECT id FROM default WHERE nullable_int IS NULL ORDER BY id;");
+    assert_eq!(output.rows, vec![vec![Int(2)], vec![Int(3)], vec![Int(5)], vec![Int(6)], vec![Int(8)]]);
+    assert!(!output.stats.decoded_columns.contains(&"nullable_int".to_string()));
+    // The last partition only contains the row with id 9, which is not null
+    assert_eq!(output.stats.rows_scanned, 9);
+    let output = query("SELECT id, nullable_int FROM default WHERE nullable_int IS NOT NULL AND id > 3 ORDER BY id;");
+    assert_eq!(output.rows, vec![vec![Int(4), Int(10)], vec![Int(7), Int(20)], vec![Int(9), Int(13)]]);
+    assert_eq!(output.stats.rows_scanned, 10);
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE nullable_int IS NULL OR id = 9;").rows, vec![vec![Int(6)]]);
+}
+
+#[test]
+fn test_equality_filter_skips_partitions() {
+    let locustdb = LocustDB::memory_only();
+    let countries: [&[&str]; 3] = [&["Germany", "USA", "France"], &["France", "Spain"], &["Turkey", "Germany"]];
+    for (partition, names) in countries.iter().enumerate() {
+        let mut transaction = Transaction::new("default");
+        let offset = partition as i64 * 100;
+        transaction.add_column("id", InputColumn::Int((offset..offset + 100).collect()));
+        transaction.add_column("country", InputColumn::Str((0..100).map(|i| names[i % names.len()].to_string()).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    // The first partition is always scanned, the bloom index of the second partition excludes Turkey
+    let output = query("SELECT COUNT(0) FROM default WHERE country = 'Turkey';");
+    assert_eq!(output.rows, vec![vec![Int(50)]]);
+    assert_eq!(output.stats.rows_scanned, 200);
+    // Only the last partition contains neither USA nor the id 150
+    let output = query("SELECT COUNT(0) FROM default WHERE country = 'USA' OR id = 150;");
+    assert_eq!(output.rows, vec![vec![Int(34)]]);
+    assert_eq!(output.stats.rows_scanned, 200);
+    let output = query("SELECT id FROM default WHERE country = 'Italy';");
+    assert_eq!(output.rows, Vec::<Vec<Value>>::new());
+    assert_eq!(output.stats.rows_scanned, 100);
+}
+
+#[test]
+fn test_range_filter_skips_frame_of_reference_partitions() {
+    let locustdb = LocustDB::memory_only();
+    for partition in 0..3 {
+        let mut transaction = Transaction::new("default");
+        // Values differ by less than 256 within each frame of 1024 rows, but span more than u16 overall
+        let values = (0..2048).map(|i| partition * 1_000_000 + i / 1024 * 100_000 + i % 200).collect();
+        transaction.add_column("value", InputColumn::Int(values));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    let output = query("SELECT COUNT(0) FROM default WHERE value >= 2000000;");
+    assert_eq!(output.rows, vec![vec![Int(2048)]]);
+    // The first partition is always scanned, the second one lies entirely below the range
+    assert_eq!(output.stats.rows_scanned, 4096);
+    let output = query("SELECT COUNT(0) FROM default WHERE value > 3000000;");
+    assert_eq!(output.rows, Vec::<Vec<Value>>::new());
+    assert_eq!(output.stats.rows_scanned, 2048);
+}
+
+#[test]
+fn test_time_range_skips_partitions() {
+    let locustdb = LocustDB::memory_only();
+    for day in 0..3 {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("ts", InputColumn::Int((0..100).map(|i| day * 86_400 + i * 60).collect()));
+        transaction.add_column("value", InputColumn::Int((0..100).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    let output = query("SELECT COUNT(0), SUM(value) FROM default WHERE ts >= 172800 AND ts < 175800;");
+    assert_eq!(output.rows, vec![vec![Int(50), Int(1225)]]);
+    // The first partition is always scanned, the second one lies entirely before the time range
+    assert_eq!(output.stats.rows_scanned, 200);
+    let output = query("SELECT COUNT(0) FROM default WHERE ts < 0 OR ts > 1000000;");
+    assert_eq!(output.rows, Vec::<Vec<Value>>::new());
+    assert_eq!(output.stats.rows_scanned, 100);
+}
+
+#[test]
+fn test_sorted_column_range() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("ts", InputColumn::Int((0..1000).map(|i| 1_000_000 + 3 * i).collect()));
+    transaction.add_column("value", InputColumn::Int((0..1000).map(|i| (i * 7919) % 1000).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    let count = |filter: &str| query(&format!("SELECT COUNT(0) FROM default WHERE {};", filter));
+    assert_eq!(count("ts >= 1000300 AND ts < 1000600"), vec![vec![Int(100)]]);
+    assert_eq!(count("ts > 1000300 AND ts <= 1000600"), vec![vec![Int(100)]]);
+    assert_eq!(count("1000030 > ts"), vec![vec![Int(10)]]);
+    assert_eq!(count("ts = 1000009"), vec![vec![Int(1)]]);
+    assert_eq!(count("ts = 1000010"), Vec::<Vec<Value>>::new());
+    assert_eq!(count("ts < 0"), Vec::<Vec<Value>>::new());
+    assert_eq!(count("ts > 9223372036854775807"), Vec::<Vec<Value>>::new());
+    assert_eq!(count("ts <= 2000000"), vec![vec![Int(1000)]]);
+    assert_eq!(count("ts < 1000030 AND value < 500"), vec![vec![Int(4)]]);
+    assert_eq!(query("SELECT ts FROM default WHERE value = 0 AND ts < 1000030;"), vec![vec![Int(1_000_000)]]);
+
+    let explain = |query: &str| match block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows[0][0] {
+        Value::Str(ref plan) => plan.to_string(),
+        ref value => panic!("Expected string, got {:?}", value),
+    };
+    assert!(explain("EXPLAIN SELECT COUNT(0) FROM default WHERE ts > 1000300;").contains("SortedRange {"));
+    assert!(!explain("EXPLAIN SELECT COUNT(0) FROM default WHERE value > 300;").contains("SortedRange {"));
+}
+
+#[test]
+fn test_sum_of_columns() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("host", InputColumn::Str((0..100).map(|i| format!("h{}", i % 3)).collect()));
+    transaction.add_column("sent", InputColumn::Int((0..100).map(|i| 1_000_000 + i * 7).collect()));
+    transaction.add_column("received", InputColumn::Int((0..100).map(|i| 5000 - i * i).collect()));
+    transaction.add_column("dropped", InputColumn::Int((0..100).map(|i| i % 4).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT SUM(sent + received - dropped) FROM default;"),
+               query("SELECT SUM(sent) + SUM(received) - SUM(dropped) FROM default;"));
+    assert_eq!(query("SELECT host, SUM(sent - received) FROM default;"),
+               query("SELECT host, SUM(sent) - SUM(received) FROM default;"));
+    let aggregations = |query: &str| match block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows[0][0] {
+        Value::Str(ref plan) => plan.matches("CheckedAggregate {").count(),
+        ref value => panic!("Expected string, got {:?}", value),
+    };
+    assert_eq!(aggregations("EXPLAIN SELECT SUM(sent + received) FROM default;"), 2);
+    assert_eq!(aggregations("EXPLAIN SELECT SUM(sent * received) FROM default;"), 1);
+
+    // Rows with nulls are excluded from the sum, so nullable columns are not summed independently
+    let mut transaction = Transaction::new("nullable");
+    transaction.add_column("a", InputColumn::Int(vec![1, 2, 3]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let mut transaction = Transaction::new("nullable");
+    transaction.add_column("a", InputColumn::Int(vec![4]));
+    transaction.add_column("b", InputColumn::Int(vec![10]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    assert_eq!(query("SELECT SUM(a + b) FROM nullable;"), vec![vec![Int(14)]]);
+}
+
+#[test]
+fn test_group_by_modulo() {
+    test_query_ec(
+        "SELECT id % 3, COUNT(0) FROM default;",
+        &[
+            vec![Int(0), Int(4)],
+            vec![Int(1), Int(3)],
+            vec![Int(2), Int(3)],
+        ],
+    );
+    test_query_ec(
+        "SELECT negative % 10, COUNT(0) FROM default;",
+        &[
+            vec![Int(-9), Int(1)],
+            vec![Int(0), Int(5)],
+            vec![Int(1), Int(1)],
+            vec![Int(2), Int(1)],
+            vec![Int(4), Int(1)],
+            vec![Int(9), Int(1)],
+        ],
     );
     test_query_ec_err(
-        "SELECT largenum + nullable_int FROM default;",
+        "SELECT id % 0, COUNT(0) FROM default;",
         QueryError::Overflow,
     );
+}
+
+#[test]
+fn test_filtered_aggregate() {
     test_query_ec(
-        "SELECT largenum / nullable_int FROM default ORDER BY id;",
+        "SELECT COUNT_IF(negative > 0), COUNT(0) FROM default;",
+        &[vec![Int(5), Int(10)]],
+    );
+    test_query_ec(
+        "SELECT country, COUNT_IF(id > 4), SUM_IF(id, id > 4), COUNT(0) FROM default;",
         &[
-            vec![Int(9223372036854775807)],
-            vec![Int(-230584300921369395)],
-            vec![Null],
-            vec![Null],
-            vec![Int(-922337203685477580)],
-            vec![Null],
-            vec![Null],
-            vec![Int(461168601842738790)],
-            vec![Null],
-            vec![Int(709490156681136600)],
+            vec![Null, Int(3), Int(20), Int(4)],
+            vec![Str("France"), Int(0), Int(0), Int(2)],
+            vec![Str("Germany"), Int(1), Int(9), Int(2)],
+            vec![Str("Turkey"), Int(1), Int(6), Int(1)],
+            vec![Str("USA"), Int(0), Int(0), Int(1)],
+        ],
+    );
+    test_query_ec(
+        "SELECT MAX_IF(negative, id < 5), MIN_IF(negative, id >= 5) FROM default;",
+        &[vec![Int(4031), Int(-130)]],
+    );
+}
+
+#[test]
+fn test_approx_count_distinct() {
+    test_query_ec(
+        "SELECT APPROX_COUNT_DISTINCT(id) FROM default;",
+        &[vec![Int(10)]],
+    );
+    test_query_ec(
+        "SELECT country, APPROX_COUNT_DISTINCT(negative, 14) FROM default;",
+        &[
+            vec![Null, Int(4)],
+            vec![Str("France"), Int(2)],
+            vec![Str("Germany"), Int(2)],
+            vec![Str("Turkey"), Int(1)],
+            vec![Str("USA"), Int(1)],
         ],
     );
     test_query_ec_err(
-        "SELECT sum(largenum) FROM default;",
-        QueryError::Overflow,
+        "SELECT APPROX_COUNT_DISTINCT(id, 20) FROM default;",
+        QueryError::ParseError("".to_string()),
     );
 }
 
 #[test]
-fn test_gen_table() {
-    use Value::*;
-    let _ = env_logger::try_init();
+fn test_range_filter_skips_partitions() {
+    test_query_ec(
+        "SELECT id FROM default WHERE id > 7 ORDER BY id;",
+        &[vec![Int(8)], vec![Int(9)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE 4000 < negative ORDER BY id;",
+        &[vec![Int(4)], vec![Int(8)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE u8_offset_encoded <= 257 AND id >= 0 ORDER BY id;",
+        &[vec![Int(0)], vec![Int(3)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE negative = 5000;",
+        &[],
+    );
+}
+
+#[test]
+fn test_sample() {
+    test_query_ec(
+        "SELECT id FROM default SAMPLE 100 PERCENT WHERE id < 3 ORDER BY id;",
+        &[vec![Int(0)], vec![Int(1)], vec![Int(2)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default SAMPLE 0 ROWS;",
+        &[],
+    );
+
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases(&locustdb, 3);
+    let query = "SELECT id FROM default SAMPLE 5 ROWS SEED 42 ORDER BY id LIMIT 10;";
+    let first = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    let second = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(first, second);
+
+    // The sample only depends on the seed and the row numbers, not on how the table is partitioned
+    let repartitioned = LocustDB::new(&Options::default());
+    load_edge_cases(&repartitioned, 4);
+    assert_eq!(block_on(repartitioned.run_query(query, false, vec![])).unwrap().0.unwrap().rows, first);
+
+    // Each row is sampled independently, the sample size is only correct in expectation
     let locustdb = LocustDB::memory_only();
-    let _ = block_on(locustdb.gen_table(
-        locustdb::colgen::GenTable {
-            name: "test".to_string(),
-            partitions: 8,
-            partition_size: 2 << 14,
-            columns: vec![
-                ("yum".to_string(), locustdb::colgen::string_markov_chain(
-                    vec!["Walnut".to_string(), "Cashew".to_string(), "Hazelnut".to_string()],
-                    vec![
-                        vec![0., 0.5, 0.5],
-                        vec![0.1, 0.5, 0.4],
-                        vec![0.1, 0.9, 0.],
-                    ],
-                ))
-            ],
-        }
-    ));
-    let query = "SELECT yum, count(1) FROM test;";
-    let expected_rows = vec![
-        [Str("Cashew".to_string()), Int(162035)],
-        [Str("Hazelnut".to_string()), Int(76401)],
-        [Str("Walnut".to_string()), Int(23708)]
-    ];
-    let result = block_on(locustdb.run_query(query, true, vec![])).unwrap();
-    assert_eq!(result.0.unwrap().rows, expected_rows);
+    for _ in 0..4 {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("value", InputColumn::Int((0..2500).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let count = |query: &str| match block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows[0][0] {
+        Int(count) => count,
+        ref value => panic!("{:?}", value),
+    };
+    let sampled = count("SELECT COUNT(0) FROM default SAMPLE 10 PERCENT SEED 1;");
+    assert!(sampled > 900 && sampled < 1100, "{}", sampled);
+    let sampled = count("SELECT COUNT(0) FROM default SAMPLE 500 ROWS SEED 1;");
+    assert!(sampled > 400 && sampled < 600, "{}", sampled);
+    assert_eq!(count("SELECT COUNT(0) FROM default SAMPLE 20000 ROWS;"), 10_000);
 }
 
 #[test]
-fn test_column_with_null_partitions() {
+fn test_column_aliases() {
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases(&locustdb, 3);
+    let output = block_on(locustdb.run_query("SELECT enum AS e, SUM(id) AS total FROM default WHERE enum = 'cc';", false, vec![]))
+        .unwrap().0.unwrap();
+    assert_eq!(output.colnames, vec!["e".to_string(), "total".to_string()]);
+    assert_eq!(output.rows, vec![vec![Str("cc"), Int(14)]]);
+
+    let output = block_on(locustdb.run_query("SELECT country, COUNT(0) AS n FROM default ORDER BY n DESC LIMIT 1;", false, vec![]))
+        .unwrap().0.unwrap();
+    assert_eq!(output.colnames, vec!["country".to_string(), "n".to_string()]);
+    assert_eq!(output.rows, vec![vec![Null, Int(4)]]);
+}
+
+#[test]
+fn test_explain() {
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases(&locustdb, 3);
+    let output = block_on(locustdb.run_query("EXPLAIN SELECT country, SUM(id) FROM default WHERE id > 3;", false, vec![]))
+        .unwrap().0.unwrap();
+    assert_eq!(output.colnames, vec!["plan".to_string()]);
+    assert_eq!(output.rows.len(), 1);
+    match output.rows[0][0] {
+        Value::Str(ref plan) => {
+            assert!(plan.contains("ColumnSection"), "{}", plan);
+            assert!(plan.contains("CheckedAggregate"), "{}", plan);
+        }
+        ref value => panic!("Expected string, got {:?}", value),
+    }
+}
+
+#[test]
+fn test_explain_many_columns_filter_once() {
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases(&locustdb, 3);
+    let filter_count = |query: &str| {
+        let output = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+        match output.rows[0][0] {
+            Value::Str(ref plan) => plan.matches("Filter {").count(),
+            ref value => panic!("Expected string, got {:?}", value),
+        }
+    };
+    let single = filter_count("EXPLAIN SELECT id FROM default WHERE id > 3;");
+    let many = filter_count("EXPLAIN SELECT id, id + 1, id * 2, id - 3, id / 2, id + id FROM default WHERE id > 3;");
+    assert!(single > 0);
+    assert_eq!(single, many);
+    let single = filter_count("EXPLAIN SELECT id FROM default WHERE id > 3 ORDER BY id;");
+    let many = filter_count("EXPLAIN SELECT id, id + 1, id * 2, id - 3, id / 2, id + id FROM default WHERE id > 3 ORDER BY id;");
+    assert_eq!(single, many);
+}
+
+#[test]
+fn test_explain_analyze() {
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases(&locustdb, 3);
+    let output = block_on(locustdb.run_query("EXPLAIN ANALYZE SELECT country, SUM(id) FROM default WHERE id > 3;", false, vec![]))
+        .unwrap().0.unwrap();
+    assert_eq!(output.colnames, vec!["plan".to_string()]);
+    assert!(!output.rows.is_empty());
+    assert_eq!(output.rows.len(), output.stats.stages.len());
+    for row in &output.rows {
+        match row[0] {
+            Value::Str(ref stage) => assert!(stage.contains("Stage") && stage.ends_with("ms]"), "{}", stage),
+            ref value => panic!("Expected string, got {:?}", value),
+        }
+    }
+}
+
+#[test]
+fn test_common_subexpression_computed_once() {
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases_unpartitioned(&locustdb);
+    let query = "EXPLAIN ANALYZE SELECT id + non_dense_ints, (id + non_dense_ints) * 2 FROM default;";
+    let output = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    let count_ops = |symbol: &str| output.stats.stages.iter()
+        .map(|(stage, _)| stage.matches(symbol).count())
+        .sum::<usize>();
+    assert_eq!(count_ops(" + "), 1);
+    assert_eq!(count_ops(" * "), 1);
+}
+
+#[test]
+fn test_filter_only_column_not_decoded() {
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases_unpartitioned(&locustdb);
+    let query = "SELECT enum FROM default WHERE u8_offset_encoded > 257;";
+    let output = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    assert!(!output.rows.is_empty());
+    assert_eq!(output.stats.decoded_columns, vec!["enum".to_string()]);
+}
+
+#[test]
+fn test_string_range_filter() {
+    test_query_ec(
+        "SELECT id FROM default WHERE country > 'M' ORDER BY id;",
+        &[vec![Int(1)], vec![Int(6)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE country <= 'Germany' ORDER BY id;",
+        &[vec![Int(0)], vec![Int(2)], vec![Int(4)], vec![Int(9)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE 'H' > country ORDER BY id;",
+        &[vec![Int(0)], vec![Int(2)], vec![Int(4)], vec![Int(9)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE country >= 'Turkey' AND country < 'V' ORDER BY id;",
+        &[vec![Int(1)], vec![Int(6)]],
+    );
+}
+
+#[test]
+fn test_sum_encoded_values() {
+    test_query_ec(
+        "SELECT SUM(u8_offset_encoded), SUM(u8_offset_encoded - 256), SUM(id), SUM(id + 0) FROM default;",
+        &[vec![Int(3591), Int(1031), Int(45), Int(45)]],
+    );
+    test_query_ec(
+        "SELECT enum, SUM(u8_offset_encoded), SUM(u8_offset_encoded - 256) FROM default ORDER BY enum;",
+        &[
+            vec![Str("aa"), Int(1705), Int(425)],
+            vec![Str("bb"), Int(1032), Int(264)],
+            vec![Str("cc"), Int(854), Int(342)],
+        ],
+    );
+}
+
+#[test]
+fn test_sum_offset_encoded() {
+    // Multiplying by 1 forces values to be decoded before they are summed
+    test_query_ec(
+        "SELECT SUM(u8_offset_encoded), SUM(u8_offset_encoded * 1), SUM(negative), SUM(negative * 1) FROM default;",
+        &[vec![Int(3591), Int(3591), Int(7557), Int(7557)]],
+    );
+    test_query_ec(
+        "SELECT enum, SUM(u8_offset_encoded), SUM(u8_offset_encoded * 1), SUM(negative), SUM(negative * 1) FROM default ORDER BY enum;",
+        &[
+            vec![Str("aa"), Int(1705), Int(1705), Int(-348), Int(-348)],
+            vec![Str("bb"), Int(1032), Int(1032), Int(4025), Int(4025)],
+            vec![Str("cc"), Int(854), Int(854), Int(3880), Int(3880)],
+        ],
+    );
+}
+
+#[test]
+fn test_sum_negative_offset_encoded() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("value", InputColumn::Int((-1000..1001).collect()));
+    transaction.add_column("positive", InputColumn::Int((-1000..1001).map(|i| (i >= 0) as i64).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let schema = locustdb.schema("default").unwrap();
+    assert!(schema.columns.contains(&("value".to_string(), BasicType::Integer, EncodingType::U16)));
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    let output = query("SELECT SUM(value), SUM(value * 1) FROM default;");
+    assert_eq!(output.rows, vec![vec![Int(0), Int(0)]]);
+    let output = query("SELECT SUM(value) FROM default WHERE positive = 1;");
+    assert_eq!(output.rows, vec![vec![Int(500500)]]);
+    // Sums offset encoded values without decoding them
+    assert!(!output.stats.decoded_columns.contains(&"value".to_string()));
+    assert_eq!(query("SELECT positive, SUM(value), MIN(value), MAX(value) FROM default ORDER BY positive;").rows,
+               vec![vec![Int(0), Int(-500500), Int(-1000), Int(-1)],
+                    vec![Int(1), Int(500500), Int(0), Int(1000)]]);
+}
+
+#[test]
+fn test_compare_offset_encoded_columns() {
+    let locustdb = LocustDB::memory_only();
+    let a = (0..50).map(|i| 1000 + i * 7 % 50).collect::<Vec<i64>>();
+    let b = (0..50).map(|i| 980 + i * 3 % 50 * 2).collect::<Vec<i64>>();
+    let c = (0..50).map(|i| 1000 + i * 11 % 50).collect::<Vec<i64>>();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("a", InputColumn::Int(a.clone()));
+    transaction.add_column("b", InputColumn::Int(b.clone()));
+    transaction.add_column("c", InputColumn::Int(c.clone()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let schema = locustdb.schema("default").unwrap();
+    for column in &["a", "b", "c"] {
+        assert!(schema.columns.contains(&(column.to_string(), BasicType::Integer, EncodingType::U8)));
+    }
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    let count = |lhs: &[i64], rhs: &[i64]| lhs.iter().zip(rhs).filter(|&(l, r)| l > r).count() as i64;
+    // Offsets of `a` and `b` differ, so both have to be decoded
+    let output = query("SELECT COUNT(0) FROM default WHERE a > b;");
+    assert_eq!(output.rows, vec![vec![Int(count(&a, &b))]]);
+    assert!(output.stats.decoded_columns.contains(&"a".to_string()));
+    let output = query("SELECT COUNT(0) FROM default WHERE b < a;");
+    assert_eq!(output.rows, vec![vec![Int(count(&a, &b))]]);
+    // `a` and `c` share their offset and are compared without decoding
+    let output = query("SELECT COUNT(0) FROM default WHERE a > c;");
+    assert_eq!(output.rows, vec![vec![Int(count(&a, &c))]]);
+    assert!(!output.stats.decoded_columns.contains(&"a".to_string()));
+    assert!(!output.stats.decoded_columns.contains(&"c".to_string()));
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE a = c;").rows,
+               vec![vec![Int(a.iter().zip(&c).filter(|&(l, r)| l == r).count() as i64)]]);
+}
+
+#[test]
+fn test_encode_constant_overflow() {
+    // `negative` is stored with a negative offset, so the constant can't be shifted into the encoded range
+    test_query_ec_err(
+        "SELECT negative FROM default WHERE negative < 9223372036854775807;",
+        QueryError::Overflow,
+    );
+}
+
+#[test]
+fn test_aggregates_share_column_scan() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("g", InputColumn::Int((0..1000).map(|i| i % 4).collect()));
+    transaction.add_column("x", InputColumn::Int((0..1000).map(|i| 1000 + i * 7 % 200).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+
+    let single = query("SELECT g, SUM(x) FROM default ORDER BY g;");
+    let multiple = query("SELECT g, SUM(x), MIN(x), MAX(x), APPROX_COUNT_DISTINCT(x) FROM default ORDER BY g;");
+    assert_eq!(multiple.rows[0][..4], [Int(0), Int(274500), Int(1000), Int(1196)]);
+    assert_eq!(multiple.rows.iter().map(|row| row[1].clone()).collect::<Vec<_>>(),
+               single.rows.iter().map(|row| row[1].clone()).collect::<Vec<_>>());
+    // Reads `g` and `x` once, regardless of the number of aggregates over `x`
+    assert_eq!(multiple.stats.column_sections_read, 2);
+    assert_eq!(multiple.stats.column_sections_read, single.stats.column_sections_read);
+}
+
+#[test]
+fn test_count_without_grouping() {
+    let locustdb = LocustDB::memory_only();
+    for _ in 0..3 {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("x", InputColumn::Int((0..1000).map(|i| i % 10).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+
+    // Counts rows from the batch lengths without reading any columns
+    let output = query("SELECT COUNT(*) FROM default;");
+    assert_eq!(output.rows, vec![vec![Int(3000)]]);
+    assert_eq!(output.stats.column_sections_read, 0);
+    assert_eq!(output.stats.chunks_processed, 0);
+    assert!(output.stats.decoded_columns.is_empty());
+    assert_eq!(query("SELECT COUNT(1) FROM default;").rows, vec![vec![Int(3000)]]);
+
+    // Counts the rows selected by the filter without aggregating
+    let output = query("SELECT COUNT(*) FROM default WHERE x < 3;");
+    assert_eq!(output.rows, vec![vec![Int(900)]]);
+    assert!(output.stats.column_sections_read > 0);
+    match query("EXPLAIN SELECT COUNT(*) FROM default WHERE x < 3;").rows[0][0] {
+        Value::Str(ref plan) => {
+            assert!(plan.contains("CountSelected {"), "{}", plan);
+            assert!(!plan.contains("Aggregate {"), "{}", plan);
+        }
+        ref value => panic!("Expected string, got {:?}", value),
+    }
+    assert_eq!(query("SELECT COUNT(*) FROM default WHERE x > 100;").rows, Vec::<Vec<Value>>::new());
+    assert_eq!(query("SELECT COUNT(x) FROM default WHERE x < 3;").rows, vec![vec![Int(900)]]);
+}
+
+#[test]
+fn test_short_circuit_filter() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("x", InputColumn::Int((0..1000).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    let short_circuits = |query: &str| match block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows[0][0] {
+        Value::Str(ref plan) => plan.contains("ShortCircuit {"),
+        ref value => panic!("Expected string, got {:?}", value),
+    };
+
+    // `x % 7 = 3` has no selectivity estimate and is only evaluated for the rows with `x < 50`
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE x < 50 AND x % 7 = 3;"), vec![vec![Int(7)]]);
+    assert!(short_circuits("EXPLAIN SELECT COUNT(0) FROM default WHERE x < 50 AND x % 7 = 3;"));
+    assert_eq!(query("SELECT x FROM default WHERE x % 7 = 3 AND x < 20 ORDER BY x;"),
+               vec![vec![Int(3)], vec![Int(10)], vec![Int(17)]]);
+    // `x % 7 = 3` is only evaluated for the rows with `x <= 50`
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE x > 50 OR x % 7 = 3;"), vec![vec![Int(956)]]);
+    assert!(short_circuits("EXPLAIN SELECT COUNT(0) FROM default WHERE x > 50 OR x % 7 = 3;"));
+    // Operands are evaluated for all rows if the first one determines the result only for few rows
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE x > 50 AND x % 7 = 3;"), vec![vec![Int(136)]]);
+    assert!(!short_circuits("EXPLAIN SELECT COUNT(0) FROM default WHERE x > 50 AND x % 7 = 3;"));
+    assert!(!short_circuits("EXPLAIN SELECT COUNT(0) FROM default WHERE x < 50 OR x % 7 = 3;"));
+}
+
+#[test]
+fn test_short_circuit_filter_nullable() {
+    test_query_ec(
+        "SELECT id FROM default WHERE id < 2 AND nullable_int < 0 ORDER BY id;",
+        &[vec![Int(0)], vec![Int(1)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE id < 2 AND nullable_int2 = -40;",
+        &[vec![Int(1)]],
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE id >= 1 OR nullable_int2 < 0 ORDER BY id;",
+        &(1..10).map(|i| vec![Int(i)]).collect::<Vec<_>>(),
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE id < 1 OR nullable_int > 10 OR country = 'France' ORDER BY id;",
+        &[vec![Int(0)], vec![Int(2)], vec![Int(4)], vec![Int(7)], vec![Int(9)]],
+    );
+}
+
+#[test]
+fn test_as_of() {
+    let locustdb = LocustDB::memory_only();
+    for &(value, seconds) in &[(1, 1000), (10, 2000), (100, 3000)] {
+        let data = vec![("value".to_string(), InputColumn::Int(vec![value; 3]))];
+        let ts = UNIX_EPOCH + Duration::from_secs(seconds);
+        block_on(locustdb.ingest_with_timestamp("default", data, ts)).unwrap().unwrap();
+    }
+    let sum = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(sum("SELECT SUM(value) FROM default;"), vec![vec![Int(333)]]);
+    assert_eq!(sum("SELECT SUM(value) FROM default FOR SYSTEM_TIME AS OF 2000;"), vec![vec![Int(33)]]);
+    assert_eq!(sum("SELECT SUM(value) FROM default FOR SYSTEM TIME AS OF 2999 WHERE value > 1;"), vec![vec![Int(30)]]);
+    assert_eq!(sum("SELECT COUNT(0) FROM default FOR SYSTEM_TIME AS OF 999;"), Vec::<Vec<Value>>::new());
+
+    // Compaction only merges partitions ingested in the same second
+    let data = vec![("value".to_string(), InputColumn::Int(vec![1000; 3]))];
+    let ts = UNIX_EPOCH + Duration::new(2000, 500);
+    block_on(locustdb.ingest_with_timestamp("default", data, ts)).unwrap().unwrap();
+    assert_eq!(block_on(locustdb.compact(10)).unwrap().unwrap(), 0);
+    let data = vec![("value".to_string(), InputColumn::Int(vec![10000; 3]))];
+    let ts = UNIX_EPOCH + Duration::from_secs(2001);
+    block_on(locustdb.ingest_with_timestamp("default", data, ts)).unwrap().unwrap();
+    assert_eq!(block_on(locustdb.compact(10)).unwrap().unwrap(), 2);
+    assert_eq!(sum("SELECT SUM(value) FROM default FOR SYSTEM_TIME AS OF 2000;"), vec![vec![Int(33)]]);
+    assert_eq!(sum("SELECT SUM(value) FROM default FOR SYSTEM_TIME AS OF 2001;"), vec![vec![Int(33033)]]);
+}
+
+#[cfg(feature = "enable_mmap")]
+#[test]
+fn test_as_of_persisted() {
+    use tempdir::TempDir;
+    let tmp_dir = TempDir::new("mmap").unwrap();
+    let mut opts = Options::default();
+    opts.db_path = Some(tmp_dir.path().to_str().unwrap().to_string());
+    opts.mmap_storage = true;
+    {
+        let locustdb = LocustDB::new(&opts);
+        for &(value, seconds) in &[(1, 1000), (10, 2000)] {
+            let data = vec![("value".to_string(), InputColumn::Int(vec![value; 3]))];
+            let ts = UNIX_EPOCH + Duration::from_secs(seconds);
+            block_on(locustdb.ingest_with_timestamp("default", data, ts)).unwrap().unwrap();
+        }
+    }
+    // Ingest timestamps are restored with the partitions
+    let locustdb = LocustDB::new(&opts);
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT SUM(value) FROM default FOR SYSTEM_TIME AS OF 1500;"), vec![vec![Int(3)]]);
+    assert_eq!(query("SELECT SUM(value) FROM default;"), vec![vec![Int(33)]]);
+}
+
+#[test]
+fn test_rownum() {
+    let locustdb = LocustDB::memory_only();
+    let mut start = 0;
+    for &len in &[5, 7, 4] {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("value", InputColumn::Int((start..start + len).map(|i| i * 10).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+        start += len;
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    // Row numbers continue across partitions
+    assert_eq!(query("SELECT COUNT(0), MAX(rownum) FROM default WHERE value = rownum * 10;"),
+               vec![vec![Int(16), Int(15)]]);
+    assert_eq!(query("SELECT value FROM default WHERE rownum % 3 = 0 ORDER BY value;"),
+               vec![vec![Int(0)], vec![Int(30)], vec![Int(60)], vec![Int(90)], vec![Int(120)], vec![Int(150)]]);
+    assert_eq!(query("SELECT rownum FROM default WHERE value > 100 AND rownum % 4 = 2 ORDER BY rownum;"),
+               vec![vec![Int(14)]]);
+    assert_eq!(query("SELECT value FROM default WHERE rownum % 5 = 7;"), Vec::<Vec<Value>>::new());
+
+    assert_eq!(query("DELETE FROM default WHERE rownum % 2 = 1;"), vec![vec![Int(8)]]);
+    assert_eq!(query("SELECT SUM(value) FROM default;"), vec![vec![Int(560)]]);
+}
+
+#[test]
+fn test_reservoir_sample() {
+    let locustdb = LocustDB::memory_only();
+    for partition in 0..3 {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("value", InputColumn::Int((partition * 1000..(partition + 1) * 1000).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+
+    let sample = query("SELECT value FROM default SAMPLE 50 SEED 3 WHERE value % 2 = 0;").unwrap().rows;
+    assert_eq!(sample.len(), 50);
+    assert!(sample.iter().all(|row| match row[0] { Int(value) => value % 2 == 0, _ => false }));
+    let mut distinct = sample.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), 50);
+    // Same seed, same sample
+    assert_eq!(query("SELECT value FROM default SAMPLE 50 SEED 3 WHERE value % 2 = 0;").unwrap().rows, sample);
+    assert_ne!(query("SELECT value FROM default SAMPLE 50 SEED 4 WHERE value % 2 = 0;").unwrap().rows, sample);
+
+    // Samples larger than the number of matching rows contain every matching row
+    assert_eq!(query("SELECT value FROM default SAMPLE 5000 WHERE value < 1200;").unwrap().rows.len(), 1200);
+    assert!(query("SELECT value FROM default SAMPLE 10 ORDER BY value;").is_err());
+}
+
+#[test]
+fn test_group_by_having_offset() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("k", InputColumn::Int((0..30).map(|i| i % 3).collect()));
+    transaction.add_column("v", InputColumn::Int((0..30).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    let rows = |q: &str| query(q).unwrap().rows;
+
+    assert_eq!(rows("SELECT k, SUM(v) FROM default GROUP BY k ORDER BY k;"),
+               vec![vec![Int(0), Int(135)], vec![Int(1), Int(145)], vec![Int(2), Int(155)]]);
+    assert_eq!(rows("SELECT k, COUNT(0) FROM default GROUP BY k HAVING SUM(v) > 140 ORDER BY k;"),
+               vec![vec![Int(1), Int(10)], vec![Int(2), Int(10)]]);
+    assert_eq!(rows("SELECT k, COUNT(0) FROM default GROUP BY k HAVING k > 0 AND COUNT(0) > 5 ORDER BY k;"),
+               vec![vec![Int(1), Int(10)], vec![Int(2), Int(10)]]);
+    assert_eq!(rows("SELECT k FROM default GROUP BY k ORDER BY k;"), vec![vec![Int(0)], vec![Int(1)], vec![Int(2)]]);
+    // Groups by a column that is not selected
+    assert_eq!(rows("SELECT SUM(v) FROM default GROUP BY k ORDER BY SUM(v) DESC LIMIT 1 OFFSET 1;"), vec![vec![Int(145)]]);
+    assert_eq!(rows("SELECT v FROM default ORDER BY v LIMIT 5 OFFSET 28;"), vec![vec![Int(28)], vec![Int(29)]]);
+    assert!(query("SELECT k, v, COUNT(0) FROM default GROUP BY k;").is_err());
+}
+
+#[test]
+fn test_order_by_aggregate_and_grouping_key() {
+    let locustdb = LocustDB::memory_only();
+    // Each transaction becomes a separate partition
+    for countries in &[["de", "us", "fr", "us"], ["fr", "nl", "de", "fr"]] {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("country", InputColumn::Str(countries.iter().map(|c| c.to_string()).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let rows = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    let row = |country: &str, count: i64| vec![Str(country), Int(count)];
+
+    assert_eq!(rows("SELECT country, COUNT(1) FROM default ORDER BY COUNT(1) DESC, country ASC;"),
+               vec![row("fr", 3), row("de", 2), row("us", 2), row("nl", 1)]);
+    assert_eq!(rows("SELECT country, COUNT(1) FROM default ORDER BY COUNT(1) DESC, country DESC;"),
+               vec![row("fr", 3), row("us", 2), row("de", 2), row("nl", 1)]);
+    assert_eq!(rows("SELECT country, COUNT(1) FROM default ORDER BY COUNT(1), country DESC LIMIT 2 OFFSET 1;"),
+               vec![row("us", 2), row("de", 2)]);
+    // Ties are broken by the grouping keys
+    assert_eq!(rows("SELECT country, COUNT(1) FROM default ORDER BY COUNT(1) DESC LIMIT 2 OFFSET 1;"),
+               vec![row("de", 2), row("us", 2)]);
+}
+
+#[test]
+fn test_sort_grouping() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    // Wide ranges with two rows per value are grouped by sorting rather than with a hash map
+    transaction.add_column("shuffled", InputColumn::Int((0..1000).map(|i| i * 7 % 500 * 100_000).collect()));
+    transaction.add_column("sorted", InputColumn::Int((0..1000).map(|i| i / 2 * 100_000).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+
+    let expected = (0..500).map(|i| vec![Int(i * 100_000), Int(2)]).collect::<Vec<_>>();
+    assert_eq!(query("SELECT shuffled, COUNT(0) FROM default LIMIT 1000;"), expected);
+    assert_eq!(query("SELECT sorted, COUNT(0) FROM default LIMIT 1000;"), expected);
+    // Expressions have no cardinality estimate and are grouped with a hash map
+    assert_eq!(query("SELECT shuffled + 0, COUNT(0) FROM default LIMIT 1000;"), expected);
+}
+
+#[test]
+fn test_sum_overflow_policy() {
+    test_query_ec_err("SELECT SUM(largenum) FROM default;", QueryError::Overflow);
+
+    let sum_largenum = |policy: OverflowPolicy, query: &str| {
+        let mut opts = Options::default();
+        opts.threads = 1;
+        opts.sum_overflow = policy;
+        let locustdb = LocustDB::new(&opts);
+        load_edge_cases(&locustdb, 3);
+        block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows
+    };
+    // The exact result is 2 * i64::MAX
+    assert_eq!(sum_largenum(OverflowPolicy::Wrap, "SELECT SUM(largenum) FROM default;"), vec![vec![Int(-2)]]);
+    assert_eq!(sum_largenum(OverflowPolicy::Saturate, "SELECT SUM(largenum) FROM default WHERE largenum > 0;"),
+               vec![vec![Int(9223372036854775807)]]);
+
+    // Only the exact sum is clamped, rather than the partial sums of each partition
+    let mut opts = Options::default();
+    opts.sum_overflow = OverflowPolicy::Saturate;
+    let locustdb = LocustDB::new(&opts);
+    for values in &[vec![i64::MAX, i64::MAX], vec![-i64::MAX, 5 - i64::MAX], vec![0, 0]] {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("g", InputColumn::Int(vec![0, 1]));
+        transaction.add_column("value", InputColumn::Int(values.clone()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT SUM(value) FROM default;"), vec![vec![Int(5)]]);
+    assert_eq!(query("SELECT g, SUM(value) FROM default;"), vec![vec![Int(0), Int(0)], vec![Int(1), Int(5)]]);
+    assert_eq!(query("SELECT SUM(value - g) FROM default;"), vec![vec![Int(2)]]);
+    assert_eq!(query("SELECT SUM(value) FROM default WHERE value > 0;"), vec![vec![Int(i64::MAX)]]);
+    assert_eq!(query("SELECT SUM(value) FROM default WHERE value < 0;"), vec![vec![Int(-i64::MAX)]]);
+}
+
+#[test]
+fn test_sum_intermediate_overflow() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("g", InputColumn::Int(vec![0, 1, 0, 1, 0, 1]));
+    transaction.add_column("value", InputColumn::Int(vec![i64::MAX, i64::MAX, i64::MAX, i64::MAX, -i64::MAX, -1]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+
+    // Partial sums exceed i64::MAX but the final sum of group 0 fits
+    assert_eq!(query("SELECT SUM(value) FROM default WHERE g = 0;").unwrap().rows, vec![vec![Int(i64::MAX)]]);
+    match query("SELECT SUM(value) FROM default WHERE value > 0 OR g = 0;") {
+        Err(QueryError::Overflow) => {}
+        result => panic!("Expected overflow, got {:?}", result.map(|output| output.rows)),
+    }
+    match query("SELECT g, SUM(value) FROM default;") {
+        Err(QueryError::Overflow) => {}
+        result => panic!("Expected overflow, got {:?}", result.map(|output| output.rows)),
+    }
+
+    // Partial sums of partitions are merged without narrowing them first
+    let locustdb = LocustDB::memory_only();
+    for values in &[vec![i64::MAX, i64::MAX], vec![-i64::MAX, 5 - i64::MAX]] {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("g", InputColumn::Int(vec![0, 1]));
+        transaction.add_column("value", InputColumn::Int(values.clone()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    assert_eq!(query("SELECT SUM(value) FROM default;").unwrap().rows, vec![vec![Int(5)]]);
+    assert_eq!(query("SELECT g, SUM(value) FROM default;").unwrap().rows, vec![vec![Int(0), Int(0)], vec![Int(1), Int(5)]]);
+    assert_eq!(query("SELECT SUM(value - g) FROM default;").unwrap().rows, vec![vec![Int(3)]]);
+    assert_eq!(query("SELECT COUNT(0) FROM default;").unwrap().rows, vec![vec![Int(4)]]);
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE value > 0;").unwrap().rows, vec![vec![Int(2)]]);
+    match query("SELECT SUM(value) FROM default WHERE value > 0;") {
+        Err(QueryError::Overflow) => {}
+        result => panic!("Expected overflow, got {:?}", result.map(|output| output.rows)),
+    }
+}
+
+#[test]
+fn test_query_memory_limit() {
+    let run_with_limit = |limit: usize| {
+        let mut opts = Options::default();
+        opts.threads = 1;
+        opts.query_memory_limit = Some(limit);
+        let locustdb = LocustDB::new(&opts);
+        load_edge_cases(&locustdb, 3);
+        block_on(locustdb.run_query("SELECT id + 1 FROM default;", false, vec![])).unwrap().0
+    };
+    match run_with_limit(1) {
+        Err(QueryError::MemoryLimitExceeded(1)) => {}
+        other => panic!("Expected memory limit to be exceeded, got {:?}", other.map(|output| output.rows)),
+    }
+    let output = run_with_limit(1 << 20).unwrap();
+    assert_eq!(output.rows.len(), 10);
+    assert!(output.stats.peak_memory_bytes > 0);
+}
+
+#[test]
+fn test_query_memory_limit_grouping() {
+    let mut opts = Options::default();
+    opts.threads = 1;
+    opts.query_memory_limit = Some(1 << 20);
+    let locustdb = LocustDB::new(&opts);
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("key", InputColumn::Int((0..100_000).map(|i| i * 1_000_003).collect()));
+    transaction.add_column("value", InputColumn::Int((0..100_000).map(|i| i % 7).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+
+    assert_eq!(query("SELECT SUM(value) FROM default;").unwrap().rows, vec![vec![Int(299_995)]]);
+    // The groups of 100,000 distinct keys don't fit into 1MiB
+    match query("SELECT key, COUNT(0) FROM default;") {
+        Err(QueryError::MemoryLimitExceeded(limit)) => assert_eq!(limit, 1 << 20),
+        other => panic!("Expected memory limit to be exceeded, got {:?}", other.map(|output| output.rows.len())),
+    }
+}
+
+#[test]
+fn test_query_timeout() {
+    let run_with_timeout = |timeout: Duration| {
+        let mut opts = Options::default();
+        opts.threads = 1;
+        opts.query_timeout = Some(timeout);
+        let locustdb = LocustDB::new(&opts);
+        load_edge_cases(&locustdb, 3);
+        block_on(locustdb.run_query("SELECT COUNT(0) FROM default;", false, vec![])).unwrap().0.unwrap()
+    };
+    // Partitions are only skipped after the first one has been processed
+    let output = run_with_timeout(Duration::from_secs(0));
+    assert!(output.truncated);
+    assert_eq!(output.rows, vec![vec![Int(3)]]);
+    let output = run_with_timeout(Duration::from_secs(3600));
+    assert!(!output.truncated);
+    assert_eq!(output.rows, vec![vec![Int(10)]]);
+}
+
+#[test]
+fn test_query_batch_size() {
+    let run_with_batch_size = |batch_size: Option<usize>, query: &str| {
+        let mut opts = Options::default();
+        opts.threads = 1;
+        opts.query_batch_size = batch_size;
+        let locustdb = LocustDB::new(&opts);
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("x", InputColumn::Int((0..10_000).map(|i| i * 7 % 1000).collect()));
+        transaction.add_column("s", InputColumn::Str((0..10_000).map(|i| format!("s{}", i % 13)).collect()));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+        block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap()
+    };
+    let (mut default_chunks, mut small_batch_chunks) = (0, 0);
+    for query in &[
+        "SELECT x % 10, COUNT(0), SUM(x) FROM default WHERE x > 100;",
+        "SELECT x, s FROM default WHERE x < 50 ORDER BY x, s LIMIT 20;",
+        "SELECT s, MAX(x) FROM default;",
+        "SELECT x, s FROM default WHERE x < 100 AND s = 's3' ORDER BY x, s;",
+        "SELECT COUNT(0) FROM default WHERE x > 50 OR s = 's3';",
+    ] {
+        let default = run_with_batch_size(None, query);
+        for &batch_size in &[8, 100, 1 << 16] {
+            let output = run_with_batch_size(Some(batch_size), query);
+            assert_eq!(output.rows, default.rows, "{} with batch size {}", query, batch_size);
+        }
+        // Queries without streaming stages process the same number of chunks regardless of batch size
+        let chunks = run_with_batch_size(Some(8), query).stats.chunks_processed;
+        assert!(chunks >= default.stats.chunks_processed, "{}", query);
+        default_chunks += default.stats.chunks_processed;
+        small_batch_chunks += chunks;
+    }
+    assert!(small_batch_chunks > default_chunks);
+}
+
+#[test]
+fn test_select_boolean() {
+    test_query_ec(
+        "SELECT id, id > 6, enum = 'aa' FROM default WHERE id < 3 OR id > 7 ORDER BY id;",
+        &[vec![Int(0), Int(0), Int(1)],
+            vec![Int(1), Int(0), Int(1)],
+            vec![Int(2), Int(0), Int(1)],
+            vec![Int(8), Int(1), Int(0)],
+            vec![Int(9), Int(1), Int(0)]],
+    );
+    test_query_ec(
+        "SELECT id, nullable_int > 0 FROM default WHERE id < 5 ORDER BY id;",
+        &[vec![Int(0), Int(0)],
+            vec![Int(1), Int(0)],
+            vec![Int(2), Null],
+            vec![Int(3), Null],
+            vec![Int(4), Int(1)]],
+    );
+
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("requests");
+    transaction.add_column("latency", InputColumn::Int(vec![10, 250, 99, 101]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let output = block_on(locustdb.run_query("SELECT latency, latency > 100 AS is_slow FROM requests ORDER BY latency;", false, vec![]))
+        .unwrap().0.unwrap();
+    assert_eq!(output.colnames, vec!["latency".to_string(), "is_slow".to_string()]);
+    assert_eq!(output.coltypes, vec![BasicType::Integer, BasicType::Boolean]);
+    assert_eq!(output.rows, vec![vec![Int(10), Int(0)], vec![Int(99), Int(0)], vec![Int(101), Int(1)], vec![Int(250), Int(1)]]);
+}
+
+#[test]
+fn test_schema() {
+    let locustdb = LocustDB::memory_only();
+    load_edge_cases_unpartitioned(&locustdb);
+    let schema = locustdb.schema("default").unwrap();
+    assert_eq!(schema.name, "default");
+    assert_eq!(schema.columns.len(), 12);
+    let column_type = |name: &str| schema.columns.iter()
+        .find(|column| column.0 == name)
+        .map(|column| column.1);
+    assert_eq!(column_type("id"), Some(BasicType::Integer));
+    assert_eq!(column_type("enum"), Some(BasicType::String));
+    assert_eq!(column_type("missing"), None);
+    assert!(locustdb.schema("nonexistent").is_none());
+}
+
+#[cfg(feature = "enable_mmap")]
+#[test]
+fn test_introspection_after_restart() {
+    use tempdir::TempDir;
+    let tmp_dir = TempDir::new("mmap").unwrap();
+    let mut opts = Options::default();
+    opts.db_path = Some(tmp_dir.path().to_str().unwrap().to_string());
+    opts.mmap_storage = true;
+    load_edge_cases(&LocustDB::new(&opts), 3);
+    // No column is in memory after the restart
+    let locustdb = LocustDB::new(&opts);
+    let schema = locustdb.schema("default").unwrap();
+    assert_eq!(schema.columns.len(), 12);
+    let column_type = |name: &str| schema.columns.iter()
+        .find(|column| column.0 == name)
+        .map(|column| column.1);
+    assert_eq!(column_type("id"), Some(BasicType::Integer));
+    assert_eq!(column_type("nullable_int"), Some(BasicType::NullableInteger));
+
+    let metadata = locustdb.column_metadata("default").unwrap();
+    assert_eq!(metadata.len(), 12);
+    let nullable_int = metadata.iter().find(|column| column.name == "nullable_int").unwrap();
+    assert_eq!((nullable_int.rows, nullable_int.null_count, nullable_int.range), (10, Some(5), Some((-40, 20))));
+
+    let description = block_on(locustdb.describe("default")).unwrap().unwrap();
+    assert_eq!(description.len(), 12);
+    let id = description.iter().find(|column| column.name == "id").unwrap();
+    assert_eq!((id.rows, id.min.clone(), id.max.clone(), id.mean), (10, Int(0), Int(9), Some(4.5)));
+}
+
+#[test]
+fn test_column_metadata() {
+    let locustdb = LocustDB::memory_only();
+    load_edge_cases(&locustdb, 3);
+    let metadata = locustdb.column_metadata("default").unwrap();
+    assert_eq!(metadata.len(), 12);
+    let column = |name: &str| metadata.iter().find(|column| column.name == name).unwrap().clone();
+
+    let id = column("id");
+    assert_eq!((id.basic_type, id.rows, id.null_count, id.range), (BasicType::Integer, 10, Some(0), Some((0, 9))));
+    assert!(id.approx_cardinality.value() <= 10);
+
+    let nullable_int = column("nullable_int");
+    assert_eq!(nullable_int.basic_type, BasicType::NullableInteger);
+    assert_eq!((nullable_int.null_count, nullable_int.range), (Some(5), Some((-40, 20))));
+
+    let enum_column = column("enum");
+    assert_eq!((enum_column.basic_type, enum_column.range), (BasicType::String, None));
+    let distinct = enum_column.approx_cardinality.value();
+    assert!(distinct >= 3 && distinct <= 10);
+
+    assert!(locustdb.column_metadata("nonexistent").is_none());
+}
+
+#[test]
+fn test_describe() {
+    let locustdb = LocustDB::memory_only();
+    load_edge_cases(&locustdb, 3);
+    let description = block_on(locustdb.describe("default")).unwrap().unwrap();
+    assert_eq!(description.len(), 12);
+    let column = |name: &str| description.iter().find(|column| column.name == name).unwrap().clone();
+
+    let id = column("id");
+    assert_eq!((id.rows, id.null_count), (10, 0));
+    assert_eq!((id.min, id.max), (Int(0), Int(9)));
+    assert_eq!(id.mean, Some(4.5));
+    assert_eq!(id.approx_distinct, 10);
+
+    let nullable_int = column("nullable_int");
+    assert_eq!((nullable_int.rows, nullable_int.null_count), (10, 5));
+    assert_eq!((nullable_int.min, nullable_int.max), (Int(-40), Int(20)));
+    assert_eq!(nullable_int.mean, Some(0.4));
+
+    let enum_column = column("enum");
+    assert_eq!((enum_column.min, enum_column.max), (Str("aa"), Str("cc")));
+    assert_eq!(enum_column.mean, None);
+    assert_eq!(enum_column.approx_distinct, 3);
+
+    assert!(block_on(locustdb.describe("nonexistent")).unwrap().is_err());
+}
+
+#[test]
+fn test_update() {
+    let locustdb = LocustDB::memory_only();
+    load_edge_cases(&locustdb, 3);
+    let update = |filter: &str, column: &str, value: &str| block_on(locustdb.update("default", filter, column, value)).unwrap();
+    assert_eq!(update("id > 7", "nullable_int", "100").unwrap(), 2);
+    assert_eq!(update("id = 1", "nullable_int", "5").unwrap(), 1);
+    assert_eq!(update("enum = 'cc'", "enum", "'dd'").unwrap(), 2);
+    assert_eq!(update("id > 100", "enum", "'ee'").unwrap(), 0);
+    assert!(update("id > 7", "nullable_int", "id + 1").is_err());
+    match update("id > 7", "missing", "1") {
+        Err(QueryError::UnknownColumn(ref column, _)) if column == "missing" => {}
+        result => panic!("Expected UnknownColumn, got {:?}", result),
+    }
+
+    let query = "SELECT id, nullable_int, enum FROM default WHERE id <= 2 OR id >= 6 ORDER BY id;";
+    let result = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    assert_eq!(result.rows, vec![
+        vec![Int(0), Int(-1), Str("aa")],
+        vec![Int(1), Int(5), Str("aa")],
+        vec![Int(2), Null, Str("aa")],
+        vec![Int(6), Null, Str("dd")],
+        vec![Int(7), Int(20), Str("aa")],
+        vec![Int(8), Int(100), Str("dd")],
+        vec![Int(9), Int(100), Str("bb")],
+    ]);
+}
+
+#[test]
+fn test_update_fails_atomically() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("a", InputColumn::Int(vec![1, 2]));
+    transaction.add_column("b", InputColumn::Int(vec![10, 20]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("a", InputColumn::Int(vec![3]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    // The second partition doesn't store `b`, so the first partition isn't updated either
+    assert!(block_on(locustdb.update("default", "a > 0", "b", "5")).unwrap().is_err());
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT SUM(b) FROM default;"), vec![vec![Int(30)]]);
+    assert_eq!(block_on(locustdb.update("default", "a < 3", "b", "5")).unwrap().unwrap(), 2);
+    assert_eq!(query("SELECT SUM(b) FROM default;"), vec![vec![Int(10)]]);
+}
+
+#[cfg(feature = "enable_mmap")]
+#[test]
+fn test_update_persisted() {
+    use tempdir::TempDir;
+    let tmp_dir = TempDir::new("mmap").unwrap();
+    let mut opts = Options::default();
+    opts.db_path = Some(tmp_dir.path().to_str().unwrap().to_string());
+    opts.mmap_storage = true;
+    {
+        let locustdb = LocustDB::new(&opts);
+        load_edge_cases(&locustdb, 3);
+        assert_eq!(block_on(locustdb.update("default", "id > 6", "enum", "'dd'")).unwrap().unwrap(), 3);
+    }
+    // Updated columns are restored from disk
+    let locustdb = LocustDB::new(&opts);
+    let query = "SELECT enum, COUNT(0) FROM default ORDER BY enum;";
+    assert_eq!(block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows,
+               vec![vec![Str("aa"), Int(4)], vec![Str("bb"), Int(2)], vec![Str("cc"), Int(1)], vec![Str("dd"), Int(3)]]);
+}
+
+#[test]
+fn test_delete() {
+    let locustdb = LocustDB::memory_only();
+    load_edge_cases(&locustdb, 3);
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("DELETE FROM default WHERE id > 6 OR enum = 'bb';"), vec![vec![Int(5)]]);
+    assert_eq!(query("DELETE FROM default WHERE id = 9;"), vec![vec![Int(0)]]);
+    let expected_ids = vec![vec![Int(0)], vec![Int(1)], vec![Int(2)], vec![Int(5)], vec![Int(6)]];
+    let expected_groups = vec![vec![Str("aa"), Int(4)], vec![Str("cc"), Int(1)]];
+    assert_eq!(query("SELECT id FROM default ORDER BY id;"), expected_ids);
+    assert_eq!(query("SELECT enum, COUNT(0) FROM default;"), expected_groups);
+
+    assert_eq!(query("VACUUM default;"), vec![vec![Int(5)]]);
+    assert_eq!(query("VACUUM default;"), vec![vec![Int(0)]]);
+    assert_eq!(query("SELECT id FROM default ORDER BY id;"), expected_ids);
+    assert_eq!(query("SELECT enum, COUNT(0) FROM default;"), expected_groups);
+    assert_eq!(query("SELECT string_packed FROM default WHERE id = 6;"), vec![vec![Str("asd")]]);
+
+    for statement in &["DELETE FROM missing WHERE id = 1;", "VACUUM missing;"] {
+        match block_on(locustdb.run_query(statement, false, vec![])).unwrap().0 {
+            Err(QueryError::UnknownTable(ref table)) if table == "missing" => {}
+            result => panic!("Expected unknown table, got {:?}", result.map(|output| output.rows)),
+        }
+    }
+}
+
+#[cfg(feature = "enable_mmap")]
+#[test]
+fn test_delete_persisted() {
+    use tempdir::TempDir;
+    let tmp_dir = TempDir::new("mmap").unwrap();
+    let mut opts = Options::default();
+    opts.db_path = Some(tmp_dir.path().to_str().unwrap().to_string());
+    opts.mmap_storage = true;
+    let query = |locustdb: &LocustDB, query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    {
+        let locustdb = LocustDB::new(&opts);
+        load_edge_cases(&locustdb, 3);
+        assert_eq!(query(&locustdb, "DELETE FROM default WHERE id > 6;"), vec![vec![Int(3)]]);
+    }
+    {
+        // Tombstones are restored from disk
+        let locustdb = LocustDB::new(&opts);
+        assert_eq!(query(&locustdb, "SELECT COUNT(0) FROM default;"), vec![vec![Int(7)]]);
+        assert_eq!(query(&locustdb, "DELETE FROM default WHERE id < 3;"), vec![vec![Int(3)]]);
+        assert_eq!(query(&locustdb, "VACUUM default;"), vec![vec![Int(6)]]);
+    }
+    // Vacuumed partitions replace the original ones on disk
+    let locustdb = LocustDB::new(&opts);
+    assert_eq!(query(&locustdb, "SELECT id FROM default ORDER BY id;"),
+               vec![vec![Int(3)], vec![Int(4)], vec![Int(5)], vec![Int(6)]]);
+    assert_eq!(query(&locustdb, "VACUUM default;"), vec![vec![Int(0)]]);
+}
+
+#[test]
+fn test_compaction() {
+    let locustdb = LocustDB::new(&Options::default());
+    load_edge_cases(&locustdb, 3);
+    assert_eq!(block_on(locustdb.compact(5)).unwrap().unwrap(), 4);
+    assert_eq!(block_on(locustdb.compact(5)).unwrap().unwrap(), 0);
+    let stats = block_on(locustdb.table_stats()).unwrap();
+    let table = stats.iter().find(|table| table.name == "default").unwrap();
+    assert_eq!((table.batches, table.rows), (2, 10));
+    let schema = locustdb.schema("default").unwrap();
+    assert!(schema.columns.contains(&("u8_offset_encoded".to_string(), BasicType::Integer, EncodingType::U8)));
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT SUM(u8_offset_encoded), SUM(negative) FROM default;"), vec![vec![Int(3591), Int(7557)]]);
+    assert_eq!(query("SELECT id FROM default WHERE nullable_int IS NULL ORDER BY id;"),
+               vec![vec![Int(2)], vec![Int(3)], vec![Int(5)], vec![Int(6)], vec![Int(8)]]);
+}
+
+#[cfg(feature = "enable_mmap")]
+#[test]
+fn test_compaction_persisted() {
+    use tempdir::TempDir;
+    let tmp_dir = TempDir::new("mmap").unwrap();
+    let mut opts = Options::default();
+    opts.db_path = Some(tmp_dir.path().to_str().unwrap().to_string());
+    opts.mmap_storage = true;
+    let query = |locustdb: &LocustDB, query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    {
+        let locustdb = LocustDB::new(&opts);
+        load_edge_cases(&locustdb, 3);
+        assert_eq!(query(&locustdb, "DELETE FROM default WHERE id = 9;"), vec![vec![Int(1)]]);
+        assert_eq!(block_on(locustdb.compact(5)).unwrap().unwrap(), 4);
+    }
+    // Merged partitions replace the partitions they were created from on disk
+    let locustdb = LocustDB::new(&opts);
+    let stats = block_on(locustdb.table_stats()).unwrap();
+    let table = stats.iter().find(|table| table.name == "default").unwrap();
+    assert_eq!((table.batches, table.rows), (2, 9));
+    assert_eq!(query(&locustdb, "SELECT COUNT(0), SUM(id) FROM default;"), vec![vec![Int(9), Int(36)]]);
+}
+
+#[test]
+fn test_import_from() {
+    let source = LocustDB::memory_only();
+    load_edge_cases(&source, 3);
+    let query = |locustdb: &LocustDB, query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query(&source, "DELETE FROM default WHERE id > 6;"), vec![vec![Int(3)]]);
+
+    let fork = LocustDB::memory_only();
+    assert_eq!(block_on(fork.import_from(&source)).unwrap().unwrap(), 7);
+    assert_eq!(query(&fork, "SELECT COUNT(0), SUM(id) FROM default;"), vec![vec![Int(7), Int(21)]]);
+    assert_eq!(query(&fork, "SELECT string_packed FROM default WHERE id = 6;"), vec![vec![Str("asd")]]);
+
+    // Changes to the fork don't affect the source
+    assert_eq!(query(&fork, "DELETE FROM default WHERE id < 3;"), vec![vec![Int(3)]]);
+    assert_eq!(query(&fork, "SELECT COUNT(0) FROM default;"), vec![vec![Int(4)]]);
+    assert_eq!(query(&source, "SELECT COUNT(0) FROM default;"), vec![vec![Int(7)]]);
+
+    // Renamed columns are renamed in the fork as well
+    source.rename_column("default", "enum", "category").unwrap();
+    let fork = LocustDB::memory_only();
+    block_on(fork.import_from(&source)).unwrap().unwrap();
+    assert_eq!(query(&fork, "SELECT category, COUNT(0) FROM default ORDER BY category;"),
+               vec![vec![Str("aa"), Int(4)], vec![Str("bb"), Int(2)], vec![Str("cc"), Int(1)]]);
+    let other = LocustDB::memory_only();
+    load_edge_cases(&other, 3);
+    other.rename_column("default", "enum", "kind").unwrap();
+    assert!(block_on(fork.import_from(&other)).unwrap().is_err());
+    assert_eq!(query(&fork, "SELECT COUNT(0) FROM default;"), vec![vec![Int(7)]]);
+}
+
+#[test]
+fn test_rename_column() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![1, 2, 3]));
+    transaction.add_column("name", InputColumn::Str(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    assert!(locustdb.rename_column("default", "missing", "other").is_err());
+    assert!(locustdb.rename_column("default", "name", "id").is_err());
+    locustdb.rename_column("default", "name", "label").unwrap();
+    assert!(locustdb.rename_column("default", "name", "other").is_err());
+
+    // Partitions ingested after the rename store the column under its new name
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![4]));
+    transaction.add_column("label", InputColumn::Str(vec!["d".to_string()]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap();
+    let output = query("SELECT id, label FROM default WHERE label <> 'b' ORDER BY id;");
+    assert_eq!(output.colnames, vec!["id".to_string(), "label".to_string()]);
+    assert_eq!(output.rows, vec![vec![Int(1), Str("a")], vec![Int(3), Str("c")], vec![Int(4), Str("d")]]);
+    assert_eq!(query("SELECT label, COUNT(0) FROM default WHERE id < 3;").rows,
+               vec![vec![Str("a"), Int(1)], vec![Str("b"), Int(1)]]);
+    let mut colnames = query("SELECT * FROM default;").colnames;
+    colnames.sort();
+    assert_eq!(colnames, vec!["id".to_string(), "label".to_string()]);
+    // The old name still refers to the stored data
+    assert_eq!(query("SELECT name FROM default WHERE id = 2;").rows, vec![vec![Str("b")]]);
+
+    let schema = locustdb.schema("default").unwrap();
+    assert_eq!(schema.columns.iter().map(|column| column.0.as_str()).collect::<Vec<_>>(), vec!["id", "label"]);
+    let description = block_on(locustdb.describe("default")).unwrap().unwrap();
+    assert_eq!(description.iter().map(|column| (column.name.as_str(), column.rows)).collect::<Vec<_>>(),
+               vec![("id", 4), ("label", 4)]);
+    assert!(locustdb.validate("SELECT label, name FROM default WHERE label = 'a';").is_empty());
+
+    // Updates and deletes refer to the column by its new name
+    assert_eq!(block_on(locustdb.update("default", "label = 'b' OR label = 'd'", "label", "'e'")).unwrap().unwrap(), 2);
+    assert_eq!(query("DELETE FROM default WHERE label = 'a';").rows, vec![vec![Int(1)]]);
+    assert_eq!(query("SELECT id, label FROM default ORDER BY id;").rows,
+               vec![vec![Int(2), Str("e")], vec![Int(3), Str("c")], vec![Int(4), Str("e")]]);
+}
+
+#[cfg(feature = "enable_mmap")]
+#[test]
+fn test_rename_column_persisted() {
+    use tempdir::TempDir;
+    let tmp_dir = TempDir::new("mmap").unwrap();
+    let mut opts = Options::default();
+    opts.db_path = Some(tmp_dir.path().to_str().unwrap().to_string());
+    opts.mmap_storage = true;
+    {
+        let locustdb = LocustDB::new(&opts);
+        load_edge_cases(&locustdb, 3);
+        locustdb.rename_column("default", "enum", "category").unwrap();
+    }
+    let locustdb = LocustDB::new(&opts);
+    let query = "SELECT category, COUNT(0) FROM default ORDER BY category;";
+    assert_eq!(block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows,
+               vec![vec![Str("aa"), Int(5)], vec![Str("bb"), Int(3)], vec![Str("cc"), Int(2)]]);
+    assert!(locustdb.rename_column("default", "enum", "other").is_err());
+}
+
+#[test]
+fn test_validate() {
+    let locustdb = LocustDB::memory_only();
+    load_edge_cases(&locustdb, 3);
+    let errors = |query: &str| locustdb.validate(query).iter().map(|err| err.to_string()).collect::<Vec<_>>();
+    assert!(errors("SELECT id, enum FROM default WHERE id > 3 ORDER BY country LIMIT 2;").is_empty());
+    assert!(errors("SELECT enum, COUNT(0) AS n FROM default ORDER BY n DESC, enum;").is_empty());
+    assert!(errors("SELECT rownum FROM default WHERE rownum > 3;").is_empty());
+    let unknown_columns = locustdb.validate("SELECT missing, SUM(id) FROM default WHERE other > 0;").into_iter()
+        .map(|err| match err {
+            QueryError::UnknownColumn(column, available) => {
+                assert!(available.starts_with("constant0, constant0_2, country, enum, id,"), "{}", available);
+                column
+            }
+            err => panic!("Expected UnknownColumn, got {:?}", err),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(unknown_columns, vec!["missing".to_string(), "other".to_string()]);
+    assert_eq!(errors("SELECT enum, COUNT(0) FROM default ORDER BY country;"),
+               vec![r#"Type error: ORDER BY expression ColName("country") is neither selected nor grouped by."#]);
+    match &locustdb.validate("SELECT SUM(COUNT(id)) FROM defualt;")[..] {
+        [QueryError::UnknownTable(ref table)] if table == "defualt" => {}
+        errors => panic!("Expected UnknownTable, got {:?}", errors),
+    }
+    let errors = errors("SELECT SUM(COUNT(id)), missing FROM default ORDER BY id;");
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[1], "Type error: Nested aggregates found.");
+}
+
+#[test]
+fn test_unknown_column() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![1, 2, 3]));
+    transaction.add_column("name", InputColumn::Str(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+    let result = block_on(locustdb.run_query("SELECT nmae FROM default WHERE id > 1;", false, vec![])).unwrap().0;
+    assert_eq!(result.err().unwrap().to_string(), "Column nmae does not exist! Available columns: id, name");
+    let result = block_on(locustdb.run_query("SELECT COUNT(*), id AS n FROM default WHERE rownum > 0 ORDER BY n;", false, vec![])).unwrap().0;
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_cast() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![1, 2, 3, 4, 5]));
+    transaction.add_column("amount", InputColumn::Str(vec!["12", " 7", "x", "-3", ""].iter().map(|s| s.to_string()).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    let query_rows = |q: &str| query(q).unwrap().rows;
+    // Strings that are not integers become null
+    assert_eq!(query_rows("SELECT id, CAST(amount AS INT) FROM default;"), vec![
+        vec![Int(1), Int(12)],
+        vec![Int(2), Int(7)],
+        vec![Int(3), Null],
+        vec![Int(4), Int(-3)],
+        vec![Int(5), Null],
+    ]);
+    assert_eq!(query_rows("SELECT id FROM default WHERE CAST(amount AS INT) + id > 8;"), vec![vec![Int(1)], vec![Int(2)]]);
+    assert_eq!(query_rows("SELECT CAST(id AS VARCHAR) FROM default WHERE CAST(id AS VARCHAR) LIKE '%4%';"), vec![vec![Str("4")]]);
+    assert_eq!(query_rows("SELECT CAST(id AS INT) + 1 FROM default WHERE id = 1;"), vec![vec![Int(2)]]);
+    assert_eq!(query_rows("SELECT CAST(amount AS TEXT) FROM default WHERE id = 1;"), vec![vec![Str("12")]]);
+    assert_eq!(query_rows("SELECT CAST(id > 3 AS INT) FROM default WHERE id = 4;"), vec![vec![Int(1)]]);
+    assert!(query("SELECT CAST(id AS DOUBLE) FROM default;").is_err());
+}
+
+#[test]
+fn test_limit_by() {
+    let locustdb = LocustDB::memory_only();
+    let hosts = ["a", "b", "a", "c", "a", "b", "a", "b"];
+    let paths = ["/1", "/2", "/3", "/4", "/5", "/6", "/7", "/8"];
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("host", InputColumn::Str(hosts.iter().map(|s| s.to_string()).collect()));
+    transaction.add_column("path", InputColumn::Str(paths.iter().map(|s| s.to_string()).collect()));
+    transaction.add_column("hits", InputColumn::Int(vec![10, 20, 30, 40, 50, 60, 70, 80]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    let query_rows = |q: &str| query(q).unwrap().rows;
+    assert_eq!(query_rows("SELECT host, path, hits FROM default ORDER BY hits DESC LIMIT 2 BY host;"), vec![
+        vec![Str("b"), Str("/8"), Int(80)],
+        vec![Str("a"), Str("/7"), Int(70)],
+        vec![Str("b"), Str("/6"), Int(60)],
+        vec![Str("a"), Str("/5"), Int(50)],
+        vec![Str("c"), Str("/4"), Int(40)],
+    ]);
+    // The limit applies to the rows retained by LIMIT BY
+    assert_eq!(query_rows("SELECT host, hits FROM default ORDER BY hits LIMIT 1 BY host LIMIT 2 OFFSET 1;"),
+               vec![vec![Str("b"), Int(20)], vec![Str("c"), Int(40)]]);
+    assert_eq!(query_rows("SELECT host, SUM(hits) AS total FROM default ORDER BY total DESC LIMIT 1 BY host LIMIT 2;"),
+               vec![vec![Str("a"), Int(160)], vec![Str("b"), Int(160)]]);
+    assert!(query("SELECT host, hits FROM default LIMIT 1 BY path;").is_err());
+}
+
+#[test]
+fn test_transaction() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![1, 2, 3]));
+    transaction.add_column("name", InputColumn::Str(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+    assert_eq!(block_on(locustdb.commit(transaction)).unwrap(), Ok(3));
+
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![4, 5]));
+    transaction.add_column("name", InputColumn::Str(vec!["d".to_string()]));
+    assert!(block_on(locustdb.commit(transaction)).unwrap().is_err());
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT id, name FROM default ORDER BY id;"),
+               vec![vec![Int(1), Str("a")], vec![Int(2), Str("b")], vec![Int(3), Str("c")]]);
+}
+
+#[test]
+fn test_lower_upper() {
+    let locustdb = LocustDB::memory_only();
+    let countries = ["Germany", "GERMANY", "france", "Germany", "France", "USA",
+                     "Germany", "france", "GERMANY", "USA", "France", "Germany"];
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int((0..12).collect()));
+    transaction.add_column("country", InputColumn::Str(countries.iter().map(|c| c.to_string()).collect()));
+    transaction.add_column("name", InputColumn::Str((0..12).map(|i| format!("Name{}", i)).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    // Entries of the dictionary that fold to the same string have to be counted as a single group
+    assert_eq!(query("SELECT lower(country), COUNT(0) FROM default;"),
+               vec![vec![Str("france"), Int(4)], vec![Str("germany"), Int(6)], vec![Str("usa"), Int(2)]]);
+    assert_eq!(query("SELECT upper(country), COUNT(0) FROM default ORDER BY upper(country) DESC LIMIT 1;"),
+               vec![vec![Str("USA"), Int(2)]]);
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE lower(country) = 'germany';"), vec![vec![Int(6)]]);
+    assert_eq!(query("SELECT id, lower(country), upper(name) FROM default WHERE id < 3 ORDER BY id;"),
+               vec![vec![Int(0), Str("germany"), Str("NAME0")],
+                    vec![Int(1), Str("germany"), Str("NAME1")],
+                    vec![Int(2), Str("france"), Str("NAME2")]]);
+}
+
+#[test]
+fn test_null_column() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![0, 1, 2, 3]));
+    transaction.add_column("n", InputColumn::Null(4));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT id, n + 1, n = 3, length(n) FROM default WHERE id > 1 ORDER BY id;"),
+               vec![vec![Int(2), Null, Null, Null], vec![Int(3), Null, Null, Null]]);
+    assert_eq!(query("SELECT COUNT(n), SUM(n * 2), COUNT(0) FROM default;"), vec![vec![Int(0), Int(0), Int(4)]]);
+    assert_eq!(query("SELECT APPROX_COUNT_DISTINCT(n), APPROX_COUNT_DISTINCT(id) FROM default;"), vec![vec![Int(0), Int(4)]]);
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE n IS NULL;"), vec![vec![Int(4)]]);
+    assert_eq!(query("SELECT id FROM default WHERE n > 0;"), Vec::<Vec<Value>>::new());
+}
+
+#[test]
+fn test_boolean_column() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int((0..20).collect()));
+    transaction.add_column("is_error", InputColumn::Bool((0..20).map(|i| i % 6 == 1).collect()));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    let schema = locustdb.schema("default").unwrap();
+    assert!(schema.columns.contains(&("is_error".to_string(), BasicType::Boolean, EncodingType::U8)));
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT id FROM default WHERE is_error ORDER BY id;"),
+               vec![vec![Int(1)], vec![Int(7)], vec![Int(13)], vec![Int(19)]]);
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE NOT is_error;"), vec![vec![Int(16)]]);
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE NOT is_error AND NOT id >= 10;"), vec![vec![Int(8)]]);
+    assert_eq!(query("SELECT COUNT(0) FROM default WHERE NOT (is_error OR id < 15);"), vec![vec![Int(4)]]);
+    assert_eq!(query("SELECT id FROM default WHERE NOT (id > 2);"), vec![vec![Int(0)], vec![Int(1)], vec![Int(2)]]);
+    assert_eq!(query("SELECT id FROM default WHERE NOT (id < 17) ORDER BY id DESC;"),
+               vec![vec![Int(19)], vec![Int(18)], vec![Int(17)]]);
+    assert_eq!(query("SELECT is_error FROM default WHERE id < 3 ORDER BY id;"),
+               vec![vec![Int(0)], vec![Int(1)], vec![Int(0)]]);
+    assert_eq!(query("SELECT is_error, COUNT(0) FROM default ORDER BY is_error;"),
+               vec![vec![Int(0), Int(16)], vec![Int(1), Int(4)]]);
+
+    let coltypes = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().coltypes;
+    assert_eq!(coltypes("SELECT id, is_error FROM default;"), vec![BasicType::Integer, BasicType::Boolean]);
+    assert_eq!(coltypes("SELECT is_error, COUNT(0) FROM default;"), vec![BasicType::Boolean, BasicType::Integer]);
+    assert_eq!(coltypes("SELECT is_error, COUNT(0) FROM default ORDER BY COUNT(0);"), vec![BasicType::Boolean, BasicType::Integer]);
+
+    // Columns that are rebuilt from their decoded values by compaction, VACUUM and UPDATE remain booleans
+    let is_error_types = || locustdb.schema("default").unwrap().columns.into_iter()
+        .filter(|(name, _, _)| name == "is_error")
+        .map(|(_, basic_type, _)| basic_type)
+        .collect::<Vec<_>>();
+    let commit = |ids: Vec<i64>| {
+        let mut transaction = Transaction::new("default");
+        transaction.add_column("is_error", InputColumn::Bool(ids.iter().map(|i| i % 6 == 1).collect()));
+        transaction.add_column("id", InputColumn::Int(ids));
+        block_on(locustdb.commit(transaction)).unwrap().unwrap();
+    };
+    commit((20..30).collect());
+    assert_eq!(block_on(locustdb.compact(100)).unwrap().unwrap(), 2);
+    assert_eq!(is_error_types(), vec![BasicType::Boolean]);
+    assert_eq!(query("DELETE FROM default WHERE id < 5;"), vec![vec![Int(5)]]);
+    assert_eq!(query("VACUUM default;"), vec![vec![Int(5)]]);
+    assert_eq!(is_error_types(), vec![BasicType::Boolean]);
+    commit((30..35).collect());
+    assert_eq!(query("DELETE FROM default WHERE id = 30;"), vec![vec![Int(1)]]);
+    assert_eq!(block_on(locustdb.compact(100)).unwrap().unwrap(), 2);
+    assert_eq!(is_error_types(), vec![BasicType::Boolean]);
+    assert_eq!(block_on(locustdb.update("default", "id = 6", "is_error", "1")).unwrap().unwrap(), 1);
+    assert_eq!(is_error_types(), vec![BasicType::Boolean]);
+    assert_eq!(query("SELECT id FROM default WHERE is_error ORDER BY id;"),
+               vec![vec![Int(6)], vec![Int(7)], vec![Int(13)], vec![Int(19)], vec![Int(25)], vec![Int(31)]]);
+    assert_eq!(coltypes("SELECT id, is_error FROM default;"), vec![BasicType::Integer, BasicType::Boolean]);
+}
+
+#[test]
+fn test_type_errors() {
+    let locustdb = LocustDB::memory_only();
+    let mut transaction = Transaction::new("default");
+    transaction.add_column("id", InputColumn::Int(vec![1, 2, 3]));
+    transaction.add_column("name", InputColumn::Str(vec!["a".to_string(), "bb".to_string(), "ccc".to_string()]));
+    block_on(locustdb.commit(transaction)).unwrap().unwrap();
+
+    let type_error = |query: &str| match block_on(locustdb.run_query(query, false, vec![])).unwrap().0 {
+        Err(QueryError::TypeError(msg)) => msg,
+        Err(err) => panic!("{}: expected type error, got {:?}", query, err),
+        Ok(_) => panic!("{}: expected type error", query),
+    };
+    assert!(type_error("SELECT sum(name) FROM default;").contains("sum requires numeric input"));
+    assert!(type_error("SELECT id, max(name) FROM default;").contains("max requires numeric input"));
+    assert!(type_error("SELECT id FROM default WHERE name;").contains("WHERE"));
+    assert!(type_error("SELECT id FROM default WHERE id + 1;").contains("WHERE"));
+    type_error("SELECT id FROM default WHERE name < 3;");
+    type_error("SELECT id FROM default WHERE id > 1 OR name;");
+    type_error("SELECT length(id) FROM default;");
+
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    assert_eq!(query("SELECT id FROM default WHERE length(name) > 1 ORDER BY id;"), vec![vec![Int(2)], vec![Int(3)]]);
+    assert_eq!(query("SELECT COUNT(name) FROM default WHERE id IS NOT NULL;"), vec![vec![Int(3)]]);
+}
+
+#[test]
+fn test_union_all() {
+    test_query_ec(
+        "SELECT id FROM default WHERE id < 2 UNION ALL SELECT id FROM default WHERE id > 7 ORDER BY id DESC;",
+        &[vec![Int(9)], vec![Int(8)], vec![Int(1)], vec![Int(0)]],
+    );
+    test_query_ec(
+        "SELECT id, enum FROM default WHERE id = 3 UNION ALL SELECT id, country FROM default ORDER BY id LIMIT 3;",
+        &[vec![Int(0), Str("Germany")], vec![Int(1), Str("USA")], vec![Int(2), Str("France")]],
+    );
+    test_query_ec_err(
+        "SELECT id, enum FROM default UNION ALL SELECT id FROM default;",
+        QueryError::TypeError("".to_string()),
+    );
+    test_query_ec_err(
+        "SELECT id FROM default UNION ALL SELECT enum FROM default;",
+        QueryError::TypeError("".to_string()),
+    );
+    test_query_ec(
+        "SELECT id FROM default WHERE id < 5 UNION ALL SELECT id AS i FROM default WHERE id >= 5
+         ORDER BY id DESC LIMIT 3 OFFSET 2;",
+        &[vec![Int(7)], vec![Int(6)], vec![Int(5)]],
+    );
+    // Types of empty results are known from the query plan
+    test_query_ec_err(
+        "SELECT id FROM default WHERE id > 100 UNION ALL SELECT enum FROM default;",
+        QueryError::TypeError("".to_string()),
+    );
+}
+
+#[test]
+fn test_rollup() {
+    test_query_ec(
+        "SELECT enum, non_dense_ints, COUNT(0) FROM default
+         GROUP BY ROLLUP(enum, non_dense_ints)
+         ORDER BY enum, non_dense_ints;",
+        &[
+            vec![Str("aa"), Int(0), Int(2)],
+            vec![Str("aa"), Int(1), Int(1)],
+            vec![Str("aa"), Int(2), Int(1)],
+            vec![Str("aa"), Int(3), Int(1)],
+            vec![Str("aa"), Null, Int(5)],
+            vec![Str("bb"), Int(1), Int(1)],
+            vec![Str("bb"), Int(3), Int(1)],
+            vec![Str("bb"), Int(4), Int(1)],
+            vec![Str("bb"), Null, Int(3)],
+            vec![Str("cc"), Int(2), Int(2)],
+            vec![Str("cc"), Null, Int(2)],
+            vec![Null, Null, Int(10)],
+        ],
+    );
+    test_query_ec(
+        "SELECT enum, SUM(id) FROM default GROUP BY ROLLUP(enum) ORDER BY enum DESC LIMIT 3;",
+        &[vec![Null, Int(45)], vec![Str("cc"), Int(14)], vec![Str("bb"), Int(16)]],
+    );
+    test_query_ec_err(
+        "SELECT enum, SUM(id) FROM default GROUP BY ROLLUP(enum) ORDER BY id;",
+        QueryError::NotImplemented("".to_string()),
+    );
+    test_query_ec(
+        "SELECT enum, COUNT(0) AS c FROM default GROUP BY ROLLUP(enum) ORDER BY c DESC LIMIT 2 OFFSET 1;",
+        &[vec![Str("aa"), Int(5)], vec![Str("bb"), Int(3)]],
+    );
+    test_query_ec_err(
+        "EXPLAIN SELECT enum, COUNT(0) FROM default GROUP BY ROLLUP(enum);",
+        QueryError::NotImplemented("".to_string()),
+    );
+}
+
+#[test]
+fn test_overflow() {
+    test_query_ec_err(
+        "SELECT largenum + non_dense_ints FROM default;",
+        QueryError::Overflow,
+    );
+    test_query_ec_err(
+        "SELECT largenum + nullable_int FROM default;",
+        QueryError::Overflow,
+    );
+    test_query_ec(
+        "SELECT largenum / nullable_int FROM default ORDER BY id;",
+        &[
+            vec![Int(9223372036854775807)],
+            vec![Int(-230584300921369395)],
+            vec![Null],
+            vec![Null],
+            vec![Int(-922337203685477580)],
+            vec![Null],
+            vec![Null],
+            vec![Int(461168601842738790)],
+            vec![Null],
+            vec![Int(709490156681136600)],
+        ],
+    );
+    test_query_ec_err(
+        "SELECT sum(largenum) FROM default;",
+        QueryError::Overflow,
+    );
+}
+
+#[test]
+fn test_gen_table() {
+    use Value::*;
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.gen_table(
+        locustdb::colgen::GenTable {
+            name: "test".to_string(),
+            partitions: 8,
+            partition_size: 2 << 14,
+            columns: vec![
+                ("yum".to_string(), locustdb::colgen::string_markov_chain(
+                    vec!["Walnut".to_string(), "Cashew".to_string(), "Hazelnut".to_string()],
+                    vec![
+                        vec![0., 0.5, 0.5],
+                        vec![0.1, 0.5, 0.4],
+                        vec![0.1, 0.9, 0.],
+                    ],
+                ))
+            ],
+        }
+    ));
+    let query = "SELECT yum, count(1) FROM test;";
+    let expected_rows = vec![
+        [Str("Cashew".to_string()), Int(162035)],
+        [Str("Hazelnut".to_string()), Int(76401)],
+        [Str("Walnut".to_string()), Int(23708)]
+    ];
+    let result = block_on(locustdb.run_query(query, true, vec![])).unwrap();
+    assert_eq!(result.0.unwrap().rows, expected_rows);
+}
+
+#[test]
+fn test_column_with_null_partitions() {
     use Value::*;
     let _ = env_logger::try_init();
     let mut opts = locustdb::Options::default();
@@ -1025,4 +2807,3 @@ fn test_restore_from_disk() {
         vec![Int(1), Int(2013), Int(2), Int(824)]
     ]);
 }
-